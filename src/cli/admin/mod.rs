@@ -0,0 +1,314 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `openobserve admin` operates on the meta store and the object store of the
+//! cluster directly, the running nodes pick the changes up through the meta
+//! store watchers. It's meant for scripting and break-glass operations.
+
+use std::str::FromStr;
+
+use actix_web::HttpResponse;
+use config::meta::stream::StreamType;
+
+use crate::{
+    common::meta::{
+        backup::RestoreRequest,
+        organization::Organization,
+        user::{UserRequest, UserRole},
+    },
+    service::{backup, bundles, db, file_list, organization, users},
+};
+
+const HOUR_MICROS: i64 = 3_600_000_000;
+
+pub fn command() -> clap::Command {
+    let org = || {
+        clap::Arg::new("org")
+            .short('o')
+            .long("org")
+            .value_name("org")
+            .required(true)
+            .help("organization id")
+    };
+    let stream_type = || {
+        clap::Arg::new("type")
+            .short('t')
+            .long("type")
+            .value_name("type")
+            .help("stream type: logs, metrics, traces, ...")
+    };
+    clap::Command::new("admin")
+        .about("administrate the cluster without the ui")
+        .subcommand_required(true)
+        .subcommands([
+            clap::Command::new("create-org")
+                .about("create an organization")
+                .args([
+                    org(),
+                    clap::Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .value_name("name")
+                        .help("name of the organization, the id by default"),
+                ]),
+            clap::Command::new("create-user")
+                .about("create a user in an organization")
+                .args([
+                    org(),
+                    clap::Arg::new("email")
+                        .short('e')
+                        .long("email")
+                        .value_name("email")
+                        .required(true)
+                        .help("email of the user"),
+                    clap::Arg::new("password")
+                        .short('p')
+                        .long("password")
+                        .value_name("password")
+                        .required(true)
+                        .help("password of the user"),
+                    clap::Arg::new("role")
+                        .short('r')
+                        .long("role")
+                        .value_name("role")
+                        .default_value("admin")
+                        .help("role of the user: admin, member"),
+                ]),
+            clap::Command::new("create-token")
+                .about("generate a new ingestion token for a user of an organization")
+                .args([
+                    org(),
+                    clap::Arg::new("email")
+                        .short('e')
+                        .long("email")
+                        .value_name("email")
+                        .required(true)
+                        .help("email of the user"),
+                ]),
+            clap::Command::new("list-streams")
+                .about("list the streams of an organization")
+                .args([org(), stream_type()]),
+            clap::Command::new("compact")
+                .about("rewind the compaction of a stream so the compactor merges its files again, a compactor holding the stream picks it up once it releases it")
+                .args([
+                    org(),
+                    stream_type().default_value("logs"),
+                    clap::Arg::new("stream")
+                        .short('s')
+                        .long("stream")
+                        .value_name("stream")
+                        .required(true)
+                        .help("stream name"),
+                    clap::Arg::new("hours")
+                        .long("hours")
+                        .value_name("hours")
+                        .value_parser(clap::value_parser!(i64))
+                        .default_value("24")
+                        .help("compact the files of the last hours"),
+                ]),
+            clap::Command::new("rebuild-file-list")
+                .about("add the parquet files of the object store missing from the file list")
+                .args([
+                    clap::Arg::new("prefix")
+                        .short('p')
+                        .long("prefix")
+                        .value_name("prefix")
+                        .default_value("files/")
+                        .help("only the files under the prefix, e.g. files/default/logs/app/"),
+                    clap::Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("only print the missing files"),
+                ]),
+            clap::Command::new("export-dashboards")
+                .about("export the folders and dashboards of an organization as a bundle")
+                .args([
+                    org(),
+                    clap::Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .value_name("file")
+                        .help("write the bundle to the file instead of stdout"),
+                ]),
+            clap::Command::new("backup")
+                .about("back up the meta store and the file list to the object store")
+                .arg(
+                    clap::Arg::new("incremental")
+                        .long("incremental")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("only back up the keys changed since the latest backup"),
+                ),
+            clap::Command::new("restore")
+                .about("restore the meta store from a backup of the object store, e.g. on a new cluster using the same bucket")
+                .args([
+                    clap::Arg::new("backup")
+                        .short('b')
+                        .long("backup")
+                        .value_name("id")
+                        .help("id of the backup, the latest by default"),
+                    clap::Arg::new("overwrite")
+                        .long("overwrite")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("replace the keys which exist already"),
+                ]),
+        ])
+}
+
+pub async fn run(command: &clap::ArgMatches) -> Result<(), anyhow::Error> {
+    let (name, args) = command.subcommand().unwrap();
+    let arg = |name: &str| args.get_one::<String>(name).cloned().unwrap_or_default();
+    match name {
+        "create-org" => {
+            let org_id = arg("org");
+            let label = args
+                .get_one::<String>("name")
+                .cloned()
+                .unwrap_or_else(|| org_id.clone());
+            let org = Organization {
+                identifier: org_id,
+                label,
+            };
+            organization::create_org(&org).await?;
+            println!("created organization {}", org.identifier);
+        }
+        "create-user" => {
+            let role = arg("role");
+            let role =
+                UserRole::from_str(&role).map_err(|_| anyhow::anyhow!("invalid role: {role}"))?;
+            db::user::cache().await?;
+            let req = UserRequest {
+                email: arg("email"),
+                first_name: "".to_string(),
+                last_name: "".to_string(),
+                password: arg("password"),
+                role,
+                is_external: false,
+            };
+            let root = config::get_config().auth.root_user_email.clone();
+            print_response(users::post_user(&arg("org"), req, &root).await?).await?;
+        }
+        "create-token" => {
+            let passcode = organization::update_passcode(Some(&arg("org")), &arg("email")).await?;
+            println!("{}", passcode.passcode);
+        }
+        "list-streams" => {
+            let stream_type = args
+                .get_one::<String>("type")
+                .map(|v| StreamType::from(v.as_str()));
+            for stream in db::schema::list(&arg("org"), stream_type, false).await? {
+                println!("{}\t{}", stream.stream_type, stream.stream_name);
+            }
+        }
+        "compact" => {
+            let org_id = arg("org");
+            let stream_type = StreamType::from(arg("type").as_str());
+            let stream_name = arg("stream");
+            let hours = *args.get_one::<i64>("hours").unwrap();
+            let now = chrono::Utc::now().timestamp_micros();
+            let offset = now - now % HOUR_MICROS - hours.max(1) * HOUR_MICROS;
+            let (current, _) =
+                db::compact::files::get_offset(&org_id, stream_type, &stream_name).await;
+            if current <= offset {
+                println!(
+                    "stream {org_id}/{stream_type}/{stream_name} isn't compacted past {offset} yet"
+                );
+                return Ok(());
+            }
+            db::compact::files::set_offset(&org_id, stream_type, &stream_name, offset, None)
+                .await?;
+            println!("rewound the compaction of {org_id}/{stream_type}/{stream_name} to {offset}");
+        }
+        "rebuild-file-list" => {
+            let dry_run = args.get_flag("dry-run");
+            let files = file_list::rebuild_from_storage(&arg("prefix"), dry_run).await?;
+            for file in files.iter() {
+                println!("{file}");
+            }
+            if dry_run {
+                println!("{} files missing from the file list", files.len());
+            } else {
+                println!(
+                    "added {} files, reset the stream stats to count them: openobserve reset -c stream-stats",
+                    files.len()
+                );
+            }
+        }
+        "export-dashboards" => {
+            let bundle = bundles::export(
+                &arg("org"),
+                &[bundles::KIND_FOLDER, bundles::KIND_DASHBOARD],
+            )
+            .await?;
+            let data = bundles::encode(&bundle, false)?;
+            match args.get_one::<String>("file") {
+                Some(file) => std::fs::write(file, data)?,
+                None => println!("{}", String::from_utf8_lossy(&data)),
+            }
+        }
+        "backup" => {
+            let manifest = backup::create(args.get_flag("incremental")).await?;
+            println!(
+                "created backup {} with {} of {} keys",
+                manifest.id, manifest.changed, manifest.keys
+            );
+        }
+        "restore" => {
+            let req = RestoreRequest {
+                backup: args.get_one::<String>("backup").cloned(),
+                overwrite: args.get_flag("overwrite"),
+            };
+            let report = backup::restore(req).await?;
+            println!(
+                "restored backup {}: {} keys restored, {} skipped, {} failed",
+                report.backup, report.restored, report.skipped, report.failed
+            );
+        }
+        _ => {
+            return Err(anyhow::anyhow!("unsupport admin command: {name}"));
+        }
+    }
+    Ok(())
+}
+
+async fn print_response(resp: HttpResponse) -> Result<(), anyhow::Error> {
+    let status = resp.status();
+    let body = actix_web::body::to_bytes(resp.into_body())
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    let body = String::from_utf8_lossy(&body);
+    if !status.is_success() {
+        return Err(anyhow::anyhow!("{status}: {body}"));
+    }
+    println!("{body}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        command().debug_assert();
+        let matches = command()
+            .try_get_matches_from(["admin", "compact", "-o", "default", "-s", "app"])
+            .unwrap();
+        let (name, args) = matches.subcommand().unwrap();
+        assert_eq!(name, "compact");
+        assert_eq!(args.get_one::<String>("type").unwrap(), "logs");
+        assert_eq!(*args.get_one::<i64>("hours").unwrap(), 24);
+        assert!(command().try_get_matches_from(["admin"]).is_err());
+    }
+}
@@ -15,13 +15,18 @@
 
 use config::utils::file::set_permission;
 use infra::file_list as infra_file_list;
+use utoipa::OpenApi;
 
 use crate::{
-    cli::data::{
-        cli::{args as dataArgs, Cli as dataCli},
-        export, import, Context,
+    cli::{
+        admin,
+        data::{
+            cli::{args as dataArgs, Cli as dataCli},
+            export, import, Context,
+        },
     },
     common::{infra::config::USERS, meta, migration},
+    handler::http::router::openapi::ApiDoc,
     service::{compact, db, file_list, users},
 };
 
@@ -109,6 +114,16 @@ pub async fn cli() -> Result<bool, anyhow::Error> {
                         .help("the parquet file name"),
                 ),
             clap::Command::new("migrate-schemas").about("migrate from single row to row per schema version"),
+            clap::Command::new("openapi")
+                .about("print the openapi document of the http api")
+                .arg(
+                    clap::Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .value_name("file")
+                        .help("write the document to the file instead of stdout"),
+                ),
+            admin::command(),
         ])
         .get_matches();
 
@@ -132,6 +147,14 @@ pub async fn cli() -> Result<bool, anyhow::Error> {
         }
         return Ok(true);
     }
+    if name == "openapi" {
+        let doc = ApiDoc::openapi().to_pretty_json()?;
+        match command.get_one::<String>("file") {
+            Some(file) => std::fs::write(file, doc)?,
+            None => println!("{doc}"),
+        }
+        return Ok(true);
+    }
 
     // init infra, create data dir & tables
     infra::init().await.expect("infra init failed");
@@ -153,6 +176,7 @@ pub async fn cli() -> Result<bool, anyhow::Error> {
                             first_name: Some("root".to_owned()),
                             last_name: Some("".to_owned()),
                             token: None,
+                            force_password_reset: None,
                         },
                     )
                     .await?;
@@ -264,6 +288,7 @@ pub async fn cli() -> Result<bool, anyhow::Error> {
             println!("Running schema migration to row per schema version");
             migration::schema::run().await?
         }
+        "admin" => admin::run(command).await?,
         _ => {
             return Err(anyhow::anyhow!("unsupport sub command: {name}"));
         }
@@ -61,6 +61,7 @@ impl Context for Export {
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            highlight: false,
         };
 
         let req = search::Request {
@@ -73,7 +74,7 @@ impl Context for Export {
             search_type,
         };
 
-        match SearchService::search("", &c.org, stream_type, None, &req).await {
+        match SearchService::search_as_system("", &c.org, stream_type, &req).await {
             Ok(res) => {
                 if c.file_type != "json" {
                     eprintln!("No other file types are implemented");
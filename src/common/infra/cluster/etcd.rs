@@ -16,7 +16,7 @@
 use config::{
     cluster::*,
     get_config,
-    meta::cluster::{Node, NodeStatus, Role},
+    meta::cluster::{Node, NodeStatus, Role, GRPC_MIN_VERSION, GRPC_VERSION},
     utils::json,
 };
 use etcd_client::PutOptions;
@@ -26,6 +26,8 @@ use infra::{
     errors::{Error, Result},
 };
 
+use crate::common::infra::tls;
+
 /// Register and keepalive the node to cluster
 pub(crate) async fn register_and_keepalive() -> Result<()> {
     if let Err(e) = register().await {
@@ -139,12 +141,21 @@ async fn register() -> Result<()> {
         uuid: LOCAL_NODE_UUID.clone(),
         name: cfg.common.instance_name.clone(),
         http_addr: format!("http://{}:{}", get_local_http_ip(), cfg.http.port),
-        grpc_addr: format!("http://{}:{}", get_local_grpc_ip(), cfg.grpc.port),
+        grpc_addr: format!(
+            "{}://{}:{}",
+            tls::grpc_scheme(),
+            get_local_grpc_ip(),
+            cfg.grpc.port
+        ),
         role: LOCAL_NODE_ROLE.clone(),
         cpu_num: cfg.limit.cpu_num as u64,
         status: NodeStatus::Prepare,
         scheduled: true,
         broadcasted: false,
+        zone: cfg.common.node_zone.clone(),
+        region: cfg.common.node_region.clone(),
+        grpc_version: GRPC_VERSION,
+        grpc_min_version: GRPC_MIN_VERSION,
     };
     let val = json::to_string(&node).unwrap();
 
@@ -213,12 +224,21 @@ pub(crate) async fn set_status(status: NodeStatus, new_lease_id: bool) -> Result
             uuid: LOCAL_NODE_UUID.clone(),
             name: cfg.common.instance_name.clone(),
             http_addr: format!("http://{}:{}", get_local_node_ip(), cfg.http.port),
-            grpc_addr: format!("http://{}:{}", get_local_node_ip(), cfg.grpc.port),
+            grpc_addr: format!(
+                "{}://{}:{}",
+                tls::grpc_scheme(),
+                get_local_node_ip(),
+                cfg.grpc.port
+            ),
             role: LOCAL_NODE_ROLE.clone(),
             cpu_num: cfg.limit.cpu_num as u64,
             status: status.clone(),
             scheduled: true,
             broadcasted: false,
+            zone: cfg.common.node_zone.clone(),
+            region: cfg.common.node_region.clone(),
+            grpc_version: GRPC_VERSION,
+            grpc_min_version: GRPC_MIN_VERSION,
         },
     };
     let val = json::to_string(&node).unwrap();
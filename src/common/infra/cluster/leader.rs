@@ -0,0 +1,95 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Leader election for the singleton jobs. Every node running a job campaigns
+//! for the lease of the job, the node holding it runs the job and the others
+//! stand by, retrying until the lease of the leader expires.
+
+use config::{
+    cluster::{is_draining, is_offline, LOCAL_NODE_UUID},
+    get_config, metrics, RwHashSet,
+};
+use infra::db::etcd;
+use once_cell::sync::Lazy;
+use tokio::time;
+
+pub const COMPACTOR: &str = "compactor";
+pub const RETENTION: &str = "retention";
+pub const REPORTING: &str = "reporting";
+pub const PATTERNS: &str = "patterns";
+pub const PROVISIONING: &str = "provisioning";
+pub const FILE_LIST_SNAPSHOT: &str = "file_list_snapshot";
+pub const USAGE_METERING: &str = "usage_metering";
+pub const SELF_MONITORING: &str = "self_monitoring";
+
+static LEADERS: Lazy<RwHashSet<String>> = Lazy::new(Default::default);
+
+/// Whether the local node should run the job, always true unless leader
+/// election is enabled in a cluster.
+pub fn is_leader(job: &str) -> bool {
+    let cfg = get_config();
+    if cfg.common.local_mode || !cfg.common.leader_election_enabled {
+        return true;
+    }
+    LEADERS.contains(job)
+}
+
+/// Spawns the campaign for the job, the local node keeps campaigning until it
+/// goes offline or starts draining.
+pub fn campaign(job: &'static str) {
+    let cfg = get_config();
+    if cfg.common.local_mode || !cfg.common.leader_election_enabled {
+        return;
+    }
+    metrics::LEADER_ELECTION_LEADER
+        .with_label_values(&[job])
+        .set(0);
+    tokio::task::spawn(async move { run_campaign(job).await });
+}
+
+async fn run_campaign(job: &'static str) {
+    let key = format!("leader/{job}");
+    loop {
+        if is_offline() || is_draining() {
+            break;
+        }
+        let ttl = get_config().common.leader_election_ttl;
+        match etcd::campaign(&key, LOCAL_NODE_UUID.as_str(), ttl).await {
+            Ok(Some(lease_id)) => {
+                log::info!("[LEADER] node {} leads job {}", *LOCAL_NODE_UUID, job);
+                LEADERS.insert(job.to_string());
+                metrics::LEADER_ELECTION_LEADER
+                    .with_label_values(&[job])
+                    .set(1);
+                let ret =
+                    etcd::hold_lease_id(lease_id, ttl, || is_offline() || is_draining()).await;
+                LEADERS.remove(job);
+                metrics::LEADER_ELECTION_LEADER
+                    .with_label_values(&[job])
+                    .set(0);
+                match ret {
+                    Ok(()) => log::info!("[LEADER] node {} resigned job {}", *LOCAL_NODE_UUID, job),
+                    Err(e) => {
+                        log::error!("[LEADER] node {} lost job {}: {}", *LOCAL_NODE_UUID, job, e)
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("[LEADER] campaign for job {} failed: {}", job, e),
+        }
+        // standby, the lease of the leader expires after ttl
+        time::sleep(time::Duration::from_secs(std::cmp::max(1, ttl / 3) as u64)).await;
+    }
+}
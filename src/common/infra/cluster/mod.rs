@@ -19,7 +19,7 @@ use config::{
     cluster::*,
     get_config, get_instance_id,
     meta::{
-        cluster::{Node, NodeStatus, Role},
+        cluster::{Node, NodeStatus, Role, GRPC_MIN_VERSION, GRPC_VERSION},
         meta_store::MetaStore,
     },
     utils::{hash::Sum64, json},
@@ -32,16 +32,19 @@ use infra::{
 use once_cell::sync::Lazy;
 use tokio::time;
 
-use crate::service::db as db_service;
+use crate::{common::infra::tls, service::db as db_service};
 
 mod etcd;
+pub mod leader;
 mod nats;
+mod postgres;
 
 const HEALTH_CHECK_FAILED_TIMES: usize = 3;
 const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
 
 static NODES: Lazy<RwAHashMap<String, Node>> = Lazy::new(Default::default);
-static QUERIER_CONSISTENT_HASH: Lazy<RwBTreeMap<u64, String>> = Lazy::new(Default::default);
+// key: node uuid, value: weight of the node
+static QUERIER_NODE_WEIGHTS: Lazy<RwAHashMap<String, u64>> = Lazy::new(Default::default);
 static COMPACTOR_CONSISTENT_HASH: Lazy<RwBTreeMap<u64, String>> = Lazy::new(Default::default);
 static FLATTEN_COMPACTOR_CONSISTENT_HASH: Lazy<RwBTreeMap<u64, String>> =
     Lazy::new(Default::default);
@@ -49,7 +52,13 @@ static NODES_HEALTH_CHECK: Lazy<RwAHashMap<String, usize>> = Lazy::new(Default::
 
 pub async fn add_node_to_consistent_hash(node: &Node, role: &Role) {
     let mut nodes = match role {
-        Role::Querier => QUERIER_CONSISTENT_HASH.write().await,
+        Role::Querier => {
+            QUERIER_NODE_WEIGHTS
+                .write()
+                .await
+                .insert(node.uuid.clone(), node.cpu_num.max(1));
+            return;
+        }
         Role::Compactor => COMPACTOR_CONSISTENT_HASH.write().await,
         Role::FlattenCompactor => FLATTEN_COMPACTOR_CONSISTENT_HASH.write().await,
         _ => return,
@@ -64,7 +73,10 @@ pub async fn add_node_to_consistent_hash(node: &Node, role: &Role) {
 
 pub async fn remove_node_from_consistent_hash(node: &Node, role: &Role) {
     let mut nodes = match role {
-        Role::Querier => QUERIER_CONSISTENT_HASH.write().await,
+        Role::Querier => {
+            QUERIER_NODE_WEIGHTS.write().await.remove(&node.uuid);
+            return;
+        }
         Role::Compactor => COMPACTOR_CONSISTENT_HASH.write().await,
         Role::FlattenCompactor => FLATTEN_COMPACTOR_CONSISTENT_HASH.write().await,
         _ => return,
@@ -77,9 +89,12 @@ pub async fn remove_node_from_consistent_hash(node: &Node, role: &Role) {
     }
 }
 
+/// Files are assigned to queriers by weighted rendezvous hashing instead of
+/// the ring, so that a querier only gains or loses the files it wins or owned
+/// when the cluster scales and it gets files in proportion to its cpus.
 pub async fn get_node_from_consistent_hash(key: &str, role: &Role) -> Option<String> {
     let nodes = match role {
-        Role::Querier => QUERIER_CONSISTENT_HASH.read().await,
+        Role::Querier => return get_node_from_rendezvous_hash(key).await,
         Role::Compactor => COMPACTOR_CONSISTENT_HASH.read().await,
         Role::FlattenCompactor => FLATTEN_COMPACTOR_CONSISTENT_HASH.read().await,
         _ => return None,
@@ -98,6 +113,55 @@ pub async fn get_node_from_consistent_hash(key: &str, role: &Role) -> Option<Str
     None
 }
 
+async fn get_node_from_rendezvous_hash(key: &str) -> Option<String> {
+    let nodes = QUERIER_NODE_WEIGHTS.read().await;
+    select_by_rendezvous_hash(key, nodes.iter().map(|(uuid, weight)| (uuid, *weight))).cloned()
+}
+
+/// Picks the querier of a key among the given nodes, it agrees with
+/// [`get_node_from_consistent_hash`] when the nodes are all the queriers.
+pub fn get_querier_from_rendezvous_hash<'a>(key: &str, nodes: &'a [Node]) -> Option<&'a Node> {
+    let uuid = select_by_rendezvous_hash(
+        key,
+        nodes
+            .iter()
+            .filter(|node| is_querier(&node.role))
+            .map(|node| (&node.uuid, node.cpu_num.max(1))),
+    )?;
+    nodes.iter().find(|node| node.uuid.eq(uuid))
+}
+
+/// Picks the ingester owning a stream among the online ingesters, a stream
+/// only moves when its owner leaves or a joining ingester wins it.
+pub async fn get_ingester_from_rendezvous_hash(key: &str) -> Option<Node> {
+    let nodes = get_cached_online_ingester_nodes().await?;
+    let uuid = select_by_rendezvous_hash(
+        key,
+        nodes.iter().map(|node| (&node.uuid, node.cpu_num.max(1))),
+    )?
+    .clone();
+    nodes.into_iter().find(|node| node.uuid.eq(&uuid))
+}
+
+fn select_by_rendezvous_hash<'a>(
+    key: &str,
+    nodes: impl Iterator<Item = (&'a String, u64)>,
+) -> Option<&'a String> {
+    let mut h = config::utils::hash::gxhash::new();
+    nodes
+        .map(|(uuid, weight)| (rendezvous_score(&mut h, key, uuid, weight), uuid))
+        .max_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(b.1)))
+        .map(|(_, uuid)| uuid)
+}
+
+// the score of a node is weight / -ln(x) with x uniform in (0, 1) derived from
+// the hash of the node and the key, the node with the highest score wins
+fn rendezvous_score(h: &mut impl Sum64, key: &str, uuid: &str, weight: u64) -> f64 {
+    let hash = h.sum64(&format!("{uuid}/{key}"));
+    let x = ((hash >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    weight as f64 / -x.ln()
+}
+
 #[inline]
 pub fn get_internal_grpc_token() -> String {
     let cfg = get_config();
@@ -127,6 +191,7 @@ pub async fn register_and_keepalive() -> Result<()> {
 
     match cfg.common.cluster_coordinator.as_str().into() {
         MetaStore::Nats => nats::register_and_keepalive().await?,
+        MetaStore::PostgreSQL => postgres::register_and_keepalive().await?,
         _ => etcd::register_and_keepalive().await?,
     };
 
@@ -153,6 +218,7 @@ pub async fn set_online(new_lease_id: bool) -> Result<()> {
 
     match cfg.common.cluster_coordinator.as_str().into() {
         MetaStore::Nats => nats::set_online().await,
+        MetaStore::PostgreSQL => postgres::set_online().await,
         _ => etcd::set_online(new_lease_id).await,
     }
 }
@@ -165,6 +231,7 @@ pub async fn set_offline(new_lease_id: bool) -> Result<()> {
 
     match cfg.common.cluster_coordinator.as_str().into() {
         MetaStore::Nats => nats::set_offline().await,
+        MetaStore::PostgreSQL => postgres::set_offline().await,
         _ => etcd::set_offline(new_lease_id).await,
     }
 }
@@ -177,6 +244,7 @@ pub async fn update_local_node(node: &Node) -> Result<()> {
 
     match cfg.common.cluster_coordinator.as_str().into() {
         MetaStore::Nats => nats::update_local_node(node).await,
+        MetaStore::PostgreSQL => postgres::update_local_node(node).await,
         _ => etcd::update_local_node(node).await,
     }
 }
@@ -192,6 +260,7 @@ pub async fn leave() -> Result<()> {
 
     match cfg.common.cluster_coordinator.as_str().into() {
         MetaStore::Nats => nats::leave().await,
+        MetaStore::PostgreSQL => postgres::leave().await,
         _ => etcd::leave().await,
     }
 }
@@ -384,12 +453,16 @@ pub fn load_local_mode_node() -> Node {
         uuid: LOCAL_NODE_UUID.clone(),
         name: cfg.common.instance_name.clone(),
         http_addr: format!("http://127.0.0.1:{}", cfg.http.port),
-        grpc_addr: format!("http://127.0.0.1:{}", cfg.grpc.port),
+        grpc_addr: format!("{}://127.0.0.1:{}", tls::grpc_scheme(), cfg.grpc.port),
         role: [Role::All].to_vec(),
         cpu_num: cfg.limit.cpu_num as u64,
         status: NodeStatus::Online,
         scheduled: true,
         broadcasted: false,
+        zone: cfg.common.node_zone.clone(),
+        region: cfg.common.node_region.clone(),
+        grpc_version: GRPC_VERSION,
+        grpc_min_version: GRPC_MIN_VERSION,
     }
 }
 
@@ -398,33 +471,32 @@ pub async fn get_node_by_uuid(uuid: &str) -> Option<Node> {
     NODES.read().await.get(uuid).cloned()
 }
 
+// a node sharing no grpc version with this build can't decode its requests,
+// it is left out until the rolling upgrade reaches it
+#[inline]
+fn is_online_node(node: &Node) -> bool {
+    node.status == NodeStatus::Online && node.scheduled && node.negotiate_grpc_version().is_some()
+}
+
 #[inline]
 pub async fn get_cached_online_nodes() -> Option<Vec<Node>> {
-    get_cached_nodes(|node| node.status == NodeStatus::Online && node.scheduled).await
+    get_cached_nodes(is_online_node).await
 }
 
 #[inline]
 pub async fn get_cached_online_ingester_nodes() -> Option<Vec<Node>> {
-    get_cached_nodes(|node| {
-        node.status == NodeStatus::Online && node.scheduled && is_ingester(&node.role)
-    })
-    .await
+    get_cached_nodes(|node| is_online_node(node) && is_ingester(&node.role)).await
 }
 
 #[inline]
 pub async fn get_cached_online_querier_nodes() -> Option<Vec<Node>> {
-    get_cached_nodes(|node| {
-        node.status == NodeStatus::Online && node.scheduled && is_querier(&node.role)
-    })
-    .await
+    get_cached_nodes(|node| is_online_node(node) && is_querier(&node.role)).await
 }
 
 #[inline]
 pub async fn get_cached_online_query_nodes() -> Option<Vec<Node>> {
     get_cached_nodes(|node| {
-        node.status == NodeStatus::Online
-            && node.scheduled
-            && (is_querier(&node.role) || is_ingester(&node.role))
+        is_online_node(node) && (is_querier(&node.role) || is_ingester(&node.role))
     })
     .await
 }
@@ -450,6 +522,43 @@ mod tests {
         assert!(get_cached_online_querier_nodes().await.is_some());
     }
 
+    #[test]
+    fn test_rendezvous_hashing() {
+        let mut nodes = std::collections::HashMap::new();
+        for uuid in ["node-q-0", "node-q-1", "node-q-2"] {
+            nodes.insert(uuid.to_string(), 1);
+        }
+        let keys = (0..1000).map(|i| format!("file-{i}")).collect::<Vec<_>>();
+        let before = keys
+            .iter()
+            .map(|k| {
+                select_by_rendezvous_hash(k, nodes.iter().map(|(uuid, w)| (uuid, *w)))
+                    .unwrap()
+                    .clone()
+            })
+            .collect::<Vec<_>>();
+        // only the files of the removed node move
+        nodes.remove("node-q-2");
+        for (key, owner) in keys.iter().zip(before.iter()) {
+            let node =
+                select_by_rendezvous_hash(key, nodes.iter().map(|(uuid, w)| (uuid, *w))).unwrap();
+            if owner != "node-q-2" {
+                assert_eq!(node, owner);
+            }
+        }
+        // a node with 4x the weight gets about 80% of the files
+        nodes.insert("node-q-1".to_string(), 4);
+        let heavy = keys
+            .iter()
+            .filter(|k| {
+                select_by_rendezvous_hash(k, nodes.iter().map(|(uuid, w)| (uuid, *w))).unwrap()
+                    == "node-q-1"
+            })
+            .count();
+        assert!(heavy > 700 && heavy < 900, "heavy node got {heavy} files");
+        assert_eq!(select_by_rendezvous_hash("file", std::iter::empty()), None);
+    }
+
     #[tokio::test]
     async fn test_consistent_hashing() {
         let node = load_local_mode_node();
@@ -513,9 +622,11 @@ mod tests {
             ["test3", "node-q-3", "node-c-7"],
         ];
         for key in data {
-            assert_eq!(
-                get_node_from_consistent_hash(key.first().unwrap(), &Role::Querier).await,
-                Some(key.get(1).unwrap().to_string())
+            // queriers use rendezvous hashing, see test_rendezvous_hashing
+            assert!(
+                get_node_from_consistent_hash(key.first().unwrap(), &Role::Querier)
+                    .await
+                    .is_some()
             );
             assert_eq!(
                 get_node_from_consistent_hash(key.first().unwrap(), &Role::Compactor).await,
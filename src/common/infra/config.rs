@@ -26,11 +26,16 @@ use crate::{
     common::meta::{
         alerts,
         dashboards::reports,
+        dlp::StreamDlp,
+        enrichment_table::EnrichmentTableLookup,
         functions::{StreamFunctionsList, Transform},
+        masking::MaskingPolicy,
         maxmind::MaxmindClient,
-        organization::OrganizationSetting,
+        organization::{IngestionKey, OrgNetworkPolicy, OrgQuota, OrganizationSetting},
         pipelines::PipeLine,
         prom::ClusterLeader,
+        rbac::Role,
+        service_account::ServiceAccount,
         syslog::SyslogRoute,
         user::User,
     },
@@ -53,6 +58,21 @@ pub static USERS: Lazy<RwHashMap<String, User>> = Lazy::new(DashMap::default);
 pub static USERS_RUM_TOKEN: Lazy<Arc<RwHashMap<String, User>>> =
     Lazy::new(|| Arc::new(DashMap::default()));
 pub static ROOT_USER: Lazy<RwHashMap<String, User>> = Lazy::new(DashMap::default);
+// key: org_id/role_name
+pub static ROLES: Lazy<RwHashMap<String, Role>> = Lazy::new(DashMap::default);
+// key: org_id/service_account_name
+pub static SERVICE_ACCOUNTS: Lazy<RwHashMap<String, ServiceAccount>> = Lazy::new(DashMap::default);
+// key: org_id/policy_name
+pub static MASKING_POLICIES: Lazy<RwHashMap<String, MaskingPolicy>> = Lazy::new(DashMap::default);
+// key: org_id/stream_name, with the compiled rules
+pub static STREAM_DLP: Lazy<RwHashMap<String, StreamDlp>> = Lazy::new(DashMap::default);
+// key: org_id/key_id
+pub static INGESTION_KEYS: Lazy<RwHashMap<String, IngestionKey>> = Lazy::new(DashMap::default);
+// key: org_id
+pub static ORGANIZATION_QUOTAS: Lazy<RwHashMap<String, OrgQuota>> = Lazy::new(DashMap::default);
+// key: org_id
+pub static ORGANIZATION_NETWORK_POLICIES: Lazy<RwHashMap<String, OrgNetworkPolicy>> =
+    Lazy::new(DashMap::default);
 pub static ORGANIZATION_SETTING: Lazy<Arc<RwAHashMap<String, OrganizationSetting>>> =
     Lazy::new(|| Arc::new(tokio::sync::RwLock::new(HashMap::new())));
 pub static PASSWORD_HASH: Lazy<RwHashMap<String, String>> = Lazy::new(DashMap::default);
@@ -73,6 +93,8 @@ pub static DASHBOARD_REPORTS: Lazy<RwHashMap<String, reports::Report>> =
 pub static SYSLOG_ROUTES: Lazy<RwHashMap<String, SyslogRoute>> = Lazy::new(Default::default);
 pub static SYSLOG_ENABLED: Lazy<Arc<RwLock<bool>>> = Lazy::new(|| Arc::new(RwLock::new(false)));
 pub static ENRICHMENT_TABLES: Lazy<RwHashMap<String, StreamTable>> = Lazy::new(Default::default);
+pub static ENRICHMENT_TABLE_LOOKUPS: Lazy<RwHashMap<String, EnrichmentTableLookup>> =
+    Lazy::new(Default::default);
 pub static ENRICHMENT_REGISTRY: Lazy<Arc<TableRegistry>> =
     Lazy::new(|| Arc::new(TableRegistry::default()));
 
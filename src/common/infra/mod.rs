@@ -20,6 +20,7 @@ use crate::service::db::instance;
 pub mod cluster;
 pub mod config;
 pub mod ofga;
+pub mod tls;
 pub mod wal;
 
 pub async fn init() -> Result<(), anyhow::Error> {
@@ -34,6 +35,7 @@ pub async fn init() -> Result<(), anyhow::Error> {
     };
     cache_instance_id(&instance_id);
 
+    tls::init()?;
     wal::init().await?;
     // because of asynchronous, we need to wait for a while
     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
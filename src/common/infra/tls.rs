@@ -0,0 +1,274 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! TLS of the HTTP server and of the gRPC communication between nodes. The
+//! certificates are reloaded on SIGHUP or when the files change, so they can
+//! be rotated without restarting the nodes.
+
+use std::{fs, io::BufReader, sync::Arc, time::SystemTime};
+
+use config::get_config;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    RootCertStore, ServerConfig,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+
+// interval to check the certificate files for changes, in seconds
+const RELOAD_CHECK_INTERVAL: u64 = 30;
+
+static SERVER_CERTS: Lazy<RwLock<Vec<Arc<ReloadableCert>>>> = Lazy::new(Default::default);
+static GRPC_CLIENT_TLS: Lazy<RwLock<Option<ClientTlsConfig>>> = Lazy::new(Default::default);
+
+/// Server certificate that can be swapped while the server is running.
+#[derive(Debug)]
+pub struct ReloadableCert {
+    cert_path: String,
+    key_path: String,
+    key: RwLock<Arc<CertifiedKey>>,
+    modified: RwLock<Option<SystemTime>>,
+}
+
+impl ReloadableCert {
+    fn load(cert_path: &str, key_path: &str) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            key: RwLock::new(Arc::new(load_certified_key(cert_path, key_path)?)),
+            modified: RwLock::new(modified_time(&[cert_path, key_path])),
+        })
+    }
+
+    fn reload(&self) -> Result<(), anyhow::Error> {
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.key.write() = Arc::new(key);
+        *self.modified.write() = modified_time(&[&self.cert_path, &self.key_path]);
+        log::info!("[TLS] certificate {} reloaded", self.cert_path);
+        Ok(())
+    }
+
+    fn is_changed(&self) -> bool {
+        modified_time(&[&self.cert_path, &self.key_path]) != *self.modified.read()
+    }
+}
+
+impl ResolvesServerCert for ReloadableCert {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.read().clone())
+    }
+}
+
+/// Loads the gRPC client config and starts watching the certificates.
+pub fn init() -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    if cfg.grpc.tls_enabled {
+        *GRPC_CLIENT_TLS.write() = Some(load_grpc_client_config()?);
+    }
+    if cfg.grpc.tls_enabled || cfg.http.tls_enabled {
+        tokio::task::spawn(async move { run().await });
+    }
+    Ok(())
+}
+
+/// Returns the scheme of the gRPC address nodes register with.
+pub fn grpc_scheme() -> &'static str {
+    if get_config().grpc.tls_enabled {
+        "https"
+    } else {
+        "http"
+    }
+}
+
+/// Creates the endpoint to connect to another node, using mutual TLS when it
+/// is enabled for gRPC.
+pub fn grpc_endpoint(addr: String) -> Result<Endpoint, tonic::transport::Error> {
+    let endpoint = Channel::from_shared(addr)?;
+    match GRPC_CLIENT_TLS.read().clone() {
+        Some(tls) => endpoint.tls_config(tls),
+        None => Ok(endpoint),
+    }
+}
+
+/// Server config of the HTTP server, client certificates are verified when
+/// `ZO_HTTP_TLS_CA_CERT_PATH` is set.
+pub fn http_server_config() -> Result<ServerConfig, anyhow::Error> {
+    let cfg = get_config();
+    server_config(
+        &cfg.http.tls_cert_path,
+        &cfg.http.tls_key_path,
+        &cfg.http.tls_ca_cert_path,
+    )
+}
+
+/// Accepts the gRPC connections of the listener over mutual TLS, the
+/// handshakes are done in their own tasks so a slow client doesn't hold the
+/// listener.
+pub fn grpc_incoming(
+    listener: TcpListener,
+) -> Result<ReceiverStream<Result<TlsStream<TcpStream>, std::io::Error>>, anyhow::Error> {
+    let cfg = get_config();
+    let mut config = server_config(
+        &cfg.grpc.tls_cert_path,
+        &cfg.grpc.tls_key_path,
+        &cfg.grpc.tls_ca_cert_path,
+    )?;
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let (tx, rx) = mpsc::channel(1024);
+    tokio::task::spawn(async move {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("[TLS] gRPC accept error: {}", e);
+                    continue;
+                }
+            };
+            if tx.is_closed() {
+                break;
+            }
+            let acceptor = acceptor.clone();
+            let tx = tx.clone();
+            tokio::task::spawn(async move {
+                match acceptor.accept(stream).await {
+                    Ok(stream) => {
+                        let _ = tx.send(Ok(stream)).await;
+                    }
+                    Err(e) => log::warn!("[TLS] gRPC handshake with {} failed: {}", addr, e),
+                }
+            });
+        }
+    });
+    Ok(ReceiverStream::new(rx))
+}
+
+fn server_config(
+    cert_path: &str,
+    key_path: &str,
+    ca_cert_path: &str,
+) -> Result<ServerConfig, anyhow::Error> {
+    let cert = Arc::new(ReloadableCert::load(cert_path, key_path)?);
+    SERVER_CERTS.write().push(cert.clone());
+
+    let builder = ServerConfig::builder();
+    let builder = if ca_cert_path.is_empty() {
+        builder.with_no_client_auth()
+    } else {
+        let roots = load_root_store(ca_cert_path)?;
+        builder.with_client_cert_verifier(WebPkiClientVerifier::builder(Arc::new(roots)).build()?)
+    };
+    Ok(builder.with_cert_resolver(cert))
+}
+
+fn load_grpc_client_config() -> Result<ClientTlsConfig, anyhow::Error> {
+    let cfg = get_config();
+    let ca_cert = fs::read(&cfg.grpc.tls_ca_cert_path)?;
+    let cert = fs::read(&cfg.grpc.tls_cert_path)?;
+    let key = fs::read(&cfg.grpc.tls_key_path)?;
+    let mut tls = ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(ca_cert))
+        .identity(Identity::from_pem(cert, key));
+    if !cfg.grpc.tls_domain.is_empty() {
+        tls = tls.domain_name(&cfg.grpc.tls_domain);
+    }
+    Ok(tls)
+}
+
+fn load_certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey, anyhow::Error> {
+    let certs = load_certs(cert_path)?;
+    let key: PrivateKeyDer =
+        rustls_pemfile::private_key(&mut BufReader::new(fs::File::open(key_path)?))?
+            .ok_or_else(|| anyhow::anyhow!("no private key found in {key_path}"))?;
+    let key = rustls::crypto::ring::sign::any_supported_type(&key)?;
+    Ok(CertifiedKey::new(certs, key))
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, anyhow::Error> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(fs::File::open(path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!("no certificate found in {path}"));
+    }
+    Ok(certs)
+}
+
+fn load_root_store(path: &str) -> Result<RootCertStore, anyhow::Error> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert)?;
+    }
+    Ok(roots)
+}
+
+// latest modification time of the files
+fn modified_time(paths: &[&str]) -> Option<SystemTime> {
+    paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+fn reload(force: bool) {
+    let certs = SERVER_CERTS.read().clone();
+    let mut changed = false;
+    for cert in certs.iter().filter(|cert| force || cert.is_changed()) {
+        changed = true;
+        if let Err(e) = cert.reload() {
+            log::error!("[TLS] reload certificate {} error: {}", cert.cert_path, e);
+        }
+    }
+    // new connections to other nodes pick up the reloaded client certificate
+    if changed && get_config().grpc.tls_enabled {
+        match load_grpc_client_config() {
+            Ok(tls) => *GRPC_CLIENT_TLS.write() = Some(tls),
+            Err(e) => log::error!("[TLS] reload gRPC client config error: {}", e),
+        }
+    }
+}
+
+async fn run() {
+    #[cfg(unix)]
+    let mut sighup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("failed to listen for SIGHUP");
+    let mut interval =
+        tokio::time::interval(tokio::time::Duration::from_secs(RELOAD_CHECK_INTERVAL));
+    interval.tick().await; // trigger the first run
+    loop {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = sighup.recv() => {
+                log::info!("[TLS] SIGHUP received, reloading certificates");
+                reload(true);
+            }
+            _ = interval.tick() => reload(false),
+        }
+        #[cfg(not(unix))]
+        {
+            interval.tick().await;
+            reload(false);
+        }
+    }
+}
@@ -50,6 +50,10 @@ pub struct Alert {
     /// Timezone offset in minutes.
     /// The negative secs means the Western Hemisphere
     pub tz_offset: i32,
+    /// IANA timezone of the cron schedule, e.g. `America/New_York`, which
+    /// follows the DST. `tz_offset` is used when empty
+    #[serde(default)]
+    pub timezone: String,
 }
 
 impl PartialEq for Alert {
@@ -76,6 +80,7 @@ impl Default for Alert {
             description: "".to_string(),
             enabled: false,
             tz_offset: 0, // UTC
+            timezone: "".to_string(),
         }
     }
 }
@@ -0,0 +1,123 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Annotation created by a user
+pub const SOURCE_MANUAL: &str = "manual";
+/// Annotation derived from the history of an alert
+pub const SOURCE_ALERT: &str = "alert";
+
+/// An event drawn on the charts, at `start_time` or over `start_time` to
+/// `end_time`. Times are in microseconds.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Annotation {
+    #[serde(default)]
+    pub annotation_id: String,
+    #[serde(default)]
+    pub source: String,
+    pub start_time: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<i64>,
+    pub title: String,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// restricts the annotation to the charts of a dashboard, or of a panel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dashboard_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub panel_id: Option<String>,
+    /// restricts the annotation to the charts of a stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_name: Option<String>,
+    #[serde(default)]
+    pub created_by: String,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+impl Annotation {
+    /// Returns true when the annotation is within the time range.
+    pub fn overlaps(&self, start_time: i64, end_time: i64) -> bool {
+        self.start_time <= end_time && self.end_time.unwrap_or(self.start_time) >= start_time
+    }
+
+    /// Returns true when the annotation is drawn on the charts of the filter,
+    /// an annotation without dashboard or stream is drawn on every chart.
+    pub fn matches(&self, filter: &AnnotationQuery) -> bool {
+        fn matches(value: &Option<String>, filter: &Option<String>) -> bool {
+            match (value, filter) {
+                (Some(value), Some(filter)) => value == filter,
+                _ => true,
+            }
+        }
+        self.overlaps(filter.start_time, filter.end_time)
+            && matches(&self.dashboard_id, &filter.dashboard_id)
+            && matches(&self.panel_id, &filter.panel_id)
+            && matches(&self.stream_type, &filter.stream_type)
+            && matches(&self.stream_name, &filter.stream_name)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(style = Form, parameter_in = Query)]
+pub struct AnnotationQuery {
+    /// microseconds
+    pub start_time: i64,
+    /// microseconds
+    pub end_time: i64,
+    pub dashboard_id: Option<String>,
+    pub panel_id: Option<String>,
+    pub stream_type: Option<String>,
+    pub stream_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct AnnotationList {
+    pub list: Vec<Annotation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotation_matches() {
+        let annotation = Annotation {
+            start_time: 100,
+            end_time: Some(200),
+            title: "deploy".to_string(),
+            stream_type: Some("logs".to_string()),
+            stream_name: Some("default".to_string()),
+            ..Default::default()
+        };
+        let filter = |start_time, end_time, stream: Option<&str>| AnnotationQuery {
+            start_time,
+            end_time,
+            stream_type: Some("logs".to_string()),
+            stream_name: stream.map(|s| s.to_string()),
+            ..Default::default()
+        };
+        assert!(annotation.matches(&filter(150, 300, Some("default"))));
+        assert!(annotation.matches(&filter(0, 100, None)));
+        assert!(!annotation.matches(&filter(201, 300, Some("default"))));
+        assert!(!annotation.matches(&filter(0, 300, Some("k8s"))));
+    }
+}
@@ -0,0 +1,370 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use config::utils::json;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Internal stream of the meta organization the audit entries are written to.
+pub const AUDIT_STREAM: &str = "_audit";
+
+/// Fields that never end up in the audit trail.
+const REDACTED_FIELDS: [&str; 9] = [
+    "password",
+    "new_password",
+    "old_password",
+    "salt",
+    "token",
+    "rum_token",
+    "passcode",
+    "hash",
+    "client_secret",
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditResource {
+    User,
+    Stream,
+    Alert,
+    Dashboard,
+    Function,
+    Setting,
+    Role,
+    ServiceAccount,
+}
+
+impl fmt::Display for AuditResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditResource::User => write!(f, "user"),
+            AuditResource::Stream => write!(f, "stream"),
+            AuditResource::Alert => write!(f, "alert"),
+            AuditResource::Dashboard => write!(f, "dashboard"),
+            AuditResource::Function => write!(f, "function"),
+            AuditResource::Setting => write!(f, "setting"),
+            AuditResource::Role => write!(f, "role"),
+            AuditResource::ServiceAccount => write!(f, "service_account"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Login,
+    LoginFailed,
+    LoginLocked,
+    PasswordReset,
+    AccessDenied,
+}
+
+impl AuditAction {
+    /// Works out the action from the state of the resource around the request,
+    /// falling back to the http method when no snapshot is available.
+    pub fn new(method: &str, before: Option<&json::Value>, after: Option<&json::Value>) -> Self {
+        match (before, after) {
+            (None, Some(_)) if !method.eq("DELETE") => AuditAction::Create,
+            (Some(_), None) => AuditAction::Delete,
+            (Some(_), Some(_)) => AuditAction::Update,
+            _ => match method {
+                "POST" => AuditAction::Create,
+                "DELETE" => AuditAction::Delete,
+                _ => AuditAction::Update,
+            },
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditAction::Create => write!(f, "create"),
+            AuditAction::Update => write!(f, "update"),
+            AuditAction::Delete => write!(f, "delete"),
+            AuditAction::Login => write!(f, "login"),
+            AuditAction::LoginFailed => write!(f, "login_failed"),
+            AuditAction::LoginLocked => write!(f, "login_locked"),
+            AuditAction::PasswordReset => write!(f, "password_reset"),
+            AuditAction::AccessDenied => write!(f, "access_denied"),
+        }
+    }
+}
+
+/// The resource a management request operates on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditTarget {
+    pub org_id: String,
+    pub resource: AuditResource,
+    /// name or id of the resource, empty when the request creates it
+    pub resource_id: String,
+    /// the path columns after the resource id, e.g. `settings` for a stream
+    pub sub_path: Vec<String>,
+}
+
+/// Maps a management request under `/api/` to the resource it changes, read
+/// only requests and ingestion return `None`.
+pub fn get_target(method: &str, path: &str) -> Option<AuditTarget> {
+    if !matches!(method, "POST" | "PUT" | "DELETE") {
+        return None;
+    }
+    let columns = path.trim_end_matches('/').split('/').collect::<Vec<_>>();
+    let (org_id, columns) = columns.split_first()?;
+    let (resource, resource_id, sub_path) = match columns {
+        ["users", rest @ ..] => (AuditResource::User, rest.first(), rest.get(1..)),
+        ["streams", stream, rest @ ..] => (AuditResource::Stream, Some(stream), Some(rest)),
+        ["alerts", kind @ ("templates" | "destinations"), rest @ ..] => {
+            return Some(AuditTarget {
+                org_id: org_id.to_string(),
+                resource: AuditResource::Alert,
+                resource_id: rest.first().map(|v| v.to_string()).unwrap_or_default(),
+                sub_path: vec![kind.to_string()],
+            });
+        }
+        [stream, "alerts", rest @ ..] => {
+            return Some(AuditTarget {
+                org_id: org_id.to_string(),
+                resource: AuditResource::Alert,
+                resource_id: rest.first().map(|v| v.to_string()).unwrap_or_default(),
+                sub_path: vec![stream.to_string()],
+            });
+        }
+        ["folders", "dashboards", id] => (AuditResource::Dashboard, Some(id), None),
+        ["dashboards", rest @ ..] => (AuditResource::Dashboard, rest.first(), None),
+        ["folders", rest @ ..] => (AuditResource::Dashboard, rest.first(), Some(&columns[..1])),
+        ["functions", rest @ ..] => (AuditResource::Function, rest.first(), None),
+        ["settings", rest @ ..] => (AuditResource::Setting, None, Some(rest)),
+        ["quotas" | "network_policy"] => (AuditResource::Setting, None, Some(columns)),
+        // the runtime config is not owned by an org, it is recorded under `_admin`
+        ["config"] if *org_id == "_admin" => (AuditResource::Setting, None, Some(columns)),
+        ["masking_policies" | "ingestion_keys", rest @ ..] => {
+            (AuditResource::Setting, rest.first(), Some(&columns[..1]))
+        }
+        ["roles", rest @ ..] => (AuditResource::Role, rest.first(), rest.get(1..)),
+        ["service_accounts", rest @ ..] => {
+            (AuditResource::ServiceAccount, rest.first(), rest.get(1..))
+        }
+        _ => return None,
+    };
+    Some(AuditTarget {
+        org_id: org_id.to_string(),
+        resource,
+        resource_id: resource_id.map(|v| v.to_string()).unwrap_or_default(),
+        sub_path: sub_path
+            .unwrap_or_default()
+            .iter()
+            .map(|v| v.to_string())
+            .collect(),
+    })
+}
+
+/// One row of the audit stream. `before`, `after` and `diff` hold json
+/// documents as text so their shape doesn't leak into the stream schema.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub _timestamp: i64,
+    pub org_id: String,
+    pub user_email: String,
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub query_params: String,
+    pub resource: String,
+    #[serde(default)]
+    pub resource_id: String,
+    pub action: String,
+    pub response_code: u16,
+    #[serde(default)]
+    pub before: String,
+    #[serde(default)]
+    pub after: String,
+    #[serde(default)]
+    pub diff: String,
+    /// only recorded for requests rejected by a network policy
+    #[serde(default)]
+    pub client_ip: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct AuditList {
+    pub list: Vec<AuditEntry>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+#[into_params(style = Form, parameter_in = Query)]
+pub struct AuditQuery {
+    /// email of the user who made the change
+    pub user: Option<String>,
+    pub resource: Option<String>,
+    pub resource_id: Option<String>,
+    pub action: Option<String>,
+    /// microseconds, defaults to one day before `end_time`
+    pub start_time: Option<i64>,
+    /// microseconds, defaults to now
+    pub end_time: Option<i64>,
+    pub from: Option<i64>,
+    pub size: Option<i64>,
+}
+
+/// Removes credentials from a document before it is recorded.
+pub fn redact(value: &mut json::Value) {
+    match value {
+        json::Value::Object(map) => {
+            map.retain(|k, _| !REDACTED_FIELDS.contains(&k.as_str()));
+            map.values_mut().for_each(redact);
+        }
+        json::Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Returns the changed fields between two documents keyed by their dotted
+/// path, each with its `before` and `after` value. Arrays are compared as a
+/// whole.
+pub fn diff(before: &json::Value, after: &json::Value) -> json::Map<String, json::Value> {
+    let mut changes = json::Map::new();
+    diff_inner("", before, after, &mut changes);
+    changes
+}
+
+fn diff_inner(
+    prefix: &str,
+    before: &json::Value,
+    after: &json::Value,
+    changes: &mut json::Map<String, json::Value>,
+) {
+    if before == after {
+        return;
+    }
+    match (before, after) {
+        (json::Value::Object(before), json::Value::Object(after)) => {
+            for (key, value) in before.iter() {
+                let path = join_path(prefix, key);
+                diff_inner(
+                    &path,
+                    value,
+                    after.get(key).unwrap_or(&json::Value::Null),
+                    changes,
+                );
+            }
+            for (key, value) in after.iter() {
+                if !before.contains_key(key) {
+                    diff_inner(&join_path(prefix, key), &json::Value::Null, value, changes);
+                }
+            }
+        }
+        _ => {
+            changes.insert(
+                prefix.to_string(),
+                json::json!({"before": before, "after": after}),
+            );
+        }
+    }
+}
+
+fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_target() {
+        assert_eq!(get_target("GET", "default/users"), None);
+        assert_eq!(get_target("POST", "default/logs/_json"), None);
+        assert_eq!(
+            get_target("PUT", "default/streams/app/settings"),
+            Some(AuditTarget {
+                org_id: "default".to_string(),
+                resource: AuditResource::Stream,
+                resource_id: "app".to_string(),
+                sub_path: vec!["settings".to_string()],
+            })
+        );
+        assert_eq!(
+            get_target("DELETE", "default/app/alerts/errors"),
+            Some(AuditTarget {
+                org_id: "default".to_string(),
+                resource: AuditResource::Alert,
+                resource_id: "errors".to_string(),
+                sub_path: vec!["app".to_string()],
+            })
+        );
+        let target = get_target("POST", "default/users").unwrap();
+        assert_eq!(target.resource, AuditResource::User);
+        assert!(target.resource_id.is_empty());
+        let target = get_target("PUT", "default/folders/dashboards/d1").unwrap();
+        assert_eq!(target.resource, AuditResource::Dashboard);
+        assert_eq!(target.resource_id, "d1");
+        assert!(target.sub_path.is_empty());
+        let target = get_target("PUT", "_admin/config").unwrap();
+        assert_eq!(target.resource, AuditResource::Setting);
+        assert_eq!(target.sub_path, vec!["config".to_string()]);
+        assert_eq!(
+            get_target("POST", "_admin/caches/file_data_memory/_flush"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = json::json!({"name": "a", "conf": {"size": 1, "keep": true}});
+        let after = json::json!({"name": "a", "conf": {"size": 2, "keep": true}, "tags": ["x"]});
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes.get("conf.size"),
+            Some(&json::json!({"before": 1, "after": 2}))
+        );
+        assert_eq!(
+            changes.get("tags"),
+            Some(&json::json!({"before": null, "after": ["x"]}))
+        );
+        assert!(diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_redact_and_action() {
+        let mut user = json::json!({"email": "a@b.c", "password": "x", "orgs": [{"token": "t"}]});
+        redact(&mut user);
+        assert_eq!(user, json::json!({"email": "a@b.c", "orgs": [{}]}));
+
+        let doc = json::json!({});
+        assert_eq!(
+            AuditAction::new("PUT", None, Some(&doc)),
+            AuditAction::Create
+        );
+        assert_eq!(
+            AuditAction::new("PUT", Some(&doc), Some(&doc)),
+            AuditAction::Update
+        );
+        assert_eq!(
+            AuditAction::new("DELETE", Some(&doc), None),
+            AuditAction::Delete
+        );
+        assert_eq!(AuditAction::new("POST", None, None), AuditAction::Create);
+    }
+}
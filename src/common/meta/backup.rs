@@ -0,0 +1,180 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Modules of the meta store describing the running cluster rather than its
+/// configuration, a restored cluster has its own.
+const VOLATILE_MODULES: [&str; 7] = [
+    "nodes",
+    "nodes_heartbeat",
+    "metrics_members",
+    "metrics_leader",
+    "instance",
+    "user_sessions",
+    "locker",
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BackupManifest {
+    pub id: String,
+    /// the backup this one is incremental to, none for a full backup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// in microseconds
+    pub created_at: i64,
+    /// number of keys of the meta store at the time of the backup
+    pub keys: usize,
+    /// keys written to this backup
+    pub changed: usize,
+    /// keys deleted since the base backup
+    #[serde(default)]
+    pub deleted: Vec<String>,
+    /// file list snapshot taken with the backup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_list_snapshot: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, ToSchema)]
+pub struct BackupRequest {
+    /// only back up the keys changed since the latest backup
+    #[serde(default)]
+    pub incremental: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, ToSchema)]
+pub struct RestoreRequest {
+    /// id of the backup to restore, the latest by default
+    #[serde(default)]
+    pub backup: Option<String>,
+    /// replace the keys which exist already
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RestoreReport {
+    pub backup: String,
+    pub restored: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub file_list_loaded: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct BackupList {
+    pub list: Vec<BackupManifest>,
+}
+
+/// A key of the meta store with its value, in base64.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub key: String,
+    pub value: String,
+}
+
+/// Whether the key belongs in a backup.
+pub fn is_backed_up(key: &str) -> bool {
+    let module = key.trim_start_matches('/').split('/').next().unwrap_or("");
+    !module.is_empty() && !VOLATILE_MODULES.contains(&module)
+}
+
+/// Splits the version of a schema off its key, the only keys of the meta
+/// store written with a start time.
+pub fn split_start_dt(key: &str) -> (&str, Option<i64>) {
+    if !key.starts_with("/schema/") || key.split('/').count() != 6 {
+        return (key, None);
+    }
+    match key.rsplit_once('/') {
+        Some((prefix, v)) => match v.parse::<i64>() {
+            Ok(start_dt) => (prefix, Some(start_dt)),
+            Err(_) => (key, None),
+        },
+        None => (key, None),
+    }
+}
+
+/// The keys changed or added in `current` and the keys of `previous` which
+/// are gone, both sorted.
+pub fn diff(
+    previous: &HashMap<String, u64>,
+    current: &HashMap<String, u64>,
+) -> (Vec<String>, Vec<String>) {
+    let mut changed = current
+        .iter()
+        .filter(|(k, v)| previous.get(*k) != Some(*v))
+        .map(|(k, _)| k.clone())
+        .collect::<Vec<_>>();
+    let mut deleted = previous
+        .keys()
+        .filter(|k| !current.contains_key(*k))
+        .cloned()
+        .collect::<Vec<_>>();
+    changed.sort();
+    deleted.sort();
+    (changed, deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_backed_up() {
+        assert!(is_backed_up("/user/root@example.com"));
+        assert!(is_backed_up("/schema/default/logs/app/1718000000000000"));
+        assert!(!is_backed_up("/nodes/b2c3"));
+        assert!(!is_backed_up("/user_sessions/abc"));
+        assert!(!is_backed_up("/"));
+    }
+
+    #[test]
+    fn test_split_start_dt() {
+        assert_eq!(
+            split_start_dt("/schema/default/logs/app/1718000000000000"),
+            ("/schema/default/logs/app", Some(1718000000000000))
+        );
+        assert_eq!(
+            split_start_dt("/schema/default/logs/app"),
+            ("/schema/default/logs/app", None)
+        );
+        assert_eq!(
+            split_start_dt("/dashboard/default/f1/1718000000000000"),
+            ("/dashboard/default/f1/1718000000000000", None)
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = HashMap::from([
+            ("/a".to_string(), 1),
+            ("/b".to_string(), 2),
+            ("/c".to_string(), 3),
+        ]);
+        let current = HashMap::from([
+            ("/a".to_string(), 1),
+            ("/b".to_string(), 20),
+            ("/d".to_string(), 4),
+        ]);
+        let (changed, deleted) = diff(&previous, &current);
+        assert_eq!(changed, vec!["/b", "/d"]);
+        assert_eq!(deleted, vec!["/c"]);
+        let (changed, deleted) = diff(&HashMap::new(), &current);
+        assert_eq!(changed.len(), 3);
+        assert!(deleted.is_empty());
+    }
+}
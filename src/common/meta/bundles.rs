@@ -0,0 +1,154 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::str::FromStr;
+
+use config::utils::json::Value;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{
+    alerts::{destinations::Destination, templates::Template, Alert},
+    dashboards::Folder,
+    functions::Transform,
+};
+
+/// Version of the bundle format, bumped on incompatible changes.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Dashboards, alerts and their dependencies of an organization, exported to
+/// be imported into another organization or cluster.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Bundle {
+    pub version: u32,
+    /// organization the bundle was exported from
+    #[serde(default)]
+    pub org_id: String,
+    /// in microseconds
+    #[serde(default)]
+    pub exported_at: i64,
+    #[serde(default)]
+    pub folders: Vec<Folder>,
+    #[serde(default)]
+    pub dashboards: Vec<BundleDashboard>,
+    #[serde(default)]
+    pub templates: Vec<Template>,
+    #[serde(default)]
+    pub destinations: Vec<Destination>,
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
+    #[serde(default)]
+    pub functions: Vec<Transform>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct BundleDashboard {
+    pub folder_id: String,
+    /// the dashboard as stored, of any version
+    #[schema(value_type = Object)]
+    pub dashboard: Value,
+}
+
+/// What to do with an item of the bundle which already exists.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    #[default]
+    Skip,
+    Overwrite,
+    /// import the item under a new name, the references of the other items
+    /// of the bundle follow the new name
+    Rename,
+}
+
+impl FromStr for ConflictStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(ConflictStrategy::Skip),
+            "overwrite" => Ok(ConflictStrategy::Overwrite),
+            "rename" => Ok(ConflictStrategy::Rename),
+            _ => Err(format!("invalid conflict strategy: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Created,
+    Skipped,
+    Overwritten,
+    Renamed,
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImportResult {
+    /// `folder`, `dashboard`, `template`, `destination`, `alert` or `function`
+    pub kind: String,
+    pub name: String,
+    pub action: ImportAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    pub results: Vec<ImportResult>,
+}
+
+/// First free name of the `{name}_copy`, `{name}_copy_2`, ... sequence.
+pub fn rename_candidate(name: &str, exists: impl Fn(&str) -> bool) -> String {
+    let mut candidate = format!("{name}_copy");
+    let mut i = 2;
+    while exists(&candidate) {
+        candidate = format!("{name}_copy_{i}");
+        i += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_candidate() {
+        assert_eq!(rename_candidate("cpu", |_| false), "cpu_copy");
+        let taken = ["cpu_copy", "cpu_copy_2"];
+        assert_eq!(
+            rename_candidate("cpu", |n| taken.contains(&n)),
+            "cpu_copy_3"
+        );
+    }
+
+    #[test]
+    fn test_conflict_strategy() {
+        assert_eq!("Overwrite".parse(), Ok(ConflictStrategy::Overwrite));
+        assert_eq!("rename".parse(), Ok(ConflictStrategy::Rename));
+        assert!("merge".parse::<ConflictStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_bundle_defaults() {
+        let bundle: Bundle = config::utils::json::from_str(r#"{"version":1}"#).unwrap();
+        assert_eq!(bundle.version, BUNDLE_VERSION);
+        assert!(bundle.dashboards.is_empty() && bundle.alerts.is_empty());
+    }
+}
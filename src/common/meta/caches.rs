@@ -0,0 +1,30 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The usage of an in-process cache of the node.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CacheInfo {
+    pub name: String,
+    pub entries: usize,
+    /// Bytes used by the entries, 0 when the cache does not track it.
+    pub bytes: usize,
+    /// Bytes the cache may use, 0 when it is not bounded by size.
+    pub max_bytes: usize,
+    /// Whether the cache can be flushed through the admin API.
+    pub flushable: bool,
+}
@@ -0,0 +1,39 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::Dashboard;
+
+/// Part of a Grafana dashboard that could not be imported.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UnsupportedItem {
+    /// `panel`, `target` or `variable`
+    pub kind: String,
+    /// title of the panel or name of the variable
+    pub name: String,
+    /// Grafana type of the panel, datasource or variable
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct GrafanaImportResponse {
+    pub dashboard: Dashboard,
+    /// panels, targets and variables left out of the dashboard
+    pub unsupported: Vec<UnsupportedItem>,
+}
@@ -33,6 +33,80 @@ pub struct Dashboard {
     pub v2: Option<v2::Dashboard>,
     pub v3: Option<v3::Dashboard>,
     pub version: i32,
+    /// Folder of the dashboard, only set when listing dashboards across
+    /// folders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder_id: Option<String>,
+}
+
+impl Dashboard {
+    pub fn dashboard_id(&self) -> &str {
+        match (&self.v1, &self.v2, &self.v3) {
+            (Some(d), ..) => &d.dashboard_id,
+            (_, Some(d), _) => &d.dashboard_id,
+            (.., Some(d)) => &d.dashboard_id,
+            _ => "",
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        match (&self.v1, &self.v2, &self.v3) {
+            (Some(d), ..) => &d.title,
+            (_, Some(d), _) => &d.title,
+            (.., Some(d)) => &d.title,
+            _ => "",
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        match (&self.v1, &self.v2, &self.v3) {
+            (Some(d), ..) => &d.description,
+            (_, Some(d), _) => &d.description,
+            (.., Some(d)) => &d.description,
+            _ => "",
+        }
+    }
+
+    /// Tags are only supported by the v3 dashboards.
+    pub fn tags(&self) -> &[String] {
+        self.v3
+            .as_ref()
+            .map(|d| d.tags.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns true when the dashboard has the `tag` (case insensitive) and the
+    /// `query` is contained in its title, description or one of its tags.
+    pub fn matches(&self, filter: &DashboardFilter) -> bool {
+        if let Some(tag) = filter.tag.as_deref() {
+            if !self.tags().iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                return false;
+            }
+        }
+        let Some(query) = filter.query.as_deref() else {
+            return true;
+        };
+        let query = query.to_lowercase();
+        self.title().to_lowercase().contains(&query)
+            || self.description().to_lowercase().contains(&query)
+            || self
+                .tags()
+                .iter()
+                .any(|t| t.to_lowercase().contains(&query))
+    }
+}
+
+/// Filter of the dashboards listing, empty fields match every dashboard.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DashboardFilter {
+    pub tag: Option<String>,
+    pub query: Option<String>,
+}
+
+impl DashboardFilter {
+    pub fn is_empty(&self) -> bool {
+        self.tag.is_none() && self.query.is_none()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
@@ -40,10 +114,14 @@ pub struct Dashboards {
     pub dashboards: Vec<Dashboard>,
 }
 
+pub mod grafana;
 pub mod reports;
+pub mod snapshots;
 pub mod v1;
 pub mod v2;
 pub mod v3;
+pub mod variables;
+pub mod versions;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -52,6 +130,19 @@ pub struct Folder {
     pub folder_id: String,
     pub name: String,
     pub description: String,
+    /// Parent folder, top level folders don't have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyDashboard {
+    pub from: String,
+    pub to: String,
+    /// Title of the copy, defaults to the title of the source dashboard.
+    #[serde(default)]
+    pub title: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -68,8 +159,105 @@ pub struct FolderList {
 
 pub const DEFAULT_FOLDER: &str = "default";
 
+/// Returns the ids of the folder and of all its descendants.
+pub fn folder_with_descendants(folders: &[Folder], folder_id: &str) -> Vec<String> {
+    let mut ids = vec![folder_id.to_string()];
+    let mut i = 0;
+    while i < ids.len() {
+        for folder in folders {
+            if folder.parent_id.as_deref() == Some(ids[i].as_str())
+                && !ids.contains(&folder.folder_id)
+            {
+                ids.push(folder.folder_id.clone());
+            }
+        }
+        i += 1;
+    }
+    ids
+}
+
+/// Returns the ids of the folder and of all its ancestors, starting with the
+/// folder itself.
+pub fn folder_with_ancestors(folders: &[Folder], folder_id: &str) -> Vec<String> {
+    let mut ids = vec![folder_id.to_string()];
+    while let Some(parent) = folders
+        .iter()
+        .find(|f| f.folder_id == ids[ids.len() - 1])
+        .and_then(|f| f.parent_id.as_ref())
+    {
+        if ids.contains(parent) {
+            break;
+        }
+        ids.push(parent.clone());
+    }
+    ids
+}
+
+/// Returns true when setting `parent_id` as the parent of `folder_id` would
+/// create a cycle in the folder hierarchy.
+pub fn creates_cycle(folders: &[Folder], folder_id: &str, parent_id: &str) -> bool {
+    folder_with_descendants(folders, folder_id)
+        .iter()
+        .any(|id| id == parent_id)
+}
+
 pub fn datetime_now() -> DateTime<FixedOffset> {
     Utc::now().with_timezone(&FixedOffset::east_opt(0).expect(
         "BUG", // This can't possibly fail. Can it?
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(id: &str, parent: Option<&str>) -> Folder {
+        Folder {
+            folder_id: id.to_string(),
+            name: id.to_string(),
+            description: "".to_string(),
+            parent_id: parent.map(|p| p.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_folder_hierarchy() {
+        let folders = vec![
+            folder("a", None),
+            folder("b", Some("a")),
+            folder("c", Some("b")),
+            folder("d", None),
+        ];
+        assert_eq!(folder_with_descendants(&folders, "a"), vec!["a", "b", "c"]);
+        assert_eq!(folder_with_descendants(&folders, "d"), vec!["d"]);
+        assert_eq!(folder_with_ancestors(&folders, "c"), vec!["c", "b", "a"]);
+        assert_eq!(folder_with_ancestors(&folders, "d"), vec!["d"]);
+        assert!(creates_cycle(&folders, "a", "c"));
+        assert!(creates_cycle(&folders, "a", "a"));
+        assert!(!creates_cycle(&folders, "c", "d"));
+    }
+
+    #[test]
+    fn test_dashboard_matches() {
+        let dash: v3::Dashboard = config::utils::json::from_str(
+            r#"{"version":3,"title":"Kubernetes Pods","description":"cpu and memory","tags":["k8s","Prod"]}"#,
+        )
+        .unwrap();
+        let dash = Dashboard {
+            v3: Some(dash),
+            version: 3,
+            ..Default::default()
+        };
+        let filter = |tag: Option<&str>, query: Option<&str>| DashboardFilter {
+            tag: tag.map(|t| t.to_string()),
+            query: query.map(|q| q.to_string()),
+        };
+        assert!(dash.matches(&DashboardFilter::default()));
+        assert!(dash.matches(&filter(Some("prod"), None)));
+        assert!(!dash.matches(&filter(Some("dev"), None)));
+        assert!(dash.matches(&filter(None, Some("pods"))));
+        assert!(dash.matches(&filter(None, Some("MEMORY"))));
+        assert!(dash.matches(&filter(Some("k8s"), Some("k8s"))));
+        assert!(!dash.matches(&filter(Some("k8s"), Some("nginx"))));
+    }
+}
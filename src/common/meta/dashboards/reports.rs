@@ -96,6 +96,12 @@ pub enum ReportFrequencyType {
     Months,
     #[serde(rename = "cron")]
     Cron,
+    /// Every day from Monday to Friday
+    #[serde(rename = "business_days")]
+    BusinessDays,
+    /// The first day of the month, every `interval` months
+    #[serde(rename = "first_of_month")]
+    FirstOfMonth,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
@@ -152,6 +158,7 @@ pub struct Report {
     /// User password for chromedriver login
     #[serde(default)]
     pub password: String,
+    /// IANA timezone of the schedule, `tz_offset` is used when empty
     #[serde(default)]
     pub timezone: String,
     /// Fixed timezone offset in minutes
@@ -0,0 +1,132 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use config::utils::json::Value;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::variables::ResolvedVariable;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct CreateSnapshotRequest {
+    /// time range of the snapshot, in microseconds
+    pub start_time: i64,
+    pub end_time: i64,
+    /// selected values of the variables, the default values of the dashboard
+    /// are used for the others
+    #[serde(default)]
+    pub values: HashMap<String, Vec<String>>,
+    /// lifetime of the snapshot in seconds, defaults to
+    /// `ZO_DASHBOARD_SNAPSHOT_TTL`
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+}
+
+/// Result of a query of a panel when the snapshot was taken.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotQueryResult {
+    /// query with the variables expanded
+    pub query: String,
+    /// search response for the sql queries, PromQL result for the others
+    #[schema(value_type = Object)]
+    pub data: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A dashboard frozen with the results of its queries, shared with the
+/// `snapshot_id` which is the secret of the public link.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Snapshot {
+    pub snapshot_id: String,
+    pub dashboard_id: String,
+    pub folder_id: String,
+    pub title: String,
+    /// the v3 dashboard
+    #[schema(value_type = Object)]
+    pub dashboard: Value,
+    pub variables: Vec<ResolvedVariable>,
+    /// results of the queries, keyed by panel id
+    pub results: HashMap<String, Vec<SnapshotQueryResult>>,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub created_by: String,
+    /// in microseconds
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+impl Snapshot {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at <= now
+    }
+
+    pub fn info(&self) -> SnapshotInfo {
+        SnapshotInfo {
+            snapshot_id: self.snapshot_id.clone(),
+            dashboard_id: self.dashboard_id.clone(),
+            title: self.title.clone(),
+            created_by: self.created_by.clone(),
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// Snapshot without its content, for the listings.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotInfo {
+    pub snapshot_id: String,
+    pub dashboard_id: String,
+    pub title: String,
+    pub created_by: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotList {
+    pub list: Vec<SnapshotInfo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_expiry() {
+        let snapshot = Snapshot {
+            snapshot_id: "abc".to_string(),
+            dashboard_id: "d1".to_string(),
+            folder_id: "default".to_string(),
+            title: "Overview".to_string(),
+            dashboard: Value::Null,
+            variables: vec![],
+            results: HashMap::new(),
+            start_time: 0,
+            end_time: 10,
+            created_by: "root@example.com".to_string(),
+            created_at: 100,
+            expires_at: 200,
+        };
+        assert!(!snapshot.is_expired(199));
+        assert!(snapshot.is_expired(200));
+        let info = snapshot.info();
+        assert_eq!(info.snapshot_id, "abc");
+        assert_eq!(info.expires_at, 200);
+    }
+}
@@ -28,6 +28,8 @@ pub struct Dashboard {
     pub dashboard_id: String,
     pub title: String,
     pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     #[serde(default)]
     pub role: String,
     #[serde(default)]
@@ -263,6 +265,16 @@ pub struct DateTimeOptions {
     pub end_time: Option<i64>,
 }
 
+/// Variable taking the values of a field of a stream, see `query_data`
+pub const VARIABLE_QUERY_VALUES: &str = "query_values";
+/// Variable taking one of the values of `options`
+pub const VARIABLE_CUSTOM: &str = "custom";
+/// Variable taking one of the time intervals of `options`, such as `5m`, or
+/// `auto` to follow the time range of the dashboard
+pub const VARIABLE_INTERVAL: &str = "interval";
+pub const VARIABLE_CONSTANT: &str = "constant";
+pub const VARIABLE_TEXTBOX: &str = "textbox";
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VariableList {
@@ -278,6 +290,12 @@ pub struct VariableList {
     pub multi_select: Option<bool>,
 }
 
+impl VariableList {
+    pub fn is_multi_select(&self) -> bool {
+        self.multi_select.unwrap_or_default()
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct QueryData {
     pub stream_type: StreamType,
@@ -0,0 +1,48 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ResolveVariablesRequest {
+    /// time range of the dashboard, in microseconds
+    pub start_time: i64,
+    pub end_time: i64,
+    /// selected values of the variables, the default values of the dashboard
+    /// are used for the others
+    #[serde(default)]
+    pub values: HashMap<String, Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ResolvedVariable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    /// values the variable can take
+    pub options: Vec<String>,
+    /// values the variable took
+    pub value: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ResolveVariablesResponse {
+    pub variables: Vec<ResolvedVariable>,
+    /// queries of the panels with the variables expanded, keyed by panel id
+    pub panels: HashMap<String, Vec<String>>,
+}
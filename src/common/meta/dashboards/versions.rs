@@ -0,0 +1,196 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json::{self, Value};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A saved state of a dashboard, a new version is recorded on every save.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SavedVersion {
+    pub version_id: i64,
+    pub dashboard_id: String,
+    pub title: String,
+    #[serde(default)]
+    pub created_by: String,
+    pub created_at: i64,
+    /// the dashboard as it was saved
+    #[schema(value_type = Object)]
+    pub dashboard: Value,
+}
+
+impl SavedVersion {
+    pub fn info(&self) -> SavedVersionInfo {
+        SavedVersionInfo {
+            version_id: self.version_id,
+            title: self.title.clone(),
+            created_by: self.created_by.clone(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SavedVersionInfo {
+    pub version_id: i64,
+    pub title: String,
+    pub created_by: String,
+    pub created_at: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SavedVersionList {
+    /// newest first
+    pub list: Vec<SavedVersionInfo>,
+}
+
+/// One operation of a JSON patch (RFC 6902)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PatchOperation {
+    pub op: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub value: Option<Value>,
+}
+
+/// JSON patch turning the `from` version into the `to` version
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct VersionDiff {
+    pub from: i64,
+    pub to: i64,
+    pub patch: Vec<PatchOperation>,
+}
+
+/// Returns the JSON patch turning `from` into `to`. The arrays are compared
+/// index by index, moved elements are reported as replaced.
+pub fn diff(from: &Value, to: &Value) -> Vec<PatchOperation> {
+    let mut patch = vec![];
+    diff_at("", from, to, &mut patch);
+    patch
+}
+
+fn diff_at(path: &str, from: &Value, to: &Value, patch: &mut Vec<PatchOperation>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            for (key, value) in from {
+                let path = format!("{path}/{}", escape(key));
+                match to.get(key) {
+                    Some(to) => diff_at(&path, value, to, patch),
+                    None => patch.push(operation("remove", path, None)),
+                }
+            }
+            for (key, value) in to {
+                if !from.contains_key(key) {
+                    let path = format!("{path}/{}", escape(key));
+                    patch.push(operation("add", path, Some(value)));
+                }
+            }
+        }
+        (Value::Array(from), Value::Array(to)) => {
+            let common = from.len().min(to.len());
+            for i in 0..common {
+                diff_at(&format!("{path}/{i}"), &from[i], &to[i], patch);
+            }
+            // removed from the end, the indexes of the remaining elements stay the same
+            for i in (common..from.len()).rev() {
+                patch.push(operation("remove", format!("{path}/{i}"), None));
+            }
+            for (i, value) in to.iter().enumerate().skip(common) {
+                patch.push(operation("add", format!("{path}/{i}"), Some(value)));
+            }
+        }
+        _ if from != to => patch.push(operation("replace", path.to_string(), Some(to))),
+        _ => {}
+    }
+}
+
+fn operation(op: &str, path: String, value: Option<&Value>) -> PatchOperation {
+    PatchOperation {
+        op: op.to_string(),
+        path,
+        value: value.cloned(),
+    }
+}
+
+// JSON pointer escaping (RFC 6901)
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Returns the dashboard as the JSON document of its own version.
+pub fn to_value(dashboard: &super::Dashboard) -> Value {
+    match (&dashboard.v1, &dashboard.v2, &dashboard.v3) {
+        (Some(d), ..) => json::to_value(d).unwrap(),
+        (_, Some(d), _) => json::to_value(d).unwrap(),
+        (.., Some(d)) => json::to_value(d).unwrap(),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json::json;
+
+    use super::*;
+
+    fn ops(patch: &[PatchOperation]) -> Vec<(&str, &str)> {
+        patch
+            .iter()
+            .map(|op| (op.op.as_str(), op.path.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let from = json!({
+            "title": "old",
+            "a/b": 1,
+            "tabs": [{"panels": [1, 2, 3]}],
+            "removed": true,
+        });
+        let to = json!({
+            "title": "new",
+            "a/b": 1,
+            "tabs": [{"panels": [1, 4]}, {"panels": []}],
+            "added": "x",
+        });
+        let patch = diff(&from, &to);
+        let mut got = ops(&patch);
+        got.sort();
+        assert_eq!(
+            got,
+            vec![
+                ("add", "/added"),
+                ("add", "/tabs/1"),
+                ("remove", "/removed"),
+                ("remove", "/tabs/0/panels/2"),
+                ("replace", "/tabs/0/panels/1"),
+                ("replace", "/title"),
+            ]
+        );
+        let title = patch.iter().find(|op| op.path == "/title").unwrap();
+        assert_eq!(title.value, Some(json!("new")));
+        assert!(diff(&from, &from).is_empty());
+    }
+
+    #[test]
+    fn test_diff_array_removals_from_the_end() {
+        let patch = diff(&json!([1, 2, 3]), &json!([1]));
+        assert_eq!(ops(&patch), vec![("remove", "/2"), ("remove", "/1")]);
+        let patch = diff(&json!({"a~b/c": 1}), &json!({"a~b/c": 2}));
+        assert_eq!(ops(&patch), vec![("replace", "/a~0b~1c")]);
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::meta::stream::StreamType;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A SQL query materialized into a stream. The query runs once on every window
+/// of `frequency` seconds of the source stream, and its rows are ingested into
+/// the `destination` logs stream. A window is written at least once, a run
+/// retried after a crash can write its rows again.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct DerivedStream {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// SQL query run on each window, the time range is set by the window
+    pub query: String,
+    /// type of the stream the query reads
+    #[serde(default)]
+    pub stream_type: StreamType,
+    /// logs stream the rows are ingested into
+    pub destination: String,
+    /// length of the windows, in seconds
+    pub frequency: i64,
+    /// seconds a window is held back after it closed, for the late records
+    #[serde(default)]
+    pub delay: i64,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// user who saved the derived stream last, the query runs as this user
+    #[serde(default)]
+    pub owner: String,
+    /// end of the last materialized window, in microseconds
+    #[serde(default)]
+    pub watermark: i64,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl DerivedStream {
+    pub fn period(&self) -> i64 {
+        self.frequency * 1_000_000
+    }
+
+    /// The next window, `[start, end)` in microseconds, once it can be
+    /// materialized at `now`.
+    pub fn next_window(&self, now: i64) -> Option<(i64, i64)> {
+        let start = self.watermark;
+        let end = start + self.period();
+        if end + self.delay * 1_000_000 <= now {
+            Some((start, end))
+        } else {
+            None
+        }
+    }
+
+    /// When the next window can be materialized, in microseconds.
+    pub fn next_run_at(&self) -> i64 {
+        self.watermark + self.period() + self.delay * 1_000_000
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct DerivedStreamList {
+    pub list: Vec<DerivedStream>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_window() {
+        let ds = DerivedStream {
+            frequency: 60,
+            delay: 30,
+            watermark: 120_000_000,
+            ..Default::default()
+        };
+        assert_eq!(ds.next_run_at(), 210_000_000);
+        assert_eq!(ds.next_window(209_999_999), None);
+        assert_eq!(
+            ds.next_window(210_000_000),
+            Some((120_000_000, 180_000_000))
+        );
+    }
+}
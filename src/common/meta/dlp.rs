@@ -0,0 +1,239 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::rbac::wildcard_match;
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+const CREDIT_CARD_PATTERN: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+const IPV4_PATTERN: &str = r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b";
+const SSN_PATTERN: &str = r"\b\d{3}-\d{2}-\d{4}\b";
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DlpDetector {
+    #[default]
+    Email,
+    /// 13 to 19 digit numbers passing the Luhn check, spaces and dashes allowed
+    CreditCard,
+    Ipv4,
+    /// US social security numbers, `123-45-6789`
+    Ssn,
+    /// the `pattern` of the rule
+    Regex,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DlpAction {
+    /// Replaces the match with the `replacement` of the rule
+    #[default]
+    Redact,
+    /// Replaces the match with its sha256, values can still be correlated
+    Hash,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct DlpRule {
+    pub name: String,
+    #[serde(default)]
+    pub detector: DlpDetector,
+    /// regular expression of the `regex` detector
+    #[serde(default)]
+    pub pattern: String,
+    /// fields scanned by the rule, supports `*` wildcards, all the string
+    /// fields are scanned when empty
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub action: DlpAction,
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+    #[serde(skip)]
+    regex: Option<Regex>,
+}
+
+fn default_replacement() -> String {
+    "[REDACTED]".to_string()
+}
+
+/// The rules applied to the records of a logs stream when they are ingested.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamDlp {
+    #[serde(default)]
+    pub rules: Vec<DlpRule>,
+}
+
+impl StreamDlp {
+    /// Compiles the patterns of the rules, must be called before `apply`.
+    pub fn compile(&mut self) -> Result<(), anyhow::Error> {
+        for rule in self.rules.iter_mut() {
+            let pattern = match rule.detector {
+                DlpDetector::Email => EMAIL_PATTERN,
+                DlpDetector::CreditCard => CREDIT_CARD_PATTERN,
+                DlpDetector::Ipv4 => IPV4_PATTERN,
+                DlpDetector::Ssn => SSN_PATTERN,
+                DlpDetector::Regex => rule.pattern.as_str(),
+            };
+            if pattern.is_empty() {
+                return Err(anyhow::anyhow!("rule {} requires a pattern", rule.name));
+            }
+            rule.regex = Some(
+                Regex::new(pattern)
+                    .map_err(|e| anyhow::anyhow!("invalid pattern of rule {}: {e}", rule.name))?,
+            );
+        }
+        Ok(())
+    }
+
+    /// Redacts the matches of the rules in the record, returns the number of
+    /// redactions of every rule.
+    pub fn apply(&self, record: &mut json::Map<String, json::Value>) -> Vec<(&str, u64)> {
+        let mut counts = Vec::new();
+        for rule in self.rules.iter() {
+            let mut count = 0;
+            for (key, value) in record.iter_mut() {
+                if !rule.fields.is_empty() && !rule.fields.iter().any(|f| wildcard_match(f, key)) {
+                    continue;
+                }
+                if let json::Value::String(v) = value {
+                    if let Some(redacted) = rule.redact(v, &mut count) {
+                        *v = redacted;
+                    }
+                }
+            }
+            if count > 0 {
+                counts.push((rule.name.as_str(), count));
+            }
+        }
+        counts
+    }
+}
+
+impl DlpRule {
+    fn redact(&self, value: &str, count: &mut u64) -> Option<String> {
+        let regex = self.regex.as_ref()?;
+        if !regex.is_match(value) {
+            return None;
+        }
+        let mut changed = false;
+        let redacted = regex.replace_all(value, |caps: &regex::Captures| {
+            let m = &caps[0];
+            if self.detector == DlpDetector::CreditCard && !luhn_check(m) {
+                return m.to_string();
+            }
+            changed = true;
+            *count += 1;
+            match self.action {
+                DlpAction::Redact => self.replacement.clone(),
+                DlpAction::Hash => sha256::digest(m),
+            }
+        });
+        changed.then(|| redacted.into_owned())
+    }
+}
+
+fn luhn_check(value: &str) -> bool {
+    let digits = value
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .collect::<Vec<_>>();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let d = d * 2;
+                if d > 9 { d - 9 } else { d }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, detector: DlpDetector, action: DlpAction) -> DlpRule {
+        DlpRule {
+            name: name.to_string(),
+            detector,
+            action,
+            replacement: default_replacement(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_luhn_check() {
+        assert!(luhn_check("4111 1111 1111 1111"));
+        assert!(luhn_check("5500-0000-0000-0004"));
+        assert!(!luhn_check("4111 1111 1111 1112"));
+        assert!(!luhn_check("1234"));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut dlp = StreamDlp {
+            rules: vec![
+                rule("email", DlpDetector::Email, DlpAction::Redact),
+                rule("card", DlpDetector::CreditCard, DlpAction::Redact),
+                DlpRule {
+                    fields: vec!["client_*".to_string()],
+                    ..rule("ip", DlpDetector::Ipv4, DlpAction::Hash)
+                },
+            ],
+        };
+        dlp.compile().unwrap();
+        let mut record = json::json!({
+            "log": "user john@example.com paid with 4111 1111 1111 1111, order 1234567890123",
+            "client_ip": "10.0.0.1",
+            "server_ip": "10.0.0.2",
+            "code": 200,
+        });
+        let counts = dlp.apply(record.as_object_mut().unwrap());
+        assert_eq!(counts, vec![("email", 1), ("card", 1), ("ip", 1)]);
+        assert_eq!(
+            record["log"],
+            "user [REDACTED] paid with [REDACTED], order 1234567890123"
+        );
+        assert_eq!(record["client_ip"], sha256::digest("10.0.0.1"));
+        assert_eq!(record["server_ip"], "10.0.0.2");
+        assert_eq!(record["code"], 200);
+    }
+
+    #[test]
+    fn test_compile() {
+        let mut dlp = StreamDlp {
+            rules: vec![rule("custom", DlpDetector::Regex, DlpAction::Redact)],
+        };
+        assert!(dlp.compile().is_err());
+        dlp.rules[0].pattern = "(".to_string();
+        assert!(dlp.compile().is_err());
+        dlp.rules[0].pattern = r"token=\w+".to_string();
+        assert!(dlp.compile().is_ok());
+    }
+}
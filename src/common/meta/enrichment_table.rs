@@ -0,0 +1,98 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A remote location an enrichment table is periodically reloaded from.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct EnrichmentTableSource {
+    /// `http(s)://` url or an object path in the configured bucket
+    pub url: String,
+    /// refresh interval, in seconds
+    #[serde(default = "default_interval")]
+    pub interval: i64,
+    /// ETag returned by the last http fetch
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// hash of the last loaded content
+    #[serde(default)]
+    pub hash: String,
+    /// last time the source was checked, in microseconds
+    #[serde(default)]
+    pub last_checked_at: i64,
+    /// last time the table was replaced, in microseconds
+    #[serde(default)]
+    pub last_refreshed_at: i64,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+fn default_interval() -> i64 {
+    3600
+}
+
+impl EnrichmentTableSource {
+    pub fn is_http(&self) -> bool {
+        self.url.starts_with("http://") || self.url.starts_with("https://")
+    }
+
+    pub fn is_due(&self, now: i64) -> bool {
+        self.last_checked_at + self.interval * 1_000_000 <= now
+    }
+}
+
+/// How the columns of an enrichment table are matched by the lookup functions,
+/// the columns not listed here are matched exactly.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct EnrichmentTableLookup {
+    #[serde(default)]
+    pub columns: HashMap<String, LookupMode>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LookupMode {
+    #[default]
+    Exact,
+    /// column holds networks (`10.0.0.0/8`) and the longest prefix containing
+    /// the ip wins
+    Cidr,
+    /// column holds inclusive numeric ranges (`1024-2048`) and the narrowest
+    /// range containing the number wins
+    Range,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enrichment_table_source_is_due() {
+        let source = EnrichmentTableSource {
+            url: "https://example.com/cmdb.csv".to_string(),
+            interval: 60,
+            last_checked_at: 1_000_000,
+            ..Default::default()
+        };
+        assert!(source.is_http());
+        assert!(!source.is_due(30_000_000));
+        assert!(source.is_due(61_000_000));
+    }
+}
@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use config::meta::stream::StreamType;
+use config::{meta::stream::StreamType, utils::json};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use vrl::{
@@ -114,6 +114,63 @@ pub struct StreamFunctionsList {
     pub list: Vec<StreamTransform>,
 }
 
+/// Execution stats of a function, summed over the nodes running it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FunctionStats {
+    pub invocations: u64,
+    pub errors: u64,
+    /// total execution time, in seconds
+    pub total_time: f64,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// in microseconds
+    #[serde(default)]
+    pub last_error_at: i64,
+}
+
+impl FunctionStats {
+    pub fn merge(&mut self, other: &FunctionStats) {
+        self.invocations += other.invocations;
+        self.errors += other.errors;
+        self.total_time += other.total_time;
+        if other.last_error_at > self.last_error_at {
+            self.last_error = other.last_error.clone();
+            self.last_error_at = other.last_error_at;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FunctionPreviewRequest {
+    /// function body to preview instead of the saved one
+    #[serde(default)]
+    pub function: Option<String>,
+    /// number of recent records to apply the function to
+    #[serde(default)]
+    pub size: Option<i64>,
+    /// how far back to look for records, in seconds
+    #[serde(default)]
+    pub lookback: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionPreviewResult {
+    #[schema(value_type = Object)]
+    pub before: json::Value,
+    /// the record as it would be ingested, null when the function drops it
+    #[schema(value_type = Object)]
+    pub after: json::Value,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FunctionPreviewResponse {
+    pub results: Vec<FunctionPreviewResult>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VRLConfig {
     pub runtime: VrlRuntime,
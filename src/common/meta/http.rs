@@ -26,6 +26,8 @@ use utoipa::ToSchema;
 /// code 503 is service unavailable
 /// code >= 1000 is custom error code
 /// message is the message or error message
+/// error is set on the error responses, clients should branch on it rather
+/// than on the message
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct HttpResponse {
     pub code: u16,
@@ -34,6 +36,46 @@ pub struct HttpResponse {
     pub error_detail: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorInfo>,
+}
+
+/// Machine readable description of an error
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorInfo {
+    /// stable name of the error, e.g. `search_stream_not_found`
+    pub code: String,
+    /// one of validation, unauthenticated, permission_denied, not_found,
+    /// conflict, rate_limited, cancelled, timeout, unavailable, internal
+    #[schema(value_type = String)]
+    pub category: errors::ErrorCategory,
+    /// whether sending the same request again may succeed
+    pub retryable: bool,
+    /// id of the request, also in the `x-request-id` header and the log lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ErrorInfo {
+    pub fn from_status(status: u16) -> Self {
+        let category = errors::ErrorCategory::from_status(status);
+        ErrorInfo {
+            code: errors::ErrorCategory::status_name(status).to_string(),
+            category,
+            retryable: category.is_retryable(),
+            request_id: config::meta::logger::get_request_id(),
+        }
+    }
+
+    pub fn from_error_code(err: &errors::ErrorCodes) -> Self {
+        let category = err.get_category();
+        ErrorInfo {
+            code: err.get_name().to_string(),
+            category,
+            retryable: category.is_retryable(),
+            request_id: config::meta::logger::get_request_id(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -50,6 +92,7 @@ impl HttpResponse {
             message,
             error_detail: None,
             trace_id: None,
+            error: None,
         }
     }
 
@@ -59,6 +102,7 @@ impl HttpResponse {
             message: error,
             error_detail: None,
             trace_id: None,
+            error: Some(ErrorInfo::from_status(code)),
         }
     }
 
@@ -68,6 +112,7 @@ impl HttpResponse {
             message: err.get_message(),
             error_detail: Some(err.get_error_detail()),
             trace_id: None,
+            error: Some(ErrorInfo::from_error_code(&err)),
         }
     }
 
@@ -77,6 +122,7 @@ impl HttpResponse {
             message: err.get_message(),
             error_detail: Some(err.get_error_detail()),
             trace_id,
+            error: Some(ErrorInfo::from_error_code(&err)),
         }
     }
 
@@ -102,6 +148,24 @@ impl HttpResponse {
             .json(Self::error(StatusCode::FORBIDDEN.into(), error.to_string()))
     }
 
+    /// Send an Unauthorized response in json format and associate the
+    /// provided error as `error` field.
+    pub fn unauthorized(error: impl ToString) -> ActixHttpResponse {
+        ActixHttpResponse::Unauthorized().json(Self::error(
+            StatusCode::UNAUTHORIZED.into(),
+            error.to_string(),
+        ))
+    }
+
+    /// Send a TooManyRequests response in json format and associate the
+    /// provided error as `error` field.
+    pub fn too_many_requests(error: impl ToString) -> ActixHttpResponse {
+        ActixHttpResponse::TooManyRequests().json(Self::error(
+            StatusCode::TOO_MANY_REQUESTS.into(),
+            error.to_string(),
+        ))
+    }
+
     /// Send a NotFound response in json format and associate the
     /// provided error as `error` field.
     pub fn not_found(error: impl ToString) -> ActixHttpResponse {
@@ -137,6 +201,7 @@ mod tests {
         let err = HttpResponse::message(http::StatusCode::OK.into(), msg.to_string());
         assert_eq!(err.code, http::StatusCode::OK);
         assert_eq!(err.message, msg);
+        assert!(err.error.is_none());
 
         let err = HttpResponse::error(
             http::StatusCode::INTERNAL_SERVER_ERROR.into(),
@@ -144,11 +209,22 @@ mod tests {
         );
         assert_eq!(err.code, http::StatusCode::INTERNAL_SERVER_ERROR);
         assert_eq!(err.message, msg);
+        let info = err.error.unwrap();
+        assert_eq!(info.code, "internal_error");
+        assert_eq!(info.category, errors::ErrorCategory::Internal);
+        assert!(!info.retryable);
 
         let errcode = errors::ErrorCodes::ServerInternalError(msg.to_string());
         let err =
             HttpResponse::error_code(errors::ErrorCodes::ServerInternalError(msg.to_string()));
         assert_eq!(err.code, errcode.get_code());
         assert_eq!(err.message, errcode.get_message());
+        assert_eq!(err.error.unwrap().code, "server_internal_error");
+
+        let err = HttpResponse::error(http::StatusCode::TOO_MANY_REQUESTS.into(), msg.to_string());
+        let body = serde_json::to_value(&err).unwrap();
+        assert_eq!(body["error"]["code"], "too_many_requests");
+        assert_eq!(body["error"]["category"], "rate_limited");
+        assert_eq!(body["error"]["retryable"], true);
     }
 }
@@ -18,7 +18,7 @@ use std::{
     io::{BufReader, Lines},
 };
 
-use actix_web::web;
+use actix_web::{http, web};
 use config::utils::json;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -61,14 +61,30 @@ pub struct IngestionResponse {
     pub status: Vec<StreamStatus>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// The data is kept in the wal of the ingester until the object store and
+    /// the meta store answer again, the code is 202 then.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
 }
 
 impl IngestionResponse {
     pub fn new(code: u16, status: Vec<StreamStatus>) -> Self {
+        let degraded = code == 200 && config::cluster::is_degraded();
         IngestionResponse {
-            code,
+            code: if degraded { 202 } else { code },
             status,
             error: None,
+            degraded,
+        }
+    }
+
+    /// The request was not accepted, the client should retry it.
+    pub fn unavailable(error: impl ToString) -> Self {
+        IngestionResponse {
+            code: http::StatusCode::SERVICE_UNAVAILABLE.into(),
+            status: vec![],
+            error: Some(error.to_string()),
+            degraded: false,
         }
     }
 }
@@ -81,10 +97,11 @@ pub struct StreamSchemaChk {
     pub has_metadata: bool,
 }
 
-pub const INGESTION_EP: [&str; 14] = [
+pub const INGESTION_EP: [&str; 15] = [
     "_bulk",
     "_json",
     "_multi",
+    "_raw",
     "traces",
     "write",
     "_kinesis_firehose",
@@ -277,14 +294,14 @@ pub enum AWSRecordType {
     KinesisFHMetrics(KinesisFHMetricData),
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GCPIngestionRequest {
     pub message: GCPMessage,
     pub subscription: String,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GCPMessage {
     pub attributes: GCPAttributes,
@@ -297,7 +314,7 @@ pub struct GCPMessage {
     pub publish_time_dup: String,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GCPAttributes {
     #[serde(rename = "logging.googleapis.com/timestamp")]
@@ -316,8 +333,13 @@ pub struct GCPIngestionResponse {
 pub enum IngestionRequest<'a> {
     JSON(&'a web::Bytes),
     Multi(&'a web::Bytes),
+    /// plain text, a record per line
+    RAW(&'a web::Bytes),
     KinesisFH(&'a KinesisFHRequest),
     GCP(&'a GCPIngestionRequest),
+    /// historical records of a backfill job, the age limit, the timestamp
+    /// skew policy and the real time alerts don't apply to them
+    Backfill(&'a Vec<json::Value>),
 }
 
 pub enum IngestionData<'a> {
@@ -359,3 +381,57 @@ pub enum IngestionDataIter<'a> {
         Option<KinesisFHIngestionResponse>,
     ),
 }
+
+/// Progress of the ingestion of an object of the landing bucket, a
+/// notification delivered again resumes it or is skipped.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BucketIngestCheckpoint {
+    pub bucket: String,
+    pub key: String,
+    pub etag: String,
+    /// records of the object ingested so far
+    pub records: usize,
+    pub done: bool,
+    /// microseconds
+    pub updated_at: i64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BackfillStatus {
+    #[default]
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// A batch of historical records ingested in the background, at a limited
+/// rate so that the live ingestion goes first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BackfillJob {
+    pub id: String,
+    pub org_id: String,
+    pub stream_name: String,
+    /// instance name of the node running the job
+    #[serde(default)]
+    pub node: String,
+    pub status: BackfillStatus,
+    pub total: usize,
+    /// records ingested so far, successful or failed
+    pub processed: usize,
+    pub successful: usize,
+    pub failed: usize,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// microseconds
+    pub created_at: i64,
+    /// microseconds
+    pub updated_at: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct ListBackfillJobs {
+    pub list: Vec<BackfillJob>,
+}
@@ -0,0 +1,206 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{meta::stream::StreamType, utils::json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::rbac::{wildcard_match, ROLE_ADMIN};
+
+/// Value of the values endpoint hits, see `_values` search handlers.
+const VALUES_KEY: &str = "zo_sql_key";
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskingAction {
+    /// Replaces the value with its sha256, values can still be correlated
+    #[default]
+    Hash,
+    /// Keeps the last `visible_chars` characters and masks the others with `*`
+    Partial,
+    /// Removes the field from the results
+    Drop,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct MaskingRule {
+    /// field name, supports `*` wildcards
+    pub field: String,
+    #[serde(default)]
+    pub action: MaskingAction,
+    #[serde(default = "default_visible_chars")]
+    pub visible_chars: usize,
+}
+
+fn default_visible_chars() -> usize {
+    4
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct MaskingPolicy {
+    pub name: String,
+    #[serde(default)]
+    pub stream_type: StreamType,
+    /// stream name, supports `*` wildcards
+    pub stream_name: String,
+    pub rules: Vec<MaskingRule>,
+    /// roles that see the original values, the root user is always exempt
+    #[serde(default = "default_exempt_roles")]
+    pub exempt_roles: Vec<String>,
+}
+
+fn default_exempt_roles() -> Vec<String> {
+    vec![ROLE_ADMIN.to_string()]
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct MaskingPolicyList {
+    pub list: Vec<MaskingPolicy>,
+}
+
+impl MaskingPolicy {
+    pub fn is_match(&self, stream_type: StreamType, stream_name: &str) -> bool {
+        self.stream_type == stream_type && wildcard_match(&self.stream_name, stream_name)
+    }
+
+    pub fn is_exempt(&self, roles: &[String]) -> bool {
+        roles.iter().any(|role| self.exempt_roles.contains(role))
+    }
+}
+
+impl MaskingRule {
+    /// Masks the field of the record.
+    pub fn apply(&self, record: &mut json::Map<String, json::Value>) {
+        if self.action == MaskingAction::Drop {
+            record.retain(|key, _| !wildcard_match(&self.field, key));
+            return;
+        }
+        for (key, value) in record.iter_mut() {
+            if wildcard_match(&self.field, key) {
+                self.mask(value);
+            }
+        }
+    }
+
+    /// Masks the values of a `_values` hit, `{"field": .., "values": [...]}`.
+    pub fn apply_values(&self, hit: &mut json::Map<String, json::Value>) {
+        let is_match = hit
+            .get("field")
+            .and_then(|v| v.as_str())
+            .map(|field| wildcard_match(&self.field, field))
+            .unwrap_or_default();
+        if !is_match {
+            return;
+        }
+        let Some(values) = hit.get_mut("values").and_then(|v| v.as_array_mut()) else {
+            return;
+        };
+        if self.action == MaskingAction::Drop {
+            values.clear();
+            return;
+        }
+        for value in values.iter_mut() {
+            if let Some(value) = value.get_mut(VALUES_KEY) {
+                self.mask(value);
+            }
+        }
+    }
+
+    fn mask(&self, value: &mut json::Value) {
+        let raw = match value {
+            json::Value::Null => return,
+            json::Value::String(v) => v.clone(),
+            v => v.to_string(),
+        };
+        *value = json::Value::String(match self.action {
+            MaskingAction::Hash => sha256::digest(raw),
+            MaskingAction::Partial => {
+                let len = raw.chars().count();
+                let visible = self.visible_chars.min(len / 2);
+                raw.chars()
+                    .enumerate()
+                    .map(|(i, c)| if i < len - visible { '*' } else { c })
+                    .collect()
+            }
+            MaskingAction::Drop => return,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(field: &str, action: MaskingAction) -> MaskingRule {
+        MaskingRule {
+            field: field.to_string(),
+            action,
+            visible_chars: 4,
+        }
+    }
+
+    #[test]
+    fn test_apply_rule() {
+        let mut record = json::json!({
+            "email": "john@example.com",
+            "card_number": "4111111111111111",
+            "ssn": "123-45-6789",
+            "user_ip": "10.0.0.1",
+            "ip_country": "US",
+            "message": null,
+        });
+        let record = record.as_object_mut().unwrap();
+        rule("email", MaskingAction::Hash).apply(record);
+        rule("card_*", MaskingAction::Partial).apply(record);
+        rule("ssn", MaskingAction::Drop).apply(record);
+        rule("message", MaskingAction::Hash).apply(record);
+        assert_eq!(record["email"], sha256::digest("john@example.com"));
+        assert_eq!(record["card_number"], "************1111");
+        assert!(!record.contains_key("ssn"));
+        assert_eq!(record["user_ip"], "10.0.0.1");
+        assert_eq!(record["message"], json::Value::Null);
+
+        // short values don't reveal more than half of their characters
+        let mut record = json::json!({"pin": "1234"});
+        let record = record.as_object_mut().unwrap();
+        rule("pin", MaskingAction::Partial).apply(record);
+        assert_eq!(record["pin"], "**34");
+    }
+
+    #[test]
+    fn test_apply_values() {
+        let mut hit = json::json!({
+            "field": "email",
+            "values": [{"zo_sql_key": "john@example.com", "zo_sql_num": 3}],
+        });
+        let hit = hit.as_object_mut().unwrap();
+        rule("email", MaskingAction::Partial).apply_values(hit);
+        assert_eq!(hit["values"][0]["zo_sql_key"], "************.com");
+        assert_eq!(hit["values"][0]["zo_sql_num"], 3);
+        rule("email", MaskingAction::Drop).apply_values(hit);
+        assert_eq!(hit["values"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_policy_match() {
+        let policy: MaskingPolicy =
+            json::from_str(r#"{"name":"pii","stream_name":"app_*","rules":[]}"#).unwrap();
+        assert!(policy.is_match(StreamType::Logs, "app_logs"));
+        assert!(!policy.is_match(StreamType::Traces, "app_logs"));
+        assert!(!policy.is_match(StreamType::Logs, "k8s_logs"));
+        assert!(policy.is_exempt(&["admin".to_string()]));
+        assert!(!policy.is_exempt(&["viewer".to_string()]));
+    }
+}
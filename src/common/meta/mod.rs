@@ -14,19 +14,34 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod alerts;
+pub mod annotations;
+pub mod audit;
 pub mod authz;
+pub mod backup;
+pub mod bundles;
+pub mod caches;
 pub mod dashboards;
+pub mod derived_streams;
+pub mod dlp;
+pub mod enrichment_table;
 pub mod functions;
 pub mod http;
 pub mod ingestion;
+pub mod masking;
 pub mod maxmind;
 pub mod middleware_data;
 pub mod organization;
 pub mod pipelines;
 pub mod prom;
+pub mod provisioning;
 pub mod proxy;
+pub mod rbac;
+pub mod runtime_config;
 pub mod saved_view;
+pub mod scim;
+pub mod search_templates;
 pub mod service;
+pub mod service_account;
 pub mod stream;
 pub mod syslog;
 pub mod telemetry;
@@ -13,10 +13,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::net::IpAddr;
+
+use config::meta::usage::UsageRollup;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use super::{alerts::Alert, functions::Transform};
+use super::{alerts::Alert, functions::Transform, service_account::is_ip_allowed};
 
 pub const DEFAULT_ORG: &str = "default";
 pub const CUSTOM: &str = "custom";
@@ -97,6 +100,77 @@ pub struct RumIngestionResponse {
     pub data: RumIngestionToken,
 }
 
+/// Prefix of the ingestion keys, the key carries its id so that it can be
+/// looked up without scanning all keys of the org.
+pub const INGESTION_KEY_PREFIX: &str = "o2ik_";
+
+/// An additional ingestion key of a user, it is accepted in place of the
+/// passcode so that agents can move to a new key before the old one is
+/// revoked.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct IngestionKey {
+    pub id: String,
+    /// email of the user the key authenticates as
+    pub user: String,
+    #[serde(default)]
+    pub description: String,
+    /// sha256 of the key secret, the key itself is only returned on creation
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+    pub created_at: i64,
+    /// microseconds, `None` while the key is active
+    #[serde(default)]
+    pub revoked_at: Option<i64>,
+    /// microseconds, 0 when the key was never used
+    #[serde(default)]
+    pub last_used_at: i64,
+    /// allowed client ips or CIDR ranges, empty means any ip
+    #[serde(default)]
+    pub ip_allowlist: Vec<String>,
+}
+
+impl IngestionKey {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none()
+    }
+
+    pub fn is_ip_allowed(&self, ip: Option<IpAddr>) -> bool {
+        is_ip_allowed(&self.ip_allowlist, ip)
+    }
+
+    /// Returns a copy without the secret hash, for responses.
+    pub fn redacted(&self) -> Self {
+        Self {
+            hash: String::new(),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct IngestionKeyRequest {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub ip_allowlist: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct IngestionKeyResponse {
+    /// the ingestion key, it can't be retrieved again
+    pub key: String,
+    pub info: IngestionKey,
+}
+
+/// Splits an ingestion key into its id and secret.
+pub fn parse_ingestion_key(key: &str) -> Option<(&str, &str)> {
+    let (id, secret) = key.strip_prefix(INGESTION_KEY_PREFIX)?.split_once('_')?;
+    if id.is_empty() || secret.is_empty() {
+        return None;
+    }
+    Some((id, secret))
+}
+
 fn default_scrape_interval() -> u32 {
     config::get_config().common.default_scrape_interval
 }
@@ -107,12 +181,16 @@ pub struct OrganizationSetting {
     /// seconds).
     #[serde(default = "default_scrape_interval")]
     pub scrape_interval: u32,
+    /// Allows sharing dashboard snapshots with public links.
+    #[serde(default)]
+    pub enable_dashboard_snapshots: bool,
 }
 
 impl Default for OrganizationSetting {
     fn default() -> Self {
         Self {
             scrape_interval: default_scrape_interval(),
+            enable_dashboard_snapshots: false,
         }
     }
 }
@@ -121,3 +199,60 @@ impl Default for OrganizationSetting {
 pub struct OrganizationSettingResponse {
     pub data: OrganizationSetting,
 }
+
+/// Resource limits of an organization, a missing limit means unlimited.
+#[derive(Serialize, ToSchema, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OrgQuota {
+    #[serde(default)]
+    pub max_streams: Option<i64>,
+    /// compressed size of the data stored for the organization
+    #[serde(default)]
+    pub max_storage_bytes: Option<i64>,
+    #[serde(default)]
+    pub max_queries_per_minute: Option<i64>,
+    #[serde(default)]
+    pub max_alerts: Option<i64>,
+}
+
+/// Client ip allowlists of an organization, root users are not restricted.
+/// An empty list allows any ip.
+#[derive(Serialize, ToSchema, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OrgNetworkPolicy {
+    /// ips or CIDR ranges allowed to use the ingestion endpoints
+    #[serde(default)]
+    pub ingestion_allowlist: Vec<String>,
+    /// ips or CIDR ranges allowed to use every other endpoint of the org
+    #[serde(default)]
+    pub management_allowlist: Vec<String>,
+}
+
+impl OrgNetworkPolicy {
+    pub fn is_ip_allowed(&self, is_ingestion: bool, ip: Option<IpAddr>) -> bool {
+        if is_ingestion {
+            is_ip_allowed(&self.ingestion_allowlist, ip)
+        } else {
+            is_ip_allowed(&self.management_allowlist, ip)
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OrgQuotaUsage {
+    pub streams: i64,
+    pub storage_bytes: i64,
+    pub queries_per_minute: i64,
+    pub alerts: i64,
+}
+
+#[derive(Serialize, ToSchema, Deserialize, Debug, Clone)]
+pub struct OrgQuotaResponse {
+    pub quota: OrgQuota,
+    pub usage: OrgQuotaUsage,
+}
+
+#[derive(Serialize, ToSchema, Deserialize, Debug, Clone)]
+pub struct OrgUsageResponse {
+    /// usage of each hour with usage, oldest first
+    pub hours: Vec<UsageRollup>,
+    pub total: UsageRollup,
+}
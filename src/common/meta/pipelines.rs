@@ -35,10 +35,39 @@ pub struct PipeLine {
     pub stream_type: StreamType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub routing: Option<HashMap<String, Vec<RoutingCondition>>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<PipelineStep>,
+    #[serde(default)]
+    pub version: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<HashMap<String, Value>>,
 }
 
+/// A step of a pipeline, the steps apply in order to every record of the
+/// stream.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct PipelineStep {
+    #[serde(default)]
+    pub name: String,
+    /// all of them must match for the step to apply, no condition always matches
+    #[serde(default)]
+    pub conditions: Vec<RoutingCondition>,
+    /// name of the function applied to the record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    /// stream the record is routed to, the following steps are skipped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    /// organization of the destination stream, the organization of the
+    /// pipeline by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination_org: Option<String>,
+    /// sends a copy of the record to the destination and keeps on with the
+    /// following steps
+    #[serde(default, rename = "clone")]
+    pub clone_record: bool,
+}
+
 impl PipeLine {
     pub fn into_response(self, functions: Option<StreamFunctionsList>) -> PipeLineResponse {
         PipeLineResponse {
@@ -47,6 +76,8 @@ impl PipeLine {
             stream_name: self.stream_name,
             stream_type: self.stream_type,
             routing: self.routing,
+            steps: self.steps,
+            version: self.version,
             functions,
             meta: self.meta,
         }
@@ -64,6 +95,10 @@ pub struct PipeLineResponse {
     pub stream_type: StreamType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub routing: Option<HashMap<String, Vec<RoutingCondition>>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<PipelineStep>,
+    #[serde(default)]
+    pub version: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub functions: Option<StreamFunctionsList>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -74,3 +109,33 @@ pub struct PipeLineResponse {
 pub struct PipeLineList {
     pub list: Vec<PipeLineResponse>,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PipelineVersionList {
+    pub list: Vec<PipeLine>,
+}
+
+/// The records a pipeline is tried on, the stored pipeline applies when no
+/// pipeline is given.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PipelineTestRequest {
+    #[serde(default)]
+    pub pipeline: Option<PipeLine>,
+    #[schema(value_type = Vec<Object>)]
+    pub records: Vec<Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PipelineTestOutput {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    pub stream_name: String,
+    #[schema(value_type = Object)]
+    pub record: Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PipelineTestResponse {
+    /// outputs of every record, an empty list means the record was dropped
+    pub results: Vec<Vec<PipelineTestOutput>>,
+}
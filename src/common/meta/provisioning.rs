@@ -0,0 +1,180 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{
+    meta::stream::{StreamSettings, StreamType},
+    utils::json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{
+    alerts::{destinations::Destination, templates::Template, Alert},
+    bundles::{BundleDashboard, ImportResult},
+    dashboards::{versions::PatchOperation, Folder},
+    functions::Transform,
+};
+
+/// A resource definition of the provisioning source, the `kind` field tells
+/// its type.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Resource {
+    Folder(Folder),
+    Dashboard(BundleDashboard),
+    Template(Template),
+    Destination(Destination),
+    Alert(Alert),
+    Function(Transform),
+    Stream(ProvisionedStream),
+}
+
+impl Resource {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Resource::Folder(_) => "folder",
+            Resource::Dashboard(_) => "dashboard",
+            Resource::Template(_) => "template",
+            Resource::Destination(_) => "destination",
+            Resource::Alert(_) => "alert",
+            Resource::Function(_) => "function",
+            Resource::Stream(_) => "stream",
+        }
+    }
+}
+
+/// The settings of a stream, the stream is created by its first ingestion.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProvisionedStream {
+    pub name: String,
+    pub stream_type: StreamType,
+    pub settings: StreamSettings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftState {
+    /// defined but not created yet
+    Missing,
+    /// differs from its definition
+    Changed,
+    InSync,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DriftItem {
+    pub kind: String,
+    pub name: String,
+    pub state: DriftState,
+    /// JSON patch from the current resource to its definition
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<PatchOperation>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ProvisioningReport {
+    pub org_id: String,
+    /// in microseconds
+    pub started_at: i64,
+    pub dry_run: bool,
+    pub drift: Vec<DriftItem>,
+    /// the changes applied when it is not a dry run
+    pub results: Vec<ImportResult>,
+    /// definitions that couldn't be read
+    pub errors: Vec<String>,
+}
+
+/// Parses the resource definitions of a file, a file holds a resource or a
+/// list of resources, YAML files can hold several documents.
+pub fn parse(path: &str, data: &[u8]) -> Result<Vec<Resource>, anyhow::Error> {
+    let documents = if path.ends_with(".json") {
+        vec![json::from_slice::<json::Value>(data)?]
+    } else {
+        serde_yaml::Deserializer::from_slice(data)
+            .map(json::Value::deserialize)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let mut resources = vec![];
+    for document in documents {
+        match document {
+            json::Value::Array(list) => {
+                for value in list {
+                    resources.push(json::from_value(value)?);
+                }
+            }
+            json::Value::Null => {}
+            value => resources.push(json::from_value(value)?),
+        }
+    }
+    Ok(resources)
+}
+
+/// Returns the organization of a definition, the first directory of its path
+/// relative to the source.
+pub fn org_of(relative_path: &str) -> Option<&str> {
+    relative_path
+        .trim_start_matches('/')
+        .split_once('/')
+        .map(|(org, _)| org)
+        .filter(|org| !org.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_yaml_documents() {
+        let data = br#"
+kind: function
+name: parse_level
+function: ".level = downcase(.level)"
+params: row
+---
+- kind: folder
+  folderId: ops
+  name: Ops
+  description: ""
+- kind: stream
+  name: app
+  stream_type: logs
+  settings:
+    data_retention: 7
+"#;
+        let resources = parse("default/resources.yaml", data).unwrap();
+        let kinds = resources.iter().map(|r| r.kind()).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["function", "folder", "stream"]);
+        let Resource::Stream(stream) = &resources[2] else {
+            panic!("not a stream");
+        };
+        assert_eq!(stream.stream_type, StreamType::Logs);
+        assert_eq!(stream.settings.data_retention, 7);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let data = br#"{"kind": "dashboard", "folder_id": "default", "dashboard": {"version": 3, "dashboardId": "7", "title": "App"}}"#;
+        let resources = parse("default/app.json", data).unwrap();
+        assert!(matches!(&resources[0], Resource::Dashboard(d) if d.folder_id == "default"));
+        assert!(parse("default/bad.json", br#"{"kind": "unknown"}"#).is_err());
+    }
+
+    #[test]
+    fn test_org_of() {
+        assert_eq!(org_of("default/dashboards/app.yaml"), Some("default"));
+        assert_eq!(org_of("/prod/alerts.yml"), Some("prod"));
+        assert_eq!(org_of("resources.yaml"), None);
+    }
+}
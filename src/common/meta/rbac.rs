@@ -0,0 +1,315 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use config::meta::stream::StreamType;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const ROLE_ADMIN: &str = "admin";
+pub const ROLE_EDITOR: &str = "editor";
+pub const ROLE_VIEWER: &str = "viewer";
+pub const BUILT_IN_ROLES: [&str; 3] = [ROLE_ADMIN, ROLE_EDITOR, ROLE_VIEWER];
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    Write,
+    Delete,
+}
+
+impl Permission {
+    /// Maps a http method to the permission it requires.
+    pub fn from_method(method: &str) -> Self {
+        match method {
+            "GET" | "HEAD" | "LIST" => Permission::Read,
+            "DELETE" => Permission::Delete,
+            _ => Permission::Write,
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Permission::Read => write!(f, "read"),
+            Permission::Write => write!(f, "write"),
+            Permission::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "read" => Ok(Permission::Read),
+            "write" => Ok(Permission::Write),
+            "delete" => Ok(Permission::Delete),
+            _ => Err(format!("invalid permission: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    /// Matches every resource type
+    All,
+    Stream,
+    Alert,
+    Dashboard,
+    Function,
+    Setting,
+    Role,
+    ServiceAccount,
+    User,
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resource::All => write!(f, "all"),
+            Resource::Stream => write!(f, "stream"),
+            Resource::Alert => write!(f, "alert"),
+            Resource::Dashboard => write!(f, "dashboard"),
+            Resource::Function => write!(f, "function"),
+            Resource::Setting => write!(f, "setting"),
+            Resource::Role => write!(f, "role"),
+            Resource::ServiceAccount => write!(f, "service_account"),
+            Resource::User => write!(f, "user"),
+        }
+    }
+}
+
+impl FromStr for Resource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "all" => Ok(Resource::All),
+            "stream" | "streams" => Ok(Resource::Stream),
+            "alert" | "alerts" => Ok(Resource::Alert),
+            "dashboard" | "dashboards" => Ok(Resource::Dashboard),
+            "function" | "functions" => Ok(Resource::Function),
+            "setting" | "settings" => Ok(Resource::Setting),
+            "role" | "roles" => Ok(Resource::Role),
+            "service_account" | "service_accounts" => Ok(Resource::ServiceAccount),
+            "user" | "users" => Ok(Resource::User),
+            _ => Err(format!("invalid resource: {s}")),
+        }
+    }
+}
+
+/// A grant allows a set of permissions on the objects of a resource type whose
+/// name matches `pattern`, the pattern supports `*` as wildcard.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Grant {
+    pub resource: Resource,
+    #[serde(default = "default_pattern")]
+    pub pattern: String,
+    pub permissions: Vec<Permission>,
+}
+
+fn default_pattern() -> String {
+    "*".to_string()
+}
+
+impl Grant {
+    pub fn new(resource: Resource, pattern: &str, permissions: &[Permission]) -> Self {
+        Self {
+            resource,
+            pattern: pattern.to_string(),
+            permissions: permissions.to_vec(),
+        }
+    }
+
+    pub fn is_allowed(&self, resource: Resource, obj: &str, permission: Permission) -> bool {
+        (self.resource == Resource::All || self.resource == resource)
+            && self.permissions.contains(&permission)
+            && wildcard_match(&self.pattern, obj)
+    }
+}
+
+/// A condition AND-ed into the queries the users of a role run on the streams
+/// matching `stream_name`. `{{user.email}}` and `{{user.<attribute>}}` stand
+/// for the email and the attributes of the user, inside quoted strings only.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RowFilter {
+    #[serde(default)]
+    pub stream_type: StreamType,
+    /// stream name, supports `*` wildcards
+    pub stream_name: String,
+    /// SQL condition, e.g. `tenant_id = '{{user.tenant}}'`
+    pub filter: String,
+}
+
+impl RowFilter {
+    pub fn is_match(&self, stream_type: StreamType, stream_name: &str) -> bool {
+        self.stream_type == stream_type && wildcard_match(&self.stream_name, stream_name)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Role {
+    pub name: String,
+    #[serde(default)]
+    pub grants: Vec<Grant>,
+    #[serde(default)]
+    pub users: HashSet<String>,
+    #[serde(default)]
+    pub built_in: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub row_filters: Vec<RowFilter>,
+}
+
+impl Role {
+    pub fn is_allowed(&self, resource: Resource, obj: &str, permission: Permission) -> bool {
+        self.grants
+            .iter()
+            .any(|g| g.is_allowed(resource, obj, permission))
+    }
+}
+
+/// Returns the definition of a built-in role, without users.
+pub fn get_built_in_role(name: &str) -> Option<Role> {
+    use Permission::*;
+    let grants = match name {
+        ROLE_ADMIN => vec![Grant::new(Resource::All, "*", &[Read, Write, Delete])],
+        ROLE_EDITOR => vec![
+            Grant::new(Resource::Stream, "*", &[Read, Write]),
+            Grant::new(Resource::Alert, "*", &[Read, Write, Delete]),
+            Grant::new(Resource::Dashboard, "*", &[Read, Write, Delete]),
+            Grant::new(Resource::Function, "*", &[Read, Write, Delete]),
+            Grant::new(Resource::Setting, "*", &[Read]),
+        ],
+        ROLE_VIEWER => vec![
+            Grant::new(Resource::Stream, "*", &[Read]),
+            Grant::new(Resource::Alert, "*", &[Read]),
+            Grant::new(Resource::Dashboard, "*", &[Read]),
+            Grant::new(Resource::Function, "*", &[Read]),
+            Grant::new(Resource::Setting, "*", &[Read]),
+        ],
+        _ => return None,
+    };
+    Some(Role {
+        name: name.to_string(),
+        grants,
+        users: HashSet::new(),
+        built_in: true,
+        row_filters: vec![],
+    })
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RoleRequest {
+    #[serde(default)]
+    pub add: Vec<Grant>,
+    #[serde(default)]
+    pub remove: Vec<Grant>,
+    pub add_users: Option<HashSet<String>>,
+    pub remove_users: Option<HashSet<String>>,
+    #[serde(default)]
+    pub add_row_filters: Vec<RowFilter>,
+    #[serde(default)]
+    pub remove_row_filters: Vec<RowFilter>,
+}
+
+/// Simple glob matching, `*` matches any sequence of characters.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    if !pattern.contains('*') {
+        return pattern == name;
+    }
+    let parts = pattern.split('*').collect::<Vec<_>>();
+    let last = parts.len() - 1;
+    let mut pos = 0;
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        if i == 0 {
+            if !name.starts_with(part) {
+                return false;
+            }
+            pos = part.len();
+        } else if i == last {
+            return name.len() >= pos + part.len() && name[pos..].ends_with(part);
+        } else {
+            match name[pos..].find(part) {
+                Some(idx) => pos += idx + part.len(),
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("logs", "logs"));
+        assert!(!wildcard_match("logs", "logs1"));
+        assert!(wildcard_match("logs_*", "logs_k8s"));
+        assert!(!wildcard_match("logs_*", "metrics_k8s"));
+        assert!(wildcard_match("*_prod", "app_prod"));
+        assert!(!wildcard_match("*_prod", "app_dev"));
+        assert!(wildcard_match("app_*_prod", "app_web_prod"));
+        assert!(!wildcard_match("app_*_prod", "app_prod"));
+    }
+
+    #[test]
+    fn test_built_in_roles() {
+        let viewer = get_built_in_role(ROLE_VIEWER).unwrap();
+        assert!(viewer.is_allowed(Resource::Stream, "default", Permission::Read));
+        assert!(!viewer.is_allowed(Resource::Stream, "default", Permission::Delete));
+        let editor = get_built_in_role(ROLE_EDITOR).unwrap();
+        assert!(editor.is_allowed(Resource::Stream, "default", Permission::Write));
+        assert!(!editor.is_allowed(Resource::Role, "viewer", Permission::Write));
+        let admin = get_built_in_role(ROLE_ADMIN).unwrap();
+        assert!(admin.is_allowed(Resource::Role, "viewer", Permission::Delete));
+        assert!(get_built_in_role("custom").is_none());
+    }
+
+    #[test]
+    fn test_row_filter_match() {
+        let filter: RowFilter =
+            json::from_str(r#"{"stream_name":"shared_*","filter":"a = 1"}"#).unwrap();
+        assert!(filter.is_match(StreamType::Logs, "shared_logs"));
+        assert!(!filter.is_match(StreamType::Traces, "shared_logs"));
+        assert!(!filter.is_match(StreamType::Logs, "app_logs"));
+    }
+
+    #[test]
+    fn test_grant_pattern() {
+        let grant = Grant::new(Resource::Stream, "team_a_*", &[Permission::Read]);
+        assert!(grant.is_allowed(Resource::Stream, "team_a_logs", Permission::Read));
+        assert!(!grant.is_allowed(Resource::Stream, "team_b_logs", Permission::Read));
+        assert!(!grant.is_allowed(Resource::Alert, "team_a_logs", Permission::Read));
+        assert!(!grant.is_allowed(Resource::Stream, "team_a_logs", Permission::Write));
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A setting that can be changed without restarting the nodes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RuntimeSetting {
+    /// Env name of the setting, e.g. `ZO_QUERY_TIMEOUT`.
+    pub name: String,
+    pub help: String,
+    /// Value set through the admin API, or else in the env of the node.
+    /// None when the default applies.
+    pub value: Option<String>,
+    /// Whether the value was set through the admin API.
+    pub overridden: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RuntimeConfig {
+    pub settings: Vec<RuntimeSetting>,
+}
+
+/// Changes to the runtime settings by env name, null goes back to the value
+/// of the env.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RuntimeConfigRequest {
+    pub settings: BTreeMap<String, Option<String>>,
+}
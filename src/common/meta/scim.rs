@@ -0,0 +1,307 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+pub const SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+pub const SCHEMA_LIST: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const SCHEMA_PATCH: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const SCHEMA_ERROR: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+pub const CONTENT_TYPE: &str = "application/scim+json";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    /// The id of a user is its email
+    #[serde(default)]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    pub user_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<ScimName>,
+    #[serde(default)]
+    pub emails: Vec<ScimEmail>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default)]
+    pub groups: Vec<ScimMember>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+impl ScimUser {
+    /// Returns the email of the user, the primary email is preferred over the
+    /// user name as identity providers may use a login name as `userName`.
+    pub fn email(&self) -> String {
+        self.emails
+            .iter()
+            .find(|e| e.primary)
+            .or_else(|| self.emails.first())
+            .map(|e| e.value.as_str())
+            .filter(|_| !self.user_name.contains('@'))
+            .unwrap_or(&self.user_name)
+            .trim()
+            .to_lowercase()
+    }
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimName {
+    #[serde(default)]
+    pub given_name: String,
+    #[serde(default)]
+    pub family_name: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ScimEmail {
+    pub value: String,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ScimMember {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimMeta {
+    pub resource_type: String,
+    pub location: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroup {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    /// The id of a group is the name of the role it is mapped to
+    #[serde(default)]
+    pub id: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub members: Vec<ScimMember>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListResponse {
+    pub schemas: Vec<String>,
+    pub total_results: usize,
+    pub start_index: usize,
+    pub items_per_page: usize,
+    #[serde(rename = "Resources")]
+    #[schema(value_type = Vec<Object>)]
+    pub resources: Vec<Value>,
+}
+
+impl ScimListResponse {
+    pub fn new(total_results: usize, start_index: usize, resources: Vec<Value>) -> Self {
+        Self {
+            schemas: vec![SCHEMA_LIST.to_string()],
+            total_results,
+            start_index,
+            items_per_page: resources.len(),
+            resources,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ScimPatchRequest {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ScimPatchOperation {
+    /// add, remove or replace, identity providers differ in casing
+    pub op: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub value: Option<Value>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimError {
+    pub schemas: Vec<String>,
+    /// HTTP status code, as a string per RFC 7644
+    pub status: String,
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scim_type: Option<String>,
+}
+
+impl ScimError {
+    pub fn new(status: u16, detail: impl ToString) -> Self {
+        Self {
+            schemas: vec![SCHEMA_ERROR.to_string()],
+            status: status.to_string(),
+            detail: detail.to_string(),
+            scim_type: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, utoipa::IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListQuery {
+    /// only `attribute eq "value"` filters are supported
+    pub filter: Option<String>,
+    /// 1-based index of the first result
+    pub start_index: Option<usize>,
+    pub count: Option<usize>,
+}
+
+/// Parses a simple `attribute eq "value"` filter, the only form identity
+/// providers use to look up users and groups before provisioning them.
+pub fn parse_filter(filter: &str) -> Option<(String, String)> {
+    let mut parts = filter.trim().splitn(3, ' ');
+    let attr = parts.next()?.trim();
+    if !parts.next()?.eq_ignore_ascii_case("eq") {
+        return None;
+    }
+    let value = parts.next()?.trim().trim_matches('"');
+    if attr.is_empty() {
+        return None;
+    }
+    Some((attr.to_string(), value.to_string()))
+}
+
+/// Returns the member id of a `members[value eq "id"]` patch path.
+pub fn parse_member_path(path: &str) -> Option<String> {
+    let filter = path.trim().strip_prefix("members[")?.strip_suffix(']')?;
+    match parse_filter(filter) {
+        Some((attr, value)) if attr.eq("value") => Some(value),
+        _ => None,
+    }
+}
+
+/// Parses comma separated `group=role` rules.
+pub fn parse_group_mapping(mapping: &str) -> Vec<(String, String)> {
+    mapping
+        .split(',')
+        .filter_map(|rule| {
+            let (group, role) = rule.split_once('=')?;
+            let (group, role) = (group.trim(), role.trim());
+            if group.is_empty() || role.is_empty() {
+                return None;
+            }
+            Some((group.to_string(), role.to_string()))
+        })
+        .collect()
+}
+
+/// Resolves the role a group is mapped to, groups without a rule map to the
+/// role of the same name with unsupported characters replaced.
+pub fn map_group(display_name: &str, rules: &[(String, String)]) -> String {
+    if let Some((_, role)) = rules
+        .iter()
+        .find(|(group, _)| group.eq_ignore_ascii_case(display_name.trim()))
+    {
+        return role.to_string();
+    }
+    display_name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Reads a boolean sent either as a json boolean or, like Azure AD does, as a
+/// string.
+pub fn value_as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(v) => Some(*v),
+        Value::String(v) => v.to_lowercase().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_filter("userName eq \"john@example.com\""),
+            Some(("userName".to_string(), "john@example.com".to_string()))
+        );
+        assert_eq!(
+            parse_filter("displayName EQ \"Okta Admins\""),
+            Some(("displayName".to_string(), "Okta Admins".to_string()))
+        );
+        assert_eq!(parse_filter("userName sw \"john\""), None);
+        assert_eq!(parse_filter("userName"), None);
+        assert_eq!(
+            parse_member_path("members[value eq \"john@example.com\"]"),
+            Some("john@example.com".to_string())
+        );
+        assert_eq!(parse_member_path("members"), None);
+    }
+
+    #[test]
+    fn test_map_group() {
+        let rules = parse_group_mapping("Okta Admins=admin, Engineering=editor,invalid,=x");
+        assert_eq!(rules.len(), 2);
+        assert_eq!(map_group("okta admins", &rules), "admin");
+        assert_eq!(map_group("Engineering", &rules), "editor");
+        assert_eq!(map_group("Data Team", &rules), "data_team");
+    }
+
+    #[test]
+    fn test_user_email() {
+        let user: ScimUser = serde_json::from_value(json!({
+            "userName": "jdoe",
+            "emails": [{"value": "other@example.com"}, {"value": "JDoe@example.com", "primary": true}]
+        }))
+        .unwrap();
+        assert!(user.active);
+        assert_eq!(user.email(), "jdoe@example.com");
+        assert_eq!(value_as_bool(&json!("False")), Some(false));
+        assert_eq!(value_as_bool(&json!(true)), Some(true));
+    }
+}
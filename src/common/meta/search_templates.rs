@@ -0,0 +1,98 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use config::{meta::stream::StreamType, utils::json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A saved SQL query run by its name, `:host` in the query stands for the
+/// `host` parameter and is bound to the value given with the run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchTemplate {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// SQL query, `:name` is replaced by the value of the parameter `name`
+    pub sql: String,
+    /// type of the stream the query reads
+    #[serde(default)]
+    pub stream_type: StreamType,
+    #[serde(default)]
+    pub params: Vec<SearchTemplateParam>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchTemplateParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub param_type: SearchTemplateParamType,
+    /// value bound when the run leaves the parameter out, the parameter is
+    /// required without it
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub default: Option<json::Value>,
+}
+
+/// Type of a parameter, the values are checked against it and bound as SQL
+/// literals, never as SQL text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchTemplateParamType {
+    String,
+    Int,
+    Float,
+    Bool,
+    /// list of strings, bound as `'a', 'b'` for the `IN (:name)` conditions
+    StringList,
+}
+
+impl std::fmt::Display for SearchTemplateParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SearchTemplateParamType::String => write!(f, "string"),
+            SearchTemplateParamType::Int => write!(f, "int"),
+            SearchTemplateParamType::Float => write!(f, "float"),
+            SearchTemplateParamType::Bool => write!(f, "bool"),
+            SearchTemplateParamType::StringList => write!(f, "string_list"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchTemplateList {
+    pub list: Vec<SearchTemplate>,
+}
+
+/// Parameters and time range of a search template run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchTemplateRunRequest {
+    /// parameter name -> value
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub params: HashMap<String, json::Value>,
+    /// microseconds
+    pub start_time: i64,
+    /// microseconds
+    pub end_time: i64,
+    #[serde(default)]
+    pub from: i64,
+    /// max rows of the response, `ZO_QUERY_DEFAULT_LIMIT` without it
+    #[serde(default)]
+    pub size: Option<i64>,
+}
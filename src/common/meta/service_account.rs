@@ -0,0 +1,315 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::rbac::{wildcard_match, Permission, Resource};
+
+/// Prefix of the api tokens, it is used to tell api tokens apart from user
+/// passwords and OIDC tokens.
+pub const TOKEN_PREFIX: &str = "o2sa_";
+/// Domain of the principal a service account token authenticates as.
+pub const PRINCIPAL_DOMAIN: &str = "serviceaccount.local";
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Write data into streams only
+    #[default]
+    Ingest,
+    /// Read only access to streams and other resources
+    Read,
+    /// Provision users and groups through the SCIM endpoints
+    Scim,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ServiceAccount {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+    /// the searches of the tokens skip the row filters, otherwise the filters
+    /// of the roles of the account apply
+    #[serde(default)]
+    pub bypass_row_filters: bool,
+    pub created_at: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ApiToken {
+    pub id: String,
+    /// sha256 of the token secret, the token itself is only returned on creation
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+    #[serde(default)]
+    pub scope: TokenScope,
+    /// stream name patterns the token can access, empty means all streams
+    #[serde(default)]
+    pub streams: Vec<String>,
+    /// allowed client ips or CIDR ranges, empty means any ip
+    #[serde(default)]
+    pub ip_allowlist: Vec<String>,
+    pub created_at: i64,
+    /// microseconds, `None` means the token never expires
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    /// id of the token that replaced this one on rotation
+    #[serde(default)]
+    pub rotated_to: Option<String>,
+}
+
+impl ApiToken {
+    pub fn is_expired(&self, now: i64) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= now)
+    }
+
+    pub fn is_ip_allowed(&self, ip: Option<IpAddr>) -> bool {
+        is_ip_allowed(&self.ip_allowlist, ip)
+    }
+
+    pub fn is_allowed(&self, resource: Resource, obj: &str, permission: Permission) -> bool {
+        let scope_allowed = match self.scope {
+            TokenScope::Ingest => resource == Resource::Stream && permission == Permission::Write,
+            TokenScope::Read => permission == Permission::Read,
+            TokenScope::Scim => resource == Resource::User,
+        };
+        if !scope_allowed {
+            return false;
+        }
+        resource != Resource::Stream
+            || self.streams.is_empty()
+            || self.streams.iter().any(|p| wildcard_match(p, obj))
+    }
+
+    /// Returns a copy without the secret hash, for responses.
+    pub fn redacted(&self) -> Self {
+        Self {
+            hash: String::new(),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ServiceAccountRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub bypass_row_filters: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ApiTokenRequest {
+    #[serde(default)]
+    pub scope: TokenScope,
+    #[serde(default)]
+    pub streams: Vec<String>,
+    #[serde(default)]
+    pub ip_allowlist: Vec<String>,
+    /// seconds, the token never expires if not set
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RotateTokenRequest {
+    /// seconds the old token stays valid, default is `ZO_API_TOKEN_ROTATION_GRACE`
+    #[serde(default)]
+    pub grace_period: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ApiTokenResponse {
+    /// the token, it is only returned once
+    pub token: String,
+    #[serde(flatten)]
+    pub info: ApiToken,
+}
+
+/// Returns the principal id used as `user_id` for the requests authenticated
+/// with a service account token.
+pub fn get_principal(name: &str, token_id: &str) -> String {
+    format!("{name}.{token_id}@{PRINCIPAL_DOMAIN}")
+}
+
+/// Parses a principal back into `(name, token_id)`.
+pub fn parse_principal(user_id: &str) -> Option<(&str, &str)> {
+    user_id
+        .strip_suffix(PRINCIPAL_DOMAIN)?
+        .strip_suffix('@')?
+        .rsplit_once('.')
+}
+
+/// Parses a token `o2sa_{token_id}_{secret}` into `(token_id, secret)`.
+pub fn parse_token(token: &str) -> Option<(&str, &str)> {
+    let (id, secret) = token.strip_prefix(TOKEN_PREFIX)?.split_once('_')?;
+    if id.is_empty() || secret.is_empty() {
+        return None;
+    }
+    Some((id, secret))
+}
+
+/// Checks an ip against an allowlist, an empty list allows any ip while an
+/// unknown ip is only allowed by an empty list.
+pub fn is_ip_allowed(allowlist: &[String], ip: Option<IpAddr>) -> bool {
+    if allowlist.is_empty() {
+        return true;
+    }
+    let Some(ip) = ip else {
+        return false;
+    };
+    allowlist.iter().any(|v| ip_matches(v, ip))
+}
+
+/// Checks that a rule is an ip or a CIDR range with a valid prefix length.
+pub fn is_valid_ip_rule(rule: &str) -> bool {
+    let (addr, prefix) = match rule.trim().split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (rule.trim(), None),
+    };
+    let Ok(addr) = addr.parse::<IpAddr>() else {
+        return false;
+    };
+    match prefix.map(|v| v.parse::<u32>()) {
+        None => true,
+        Some(Ok(v)) => v <= if addr.is_ipv4() { 32 } else { 128 },
+        Some(Err(_)) => false,
+    }
+}
+
+/// Checks an ip against a single ip or a CIDR range.
+pub fn ip_matches(rule: &str, ip: IpAddr) -> bool {
+    let (addr, prefix) = match rule.trim().split_once('/') {
+        Some((addr, prefix)) => match prefix.parse::<u32>() {
+            Ok(prefix) => (addr, Some(prefix)),
+            Err(_) => return false,
+        },
+        None => (rule.trim(), None),
+    };
+    let Ok(addr) = addr.parse::<IpAddr>() else {
+        return false;
+    };
+    match (addr, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let prefix = prefix.unwrap_or(32);
+            if prefix > 32 {
+                return false;
+            }
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let prefix = prefix.unwrap_or(128);
+            if prefix > 128 {
+                return false;
+            }
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_matches() {
+        let ip: IpAddr = "10.1.2.3".parse().unwrap();
+        assert!(ip_matches("10.1.2.3", ip));
+        assert!(ip_matches("10.1.0.0/16", ip));
+        assert!(ip_matches("0.0.0.0/0", ip));
+        assert!(!ip_matches("10.2.0.0/16", ip));
+        assert!(!ip_matches("10.1.0.0/33", ip));
+        assert!(!ip_matches("fd00::/8", ip));
+        let ip: IpAddr = "fd00::1".parse().unwrap();
+        assert!(ip_matches("fd00::/8", ip));
+        assert!(!ip_matches("fe80::/10", ip));
+    }
+
+    #[test]
+    fn test_is_ip_allowed() {
+        let ip: IpAddr = "10.1.2.3".parse().unwrap();
+        assert!(is_ip_allowed(&[], Some(ip)));
+        assert!(is_ip_allowed(&[], None));
+        let allowlist = vec!["192.168.0.0/16".to_string(), "10.1.2.3".to_string()];
+        assert!(is_ip_allowed(&allowlist, Some(ip)));
+        assert!(!is_ip_allowed(
+            &allowlist,
+            Some("10.1.2.4".parse().unwrap())
+        ));
+        assert!(!is_ip_allowed(&allowlist, None));
+        assert!(is_valid_ip_rule("10.0.0.0/8"));
+        assert!(is_valid_ip_rule("fd00::/8"));
+        assert!(!is_valid_ip_rule("10.0.0.0/33"));
+        assert!(!is_valid_ip_rule("example.com"));
+    }
+
+    #[test]
+    fn test_principal() {
+        let principal = get_principal("fluent-bit", "abc123");
+        assert_eq!(principal, "fluent-bit.abc123@serviceaccount.local");
+        assert_eq!(parse_principal(&principal), Some(("fluent-bit", "abc123")));
+        assert_eq!(parse_principal("root@example.com"), None);
+        assert_eq!(
+            parse_token("o2sa_abc123_secret"),
+            Some(("abc123", "secret"))
+        );
+        assert_eq!(parse_token("o2sa_abc123_"), None);
+        assert_eq!(parse_token("password"), None);
+    }
+
+    #[test]
+    fn test_token_is_allowed() {
+        let token = ApiToken {
+            scope: TokenScope::Ingest,
+            streams: vec!["k8s_*".to_string()],
+            ..Default::default()
+        };
+        assert!(token.is_allowed(Resource::Stream, "k8s_logs", Permission::Write));
+        assert!(!token.is_allowed(Resource::Stream, "app_logs", Permission::Write));
+        assert!(!token.is_allowed(Resource::Stream, "k8s_logs", Permission::Read));
+        assert!(!token.is_allowed(Resource::Dashboard, "1", Permission::Write));
+
+        let token = ApiToken {
+            scope: TokenScope::Read,
+            ..Default::default()
+        };
+        assert!(token.is_allowed(Resource::Stream, "app_logs", Permission::Read));
+        assert!(token.is_allowed(Resource::Dashboard, "1", Permission::Read));
+        assert!(!token.is_allowed(Resource::Stream, "app_logs", Permission::Delete));
+    }
+
+    #[test]
+    fn test_token_expiry_and_ip() {
+        let token = ApiToken {
+            expires_at: Some(100),
+            ip_allowlist: vec!["192.168.0.0/24".to_string()],
+            ..Default::default()
+        };
+        assert!(!token.is_expired(99));
+        assert!(token.is_expired(100));
+        assert!(token.is_ip_allowed(Some("192.168.0.10".parse().unwrap())));
+        assert!(!token.is_ip_allowed(Some("192.168.1.10".parse().unwrap())));
+        assert!(!token.is_ip_allowed(None));
+    }
+}
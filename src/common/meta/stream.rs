@@ -17,14 +17,14 @@ use std::sync::Arc;
 
 use arrow_schema::Field;
 use config::{
-    meta::stream::{StreamSettings, StreamStats, StreamType},
+    meta::stream::{FieldType, FileMeta, StreamSettings, StreamStats, StreamType},
     utils::json,
 };
 use datafusion::arrow::datatypes::Schema;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use super::prom::Metadata;
+use super::{alerts::Alert, prom::Metadata};
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct Stream {
@@ -44,6 +44,8 @@ pub struct StreamProperty {
     pub name: String,
     #[serde(rename = "type")]
     pub prop_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logical_type: Option<FieldType>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -99,6 +101,171 @@ pub struct StreamDeleteFields {
     pub fields: Vec<String>,
 }
 
+/// Fields left out of the schema and the search results of a stream.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamHiddenFields {
+    pub fields: Vec<String>,
+}
+
+/// Names the fields of a stream are searched and shown by, alias -> field.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamFieldAliases {
+    pub aliases: hashbrown::HashMap<String, String>,
+}
+
+/// A type change of a field rejected at ingest by a stream in strict schema
+/// mode, waiting to be approved.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PendingSchemaChange {
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    pub field: String,
+    pub from_type: String,
+    pub to_type: String,
+    /// microseconds
+    pub first_seen: i64,
+    /// microseconds
+    pub last_seen: i64,
+}
+
+/// How often the searches of a stream filter on a field by value.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldFilterUsage {
+    pub field: String,
+    /// the searches filtering on the field
+    pub queries: usize,
+    /// the distinct values the searches filtered the field by
+    pub distinct_values: usize,
+    /// the average number of records the searches returned
+    pub avg_hits: f64,
+}
+
+/// The partition keys and bloom filter fields suggested for a stream from its
+/// searches.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct IndexAdvice {
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    /// the searches analyzed
+    pub queries: usize,
+    pub partition_keys: Vec<FieldFilterUsage>,
+    pub bloom_filter_fields: Vec<FieldFilterUsage>,
+}
+
+/// Version of the stream archive format, bumped on incompatible changes.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// A stream archived with its data under `archive/{id}/` of the object store,
+/// the prefix can be copied to the bucket of another cluster and restored
+/// there.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StreamArchive {
+    pub version: u32,
+    #[serde(flatten)]
+    pub info: StreamArchiveInfo,
+    /// the schema versions, oldest first
+    pub schemas: Vec<json::Value>,
+    pub files: Vec<ArchivedFile>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StreamArchiveInfo {
+    pub id: String,
+    /// organization and stream the archive was taken from
+    pub org_id: String,
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    /// in microseconds
+    pub created_at: i64,
+    pub files: usize,
+    pub records: i64,
+    pub compressed_size: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedFile {
+    /// key of the file under the stream, `{YYYY}/{MM}/{DD}/{HH}/{name}`
+    pub key: String,
+    pub meta: FileMeta,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ListStreamArchive {
+    pub list: Vec<StreamArchiveInfo>,
+}
+
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct StreamRestoreRequest {
+    pub archive_id: String,
+    /// name of the restored stream, the name of the archived stream by
+    /// default
+    #[serde(default)]
+    pub stream_name: Option<String>,
+}
+
+/// A stream with its settings, functions and alerts, created in one call.
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct StreamTemplate {
+    pub stream_name: String,
+    #[serde(default)]
+    pub stream_type: StreamType,
+    /// initial fields of the schema, `_timestamp` is always added
+    #[serde(default)]
+    pub fields: Vec<StreamTemplateField>,
+    /// partition keys, retention, full text search fields and the rest
+    #[serde(default)]
+    pub settings: StreamSettings,
+    /// existing functions of the organization applied to the stream
+    #[serde(default)]
+    pub functions: Vec<StreamTemplateFunction>,
+    /// alerts created on the stream, their stream is the template one
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct StreamTemplateField {
+    pub name: String,
+    #[serde(rename = "type")]
+    #[serde(default)]
+    pub field_type: StreamTemplateFieldType,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamTemplateFieldType {
+    #[default]
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct StreamTemplateFunction {
+    pub name: String,
+    /// order of the function among the functions of the stream
+    #[serde(default)]
+    pub order: u8,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, ToSchema)]
+pub struct StreamTemplateResponse {
+    pub stream_name: String,
+    pub stream_type: StreamType,
+    pub functions: Vec<String>,
+    pub alerts: Vec<String>,
+}
+
+/// The key of a data file under its stream, see `ArchivedFile`.
+pub fn relative_file_key(key: &str) -> Option<&str> {
+    let columns = key.splitn(5, '/').collect::<Vec<_>>();
+    if columns.len() < 5 || columns[0] != "files" {
+        return None;
+    }
+    Some(columns[4])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -118,4 +285,20 @@ mod tests {
         assert_eq!(params.stream_name, "stream_name");
         assert_eq!(params.stream_type, StreamType::Logs);
     }
+
+    #[test]
+    fn test_relative_file_key() {
+        let key = "files/default/logs/app/2024/06/10/08/7204846314723379200.parquet";
+        let rel = relative_file_key(key).unwrap();
+        assert_eq!(rel, "2024/06/10/08/7204846314723379200.parquet");
+        assert_eq!(
+            format!("files/prod/logs/app_copy/{rel}"),
+            "files/prod/logs/app_copy/2024/06/10/08/7204846314723379200.parquet"
+        );
+        assert_eq!(
+            relative_file_key("file_list/2024/06/10/08/1.json.zst"),
+            None
+        );
+        assert_eq!(relative_file_key("files/default/logs"), None);
+    }
 }
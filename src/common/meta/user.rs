@@ -60,6 +60,8 @@ impl UserRequest {
             }],
             is_external,
             password_ext: Some(password_ext),
+            password_updated_at: chrono::Utc::now().timestamp_micros(),
+            force_password_reset: false,
         }
     }
 }
@@ -78,6 +80,12 @@ pub struct DBUser {
     #[serde(default)]
     pub is_external: bool,
     pub password_ext: Option<String>,
+    /// When the password was last changed, in microseconds, 0 when unknown
+    #[serde(default)]
+    pub password_updated_at: i64,
+    /// The user has to change the password before signing in again
+    #[serde(default)]
+    pub force_password_reset: bool,
 }
 
 impl DBUser {
@@ -105,6 +113,8 @@ impl DBUser {
             salt: local.salt,
             is_external: self.is_external,
             password_ext: self.password_ext.clone(),
+            password_updated_at: self.password_updated_at,
+            force_password_reset: self.force_password_reset,
         })
     }
 
@@ -126,6 +136,8 @@ impl DBUser {
                     salt: self.salt.clone(),
                     is_external: self.is_external,
                     password_ext: self.password_ext.clone(),
+                    password_updated_at: self.password_updated_at,
+                    force_password_reset: self.force_password_reset,
                 })
             }
             ret_val
@@ -151,6 +163,10 @@ pub struct User {
     /// Is the user authenticated and created via LDAP
     pub is_external: bool,
     pub password_ext: Option<String>,
+    #[serde(default)]
+    pub password_updated_at: i64,
+    #[serde(default)]
+    pub force_password_reset: bool,
 }
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize, ToSchema)]
@@ -191,6 +207,9 @@ pub struct UpdateUser {
     pub role: Option<UserRole>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    /// Makes the user change the password on the next login, admins only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_password_reset: Option<bool>,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, ToSchema, EnumIter)]
@@ -304,6 +323,16 @@ pub struct SignInUser {
 pub struct SignInResponse {
     pub status: bool,
     pub message: String,
+    /// The password has to be changed through `/auth/reset_password` first
+    #[serde(default)]
+    pub password_reset_required: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasswordResetRequest {
+    pub name: String,
+    pub old_password: String,
+    pub new_password: String,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -0,0 +1,184 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Versioned migrations of the layout of the meta store: the format of its
+//! keys and of the resources stored in it. The layout version lives at
+//! `/meta/kv/layout_version`, the migrations newer than it run in order at
+//! startup, one node at a time.
+//!
+//! A change to the layout adds a `Migration` to the end of `MIGRATIONS` with
+//! the next version. It must be safe to run again after a failure, its
+//! `check` refuses a store it can't migrate before any migration is applied,
+//! and its `rollback` tells how to go back to the previous layout. Before
+//! migrating, the meta store is backed up to the object store, restoring that
+//! backup and starting the previous release is the generic rollback.
+//!
+//! A node finding a layout newer than it knows refuses to start, it would
+//! misread the resources written by the newer release.
+
+use config::get_config;
+use futures::future::BoxFuture;
+use infra::dist_lock;
+
+use crate::service::{backup, db};
+
+type MigrationFn = fn() -> BoxFuture<'static, Result<(), anyhow::Error>>;
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    /// how to go back to the previous layout, logged before the migration
+    pub rollback: &'static str,
+    /// refuses a store the migration can't handle, all the checks of the
+    /// pending migrations run before the first one is applied
+    pub check: Option<MigrationFn>,
+    pub up: MigrationFn,
+}
+
+pub static MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    name: "dashboards_into_folders",
+    rollback: "the previous releases read the dashboards from the default folder too, nothing to do",
+    check: None,
+    up: dashboards_into_folders,
+}];
+
+fn dashboards_into_folders() -> BoxFuture<'static, Result<(), anyhow::Error>> {
+    Box::pin(super::dashboards::run())
+}
+
+const LOCK_KEY: &str = "/meta/migration";
+
+/// The latest layout version known by this release.
+pub fn latest() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or_default()
+}
+
+/// The migrations to run on a store of the `current` layout.
+fn pending(current: u32, migrations: &[Migration]) -> Result<Vec<&Migration>, anyhow::Error> {
+    let latest = migrations.last().map(|m| m.version).unwrap_or_default();
+    if current > latest && !get_config().common.meta_migration_allow_newer_layout {
+        return Err(anyhow::anyhow!(
+            "the meta store has layout version {current}, newer than the version {latest} of this release, upgrade this node or set ZO_META_MIGRATION_ALLOW_NEWER_LAYOUT=true at your own risk"
+        ));
+    }
+    Ok(migrations.iter().filter(|m| m.version > current).collect())
+}
+
+/// Brings the layout of the meta store to the latest version, a new install
+/// starts at it.
+pub async fn run(new_install: bool) -> Result<(), anyhow::Error> {
+    let locker = dist_lock::lock(LOCK_KEY, 0).await?;
+    let ret = migrate(new_install).await;
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+async fn migrate(new_install: bool) -> Result<(), anyhow::Error> {
+    let current = db::version::get_layout().await?;
+    if new_install && current == 0 {
+        return db::version::set_layout(latest()).await;
+    }
+    let pending = pending(current, MIGRATIONS)?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    // pre-flight checks
+    for migration in pending.iter() {
+        if let Some(check) = migration.check {
+            check().await.map_err(|e| {
+                anyhow::anyhow!(
+                    "meta store migration {} {} pre-flight check failed: {e}",
+                    migration.version,
+                    migration.name
+                )
+            })?;
+        }
+    }
+    if get_config().common.meta_migration_backup {
+        let manifest = backup::create(false).await?;
+        log::info!(
+            "[MIGRATION] meta store backed up to {}, restore it with `openobserve admin restore --backup {} --overwrite` to roll back",
+            manifest.id,
+            manifest.id
+        );
+    }
+
+    for migration in pending {
+        log::info!(
+            "[MIGRATION] meta store layout {} {} start, rollback: {}",
+            migration.version,
+            migration.name,
+            migration.rollback
+        );
+        let start = std::time::Instant::now();
+        (migration.up)().await.map_err(|e| {
+            anyhow::anyhow!(
+                "meta store migration {} {} failed: {e}",
+                migration.version,
+                migration.name
+            )
+        })?;
+        // recorded one by one, a failed migration resumes at itself
+        db::version::set_layout(migration.version).await?;
+        log::info!(
+            "[MIGRATION] meta store layout {} {} done, took: {}ms",
+            migration.version,
+            migration.name,
+            start.elapsed().as_millis()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop() -> BoxFuture<'static, Result<(), anyhow::Error>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    #[test]
+    fn test_migrations_in_order() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as u32 + 1, "{}", migration.name);
+            assert!(!migration.rollback.is_empty(), "{}", migration.name);
+        }
+    }
+
+    #[test]
+    fn test_pending() {
+        let migrations = [1, 2, 3].map(|version| Migration {
+            version,
+            name: "test",
+            rollback: "none",
+            check: None,
+            up: noop,
+        });
+        let versions = |current| -> Vec<u32> {
+            pending(current, &migrations)
+                .unwrap()
+                .iter()
+                .map(|m| m.version)
+                .collect()
+        };
+        assert_eq!(versions(0), vec![1, 2, 3]);
+        assert_eq!(versions(2), vec![3]);
+        assert!(versions(3).is_empty());
+        assert!(pending(4, &migrations).is_err());
+    }
+}
@@ -17,6 +17,7 @@ use version_compare::Version;
 
 pub mod dashboards;
 pub mod file_list;
+pub mod layout;
 pub mod meta;
 pub mod schema;
 
@@ -19,12 +19,10 @@ use base64::Engine;
 use config::utils::json;
 use futures::future::{ready, Ready};
 
-#[cfg(feature = "enterprise")]
-use crate::common::infra::config::USER_SESSIONS;
 #[cfg(feature = "enterprise")]
 use crate::common::meta::ingestion::INGESTION_EP;
 use crate::common::{
-    infra::config::{PASSWORD_HASH, USERS},
+    infra::config::{PASSWORD_HASH, USERS, USER_SESSIONS},
     meta::{
         authz::Authz,
         organization::DEFAULT_ORG,
@@ -62,6 +60,13 @@ pub(crate) fn is_root_user(user_id: &str) -> bool {
     }
 }
 
+/// Service accounts are not users, the scope of their tokens is checked by the
+/// validator.
+#[cfg(feature = "enterprise")]
+pub(crate) fn is_service_account(user_id: &str) -> bool {
+    crate::common::meta::service_account::parse_principal(user_id).is_some()
+}
+
 #[cfg(feature = "enterprise")]
 pub async fn set_ownership(org_id: &str, obj_type: &str, obj: Authz) {
     use o2_enterprise::enterprise::common::infra::config::O2_CONFIG;
@@ -497,6 +502,11 @@ impl FromRequest for AuthExtractor {
             let access_token = auth_tokens.access_token;
             if access_token.starts_with("Basic") || access_token.starts_with("Bearer") {
                 access_token
+            } else if let Some(session_key) = access_token.strip_prefix("session ") {
+                match USER_SESSIONS.get(session_key) {
+                    Some(token) => format!("Bearer {}", *token),
+                    None => access_token,
+                }
             } else {
                 format!("Bearer {}", access_token)
             }
@@ -512,12 +522,30 @@ impl FromRequest for AuthExtractor {
 
         // if let Some(auth_header) = req.headers().get("Authorization") {
         if !auth_str.is_empty() {
+            let local_path = req.path().to_string();
+            let path = local_path
+                .strip_prefix(format!("{}/api/", config::get_config().common.base_uri).as_str());
+            // only the requests mapped to a rbac resource need to check permissions
+            let permission = path.and_then(|path| {
+                crate::service::rbac::get_permission_for_path(req.method().as_str(), path)
+                    .map(|v| (path.split('/').next().unwrap_or_default().to_string(), v))
+            });
+            let Some((org_id, (permission, resource, obj))) = permission else {
+                return ready(Ok(AuthExtractor {
+                    auth: auth_str.to_owned(),
+                    method: "".to_string(),
+                    o2_type: "".to_string(),
+                    org_id: "".to_string(),
+                    bypass_check: true, // bypass check permissions
+                    parent_id: "".to_string(),
+                }));
+            };
             return ready(Ok(AuthExtractor {
                 auth: auth_str.to_owned(),
-                method: "".to_string(),
-                o2_type: "".to_string(),
-                org_id: "".to_string(),
-                bypass_check: true, // bypass check permissions
+                method: permission.to_string(),
+                o2_type: format!("{resource}:{obj}"),
+                org_id,
+                bypass_check: false,
                 parent_id: "".to_string(),
             }));
         }
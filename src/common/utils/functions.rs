@@ -44,6 +44,7 @@ pub fn get_vrl_compiler_config(org_id: &str) -> VRLCompilerConfig {
     let en_tables = ENRICHMENT_TABLES.clone();
     let mut functions = vrl::stdlib::all();
     functions.append(&mut vector_enrichment::vrl_functions());
+    functions.append(&mut super::vrl_functions::all());
     let registry = TableRegistry::default();
     let mut tables: HashMap<String, Box<dyn Table + Send + Sync>> = HashMap::new();
 
@@ -16,11 +16,15 @@
 use std::{
     collections::HashMap,
     io::{Error, ErrorKind},
+    net::{IpAddr, SocketAddr},
 };
 
-use actix_web::web::Query;
+use actix_web::{dev::ConnectionInfo, web::Query};
 use awc::http::header::HeaderMap;
-use config::meta::{search::SearchEventType, stream::StreamType};
+use config::{
+    get_config,
+    meta::{search::SearchEventType, stream::StreamType},
+};
 use opentelemetry::propagation::Extractor;
 
 #[inline(always)]
@@ -81,6 +85,24 @@ pub(crate) fn get_folder(query: &Query<HashMap<String, String>>) -> String {
     }
 }
 
+/// Client ip of the request, the `Forwarded` / `X-Forwarded-For` headers are
+/// only used when `ZO_TRUST_FORWARDED_HEADERS` is enabled.
+pub(crate) fn get_client_ip(
+    conn_info: &ConnectionInfo,
+    peer_addr: Option<SocketAddr>,
+) -> Option<IpAddr> {
+    if get_config().auth.trust_forwarded_headers {
+        conn_info.realip_remote_addr().and_then(|v| {
+            v.parse::<SocketAddr>()
+                .map(|v| v.ip())
+                .or_else(|_| v.parse::<IpAddr>())
+                .ok()
+        })
+    } else {
+        peer_addr.map(|v| v.ip())
+    }
+}
+
 // Extractor for request headers
 pub struct RequestHeaderExtractor<'a> {
     headers: &'a HeaderMap,
@@ -102,6 +124,15 @@ impl<'a> Extractor for RequestHeaderExtractor<'a> {
     }
 }
 
+/// Extracts the W3C trace context (`traceparent` / `tracestate`) from the
+/// request headers, it returns an empty context if the headers are absent.
+#[inline(always)]
+pub(crate) fn get_trace_context(headers: &HeaderMap) -> opentelemetry::Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&RequestHeaderExtractor::new(headers))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
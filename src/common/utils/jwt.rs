@@ -1,22 +1,21 @@
-#[cfg(feature = "enterprise")]
-use {
-    jsonwebtoken::{
-        decode, decode_header,
-        jwk::{self, AlgorithmParameters},
-        Algorithm, DecodingKey, TokenData, Validation,
-    },
-    serde_json::Value,
-    std::{collections::HashMap, str::FromStr},
+use std::{collections::HashMap, str::FromStr};
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{self, AlgorithmParameters},
+    Algorithm, DecodingKey, TokenData, Validation,
 };
+use serde_json::Value;
 
-#[cfg(feature = "enterprise")]
 use crate::common::meta::user::TokenValidationResponse;
 
-#[cfg(feature = "enterprise")]
+/// Verifies a token with the keys of `jwks`, and its issuer when `issuer` is
+/// set.
 pub(crate) async fn verify_decode_token(
     token: &str,
     jwks: &str,
     aud: &str,
+    issuer: Option<&str>,
     get_decode_token: bool,
 ) -> Result<
     (
@@ -27,75 +26,70 @@ pub(crate) async fn verify_decode_token(
 > {
     use infra::errors::JwtError;
 
-    let jwks: jwk::JwkSet = serde_json::from_str(jwks).unwrap();
+    let jwks: jwk::JwkSet = serde_json::from_str(jwks)?;
     let header = decode_header(token)?;
-    let kid = match header.kid {
+    let kid = match header.kid.as_ref() {
         Some(k) => k,
         None => return Err(JwtError::MissingAttribute("`kid` header".to_owned()).into()),
     };
 
-    if let Some(j) = jwks.find(&kid) {
-        match &j.algorithm {
-            AlgorithmParameters::RSA(rsa) => {
-                let decoding_key = DecodingKey::from_rsa_components(&rsa.n, &rsa.e).unwrap();
-
-                let mut validation = Validation::new(
-                    Algorithm::from_str(j.common.key_algorithm.unwrap().to_string().as_str())
-                        .unwrap(),
-                );
-                validation.validate_exp = true;
-                validation.set_audience(&[aud]);
-                let decoded_token = decode::<HashMap<String, serde_json::Value>>(
-                    token,
-                    &decoding_key,
-                    &validation,
-                )?;
-
-                let user_email = if let Some(email) = decoded_token.claims.get("email") {
-                    email.as_str().unwrap()
-                } else {
-                    ""
-                };
-
-                let user_name = if let Some(name) = decoded_token.claims.get("name") {
-                    name.as_str().unwrap()
-                } else {
-                    ""
-                };
-
-                let family_name = if let Some(family_name) = decoded_token.claims.get("family_name")
-                {
-                    family_name.as_str().unwrap()
-                } else {
-                    ""
-                };
-
-                let given_name = if let Some(given_name) = decoded_token.claims.get("given_name") {
-                    given_name.as_str().unwrap()
-                } else {
-                    ""
-                };
+    let Some(j) = jwks.find(kid) else {
+        return Err(JwtError::KeyNotExists().into());
+    };
+    let AlgorithmParameters::RSA(rsa) = &j.algorithm else {
+        return Err(JwtError::ValidationFailed().into());
+    };
+    let decoding_key = DecodingKey::from_rsa_components(&rsa.n, &rsa.e)?;
+    // `alg` is optional in a JWK, the one of the token is only taken within
+    // the family of the key
+    let algorithm = match &j.common.key_algorithm {
+        Some(alg) => Algorithm::from_str(alg.to_string().as_str())?,
+        None => header.alg,
+    };
+    if !matches!(
+        algorithm,
+        Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512
+    ) {
+        return Err(JwtError::ValidationFailed().into());
+    }
 
-                Ok((
-                    TokenValidationResponse {
-                        is_valid: true,
-                        user_email: user_email.to_owned(),
-                        user_name: user_name.to_owned(),
-                        family_name: family_name.to_owned(),
-                        given_name: given_name.to_owned(),
-                        is_internal_user: false,
-                        user_role: None,
-                    },
-                    if get_decode_token {
-                        Some(decoded_token)
-                    } else {
-                        None
-                    },
-                ))
-            }
-            _ => Err(JwtError::ValidationFailed().into()),
-        }
-    } else {
-        Err(JwtError::KeyNotExists().into())
+    let mut validation = Validation::new(algorithm);
+    validation.validate_exp = true;
+    validation.set_audience(&[aud]);
+    if let Some(issuer) = issuer {
+        validation.set_issuer(&[issuer]);
     }
+    let decoded_token =
+        decode::<HashMap<String, serde_json::Value>>(token, &decoding_key, &validation)?;
+
+    let claim = |name: &str| {
+        decoded_token
+            .claims
+            .get(name)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_owned()
+    };
+    let response = TokenValidationResponse {
+        is_valid: true,
+        user_email: claim("email"),
+        user_name: claim("name"),
+        family_name: claim("family_name"),
+        given_name: claim("given_name"),
+        is_internal_user: false,
+        user_role: None,
+    };
+    Ok((
+        response,
+        if get_decode_token {
+            Some(decoded_token)
+        } else {
+            None
+        },
+    ))
 }
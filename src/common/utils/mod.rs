@@ -17,5 +17,7 @@ pub mod auth;
 pub mod functions;
 pub mod http;
 pub mod jwt;
+pub mod schedule;
 pub mod stream;
+pub mod vrl_functions;
 pub mod zo_logger;
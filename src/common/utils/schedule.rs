@@ -0,0 +1,395 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Evaluates the alert and report schedules in their timezone. The schedules
+//! walk the wall clock of the timezone, so "every day at 9:00" stays at 9:00
+//! across the DST changes. A wall clock time skipped by the spring forward
+//! runs at the end of the gap, a time repeated by the fall back runs once.
+
+use std::str::FromStr;
+
+use chrono::{
+    DateTime, Datelike, Days, Duration, FixedOffset, Months, NaiveDate, NaiveDateTime, TimeZone,
+    Utc, Weekday,
+};
+use chrono_tz::Tz;
+use cron::Schedule;
+
+/// The timezone of a schedule.
+#[derive(Clone, Copy, Debug)]
+pub enum ScheduleTz {
+    /// An IANA timezone, with its DST rules
+    Named(Tz),
+    /// A fixed offset, which ignores the DST
+    Fixed(FixedOffset),
+}
+
+impl ScheduleTz {
+    /// Uses the IANA `timezone` when set, else the fixed offset in minutes.
+    pub fn new(timezone: &str, tz_offset: i32) -> Result<Self, anyhow::Error> {
+        if !timezone.is_empty() {
+            return timezone
+                .parse::<Tz>()
+                .map(Self::Named)
+                .map_err(|e| anyhow::anyhow!("Invalid timezone {timezone}: {e}"));
+        }
+        Self::fixed(tz_offset)
+    }
+
+    /// Like [`ScheduleTz::new`], an unknown `timezone` falls back to the offset.
+    pub fn new_or_fixed(timezone: &str, tz_offset: i32) -> Result<Self, anyhow::Error> {
+        Self::new(timezone, tz_offset).or_else(|e| {
+            log::warn!("{e}, using the timezone offset {tz_offset}");
+            Self::fixed(tz_offset)
+        })
+    }
+
+    pub fn fixed(tz_offset: i32) -> Result<Self, anyhow::Error> {
+        FixedOffset::east_opt(tz_offset * 60)
+            .map(Self::Fixed)
+            .ok_or_else(|| anyhow::anyhow!("Invalid timezone offset {tz_offset}"))
+    }
+}
+
+/// A calendar interval between two runs, counted on the wall clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CalendarInterval {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    /// The first day of the month, every `n` months
+    FirstOfMonth(u32),
+    /// Every day from Monday to Friday
+    BusinessDays,
+}
+
+/// Returns the first run of the cron expression after `after`, in
+/// microseconds.
+pub fn next_cron_run(cron: &str, tz: ScheduleTz, after: i64) -> Result<i64, anyhow::Error> {
+    let schedule = Schedule::from_str(cron)?;
+    let next = match tz {
+        ScheduleTz::Named(tz) => next_cron_in(&schedule, &tz, after),
+        ScheduleTz::Fixed(tz) => next_cron_in(&schedule, &tz, after),
+    };
+    next.ok_or_else(|| anyhow::anyhow!("Cron expression {cron} has no upcoming run"))
+}
+
+/// Returns the first run after `now` of the runs at `anchor` and every
+/// interval after it, in microseconds. The runs keep the time of day of the
+/// anchor.
+pub fn next_calendar_run(
+    interval: CalendarInterval,
+    tz: ScheduleTz,
+    anchor: i64,
+    now: i64,
+) -> Result<i64, anyhow::Error> {
+    let next = match tz {
+        ScheduleTz::Named(tz) => next_calendar_in(interval, &tz, anchor, now),
+        ScheduleTz::Fixed(tz) => next_calendar_in(interval, &tz, anchor, now),
+    };
+    next.ok_or_else(|| anyhow::anyhow!("Interval {interval:?} has no upcoming run"))
+}
+
+fn next_cron_in<Z: TimeZone>(schedule: &Schedule, tz: &Z, after: i64) -> Option<i64> {
+    // the schedule walks the wall clock as if it was utc, which has no DST
+    let mut local = DateTime::from_timestamp_micros(after)?
+        .with_timezone(tz)
+        .naive_local();
+    // a wall clock time repeated by the fall back may be behind `after`
+    for _ in 0..8 {
+        let next = schedule
+            .after(&Utc.from_utc_datetime(&local))
+            .next()?
+            .naive_utc();
+        let run = resolve_local(tz, next)?;
+        if run > after {
+            return Some(run);
+        }
+        local = next;
+    }
+    None
+}
+
+fn next_calendar_in<Z: TimeZone>(
+    interval: CalendarInterval,
+    tz: &Z,
+    anchor: i64,
+    now: i64,
+) -> Option<i64> {
+    let anchor_local = DateTime::from_timestamp_micros(anchor)?
+        .with_timezone(tz)
+        .naive_local();
+    let after = now.max(anchor - 1);
+    if interval == CalendarInterval::BusinessDays {
+        let mut day = DateTime::from_timestamp_micros(after)?
+            .with_timezone(tz)
+            .date_naive();
+        for _ in 0..8 {
+            if !matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+                let run = resolve_local(tz, day.and_time(anchor_local.time()))?;
+                if run > after {
+                    return Some(run);
+                }
+            }
+            day = day.succ_opt()?;
+        }
+        return None;
+    }
+
+    // the number of intervals between the anchor and `after` on the calendar
+    // of the timezone, the run before it may still be ahead of `after`
+    let after_local = DateTime::from_timestamp_micros(after)?
+        .with_timezone(tz)
+        .naive_local();
+    let k = passed_intervals(interval, anchor_local, after_local).saturating_sub(1);
+    for k in k..k.saturating_add(4) {
+        let local = nth_run(interval, anchor_local, k)?;
+        let run = resolve_local(tz, local)?;
+        if run > after {
+            return Some(run);
+        }
+    }
+    None
+}
+
+/// The whole intervals from the anchor to `after`, on the wall clock.
+fn passed_intervals(
+    interval: CalendarInterval,
+    anchor: NaiveDateTime,
+    after: NaiveDateTime,
+) -> u32 {
+    let days = (after.date() - anchor.date()).num_days().max(0);
+    let months = |from: NaiveDate| {
+        ((after.year() - from.year()) * 12 + after.month() as i32 - from.month() as i32).max(0)
+            as i64
+    };
+    let passed = match interval {
+        CalendarInterval::Days(n) => days / n.max(1) as i64,
+        CalendarInterval::Weeks(n) => days / (7 * n.max(1) as i64),
+        CalendarInterval::Months(n) => months(anchor.date()) / n.max(1) as i64,
+        CalendarInterval::FirstOfMonth(n) => {
+            // counted from the first run, the first day of the month after
+            // the anchor unless the anchor is a first day
+            let first = match anchor.day() {
+                1 => anchor.date(),
+                _ => anchor
+                    .date()
+                    .checked_add_months(Months::new(1))
+                    .unwrap_or(anchor.date()),
+            };
+            months(first) / n.max(1) as i64
+        }
+        CalendarInterval::BusinessDays => 0,
+    };
+    u32::try_from(passed).unwrap_or(u32::MAX)
+}
+
+/// The wall clock time of the `k`-th run after the anchor.
+fn nth_run(interval: CalendarInterval, anchor: NaiveDateTime, k: u32) -> Option<NaiveDateTime> {
+    match interval {
+        CalendarInterval::Days(n) => anchor.checked_add_days(Days::new((k * n.max(1)) as u64)),
+        CalendarInterval::Weeks(n) => anchor.checked_add_days(Days::new((7 * k * n.max(1)) as u64)),
+        CalendarInterval::Months(n) => anchor.checked_add_months(Months::new(k * n.max(1))),
+        CalendarInterval::FirstOfMonth(n) => {
+            // the first run is the first day of a month at or after the anchor
+            let skip = if anchor.day() == 1 { 0 } else { 1 };
+            anchor
+                .date()
+                .with_day(1)?
+                .checked_add_months(Months::new(skip + k * n.max(1)))
+                .map(|day| day.and_time(anchor.time()))
+        }
+        CalendarInterval::BusinessDays => None,
+    }
+}
+
+/// Maps a wall clock time to microseconds, the earliest of a repeated time
+/// and the end of the gap for a skipped time.
+fn resolve_local<Z: TimeZone>(tz: &Z, local: NaiveDateTime) -> Option<i64> {
+    if let Some(time) = tz.from_local_datetime(&local).earliest() {
+        return Some(time.timestamp_micros());
+    }
+    (1..=24 * 60).find_map(|minutes| {
+        tz.from_local_datetime(&(local + Duration::try_minutes(minutes)?))
+            .earliest()
+            .map(|time| time.timestamp_micros())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn micros(time: &str) -> i64 {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .timestamp_micros()
+    }
+
+    fn new_york() -> ScheduleTz {
+        ScheduleTz::new("America/New_York", 0).unwrap()
+    }
+
+    #[test]
+    fn test_schedule_tz() {
+        assert!(matches!(
+            ScheduleTz::new("Europe/Berlin", 0).unwrap(),
+            ScheduleTz::Named(_)
+        ));
+        assert!(matches!(
+            ScheduleTz::new("", 330).unwrap(),
+            ScheduleTz::Fixed(_)
+        ));
+        assert!(ScheduleTz::new("Mars/Olympus", 0).is_err());
+        assert!(matches!(
+            ScheduleTz::new_or_fixed("Mars/Olympus", 0).unwrap(),
+            ScheduleTz::Fixed(_)
+        ));
+    }
+
+    #[test]
+    fn test_next_cron_run_follows_dst() {
+        let daily = "0 0 9 * * *";
+        // the day of the spring forward, 9:00 EDT is 13:00 utc
+        assert_eq!(
+            next_cron_run(daily, new_york(), micros("2024-03-09T15:00:00Z")).unwrap(),
+            micros("2024-03-10T13:00:00Z")
+        );
+        // a fixed offset drifts
+        assert_eq!(
+            next_cron_run(
+                daily,
+                ScheduleTz::fixed(-300).unwrap(),
+                micros("2024-03-09T15:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-03-10T14:00:00Z")
+        );
+        // 2:30 does not exist on the spring forward, runs at 3:00 EDT
+        assert_eq!(
+            next_cron_run("0 30 2 * * *", new_york(), micros("2024-03-10T05:00:00Z")).unwrap(),
+            micros("2024-03-10T07:00:00Z")
+        );
+        // 1:30 happens twice on the fall back, runs once
+        let first = next_cron_run("0 30 1 * * *", new_york(), micros("2024-11-03T04:00:00Z"));
+        assert_eq!(first.unwrap(), micros("2024-11-03T05:30:00Z"));
+        assert_eq!(
+            next_cron_run("0 30 1 * * *", new_york(), micros("2024-11-03T05:30:01Z")).unwrap(),
+            micros("2024-11-04T06:30:00Z")
+        );
+        assert!(next_cron_run("not a cron", new_york(), 0).is_err());
+    }
+
+    #[test]
+    fn test_next_calendar_run() {
+        // daily at 9:00 EST, then 9:00 EDT
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::Days(1),
+                new_york(),
+                micros("2024-03-08T14:00:00Z"),
+                micros("2024-03-11T12:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-03-11T13:00:00Z")
+        );
+        // the anchor is the first run
+        let utc = ScheduleTz::fixed(0).unwrap();
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::Weeks(2),
+                utc,
+                micros("2024-05-01T08:00:00Z"),
+                micros("2024-04-01T00:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-05-01T08:00:00Z")
+        );
+        // months keep the day of the anchor when the month has it
+        let anchor = micros("2024-01-31T10:00:00Z");
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::Months(1),
+                utc,
+                anchor,
+                micros("2024-02-15T00:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-02-29T10:00:00Z")
+        );
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::Months(1),
+                utc,
+                anchor,
+                micros("2024-03-01T00:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-03-31T10:00:00Z")
+        );
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::FirstOfMonth(1),
+                utc,
+                micros("2024-01-15T09:00:00Z"),
+                micros("2024-01-20T00:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-02-01T09:00:00Z")
+        );
+        // an anchor years in the past
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::Days(1),
+                new_york(),
+                micros("2019-01-01T14:00:00Z"),
+                micros("2024-07-04T12:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-07-04T13:00:00Z")
+        );
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::Weeks(1),
+                utc,
+                micros("2015-01-05T09:00:00Z"),
+                micros("2024-07-04T12:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-07-08T09:00:00Z")
+        );
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::Months(3),
+                utc,
+                micros("2010-01-31T10:00:00Z"),
+                micros("2024-07-04T12:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-07-31T10:00:00Z")
+        );
+        // friday after the run, next is monday
+        assert_eq!(
+            next_calendar_run(
+                CalendarInterval::BusinessDays,
+                utc,
+                micros("2024-03-01T09:00:00Z"),
+                micros("2024-03-08T10:00:00Z")
+            )
+            .unwrap(),
+            micros("2024-03-11T09:00:00Z")
+        );
+    }
+}
@@ -0,0 +1,408 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Functions added to the vrl stdlib for parsing legacy formats. Grok is
+//! covered by the stdlib `parse_grok` and `parse_groks`, the latter accepts
+//! custom pattern definitions through its `aliases`.
+
+use config::utils::json;
+use regex::Regex;
+use vrl::prelude::*;
+
+pub fn all() -> Vec<Box<dyn Function>> {
+    vec![
+        Box::new(ParseKeyValue),
+        Box::new(ParseCsvHeader),
+        Box::new(ParseMultiline),
+    ]
+}
+
+fn resolve_string(
+    expr: &Option<Box<dyn Expression>>,
+    ctx: &mut Context,
+    default: &str,
+) -> Result<String, ExpressionError> {
+    match expr {
+        Some(expr) => Ok(expr.resolve(ctx)?.try_bytes_utf8_lossy()?.into_owned()),
+        None => Ok(default.to_string()),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ParseKeyValue;
+
+impl Function for ParseKeyValue {
+    fn identifier(&self) -> &'static str {
+        "parse_keyvalue"
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[
+            Parameter {
+                keyword: "value",
+                kind: kind::BYTES,
+                required: true,
+            },
+            Parameter {
+                keyword: "field_delimiter",
+                kind: kind::BYTES,
+                required: false,
+            },
+            Parameter {
+                keyword: "key_value_delimiter",
+                kind: kind::BYTES,
+                required: false,
+            },
+        ]
+    }
+
+    fn examples(&self) -> &'static [Example] {
+        &[Example {
+            title: "audit log",
+            source: r#"parse_keyvalue!(s'type=USER_LOGIN msg="op=login acct=root" res=ok')"#,
+            result: Ok(r#"{"msg": "op=login acct=root", "res": "ok", "type": "USER_LOGIN"}"#),
+        }]
+    }
+
+    fn compile(
+        &self,
+        _state: &state::TypeState,
+        _ctx: &mut FunctionCompileContext,
+        arguments: ArgumentList,
+    ) -> Compiled {
+        Ok(ParseKeyValueFn {
+            value: arguments.required("value"),
+            field_delimiter: arguments.optional("field_delimiter"),
+            key_value_delimiter: arguments.optional("key_value_delimiter"),
+        }
+        .as_expr())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ParseKeyValueFn {
+    value: Box<dyn Expression>,
+    field_delimiter: Option<Box<dyn Expression>>,
+    key_value_delimiter: Option<Box<dyn Expression>>,
+}
+
+impl FunctionExpression for ParseKeyValueFn {
+    fn resolve(&self, ctx: &mut Context) -> Resolved {
+        let value = self.value.resolve(ctx)?;
+        let value = value.try_bytes_utf8_lossy()?;
+        let field_delimiter = resolve_string(&self.field_delimiter, ctx, " ")?;
+        let key_value_delimiter = resolve_string(&self.key_value_delimiter, ctx, "=")?;
+        if field_delimiter.is_empty() || key_value_delimiter.is_empty() {
+            return Err("delimiters can not be empty".into());
+        }
+        let map = parse_keyvalue(&value, &field_delimiter, &key_value_delimiter);
+        Ok(Value::from(&json::Value::Object(map)))
+    }
+
+    fn type_def(&self, _: &state::TypeState) -> TypeDef {
+        TypeDef::object(Collection::any()).fallible()
+    }
+}
+
+/// Parses `key=value` pairs, values may be double quoted to contain the
+/// delimiters, a key without value is true, repeated keys are collected into
+/// an array.
+fn parse_keyvalue(
+    value: &str,
+    field_delimiter: &str,
+    key_value_delimiter: &str,
+) -> json::Map<String, json::Value> {
+    let mut map = json::Map::new();
+    let mut rest = value;
+    loop {
+        while let Some(v) = rest.strip_prefix(field_delimiter) {
+            rest = v;
+        }
+        if rest.is_empty() {
+            break;
+        }
+        let key_end = match (rest.find(key_value_delimiter), rest.find(field_delimiter)) {
+            (Some(kv), Some(fd)) if fd < kv => {
+                insert_value(&mut map, &rest[..fd], json::Value::Bool(true));
+                rest = &rest[fd..];
+                continue;
+            }
+            (Some(kv), _) => kv,
+            (None, fd) => {
+                let key = &rest[..fd.unwrap_or(rest.len())];
+                insert_value(&mut map, key, json::Value::Bool(true));
+                rest = &rest[key.len()..];
+                continue;
+            }
+        };
+        let key = &rest[..key_end];
+        rest = &rest[key_end + key_value_delimiter.len()..];
+        let val = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            rest = quoted.get(end + 1..).unwrap_or_default();
+            &quoted[..end]
+        } else {
+            let end = rest.find(field_delimiter).unwrap_or(rest.len());
+            let val = &rest[..end];
+            rest = &rest[end..];
+            val
+        };
+        insert_value(&mut map, key, json::Value::String(val.to_string()));
+    }
+    map
+}
+
+fn insert_value(map: &mut json::Map<String, json::Value>, key: &str, value: json::Value) {
+    let key = key.trim();
+    if key.is_empty() {
+        return;
+    }
+    match map.get_mut(key) {
+        None => {
+            map.insert(key.to_string(), value);
+        }
+        Some(json::Value::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = json::Value::Array(vec![first, value]);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ParseCsvHeader;
+
+impl Function for ParseCsvHeader {
+    fn identifier(&self) -> &'static str {
+        "parse_csv_header"
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[
+            Parameter {
+                keyword: "value",
+                kind: kind::BYTES,
+                required: true,
+            },
+            Parameter {
+                keyword: "header",
+                kind: kind::ARRAY,
+                required: true,
+            },
+            Parameter {
+                keyword: "delimiter",
+                kind: kind::BYTES,
+                required: false,
+            },
+        ]
+    }
+
+    fn examples(&self) -> &'static [Example] {
+        &[Example {
+            title: "haproxy csv stats row",
+            source: r#"parse_csv_header!("web,FRONTEND,12", ["pxname", "svname", "scur"])"#,
+            result: Ok(r#"{"pxname": "web", "scur": "12", "svname": "FRONTEND"}"#),
+        }]
+    }
+
+    fn compile(
+        &self,
+        _state: &state::TypeState,
+        _ctx: &mut FunctionCompileContext,
+        arguments: ArgumentList,
+    ) -> Compiled {
+        Ok(ParseCsvHeaderFn {
+            value: arguments.required("value"),
+            header: arguments.required("header"),
+            delimiter: arguments.optional("delimiter"),
+        }
+        .as_expr())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ParseCsvHeaderFn {
+    value: Box<dyn Expression>,
+    header: Box<dyn Expression>,
+    delimiter: Option<Box<dyn Expression>>,
+}
+
+impl FunctionExpression for ParseCsvHeaderFn {
+    fn resolve(&self, ctx: &mut Context) -> Resolved {
+        let value = self.value.resolve(ctx)?;
+        let value = value.try_bytes_utf8_lossy()?;
+        let header = self
+            .header
+            .resolve(ctx)?
+            .try_array()?
+            .into_iter()
+            .map(|v| Ok(v.try_bytes_utf8_lossy()?.into_owned()))
+            .collect::<Result<Vec<_>, ExpressionError>>()?;
+        let delimiter = resolve_string(&self.delimiter, ctx, ",")?;
+        let [delimiter] = delimiter.as_bytes() else {
+            return Err("delimiter must be a single byte".into());
+        };
+        let map = parse_csv_header(&value, &header, *delimiter).map_err(|e| e.to_string())?;
+        Ok(Value::from(&json::Value::Object(map)))
+    }
+
+    fn type_def(&self, _: &state::TypeState) -> TypeDef {
+        TypeDef::object(Collection::any()).fallible()
+    }
+}
+
+/// Parses a csv row into the columns of the header, the fields without a
+/// column are dropped.
+fn parse_csv_header(
+    value: &str,
+    header: &[String],
+    delimiter: u8,
+) -> Result<json::Map<String, json::Value>, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(value.as_bytes());
+    let mut map = json::Map::new();
+    if let Some(record) = reader.records().next() {
+        for (name, field) in header.iter().zip(record?.iter()) {
+            map.insert(name.to_string(), json::Value::String(field.to_string()));
+        }
+    }
+    Ok(map)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ParseMultiline;
+
+impl Function for ParseMultiline {
+    fn identifier(&self) -> &'static str {
+        "parse_multiline"
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[
+            Parameter {
+                keyword: "value",
+                kind: kind::BYTES,
+                required: true,
+            },
+            Parameter {
+                keyword: "pattern",
+                kind: kind::REGEX,
+                required: true,
+            },
+        ]
+    }
+
+    fn examples(&self) -> &'static [Example] {
+        &[Example {
+            title: "stack trace",
+            source: r#"parse_multiline("2024 error\n  at main\n2024 done", r'^\d{4} ')"#,
+            result: Ok(r#"["2024 error\n  at main", "2024 done"]"#),
+        }]
+    }
+
+    fn compile(
+        &self,
+        state: &state::TypeState,
+        _ctx: &mut FunctionCompileContext,
+        mut arguments: ArgumentList,
+    ) -> Compiled {
+        Ok(ParseMultilineFn {
+            value: arguments.required("value"),
+            pattern: arguments.required_regex("pattern", state)?,
+        }
+        .as_expr())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ParseMultilineFn {
+    value: Box<dyn Expression>,
+    pattern: Regex,
+}
+
+impl FunctionExpression for ParseMultilineFn {
+    fn resolve(&self, ctx: &mut Context) -> Resolved {
+        let value = self.value.resolve(ctx)?;
+        let value = value.try_bytes_utf8_lossy()?;
+        let records = parse_multiline(&value, &self.pattern)
+            .into_iter()
+            .map(Value::from)
+            .collect::<Vec<_>>();
+        Ok(Value::Array(records))
+    }
+
+    fn type_def(&self, _: &state::TypeState) -> TypeDef {
+        TypeDef::array(Collection::any())
+    }
+}
+
+/// Splits the value into records, every line matching the pattern starts a
+/// new record and the other lines continue the previous one.
+fn parse_multiline(value: &str, pattern: &Regex) -> Vec<String> {
+    let mut records: Vec<String> = Vec::new();
+    for line in value.lines() {
+        match records.last_mut() {
+            Some(record) if !pattern.is_match(line) => {
+                record.push('\n');
+                record.push_str(line);
+            }
+            _ => records.push(line.to_string()),
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keyvalue() {
+        let map = parse_keyvalue(
+            r#"type=USER_LOGIN  msg="op=login acct=root" ip=1.1.1.1 ip=2.2.2.2 debug"#,
+            " ",
+            "=",
+        );
+        assert_eq!(map["type"], "USER_LOGIN");
+        assert_eq!(map["msg"], "op=login acct=root");
+        assert_eq!(map["ip"], json::json!(["1.1.1.1", "2.2.2.2"]));
+        assert_eq!(map["debug"], true);
+
+        let map = parse_keyvalue("a: 1 | b: 2", " | ", ": ");
+        assert_eq!(map["a"], "1");
+        assert_eq!(map["b"], "2");
+    }
+
+    #[test]
+    fn test_parse_csv_header() {
+        let header = vec!["pxname".to_string(), "svname".to_string()];
+        let map = parse_csv_header(r#"web,"FRONT,END",12"#, &header, b',').unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["svname"], "FRONT,END");
+    }
+
+    #[test]
+    fn test_parse_multiline() {
+        let pattern = Regex::new(r"^\d{4} ").unwrap();
+        let records = parse_multiline("2024 error\n  at main\n  at run\n2024 done", &pattern);
+        assert_eq!(
+            records,
+            vec!["2024 error\n  at main\n  at run", "2024 done"]
+        );
+    }
+}
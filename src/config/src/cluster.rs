@@ -13,7 +13,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::net::IpAddr;
+use std::{
+    net::IpAddr,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use once_cell::sync::Lazy;
 
@@ -27,6 +30,8 @@ pub static mut LOCAL_NODE_KEY_LEASE_ID: i64 = 0;
 pub static mut LOCAL_NODE_STATUS: NodeStatus = NodeStatus::Prepare;
 pub static LOCAL_NODE_UUID: Lazy<String> = Lazy::new(load_local_node_uuid);
 pub static LOCAL_NODE_ROLE: Lazy<Vec<Role>> = Lazy::new(load_local_node_role);
+static LOCAL_NODE_DRAINING: AtomicBool = AtomicBool::new(false);
+static LOCAL_NODE_DEGRADED: AtomicBool = AtomicBool::new(false);
 
 #[inline(always)]
 pub fn load_local_node_uuid() -> String {
@@ -114,6 +119,29 @@ pub fn is_offline() -> bool {
     unsafe { LOCAL_NODE_STATUS == NodeStatus::Offline }
 }
 
+/// A draining node rejects new ingestion and uploads its wal right away.
+#[inline(always)]
+pub fn is_draining() -> bool {
+    LOCAL_NODE_DRAINING.load(Ordering::SeqCst)
+}
+
+#[inline(always)]
+pub fn set_draining(draining: bool) {
+    LOCAL_NODE_DRAINING.store(draining, Ordering::SeqCst)
+}
+
+/// A degraded ingester keeps the ingested data in its wal, the object store or
+/// the meta store failing.
+#[inline(always)]
+pub fn is_degraded() -> bool {
+    LOCAL_NODE_DEGRADED.load(Ordering::SeqCst)
+}
+
+#[inline(always)]
+pub fn set_degraded(degraded: bool) {
+    LOCAL_NODE_DEGRADED.store(degraded, Ordering::SeqCst)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -16,6 +16,15 @@
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Version of the internal grpc messages spoken by this build, bump it with a
+/// change of the messages the previous version can't decode.
+pub const GRPC_VERSION: u32 = 2;
+/// Oldest version of the internal grpc messages this build still decodes.
+pub const GRPC_MIN_VERSION: u32 = 1;
+/// Metadata key of the version a grpc request is encoded with.
+pub const GRPC_VERSION_HEADER: &str = "zo-grpc-version";
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Node {
@@ -31,6 +40,22 @@ pub struct Node {
     pub scheduled: bool,
     #[serde(default)]
     pub broadcasted: bool,
+    /// availability zone of the node, e.g. `us-east-1a`
+    #[serde(default)]
+    pub zone: String,
+    #[serde(default)]
+    pub region: String,
+    /// newest version of the internal grpc messages the node speaks
+    #[serde(default = "default_grpc_version")]
+    pub grpc_version: u32,
+    /// oldest version of the internal grpc messages the node decodes
+    #[serde(default = "default_grpc_version")]
+    pub grpc_min_version: u32,
+}
+
+// the nodes registered before the versions were advertised speak version 1
+fn default_grpc_version() -> u32 {
+    1
 }
 
 impl Node {
@@ -46,8 +71,25 @@ impl Node {
             status: NodeStatus::Prepare,
             scheduled: false,
             broadcasted: false,
+            zone: "".to_string(),
+            region: "".to_string(),
+            grpc_version: GRPC_VERSION,
+            grpc_min_version: GRPC_MIN_VERSION,
         }
     }
+
+    /// Returns the version of the grpc requests sent to the node, None when
+    /// the node and this build share no version.
+    pub fn negotiate_grpc_version(&self) -> Option<u32> {
+        negotiate_grpc_version(self.grpc_min_version, self.grpc_version)
+    }
+}
+
+/// Returns the newest version spoken by both this build and a peer speaking
+/// the versions from `min` to `max`.
+pub fn negotiate_grpc_version(min: u32, max: u32) -> Option<u32> {
+    let version = GRPC_VERSION.min(max);
+    (version >= GRPC_MIN_VERSION.max(min)).then_some(version)
 }
 
 impl Default for Node {
@@ -56,6 +98,77 @@ impl Default for Node {
     }
 }
 
+/// Load of a node, reported with its heartbeat.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NodeMetrics {
+    pub cpu_num: u64,
+    /// cpu usage in percent of all the cores
+    pub cpu_usage: f32,
+    pub memory_total: u64,
+    pub memory_usage: u64,
+    /// bytes in the memtables and in the wal files waiting for upload
+    pub wal_backlog_bytes: u64,
+    /// ratio of the searched files found in the cache since the last heartbeat
+    pub cache_hit_rate: f64,
+    pub running_queries: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeHeartbeat {
+    pub uuid: String,
+    pub version: String,
+    /// microseconds
+    pub started_at: i64,
+    /// microseconds
+    pub updated_at: i64,
+    pub metrics: NodeMetrics,
+}
+
+/// A member of the cluster with its last heartbeat.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct NodeInfo {
+    pub uuid: String,
+    pub name: String,
+    pub http_addr: String,
+    pub grpc_addr: String,
+    #[schema(value_type = Vec<String>)]
+    pub role: Vec<Role>,
+    pub zone: String,
+    pub region: String,
+    #[schema(value_type = String)]
+    pub status: NodeStatus,
+    pub scheduled: bool,
+    pub version: String,
+    /// seconds since the node started
+    pub uptime: i64,
+    /// microseconds, 0 if the node never reported
+    pub last_heartbeat: i64,
+    /// the node missed its last heartbeats
+    pub stale: bool,
+    pub metrics: NodeMetrics,
+}
+
+/// Whether a search only uses the queriers in the zone of the node it runs on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZoneLocality {
+    /// any querier of the cluster
+    Disabled,
+    /// the queriers in the zone, or any querier if the zone has none online
+    Prefer,
+    /// only the queriers in the zone, the search fails if the zone has none
+    Require,
+}
+
+impl From<&str> for ZoneLocality {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "prefer" => ZoneLocality::Prefer,
+            "require" => ZoneLocality::Require,
+            _ => ZoneLocality::Disabled,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum NodeStatus {
     Prepare,
@@ -104,3 +217,26 @@ impl std::fmt::Display for Role {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_grpc_version() {
+        assert_eq!(negotiate_grpc_version(1, 1), Some(1));
+        assert_eq!(
+            negotiate_grpc_version(GRPC_MIN_VERSION, GRPC_VERSION),
+            Some(GRPC_VERSION)
+        );
+        assert_eq!(
+            negotiate_grpc_version(1, GRPC_VERSION + 1),
+            Some(GRPC_VERSION)
+        );
+        // the peer dropped all the versions this build speaks
+        assert_eq!(
+            negotiate_grpc_version(GRPC_VERSION + 1, GRPC_VERSION + 2),
+            None
+        );
+    }
+}
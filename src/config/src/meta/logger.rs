@@ -15,6 +15,8 @@
 
 // refer: https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/trait.FormatEvent.html#examples
 
+use std::future::Future;
+
 use chrono::{Local, Utc};
 use tracing::{Event, Subscriber};
 use tracing_log::NormalizeEvent;
@@ -27,6 +29,40 @@ use tracing_subscriber::{
     registry::LookupSpan,
 };
 
+/// The header carrying the id of a request, across the nodes too.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Returns the id of the request handled by the current task.
+pub fn get_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Returns the request id sent by the client, or a new one when it sent none
+/// or an id that isn't a short token.
+pub fn parse_request_id(header: Option<&str>) -> String {
+    match header {
+        Some(id)
+            if !id.is_empty()
+                && id.len() <= 128
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')) =>
+        {
+            id.to_string()
+        }
+        _ => crate::ider::uuid(),
+    }
+}
+
+/// Runs the future as part of the request, its log lines carry the id.
+pub async fn with_request_id<F: Future>(request_id: String, f: F) -> F::Output {
+    REQUEST_ID.scope(request_id, f).await
+}
+
 pub struct CustomTimeFormat;
 
 impl FormatTime for CustomTimeFormat {
@@ -74,6 +110,9 @@ where
         let meta = normalized_meta.as_ref().unwrap_or_else(|| event.metadata());
         self.timer.format_time(&mut writer)?;
         write!(&mut writer, " {} {}: ", meta.level(), meta.target())?;
+        if let Ok(ret) = REQUEST_ID.try_with(|id| write!(&mut writer, "[request_id {id}] ")) {
+            ret?;
+        }
 
         // Write fields on the event
         ctx.field_format().format_fields(writer.by_ref(), event)?;
@@ -81,3 +120,20 @@ where
         writeln!(writer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_id() {
+        assert_eq!(parse_request_id(Some("abc-123_x.y:z")), "abc-123_x.y:z");
+        assert_ne!(parse_request_id(Some("a b")), "a b");
+        assert!(!parse_request_id(Some("")).is_empty());
+        assert!(!parse_request_id(None).is_empty());
+
+        assert_eq!(get_request_id(), None);
+        let id = with_request_id("abc".to_string(), async { get_request_id() }).await;
+        assert_eq!(id.as_deref(), Some("abc"));
+    }
+}
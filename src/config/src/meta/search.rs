@@ -121,6 +121,9 @@ pub struct Query {
     pub query_fn: Option<String>,
     #[serde(default)]
     pub skip_wal: bool,
+    /// Returns the character offsets of the full text matches in each hit
+    #[serde(default)]
+    pub highlight: bool,
 }
 
 fn default_size() -> i64 {
@@ -144,6 +147,7 @@ impl Default for Query {
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            highlight: false,
         }
     }
 }
@@ -361,6 +365,43 @@ pub struct SearchPartitionResponse {
     pub partitions: Vec<[i64; 2]>,
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchExplainResponse {
+    pub trace_id: String,
+    /// The sql after the rewrite, as it is planned
+    pub sql: String,
+    pub time_range: [i64; 2],
+    pub logical_plan: String,
+    pub physical_plan: String,
+    /// Total of the files left after pruning
+    pub file_num: usize,
+    pub records: usize,
+    pub original_size: usize,
+    pub compressed_size: usize,
+    pub partitions: Vec<SearchExplainPartition>,
+}
+
+/// The files of one partition directory that would be scanned.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SearchExplainPartition {
+    pub prefix: String,
+    pub file_num: usize,
+    pub records: usize,
+    pub original_size: usize,
+    pub compressed_size: usize,
+    pub files: Vec<SearchExplainFile>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SearchExplainFile {
+    pub key: String,
+    pub min_ts: i64,
+    pub max_ts: i64,
+    pub records: usize,
+    pub original_size: usize,
+    pub compressed_size: usize,
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct QueryStatusResponse {
     pub status: Vec<QueryStatus>,
@@ -392,6 +433,29 @@ pub struct CancelQueryResponse {
     pub is_success: bool,
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RunningQueriesResponse {
+    pub queries: Vec<RunningQuery>,
+}
+
+/// A query in flight, summed over the nodes running it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RunningQuery {
+    pub trace_id: String,
+    pub org_id: String,
+    pub stream_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    pub sql: String,
+    pub started_at: i64,
+    /// Bytes reserved by datafusion for the query
+    pub memory_size: i64,
+    /// Microseconds spent polling the query tasks
+    pub cpu_time: i64,
+    pub nodes: usize,
+    pub is_cancelled: bool,
+}
+
 #[derive(Clone, Debug, Copy, Default, Serialize, Deserialize, ToSchema)]
 pub struct ScanStats {
     pub files: i64,
@@ -497,6 +561,42 @@ impl From<&cluster_rpc::ScanStats> for ScanStats {
     }
 }
 
+/// A message of a search streamed over websocket, the hits of the newest
+/// partitions of the time range come first.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamingEvent {
+    /// The hits of a partition [start_time, end_time).
+    Hits {
+        partition: usize,
+        start_time: i64,
+        end_time: i64,
+        response: Response,
+    },
+    /// Sent after each partition, took is in milliseconds since the start.
+    Progress {
+        trace_id: String,
+        partitions: usize,
+        partitions_done: usize,
+        hits: usize,
+        scan_size: usize,
+        scan_records: usize,
+        took: usize,
+    },
+    /// The search stopped, no more messages follow for it.
+    Error {
+        trace_id: String,
+        code: u16,
+        message: String,
+    },
+    /// The search is done, no more messages follow for it.
+    End {
+        trace_id: String,
+        hits: usize,
+        took: usize,
+    },
+}
+
 #[derive(Hash, Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SearchEventType {
     UI,
@@ -506,6 +606,7 @@ pub enum SearchEventType {
     Values,
     Other,
     RUM,
+    DerivedStream,
 }
 
 impl std::fmt::Display for SearchEventType {
@@ -518,6 +619,7 @@ impl std::fmt::Display for SearchEventType {
             SearchEventType::Other => write!(f, "Other"),
             SearchEventType::Values => write!(f, "_values"),
             SearchEventType::RUM => write!(f, "RUM"),
+            SearchEventType::DerivedStream => write!(f, "DerivedStream"),
         }
     }
 }
@@ -607,6 +709,7 @@ mod tests {
                 uses_zo_fn: false,
                 query_fn: None,
                 skip_wal: false,
+                highlight: false,
             },
             aggs: HashMap::new(),
             encoding: "base64".into(),
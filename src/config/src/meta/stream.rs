@@ -423,6 +423,36 @@ pub struct StreamSettings {
     pub flatten_level: Option<i64>,
     #[serde(skip_serializing_if = "Option::None")]
     pub defined_schema_fields: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::None")]
+    pub flush_policy: Option<FlushPolicy>,
+    /// fields left out of the schema and the search results, the data stays
+    #[serde(default)]
+    pub hidden_fields: Vec<String>,
+    /// names the fields are searched and shown by, alias -> field
+    #[serde(default)]
+    pub field_aliases: HashMap<String, String>,
+    /// max fields of the schema, 0 uses `ZO_SCHEMA_MAX_FIELDS`
+    #[serde(default)]
+    pub max_fields: usize,
+    #[serde(skip_serializing_if = "Option::None")]
+    pub fields_overflow: Option<FieldsOverflow>,
+    /// rejects the records changing the type of a field until the change is
+    /// approved, instead of widening the field
+    #[serde(default)]
+    pub strict_schema: bool,
+    /// logical types of the string fields, field -> type
+    #[serde(default)]
+    pub field_types: HashMap<String, FieldType>,
+    /// regex with named groups, or `%{NAME:field}` grok patterns, extracting
+    /// fields from the lines ingested as raw text
+    #[serde(skip_serializing_if = "Option::None")]
+    pub line_pattern: Option<String>,
+    /// built-in parser of the records, mapping a known log format into fields
+    #[serde(skip_serializing_if = "Option::None")]
+    pub log_format: Option<LogFormat>,
+    /// microseconds, the ephemeral stream is deleted with its data once expired
+    #[serde(skip_serializing_if = "Option::None")]
+    pub expires_at: Option<i64>,
 }
 
 impl Serialize for StreamSettings {
@@ -464,10 +494,103 @@ impl Serialize for StreamSettings {
                 state.skip_field("flatten_level")?;
             }
         }
+        match self.flush_policy.as_ref() {
+            Some(flush_policy) if !flush_policy.is_empty() => {
+                state.serialize_field("flush_policy", flush_policy)?;
+            }
+            _ => {
+                state.skip_field("flush_policy")?;
+            }
+        }
+        if !self.hidden_fields.is_empty() {
+            state.serialize_field("hidden_fields", &self.hidden_fields)?;
+        } else {
+            state.skip_field("hidden_fields")?;
+        }
+        if !self.field_aliases.is_empty() {
+            state.serialize_field("field_aliases", &self.field_aliases)?;
+        } else {
+            state.skip_field("field_aliases")?;
+        }
+        if self.max_fields > 0 {
+            state.serialize_field("max_fields", &self.max_fields)?;
+        } else {
+            state.skip_field("max_fields")?;
+        }
+        match self.fields_overflow.as_ref() {
+            Some(fields_overflow) => {
+                state.serialize_field("fields_overflow", fields_overflow)?;
+            }
+            None => {
+                state.skip_field("fields_overflow")?;
+            }
+        }
+        if self.strict_schema {
+            state.serialize_field("strict_schema", &self.strict_schema)?;
+        } else {
+            state.skip_field("strict_schema")?;
+        }
+        if !self.field_types.is_empty() {
+            state.serialize_field("field_types", &self.field_types)?;
+        } else {
+            state.skip_field("field_types")?;
+        }
+        match self.line_pattern.as_ref() {
+            Some(line_pattern) => {
+                state.serialize_field("line_pattern", line_pattern)?;
+            }
+            None => {
+                state.skip_field("line_pattern")?;
+            }
+        }
+        match self.log_format.as_ref() {
+            Some(log_format) => {
+                state.serialize_field("log_format", log_format)?;
+            }
+            None => {
+                state.skip_field("log_format")?;
+            }
+        }
+        match self.expires_at.as_ref() {
+            Some(expires_at) => {
+                state.serialize_field("expires_at", expires_at)?;
+            }
+            None => {
+                state.skip_field("expires_at")?;
+            }
+        }
         state.end()
     }
 }
 
+/// Memtable flush triggers of a stream, checked in addition to the global
+/// limits. A zero value means the trigger is not set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FlushPolicy {
+    /// MB of json data buffered for the stream
+    #[serde(default)]
+    pub max_size: usize,
+    /// number of records buffered for the stream
+    #[serde(default)]
+    pub max_records: usize,
+    /// seconds since the first buffered record of the stream
+    #[serde(default)]
+    pub max_age: u64,
+}
+
+impl FlushPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.max_size == 0 && self.max_records == 0 && self.max_age == 0
+    }
+
+    /// Returns true if the buffered data of the stream has to be flushed
+    pub fn is_exceeded(&self, json_size: usize, records: usize, age_micros: i64) -> bool {
+        (self.max_size > 0 && json_size >= self.max_size * 1024 * 1024)
+            || (self.max_records > 0 && records >= self.max_records)
+            || (self.max_age > 0 && age_micros >= self.max_age as i64 * 1_000_000)
+    }
+}
+
 impl From<&str> for StreamSettings {
     fn from(data: &str) -> Self {
         let settings: json::Value = json::from_slice(data.as_bytes()).unwrap();
@@ -534,6 +657,52 @@ impl From<&str> for StreamSettings {
 
         let flatten_level = settings.get("flatten_level").map(|v| v.as_i64().unwrap());
 
+        let flush_policy = settings
+            .get("flush_policy")
+            .and_then(|v| json::from_value::<FlushPolicy>(v.clone()).ok())
+            .filter(|v| !v.is_empty());
+
+        let hidden_fields = settings
+            .get("hidden_fields")
+            .and_then(|v| json::from_value::<Vec<String>>(v.clone()).ok())
+            .unwrap_or_default();
+
+        let field_aliases = settings
+            .get("field_aliases")
+            .and_then(|v| json::from_value::<HashMap<String, String>>(v.clone()).ok())
+            .unwrap_or_default();
+
+        let max_fields = settings
+            .get("max_fields")
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default() as usize;
+
+        let fields_overflow = settings
+            .get("fields_overflow")
+            .and_then(|v| json::from_value::<FieldsOverflow>(v.clone()).ok());
+
+        let strict_schema = settings
+            .get("strict_schema")
+            .and_then(|v| v.as_bool())
+            .unwrap_or_default();
+
+        let field_types = settings
+            .get("field_types")
+            .and_then(|v| json::from_value::<HashMap<String, FieldType>>(v.clone()).ok())
+            .unwrap_or_default();
+
+        let line_pattern = settings
+            .get("line_pattern")
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string());
+
+        let log_format = settings
+            .get("log_format")
+            .and_then(|v| json::from_value::<LogFormat>(v.clone()).ok());
+
+        let expires_at = settings.get("expires_at").and_then(|v| v.as_i64());
+
         Self {
             partition_keys,
             partition_time_level,
@@ -542,10 +711,92 @@ impl From<&str> for StreamSettings {
             data_retention,
             flatten_level,
             defined_schema_fields,
+            flush_policy,
+            hidden_fields,
+            field_aliases,
+            max_fields,
+            fields_overflow,
+            strict_schema,
+            field_types,
+            line_pattern,
+            log_format,
+            expires_at,
+        }
+    }
+}
+
+/// Logical type of a string field, the searches compare and sort the values
+/// with the functions of the type, e.g. `ip_in_cidr`, `ip_sort_key`,
+/// `duration_to_micros` and `uuid_normalize`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    IpAddr,
+    Uuid,
+    Duration,
+}
+
+/// Log format of the records of a stream, parsed into typed fields at
+/// ingestion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// CloudTrail events, alone or in the `Records` of a delivered file
+    #[serde(rename = "cloudtrail")]
+    CloudTrail,
+    /// VPC Flow Logs lines of the default format
+    VpcFlow,
+    /// Application Load Balancer access logs lines
+    Alb,
+    /// Windows Event Log xml, as shipped by winlogbeat or NXLog
+    WindowsEvent,
+}
+
+/// What happens to the new fields of a stream once its schema has the max
+/// fields.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldsOverflow {
+    /// the new fields are kept as json in the `_extra` field
+    #[default]
+    Extra,
+    /// the records with new fields are rejected
+    Reject,
+}
+
+impl From<&str> for FieldsOverflow {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "reject" => FieldsOverflow::Reject,
+            _ => FieldsOverflow::Extra,
         }
     }
 }
 
+impl StreamSettings {
+    /// Returns the field an alias stands for, or the name itself.
+    pub fn resolve_alias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.field_aliases
+            .get(name)
+            .map(|v| v.as_str())
+            .unwrap_or(name)
+    }
+
+    /// Returns the alias a field is shown by.
+    pub fn alias_of(&self, field: &str) -> Option<&str> {
+        self.field_aliases
+            .iter()
+            .find(|(_, v)| v.as_str() == field)
+            .map(|(k, _)| k.as_str())
+    }
+
+    /// Returns true if the stream is ephemeral and expired at `now`, in
+    /// microseconds.
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|v| v <= now)
+    }
+}
+
 #[derive(Clone, Debug, Default, Hash, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct StreamPartition {
     pub field: String,
@@ -810,4 +1061,40 @@ mod tests {
         assert_eq!(part.get_partition_key("test2"), "field=4");
         assert_eq!(part.get_partition_key("test3"), "field=2");
     }
+
+    #[test]
+    fn test_expires_at() {
+        let settings = StreamSettings {
+            expires_at: Some(100),
+            ..Default::default()
+        };
+        assert!(!settings.is_expired(99));
+        assert!(settings.is_expired(100));
+        assert!(!StreamSettings::default().is_expired(i64::MAX));
+
+        let data = json::to_string(&settings).unwrap();
+        assert_eq!(StreamSettings::from(data.as_str()).expires_at, Some(100));
+        let data = json::to_string(&StreamSettings::default()).unwrap();
+        assert!(!data.contains("expires_at"));
+    }
+
+    #[test]
+    fn test_flush_policy() {
+        let policy = FlushPolicy {
+            max_size: 1,
+            max_records: 100,
+            max_age: 10,
+        };
+        assert!(!policy.is_exceeded(1024, 10, 1_000_000));
+        assert!(policy.is_exceeded(1024 * 1024, 10, 1_000_000));
+        assert!(policy.is_exceeded(1024, 100, 1_000_000));
+        assert!(policy.is_exceeded(1024, 10, 10_000_000));
+        assert!(!FlushPolicy::default().is_exceeded(usize::MAX, usize::MAX, i64::MAX));
+
+        let settings =
+            StreamSettings::from(r#"{"partition_keys":{},"flush_policy":{"max_records":100}}"#);
+        let flush_policy = settings.flush_policy.unwrap();
+        assert_eq!(flush_policy.max_records, 100);
+        assert_eq!(flush_policy.max_age, 0);
+    }
 }
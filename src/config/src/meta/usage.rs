@@ -24,6 +24,8 @@ use crate::{
 pub const USAGE_STREAM: &str = "usage";
 pub const STATS_STREAM: &str = "stats";
 pub const TRIGGERS_USAGE_STREAM: &str = "triggers";
+/// Stream of the usage organization holding the hourly usage of each org
+pub const USAGE_ROLLUP_STREAM: &str = "_meta_usage";
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TriggerDataStatus {
@@ -41,6 +43,8 @@ pub enum TriggerDataType {
     Report,
     #[serde(rename = "alert")]
     Alert,
+    #[serde(rename = "derived_stream")]
+    DerivedStream,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -89,6 +93,46 @@ pub struct UsageData {
     pub search_type: Option<SearchEventType>,
 }
 
+/// Usage of an organization during an hour. Each node meters its own share,
+/// the shares are summed when read, except the storage which is the largest
+/// size seen during the hour.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct UsageRollup {
+    pub org_id: String,
+    /// start of the hour, in microseconds
+    pub hour: i64,
+    #[serde(default)]
+    pub ingested_bytes: i64,
+    #[serde(default)]
+    pub ingested_records: i64,
+    #[serde(default)]
+    pub searches: i64,
+    #[serde(default)]
+    pub scanned_bytes: i64,
+    /// compressed size of the streams of the organization
+    #[serde(default)]
+    pub storage_bytes: i64,
+}
+
+impl UsageRollup {
+    pub fn new(org_id: &str, hour: i64) -> Self {
+        UsageRollup {
+            org_id: org_id.to_string(),
+            hour,
+            ..Default::default()
+        }
+    }
+
+    /// Adds the usage metered in the same hour by another node, or later.
+    pub fn merge(&mut self, other: &UsageRollup) {
+        self.ingested_bytes += other.ingested_bytes;
+        self.ingested_records += other.ingested_records;
+        self.searches += other.searches;
+        self.scanned_bytes += other.scanned_bytes;
+        self.storage_bytes = self.storage_bytes.max(other.storage_bytes);
+    }
+}
+
 #[derive(Hash, PartialEq, Eq)]
 pub struct GroupKey {
     pub stream_name: String,
@@ -282,3 +326,29 @@ pub struct Stats {
     #[serde(default)]
     pub compressed_size: Option<f64>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_rollup_merge() {
+        let mut rollup = UsageRollup::new("default", 0);
+        rollup.ingested_bytes = 10;
+        rollup.storage_bytes = 100;
+        let other = UsageRollup {
+            ingested_bytes: 5,
+            ingested_records: 2,
+            searches: 1,
+            scanned_bytes: 50,
+            storage_bytes: 80,
+            ..UsageRollup::new("default", 0)
+        };
+        rollup.merge(&other);
+        assert_eq!(rollup.ingested_bytes, 15);
+        assert_eq!(rollup.ingested_records, 2);
+        assert_eq!(rollup.searches, 1);
+        assert_eq!(rollup.scanned_bytes, 50);
+        assert_eq!(rollup.storage_bytes, 100);
+    }
+}
@@ -13,6 +13,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+//! The metrics of the node, under the `zo_` namespace with the `cluster`,
+//! `instance` and `role` labels. The names and labels below are stable, the
+//! dashboards and the alerts can rely on them:
+//!
+//! - `http_request_duration_seconds{handler, method, status, organization, stream}`: the latency of
+//!   the HTTP requests by handler, the route pattern they matched, with the traces as exemplars.
+//! - `http_incoming_requests`, `http_response_time`, `grpc_incoming_requests`,
+//!   `grpc_response_time`: the requests of the search and ingestion endpoints, by `endpoint`.
+//! - `ingest_*`: the records, the wal and the memtables of the ingesters.
+//! - `query_*`, `cache_*`, `storage_*`: the caches of the queriers and the object store.
+//! - `compact_*`: the compactor.
+//!
+//! The `organization` and `stream` labels are bound by `ZO_METRICS_ORGS_ALLOWLIST` and
+//! `ZO_METRICS_STREAMS_ALLOWLIST`, see [`crate::openmetrics`].
+
 use std::collections::HashMap;
 
 use actix_web_prometheus::{PrometheusMetrics, PrometheusMetricsBuilder};
@@ -21,11 +36,32 @@ use prometheus::{
     CounterVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
 };
 
+use crate::openmetrics::Relabeled;
+
 pub const NAMESPACE: &str = "zo";
 const HELP_SUFFIX: &str =
     "Please include 'organization, 'stream type', and 'stream' labels for this metric.";
 
+/// The metrics below, exported with the allowlists applied.
+pub static REGISTRY: Lazy<Registry> = Lazy::new(|| {
+    let registry = Registry::new();
+    register_metrics(&registry);
+    registry
+});
+
 // http latency
+pub static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "http_request_duration_seconds",
+            "HTTP request latency by handler, the route pattern of the request.",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["handler", "method", "status", "organization", "stream"],
+    )
+    .expect("Metric created")
+});
 pub static HTTP_INCOMING_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
     IntCounterVec::new(
         Opts::new(
@@ -159,6 +195,34 @@ pub static INGEST_WAL_READ_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static INGEST_DEGRADED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "ingest_degraded",
+            "Ingestor keeping the ingested data in the wal only, the object store or the meta store failing. "
+                .to_owned()
+                + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_DEDUP_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_dedup_bytes",
+            "Ingestor bytes of the files not uploaded, having the content of an uploaded file. "
+                .to_owned()
+                + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream_type"],
+    )
+    .expect("Metric created")
+});
 pub static INGEST_MEMTABLE_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
         Opts::new(
@@ -195,6 +259,89 @@ pub static INGEST_MEMTABLE_FILES: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static INGEST_EVENT_LAG: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "ingest_event_lag_seconds",
+            "Seconds between the timestamp of the records and their ingestion, negative in the future. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .buckets(vec![
+            -3600.0, -60.0, 0.0, 1.0, 10.0, 60.0, 300.0, 900.0, 3600.0, 21600.0, 86400.0,
+        ])
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "stream_type"],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_SKEWED_RECORDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_skewed_records",
+            "Records with a timestamp outside of the skew window of the ingest time. ".to_owned()
+                + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "stream_type", "skew", "policy"],
+    )
+    .expect("Metric created")
+});
+
+// function stats
+pub static FUNCTION_INVOCATIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "function_invocations",
+            "Function invocations. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "function"],
+    )
+    .expect("Metric created")
+});
+pub static FUNCTION_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "function_errors",
+            "Function runtime errors. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "function"],
+    )
+    .expect("Metric created")
+});
+pub static FUNCTION_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "function_time",
+            "Function execution time. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .buckets(vec![
+            0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1,
+        ])
+        .const_labels(create_const_labels()),
+        &["organization", "function"],
+    )
+    .expect("Metric created")
+});
+
+// ingest dlp
+pub static DLP_REDACTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "dlp_redactions",
+            "Values redacted by the DLP rules at ingest. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "rule"],
+    )
+    .expect("Metric created")
+});
 
 // querier memory cache stats
 pub static QUERY_MEMORY_CACHE_LIMIT_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
@@ -272,6 +419,223 @@ pub static QUERY_DISK_CACHE_FILES: Lazy<IntGaugeVec> = Lazy::new(|| {
     .expect("Metric created")
 });
 
+pub static QUERY_CACHE_HIT_FILES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_cache_hit_files",
+            "Querier files found in memory or disk cache",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_BATCH_CACHE_HITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_batch_cache_hits",
+            "Querier parquet row groups found decoded in the batch cache",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_BATCH_CACHE_MISSES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_batch_cache_misses",
+            "Querier parquet row groups decoded into the batch cache",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_BATCH_CACHE_USED_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_batch_cache_used_bytes",
+            "Querier memory used by the decoded row groups of the batch cache",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_CACHE_MISS_FILES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_cache_miss_files",
+            "Querier files downloaded from storage",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static CACHE_HITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("cache_hits", "Lookups found in the cache")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["cache"],
+    )
+    .expect("Metric created")
+});
+pub static CACHE_MISSES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("cache_misses", "Lookups not found in the cache")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["cache"],
+    )
+    .expect("Metric created")
+});
+pub static CACHE_EVICTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "cache_evictions",
+            "Entries evicted from the cache to make room",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["cache"],
+    )
+    .expect("Metric created")
+});
+pub static CACHE_ENTRIES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new("cache_entries", "Entries in the cache")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["cache"],
+    )
+    .expect("Metric created")
+});
+pub static CACHE_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new("cache_bytes", "Bytes used by the cache")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["cache"],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_RUNNING_NUMS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new("query_running_nums", "Querier running searches")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_QUEUED_NUMS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_queued_nums",
+            "Querier searches waiting for admission",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_QUEUE_DEPTH: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_queue_depth",
+            "Searches waiting in the queue of the organization",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization"],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_QUEUE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "query_queue_time_seconds",
+            "Seconds a search waited in the queue of the organization",
+        )
+        .namespace(NAMESPACE)
+        .buckets(vec![
+            0.001, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+        ])
+        .const_labels(create_const_labels()),
+        &["organization"],
+    )
+    .expect("Metric created")
+});
+pub static STORAGE_GETS_QUEUED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "storage_gets_queued",
+            "Object store downloads waiting for a slot",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_WAL_BUDGET_USED_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "ingest_wal_budget_used_bytes",
+            "Ingester wal and pending files bytes counted against ZO_INGESTER_WAL_MAX_SIZE",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_SPILL_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "ingest_spill_bytes",
+            "Ingester spilled bytes waiting to be written into the memtables",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_SPILL_FILES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "ingest_spill_files",
+            "Ingester spill files waiting to be written into the memtables",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static ADMISSION_REJECTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "admission_rejected",
+            "Requests shed by the admission control",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["kind"],
+    )
+    .expect("Metric created")
+});
+
 // compactor stats
 pub static COMPACT_USED_TIME: Lazy<CounterVec> = Lazy::new(|| {
     CounterVec::new(
@@ -321,6 +685,18 @@ pub static COMPACT_DELAY_HOURS: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static LEADER_ELECTION_LEADER: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "leader_election_leader",
+            "Whether the node leads the singleton job. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["job"],
+    )
+    .expect("Metric created")
+});
 // TODO deletion / archiving stats
 
 // storage stats
@@ -528,6 +904,64 @@ pub static META_NUM_DASHBOARDS: Lazy<IntGaugeVec> = Lazy::new(|| {
     .expect("Metric created")
 });
 
+// org quota
+pub static ORG_QUOTA_LIMIT: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new("org_quota_limit", "Organization quota limit")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["organization", "resource"],
+    )
+    .expect("Metric created")
+});
+pub static ORG_QUOTA_USAGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new("org_quota_usage", "Organization quota usage")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["organization", "resource"],
+    )
+    .expect("Metric created")
+});
+
+// bucket ingestion
+pub static BUCKET_INGEST_OBJECTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "bucket_ingest_objects",
+            "Objects of the landing bucket, by status. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "status"],
+    )
+    .expect("Metric created")
+});
+pub static BUCKET_INGEST_POISON_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "bucket_ingest_poison_messages",
+            "Notifications of the landing bucket which can't be ingested. ".to_owned()
+                + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+
+// chaos
+pub static CHAOS_FAULTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("chaos_faults", "Faults injected by ZO_CHAOS_ENABLED")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["target", "operation", "fault"],
+    )
+    .expect("Metric created")
+});
+
 pub static MEMORY_USAGE: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
         Opts::new("memory_usage", "Process memory usage")
@@ -539,6 +973,9 @@ pub static MEMORY_USAGE: Lazy<IntGaugeVec> = Lazy::new(|| {
 });
 
 fn register_metrics(registry: &Registry) {
+    registry
+        .register(Box::new(HTTP_REQUEST_DURATION.clone()))
+        .expect("Metric registered");
     // http latency
     registry
         .register(Box::new(HTTP_INCOMING_REQUESTS.clone()))
@@ -571,6 +1008,12 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(INGEST_WAL_READ_BYTES.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_DEDUP_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_DEGRADED.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(INGEST_MEMTABLE_BYTES.clone()))
         .expect("Metric registered");
@@ -580,6 +1023,28 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(INGEST_MEMTABLE_FILES.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_EVENT_LAG.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_SKEWED_RECORDS.clone()))
+        .expect("Metric registered");
+
+    // function stats
+    registry
+        .register(Box::new(FUNCTION_INVOCATIONS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(FUNCTION_ERRORS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(FUNCTION_TIME.clone()))
+        .expect("Metric registered");
+
+    // ingest dlp
+    registry
+        .register(Box::new(DLP_REDACTIONS.clone()))
+        .expect("Metric registered");
 
     // querier stats
     registry
@@ -600,6 +1065,63 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(QUERY_DISK_CACHE_FILES.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_CACHE_HIT_FILES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_CACHE_MISS_FILES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_BATCH_CACHE_HITS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_BATCH_CACHE_MISSES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_BATCH_CACHE_USED_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(CACHE_HITS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(CACHE_MISSES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(CACHE_EVICTIONS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(CACHE_ENTRIES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(CACHE_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_RUNNING_NUMS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_QUEUED_NUMS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_QUEUE_DEPTH.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_QUEUE_TIME.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_WAL_BUDGET_USED_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(STORAGE_GETS_QUEUED.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_SPILL_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_SPILL_FILES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(ADMISSION_REJECTED.clone()))
+        .expect("Metric registered");
 
     // compactor stats
     registry
@@ -614,6 +1136,9 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(COMPACT_DELAY_HOURS.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(LEADER_ELECTION_LEADER.clone()))
+        .expect("Metric registered");
 
     // storage stats
     registry
@@ -677,9 +1202,30 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(MEMORY_USAGE.clone()))
         .expect("Metric registered");
+
+    // org quota
+    registry
+        .register(Box::new(ORG_QUOTA_LIMIT.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(ORG_QUOTA_USAGE.clone()))
+        .expect("Metric registered");
+
+    // bucket ingestion
+    registry
+        .register(Box::new(BUCKET_INGEST_OBJECTS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(BUCKET_INGEST_POISON_MESSAGES.clone()))
+        .expect("Metric registered");
+
+    // chaos
+    registry
+        .register(Box::new(CHAOS_FAULTS.clone()))
+        .expect("Metric registered");
 }
 
-fn create_const_labels() -> HashMap<String, String> {
+pub(crate) fn create_const_labels() -> HashMap<String, String> {
     let cfg = crate::config::get_config();
     let mut labels = HashMap::new();
     labels.insert("cluster".to_string(), cfg.common.cluster_name.clone());
@@ -690,7 +1236,9 @@ fn create_const_labels() -> HashMap<String, String> {
 
 pub fn create_prometheus_handler() -> PrometheusMetrics {
     let registry = prometheus::Registry::new();
-    register_metrics(&registry);
+    registry
+        .register(Box::new(Relabeled(REGISTRY.clone())))
+        .expect("Metric registered");
 
     PrometheusMetricsBuilder::new(NAMESPACE)
         .endpoint(format!("{}/metrics", crate::config::get_config().common.base_uri).as_str())
@@ -699,3 +1247,8 @@ pub fn create_prometheus_handler() -> PrometheusMetrics {
         .build()
         .expect("Prometheus build failed")
 }
+
+/// The metrics in the OpenMetrics format, with the exemplars.
+pub fn openmetrics() -> String {
+    crate::openmetrics::encode(&REGISTRY)
+}
@@ -0,0 +1,477 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Exposition of the metrics of the node. The allowlists of organizations and
+//! streams bound the series exported by `/metrics`: the values out of them are
+//! exported as `_other` and the series they fold together are summed.
+//! `/openmetrics` exports the same series in the OpenMetrics format, with the
+//! traces of the requests as exemplars of the latency histograms.
+
+use std::collections::{HashMap, VecDeque};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use prometheus::{
+    core::{Collector, Desc},
+    proto::{Metric, MetricFamily, MetricType},
+    HistogramVec, Registry,
+};
+use regex::Regex;
+
+use crate::get_config;
+
+pub const OTHER_LABEL_VALUE: &str = "_other";
+
+/// The exemplars kept by series, the latest of each bucket is exported.
+const EXEMPLARS_PER_SERIES: usize = 16;
+
+static ORGS: Lazy<Allowlist> =
+    Lazy::new(|| Allowlist::new(&get_config().common.metrics_orgs_allowlist));
+static STREAMS: Lazy<Allowlist> =
+    Lazy::new(|| Allowlist::new(&get_config().common.metrics_streams_allowlist));
+
+static EXEMPLARS: Lazy<Mutex<HashMap<String, VecDeque<Exemplar>>>> = Lazy::new(Default::default);
+
+struct Allowlist(Option<Regex>);
+
+impl Allowlist {
+    fn new(patterns: &str) -> Self {
+        let patterns = patterns
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(|p| regex::escape(p).replace(r"\*", ".*"))
+            .collect::<Vec<_>>();
+        if patterns.is_empty() {
+            return Self(None);
+        }
+        Self(Some(
+            Regex::new(&format!("^(?:{})$", patterns.join("|"))).unwrap(),
+        ))
+    }
+
+    fn allows(&self, value: &str) -> bool {
+        match &self.0 {
+            Some(re) => value.is_empty() || re.is_match(value),
+            None => true,
+        }
+    }
+}
+
+struct Exemplar {
+    trace_id: String,
+    value: f64,
+    timestamp: f64,
+}
+
+/// Whether the value of the label is exported as is.
+fn is_allowed(name: &str, value: &str) -> bool {
+    match name {
+        "organization" => ORGS.allows(value),
+        "stream" => STREAMS.allows(value),
+        _ => true,
+    }
+}
+
+/// Collects the families of a registry with the allowlists applied.
+pub struct Relabeled(pub Registry);
+
+impl Collector for Relabeled {
+    fn desc(&self) -> Vec<&Desc> {
+        // the metrics are registered in the inner registry
+        vec![]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        relabel(self.0.gather(), &is_allowed)
+    }
+}
+
+/// Observes the latency, and keeps the trace as an exemplar of the series.
+pub fn observe_with_exemplar(
+    histogram: &HistogramVec,
+    name: &str,
+    labels: &[(&str, &str)],
+    value: f64,
+    trace_id: Option<&str>,
+) {
+    let values = labels.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+    histogram.with_label_values(&values).observe(value);
+    let Some(trace_id) = trace_id else {
+        return;
+    };
+    if !get_config().common.metrics_exemplars_enabled {
+        return;
+    }
+    let const_labels = crate::metrics::create_const_labels();
+    let mut pairs = const_labels
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .chain(labels.iter().copied())
+        .map(|(k, v)| {
+            (
+                k,
+                if is_allowed(k, v) {
+                    v
+                } else {
+                    OTHER_LABEL_VALUE
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    pairs.sort();
+    let key = series_key(&format!("{}_{name}", crate::metrics::NAMESPACE), &pairs);
+    let mut exemplars = EXEMPLARS.lock();
+    let series = exemplars.entry(key).or_default();
+    if series.len() >= EXEMPLARS_PER_SERIES {
+        series.pop_front();
+    }
+    series.push_back(Exemplar {
+        trace_id: trace_id.to_string(),
+        value,
+        timestamp: chrono::Utc::now().timestamp_micros() as f64 / 1_000_000.0,
+    });
+}
+
+fn series_key(name: &str, labels: &[(&str, &str)]) -> String {
+    let labels = labels
+        .iter()
+        .map(|(k, v)| format!("{k}={v:?}"))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{name}{{{labels}}}")
+}
+
+/// Replaces the values out of the allowlists by `_other`, and merges the
+/// series which have the same labels then.
+fn relabel(
+    mut families: Vec<MetricFamily>,
+    is_allowed: &dyn Fn(&str, &str) -> bool,
+) -> Vec<MetricFamily> {
+    for mf in families.iter_mut() {
+        let metric_type = mf.get_field_type();
+        let mut merged: Vec<Metric> = Vec::new();
+        let mut index: HashMap<Vec<(String, String)>, usize> = HashMap::new();
+        for mut metric in mf.take_metric().into_iter() {
+            for pair in metric.mut_label().iter_mut() {
+                if !is_allowed(pair.get_name(), pair.get_value()) {
+                    pair.set_value(OTHER_LABEL_VALUE.to_string());
+                }
+            }
+            let key = metric
+                .get_label()
+                .iter()
+                .map(|p| (p.get_name().to_string(), p.get_value().to_string()))
+                .collect::<Vec<_>>();
+            match index.get(&key) {
+                Some(&i) => merge(&mut merged[i], &metric, metric_type),
+                None => {
+                    index.insert(key, merged.len());
+                    merged.push(metric);
+                }
+            }
+        }
+        mf.set_metric(merged.into());
+    }
+    families
+}
+
+fn merge(into: &mut Metric, from: &Metric, metric_type: MetricType) {
+    match metric_type {
+        MetricType::COUNTER => {
+            let value = into.get_counter().get_value() + from.get_counter().get_value();
+            into.mut_counter().set_value(value);
+        }
+        MetricType::GAUGE => {
+            let value = into.get_gauge().get_value() + from.get_gauge().get_value();
+            into.mut_gauge().set_value(value);
+        }
+        MetricType::UNTYPED => {
+            let value = into.get_untyped().get_value() + from.get_untyped().get_value();
+            into.mut_untyped().set_value(value);
+        }
+        MetricType::HISTOGRAM => {
+            let from = from.get_histogram();
+            let into = into.mut_histogram();
+            into.set_sample_count(into.get_sample_count() + from.get_sample_count());
+            into.set_sample_sum(into.get_sample_sum() + from.get_sample_sum());
+            for (bucket, other) in into.mut_bucket().iter_mut().zip(from.get_bucket()) {
+                bucket.set_cumulative_count(
+                    bucket.get_cumulative_count() + other.get_cumulative_count(),
+                );
+            }
+        }
+        MetricType::SUMMARY => {
+            // the quantiles of several series can't be merged
+            let from = from.get_summary();
+            let into = into.mut_summary();
+            into.set_sample_count(into.get_sample_count() + from.get_sample_count());
+            into.set_sample_sum(into.get_sample_sum() + from.get_sample_sum());
+            into.mut_quantile().clear();
+        }
+    }
+}
+
+/// The metrics of the registry in the OpenMetrics text format. The counters
+/// are exported with the `_total` suffix the format requires.
+pub fn encode(registry: &Registry) -> String {
+    let families = relabel(registry.gather(), &is_allowed);
+    let exemplars = EXEMPLARS.lock();
+    encode_families(&families, &exemplars)
+}
+
+fn encode_families(
+    families: &[MetricFamily],
+    exemplars: &HashMap<String, VecDeque<Exemplar>>,
+) -> String {
+    let mut out = String::new();
+    for mf in families {
+        let name = mf.get_name();
+        let metric_type = mf.get_field_type();
+        let (family, type_name) = match metric_type {
+            MetricType::COUNTER => (name.strip_suffix("_total").unwrap_or(name), "counter"),
+            MetricType::GAUGE => (name, "gauge"),
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+        out.push_str(&format!("# TYPE {family} {type_name}\n"));
+        if !mf.get_help().is_empty() {
+            let help = mf.get_help().replace('\\', r"\\").replace('\n', r"\n");
+            out.push_str(&format!("# HELP {family} {help}\n"));
+        }
+        for metric in mf.get_metric() {
+            let labels = metric
+                .get_label()
+                .iter()
+                .map(|p| (p.get_name(), p.get_value()))
+                .collect::<Vec<_>>();
+            match metric_type {
+                MetricType::COUNTER => {
+                    let value = metric.get_counter().get_value();
+                    write_sample(&mut out, &format!("{family}_total"), &labels, value, None);
+                }
+                MetricType::GAUGE => {
+                    write_sample(
+                        &mut out,
+                        name,
+                        &labels,
+                        metric.get_gauge().get_value(),
+                        None,
+                    );
+                }
+                MetricType::UNTYPED => {
+                    let value = metric.get_untyped().get_value();
+                    write_sample(&mut out, name, &labels, value, None);
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let series = exemplars.get(&series_key(name, &labels));
+                    let exemplar = |lower: f64, upper: f64| {
+                        series.and_then(|s| {
+                            s.iter().rev().find(|e| e.value > lower && e.value <= upper)
+                        })
+                    };
+                    let bucket_name = format!("{name}_bucket");
+                    let mut lower = f64::NEG_INFINITY;
+                    for bucket in histogram.get_bucket() {
+                        let upper = bucket.get_upper_bound();
+                        let le = format_value(upper);
+                        let mut bucket_labels = labels.clone();
+                        bucket_labels.push(("le", le.as_str()));
+                        write_sample(
+                            &mut out,
+                            &bucket_name,
+                            &bucket_labels,
+                            bucket.get_cumulative_count() as f64,
+                            exemplar(lower, upper),
+                        );
+                        lower = upper;
+                    }
+                    let mut bucket_labels = labels.clone();
+                    bucket_labels.push(("le", "+Inf"));
+                    write_sample(
+                        &mut out,
+                        &bucket_name,
+                        &bucket_labels,
+                        histogram.get_sample_count() as f64,
+                        exemplar(lower, f64::INFINITY),
+                    );
+                    let count = histogram.get_sample_count() as f64;
+                    write_sample(&mut out, &format!("{name}_count"), &labels, count, None);
+                    let sum = histogram.get_sample_sum();
+                    write_sample(&mut out, &format!("{name}_sum"), &labels, sum, None);
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        let q = format_value(quantile.get_quantile());
+                        let mut quantile_labels = labels.clone();
+                        quantile_labels.push(("quantile", q.as_str()));
+                        write_sample(&mut out, name, &quantile_labels, quantile.get_value(), None);
+                    }
+                    let count = summary.get_sample_count() as f64;
+                    write_sample(&mut out, &format!("{name}_count"), &labels, count, None);
+                    let sum = summary.get_sample_sum();
+                    write_sample(&mut out, &format!("{name}_sum"), &labels, sum, None);
+                }
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn write_sample(
+    out: &mut String,
+    name: &str,
+    labels: &[(&str, &str)],
+    value: f64,
+    exemplar: Option<&Exemplar>,
+) {
+    out.push_str(name);
+    write_labels(out, labels);
+    out.push(' ');
+    out.push_str(&format_value(value));
+    if let Some(exemplar) = exemplar {
+        out.push_str(" # ");
+        write_labels(out, &[("trace_id", exemplar.trace_id.as_str())]);
+        out.push_str(&format!(
+            " {} {:.3}",
+            format_value(exemplar.value),
+            exemplar.timestamp
+        ));
+    }
+    out.push('\n');
+}
+
+fn write_labels(out: &mut String, labels: &[(&str, &str)]) {
+    if labels.is_empty() {
+        return;
+    }
+    let labels = labels
+        .iter()
+        .map(|(k, v)| {
+            let v = v
+                .replace('\\', r"\\")
+                .replace('"', "\\\"")
+                .replace('\n', r"\n");
+            format!("{k}=\"{v}\"")
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    out.push('{');
+    out.push_str(&labels);
+    out.push('}');
+}
+
+fn format_value(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else if value.is_nan() {
+        "NaN".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::{HistogramOpts, IntCounterVec, Opts};
+
+    use super::*;
+
+    fn registry() -> (Registry, IntCounterVec, HistogramVec) {
+        let registry = Registry::new();
+        let counter = IntCounterVec::new(
+            Opts::new("requests", "Requests.").namespace("zo"),
+            &["organization", "stream"],
+        )
+        .unwrap();
+        let histogram = HistogramVec::new(
+            HistogramOpts::new("latency", "Latency.")
+                .namespace("zo")
+                .buckets(vec![0.1, 1.0]),
+            &["handler"],
+        )
+        .unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        (registry, counter, histogram)
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let allowlist = Allowlist::new("default, app_*");
+        assert!(allowlist.allows("default"));
+        assert!(allowlist.allows("app_logs"));
+        assert!(allowlist.allows(""));
+        assert!(!allowlist.allows("other"));
+        assert!(!allowlist.allows("my_app_logs"));
+        assert!(Allowlist::new("").allows("other"));
+    }
+
+    #[test]
+    fn test_relabel() {
+        let (registry, counter, _) = registry();
+        counter.with_label_values(&["default", "app"]).inc_by(2);
+        counter.with_label_values(&["default", "file_1"]).inc_by(3);
+        counter.with_label_values(&["default", "file_2"]).inc_by(4);
+        let is_allowed = |name: &str, value: &str| name != "stream" || value == "app";
+        let families = relabel(registry.gather(), &is_allowed);
+        let metrics = families[0].get_metric();
+        assert_eq!(metrics.len(), 2);
+        let other = metrics
+            .iter()
+            .find(|m| m.get_label()[1].get_value() == OTHER_LABEL_VALUE)
+            .unwrap();
+        assert_eq!(other.get_counter().get_value(), 7.0);
+    }
+
+    #[test]
+    fn test_encode_families() {
+        let (registry, counter, histogram) = registry();
+        counter.with_label_values(&["default", "app"]).inc();
+        histogram
+            .with_label_values(&["/api/{org_id}/_search"])
+            .observe(0.5);
+        let mut exemplars = HashMap::new();
+        exemplars.insert(
+            series_key("zo_latency", &[("handler", "/api/{org_id}/_search")]),
+            VecDeque::from([Exemplar {
+                trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+                value: 0.5,
+                timestamp: 1700000000.0,
+            }]),
+        );
+        let out = encode_families(&registry.gather(), &exemplars);
+        assert!(out.contains("# TYPE zo_requests counter\n"));
+        assert!(out.contains("zo_requests_total{organization=\"default\",stream=\"app\"} 1\n"));
+        assert!(out.contains("# TYPE zo_latency histogram\n"));
+        assert!(
+            out.contains("zo_latency_bucket{handler=\"/api/{org_id}/_search\",le=\"0.1\"} 0\n")
+        );
+        assert!(out.contains(
+            "zo_latency_bucket{handler=\"/api/{org_id}/_search\",le=\"1\"} 1 # {trace_id=\"4bf92f3577b34da6a3ce929d0e0e4736\"} 0.5 1700000000.000\n"
+        ));
+        assert!(
+            out.contains("zo_latency_bucket{handler=\"/api/{org_id}/_search\",le=\"+Inf\"} 1\n")
+        );
+        assert!(out.contains("zo_latency_count{handler=\"/api/{org_id}/_search\"} 1\n"));
+        assert!(out.ends_with("# EOF\n"));
+    }
+}
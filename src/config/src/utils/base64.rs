@@ -37,6 +37,10 @@ pub fn encode(s: &str) -> String {
     base64::engine::general_purpose::STANDARD.encode(s.as_bytes())
 }
 
+pub fn encode_raw(s: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(s)
+}
+
 pub fn encode_url(s: &str) -> String {
     encode(s)
         .replace('+', "-")
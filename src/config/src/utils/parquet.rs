@@ -21,7 +21,12 @@ use futures::TryStreamExt;
 use parquet::{
     arrow::{arrow_reader::ArrowReaderMetadata, AsyncArrowWriter, ParquetRecordBatchStreamBuilder},
     basic::{Compression, Encoding},
-    file::{metadata::KeyValue, properties::WriterProperties},
+    file::{
+        footer::{decode_footer, decode_metadata},
+        metadata::KeyValue,
+        properties::WriterProperties,
+        FOOTER_SIZE,
+    },
 };
 
 use crate::{config::*, ider, meta::stream::FileMeta};
@@ -202,6 +207,40 @@ pub async fn read_metadata_from_file(path: &PathBuf) -> Result<FileMeta, anyhow:
     Ok(meta)
 }
 
+/// Bytes read from the end of a parquet file to get its metadata in one
+/// request most of the time.
+pub const FOOTER_READ_SIZE: usize = 64 * 1024;
+
+/// Number of bytes at the end of a parquet file holding its metadata, from
+/// at least its last 8 bytes.
+pub fn footer_len(tail: &[u8]) -> Result<usize, anyhow::Error> {
+    if tail.len() < FOOTER_SIZE {
+        return Err(anyhow::anyhow!("parquet footer too short"));
+    }
+    let footer: [u8; FOOTER_SIZE] = tail[tail.len() - FOOTER_SIZE..].try_into()?;
+    Ok(decode_footer(&footer)? + FOOTER_SIZE)
+}
+
+/// Reads the meta of a parquet file from its last bytes, they must hold the
+/// whole footer, see `footer_len`. The number of rows of the file stands in
+/// for the records of the files written without them.
+pub fn read_metadata_from_footer(tail: &[u8]) -> Result<FileMeta, anyhow::Error> {
+    let len = footer_len(tail)?;
+    if tail.len() < len {
+        return Err(anyhow::anyhow!("parquet footer incomplete"));
+    }
+    let metadata = decode_metadata(&tail[tail.len() - len..tail.len() - FOOTER_SIZE])?;
+    let file_metadata = metadata.file_metadata();
+    let mut meta = match file_metadata.key_value_metadata() {
+        Some(values) => FileMeta::from(values.as_slice()),
+        None => FileMeta::default(),
+    };
+    if meta.records == 0 {
+        meta.records = file_metadata.num_rows();
+    }
+    Ok(meta)
+}
+
 pub fn generate_filename_with_time_range(min_ts: i64, max_ts: i64) -> String {
     format!(
         "{}.{}.{}{}",
@@ -224,3 +263,44 @@ pub fn parse_time_range_from_filename(mut name: &str) -> (i64, i64) {
     let max_ts = columns[1].parse::<i64>().unwrap_or(0);
     (min_ts, max_ts)
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Int64Array;
+    use arrow_schema::{DataType, Field};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_metadata_from_footer() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "_timestamp",
+            DataType::Int64,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+        let meta = FileMeta {
+            min_ts: 1,
+            max_ts: 3,
+            records: 3,
+            original_size: 24,
+            ..Default::default()
+        };
+        let data = write_recordbatch_to_parquet(schema, &[batch], &[], &[], &meta)
+            .await
+            .unwrap();
+
+        let len = footer_len(&data).unwrap();
+        assert!(len < data.len());
+        let read = read_metadata_from_footer(&data[data.len() - len..]).unwrap();
+        assert_eq!(read.min_ts, 1);
+        assert_eq!(read.max_ts, 3);
+        assert_eq!(read.records, 3);
+        assert_eq!(read.original_size, 24);
+        assert!(read_metadata_from_footer(&data[data.len() - len + 1..]).is_err());
+    }
+}
@@ -24,3 +24,8 @@ pub fn get_rand_element<T>(arr: &[T]) -> &T {
 pub fn generate_random_string(len: usize) -> String {
     Alphanumeric.sample_string(&mut rand::thread_rng(), len)
 }
+
+/// Returns a random number in `[0, 1)`.
+pub fn get_rand_float() -> f64 {
+    rand::random::<f64>()
+}
@@ -13,15 +13,25 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::net::IpAddr;
+
+use config::meta::cluster::{GRPC_MIN_VERSION, GRPC_VERSION, GRPC_VERSION_HEADER};
 use http_auth_basic::Credentials;
 use tonic::{metadata::MetadataValue, Request, Status};
 
-use crate::common::{
-    infra::{
-        cluster::get_internal_grpc_token,
-        config::{ROOT_USER, USERS},
+use crate::{
+    common::{
+        infra::{
+            cluster::get_internal_grpc_token,
+            config::{ROOT_USER, USERS},
+        },
+        meta::{
+            rbac::{Permission, Resource},
+            service_account::{parse_principal, TOKEN_PREFIX},
+        },
+        utils::auth::{get_hash, is_root_user},
     },
-    utils::auth::{get_hash, is_root_user},
+    service::{ingestion_keys, network_policy, service_accounts},
 };
 
 pub fn check_auth(req: Request<()>) -> Result<Request<()>, Status> {
@@ -39,15 +49,34 @@ pub fn check_auth(req: Request<()>) -> Result<Request<()>, Status> {
         .unwrap()
         .to_string();
     if token.eq(get_internal_grpc_token().as_str()) {
+        // the requests of the nodes before the versions were advertised have no version
+        if let Some(version) = metadata.get(GRPC_VERSION_HEADER) {
+            let version: u32 = version
+                .to_str()
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default();
+            if !(GRPC_MIN_VERSION..=GRPC_VERSION).contains(&version) {
+                return Err(Status::failed_precondition(format!(
+                    "Unsupported grpc version {version}, this node speaks {GRPC_MIN_VERSION} to {GRPC_VERSION}"
+                )));
+            }
+        }
         Ok(req)
     } else {
-        let org_id = metadata.get(&cfg.grpc.org_header_key);
-        if org_id.is_none() {
+        // only external requests are ingestion, internal ones still search the node
+        if config::cluster::is_draining() {
+            return Err(Status::unavailable("Node is draining"));
+        }
+        let Some(org_id) = metadata
+            .get(&cfg.grpc.org_header_key)
+            .map(|v| v.to_str().unwrap_or_default())
+        else {
             return Err(Status::invalid_argument(format!(
                 "Please specify organization id with header key '{}' ",
                 &cfg.grpc.org_header_key
             )));
-        }
+        };
 
         let credentials = match Credentials::from_header(token) {
             Ok(c) => c,
@@ -58,25 +87,32 @@ pub fn check_auth(req: Request<()>) -> Result<Request<()>, Status> {
         };
 
         let user_id = credentials.user_id;
+        if credentials.password.starts_with(TOKEN_PREFIX) {
+            return check_service_account_auth(req, &credentials.password);
+        }
         let user = if is_root_user(&user_id) {
             ROOT_USER.get("root").unwrap()
-        } else if let Some(user) = USERS.get(&format!(
-            "{}/{}",
-            org_id.unwrap().to_str().unwrap(),
-            &user_id
-        )) {
+        } else if let Some(user) = USERS.get(&format!("{}/{}", org_id, &user_id)) {
             user
         } else {
             return Err(Status::unauthenticated("No valid auth token"));
         };
 
-        if user.token.eq(&credentials.password) {
+        let ip = req.remote_addr().map(|v| v.ip());
+        if user.token.eq(&credentials.password)
+            || ingestion_keys::validate_key(org_id, &user.email, &credentials.password)
+        {
+            if !ingestion_keys::is_ip_allowed(org_id, &credentials.password, ip) {
+                return Err(Status::permission_denied("Unauthorized Access"));
+            }
+            check_network_policy(org_id, &user.email, ip)?;
             return Ok(req);
         }
         let in_pass = get_hash(&credentials.password, &user.salt);
         if user_id.eq(&user.email)
             && (credentials.password.eq(&user.password) || in_pass.eq(&user.password))
         {
+            check_network_policy(org_id, &user_id, ip)?;
             let mut req = req;
             let user_id_metadata = MetadataValue::try_from(&user_id).unwrap();
             req.metadata_mut().append("user_id", user_id_metadata);
@@ -88,6 +124,58 @@ pub fn check_auth(req: Request<()>) -> Result<Request<()>, Status> {
     }
 }
 
+// grpc only serves ingestion, the token must be allowed to write the stream
+fn check_service_account_auth(req: Request<()>, token: &str) -> Result<Request<()>, Status> {
+    let cfg = config::get_config();
+    let metadata = req.metadata();
+    let org_id = metadata
+        .get(&cfg.grpc.org_header_key)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let stream_name = metadata
+        .get(&cfg.grpc.stream_header_key)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("*")
+        .to_string();
+    let principal =
+        match service_accounts::validate_token(&org_id, token, req.remote_addr().map(|v| v.ip())) {
+            Ok(v) => v,
+            Err(e) => return Err(Status::unauthenticated(e)),
+        };
+    check_network_policy(&org_id, &principal, req.remote_addr().map(|v| v.ip()))?;
+    let (name, token_id) = parse_principal(&principal).unwrap();
+    if !service_accounts::is_token_allowed(
+        &org_id,
+        name,
+        token_id,
+        Resource::Stream,
+        &stream_name,
+        Permission::Write,
+    ) {
+        return Err(Status::permission_denied("Unauthorized Access"));
+    }
+    let mut req = req;
+    let user_id_metadata = MetadataValue::try_from(&principal).unwrap();
+    req.metadata_mut().append("user_id", user_id_metadata);
+    Ok(req)
+}
+
+// grpc only serves ingestion, so the ingestion allowlist of the org applies
+fn check_network_policy(org_id: &str, user_id: &str, ip: Option<IpAddr>) -> Result<(), Status> {
+    if network_policy::is_ip_allowed(org_id, user_id, true, ip) {
+        return Ok(());
+    }
+    log::warn!("Grpc request of {user_id} from {ip:?} denied by network policy of {org_id}");
+    let (org_id, user_id) = (org_id.to_string(), user_id.to_string());
+    tokio::task::spawn(async move {
+        network_policy::audit_denied(&org_id, &user_id, "POST", "grpc", ip).await;
+    });
+    Err(Status::permission_denied(
+        "Client ip isn't allowed by the network policy of the organization",
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use config::cache_instance_id;
@@ -112,6 +200,8 @@ mod tests {
                 org: "dummy".to_owned(),
                 is_external: false,
                 password_ext: Some("Complexpass#123".to_string()),
+                password_updated_at: 0,
+                force_password_reset: false,
             },
         );
 
@@ -142,6 +232,8 @@ mod tests {
                 org: "dummy".to_owned(),
                 is_external: false,
                 password_ext: Some("Complexpass#123".to_string()),
+                password_updated_at: 0,
+                force_password_reset: false,
             },
         );
 
@@ -170,6 +262,8 @@ mod tests {
                 org: "dummy".to_owned(),
                 is_external: false,
                 password_ext: Some("Complexpass#123".to_string()),
+                password_updated_at: 0,
+                force_password_reset: false,
             },
         );
         let mut request = tonic::Request::new(());
@@ -0,0 +1,33 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The standard gRPC health checks of the services of a server, they need no
+//! auth token so that the load balancers can check the gRPC port.
+
+use tonic::server::NamedService;
+use tonic_health::{server::HealthReporter, ServingStatus};
+
+/// Returns the name a service is health checked by.
+pub fn service_name<S: NamedService>(_svc: &S) -> &'static str {
+    S::NAME
+}
+
+/// Sets the status of the services and of the whole server, the empty name.
+pub async fn set_status(reporter: &mut HealthReporter, services: &[&str], status: ServingStatus) {
+    reporter.set_service_status("", status).await;
+    for service in services {
+        reporter.set_service_status(*service, status).await;
+    }
+}
@@ -19,7 +19,9 @@ use proto::cluster_rpc;
 use crate::service::promql;
 
 pub mod auth;
+pub mod health;
 pub mod request;
+pub mod request_id;
 
 impl From<promql::MetricsQueryRequest> for cluster_rpc::MetricsQueryRequest {
     fn from(req: promql::MetricsQueryRequest) -> Self {
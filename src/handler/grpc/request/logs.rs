@@ -18,18 +18,24 @@ use opentelemetry_proto::tonic::collector::logs::v1::{
     logs_service_server::LogsService, ExportLogsServiceRequest, ExportLogsServiceResponse,
 };
 use tonic::{Response, Status};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 #[derive(Default)]
 pub struct LogsServer;
 
 #[async_trait]
 impl LogsService for LogsServer {
+    #[tracing::instrument(name = "grpc:logs:enter", skip_all)]
     async fn export(
         &self,
         request: tonic::Request<ExportLogsServiceRequest>,
     ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
         let cfg = config::get_config();
         let metadata = request.metadata().clone();
+        let parent_cx = opentelemetry::global::get_text_map_propagator(|prop| {
+            prop.extract(&super::MetadataMap(&metadata))
+        });
+        tracing::Span::current().set_parent(parent_cx);
         let msg = format!(
             "Please specify organization id with header key '{}' ",
             &cfg.grpc.org_header_key
@@ -19,6 +19,7 @@ pub mod event;
 pub mod file_list;
 pub mod logs;
 pub mod metrics;
+pub mod replication;
 pub mod search;
 pub mod traces;
 pub mod usage;
@@ -0,0 +1,60 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::metrics;
+use proto::cluster_rpc::{
+    replication_server::Replication, EmptyResponse, ReleaseRequest, ReplicateRequest,
+};
+use tonic::{Request, Response, Status};
+
+use crate::service::ingestion::replication;
+
+pub struct Replicator;
+
+#[tonic::async_trait]
+impl Replication for Replicator {
+    async fn replicate(
+        &self,
+        req: Request<ReplicateRequest>,
+    ) -> Result<Response<EmptyResponse>, Status> {
+        let start = std::time::Instant::now();
+        let ret = replication::receive(req.into_inner()).await;
+        let code = if ret.is_ok() { "200" } else { "500" };
+        let time = start.elapsed().as_secs_f64();
+        metrics::GRPC_RESPONSE_TIME
+            .with_label_values(&["/replication/replicate", code, "", "", ""])
+            .observe(time);
+        metrics::GRPC_INCOMING_REQUESTS
+            .with_label_values(&["/replication/replicate", code, "", "", ""])
+            .inc();
+        match ret {
+            Ok(()) => Ok(Response::new(EmptyResponse {})),
+            Err(e) => {
+                log::error!("[REPLICATION] store replicas failed: {}", e);
+                Err(Status::internal(e.to_string()))
+            }
+        }
+    }
+
+    async fn release(
+        &self,
+        req: Request<ReleaseRequest>,
+    ) -> Result<Response<EmptyResponse>, Status> {
+        match replication::release_replicas(&req.get_ref().source).await {
+            Ok(()) => Ok(Response::new(EmptyResponse {})),
+            Err(e) => Err(Status::internal(e.to_string())),
+        }
+    }
+}
@@ -21,13 +21,14 @@ use o2_enterprise::enterprise::{
     search::{QueryManager, TaskStatus},
 };
 use proto::cluster_rpc::{
-    search_server::Search, CancelQueryRequest, CancelQueryResponse, QueryStatusRequest,
-    QueryStatusResponse, SearchRequest, SearchResponse,
+    search_server::Search, CancelQueryRequest, CancelQueryResponse, KillQueryRequest,
+    KillQueryResponse, QueryStatusRequest, QueryStatusResponse, RunningQueriesRequest,
+    RunningQueriesResponse, SearchRequest, SearchResponse,
 };
 use tonic::{Request, Response, Status};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::service::search as SearchService;
+use crate::service::{admission, search as SearchService};
 
 #[derive(Clone, Debug)]
 #[cfg(feature = "enterprise")]
@@ -126,6 +127,11 @@ impl Search for Searcher {
         let org_id = req.org_id.clone();
         let stream_type = req.stream_type.clone();
 
+        // queue the search until the querier has room for it
+        let _permit = admission::admit_search()
+            .await
+            .map_err(|e| Status::resource_exhausted(e.to_string()))?;
+
         // set search task
         #[cfg(feature = "enterprise")]
         let trace_id = req.job.as_ref().unwrap().trace_id.to_string();
@@ -138,7 +144,19 @@ impl Search for Searcher {
             .await;
         }
 
-        let result = SearchService::grpc::search(&req).await;
+        let job_trace_id = &req.job.as_ref().unwrap().trace_id;
+        let sql = req
+            .query
+            .as_ref()
+            .map(|q| q.sql.as_str())
+            .unwrap_or_default();
+        let _query_guard =
+            SearchService::tracker::register(job_trace_id, &org_id, &stream_type, None, sql);
+
+        metrics::QUERY_RUNNING_NUMS.with_label_values(&[]).inc();
+        let result =
+            SearchService::tracker::timed(job_trace_id, SearchService::grpc::search(&req)).await;
+        metrics::QUERY_RUNNING_NUMS.with_label_values(&[]).dec();
 
         // remove task
         #[cfg(feature = "enterprise")]
@@ -282,4 +300,31 @@ impl Search for Searcher {
     ) -> Result<Response<CancelQueryResponse>, Status> {
         Err(Status::unimplemented("Not Supported"))
     }
+
+    async fn running_queries(
+        &self,
+        req: Request<RunningQueriesRequest>,
+    ) -> Result<Response<RunningQueriesResponse>, Status> {
+        let org_id = req.into_inner().org_id;
+        let queries = SearchService::tracker::local_queries(&org_id);
+        Ok(Response::new(RunningQueriesResponse { queries }))
+    }
+
+    async fn kill_query(
+        &self,
+        req: Request<KillQueryRequest>,
+    ) -> Result<Response<KillQueryResponse>, Status> {
+        let req = req.into_inner();
+        let is_success = SearchService::tracker::cancel_local(&req.org_id, &req.trace_id);
+        // the enterprise query manager aborts its own tasks
+        #[cfg(feature = "enterprise")]
+        if is_success {
+            if let Some((_, senders)) = self.remove(&req.trace_id).await {
+                for sender in senders.abort_senders.into_iter().rev() {
+                    let _ = sender.send(());
+                }
+            }
+        }
+        Ok(Response::new(KillQueryResponse { is_success }))
+    }
 }
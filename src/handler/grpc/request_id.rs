@@ -0,0 +1,77 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use config::meta::logger::{parse_request_id, with_request_id, REQUEST_ID_HEADER};
+use tonic::{
+    codegen::{http, BoxFuture, Service},
+    server::NamedService,
+};
+
+/// Tags the grpc requests of the service with the id sent by the calling
+/// node, or a new one, and logs the requests slower than the slow request
+/// threshold.
+#[derive(Clone)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S> RequestIdService<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for RequestIdService<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let request_id = parse_request_id(
+            req.headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|v| v.to_str().ok()),
+        );
+        let path = req.uri().path().to_string();
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let start = Instant::now();
+            let res = with_request_id(request_id.clone(), fut).await;
+            let threshold = config::get_config().log.slow_request_threshold;
+            let took = start.elapsed().as_millis() as u64;
+            if threshold > 0 && took >= threshold {
+                log::warn!("[request_id {request_id}] slow grpc request: {path}, took: {took} ms");
+            }
+            res
+        })
+    }
+}
+
+impl<S: NamedService> NamedService for RequestIdService<S> {
+    const NAME: &'static str = S::NAME;
+}
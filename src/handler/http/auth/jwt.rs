@@ -154,6 +154,8 @@ pub async fn process_token(
             organizations: source_orgs,
             is_external: true,
             password_ext: Some("".to_owned()),
+            password_updated_at: 0,
+            force_password_reset: false,
         };
 
         match users::update_db_user(updated_db_user).await {
@@ -410,6 +412,8 @@ async fn map_group_to_custom_role(user_email: &str, name: &str, custom_roles: Ve
             }],
             is_external: true,
             password_ext: Some("".to_owned()),
+            password_updated_at: 0,
+            force_password_reset: false,
         };
 
         match users::update_db_user(updated_db_user).await {
@@ -53,6 +53,7 @@ pub async fn token_validator(
         auth_info.auth.strip_prefix("Bearer").unwrap().trim(),
         &keys,
         &O2_CONFIG.dex.client_id,
+        None,
         false,
     )
     .await
@@ -118,9 +119,73 @@ pub async fn token_validator(
 #[cfg(not(feature = "enterprise"))]
 pub async fn token_validator(
     req: ServiceRequest,
-    _token: AuthExtractor,
+    auth_info: AuthExtractor,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
-    use actix_web::error::ErrorForbidden;
+    use actix_web::{
+        error::{ErrorForbidden, ErrorUnauthorized},
+        http::{header, Method},
+    };
+
+    use super::validator::check_permissions;
+    use crate::service::{db, oidc, users};
+
+    if !config::get_config().oidc.enabled {
+        return Err((ErrorForbidden("Not Supported"), req));
+    }
+
+    let token = auth_info.auth.strip_prefix("Bearer").unwrap().trim();
+    let res = match oidc::verify_token(token).await {
+        Ok(res) => res,
+        Err(err) => return Err((ErrorUnauthorized(err), req)),
+    };
+    let user_id = &res.0.user_email;
+    if !res.0.is_valid || user_id.is_empty() {
+        return Err((ErrorForbidden("Unauthorized Access"), req));
+    }
+    // the tokens only act as the external users with the same verified email
+    let existing = db::user::get_user_by_email(user_id).await;
+    if !matches!(
+        oidc::check_existing_user(user_id, existing.as_ref(), oidc::is_email_verified(&res)),
+        Ok(true)
+    ) {
+        return Err((ErrorForbidden("Unauthorized Access"), req));
+    }
+
+    let path = match req
+        .request()
+        .path()
+        .strip_prefix(format!("{}/api/", config::get_config().common.base_uri).as_str())
+    {
+        Some(path) => path,
+        None => req.request().path(),
+    };
+    let org_id = path.split('/').next().unwrap_or_default();
+    let role = if org_id.eq("organizations") || org_id.eq("clusters") || org_id.is_empty() {
+        db::user::get_user_by_email(user_id).await.map(|_| None)
+    } else {
+        users::get_user(Some(org_id), user_id)
+            .await
+            .map(|user| Some(user.role))
+    };
+    let Some(role) = role else {
+        return Err((ErrorForbidden("Unauthorized Access"), req));
+    };
 
-    Err((ErrorForbidden("Not Supported"), req))
+    // / Hack for prometheus, need support POST and check the header
+    let mut req = req;
+    if req.method().eq(&Method::POST) && !req.headers().contains_key("content-type") {
+        req.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+    }
+    req.headers_mut().insert(
+        header::HeaderName::from_static("user_id"),
+        header::HeaderValue::from_str(user_id).unwrap(),
+    );
+    if auth_info.bypass_check || check_permissions(user_id, auth_info, role).await {
+        Ok(req)
+    } else {
+        Err((ErrorForbidden("Unauthorized Access"), req))
+    }
 }
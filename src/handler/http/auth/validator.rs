@@ -15,7 +15,7 @@
 
 use actix_web::{
     dev::ServiceRequest,
-    error::{ErrorForbidden, ErrorUnauthorized},
+    error::{ErrorForbidden, ErrorTooManyRequests, ErrorUnauthorized},
     http::{header, Method},
     web, Error,
 };
@@ -26,14 +26,18 @@ use crate::{
     common::{
         meta::{
             ingestion::INGESTION_EP,
+            service_account::TOKEN_PREFIX,
             user::{
                 AuthTokensExt, DBUser, TokenValidationResponse, TokenValidationResponseBuilder,
                 UserRole,
             },
         },
-        utils::auth::{get_hash, is_root_user, AuthExtractor},
+        utils::{
+            auth::{get_hash, is_root_user, AuthExtractor},
+            http::get_client_ip,
+        },
     },
-    service::{db, users},
+    service::{db, ingestion_keys, ldap, login_security, service_accounts, users},
 };
 
 pub const PKCE_STATE_ORG: &str = "o2_pkce_state";
@@ -57,6 +61,15 @@ pub async fn validator(
         Some(path) => path,
         None => req.request().path(),
     };
+    let ip = get_client_ip(&req.connection_info(), req.peer_addr());
+    if let Some(retry_after) = login_security::check(user_id, ip) {
+        return Err((
+            ErrorTooManyRequests(format!(
+                "Too many failed logins, retry after {retry_after} seconds"
+            )),
+            req,
+        ));
+    }
     match if auth_info.auth.starts_with("{\"auth_ext\":") {
         let auth_token: AuthTokensExt =
             config::utils::json::from_str(&auth_info.auth).unwrap_or_default();
@@ -66,6 +79,11 @@ pub async fn validator(
     } {
         Ok(res) => {
             if res.is_valid {
+                login_security::record_success(user_id);
+                let org_id = path.split('/').next().unwrap_or_default();
+                if !ingestion_keys::is_ip_allowed(org_id, password.trim(), ip) {
+                    return Err((ErrorForbidden("Unauthorized Access"), req));
+                }
                 // / Hack for prometheus, need support POST and check the header
                 let mut req = req;
                 if req.method().eq(&Method::POST) && !req.headers().contains_key("content-type") {
@@ -87,6 +105,7 @@ pub async fn validator(
                     Err((ErrorForbidden("Unauthorized Access"), req))
                 }
             } else {
+                login_security::record_failure(user_id, ip, req.path()).await;
                 Err((ErrorUnauthorized("Unauthorized Access"), req))
             }
         }
@@ -171,7 +190,12 @@ pub async fn validate_credentials(
     let user = user.unwrap();
 
     if (path_columns.len() == 1 || INGESTION_EP.iter().any(|s| path_columns.contains(s)))
-        && user.token.eq(&user_password)
+        && (user.token.eq(&user_password)
+            || ingestion_keys::validate_key(
+                path_columns.first().copied().unwrap_or_default(),
+                &user.email,
+                user_password,
+            ))
     {
         return Ok(TokenValidationResponse {
             is_valid: true,
@@ -185,7 +209,14 @@ pub async fn validate_credentials(
     }
 
     let in_pass = get_hash(user_password, &user.salt);
-    if !user.password.eq(&in_pass)
+    if ldap::is_ldap_user(user_id, Some(user.is_external)) {
+        // the local hash of an ldap user is only a cache of the last login
+        if !(user.password.eq(&in_pass) && ldap::is_cache_valid(user.password_updated_at))
+            && ldap::login(user_id, user_password).await.is_err()
+        {
+            return Ok(TokenValidationResponse::default());
+        }
+    } else if !user.password.eq(&in_pass)
         && !user
             .password_ext
             .unwrap_or("".to_string())
@@ -201,6 +232,14 @@ pub async fn validate_credentials(
             given_name: "".to_string(),
         });
     }
+    if login_security::is_reset_required(
+        &user.email,
+        user.is_external,
+        user.force_password_reset,
+        user.password_updated_at,
+    ) {
+        return Err(ErrorForbidden("Password reset required"));
+    }
     if !path.contains("/user")
         || (path.contains("/user")
             && (user.role.eq(&UserRole::Admin)
@@ -379,6 +418,12 @@ pub async fn validate_user(
     user_password: &str,
 ) -> Result<TokenValidationResponse, Error> {
     let db_user = db::user::get_db_user(user_id).await;
+    if ldap::is_ldap_user(user_id, db_user.as_ref().ok().map(|user| user.is_external)) {
+        return ldap::login(user_id, user_password).await.map_err(|e| {
+            log::info!("ldap login failed for {user_id}: {e}");
+            ErrorForbidden("Not allowed")
+        });
+    }
     let config = get_config();
     validate_user_from_db(db_user, user_password, None, 0, &config.auth.ext_auth_salt).await
 }
@@ -541,8 +586,16 @@ async fn oo_validator_internal(
             Some(value) => value,
             None => return Err((ErrorUnauthorized("Unauthorized Access"), req)),
         };
+        if password.starts_with(TOKEN_PREFIX) {
+            return validate_service_account(req, &password, path_prefix).await;
+        }
         validator(req, &username, &password, auth_info, path_prefix).await
     } else if auth_info.auth.starts_with("Bearer") {
+        let token = auth_info.auth.strip_prefix("Bearer").unwrap().trim();
+        if token.starts_with(TOKEN_PREFIX) {
+            let token = token.to_string();
+            return validate_service_account(req, &token, path_prefix).await;
+        }
         super::token::token_validator(req, auth_info).await
     } else if auth_info.auth.starts_with("{\"auth_ext\":") {
         let auth_tokens: AuthTokensExt =
@@ -569,6 +622,46 @@ async fn oo_validator_internal(
     }
 }
 
+/// Validates a service account api token, the token is restricted to the org
+/// it was created in and to its scope, streams and ip allowlist.
+async fn validate_service_account(
+    req: ServiceRequest,
+    token: &str,
+    path_prefix: &str,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let cfg = get_config();
+    let path = match req
+        .request()
+        .path()
+        .strip_prefix(format!("{}{}", cfg.common.base_uri, path_prefix).as_str())
+    {
+        Some(path) => path.to_string(),
+        None => return Err((ErrorUnauthorized("Unauthorized Access"), req)),
+    };
+    let org_id = path.split('/').next().unwrap_or_default();
+    let ip = get_client_ip(&req.connection_info(), req.peer_addr());
+    let principal = match service_accounts::validate_token(org_id, token, ip) {
+        Ok(v) => v,
+        Err(e) => return Err((ErrorUnauthorized(e), req)),
+    };
+    if !service_accounts::is_request_allowed(org_id, &principal, req.method().as_str(), &path) {
+        return Err((ErrorForbidden("Unauthorized Access"), req));
+    }
+
+    let mut req = req;
+    if req.method().eq(&Method::POST) && !req.headers().contains_key("content-type") {
+        req.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+    }
+    req.headers_mut().insert(
+        header::HeaderName::from_static("user_id"),
+        header::HeaderValue::from_str(&principal).unwrap(),
+    );
+    Ok(req)
+}
+
 fn get_user_details(decoded: String) -> Option<(String, String)> {
     let credentials = String::from_utf8(decoded.into())
         .map_err(|_| ())
@@ -673,11 +766,25 @@ pub(crate) async fn check_permissions(
 
 #[cfg(not(feature = "enterprise"))]
 pub(crate) async fn check_permissions(
-    _user_id: &str,
-    _auth_info: AuthExtractor,
-    _role: Option<UserRole>,
+    user_id: &str,
+    auth_info: AuthExtractor,
+    role: Option<UserRole>,
 ) -> bool {
-    true
+    use crate::common::meta::rbac::{Permission, Resource};
+
+    if role.eq(&Some(UserRole::Root)) {
+        return true;
+    }
+    let Some((resource, obj)) = auth_info.o2_type.split_once(':') else {
+        return true;
+    };
+    let (Ok(resource), Ok(permission)) = (
+        resource.parse::<Resource>(),
+        auth_info.method.parse::<Permission>(),
+    ) else {
+        return true;
+    };
+    crate::service::rbac::is_allowed(&auth_info.org_id, user_id, resource, obj, permission)
 }
 
 #[cfg(feature = "enterprise")]
@@ -734,6 +841,8 @@ mod tests {
             organizations: vec![],
             is_external: false,
             password_ext: Some("some_pass_ext".into()),
+            password_updated_at: 0,
+            force_password_reset: false,
         };
 
         let resp_from_builder = TokenValidationResponseBuilder::from_db_user(&user).build();
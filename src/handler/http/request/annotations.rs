@@ -0,0 +1,115 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::meta::annotations::{Annotation, AnnotationQuery},
+    service::annotations,
+};
+
+/// ListAnnotations
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Annotations",
+    operation_id = "ListAnnotations",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        AnnotationQuery,
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = AnnotationList),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/annotations")]
+pub async fn list(
+    path: web::Path<String>,
+    query: web::Query<AnnotationQuery>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    annotations::list(&org_id, query.into_inner()).await
+}
+
+/// CreateAnnotation
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Annotations",
+    operation_id = "CreateAnnotation",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(
+        content = Annotation,
+        description = "Annotation data",
+        example = json!({
+            "start_time": 1714857600000000_i64,
+            "title": "Deploy v1.2.0",
+            "text": "Rolled out to prod",
+            "tags": ["deploy"],
+            "stream_type": "logs",
+            "stream_name": "default",
+        }),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Annotation),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/annotations")]
+pub async fn create(
+    path: web::Path<String>,
+    body: web::Json<Annotation>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    annotations::create(&org_id, user_id, body.into_inner()).await
+}
+
+/// DeleteAnnotation
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Annotations",
+    operation_id = "DeleteAnnotation",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("annotation_id" = String, Path, description = "Annotation ID"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/annotations/{annotation_id}")]
+pub async fn delete(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, annotation_id) = path.into_inner();
+    annotations::delete(&org_id, &annotation_id).await
+}
@@ -0,0 +1,46 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{get, web, HttpResponse};
+
+use crate::{common::meta::audit::AuditQuery, service::audit};
+
+/// ListAuditEntries
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Audit",
+    operation_id = "ListAuditEntries",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        AuditQuery,
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = AuditList),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/audit")]
+pub async fn list(
+    path: web::Path<String>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    audit::list(&org_id, query.into_inner()).await
+}
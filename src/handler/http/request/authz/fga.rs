@@ -18,7 +18,12 @@ use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 #[cfg(feature = "enterprise")]
 use o2_enterprise::enterprise::dex::meta::auth::RoleRequest;
 
-use crate::common::meta::user::{UserGroup, UserGroupRequest, UserRoleRequest};
+#[cfg(not(feature = "enterprise"))]
+use crate::common::meta::rbac::RoleRequest;
+use crate::common::meta::{
+    http::HttpResponse as MetaHttpResponse,
+    user::{UserGroup, UserGroupRequest, UserRoleRequest},
+};
 
 #[cfg(feature = "enterprise")]
 #[post("/{org_id}/roles")]
@@ -36,17 +41,19 @@ pub async fn create_role(
     .await
     {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[post("/{org_id}/roles")]
 pub async fn create_role(
-    _org_id: web::Path<String>,
-    _role_id: web::Json<UserRoleRequest>,
+    org_id: web::Path<String>,
+    user_req: web::Json<UserRoleRequest>,
 ) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    let org_id = org_id.into_inner();
+    let user_req = user_req.into_inner();
+    crate::service::rbac::create_role(&org_id, &user_req.name).await
 }
 
 #[cfg(feature = "enterprise")]
@@ -58,14 +65,15 @@ pub async fn delete_role(path: web::Path<(String, String)>) -> Result<HttpRespon
         .await
     {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[delete("/{org_id}/roles/{role_id}")]
-pub async fn delete_role(_path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+pub async fn delete_role(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, role_name) = path.into_inner();
+    crate::service::rbac::delete_role(&org_id, &role_name).await
 }
 
 #[cfg(feature = "enterprise")]
@@ -113,17 +121,18 @@ pub async fn get_roles(org_id: web::Path<String>, req: HttpRequest) -> Result<Ht
         .await
     {
         Ok(res) => Ok(HttpResponse::Ok().json(res)),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[get("/{org_id}/roles")]
 pub async fn get_roles(
-    _org_id: web::Path<String>,
+    org_id: web::Path<String>,
     _req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    let org_id = org_id.into_inner();
+    crate::service::rbac::list_roles(&org_id).await
 }
 
 #[cfg(feature = "enterprise")]
@@ -146,17 +155,19 @@ pub async fn update_role(
     .await
     {
         Ok(res) => Ok(HttpResponse::Ok().json(res)),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
-#[post("/{org_id}/roles/{role_id}")]
+#[put("/{org_id}/roles/{role_id}")]
 pub async fn update_role(
-    _path: web::Path<(String, String)>,
-    _permissions: web::Json<String>,
-) -> Result<HttpResponse, actix_web::Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    path: web::Path<(String, String)>,
+    update_role: web::Json<RoleRequest>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, role_id) = path.into_inner();
+    let update_role = update_role.into_inner();
+    crate::service::rbac::update_role(&org_id, &role_id, update_role).await
 }
 
 #[cfg(feature = "enterprise")]
@@ -171,16 +182,17 @@ pub async fn get_role_permissions(
     .await
     {
         Ok(res) => Ok(HttpResponse::Ok().json(res)),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[get("/{org_id}/roles/{role_id}/permissions/{resource}")]
 pub async fn get_role_permissions(
-    _path: web::Path<(String, String)>,
+    path: web::Path<(String, String, String)>,
 ) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    let (org_id, role_id, resource) = path.into_inner();
+    crate::service::rbac::get_role_permissions(&org_id, &role_id, &resource).await
 }
 
 #[cfg(feature = "enterprise")]
@@ -193,14 +205,15 @@ pub async fn get_users_with_role(path: web::Path<(String, String)>) -> Result<Ht
     .await
     {
         Ok(res) => Ok(HttpResponse::Ok().json(res)),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[get("/{org_id}/roles/{role_id}/users")]
-pub async fn get_users_with_role(_org_id: web::Path<String>) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+pub async fn get_users_with_role(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, role_id) = path.into_inner();
+    crate::service::rbac::get_users_with_role(&org_id, &role_id).await
 }
 
 #[cfg(feature = "enterprise")]
@@ -220,7 +233,7 @@ pub async fn create_group(
     .await
     {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
@@ -230,7 +243,7 @@ pub async fn create_group(
     _org_id: web::Path<String>,
     _user_group: web::Json<UserGroup>,
 ) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -253,7 +266,7 @@ pub async fn update_group(
     .await
     {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
@@ -263,7 +276,7 @@ pub async fn update_group(
     _org_id: web::Path<String>,
     _user_group: web::Json<UserGroupRequest>,
 ) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -312,14 +325,14 @@ pub async fn get_groups(path: web::Path<String>, req: HttpRequest) -> Result<Htt
         .await
     {
         Ok(res) => Ok(HttpResponse::Ok().json(res)),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[get("/{org_id}/groups")]
 pub async fn get_groups(_path: web::Path<String>) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -334,14 +347,14 @@ pub async fn get_group_details(path: web::Path<(String, String)>) -> Result<Http
     .await
     {
         Ok(res) => Ok(HttpResponse::Ok().json(res)),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[get("/{org_id}/groups/{group_name}")]
 pub async fn get_group_details(_path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -357,7 +370,7 @@ pub async fn get_resources(_org_id: web::Path<String>) -> Result<HttpResponse, E
 #[cfg(not(feature = "enterprise"))]
 #[get("/{org_id}/resources")]
 pub async fn get_resources(_org_id: web::Path<String>) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -369,12 +382,12 @@ pub async fn delete_group(path: web::Path<(String, String)>) -> Result<HttpRespo
         .await
     {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
-        Err(err) => Ok(HttpResponse::InternalServerError().body(err.to_string())),
+        Err(err) => Ok(MetaHttpResponse::internal_error(err)),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[delete("/{org_id}/groups/{group_name}")]
 pub async fn delete_group(_path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
@@ -0,0 +1,138 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::{
+        meta::{
+            backup::{BackupList, BackupManifest, BackupRequest, RestoreReport, RestoreRequest},
+            http::HttpResponse as MetaHttpResponse,
+        },
+        utils::auth::is_root_user,
+    },
+    service::backup,
+};
+
+/// CreateBackup
+///
+/// Backs up the meta store and the file list to the object store, only the
+/// root user can access it.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Backup",
+    operation_id = "CreateBackup",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("incremental" = Option<bool>, Query, description = "only back up the keys changed since the latest backup"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = BackupManifest),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/_admin/backup")]
+pub async fn create(
+    query: web::Query<BackupRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    if !is_root_user(user_id(&req)) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only the root user can back up the meta store",
+        ));
+    }
+    match backup::create(query.incremental).await {
+        Ok(manifest) => Ok(MetaHttpResponse::json(manifest)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// ListBackups
+///
+/// Lists the backups of the meta store, newest first.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Backup",
+    operation_id = "ListBackups",
+    security(
+        ("Authorization"= [])
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = BackupList),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/_admin/backup")]
+pub async fn list(req: HttpRequest) -> Result<HttpResponse, Error> {
+    if !is_root_user(user_id(&req)) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only the root user can list the backups",
+        ));
+    }
+    match backup::list().await {
+        Ok(list) => Ok(MetaHttpResponse::json(BackupList { list })),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// RestoreBackup
+///
+/// Restores the meta store from a backup, the latest by default, and loads
+/// the file list. The keys which exist already are kept unless `overwrite`
+/// is set. Only the root user can access it.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Backup",
+    operation_id = "RestoreBackup",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("backup" = Option<String>, Query, description = "id of the backup, the latest by default"),
+        ("overwrite" = Option<bool>, Query, description = "replace the keys which exist already"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RestoreReport),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/_admin/restore")]
+pub async fn restore(
+    query: web::Query<RestoreRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    if !is_root_user(user_id(&req)) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only the root user can restore the meta store",
+        ));
+    }
+    match backup::restore(query.into_inner()).await {
+        Ok(report) => Ok(MetaHttpResponse::json(report)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+fn user_id(req: &HttpRequest) -> &str {
+    req.headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
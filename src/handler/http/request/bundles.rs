@@ -0,0 +1,130 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{get, http::header, post, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::meta::{bundles::ConflictStrategy, http::HttpResponse as MetaHttpResponse},
+    service::bundles,
+};
+
+/// ExportBundle
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Bundles",
+    operation_id = "ExportBundle",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("include" = Option<String>, Query, description = "Comma separated kinds to export: folder, dashboard, template, destination, alert, function. Defaults to all"),
+        ("format" = Option<String>, Query, description = "json (default) or gzip"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Bundle),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/_export")]
+pub async fn export_bundle(
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let kinds = match query.get("include").filter(|v| !v.is_empty()) {
+        Some(include) => include.split(',').map(|v| v.trim()).collect::<Vec<_>>(),
+        None => bundles::ALL_KINDS.to_vec(),
+    };
+    if let Some(kind) = kinds.iter().find(|k| !bundles::ALL_KINDS.contains(k)) {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "invalid kind: {kind}"
+        )));
+    }
+    let gzip = match query.get("format").map(|v| v.as_str()) {
+        None | Some("json") => false,
+        Some("gzip") => true,
+        Some(format) => {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "invalid format: {format}"
+            )));
+        }
+    };
+
+    let bundle = match bundles::export(&org_id, &kinds).await {
+        Ok(bundle) => bundle,
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    let data = match bundles::encode(&bundle, gzip) {
+        Ok(data) => data,
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    let (content_type, ext) = if gzip {
+        ("application/gzip", "json.gz")
+    } else {
+        ("application/json", "json")
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{org_id}_bundle.{ext}\""),
+        ))
+        .body(data))
+}
+
+/// ImportBundle
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Bundles",
+    operation_id = "ImportBundle",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("on_conflict" = Option<String>, Query, description = "skip (default), overwrite or rename the existing items"),
+    ),
+    request_body(content = Bundle, description = "Bundle, as exported, json or gzip", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Result of the import of every item", content_type = "application/json", body = ImportReport),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_import")]
+pub async fn import_bundle(
+    path: web::Path<String>,
+    body: web::Bytes,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let strategy = match query.get("on_conflict") {
+        Some(v) => match v.parse::<ConflictStrategy>() {
+            Ok(strategy) => strategy,
+            Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+        },
+        None => ConflictStrategy::default(),
+    };
+    let bundle = match bundles::decode(&body) {
+        Ok(bundle) => bundle,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let report = bundles::import(&org_id, bundle, strategy).await;
+    Ok(HttpResponse::Ok().json(report))
+}
@@ -0,0 +1,101 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::{
+        meta::{caches::CacheInfo, http::HttpResponse as MetaHttpResponse},
+        utils::auth::is_root_user,
+    },
+    service::caches,
+};
+
+/// ListCaches
+///
+/// Lists the in-process caches of the node serving the request with their
+/// entries and bytes, only the root user can access it.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Caches",
+    operation_id = "ListCaches",
+    security(
+        ("Authorization"= [])
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<CacheInfo>),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/_admin/caches")]
+pub async fn list(req: HttpRequest) -> Result<HttpResponse, Error> {
+    if !is_root_user(user_id(&req)) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only the root user can list the caches",
+        ));
+    }
+    Ok(MetaHttpResponse::json(caches::list().await))
+}
+
+/// FlushCache
+///
+/// Flushes a cache of the node serving the request, only the root user can
+/// access it.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Caches",
+    operation_id = "FlushCache",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("name" = String, Path, description = "Cache name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/_admin/caches/{name}/_flush")]
+pub async fn flush(path: web::Path<String>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    if !is_root_user(user_id(&req)) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only the root user can flush the caches",
+        ));
+    }
+    let name = path.into_inner();
+    if !caches::list().await.iter().any(|c| c.name == name) {
+        return Ok(MetaHttpResponse::not_found(format!(
+            "Cache {name} not found"
+        )));
+    }
+    match caches::flush(&name).await {
+        Ok(entries) => Ok(MetaHttpResponse::ok(format!(
+            "Cache {name} flushed, {entries} entries dropped"
+        ))),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+fn user_id(req: &HttpRequest) -> &str {
+    req.headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
@@ -15,11 +15,14 @@
 
 use std::io::Error;
 
-use actix_web::{get, HttpResponse};
+use actix_web::{get, HttpRequest, HttpResponse};
+use config::meta::cluster::NodeInfo;
 use hashbrown::HashMap;
 #[cfg(feature = "enterprise")]
 use {o2_enterprise::enterprise::common::infra::config::O2_CONFIG, std::io::ErrorKind};
 
+use crate::common::{meta::http::HttpResponse as MetaHttpResponse, utils::auth::is_root_user};
+
 /// ListClusters
 #[utoipa::path(
     context_path = "/api",
@@ -52,3 +55,37 @@ pub async fn list_clusters() -> Result<HttpResponse, Error> {
     let clusters: HashMap<String, String> = HashMap::new();
     Ok(HttpResponse::Ok().json(clusters))
 }
+
+/// ListClusterNodes
+///
+/// Lists the nodes of the cluster with their role, version, uptime, status
+/// and the load of their last heartbeat, only the root user can access it.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Clusters",
+    operation_id = "ListClusterNodes",
+    security(
+        ("Authorization"= [])
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<NodeInfo>),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/_cluster/nodes")]
+pub async fn list_nodes(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !is_root_user(user_id) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only the root user can list the nodes",
+        ));
+    }
+    match crate::service::node::list_nodes().await {
+        Ok(nodes) => Ok(MetaHttpResponse::json(nodes)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
@@ -18,12 +18,21 @@ use std::{collections::HashMap, io::Error};
 use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse, Responder};
 
 use crate::{
-    common::meta::{dashboards::MoveDashboard, http::HttpResponse as MetaHttpResponse},
+    common::meta::{
+        dashboards::{
+            variables::ResolveVariablesRequest, CopyDashboard, DashboardFilter, MoveDashboard,
+            DEFAULT_FOLDER,
+        },
+        http::HttpResponse as MetaHttpResponse,
+        rbac::Permission,
+    },
     service::dashboards,
 };
 
 pub mod folders;
 pub mod reports;
+pub mod snapshots;
+pub mod versions;
 
 /// CreateDashboard
 #[utoipa::path(
@@ -56,8 +65,9 @@ pub async fn create_dashboard(
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let org_id = path.into_inner();
+    let user_id = get_user_id(&req);
     let folder = get_folder(req);
-    dashboards::create_dashboard(&org_id, &folder, body).await
+    dashboards::create_dashboard(&org_id, &folder, body, &user_id).await
 }
 
 /// UpdateDashboard
@@ -89,8 +99,9 @@ async fn update_dashboard(
     req: HttpRequest,
 ) -> impl Responder {
     let (org_id, dashboard_id) = path.into_inner();
+    let user_id = get_user_id(&req);
     let folder = get_folder(req);
-    dashboards::update_dashboard(&org_id, &dashboard_id, &folder, body).await
+    dashboards::update_dashboard(&org_id, &dashboard_id, &folder, body, &user_id).await
 }
 
 /// ListDashboards
@@ -103,6 +114,10 @@ async fn update_dashboard(
     ),
     params(
         ("org_id" = String, Path, description = "Organization name"),
+        ("folder" = Option<String>, Query, description = "Folder id, defaults to all the folders when searching and to the default folder otherwise"),
+        ("recursive" = Option<bool>, Query, description = "Include the dashboards of the subfolders"),
+        ("tag" = Option<String>, Query, description = "Only the dashboards with this tag"),
+        ("query" = Option<String>, Query, description = "Text searched in the title, description and tags"),
     ),
     responses(
         (status = StatusCode::OK, body = Dashboards),
@@ -110,8 +125,55 @@ async fn update_dashboard(
 )]
 #[get("/{org_id}/dashboards")]
 async fn list_dashboards(org_id: web::Path<String>, req: HttpRequest) -> impl Responder {
-    let folder = get_folder(req);
-    dashboards::list_dashboards(&org_id.into_inner(), &folder).await
+    let org_id = org_id.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let filter = DashboardFilter {
+        tag: query.get("tag").filter(|v| !v.is_empty()).cloned(),
+        query: query.get("query").filter(|v| !v.is_empty()).cloned(),
+    };
+    let recursive = query
+        .get("recursive")
+        .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+    // searching without a folder looks into every folder
+    let folder = match query.get("folder") {
+        Some(folder) => Some(folder.to_string()),
+        None if filter.is_empty() && !recursive => Some(DEFAULT_FOLDER.to_string()),
+        None => None,
+    };
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    let mut _permitted = None;
+    // Get List of allowed objects
+    #[cfg(feature = "enterprise")]
+    {
+        match crate::handler::http::auth::validator::list_objects_for_user(
+            &org_id, user_id, "GET", "dfolder",
+        )
+        .await
+        {
+            Ok(list) => {
+                _permitted = list;
+            }
+            Err(e) => {
+                return Ok(MetaHttpResponse::forbidden(e.to_string()));
+            }
+        }
+        // Get List of allowed objects ends
+    }
+
+    dashboards::list_dashboards(
+        &org_id,
+        folder.as_deref(),
+        recursive,
+        &filter,
+        user_id,
+        _permitted,
+    )
+    .await
 }
 
 /// GetDashboard
@@ -192,6 +254,7 @@ async fn delete_dashboard(path: web::Path<(String, String)>, req: HttpRequest) -
 async fn move_dashboard(
     path: web::Path<(String, String)>,
     folder: web::Json<MoveDashboard>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, dashboard_id) = path.into_inner();
     if folder.from.is_empty() || folder.to.is_empty() {
@@ -203,10 +266,168 @@ async fn move_dashboard(
         );
     };
 
+    if !is_destination_allowed(&org_id, &req, &folder.to).await {
+        return Ok(MetaHttpResponse::forbidden(
+            "Unauthorized Access to the destination folder",
+        ));
+    }
+
     dashboards::move_dashboard(&org_id, &dashboard_id, &folder.from, &folder.to).await
 }
 
+/// CopyDashboard
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "CopyDashboard",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dashboard_id" = String, Path, description = "Dashboard ID"),
+    ),
+    request_body(
+        content = CopyDashboard,
+        description = "CopyDashboard details",
+        example = json!({
+            "from": "Source folder id",
+            "to": "Destination folder id",
+            "title": "Title of the copy",
+        }),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "The copied dashboard", body = Dashboard),
+        (status = StatusCode::NOT_FOUND, description = "Dashboard not found", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/folders/dashboards/{dashboard_id}/_copy")]
+async fn copy_dashboard(
+    path: web::Path<(String, String)>,
+    body: web::Json<CopyDashboard>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, dashboard_id) = path.into_inner();
+    if body.from.is_empty() || body.to.is_empty() {
+        return Ok(MetaHttpResponse::bad_request(
+            "Please specify from & to folder for the dashboard copy",
+        ));
+    };
+    if !is_destination_allowed(&org_id, &req, &body.to).await {
+        return Ok(MetaHttpResponse::forbidden(
+            "Unauthorized Access to the destination folder",
+        ));
+    }
+
+    dashboards::copy_dashboard(
+        &org_id,
+        &dashboard_id,
+        &body.from,
+        &body.to,
+        body.title.as_deref(),
+        &get_user_id(&req),
+    )
+    .await
+}
+
+// the user needs to be allowed to write into the destination folder
+async fn is_destination_allowed(org_id: &str, req: &HttpRequest, folder_id: &str) -> bool {
+    if cfg!(feature = "enterprise") {
+        return true;
+    }
+    let Some(user_id) = req.headers().get("user_id").and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    let folders = crate::service::db::dashboards::folders::list(org_id)
+        .await
+        .unwrap_or_default();
+    dashboards::is_folder_allowed(org_id, user_id, &folders, folder_id, Permission::Write)
+}
+
+/// ResolveDashboardVariables
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "ResolveDashboardVariables",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dashboard_id" = String, Path, description = "Dashboard ID"),
+    ),
+    request_body(
+        content = ResolveVariablesRequest,
+        description = "Time range and selected values of the variables",
+        example = json!({
+            "start_time": 1714857600000000_i64,
+            "end_time": 1714861200000000_i64,
+            "values": {"namespace": ["prod"]},
+        }),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Values of the variables and the expanded panel queries", body = ResolveVariablesResponse),
+        (status = StatusCode::FORBIDDEN, description = "A query variable reads a stream the user can not read", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Dashboard not found", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/dashboards/{dashboard_id}/variables/_resolve")]
+async fn resolve_variables(
+    path: web::Path<(String, String)>,
+    body: web::Json<ResolveVariablesRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, dashboard_id) = path.into_inner();
+    let user_id = get_user_id(&req);
+    let folder_id = get_folder(req);
+    dashboards::variables::resolve_variables(
+        &org_id,
+        &dashboard_id,
+        &folder_id,
+        &user_id,
+        body.into_inner(),
+    )
+    .await
+}
+
+/// ImportGrafanaDashboard
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "ImportGrafanaDashboard",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("folder" = Option<String>, Query, description = "Folder id, defaults to the default folder"),
+    ),
+    request_body(content = String, description = "Grafana dashboard JSON", content_type = "application/json"),
+    responses(
+        (status = StatusCode::OK, description = "The imported dashboard and the items that were left out", body = GrafanaImportResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid Grafana dashboard", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/dashboards/_import/grafana")]
+async fn import_grafana_dashboard(
+    path: web::Path<String>,
+    body: web::Bytes,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let folder = get_folder(req);
+    dashboards::grafana::import(&org_id, &folder, body).await
+}
+
 fn get_folder(req: HttpRequest) -> String {
     let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
     crate::common::utils::http::get_folder(&query)
 }
+
+fn get_user_id(req: &HttpRequest) -> String {
+    req.headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
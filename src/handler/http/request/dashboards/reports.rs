@@ -202,7 +202,7 @@ async fn delete_report(path: web::Path<(String, String)>) -> Result<HttpResponse
 /// EnableReport
 #[utoipa::path(
     context_path = "/api",
-    tag = "Report",
+    tag = "Reports",
     operation_id = "EnableReport",
     security(
         ("Authorization"= [])
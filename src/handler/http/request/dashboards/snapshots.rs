@@ -0,0 +1,145 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+
+use crate::{common::meta::dashboards::snapshots::CreateSnapshotRequest, service::dashboards};
+
+/// CreateDashboardSnapshot
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "CreateDashboardSnapshot",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dashboard_id" = String, Path, description = "Dashboard ID"),
+        ("folder" = Option<String>, Query, description = "Folder id, defaults to the default folder"),
+    ),
+    request_body(
+        content = CreateSnapshotRequest,
+        description = "Time range, values of the variables and lifetime of the snapshot",
+        example = json!({
+            "start_time": 1714857600000000_i64,
+            "end_time": 1714861200000000_i64,
+            "values": {"namespace": ["prod"]},
+            "expires_in": 86400,
+        }),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Snapshot created", body = SnapshotInfo),
+        (status = StatusCode::FORBIDDEN, description = "Snapshots are disabled for the organization", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Dashboard not found", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/dashboards/{dashboard_id}/snapshots")]
+pub async fn create_snapshot(
+    path: web::Path<(String, String)>,
+    body: web::Json<CreateSnapshotRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, dashboard_id) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let folder_id = crate::common::utils::http::get_folder(&query);
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    dashboards::snapshots::create_snapshot(
+        &org_id,
+        &dashboard_id,
+        &folder_id,
+        user_id,
+        body.into_inner(),
+    )
+    .await
+}
+
+/// ListDashboardSnapshots
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "ListDashboardSnapshots",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dashboard_id" = Option<String>, Query, description = "Only the snapshots of this dashboard"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = SnapshotList),
+    ),
+)]
+#[get("/{org_id}/snapshots")]
+pub async fn list_snapshots(
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let dashboard_id = query.get("dashboard_id").map(|v| v.as_str());
+    dashboards::snapshots::list_snapshots(&org_id, dashboard_id).await
+}
+
+/// DeleteDashboardSnapshot
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "DeleteDashboardSnapshot",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("snapshot_id" = String, Path, description = "Snapshot ID"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Snapshot deleted", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Snapshot not found", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/snapshots/{snapshot_id}")]
+pub async fn delete_snapshot(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, snapshot_id) = path.into_inner();
+    dashboards::snapshots::delete_snapshot(&org_id, &snapshot_id).await
+}
+
+/// GetPublicDashboardSnapshot
+///
+/// Served without authentication, the snapshot id is the secret of the link.
+#[utoipa::path(
+    context_path = "/public",
+    tag = "Dashboards",
+    operation_id = "GetPublicDashboardSnapshot",
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("snapshot_id" = String, Path, description = "Snapshot ID"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = Snapshot),
+        (status = StatusCode::NOT_FOUND, description = "Snapshot not found or expired", body = HttpResponse),
+    ),
+)]
+#[get("/snapshots/{org_id}/{snapshot_id}")]
+pub async fn get_public_snapshot(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, snapshot_id) = path.into_inner();
+    dashboards::snapshots::get_public_snapshot(&org_id, &snapshot_id).await
+}
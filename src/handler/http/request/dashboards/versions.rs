@@ -0,0 +1,145 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+
+use crate::{common::meta::http::HttpResponse as MetaHttpResponse, service::dashboards};
+
+/// ListDashboardVersions
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "ListDashboardVersions",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dashboard_id" = String, Path, description = "Dashboard ID"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = SavedVersionList),
+    ),
+)]
+#[get("/{org_id}/dashboards/{dashboard_id}/versions")]
+pub async fn list_versions(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, dashboard_id) = path.into_inner();
+    dashboards::versions::list_versions(&org_id, &dashboard_id).await
+}
+
+/// DiffDashboardVersions
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "DiffDashboardVersions",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dashboard_id" = String, Path, description = "Dashboard ID"),
+        ("from" = i64, Query, description = "Version to diff from"),
+        ("to" = Option<i64>, Query, description = "Version to diff to, defaults to the newest version"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "JSON patch from one version to the other", body = VersionDiff),
+        (status = StatusCode::NOT_FOUND, description = "Dashboard version not found", body = HttpResponse),
+    ),
+)]
+#[get("/{org_id}/dashboards/{dashboard_id}/versions/_diff")]
+pub async fn diff_versions(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, dashboard_id) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let Some(Ok(from)) = query.get("from").map(|v| v.parse::<i64>()) else {
+        return Ok(MetaHttpResponse::bad_request(
+            "from should be a version of the dashboard",
+        ));
+    };
+    let to = match query.get("to").map(|v| v.parse::<i64>()) {
+        Some(Ok(to)) => Some(to),
+        Some(Err(_)) => {
+            return Ok(MetaHttpResponse::bad_request(
+                "to should be a version of the dashboard",
+            ));
+        }
+        None => None,
+    };
+    dashboards::versions::diff_versions(&org_id, &dashboard_id, from, to).await
+}
+
+/// GetDashboardVersion
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "GetDashboardVersion",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dashboard_id" = String, Path, description = "Dashboard ID"),
+        ("version_id" = i64, Path, description = "Version ID"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = SavedVersion),
+        (status = StatusCode::NOT_FOUND, description = "Dashboard version not found", body = HttpResponse),
+    ),
+)]
+#[get("/{org_id}/dashboards/{dashboard_id}/versions/{version_id}")]
+pub async fn get_version(path: web::Path<(String, String, i64)>) -> Result<HttpResponse, Error> {
+    let (org_id, dashboard_id, version_id) = path.into_inner();
+    dashboards::versions::get_version(&org_id, &dashboard_id, version_id).await
+}
+
+/// RestoreDashboardVersion
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "RestoreDashboardVersion",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dashboard_id" = String, Path, description = "Dashboard ID"),
+        ("version_id" = i64, Path, description = "Version ID"),
+        ("folder" = Option<String>, Query, description = "Folder id, defaults to the default folder"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Dashboard restored", body = Dashboard),
+        (status = StatusCode::NOT_FOUND, description = "Dashboard or version not found", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/dashboards/{dashboard_id}/versions/{version_id}/_restore")]
+pub async fn restore_version(
+    path: web::Path<(String, String, i64)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, dashboard_id, version_id) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let folder_id = crate::common::utils::http::get_folder(&query);
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    dashboards::versions::restore_version(&org_id, &dashboard_id, &folder_id, version_id, user_id)
+        .await
+}
@@ -0,0 +1,152 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+
+use crate::{common::meta::derived_streams::DerivedStream, service::derived_streams};
+
+/// ListDerivedStreams
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Derived Streams",
+    operation_id = "ListDerivedStreams",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = DerivedStreamList),
+    )
+)]
+#[get("/{org_id}/derived_streams")]
+pub async fn list(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    derived_streams::list(&org_id).await
+}
+
+/// GetDerivedStream
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Derived Streams",
+    operation_id = "GetDerivedStream",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Derived stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = DerivedStream),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/derived_streams/{name}")]
+pub async fn get(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    derived_streams::get(&org_id, &name).await
+}
+
+/// CreateDerivedStream
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Derived Streams",
+    operation_id = "CreateDerivedStream",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = DerivedStream, description = "Derived stream data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = DerivedStream),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/derived_streams")]
+pub async fn create(
+    path: web::Path<String>,
+    ds: web::Json<DerivedStream>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let ds = ds.into_inner();
+    let name = ds.name.clone();
+    derived_streams::save(&org_id, &name, ds, true, user_id(&req)).await
+}
+
+/// UpdateDerivedStream
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Derived Streams",
+    operation_id = "UpdateDerivedStream",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Derived stream name"),
+    ),
+    request_body(content = DerivedStream, description = "Derived stream data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = DerivedStream),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/derived_streams/{name}")]
+pub async fn update(
+    path: web::Path<(String, String)>,
+    ds: web::Json<DerivedStream>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    derived_streams::save(&org_id, &name, ds.into_inner(), false, user_id(&req)).await
+}
+
+/// DeleteDerivedStream
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Derived Streams",
+    operation_id = "DeleteDerivedStream",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Derived stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/derived_streams/{name}")]
+pub async fn delete(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    derived_streams::delete(&org_id, &name).await
+}
+
+fn user_id(req: &HttpRequest) -> &str {
+    req.headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
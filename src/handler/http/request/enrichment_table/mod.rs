@@ -16,13 +16,16 @@
 use std::io::Error;
 
 use actix_multipart::Multipart;
-use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use config::SIZE_IN_MB;
 use hashbrown::HashMap;
 
 use crate::{
-    common::meta::http::HttpResponse as MetaHttpResponse,
-    service::enrichment_table::save_enrichment_data,
+    common::meta::{
+        enrichment_table::{EnrichmentTableLookup, EnrichmentTableSource},
+        http::HttpResponse as MetaHttpResponse,
+    },
+    service::enrichment_table::{lookup, save_enrichment_data, source},
 };
 
 /// CreateEnrichmentTable
@@ -94,3 +97,165 @@ pub async fn save_enrichment_table(
         )),
     }
 }
+
+/// SetEnrichmentTableSource
+///
+/// Loads the table from a CSV at an http(s) url, or an object path in the
+/// configured bucket, and reloads it whenever the content changes.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "SetEnrichmentTableSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    request_body(content = EnrichmentTableSource, description = "Enrichment table source", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/enrichment_tables/{table_name}/source")]
+pub async fn set_enrichment_table_source(
+    path: web::Path<(String, String)>,
+    body: web::Json<EnrichmentTableSource>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    source::save_source(&org_id, &table_name, body.into_inner()).await
+}
+
+/// GetEnrichmentTableSource
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "GetEnrichmentTableSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = EnrichmentTableSource),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/enrichment_tables/{table_name}/source")]
+pub async fn get_enrichment_table_source(
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    source::get_source(&org_id, &table_name).await
+}
+
+/// DeleteEnrichmentTableSource
+///
+/// Stops refreshing the table, the data already loaded is kept.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "DeleteEnrichmentTableSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/enrichment_tables/{table_name}/source")]
+pub async fn delete_enrichment_table_source(
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    source::delete_source(&org_id, &table_name).await
+}
+
+/// SetEnrichmentTableLookup
+///
+/// Sets how the table columns are matched by the lookup functions: `exact`,
+/// `cidr` for the longest network containing an ip, or `range` for the
+/// narrowest numeric range containing a number. A lookup with several
+/// conditions only returns the rows matching all of them.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "SetEnrichmentTableLookup",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    request_body(content = EnrichmentTableLookup, description = "Enrichment table lookup", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/enrichment_tables/{table_name}/lookup")]
+pub async fn set_enrichment_table_lookup(
+    path: web::Path<(String, String)>,
+    body: web::Json<EnrichmentTableLookup>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    lookup::save_lookup(&org_id, &table_name, body.into_inner()).await
+}
+
+/// GetEnrichmentTableLookup
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "GetEnrichmentTableLookup",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = EnrichmentTableLookup),
+    )
+)]
+#[get("/{org_id}/enrichment_tables/{table_name}/lookup")]
+pub async fn get_enrichment_table_lookup(
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    lookup::get_lookup(&org_id, &table_name).await
+}
+
+/// DeleteEnrichmentTableLookup
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "DeleteEnrichmentTableLookup",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/enrichment_tables/{table_name}/lookup")]
+pub async fn delete_enrichment_table_lookup(
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    lookup::delete_lookup(&org_id, &table_name).await
+}
@@ -19,7 +19,9 @@ use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse};
 
 use crate::common::{
     meta,
-    meta::functions::{StreamOrder, Transform},
+    meta::functions::{
+        FunctionPreviewRequest, FunctionPreviewResponse, FunctionStats, StreamOrder, Transform,
+    },
     utils::http::get_stream_type_from_request,
 };
 
@@ -123,6 +125,100 @@ async fn delete_function(path: web::Path<(String, String)>) -> Result<HttpRespon
     crate::service::functions::delete_function(org_id, name).await
 }
 
+/// GetFunctionStats
+///
+/// Returns the invocations, errors and execution time of the function, summed
+/// over the ingesters since they started. The records the function failed on
+/// are ingested to the `function_errors_{name}` stream.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "getFunctionStats",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Function name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = FunctionStats),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/functions/{name}/stats")]
+async fn get_function_stats(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    crate::service::functions::get_function_stats(&org_id, &name).await
+}
+
+/// PreviewFunction
+///
+/// Applies the function, or the function given in the body, to the latest
+/// records of the stream and returns them before and after, nothing is
+/// ingested.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "previewFunction",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Function name"),
+        ("stream" = String, Query, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type"),
+    ),
+    request_body(content = FunctionPreviewRequest, description = "Preview options", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = FunctionPreviewResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/functions/{name}/_preview")]
+async fn preview_function(
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => return Ok(meta::http::HttpResponse::bad_request(e)),
+    };
+    let Some(stream_name) = query.get("stream").filter(|v| !v.is_empty()) else {
+        return Ok(meta::http::HttpResponse::bad_request(
+            "stream query param is required",
+        ));
+    };
+    let preview_req = if body.is_empty() {
+        FunctionPreviewRequest::default()
+    } else {
+        match config::utils::json::from_slice(&body) {
+            Ok(v) => v,
+            Err(e) => return Ok(meta::http::HttpResponse::bad_request(e)),
+        }
+    };
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    crate::service::functions::preview_function(
+        &org_id,
+        &name,
+        stream_type,
+        stream_name,
+        user_id,
+        preview_req,
+    )
+    .await
+}
+
 /// UpdateFunction
 #[utoipa::path(
     context_path = "/api",
@@ -0,0 +1,132 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+
+use crate::{
+    common::meta::{
+        http::HttpResponse as MetaHttpResponse,
+        ingestion::{BackfillJob, ListBackfillJobs},
+    },
+    service::logs,
+};
+
+/// BackfillLogs
+///
+/// Ingests a large batch of historical records, a json array or ndjson, in
+/// the background. The records older than `ZO_INGEST_ALLOWED_UPTO` are
+/// accepted and the real time alerts are not evaluated. Returns the job to
+/// follow the progress with.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsBackfill",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = String, description = "Ingest data (json array or ndjson)", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = BackfillJob),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/{stream_name}/_backfill")]
+pub async fn backfill(
+    path: web::Path<(String, String)>,
+    mut payload: web::Payload,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let user_email = in_req.headers().get("user_id").unwrap().to_str().unwrap();
+    let limit = config::get_config().limit.backfill_payload_limit;
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        match chunk {
+            Ok(chunk) => body.extend_from_slice(&chunk),
+            Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+        }
+        if body.len() > limit {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "backfill request is larger than {limit} bytes"
+            )));
+        }
+    }
+    match logs::backfill::submit(&org_id, &stream_name, &body, user_email).await {
+        Ok(job) => Ok(MetaHttpResponse::json(job)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// ListBackfillJobs
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsListBackfill",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ListBackfillJobs),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/_backfill")]
+pub async fn list_backfill(org_id: web::Path<String>) -> Result<HttpResponse, Error> {
+    match logs::backfill::list(&org_id).await {
+        Ok(list) => Ok(MetaHttpResponse::json(ListBackfillJobs { list })),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// GetBackfillJob
+///
+/// Returns the progress of a backfill job.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsGetBackfill",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("job_id" = String, Path, description = "Backfill job id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = BackfillJob),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/_backfill/{job_id}")]
+pub async fn get_backfill(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, job_id) = path.into_inner();
+    match logs::backfill::get(&org_id, &job_id).await {
+        Ok(Some(job)) => Ok(MetaHttpResponse::json(job)),
+        Ok(None) => Ok(MetaHttpResponse::not_found(format!(
+            "backfill job {job_id} not found"
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
@@ -15,7 +15,9 @@
 
 use std::io::Error;
 
+use actix_multipart::Multipart;
 use actix_web::{http, post, web, HttpRequest, HttpResponse};
+use futures::StreamExt;
 
 use crate::{
     common::meta::{
@@ -26,6 +28,7 @@ use crate::{
     },
     handler::http::request::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO},
     service::{
+        ingestion::replication::ReplicationError,
         logs,
         logs::otlp_http::{logs_json_handler, logs_proto_handler},
     },
@@ -60,6 +63,12 @@ pub async fn bulk(
     Ok(
         match logs::bulk::ingest(&org_id, body, **thread_id, user_email).await {
             Ok(v) => MetaHttpResponse::json(v),
+            Err(e) if e.is::<ReplicationError>() => {
+                HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                    http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                    e.to_string(),
+                ))
+            }
             Err(e) => {
                 log::error!("Error processing request {org_id}/_bulk: {:?}", e);
                 HttpResponse::BadRequest().json(MetaHttpResponse::error(
@@ -86,6 +95,7 @@ pub async fn bulk(
     request_body(content = String, description = "Ingest data (multiple line json)", content_type = "application/json"),
     responses(
         (status = 200, description = "Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "olympics","successful": 3,"failed": 0}]})),
+        (status = 202, description = "Kept in the wal only, the object store or the meta store failing", content_type = "application/json", body = IngestionResponse),
         (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -109,6 +119,7 @@ pub async fn multi(
         .await
         {
             Ok(v) => match v.code {
+                202 => HttpResponse::Accepted().json(v),
                 503 => HttpResponse::ServiceUnavailable().json(v),
                 _ => MetaHttpResponse::json(v),
             },
@@ -123,6 +134,83 @@ pub async fn multi(
     )
 }
 
+/// _raw ingestion API
+///
+/// The body is plain text with a record per line, the `line_pattern` of the
+/// stream settings extracts fields from the lines, or its `log_format` parses
+/// them as VPC Flow Logs, ALB access logs or Windows events. A
+/// `multipart/form-data` body uploads log files instead, gzipped when their
+/// name ends in `.gz` and read as multiple line json when their name or
+/// content type is json.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsIngestionRaw",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = String, description = "Ingest data (a record per line)", content_type = "text/plain", example = "2024-03-01T10:00:00Z INFO started\n2024-03-01T10:00:01Z ERROR failed"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "app","successful": 2,"failed": 0}]})),
+        (status = 202, description = "Kept in the wal only, the object store or the meta store failing", content_type = "application/json", body = IngestionResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/{stream_name}/_raw")]
+pub async fn raw(
+    path: web::Path<(String, String)>,
+    mut payload: web::Payload,
+    thread_id: web::Data<usize>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let user_email = in_req.headers().get("user_id").unwrap().to_str().unwrap();
+    let content_type = in_req
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let ret = if content_type.starts_with("multipart/form-data") {
+        let payload = Multipart::new(in_req.headers(), payload);
+        logs::raw::ingest_upload(&org_id, &stream_name, payload, **thread_id, user_email).await
+    } else {
+        let mut body = web::BytesMut::new();
+        while let Some(chunk) = payload.next().await {
+            match chunk {
+                Ok(chunk) => body.extend_from_slice(&chunk),
+                Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+            }
+        }
+        let body = body.freeze();
+        logs::ingest::ingest(
+            &org_id,
+            &stream_name,
+            IngestionRequest::RAW(&body),
+            **thread_id,
+            user_email,
+        )
+        .await
+    };
+    Ok(match ret {
+        Ok(v) => match v.code {
+            202 => HttpResponse::Accepted().json(v),
+            503 => HttpResponse::ServiceUnavailable().json(v),
+            _ => MetaHttpResponse::json(v),
+        },
+        Err(e) => {
+            log::error!("Error processing request {org_id}/{stream_name}: {:?}", e);
+            HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                http::StatusCode::BAD_REQUEST.into(),
+                e.to_string(),
+            ))
+        }
+    })
+}
+
 /// _json ingestion API
 #[utoipa::path(
     context_path = "/api",
@@ -138,6 +226,7 @@ pub async fn multi(
     request_body(content = String, description = "Ingest data (json array)", content_type = "application/json", example = json!([{"Year": 1896, "City": "Athens", "Sport": "Aquatics", "Discipline": "Swimming", "Athlete": "Alfred", "Country": "HUN"},{"Year": 1896, "City": "Athens", "Sport": "Aquatics", "Discipline": "Swimming", "Athlete": "HERSCHMANN", "Country":"CHN"}])),
     responses(
         (status = 200, description = "Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "olympics","successful": 3,"failed": 0}]})),
+        (status = 202, description = "Kept in the wal only, the object store or the meta store failing", content_type = "application/json", body = IngestionResponse),
         (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -161,6 +250,7 @@ pub async fn json(
         .await
         {
             Ok(v) => match v.code {
+                202 => HttpResponse::Accepted().json(v),
                 503 => HttpResponse::ServiceUnavailable().json(v),
                 _ => MetaHttpResponse::json(v),
             },
@@ -177,7 +267,7 @@ pub async fn json(
 
 /// _kinesis_firehose ingestion API
 #[utoipa::path(
-    context_path = "/api",
+    context_path = "/aws",
     tag = "Logs",
     operation_id = "AWSLogsIngestion",
     security(
@@ -233,6 +323,25 @@ pub async fn handle_kinesis_request(
     )
 }
 
+/// _sub GCP pub/sub push ingestion API
+#[utoipa::path(
+    context_path = "/gcp",
+    tag = "Logs",
+    operation_id = "GCPLogsIngestion",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = GCPIngestionRequest, description = "Pub/Sub push message", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "olympics","successful": 1,"failed": 0}]})),
+        (status = 202, description = "Kept in the wal only, the object store or the meta store failing", content_type = "application/json", body = IngestionResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[post("/{org_id}/{stream_name}/_sub")]
 pub async fn handle_gcp_request(
     path: web::Path<(String, String)>,
@@ -252,7 +361,10 @@ pub async fn handle_gcp_request(
         )
         .await
         {
-            Ok(v) => MetaHttpResponse::json(v),
+            Ok(v) => match v.code {
+                202 => HttpResponse::Accepted().json(v),
+                _ => MetaHttpResponse::json(v),
+            },
             Err(e) => {
                 log::error!("Error processing request {org_id}/{stream_name}: {:?}", e);
                 HttpResponse::BadRequest().json(MetaHttpResponse::error(
@@ -0,0 +1,113 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, web, HttpResponse};
+
+use crate::{common::meta::masking::MaskingPolicy, service::masking};
+
+/// ListMaskingPolicies
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Masking",
+    operation_id = "ListMaskingPolicies",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = MaskingPolicyList),
+    )
+)]
+#[get("/{org_id}/masking_policies")]
+pub async fn list(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    masking::list_policies(&org_id).await
+}
+
+/// GetMaskingPolicy
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Masking",
+    operation_id = "GetMaskingPolicy",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Masking policy name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = MaskingPolicy),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/masking_policies/{name}")]
+pub async fn get(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    masking::get_policy(&org_id, &name).await
+}
+
+/// SaveMaskingPolicy
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Masking",
+    operation_id = "SaveMaskingPolicy",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = MaskingPolicy, description = "Masking policy data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = MaskingPolicy),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/masking_policies")]
+pub async fn save(
+    path: web::Path<String>,
+    policy: web::Json<MaskingPolicy>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    masking::save_policy(&org_id, policy.into_inner()).await
+}
+
+/// DeleteMaskingPolicy
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Masking",
+    operation_id = "DeleteMaskingPolicy",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Masking policy name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/masking_policies/{name}")]
+pub async fn delete(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    masking::delete_policy(&org_id, &name).await
+}
@@ -40,6 +40,7 @@ use crate::{
     request_body(content = String, description = "Ingest data (json array)", content_type = "application/json", example = json!([{"__name__":"metrics stream name","__type__":"counter / gauge / histogram / summary","label_name1":"label_value1","label_name2":"label_value2", "_timestamp":1687175143,"value":1.2}])),
     responses(
         (status = 200, description = "Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "up","successful": 3,"failed": 0}]})),
+        (status = 202, description = "Kept in the wal only, the object store or the meta store failing", content_type = "application/json", body = IngestionResponse),
         (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -52,7 +53,11 @@ pub async fn json(
     let org_id = org_id.into_inner();
     Ok(
         match metrics::json::ingest(&org_id, body, **thread_id).await {
-            Ok(v) => HttpResponse::Ok().json(v),
+            Ok(v) => match v.code {
+                202 => HttpResponse::Accepted().json(v),
+                503 => HttpResponse::ServiceUnavailable().json(v),
+                _ => HttpResponse::Ok().json(v),
+            },
             Err(e) => {
                 log::error!("Error processing request {org_id}/metrics: {:?}", e);
                 HttpResponse::BadRequest().json(MetaHttpResponse::error(
@@ -14,19 +14,30 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod alerts;
+pub mod annotations;
+pub mod audit;
 pub mod authz;
+pub mod backup;
+pub mod bundles;
+pub mod caches;
 pub mod clusters;
 pub mod dashboards;
+pub mod derived_streams;
 pub mod enrichment_table;
 pub mod functions;
 pub mod kv;
 pub mod logs;
+pub mod masking;
 pub mod metrics;
 pub mod organization;
 pub mod pipelines;
 pub mod prom;
+pub mod provisioning;
 pub mod rum;
+pub mod runtime_config;
+pub mod scim;
 pub mod search;
+pub mod service_accounts;
 pub mod status;
 pub mod stream;
 pub mod syslog;
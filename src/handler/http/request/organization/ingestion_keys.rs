@@ -0,0 +1,97 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, web, HttpResponse};
+
+use crate::{
+    common::{meta::organization::IngestionKeyRequest, utils::auth::UserEmail},
+    service::ingestion_keys,
+};
+
+/// ListIngestionKeys
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "ListOrganizationIngestionKeys",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<IngestionKey>),
+    )
+)]
+#[get("/{org_id}/ingestion_keys")]
+pub async fn list(path: web::Path<String>, user_email: UserEmail) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    ingestion_keys::list_keys(&org_id, &user_email.user_id).await
+}
+
+/// CreateIngestionKey
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "CreateOrganizationIngestionKey",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = IngestionKeyRequest, description = "Ingestion key data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = IngestionKeyResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/ingestion_keys")]
+pub async fn create(
+    path: web::Path<String>,
+    user_email: UserEmail,
+    req: web::Json<IngestionKeyRequest>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    ingestion_keys::create_key(&org_id, &user_email.user_id, req.into_inner()).await
+}
+
+/// RevokeIngestionKey
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "RevokeOrganizationIngestionKey",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("key_id" = String, Path, description = "Ingestion key id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/ingestion_keys/{key_id}")]
+pub async fn revoke(
+    path: web::Path<(String, String)>,
+    user_email: UserEmail,
+) -> Result<HttpResponse, Error> {
+    let (org_id, key_id) = path.into_inner();
+    ingestion_keys::revoke_key(&org_id, &user_email.user_id, &key_id).await
+}
@@ -13,5 +13,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 pub mod es;
+pub mod ingestion_keys;
+pub mod network_policy;
 pub mod org;
+pub mod quota;
 pub mod settings;
+pub mod usage;
@@ -0,0 +1,98 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, put, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::{
+        meta::organization::OrgNetworkPolicy,
+        utils::{auth::UserEmail, http::get_client_ip},
+    },
+    service::network_policy,
+};
+
+/// GetOrganizationNetworkPolicy
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationNetworkPolicyGet",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = OrgNetworkPolicy),
+    )
+)]
+#[get("/{org_id}/network_policy")]
+pub async fn get(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    network_policy::get_policy(&org_id).await
+}
+
+/// SetOrganizationNetworkPolicy
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationNetworkPolicySet",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = OrgNetworkPolicy, description = "Organization network policy", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = OrgNetworkPolicy),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/network_policy")]
+pub async fn set(
+    path: web::Path<String>,
+    user_email: UserEmail,
+    body: web::Json<OrgNetworkPolicy>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let ip = get_client_ip(&req.connection_info(), req.peer_addr());
+    network_policy::set_policy(&org_id, &user_email.user_id, ip, body.into_inner()).await
+}
+
+/// DeleteOrganizationNetworkPolicy
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationNetworkPolicyDelete",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/network_policy")]
+pub async fn delete(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    network_policy::delete_policy(&org_id).await
+}
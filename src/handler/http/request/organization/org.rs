@@ -337,11 +337,10 @@ async fn create_user_rumtoken(
     security(
         ("Authorization"= [])
     ),
-    params(
-        ("org_id" = String, Path, description = "Organization name"),
-      ),
+    request_body(content = Organization, description = "Organization data", content_type = "application/json", example = json!({"identifier": "acme", "label": "Acme"})),
     responses(
-        (status = 200, description = "Success", content_type = "application/json", body = RumIngestionResponse),
+        (status = 200, description = "Success", content_type = "application/json", body = Organization),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
 #[post("/organizations")]
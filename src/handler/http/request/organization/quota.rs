@@ -0,0 +1,108 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, put, web, HttpResponse};
+
+use crate::{
+    common::{
+        meta::{http::HttpResponse as MetaHttpResponse, organization::OrgQuota},
+        utils::auth::{is_root_user, UserEmail},
+    },
+    service::quota,
+};
+
+/// GetOrganizationQuota
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationQuotaGet",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = OrgQuotaResponse),
+    )
+)]
+#[get("/{org_id}/quotas")]
+pub async fn get(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    quota::get_quota(&org_id).await
+}
+
+/// SetOrganizationQuota
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationQuotaSet",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = OrgQuota, description = "Organization quota", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = OrgQuota),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/quotas")]
+pub async fn set(
+    path: web::Path<String>,
+    user_email: UserEmail,
+    body: web::Json<OrgQuota>,
+) -> Result<HttpResponse, Error> {
+    if !is_root_user(&user_email.user_id) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only root user can set organization quotas",
+        ));
+    }
+    let org_id = path.into_inner();
+    quota::set_quota(&org_id, body.into_inner()).await
+}
+
+/// DeleteOrganizationQuota
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationQuotaDelete",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/quotas")]
+pub async fn delete(path: web::Path<String>, user_email: UserEmail) -> Result<HttpResponse, Error> {
+    if !is_root_user(&user_email.user_id) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only root user can delete organization quotas",
+        ));
+    }
+    let org_id = path.into_inner();
+    quota::delete_quota(&org_id).await
+}
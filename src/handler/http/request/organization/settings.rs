@@ -136,7 +136,7 @@ async fn upload_logo(mut payload: Multipart) -> Result<HttpResponse, StdErr> {
 #[cfg(not(feature = "enterprise"))]
 #[post("/{org_id}/settings/logo")]
 async fn upload_logo() -> Result<HttpResponse, StdErr> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -151,7 +151,7 @@ async fn delete_logo() -> Result<HttpResponse, StdErr> {
 #[cfg(not(feature = "enterprise"))]
 #[delete("/{org_id}/settings/logo")]
 async fn delete_logo() -> Result<HttpResponse, StdErr> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -166,7 +166,7 @@ async fn set_logo_text(body: web::Bytes) -> Result<HttpResponse, StdErr> {
 #[cfg(not(feature = "enterprise"))]
 #[post("/{org_id}/settings/logo/text")]
 async fn set_logo_text() -> Result<HttpResponse, StdErr> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -181,5 +181,5 @@ async fn delete_logo_text() -> Result<HttpResponse, StdErr> {
 #[cfg(not(feature = "enterprise"))]
 #[delete("/{org_id}/settings/logo/text")]
 async fn delete_logo_text() -> Result<HttpResponse, StdErr> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
@@ -0,0 +1,58 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{get, web, HttpResponse};
+
+use crate::service::usage::metering;
+
+/// GetOrganizationUsage
+///
+/// Returns the ingested, searched and stored bytes of the organization for
+/// each hour, the last day unless `start_time` and `end_time` are given.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationUsageGet",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("start_time" = Option<i64>, Query, description = "start time, in microseconds"),
+        ("end_time" = Option<i64>, Query, description = "end time, in microseconds"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = OrgUsageResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/metering")]
+pub async fn get(
+    path: web::Path<String>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let end_time = query
+        .get("end_time")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_micros());
+    let start_time = query
+        .get("start_time")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(end_time - 86_400_000_000);
+    metering::get(&org_id, start_time, end_time).await
+}
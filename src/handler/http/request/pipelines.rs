@@ -19,7 +19,10 @@ use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse};
 
 use crate::{
     common::{
-        meta::{self, pipelines::PipeLine},
+        meta::{
+            self,
+            pipelines::{PipeLine, PipelineTestRequest},
+        },
         utils::http::get_stream_type_from_request,
     },
     service::format_stream_name,
@@ -80,6 +83,11 @@ pub async fn save_pipeline(
             routing.insert(formatted_key, value);
         }
     }
+    for step in pipeline.steps.iter_mut() {
+        if let Some(destination) = step.destination.as_mut() {
+            *destination = format_stream_name(destination);
+        }
+    }
     pipeline.stream_type = stream_type;
     crate::service::pipelines::save_pipeline(org_id, pipeline).await
 }
@@ -96,7 +104,7 @@ pub async fn save_pipeline(
         ("org_id" = String, Path, description = "Organization name"),
     ),
     responses(
-        (status = 200, description = "Success", content_type = "application/json", body = PipelineList),
+        (status = 200, description = "Success", content_type = "application/json", body = PipeLineList),
     )
 )]
 #[get("/{org_id}/pipelines")]
@@ -219,5 +227,90 @@ pub async fn update_pipeline(
             routing.insert(formatted_key, value);
         }
     }
+    for step in pipeline.steps.iter_mut() {
+        if let Some(destination) = step.destination.as_mut() {
+            *destination = format_stream_name(destination);
+        }
+    }
     crate::service::pipelines::update_pipeline(&org_id, name, pipeline).await
 }
+
+/// ListPipelineVersions
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Pipelines",
+    operation_id = "listPipelineVersions",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("name" = String, Path, description = "Pipeline name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = PipelineVersionList),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/pipelines/{name}/versions")]
+async fn list_pipeline_versions(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(crate::common::meta::http::HttpResponse::bad_request(e));
+        }
+    };
+    crate::service::pipelines::list_pipeline_versions(&org_id, stream_type, &stream_name, &name)
+        .await
+}
+
+/// TestPipeline
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Pipelines",
+    operation_id = "testPipeline",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("name" = String, Path, description = "Pipeline name"),
+    ),
+    request_body(content = PipelineTestRequest, description = "Sample records, and optionally an unsaved pipeline", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = PipelineTestResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/pipelines/{name}/_test")]
+async fn test_pipeline(
+    path: web::Path<(String, String, String)>,
+    body: web::Json<PipelineTestRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(crate::common::meta::http::HttpResponse::bad_request(e));
+        }
+    };
+    let mut body = body.into_inner();
+    if let Some(pipeline) = body.pipeline.as_mut() {
+        pipeline.name = name.trim().to_string();
+        for step in pipeline.steps.iter_mut() {
+            if let Some(destination) = step.destination.as_mut() {
+                *destination = format_stream_name(destination);
+            }
+        }
+    }
+    crate::service::pipelines::test_pipeline(&org_id, stream_type, &stream_name, &name, body).await
+}
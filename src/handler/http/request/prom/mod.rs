@@ -23,7 +23,10 @@ use promql_parser::parser;
 
 use crate::{
     common::meta::{self, http::HttpResponse as MetaHttpResponse},
-    service::{metrics, promql, promql::MetricsQueryRequest},
+    service::{
+        ingestion::replication::ReplicationError, metrics, promql, promql::MetricsQueryRequest,
+        quota,
+    },
 };
 
 /// prometheus remote-write endpoint for metrics
@@ -56,6 +59,12 @@ pub async fn remote_write(
         Ok(
             match metrics::prom::remote_write(&org_id, **thread_id, body).await {
                 Ok(_) => HttpResponse::Ok().into(),
+                Err(e) if e.is::<ReplicationError>() => {
+                    HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                        http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                        e.to_string(),
+                    ))
+                }
                 Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
                     http::StatusCode::BAD_REQUEST.into(),
                     e.to_string(),
@@ -122,6 +131,48 @@ pub async fn query_get(
     query(&org_id.into_inner(), req.into_inner(), in_req).await
 }
 
+/// prometheus instant queries, with the parameters in the form body
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Metrics",
+    operation_id = "PrometheusQueryPost",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("query" = String, Query, description = "Prometheus expression query string"),
+        ("time" = Option<String>, Query, description = "<rfc3339 | unix_timestamp>: Evaluation timestamp. Optional"),
+        ("timeout" = Option<String>, Query, description = "Evaluation timeout"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse, example = json!({
+            "status" : "success",
+            "data" : {
+               "resultType" : "vector",
+               "result" : [
+                  {
+                     "metric" : {
+                        "__name__" : "up",
+                        "job" : "prometheus",
+                        "instance" : "localhost:9090"
+                     },
+                     "value": [ 1435781451.781, "1" ]
+                  },
+                  {
+                     "metric" : {
+                        "__name__" : "up",
+                        "job" : "node",
+                        "instance" : "localhost:9100"
+                     },
+                     "value" : [ 1435781451.781, "0" ]
+                  }
+               ]
+            }
+        })),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[post("/{org_id}/prometheus/api/v1/query")]
 pub async fn query_post(
     org_id: web::Path<String>,
@@ -148,7 +199,7 @@ async fn query(
     {
         use crate::common::{
             infra::config::USERS,
-            utils::auth::{is_root_user, AuthExtractor},
+            utils::auth::{is_root_user, is_service_account, AuthExtractor},
         };
 
         let ast = parser::parse(&req.query.clone().unwrap()).unwrap();
@@ -157,7 +208,7 @@ async fn query(
         };
         promql_parser::util::walk_expr(&mut visitor, &ast).unwrap();
 
-        if !is_root_user(user_email) {
+        if !is_root_user(user_email) && !is_service_account(user_email) {
             for name in visitor.name {
                 let user: meta::user::User = USERS
                     .get(&format!("{org_id}/{}", user_email))
@@ -274,6 +325,58 @@ pub async fn query_range_get(
     query_range(&org_id.into_inner(), req.into_inner(), in_req).await
 }
 
+/// prometheus range queries, with the parameters in the form body
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Metrics",
+    operation_id = "PrometheusRangeQueryPost",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("query" = String, Query, description = "Prometheus expression query string"),
+        ("start" = String, Query, description = "<rfc3339 | unix_timestamp>: Start timestamp, inclusive"),
+        ("end" = String, Query, description = "<rfc3339 | unix_timestamp>: End timestamp, inclusive"),
+        ("step" = Option<String>, Query, description = "Query resolution step width in duration format or float number of seconds"),
+        ("timeout" = Option<String>, Query, description = "Evaluation timeout"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse, example = json!({
+            "status" : "success",
+            "data" : {
+               "resultType" : "matrix",
+               "result" : [
+                  {
+                     "metric" : {
+                        "__name__" : "up",
+                        "job" : "prometheus",
+                        "instance" : "localhost:9090"
+                     },
+                     "values" : [
+                        [ 1435781430.781, "1" ],
+                        [ 1435781445.781, "1" ],
+                        [ 1435781460.781, "1" ]
+                     ]
+                  },
+                  {
+                     "metric" : {
+                        "__name__" : "up",
+                        "job" : "node",
+                        "instance" : "localhost:9091"
+                     },
+                     "values" : [
+                        [ 1435781430.781, "0" ],
+                        [ 1435781445.781, "0" ],
+                        [ 1435781460.781, "1" ]
+                     ]
+                  }
+               ]
+            }
+        })),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[post("/{org_id}/prometheus/api/v1/query_range")]
 pub async fn query_range_post(
     org_id: web::Path<String>,
@@ -300,7 +403,7 @@ async fn query_range(
     {
         use crate::common::{
             infra::config::USERS,
-            utils::auth::{is_root_user, AuthExtractor},
+            utils::auth::{is_root_user, is_service_account, AuthExtractor},
         };
 
         let ast = parser::parse(&req.query.clone().unwrap()).unwrap();
@@ -309,7 +412,7 @@ async fn query_range(
         };
         promql_parser::util::walk_expr(&mut visitor, &ast).unwrap();
 
-        if !is_root_user(user_email) {
+        if !is_root_user(user_email) && !is_service_account(user_email) {
             for name in visitor.name {
                 let user: meta::user::User = USERS
                     .get(&format!("{org_id}/{}", user_email))
@@ -507,6 +610,44 @@ pub async fn series_get(
     series(&org_id, req.into_inner(), _in_req).await
 }
 
+/// prometheus finding series by label matchers, with the parameters in the form body
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Metrics",
+    operation_id = "PrometheusSeriesPost",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("match[]" = String, Query, description = "<series_selector>: Series selector argument that selects the series to return"),
+        ("start" = Option<String>, Query, description = "<rfc3339 | unix_timestamp>: Start timestamp"),
+        ("end" = Option<String>, Query, description = "<rfc3339 | unix_timestamp>: End timestamp"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse, example = json!({
+            "status" : "success",
+            "data" : [
+               {
+                  "__name__" : "up",
+                  "job" : "prometheus",
+                  "instance" : "localhost:9090"
+               },
+               {
+                  "__name__" : "up",
+                  "job" : "node",
+                  "instance" : "localhost:9091"
+               },
+               {
+                  "__name__" : "process_start_time_seconds",
+                  "job" : "prometheus",
+                  "instance" : "localhost:9090"
+               }
+            ]
+        })),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[post("/{org_id}/prometheus/api/v1/series")]
 pub async fn series_post(
     org_id: web::Path<String>,
@@ -545,7 +686,7 @@ async fn series(
     {
         use crate::common::{
             infra::config::USERS,
-            utils::auth::{is_root_user, AuthExtractor},
+            utils::auth::{is_root_user, is_service_account, AuthExtractor},
         };
 
         let metric_name = match selector
@@ -559,7 +700,7 @@ async fn series(
         let user_id = _in_req.headers().get("user_id").unwrap();
         let user_email = user_id.to_str().unwrap();
 
-        if !is_root_user(user_email) {
+        if !is_root_user(user_email) && !is_service_account(user_email) {
             let user: meta::user::User = USERS
                 .get(&format!("{org_id}/{}", user_email))
                 .unwrap()
@@ -585,7 +726,7 @@ async fn series(
     }
 
     Ok(
-        match metrics::prom::get_series(org_id, selector, start, end).await {
+        match metrics::prom::get_series(org_id, &user_id(&_in_req), selector, start, end).await {
             Ok(resp) => HttpResponse::Ok().json(promql::ApiFuncResponse::ok(resp)),
             Err(err) => {
                 log::error!("get_series failed: {err}");
@@ -649,6 +790,50 @@ pub async fn labels_get(
     labels(&org_id, req.into_inner()).await
 }
 
+/// prometheus getting label names, with the parameters in the form body
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Metrics",
+    operation_id = "PrometheusLabelsPost",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("match[]" = String, Query, description = "Series selector argument that selects the series from which to read the label names"),
+        ("start" = Option<String>, Query, description = "<rfc3339 | unix_timestamp>: Start timestamp"),
+        ("end" = Option<String>, Query, description = "<rfc3339 | unix_timestamp>: End timestamp"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse, example = json!({
+            "status": "success",
+            "data": [
+                "__name__",
+                "call",
+                "code",
+                "config",
+                "dialer_name",
+                "endpoint",
+                "event",
+                "goversion",
+                "handler",
+                "instance",
+                "interval",
+                "job",
+                "le",
+                "listener_name",
+                "name",
+                "quantile",
+                "reason",
+                "role",
+                "scrape_job",
+                "slice",
+                "version"
+            ]
+        })),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[post("/{org_id}/prometheus/api/v1/labels")]
 pub async fn labels_post(
     org_id: web::Path<String>,
@@ -720,6 +905,7 @@ async fn labels(org_id: &str, req: meta::prom::RequestLabels) -> Result<HttpResp
 pub async fn label_values(
     path: web::Path<(String, String)>,
     req: web::Query<meta::prom::RequestLabelValues>,
+    in_req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, label_name) = path.into_inner();
     let meta::prom::RequestLabelValues {
@@ -736,7 +922,16 @@ pub async fn label_values(
         }
     };
     Ok(
-        match metrics::prom::get_label_values(&org_id, label_name, selector, start, end).await {
+        match metrics::prom::get_label_values(
+            &org_id,
+            &user_id(&in_req),
+            label_name,
+            selector,
+            start,
+            end,
+        )
+        .await
+        {
             Ok(resp) => HttpResponse::Ok().json(promql::ApiFuncResponse::ok(resp)),
             Err(err) => {
                 log::error!("get_label_values failed: {err}");
@@ -830,6 +1025,25 @@ pub async fn format_query_get(
     format_query(&org_id, &req.query, _in_req)
 }
 
+/// prometheus formatting query expressions, with the parameters in the form body
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Metrics",
+    operation_id = "PrometheusFormatQueryPost",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("query" = String, Query, description = "Prometheus expression query string."),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse, example = json!({
+            "status" : "success",
+            "data" : "foo / bar"
+        })),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[post("/{org_id}/prometheus/api/v1/format_query")]
 pub async fn format_query_post(
     org_id: web::Path<String>,
@@ -857,6 +1071,14 @@ fn format_query(_org_id: &str, query: &str, _in_req: HttpRequest) -> Result<Http
     Ok(HttpResponse::Ok().json(promql::ApiFuncResponse::ok(expr.prettify())))
 }
 
+fn user_id(req: &HttpRequest) -> String {
+    req.headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
 fn search_timeout(timeout: Option<String>) -> i64 {
     match timeout {
         None => 0,
@@ -876,6 +1098,14 @@ async fn search(
     req: &MetricsQueryRequest,
     user_email: &str,
 ) -> Result<HttpResponse, Error> {
+    if let Err(e) = quota::check_query_quota(org_id).await {
+        return Ok(HttpResponse::TooManyRequests().json(promql::QueryResponse {
+            status: promql::Status::Error,
+            data: None,
+            error_type: Some("too_many_requests".to_string()),
+            error: Some(e.to_string()),
+        }));
+    }
     match promql::search::search(org_id, req, timeout, user_email).await {
         Ok(data) => Ok(HttpResponse::Ok().json(promql::QueryResponse {
             status: promql::Status::Success,
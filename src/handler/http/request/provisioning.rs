@@ -0,0 +1,87 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use config::get_config;
+
+use crate::{
+    common::meta::http::HttpResponse as MetaHttpResponse,
+    service::{db, provisioning},
+};
+
+/// GetProvisioningReport
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Provisioning",
+    operation_id = "GetProvisioningReport",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Report of the last reconciliation", content_type = "application/json", body = ProvisioningReport),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/provisioning")]
+pub async fn get_report(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match db::provisioning::get_report(&org_id).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(_) => Ok(MetaHttpResponse::not_found(
+            "The organization has not been reconciled yet",
+        )),
+    }
+}
+
+/// SyncProvisioning
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Provisioning",
+    operation_id = "SyncProvisioning",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("dry_run" = Option<bool>, Query, description = "Only report the drift, defaults to ZO_PROVISIONING_DRY_RUN"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ProvisioningReport),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Provisioning is disabled", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/provisioning/_sync")]
+pub async fn sync(path: web::Path<String>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let cfg = get_config();
+    if !cfg.provisioning.enabled {
+        return Ok(MetaHttpResponse::forbidden("Provisioning is disabled"));
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let dry_run = match query.get("dry_run") {
+        Some(v) => v.parse::<bool>().unwrap_or(true),
+        None => cfg.provisioning.dry_run,
+    };
+    match provisioning::sync(&org_id, dry_run).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
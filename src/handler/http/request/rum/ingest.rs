@@ -209,6 +209,7 @@ async fn ingest_multi_json(
     Ok(
         match logs::multi::ingest_with_keys(org_id, stream_name, body, extend_json, thread_id).await
         {
+            Ok(v) if v.code == 503 => HttpResponse::ServiceUnavailable().json(v),
             Ok(v) => MetaHttpResponse::json(v),
             Err(e) => MetaHttpResponse::bad_request(e),
         },
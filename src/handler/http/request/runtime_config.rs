@@ -0,0 +1,96 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{get, put, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::{
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            runtime_config::{RuntimeConfig, RuntimeConfigRequest},
+        },
+        utils::auth::is_root_user,
+    },
+    service::runtime_config,
+};
+
+/// GetRuntimeConfig
+///
+/// Lists the settings that can be changed without a restart with their
+/// current values, only the root user can access it.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Config",
+    operation_id = "GetRuntimeConfig",
+    security(
+        ("Authorization"= [])
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RuntimeConfig),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/_admin/config")]
+pub async fn get(req: HttpRequest) -> Result<HttpResponse, Error> {
+    if !is_root_user(user_id(&req)) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only the root user can read the runtime config",
+        ));
+    }
+    Ok(MetaHttpResponse::json(runtime_config::get()))
+}
+
+/// UpdateRuntimeConfig
+///
+/// Changes settings on all the nodes without a restart, a null value goes
+/// back to the value of the env. Only the root user can access it.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Config",
+    operation_id = "UpdateRuntimeConfig",
+    security(
+        ("Authorization"= [])
+    ),
+    request_body(content = RuntimeConfigRequest, description = "Settings to change", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RuntimeConfig),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/_admin/config")]
+pub async fn update(
+    body: web::Json<RuntimeConfigRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    if !is_root_user(user_id(&req)) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only the root user can change the runtime config",
+        ));
+    }
+    match runtime_config::update(body.into_inner()).await {
+        Ok(config) => Ok(MetaHttpResponse::json(config)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+fn user_id(req: &HttpRequest) -> &str {
+    req.headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
@@ -0,0 +1,329 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, patch, post, put, web, HttpResponse};
+
+use crate::{
+    common::meta::scim::{ScimGroup, ScimListQuery, ScimPatchRequest, ScimUser},
+    service::scim,
+};
+
+/// ScimListUsers
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimListUsers",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ScimListQuery,
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/scim+json", body = ScimListResponse),
+        (status = 400, description = "Failure", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[get("/{org_id}/scim/v2/Users")]
+pub async fn list_users(
+    path: web::Path<String>,
+    query: web::Query<ScimListQuery>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    scim::list_users(&org_id, query.into_inner()).await
+}
+
+/// ScimGetUser
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimGetUser",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "User id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/scim+json", body = ScimUser),
+        (status = 404, description = "NotFound", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[get("/{org_id}/scim/v2/Users/{id}")]
+pub async fn get_user(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    scim::get_user(&org_id, &id).await
+}
+
+/// ScimCreateUser
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimCreateUser",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = ScimUser, description = "User data", content_type = "application/scim+json"),
+    responses(
+        (status = 201, description = "Success", content_type = "application/scim+json", body = ScimUser),
+        (status = 400, description = "Failure", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[post("/{org_id}/scim/v2/Users")]
+pub async fn create_user(
+    path: web::Path<String>,
+    body: web::Json<ScimUser>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    scim::create_user(&org_id, body.into_inner()).await
+}
+
+/// ScimUpdateUser
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimUpdateUser",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "User id"),
+    ),
+    request_body(content = ScimUser, description = "User data", content_type = "application/scim+json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/scim+json", body = ScimUser),
+        (status = 404, description = "NotFound", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[put("/{org_id}/scim/v2/Users/{id}")]
+pub async fn update_user(
+    path: web::Path<(String, String)>,
+    body: web::Json<ScimUser>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    scim::update_user(&org_id, &id, body.into_inner()).await
+}
+
+/// ScimPatchUser
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimPatchUser",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "User id"),
+    ),
+    request_body(content = ScimPatchRequest, description = "Patch operations", content_type = "application/scim+json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/scim+json", body = ScimUser),
+        (status = 400, description = "Failure", content_type = "application/scim+json", body = ScimError),
+        (status = 404, description = "NotFound", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[patch("/{org_id}/scim/v2/Users/{id}")]
+pub async fn patch_user(
+    path: web::Path<(String, String)>,
+    body: web::Json<ScimPatchRequest>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    scim::patch_user(&org_id, &id, body.into_inner()).await
+}
+
+/// ScimDeleteUser
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimDeleteUser",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "User id"),
+    ),
+    responses(
+        (status = 204, description = "Success"),
+        (status = 404, description = "NotFound", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[delete("/{org_id}/scim/v2/Users/{id}")]
+pub async fn delete_user(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    scim::delete_user(&org_id, &id).await
+}
+
+/// ScimListGroups
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimListGroups",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ScimListQuery,
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/scim+json", body = ScimListResponse),
+        (status = 400, description = "Failure", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[get("/{org_id}/scim/v2/Groups")]
+pub async fn list_groups(
+    path: web::Path<String>,
+    query: web::Query<ScimListQuery>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    scim::list_groups(&org_id, query.into_inner()).await
+}
+
+/// ScimGetGroup
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimGetGroup",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Group id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/scim+json", body = ScimGroup),
+        (status = 404, description = "NotFound", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[get("/{org_id}/scim/v2/Groups/{id}")]
+pub async fn get_group(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    scim::get_group(&org_id, &id).await
+}
+
+/// ScimCreateGroup
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimCreateGroup",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = ScimGroup, description = "Group data", content_type = "application/scim+json"),
+    responses(
+        (status = 201, description = "Success", content_type = "application/scim+json", body = ScimGroup),
+        (status = 400, description = "Failure", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[post("/{org_id}/scim/v2/Groups")]
+pub async fn create_group(
+    path: web::Path<String>,
+    body: web::Json<ScimGroup>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    scim::create_group(&org_id, body.into_inner()).await
+}
+
+/// ScimUpdateGroup
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimUpdateGroup",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Group id"),
+    ),
+    request_body(content = ScimGroup, description = "Group data", content_type = "application/scim+json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/scim+json", body = ScimGroup),
+        (status = 404, description = "NotFound", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[put("/{org_id}/scim/v2/Groups/{id}")]
+pub async fn update_group(
+    path: web::Path<(String, String)>,
+    body: web::Json<ScimGroup>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    scim::update_group(&org_id, &id, body.into_inner()).await
+}
+
+/// ScimPatchGroup
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimPatchGroup",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Group id"),
+    ),
+    request_body(content = ScimPatchRequest, description = "Patch operations", content_type = "application/scim+json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/scim+json", body = ScimGroup),
+        (status = 400, description = "Failure", content_type = "application/scim+json", body = ScimError),
+        (status = 404, description = "NotFound", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[patch("/{org_id}/scim/v2/Groups/{id}")]
+pub async fn patch_group(
+    path: web::Path<(String, String)>,
+    body: web::Json<ScimPatchRequest>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    scim::patch_group(&org_id, &id, body.into_inner()).await
+}
+
+/// ScimDeleteGroup
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SCIM",
+    operation_id = "ScimDeleteGroup",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Group id"),
+    ),
+    responses(
+        (status = 204, description = "Success"),
+        (status = 404, description = "NotFound", content_type = "application/scim+json", body = ScimError),
+    )
+)]
+#[delete("/{org_id}/scim/v2/Groups/{id}")]
+pub async fn delete_group(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    scim::delete_group(&org_id, &id).await
+}
@@ -17,6 +17,8 @@ use std::io::Error;
 
 use actix_web::{delete, get, web, HttpResponse};
 
+use crate::common::meta::http::HttpResponse as MetaHttpResponse;
+
 #[cfg(feature = "enterprise")]
 #[delete("/{org_id}/query_manager/{trace_id}")]
 pub async fn cancel_query(params: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
@@ -24,14 +26,14 @@ pub async fn cancel_query(params: web::Path<(String, String)>) -> Result<HttpRes
     let res = crate::service::search::cancel_query(&trace_id).await;
     match res {
         Ok(status) => Ok(HttpResponse::Ok().json(status)),
-        Err(e) => Ok(HttpResponse::InternalServerError().body(format!("{:?}", e))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(format!("{:?}", e))),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[delete("/{org_id}/query_manager/{trace_id}")]
 pub async fn cancel_query(_params: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
 }
 
 #[cfg(feature = "enterprise")]
@@ -40,12 +42,72 @@ pub async fn query_status(_params: web::Path<String>) -> Result<HttpResponse, Er
     let res = crate::service::search::query_status().await;
     match res {
         Ok(query_status) => Ok(HttpResponse::Ok().json(query_status)),
-        Err(e) => Ok(HttpResponse::InternalServerError().body(format!("{:?}", e))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(format!("{:?}", e))),
     }
 }
 
 #[cfg(not(feature = "enterprise"))]
 #[get("/{org_id}/query_manager/status")]
 pub async fn query_status(_params: web::Path<String>) -> Result<HttpResponse, Error> {
-    Ok(HttpResponse::Forbidden().json("Not Supported"))
+    Ok(MetaHttpResponse::forbidden("Not Supported"))
+}
+
+/// ListRunningQueries
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "ListRunningQueries",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RunningQueriesResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/queries")]
+pub async fn running_queries(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match crate::service::search::tracker::running_queries(&org_id).await {
+        Ok(queries) => Ok(HttpResponse::Ok().json(queries)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// KillQuery
+///
+/// Cancels the query on all the nodes running it, its file downloads and
+/// datafusion tasks stop at their next poll.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "KillQuery",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("trace_id" = String, Path, description = "Trace id of the query"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = CancelQueryResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/queries/{trace_id}")]
+pub async fn kill_query(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, trace_id) = path.into_inner();
+    match crate::service::search::tracker::kill_query(&org_id, &trace_id).await {
+        Ok(res) if res.is_success => Ok(HttpResponse::Ok().json(res)),
+        Ok(_) => Ok(MetaHttpResponse::not_found(format!(
+            "query {trace_id} is not running"
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
 }
@@ -29,28 +29,31 @@ use config::{
     DISTINCT_FIELDS,
 };
 use infra::{errors, schema::STREAM_SCHEMAS_LATEST};
-use opentelemetry::{global, trace::TraceContextExt};
+use opentelemetry::trace::TraceContextExt;
 use tracing::{Instrument, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+pub(crate) use crate::service::search::can_read_stream;
 use crate::{
     common::{
         meta::{self, http::HttpResponse as MetaHttpResponse},
         utils::{
             functions,
-            http::{
-                get_search_type_from_request, get_stream_type_from_request, RequestHeaderExtractor,
-            },
+            http::{get_search_type_from_request, get_stream_type_from_request, get_trace_context},
         },
     },
     service::{
+        masking, quota,
         search::{self as SearchService, sql::RE_ONLY_SELECT},
+        stream,
         usage::report_request_usage_stats,
     },
 };
 
 pub mod job;
 pub mod saved_view;
+pub mod streaming;
+pub mod templates;
 
 /// SearchStreamData
 #[utoipa::path(
@@ -125,16 +128,18 @@ pub async fn search(
 ) -> Result<HttpResponse, Error> {
     let start = std::time::Instant::now();
     let org_id = org_id.into_inner();
+    if let Err(e) = quota::check_query_quota(&org_id).await {
+        return Ok(MetaHttpResponse::too_many_requests(e));
+    }
 
     let cfg = get_config();
     let mut http_span = None;
     let trace_id = if cfg.common.tracing_enabled {
-        let ctx = global::get_text_map_propagator(|propagator| {
-            propagator.extract(&RequestHeaderExtractor::new(in_req.headers()))
-        });
+        let ctx = get_trace_context(in_req.headers());
         ctx.span().span_context().trace_id().to_string()
     } else if cfg.common.tracing_search_enabled {
         let span = tracing::info_span!("/api/{org_id}/_search", org_id = org_id.clone());
+        span.set_parent(get_trace_context(in_req.headers()));
         let trace_id = span.context().span().span_context().trace_id().to_string();
         http_span = Some(span);
         trace_id
@@ -178,73 +183,32 @@ pub async fn search(
         }
     };
 
-    // Check permissions on stream
-    #[cfg(feature = "enterprise")]
+    if !can_read_stream(
+        &org_id,
+        user_id.to_str().unwrap(),
+        stream_type,
+        &stream_name,
+    )
+    .await
     {
-        use crate::common::{
-            infra::config::USERS,
-            utils::auth::{is_root_user, AuthExtractor},
-        };
-
-        if !is_root_user(user_id.to_str().unwrap()) {
-            let user: meta::user::User = USERS
-                .get(&format!("{org_id}/{}", user_id.to_str().unwrap()))
-                .unwrap()
-                .clone();
-
-            if user.is_external
-                && !crate::handler::http::auth::validator::check_permissions(
-                    user_id.to_str().unwrap(),
-                    AuthExtractor {
-                        auth: "".to_string(),
-                        method: "GET".to_string(),
-                        o2_type: format!("{}:{}", stream_type, stream_name),
-                        org_id: org_id.clone(),
-                        bypass_check: false,
-                        parent_id: "".to_string(),
-                    },
-                    Some(user.role),
-                )
-                .await
-            {
-                return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
-            }
-        }
-        // Check permissions on stream ends
-    }
-
-    let mut query_fn = req.query.query_fn.and_then(|v| base64::decode_url(&v).ok());
-    if let Some(vrl_function) = &query_fn {
-        if !vrl_function.trim().ends_with('.') {
-            query_fn = Some(format!("{} \n .", vrl_function));
-        }
-    }
-    req.query.query_fn = query_fn;
-
-    for fn_name in functions::get_all_transform_keys(&org_id).await {
-        if req.query.sql.contains(&format!("{}(", fn_name)) {
-            req.query.uses_zo_fn = true;
-            break;
-        }
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
     }
+    prepare_query_fn(&org_id, &mut req).await;
 
     let cfg = get_config();
-    // get a local search queue lock
-    #[cfg(not(feature = "enterprise"))]
-    let locker = SearchService::QUEUE_LOCKER.clone();
+    // wait for a slot in the search queue of the organization
     #[cfg(not(feature = "enterprise"))]
-    let locker = locker.lock().await;
-    #[cfg(not(feature = "enterprise"))]
-    if !cfg.common.feature_query_queue_enabled {
-        drop(locker);
-    }
+    let _ticket = match SearchService::queue::enter(&org_id, user_id.to_str().unwrap()).await {
+        Ok(ticket) => ticket,
+        Err(e) => return Ok(MetaHttpResponse::too_many_requests(e)),
+    };
     #[cfg(not(feature = "enterprise"))]
     let took_wait = start.elapsed().as_millis() as usize;
     #[cfg(feature = "enterprise")]
     let took_wait = 0;
     log::info!("http search API wait in queue took: {} ms", took_wait);
 
-    let search_fut = SearchService::search(
+    let search_fut = SearchService::federation::search(
         &trace_id,
         &org_id,
         stream_type,
@@ -279,6 +243,14 @@ pub async fn search(
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
+            masking::mask_hits(
+                &org_id,
+                user_id.to_str().unwrap(),
+                stream_type,
+                &stream_name,
+                &mut res.hits,
+            );
+            stream::apply_field_settings(&org_id, stream_type, &stream_name, &mut res.hits).await;
             res.set_trace_id(trace_id.clone());
             res.set_local_took(start.elapsed().as_millis() as usize, took_wait);
 
@@ -400,12 +372,13 @@ pub async fn around(
 ) -> Result<HttpResponse, Error> {
     let start = std::time::Instant::now();
     let (org_id, stream_name) = path.into_inner();
+    if let Err(e) = quota::check_query_quota(&org_id).await {
+        return Ok(MetaHttpResponse::too_many_requests(e));
+    }
     let cfg = get_config();
     let mut http_span = None;
     let trace_id = if cfg.common.tracing_enabled {
-        let ctx = global::get_text_map_propagator(|propagator| {
-            propagator.extract(&RequestHeaderExtractor::new(in_req.headers()))
-        });
+        let ctx = get_trace_context(in_req.headers());
         ctx.span().span_context().trace_id().to_string()
     } else if cfg.common.tracing_search_enabled {
         let span = tracing::info_span!(
@@ -413,6 +386,7 @@ pub async fn around(
             org_id = org_id.clone(),
             stream_name = stream_name.clone()
         );
+        span.set_parent(get_trace_context(in_req.headers()));
         let trace_id = span.context().span().span_context().trace_id().to_string();
         http_span = Some(span);
         trace_id
@@ -474,15 +448,21 @@ pub async fn around(
             .collect::<Vec<_>>()
     });
 
-    // get a local search queue lock
-    #[cfg(not(feature = "enterprise"))]
-    let locker = SearchService::QUEUE_LOCKER.clone();
-    #[cfg(not(feature = "enterprise"))]
-    let locker = locker.lock().await;
+    // wait for a slot in the search queue of the organization
     #[cfg(not(feature = "enterprise"))]
-    if !cfg.common.feature_query_queue_enabled {
-        drop(locker);
-    }
+    let _ticket = match SearchService::queue::enter(
+        &org_id,
+        in_req
+            .headers()
+            .get("user_id")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default(),
+    )
+    .await
+    {
+        Ok(ticket) => ticket,
+        Err(e) => return Ok(MetaHttpResponse::too_many_requests(e)),
+    };
     #[cfg(not(feature = "enterprise"))]
     let took_wait = start.elapsed().as_millis() as usize;
     #[cfg(feature = "enterprise")]
@@ -525,6 +505,7 @@ pub async fn around(
             uses_zo_fn: uses_fn,
             query_fn: query_fn.clone(),
             skip_wal: false,
+            highlight: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
@@ -605,6 +586,7 @@ pub async fn around(
             uses_zo_fn: uses_fn,
             query_fn: query_fn.clone(),
             skip_wal: false,
+            highlight: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
@@ -702,6 +684,8 @@ pub async fn around(
         Some(v) => v.to_str().unwrap(),
         None => "",
     };
+    masking::mask_hits(&org_id, user_id, stream_type, &stream_name, &mut resp.hits);
+    stream::apply_field_settings(&org_id, stream_type, &stream_name, &mut resp.hits).await;
     let req_stats = RequestStats {
         records: resp.hits.len() as i64,
         response_time: time,
@@ -768,6 +752,9 @@ pub async fn values(
     in_req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, stream_name) = path.into_inner();
+    if let Err(e) = quota::check_query_quota(&org_id).await {
+        return Ok(MetaHttpResponse::too_many_requests(e));
+    }
     let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
     let stream_type = match get_stream_type_from_request(&query) {
         Ok(v) => v.unwrap_or(StreamType::Logs),
@@ -791,9 +778,7 @@ pub async fn values(
     let cfg = get_config();
     let mut http_span = None;
     let trace_id = if cfg.common.tracing_enabled {
-        let ctx = global::get_text_map_propagator(|propagator| {
-            propagator.extract(&RequestHeaderExtractor::new(in_req.headers()))
-        });
+        let ctx = get_trace_context(in_req.headers());
         ctx.span().span_context().trace_id().to_string()
     } else if cfg.common.tracing_search_enabled {
         let span = tracing::info_span!(
@@ -801,6 +786,7 @@ pub async fn values(
             org_id = org_id.clone(),
             stream_name = stream_name.clone()
         );
+        span.set_parent(get_trace_context(in_req.headers()));
         let trace_id = span.context().span().span_context().trace_id().to_string();
         http_span = Some(span);
         trace_id
@@ -980,15 +966,12 @@ async fn values_v1(
         .get("timeout")
         .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
 
-    // get a local search queue lock
-    #[cfg(not(feature = "enterprise"))]
-    let locker = SearchService::QUEUE_LOCKER.clone();
-    #[cfg(not(feature = "enterprise"))]
-    let locker = locker.lock().await;
+    // wait for a slot in the search queue of the organization
     #[cfg(not(feature = "enterprise"))]
-    if !cfg.common.feature_query_queue_enabled {
-        drop(locker);
-    }
+    let _ticket = match SearchService::queue::enter(org_id, user_id).await {
+        Ok(ticket) => ticket,
+        Err(e) => return Ok(MetaHttpResponse::too_many_requests(e)),
+    };
     #[cfg(not(feature = "enterprise"))]
     let took_wait = start.elapsed().as_millis() as usize;
     #[cfg(feature = "enterprise")]
@@ -1015,6 +998,7 @@ async fn values_v1(
             uses_zo_fn: uses_fn,
             query_fn: query_fn.clone(),
             skip_wal: false,
+            highlight: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
@@ -1110,6 +1094,7 @@ async fn values_v1(
     }
     resp.total = fields.len();
     resp.hits = hit_values;
+    masking::mask_values(org_id, user_id, stream_type, stream_name, &mut resp.hits);
     resp.size = size;
     resp.scan_size = resp_search.scan_size;
     resp.took = start.elapsed().as_millis() as usize;
@@ -1231,15 +1216,12 @@ async fn values_v2(
         .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
 
     let cfg = get_config();
-    // get a local search queue lock
-    #[cfg(not(feature = "enterprise"))]
-    let locker = SearchService::QUEUE_LOCKER.clone();
+    // wait for a slot in the search queue of the organization
     #[cfg(not(feature = "enterprise"))]
-    let locker = locker.lock().await;
-    #[cfg(not(feature = "enterprise"))]
-    if !cfg.common.feature_query_queue_enabled {
-        drop(locker);
-    }
+    let _ticket = match SearchService::queue::enter(org_id, user_id).await {
+        Ok(ticket) => ticket,
+        Err(e) => return Ok(MetaHttpResponse::too_many_requests(e)),
+    };
     #[cfg(not(feature = "enterprise"))]
     let took_wait = start.elapsed().as_millis() as usize;
     #[cfg(feature = "enterprise")]
@@ -1266,6 +1248,7 @@ async fn values_v2(
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            highlight: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
@@ -1337,6 +1320,7 @@ async fn values_v2(
 
     resp.total = 1;
     resp.hits = hit_values;
+    masking::mask_values(org_id, user_id, stream_type, stream_name, &mut resp.hits);
     resp.size = size;
     resp.scan_size = resp_search.scan_size;
     resp.took = start.elapsed().as_millis() as usize;
@@ -1430,12 +1414,11 @@ pub async fn search_partition(
     let cfg = get_config();
     let mut http_span = None;
     let trace_id = if cfg.common.tracing_enabled {
-        let ctx = global::get_text_map_propagator(|propagator| {
-            propagator.extract(&RequestHeaderExtractor::new(in_req.headers()))
-        });
+        let ctx = get_trace_context(in_req.headers());
         ctx.span().span_context().trace_id().to_string()
     } else if cfg.common.tracing_search_enabled {
         let span = tracing::info_span!("/api/{org_id}/_search_partition", org_id = org_id.clone());
+        span.set_parent(get_trace_context(in_req.headers()));
         let trace_id = span.context().span().span_context().trace_id().to_string();
         http_span = Some(span);
         trace_id
@@ -1522,3 +1505,133 @@ pub async fn search_partition(
         }
     }
 }
+
+/// SearchExplain
+///
+/// Plans the query without running it: returns the logical and physical plan,
+/// the partitions and files left to scan after pruning and their sizes.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SearchExplain",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = SearchPartitionRequest, description = "Search query", content_type = "application/json", example = json!({
+        "sql": "select * from k8s where kubernetes_host = 'node-1'",
+        "start_time": 1675182660872049i64,
+        "end_time": 1675185660872049i64
+    })),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchExplainResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_search_explain")]
+pub async fn search_explain(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let start = std::time::Instant::now();
+    let cfg = get_config();
+    let mut http_span = None;
+    let trace_id = if cfg.common.tracing_enabled {
+        let ctx = get_trace_context(in_req.headers());
+        ctx.span().span_context().trace_id().to_string()
+    } else if cfg.common.tracing_search_enabled {
+        let span = tracing::info_span!("/api/{org_id}/_search_explain", org_id = org_id.clone());
+        span.set_parent(get_trace_context(in_req.headers()));
+        let trace_id = span.context().span().span_context().trace_id().to_string();
+        http_span = Some(span);
+        trace_id
+    } else {
+        ider::uuid()
+    };
+
+    let org_id = org_id.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+
+    let mut req: config::meta::search::SearchPartitionRequest = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    if let Err(e) = req.decode() {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+
+    let explain_fut = SearchService::search_explain(&trace_id, &org_id, stream_type, &req);
+    let explain_res = if !cfg.common.tracing_enabled && cfg.common.tracing_search_enabled {
+        explain_fut.instrument(http_span.unwrap()).await
+    } else {
+        explain_fut.await
+    };
+
+    let status = if explain_res.is_ok() { "200" } else { "500" };
+    let time = start.elapsed().as_secs_f64();
+    metrics::HTTP_RESPONSE_TIME
+        .with_label_values(&[
+            "/api/org/_search_explain",
+            status,
+            &org_id,
+            "",
+            stream_type.to_string().as_str(),
+        ])
+        .observe(time);
+    metrics::HTTP_INCOMING_REQUESTS
+        .with_label_values(&[
+            "/api/org/_search_explain",
+            status,
+            &org_id,
+            "",
+            stream_type.to_string().as_str(),
+        ])
+        .inc();
+
+    match explain_res {
+        Ok(res) => Ok(HttpResponse::Ok().json(res)),
+        Err(err) => {
+            log::error!("search explain error: {:?}", err);
+            Ok(match err {
+                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError().json(
+                    meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
+                ),
+                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
+                    StatusCode::INTERNAL_SERVER_ERROR.into(),
+                    err.to_string(),
+                )),
+            })
+        }
+    }
+}
+
+/// Decodes the vrl function of the query, and flags the queries calling the
+/// functions of the org.
+pub(crate) async fn prepare_query_fn(org_id: &str, req: &mut config::meta::search::Request) {
+    let mut query_fn = req
+        .query
+        .query_fn
+        .take()
+        .and_then(|v| base64::decode_url(&v).ok());
+    if let Some(vrl_function) = &query_fn {
+        if !vrl_function.trim().ends_with('.') {
+            query_fn = Some(format!("{} \n .", vrl_function));
+        }
+    }
+    req.query.query_fn = query_fn;
+
+    for fn_name in functions::get_all_transform_keys(org_id).await {
+        if req.query.sql.contains(&format!("{}(", fn_name)) {
+            req.query.uses_zo_fn = true;
+            break;
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use actix_web::{get, rt, web, HttpRequest, HttpResponse};
+use actix_ws::{Message, Session};
+use config::{
+    ider,
+    meta::{
+        search::{self, SearchEventType, StreamingEvent},
+        stream::StreamType,
+    },
+    metrics,
+    utils::json,
+};
+use futures::StreamExt;
+use infra::errors;
+use tokio::sync::mpsc;
+
+use super::{can_read_stream, prepare_query_fn};
+use crate::{
+    common::{
+        meta::http::HttpResponse as MetaHttpResponse,
+        utils::http::{get_search_type_from_request, get_stream_type_from_request},
+    },
+    service::{quota, search as SearchService},
+};
+
+/// Events buffered between the search and the websocket.
+const EVENT_CHANNEL_SIZE: usize = 8;
+
+/// SearchStream
+///
+/// Upgrades the connection to a websocket. Each text message is a search
+/// request, answered with `hits` and `progress` messages as the partitions of
+/// the time range are scanned, newest first, then an `end` or `error`
+/// message. A new request cancels the one running.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SearchStream",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("type" = Option<String>, Query, description = "Stream type, logs by default"),
+        ("search_type" = Option<String>, Query, description = "Where the search comes from, e.g. ui"),
+    ),
+    responses(
+        (status = 101, description = "Switching protocols, the messages are StreamingEvent", body = StreamingEvent),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/_search_stream")]
+pub async fn search_stream(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let org_id = org_id.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let search_type = match get_search_type_from_request(&query) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let (res, mut session, mut messages) = actix_ws::handle(&in_req, body)?;
+    rt::spawn(async move {
+        let mut running: Option<rt::task::JoinHandle<()>> = None;
+        while let Some(Ok(msg)) = messages.next().await {
+            match msg {
+                Message::Text(text) => {
+                    if let Some(task) = running.take() {
+                        task.abort();
+                    }
+                    running = Some(rt::spawn(run_search(
+                        session.clone(),
+                        org_id.clone(),
+                        stream_type,
+                        search_type,
+                        user_id.clone(),
+                        text.to_string(),
+                    )));
+                }
+                Message::Ping(bytes) => {
+                    if session.pong(&bytes).await.is_err() {
+                        break;
+                    }
+                }
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        if let Some(task) = running {
+            task.abort();
+        }
+        let _ = session.close(None).await;
+    });
+    Ok(res)
+}
+
+async fn run_search(
+    mut session: Session,
+    org_id: String,
+    stream_type: StreamType,
+    search_type: Option<SearchEventType>,
+    user_id: String,
+    body: String,
+) {
+    let start = std::time::Instant::now();
+    let trace_id = ider::uuid();
+    let mut req: search::Request = match json::from_str(&body) {
+        Ok(v) => v,
+        Err(e) => return send_error(&mut session, &trace_id, 400, e.to_string()).await,
+    };
+    if let Err(e) = req.decode() {
+        return send_error(&mut session, &trace_id, 400, e.to_string()).await;
+    }
+    if let Err(e) = quota::check_query_quota(&org_id).await {
+        return send_error(&mut session, &trace_id, 429, e.to_string()).await;
+    }
+    let stream_name = match config::meta::sql::Sql::new(&req.query.sql) {
+        Ok(v) => v.source.to_string(),
+        Err(e) => return send_error(&mut session, &trace_id, 400, e.to_string()).await,
+    };
+    if !can_read_stream(&org_id, &user_id, stream_type, &stream_name).await {
+        let msg = "Unauthorized Access".to_string();
+        return send_error(&mut session, &trace_id, 403, msg).await;
+    }
+    prepare_query_fn(&org_id, &mut req).await;
+    if search_type.is_some() {
+        req.search_type = search_type;
+    }
+
+    let (tx, mut rx) = mpsc::channel(EVENT_CHANNEL_SIZE);
+    let search =
+        SearchService::streaming::search(&trace_id, &org_id, stream_type, &user_id, req, tx);
+    // the search stops when the websocket is gone and the receiver dropped
+    let mut events_session = session.clone();
+    let forward = async move {
+        while let Some(event) = rx.recv().await {
+            if events_session
+                .text(json::to_string(&event).unwrap())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    };
+    let (search_res, _) = tokio::join!(search, forward);
+
+    let status = match search_res {
+        Ok(_) => "200",
+        Err(err) => {
+            log::error!("[trace_id {trace_id}] search stream error: {:?}", err);
+            let (code, message) = match err {
+                errors::Error::ErrorCode(code) => (code.get_code(), code.get_message()),
+                err => (500, err.to_string()),
+            };
+            send_error(&mut session, &trace_id, code, message).await;
+            "500"
+        }
+    };
+    let time = start.elapsed().as_secs_f64();
+    metrics::HTTP_RESPONSE_TIME
+        .with_label_values(&[
+            "/api/org/_search_stream",
+            status,
+            &org_id,
+            "",
+            stream_type.to_string().as_str(),
+        ])
+        .observe(time);
+    metrics::HTTP_INCOMING_REQUESTS
+        .with_label_values(&[
+            "/api/org/_search_stream",
+            status,
+            &org_id,
+            "",
+            stream_type.to_string().as_str(),
+        ])
+        .inc();
+}
+
+async fn send_error(session: &mut Session, trace_id: &str, code: u16, message: String) {
+    let event = StreamingEvent::Error {
+        trace_id: trace_id.to_string(),
+        code,
+        message,
+    };
+    let _ = session.text(json::to_string(&event).unwrap()).await;
+}
@@ -0,0 +1,255 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use config::{ider, meta::sql::Sql};
+use infra::errors;
+
+use super::{can_read_stream, prepare_query_fn};
+use crate::{
+    common::meta::{
+        http::HttpResponse as MetaHttpResponse,
+        search_templates::{SearchTemplate, SearchTemplateRunRequest},
+    },
+    service::{
+        db, masking, quota, search as SearchService, search_templates,
+        search_templates::SEARCH_TEMPLATE_NOT_FOUND, stream,
+    },
+};
+
+/// ListSearchTemplates
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "ListSearchTemplates",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchTemplateList),
+    )
+)]
+#[get("/{org_id}/search_templates")]
+pub async fn list(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    search_templates::list(&org_id).await
+}
+
+/// GetSearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "GetSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Search template name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchTemplate),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/search_templates/{name}")]
+pub async fn get(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    search_templates::get(&org_id, &name).await
+}
+
+/// CreateSearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "CreateSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = SearchTemplate, description = "Search template data", content_type = "application/json", example = json!({
+        "name": "errors_by_host",
+        "sql": "SELECT * FROM k8s WHERE host = :host AND code >= :code",
+        "params": [
+            {"name": "host", "type": "string"},
+            {"name": "code", "type": "int", "default": 500}
+        ]
+    })),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchTemplate),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/search_templates")]
+pub async fn create(
+    path: web::Path<String>,
+    template: web::Json<SearchTemplate>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let template = template.into_inner();
+    let name = template.name.clone();
+    search_templates::save(&org_id, &name, template, true).await
+}
+
+/// UpdateSearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "UpdateSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Search template name"),
+    ),
+    request_body(content = SearchTemplate, description = "Search template data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchTemplate),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/search_templates/{name}")]
+pub async fn update(
+    path: web::Path<(String, String)>,
+    template: web::Json<SearchTemplate>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    search_templates::save(&org_id, &name, template.into_inner(), false).await
+}
+
+/// DeleteSearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "DeleteSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Search template name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/search_templates/{name}")]
+pub async fn delete(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    search_templates::delete(&org_id, &name).await
+}
+
+/// RunSearchTemplate
+///
+/// Binds the parameters into the query of the template and runs it, the
+/// values are bound as SQL literals of the types of the parameters.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "RunSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Search template name"),
+    ),
+    request_body(content = SearchTemplateRunRequest, description = "Parameters and time range", content_type = "application/json", example = json!({
+        "params": {"host": "web-1"},
+        "start_time": 1675182660872049i64,
+        "end_time": 1675185660872049i64,
+        "size": 100
+    })),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/search_templates/{name}/_run")]
+pub async fn run(
+    path: web::Path<(String, String)>,
+    in_req: HttpRequest,
+    run: web::Json<SearchTemplateRunRequest>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    if let Err(e) = quota::check_query_quota(&org_id).await {
+        return Ok(MetaHttpResponse::too_many_requests(e));
+    }
+    let Ok(template) = db::search_templates::get(&org_id, &name).await else {
+        return Ok(MetaHttpResponse::not_found(SEARCH_TEMPLATE_NOT_FOUND));
+    };
+    let mut req = match search_templates::bind_request(&template, &run) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let stream_type = template.stream_type;
+    let stream_name = match Sql::new(&req.query.sql) {
+        Ok(v) => v.source.to_string(),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if !can_read_stream(&org_id, &user_id, stream_type, &stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+    prepare_query_fn(&org_id, &mut req).await;
+
+    let trace_id = ider::uuid();
+    let search_res = SearchService::federation::search(
+        &trace_id,
+        &org_id,
+        stream_type,
+        Some(user_id.clone()),
+        &req,
+    )
+    .await;
+    match search_res {
+        Ok(mut res) => {
+            masking::mask_hits(&org_id, &user_id, stream_type, &stream_name, &mut res.hits);
+            stream::apply_field_settings(&org_id, stream_type, &stream_name, &mut res.hits).await;
+            res.set_trace_id(trace_id);
+            Ok(HttpResponse::Ok().json(res))
+        }
+        Err(err) => {
+            log::error!(
+                "[trace_id {trace_id}] search template {name} error: {:?}",
+                err
+            );
+            Ok(match err {
+                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError().json(
+                    MetaHttpResponse::error_code_with_trace_id(code, Some(trace_id)),
+                ),
+                err => MetaHttpResponse::internal_error(err),
+            })
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use crate::{
+    common::meta::service_account::{ApiTokenRequest, RotateTokenRequest, ServiceAccountRequest},
+    service::service_accounts,
+};
+
+/// ListServiceAccounts
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Service Accounts",
+    operation_id = "ListServiceAccounts",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<ServiceAccount>),
+    )
+)]
+#[get("/{org_id}/service_accounts")]
+pub async fn list(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    service_accounts::list_service_accounts(&org_id).await
+}
+
+/// CreateServiceAccount
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Service Accounts",
+    operation_id = "CreateServiceAccount",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = ServiceAccountRequest, description = "Service account data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ServiceAccount),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/service_accounts")]
+pub async fn save(
+    path: web::Path<String>,
+    req: web::Json<ServiceAccountRequest>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    service_accounts::create_service_account(&org_id, req.into_inner()).await
+}
+
+/// DeleteServiceAccount
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Service Accounts",
+    operation_id = "DeleteServiceAccount",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Service account name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/service_accounts/{name}")]
+pub async fn delete(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    service_accounts::delete_service_account(&org_id, &name).await
+}
+
+/// CreateApiToken
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Service Accounts",
+    operation_id = "CreateApiToken",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Service account name"),
+    ),
+    request_body(content = ApiTokenRequest, description = "Token restrictions", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ApiTokenResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/service_accounts/{name}/tokens")]
+pub async fn create_token(
+    path: web::Path<(String, String)>,
+    req: web::Json<ApiTokenRequest>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    service_accounts::create_token(&org_id, &name, req.into_inner()).await
+}
+
+/// RotateApiToken
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Service Accounts",
+    operation_id = "RotateApiToken",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Service account name"),
+        ("token_id" = String, Path, description = "Token id"),
+    ),
+    request_body(content = RotateTokenRequest, description = "Rotation options", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ApiTokenResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/service_accounts/{name}/tokens/{token_id}/rotate")]
+pub async fn rotate_token(
+    path: web::Path<(String, String, String)>,
+    req: Option<web::Json<RotateTokenRequest>>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name, token_id) = path.into_inner();
+    let req = req.map(|v| v.into_inner()).unwrap_or_default();
+    service_accounts::rotate_token(&org_id, &name, &token_id, req).await
+}
+
+/// DeleteApiToken
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Service Accounts",
+    operation_id = "DeleteApiToken",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Service account name"),
+        ("token_id" = String, Path, description = "Token id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/service_accounts/{name}/tokens/{token_id}")]
+pub async fn delete_token(
+    path: web::Path<(String, String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name, token_id) = path.into_inner();
+    service_accounts::delete_token(&org_id, &name, &token_id).await
+}
@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{io::Error, sync::Arc};
+use std::{collections::BTreeMap, io::Error, sync::Arc};
 
 use actix_web::{
     cookie,
@@ -67,7 +67,7 @@ use crate::{
         },
     },
     service::{
-        db,
+        db, health,
         search::datafusion::{storage::file_statistics_cache, DEFAULT_FUNCTIONS},
     },
 };
@@ -77,6 +77,20 @@ pub struct HealthzResponse {
     status: String,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct ReadyzResponse {
+    status: String,
+    /// status of each dependency, by name
+    checks: BTreeMap<String, DependencyStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DependencyStatus {
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
 #[derive(Serialize)]
 struct ConfigResponse<'a> {
     version: String,
@@ -126,6 +140,8 @@ struct Rum {
 }
 
 /// Healthz
+///
+/// Liveness of the node, it answers as soon as the http server runs.
 #[utoipa::path(
     path = "/healthz",
     tag = "Meta",
@@ -140,6 +156,41 @@ pub async fn healthz() -> Result<HttpResponse, Error> {
     }))
 }
 
+/// Readyz
+///
+/// Readiness of the node: the meta store and the object store answer, the disk
+/// cache is loaded and the node is online in the cluster.
+#[utoipa::path(
+    path = "/readyz",
+    tag = "Meta",
+    responses(
+        (status = 200, description="Staus OK", content_type = "application/json", body = ReadyzResponse, example = json!({"status": "ok", "checks": {"meta_store": {"status": "ok"}, "object_store": {"status": "ok"}, "disk_cache": {"status": "ok"}, "cluster": {"status": "ok"}}})),
+        (status = 503, description="Staus Not OK", content_type = "application/json", body = ReadyzResponse, example = json!({"status": "not ok", "checks": {"meta_store": {"status": "ok"}, "object_store": {"status": "ok"}, "disk_cache": {"status": "not ok", "error": "loading the files of the cache dir"}, "cluster": {"status": "ok"}}})),
+    )
+)]
+#[get("/readyz")]
+pub async fn readyz() -> Result<HttpResponse, Error> {
+    let mut ready = true;
+    let mut checks = BTreeMap::new();
+    for (name, res) in health::readiness().await {
+        ready &= res.is_ok();
+        let status = DependencyStatus {
+            status: if res.is_ok() { "ok" } else { "not ok" }.to_string(),
+            error: res.err(),
+        };
+        checks.insert(name.to_string(), status);
+    }
+    let resp = ReadyzResponse {
+        status: if ready { "ok" } else { "not ok" }.to_string(),
+        checks,
+    };
+    Ok(if ready {
+        HttpResponse::Ok().json(resp)
+    } else {
+        HttpResponse::ServiceUnavailable().json(resp)
+    })
+}
+
 /// Healthz of the node for scheduled status
 #[utoipa::path(
     path = "/schedulez",
@@ -168,16 +219,34 @@ pub async fn schedulez() -> Result<HttpResponse, Error> {
     })
 }
 
+/// OpenMetrics
+///
+/// The metrics of the node in the OpenMetrics format, the latency histograms
+/// carry the traces of the requests as exemplars.
+#[utoipa::path(
+    path = "/openmetrics",
+    tag = "Meta",
+    responses(
+        (status = 200, description="Success", content_type = "application/openmetrics-text", body = String),
+    )
+)]
+#[get("/openmetrics")]
+pub async fn openmetrics() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok()
+        .content_type("application/openmetrics-text; version=1.0.0; charset=utf-8")
+        .body(config::metrics::openmetrics()))
+}
+
 #[get("")]
 pub async fn zo_config() -> Result<HttpResponse, Error> {
     #[cfg(feature = "enterprise")]
     let sso_enabled = O2_CONFIG.dex.dex_enabled;
     #[cfg(not(feature = "enterprise"))]
-    let sso_enabled = false;
+    let sso_enabled = get_config().oidc.enabled;
     #[cfg(feature = "enterprise")]
     let native_login_enabled = O2_CONFIG.dex.native_login_enabled;
     #[cfg(not(feature = "enterprise"))]
-    let native_login_enabled = true;
+    let native_login_enabled = !sso_enabled || get_config().oidc.native_login_enabled;
 
     #[cfg(feature = "enterprise")]
     let rbac_enabled = O2_CONFIG.openfga.enabled;
@@ -404,7 +473,8 @@ pub async fn redirect(req: HttpRequest) -> Result<HttpResponse, Error> {
             let access_token = login_data.access_token;
             let keys = get_jwks().await;
             let token_ver =
-                verify_decode_token(&access_token, &keys, &O2_CONFIG.dex.client_id, true).await;
+                verify_decode_token(&access_token, &keys, &O2_CONFIG.dex.client_id, None, true)
+                    .await;
             let id_token;
             match token_ver {
                 Ok(res) => {
@@ -553,6 +623,121 @@ async fn refresh_token_with_dex(req: actix_web::HttpRequest) -> HttpResponse {
     }
 }
 
+#[cfg(not(feature = "enterprise"))]
+#[get("/redirect")]
+pub async fn redirect(req: HttpRequest) -> Result<HttpResponse, Error> {
+    use config::{ider, utils::base64};
+
+    use crate::{handler::http::auth::validator::ID_TOKEN_HEADER, service::oidc};
+
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let (Some(code), Some(state)) = (query.get("code"), query.get("state")) else {
+        return Ok(MetaHttpResponse::bad_request("no code or state in request"));
+    };
+
+    let login_data = match oidc::exchange_code(code, state).await {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::unauthorized(e)),
+    };
+    let token = login_data
+        .id_token
+        .unwrap_or_else(|| login_data.access_token.clone());
+    let res = match oidc::verify_token(&token).await {
+        Ok(res) => res,
+        Err(e) => return Ok(MetaHttpResponse::unauthorized(e)),
+    };
+    if let Err(e) = oidc::process_token(&res).await {
+        log::error!("Error processing oidc token: {}", e);
+        return Ok(MetaHttpResponse::unauthorized(e));
+    }
+    let id_token = json::to_string(&json::json!({
+        "email": res.0.user_email,
+        "name": res.0.user_name,
+        "family_name": res.0.family_name,
+        "given_name": res.0.given_name,
+        "is_valid": res.0.is_valid,
+    }))
+    .unwrap();
+    let cfg = get_config();
+    let login_url = format!(
+        "{}{}/web/cb#id_token={}.{}",
+        cfg.common.web_url,
+        cfg.common.base_uri,
+        ID_TOKEN_HEADER,
+        base64::encode(&id_token)
+    );
+
+    // keep the token in the cluster coordinator, the cookie only carries the session id
+    let session_id = ider::uuid();
+    let _ = crate::service::session::set_session(&session_id, &token).await;
+    let tokens = AuthTokens {
+        access_token: format!("session {}", session_id),
+        refresh_token: login_data.refresh_token.unwrap_or_default(),
+    };
+    log::info!("Redirecting user after processing oidc token");
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, login_url))
+        .cookie(prepare_empty_cookie("auth_tokens", &tokens, &cfg))
+        .finish())
+}
+
+#[cfg(not(feature = "enterprise"))]
+#[get("/dex_login")]
+pub async fn dex_login() -> Result<HttpResponse, Error> {
+    if !get_config().oidc.enabled {
+        return Ok(MetaHttpResponse::forbidden("Not Supported"));
+    }
+    match crate::service::oidc::get_login_url().await {
+        Ok(url) => Ok(HttpResponse::Ok().json(url)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+#[cfg(not(feature = "enterprise"))]
+#[get("/dex_refresh")]
+async fn refresh_token_with_dex(req: actix_web::HttpRequest) -> HttpResponse {
+    use config::ider;
+
+    let conf = get_config();
+    let refresh_token = match req.cookie("auth_tokens") {
+        Some(cookie) => {
+            let auth_tokens: AuthTokens = json::from_str(cookie.value()).unwrap_or_default();
+            if let Some(session_id) = auth_tokens.access_token.strip_prefix("session ") {
+                crate::service::session::remove_session(session_id).await;
+            }
+            auth_tokens.refresh_token
+        }
+        None => return HttpResponse::Unauthorized().finish(),
+    };
+
+    match crate::service::oidc::refresh_token(&refresh_token).await {
+        Ok(login_data) => {
+            let session_id = ider::uuid();
+            let token = login_data.id_token.unwrap_or(login_data.access_token);
+            let _ = crate::service::session::set_session(&session_id, &token).await;
+            let tokens = AuthTokens {
+                access_token: format!("session {}", session_id),
+                // some providers do not rotate the refresh token
+                refresh_token: login_data.refresh_token.unwrap_or(refresh_token),
+            };
+            HttpResponse::Ok()
+                .cookie(prepare_empty_cookie("auth_tokens", &tokens, &conf))
+                .finish()
+        }
+        Err(e) => {
+            log::error!("Error refreshing oidc token: {}", e);
+            HttpResponse::Unauthorized()
+                .append_header((header::LOCATION, "/"))
+                .cookie(prepare_empty_cookie(
+                    "auth_tokens",
+                    &AuthTokens::default(),
+                    &conf,
+                ))
+                .finish()
+        }
+    }
+}
+
 fn prepare_empty_cookie<'a, T: Serialize + ?Sized>(
     cookie_name: &'a str,
     token_struct: &T,
@@ -598,6 +783,23 @@ async fn logout(req: actix_web::HttpRequest) -> HttpResponse {
         .finish()
 }
 
+/// Enables or disables the scheduling of the local node
+#[utoipa::path(
+    context_path = "/node",
+    tag = "Meta",
+    operation_id = "NodeEnable",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("value" = bool, Query, description = "Schedule the node or not"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = bool, example = json!(true)),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[put("/enable")]
 async fn enable_node(req: HttpRequest) -> Result<HttpResponse, Error> {
     let node_id = LOCAL_NODE_UUID.clone();
@@ -617,6 +819,20 @@ async fn enable_node(req: HttpRequest) -> Result<HttpResponse, Error> {
     }
 }
 
+/// Flushes the memtables of the local ingester to disk
+#[utoipa::path(
+    context_path = "/node",
+    tag = "Meta",
+    operation_id = "NodeFlush",
+    security(
+        ("Authorization"= [])
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = bool, example = json!(true)),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[put("/flush")]
 async fn flush_node() -> Result<HttpResponse, Error> {
     if !is_ingester(&LOCAL_NODE_ROLE) {
@@ -629,6 +845,52 @@ async fn flush_node() -> Result<HttpResponse, Error> {
     }
 }
 
+/// Flushes the local ingester and takes it offline
+#[utoipa::path(
+    context_path = "/node",
+    tag = "Meta",
+    operation_id = "NodeDrain",
+    security(
+        ("Authorization"= [])
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = bool, example = json!(true)),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/drain")]
+async fn drain_node() -> Result<HttpResponse, Error> {
+    if !is_ingester(&LOCAL_NODE_ROLE) {
+        return Ok(MetaHttpResponse::not_found("local node is not an ingester"));
+    };
+
+    if let Err(e) = crate::service::node::drain().await {
+        return Ok(MetaHttpResponse::internal_error(e));
+    }
+    match cluster::set_offline(true).await {
+        Ok(_) => Ok(MetaHttpResponse::json(true)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Fields of a stream the local node has seen
+#[utoipa::path(
+    context_path = "/node",
+    tag = "Meta",
+    operation_id = "NodeStreamFields",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_type" = String, Path, description = "Stream type"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Object, example = json!({"updated_at": 1712132400000000_i64, "fields": ["level", "message"]})),
+    )
+)]
 #[get("/stream_fields/{org_id}/{stream_type}/{stream_name}")]
 async fn stream_fields(path: web::Path<(String, String, String)>) -> Result<HttpResponse, Error> {
     let (org_id, stream_type, stream_name) = path.into_inner();
@@ -18,21 +18,32 @@ use std::{
     io::{Error, ErrorKind},
 };
 
-use actix_web::{delete, get, http, put, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse, Responder};
 use config::meta::stream::{StreamSettings, StreamType};
 
 use crate::{
     common::{
         meta::{
             self,
+            dlp::StreamDlp,
             http::HttpResponse as MetaHttpResponse,
-            stream::{ListStream, StreamDeleteFields},
+            stream::{
+                IndexAdvice, ListStream, ListStreamArchive, PendingSchemaChange, StreamArchiveInfo,
+                StreamDeleteFields, StreamFieldAliases, StreamHiddenFields, StreamRestoreRequest,
+                StreamTemplate, StreamTemplateResponse,
+            },
         },
-        utils::http::get_stream_type_from_request,
+        utils::{auth::is_root_user, http::get_stream_type_from_request},
+    },
+    service::{
+        dlp, format_stream_name, index_advisor, schema_changes, stream, stream_archive,
+        stream_template,
     },
-    service::{format_stream_name, stream},
 };
 
+// the days of searches the index advice reads by default
+const INDEX_ADVICE_DAYS: i64 = 7;
+
 /// GetSchema
 #[utoipa::path(
     context_path = "/api",
@@ -185,6 +196,375 @@ async fn delete_fields(
     }
 }
 
+/// SetStreamHiddenFields
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamSetHiddenFields",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = StreamHiddenFields, description = "Fields left out of the schema and the search results", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/streams/{stream_name}/hidden_fields")]
+async fn set_hidden_fields(
+    path: web::Path<(String, String)>,
+    fields: web::Json<StreamHiddenFields>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    stream::set_hidden_fields(
+        &org_id,
+        &stream_name,
+        stream_type,
+        fields.into_inner().fields,
+    )
+    .await
+}
+
+/// SetStreamFieldAliases
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamSetFieldAliases",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = StreamFieldAliases, description = "Names the fields are searched and shown by", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/streams/{stream_name}/field_aliases")]
+async fn set_field_aliases(
+    path: web::Path<(String, String)>,
+    aliases: web::Json<StreamFieldAliases>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    stream::set_field_aliases(
+        &org_id,
+        &stream_name,
+        stream_type,
+        aliases.into_inner().aliases,
+    )
+    .await
+}
+
+/// ListStreamSchemaChanges
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamListSchemaChanges",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<PendingSchemaChange>),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/schema_changes")]
+async fn list_schema_changes(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    schema_changes::list(&org_id, stream_type, &stream_name).await
+}
+
+/// ApproveStreamSchemaChange
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamApproveSchemaChange",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("field" = String, Path, description = "Field name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/schema_changes/{field}/_approve")]
+async fn approve_schema_change(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, field) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    schema_changes::approve(&org_id, stream_type, &stream_name, &field).await
+}
+
+/// RejectStreamSchemaChange
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamRejectSchemaChange",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("field" = String, Path, description = "Field name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/streams/{stream_name}/schema_changes/{field}")]
+async fn reject_schema_change(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, field) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    schema_changes::reject(&org_id, stream_type, &stream_name, &field).await
+}
+
+/// GetStreamIndexAdvice
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamIndexAdvice",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("days" = Option<i64>, Query, description = "Days of searches to analyze, default 7"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = IndexAdvice),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/index_advice")]
+async fn get_index_advice(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    let days = query
+        .get("days")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(INDEX_ADVICE_DAYS);
+    index_advisor::get(&org_id, stream_type, &stream_name, days).await
+}
+
+/// ApplyStreamIndexAdvice
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamApplyIndexAdvice",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("days" = Option<i64>, Query, description = "Days of searches to analyze, default 7"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/index_advice/_apply")]
+async fn apply_index_advice(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    let days = query
+        .get("days")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(INDEX_ADVICE_DAYS);
+    index_advisor::apply(&org_id, stream_type, &stream_name, days).await
+}
+
+/// GetStreamDlpRules
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamGetDlpRules",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Logs stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = StreamDlp),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/dlp")]
+async fn get_dlp(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    dlp::get_rules(&org_id, &stream_name).await
+}
+
+/// SetStreamDlpRules
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamSetDlpRules",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Logs stream name"),
+    ),
+    request_body(content = StreamDlp, description = "DLP rules redacting the records at ingest", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = StreamDlp),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/streams/{stream_name}/dlp")]
+async fn set_dlp(
+    path: web::Path<(String, String)>,
+    rules: web::Json<StreamDlp>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    dlp::save_rules(&org_id, &stream_name, rules.into_inner()).await
+}
+
+/// DeleteStreamDlpRules
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamDeleteDlpRules",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Logs stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/streams/{stream_name}/dlp")]
+async fn delete_dlp(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    dlp::delete_rules(&org_id, &stream_name).await
+}
+
 /// DeleteStream
 #[utoipa::path(
     context_path = "/api",
@@ -310,3 +690,142 @@ async fn list(org_id: web::Path<String>, req: HttpRequest) -> impl Responder {
     indices.sort_by(|a, b| a.name.cmp(&b.name));
     Ok(HttpResponse::Ok().json(ListStream { list: indices }))
 }
+
+/// ArchiveStream
+///
+/// Copies the data files, the schema and the settings of the stream into a
+/// portable archive of the object store.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamArchive",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = StreamArchiveInfo),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/_archive")]
+async fn archive(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(MetaHttpResponse::bad_request(e));
+        }
+    };
+    match stream_archive::archive(&org_id, stream_type, &stream_name).await {
+        Ok(info) => Ok(MetaHttpResponse::json(info)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// ListStreamArchives
+///
+/// Lists the archives of the organization, the root user sees the archives
+/// of all the organizations.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamListArchives",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ListStreamArchive),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/_archives")]
+async fn list_archives(org_id: web::Path<String>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let org_id = (!is_root_user(user_id(&req))).then_some(org_id.as_str());
+    match stream_archive::list(org_id).await {
+        Ok(list) => Ok(MetaHttpResponse::json(ListStreamArchive { list })),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// RestoreStreamArchive
+///
+/// Restores an archive into a new stream of the organization, under the
+/// archived name unless `stream_name` is given.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamRestoreArchive",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = StreamRestoreRequest, description = "Archive to restore", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = StreamArchiveInfo),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/_restore")]
+async fn restore_archive(
+    org_id: web::Path<String>,
+    body: web::Json<StreamRestoreRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let is_root = is_root_user(user_id(&req));
+    match stream_archive::restore(&org_id, body.into_inner(), is_root).await {
+        Ok(info) => Ok(MetaHttpResponse::json(info)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// CreateStreamFromTemplate
+///
+/// Creates a stream with its fields, settings, functions and alerts from a
+/// template. The stream must not exist, the functions and the destinations
+/// of the alerts must.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamCreateFromTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = StreamTemplate, description = "Stream template", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = StreamTemplateResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/_from_template")]
+async fn create_from_template(
+    org_id: web::Path<String>,
+    body: web::Json<StreamTemplate>,
+) -> Result<HttpResponse, Error> {
+    match stream_template::create(&org_id, body.into_inner()).await {
+        Ok(res) => Ok(MetaHttpResponse::json(res)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+fn user_id(req: &HttpRequest) -> &str {
+    req.headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
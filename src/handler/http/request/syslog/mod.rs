@@ -23,7 +23,26 @@ use crate::{
 };
 
 /// Start/StopSyslog Server
-
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Syslog Routes",
+    operation_id = "ToggleSyslogServer",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(
+        content = SyslogServer,
+        description = "Syslog server state",
+        example = json!({"state": true}),
+    ),
+    responses(
+        (status = StatusCode::CREATED, description = "Server state changed", body = bool),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
 #[post("/{org_id}/syslog-server")]
 pub async fn toggle_state(details: web::Json<SyslogServer>) -> Result<HttpResponse, Error> {
     syslogs_route::toggle_state(details.into_inner()).await
@@ -18,7 +18,7 @@ use std::{collections::HashMap, io::Error};
 use actix_web::{get, http, post, web, HttpRequest, HttpResponse};
 use config::{get_config, ider, meta::stream::StreamType, metrics, utils::json};
 use infra::errors;
-use opentelemetry::{global, trace::TraceContextExt};
+use opentelemetry::trace::TraceContextExt;
 use serde::Serialize;
 use tracing::Instrument;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
@@ -26,7 +26,7 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 use crate::{
     common::{
         meta::{self, http::HttpResponse as MetaHttpResponse},
-        utils::http::RequestHeaderExtractor,
+        utils::http::get_trace_context,
     },
     handler::http::request::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO},
     service::{search as SearchService, traces::otlp_http},
@@ -56,6 +56,23 @@ pub async fn traces_write(
     handle_req(org_id, thread_id, req, body).await
 }
 
+/// OtlpTracesIngest
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Traces",
+    operation_id = "PostOtlpTraces",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = String, description = "ExportTraceServiceRequest, protobuf or json", content_type = "application/x-protobuf"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200})),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[post("/{org_id}/v1/traces")]
 pub async fn otlp_traces_write(
     org_id: web::Path<String>,
@@ -139,9 +156,7 @@ pub async fn get_latest_traces(
     let cfg = get_config();
     let mut http_span = None;
     let trace_id = if cfg.common.tracing_enabled {
-        let ctx = global::get_text_map_propagator(|propagator| {
-            propagator.extract(&RequestHeaderExtractor::new(in_req.headers()))
-        });
+        let ctx = get_trace_context(in_req.headers());
         ctx.span().span_context().trace_id().to_string()
     } else if cfg.common.tracing_search_enabled {
         let span = tracing::info_span!(
@@ -149,6 +164,7 @@ pub async fn get_latest_traces(
             org_id = org_id.clone(),
             stream_name = stream_name.clone()
         );
+        span.set_parent(get_trace_context(in_req.headers()));
         let trace_id = span.context().span().span_context().trace_id().to_string();
         http_span = Some(span);
         trace_id
@@ -164,10 +180,12 @@ pub async fn get_latest_traces(
     {
         use crate::common::{
             infra::config::USERS,
-            utils::auth::{is_root_user, AuthExtractor},
+            utils::auth::{is_root_user, is_service_account, AuthExtractor},
         };
         let user_id = in_req.headers().get("user_id").unwrap();
-        if !is_root_user(user_id.to_str().unwrap()) {
+        if !is_root_user(user_id.to_str().unwrap())
+            && !is_service_account(user_id.to_str().unwrap())
+        {
             let user: meta::user::User = USERS
                 .get(&format!("{org_id}/{}", user_id.to_str().unwrap()))
                 .unwrap()
@@ -222,15 +240,21 @@ pub async fn get_latest_traces(
         .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
 
     let cfg = get_config();
-    // get a local search queue lock
-    #[cfg(not(feature = "enterprise"))]
-    let locker = SearchService::QUEUE_LOCKER.clone();
+    // wait for a slot in the search queue of the organization
     #[cfg(not(feature = "enterprise"))]
-    let locker = locker.lock().await;
-    #[cfg(not(feature = "enterprise"))]
-    if !cfg.common.feature_query_queue_enabled {
-        drop(locker);
-    }
+    let _ticket = match SearchService::queue::enter(
+        &org_id,
+        in_req
+            .headers()
+            .get("user_id")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default(),
+    )
+    .await
+    {
+        Ok(ticket) => ticket,
+        Err(e) => return Ok(MetaHttpResponse::too_many_requests(e)),
+    };
     #[cfg(not(feature = "enterprise"))]
     let took_wait = start.elapsed().as_millis() as usize;
     #[cfg(feature = "enterprise")]
@@ -266,6 +290,7 @@ pub async fn get_latest_traces(
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            highlight: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
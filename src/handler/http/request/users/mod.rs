@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Error;
+use std::{collections::HashMap, io::Error};
 
 use actix_web::{
     cookie, delete, get,
@@ -31,14 +31,18 @@ use crate::{
     common::{
         meta::{
             self,
+            audit::AuditAction,
             user::{
-                AuthTokens, RolesResponse, SignInResponse, SignInUser, UpdateUser, UserOrgRole,
-                UserRequest, UserRole,
+                AuthTokens, PasswordResetRequest, RolesResponse, SignInResponse, SignInUser,
+                UpdateUser, UserOrgRole, UserRequest, UserRole,
             },
         },
-        utils::auth::{generate_presigned_url, UserEmail},
+        utils::{
+            auth::{generate_presigned_url, UserEmail},
+            http::get_client_ip,
+        },
     },
-    service::users,
+    service::{db, login_security, users},
 };
 
 /// ListUsers
@@ -207,6 +211,62 @@ pub async fn delete(
     users::remove_user_from_org(&org_id, &email_id, &initiator_id).await
 }
 
+/// GetUserAttributes
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Users",
+    operation_id = "GetUserAttributes",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("email_id" = String, Path, description = "User's email id"),
+      ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Object),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/users/{email_id}/attributes")]
+pub async fn get_attributes(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, email_id) = path.into_inner();
+    users::get_attributes(&org_id, email_id.trim()).await
+}
+
+/// UpdateUserAttributes
+///
+/// Replaces the attributes of the user in the organization, the row filters
+/// of the roles refer to them as `{{user.<attribute>}}`.
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Users",
+    operation_id = "UpdateUserAttributes",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("email_id" = String, Path, description = "User's email id"),
+      ),
+    request_body(content = Object, description = "User attributes", content_type = "application/json", example = json!({
+        "tenant": "acme"
+    })),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Object),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/users/{email_id}/attributes")]
+pub async fn set_attributes(
+    path: web::Path<(String, String)>,
+    attributes: web::Json<HashMap<String, String>>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, email_id) = path.into_inner();
+    users::set_attributes(&org_id, email_id.trim(), attributes.into_inner()).await
+}
+
 /// AuthenticateUser
 #[utoipa::path(
     context_path = "/auth",
@@ -215,12 +275,14 @@ pub async fn delete(
     request_body(content = SignInUser, description = "User login", content_type = "application/json"),
     responses(
         (status = 200, description = "Success", content_type = "application/json", body = SignInResponse),
+        (status = 403, description = "Password reset required", content_type = "application/json", body = SignInResponse),
+        (status = 429, description = "Too many failed logins", content_type = "application/json", body = SignInResponse),
     )
 )]
 #[post("/login")]
 pub async fn authentication(
     auth: Option<web::Json<SignInUser>>,
-    _req: HttpRequest,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     #[cfg(feature = "enterprise")]
     use o2_enterprise::enterprise::common::infra::config::O2_CONFIG;
@@ -230,7 +292,7 @@ pub async fn authentication(
     let native_login_enabled = true;
 
     if !native_login_enabled {
-        return Ok(HttpResponse::Forbidden().json("Not Supported"));
+        return Ok(meta::http::HttpResponse::forbidden("Not Supported"));
     }
 
     let mut resp = SignInResponse::default();
@@ -240,7 +302,7 @@ pub async fn authentication(
             // get Authorization header from request
             #[cfg(feature = "enterprise")]
             {
-                let auth_header = _req.headers().get("Authorization");
+                let auth_header = req.headers().get("Authorization");
                 if auth_header.is_some() {
                     let auth_header = auth_header.unwrap().to_str().unwrap();
                     if let Some((name, password)) =
@@ -263,23 +325,46 @@ pub async fn authentication(
         }
     };
 
-    match crate::handler::http::auth::validator::validate_user(&auth.name, &auth.password).await {
-        Ok(v) => {
-            if v.is_valid {
-                resp.status = true;
-            } else {
-                return unauthorized_error(resp);
-            }
+    let ip = get_client_ip(&req.connection_info(), req.peer_addr());
+    if let Some(retry_after) = login_security::check(&auth.name, ip) {
+        return too_many_requests_error(resp, retry_after);
+    }
+    // ldap users may sign in with their directory name, the session uses the email
+    let user_email = match crate::handler::http::auth::validator::validate_user(
+        &auth.name,
+        &auth.password,
+    )
+    .await
+    {
+        Ok(v) if v.is_valid => {
+            resp.status = true;
+            v.user_email
         }
-        Err(_e) => {
+        _ => {
+            login_security::record_failure(&auth.name, ip, req.path()).await;
             return unauthorized_error(resp);
         }
     };
+    login_security::record_success(&auth.name);
+    if let Ok(db_user) = db::user::get_db_user(&user_email).await {
+        if login_security::is_reset_required(
+            &db_user.email,
+            db_user.is_external,
+            db_user.force_password_reset,
+            db_user.password_updated_at,
+        ) {
+            resp.status = false;
+            resp.message = "Password reset required".to_string();
+            resp.password_reset_required = true;
+            return Ok(HttpResponse::Forbidden().json(resp));
+        }
+    }
+    login_security::audit_event(&auth.name, req.path(), AuditAction::Login, 200).await;
     if resp.status {
         let cfg = get_config();
         let access_token = format!(
             "Basic {}",
-            base64::encode(&format!("{}:{}", auth.name, auth.password))
+            base64::encode(&format!("{}:{}", user_email, auth.password))
         );
         let tokens = json::to_string(&AuthTokens {
             access_token,
@@ -305,6 +390,46 @@ pub async fn authentication(
     }
 }
 
+/// ResetPassword
+#[utoipa::path(
+    context_path = "/auth",
+    tag = "Auth",
+    operation_id = "UserResetPassword",
+    request_body(content = PasswordResetRequest, description = "Old and new password", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Password policy violation", content_type = "application/json", body = HttpResponse),
+        (status = 401, description = "Invalid credentials", content_type = "application/json", body = SignInResponse),
+        (status = 429, description = "Too many failed logins", content_type = "application/json", body = SignInResponse),
+    )
+)]
+#[post("/reset_password")]
+pub async fn reset_password(
+    body: web::Json<PasswordResetRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let body = body.into_inner();
+    let resp = SignInResponse::default();
+    let ip = get_client_ip(&req.connection_info(), req.peer_addr());
+    if let Some(retry_after) = login_security::check(&body.name, ip) {
+        return too_many_requests_error(resp, retry_after);
+    }
+    match crate::handler::http::auth::validator::validate_user(&body.name, &body.old_password).await
+    {
+        Ok(v) if v.is_valid => {}
+        _ => {
+            login_security::record_failure(&body.name, ip, req.path()).await;
+            return unauthorized_error(resp);
+        }
+    };
+    login_security::record_success(&body.name);
+    let res = users::reset_password(&body.name, &body.new_password).await?;
+    if res.status().is_success() {
+        login_security::audit_event(&body.name, req.path(), AuditAction::PasswordReset, 200).await;
+    }
+    Ok(res)
+}
+
 #[derive(serde::Deserialize)]
 struct PresignedURLGenerator {
     #[serde(default = "default_exp_in")]
@@ -320,6 +445,22 @@ const fn default_exp_in() -> u32 {
     600
 }
 
+/// GetPresignedUrl
+#[utoipa::path(
+    context_path = "/auth",
+    tag = "Auth",
+    operation_id = "UserPresignedUrl",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("exp_in" = Option<u32>, Query, description = "Seconds the url is valid for, default 600"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Object, example = json!({"url": "https://openobserve.example.com/web/?auth=...&request_time=...&exp_in=600"})),
+        (status = 401, description = "Unauthorized", content_type = "application/json", body = HttpResponse),
+    )
+)]
 #[get("/presigned-url")]
 pub async fn get_presigned_url(
     _req: HttpRequest,
@@ -480,11 +621,11 @@ pub async fn get_auth(_req: HttpRequest) -> Result<HttpResponse, Error> {
 
     #[cfg(not(feature = "enterprise"))]
     {
-        Ok(HttpResponse::Forbidden().json("Not Supported"))
+        Ok(meta::http::HttpResponse::forbidden("Not Supported"))
     }
 }
 
-/// ListUsers
+/// ListUserRoles
 #[utoipa::path(
     context_path = "/api",
     tag = "Users",
@@ -496,7 +637,7 @@ pub async fn get_auth(_req: HttpRequest) -> Result<HttpResponse, Error> {
         ("org_id" = String, Path, description = "Organization name"),
       ),
     responses(
-        (status = 200, description = "Success", content_type = "application/json", body = UserList),
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<RolesResponse>, example = json!([{"label": "Admin", "value": "admin"}])),
     )
 )]
 #[get("/{org_id}/users/roles")]
@@ -523,6 +664,17 @@ fn unauthorized_error(mut resp: SignInResponse) -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Unauthorized().json(resp))
 }
 
+fn too_many_requests_error(
+    mut resp: SignInResponse,
+    retry_after: i64,
+) -> Result<HttpResponse, Error> {
+    resp.status = false;
+    resp.message = format!("Too many failed logins, retry after {retry_after} seconds");
+    Ok(HttpResponse::TooManyRequests()
+        .insert_header((http::header::RETRY_AFTER, retry_after.to_string()))
+        .json(resp))
+}
+
 #[cfg(test)]
 mod tests {
     use actix_web::{test, App};
@@ -13,28 +13,35 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+#[cfg(not(feature = "enterprise"))]
+use std::collections::HashMap;
 use std::{rc::Rc, str::FromStr};
 
 use actix_cors::Cors;
+use actix_http::h1::Payload;
 use actix_web::{
     body::MessageBody,
     dev::{Service, ServiceRequest, ServiceResponse},
-    http::header,
-    middleware, web, HttpRequest, HttpResponse,
+    http::{header, StatusCode},
+    middleware,
+    web::{self, BytesMut},
+    HttpMessage, HttpRequest, HttpResponse, ResponseError,
 };
 use actix_web_httpauth::middleware::HttpAuthentication;
 use actix_web_lab::middleware::{from_fn, Next};
-use config::get_config;
-use futures::FutureExt;
+use config::{
+    get_config,
+    meta::logger::{parse_request_id, with_request_id, REQUEST_ID_HEADER},
+};
+use futures::{FutureExt, StreamExt};
+use opentelemetry::trace::TraceContextExt;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 #[cfg(feature = "enterprise")]
 use {
     crate::{common::meta::ingestion::INGESTION_EP, service::usage::audit},
-    actix_http::h1::Payload,
-    actix_web::{web::BytesMut, HttpMessage},
     base64::{engine::general_purpose, Engine as _},
-    futures::StreamExt,
     o2_enterprise::enterprise::common::{auditor::AuditMessage, infra::config::O2_CONFIG},
 };
 
@@ -42,7 +49,16 @@ use super::{
     auth::validator::{validator_aws, validator_gcp, validator_proxy_url, validator_rum},
     request::*,
 };
-use crate::common::meta::{middleware_data::RumExtraData, proxy::PathParamProxyURL};
+use crate::{
+    common::{
+        meta::{
+            http::HttpResponse as MetaHttpResponse, middleware_data::RumExtraData,
+            proxy::PathParamProxyURL,
+        },
+        utils::http::get_client_ip,
+    },
+    service,
+};
 
 pub mod openapi;
 pub mod ui;
@@ -78,6 +94,64 @@ fn get_cors() -> Rc<Cors> {
     Rc::new(cors)
 }
 
+/// Answers the requests rejected by the json, query and path extractors with
+/// the same error body as the handlers.
+pub fn extractor_error_handler<E: ResponseError + 'static>(
+    err: E,
+    _req: &HttpRequest,
+) -> actix_web::Error {
+    let status = err.status_code();
+    let resp =
+        HttpResponse::build(status).json(MetaHttpResponse::error(status.into(), err.to_string()));
+    actix_web::error::InternalError::from_response(err, resp).into()
+}
+
+/// Tags the request with the id sent in the `x-request-id` header, or a new
+/// one, the log lines and error responses of the request carry it and the
+/// response returns it. The requests slower than the slow request threshold
+/// are logged with their handler and parameters.
+pub async fn request_id_middleware(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = parse_request_id(
+        req.headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok()),
+    );
+    let header_name = header::HeaderName::from_static(REQUEST_ID_HEADER);
+    let header_value =
+        header::HeaderValue::from_str(&request_id).map_err(actix_web::error::ErrorBadRequest)?;
+    // the router forwards it to the other nodes along with the other headers
+    req.headers_mut()
+        .insert(header_name.clone(), header_value.clone());
+
+    let start = std::time::Instant::now();
+    let mut res = with_request_id(request_id.clone(), next.call(req)).await?;
+    res.headers_mut().insert(header_name, header_value);
+
+    let threshold = get_config().log.slow_request_threshold;
+    let took = start.elapsed().as_millis() as u64;
+    if threshold > 0 && took >= threshold {
+        let req = res.request();
+        let params = req
+            .match_info()
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        log::warn!(
+            "[request_id {request_id}] slow request: {} {}, handler: {}, params: {params}, query: {}, status: {}, took: {took} ms",
+            req.method(),
+            req.path(),
+            req.match_name().unwrap_or("-"),
+            req.query_string(),
+            res.status().as_u16(),
+        );
+    }
+    Ok(res)
+}
+
 #[cfg(feature = "enterprise")]
 async fn audit_middleware(
     mut req: ServiceRequest,
@@ -149,12 +223,241 @@ async fn audit_middleware(
 
 #[cfg(not(feature = "enterprise"))]
 async fn audit_middleware(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let cfg = get_config();
+    let method = req.method().to_string();
+    let prefix = format!("{}/api/", cfg.common.base_uri);
+    let path = req
+        .path()
+        .strip_prefix(&prefix)
+        .unwrap_or(req.path())
+        .to_string();
+    let target = if cfg.common.audit_enabled {
+        crate::common::meta::audit::get_target(&method, &path)
+    } else {
+        None
+    };
+    if let Some(target) = target {
+        let query_params = req.query_string().to_string();
+        let query = web::Query::<HashMap<String, String>>::from_query(&query_params)
+            .unwrap_or_else(|_| web::Query(HashMap::new()));
+        let user_email = req
+            .headers()
+            .get("user_id")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        let mut request_body = BytesMut::new();
+        if !path.ends_with("/settings/logo") {
+            let mut payload_stream = req.take_payload();
+            while let Some(chunk) = payload_stream.next().await {
+                request_body.extend_from_slice(&chunk?);
+            }
+            // Put the payload back into the req
+            let (_, mut payload) = Payload::create(true);
+            payload.unread_data(request_body.clone().into());
+            req.set_payload(payload.into());
+        }
+
+        let before = service::audit::snapshot(&target, &query).await;
+        let res = next.call(req).await?;
+        if res.status().is_success() {
+            let after = service::audit::snapshot(&target, &query).await;
+            service::audit::audit(service::audit::new_entry(
+                &target,
+                &user_email,
+                &method,
+                &path,
+                &query_params,
+                res.status().as_u16(),
+                before,
+                after,
+                &request_body,
+            ))
+            .await;
+        }
+        Ok(res)
+    } else {
+        next.call(req).await
+    }
+}
+
+/// Rejects requests from client ips outside of the network policy of the org,
+/// it runs after authentication so that the user of the request is known.
+async fn network_policy_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // the path looks like `{scope}/{org_id}/...` for /api, /aws and /gcp
+    let path = req
+        .path()
+        .strip_prefix(&get_config().common.base_uri)
+        .unwrap_or(req.path())
+        .trim_start_matches('/');
+    let Some((_, path)) = path.split_once('/') else {
+        return next.call(req).await;
+    };
+    let org_id = path.split('/').next().unwrap_or_default().to_string();
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let method = req.method().to_string();
+    let ip = get_client_ip(&req.connection_info(), req.peer_addr());
+    if service::network_policy::is_request_allowed(&org_id, &user_id, &method, path, ip) {
+        return next.call(req).await;
+    }
+    let path = path.to_string();
+    log::warn!("Request of {user_id} to {path} from {ip:?} denied by network policy of {org_id}");
+    service::network_policy::audit_denied(&org_id, &user_id, &method, &path, ip).await;
+    Err(actix_web::error::ErrorForbidden(
+        "Client ip isn't allowed by the network policy of the organization",
+    ))
+}
+
+/// Records the latency of the request by handler, the route pattern it
+/// matched, with the trace of the request as exemplar.
+pub async fn http_metrics_middleware(
     req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let start = std::time::Instant::now();
+    let trace_id = trace_id(req.request());
+    let method = req.method().to_string();
+    let res = next.call(req).await;
+    let took = start.elapsed().as_secs_f64();
+    let (handler, status, org_id, stream_name) = match &res {
+        Ok(res) => {
+            let req = res.request();
+            (
+                req.match_pattern()
+                    .unwrap_or_else(|| "unmatched".to_string()),
+                res.status(),
+                req.match_info()
+                    .get("org_id")
+                    .unwrap_or_default()
+                    .to_string(),
+                req.match_info()
+                    .get("stream_name")
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        }
+        Err(e) => (
+            "unmatched".to_string(),
+            e.as_response_error().status_code(),
+            String::new(),
+            String::new(),
+        ),
+    };
+    let status = format!("{}xx", status.as_u16() / 100);
+    config::openmetrics::observe_with_exemplar(
+        &config::metrics::HTTP_REQUEST_DURATION,
+        "http_request_duration_seconds",
+        &[
+            ("handler", handler.as_str()),
+            ("method", method.as_str()),
+            ("status", status.as_str()),
+            ("organization", org_id.as_str()),
+            ("stream", stream_name.as_str()),
+        ],
+        took,
+        trace_id.as_deref(),
+    );
+    res
+}
+
+/// The trace of the request, from its span or its `traceparent` header.
+fn trace_id(req: &HttpRequest) -> Option<String> {
+    let span = tracing::Span::current()
+        .context()
+        .span()
+        .span_context()
+        .clone();
+    if span.is_valid() {
+        return Some(span.trace_id().to_string());
+    }
+    req.headers()
+        .get("traceparent")?
+        .to_str()
+        .ok()?
+        .split('-')
+        .nth(1)
+        .filter(|id| id.len() == 32 && id.bytes().any(|b| b != b'0'))
+        .map(str::to_string)
+}
+
+/// Rejects ingestion requests with 503 while the node is draining, so that the
+/// clients retry on another ingester, and with 403 on a read replica.
+async fn drain_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let path = req
+        .path()
+        .strip_prefix(&get_config().common.base_uri)
+        .unwrap_or(req.path())
+        .to_string();
+    if !service::network_policy::is_ingestion_request(req.method().as_str(), &path) {
+        return next.call(req).await;
+    }
+    // count the request before checking the flag, drain waits for it to finish
+    let _guard = service::node::IngestGuard::new();
+    if get_config().read_replica.enabled {
+        return Err(json_error(
+            StatusCode::FORBIDDEN,
+            "Node is a read replica, ingest into the primary cluster",
+        ));
+    }
+    if config::cluster::is_draining() {
+        return Err(json_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Node is draining, retry on another ingester",
+        ));
+    }
+    let body_size = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let _permit = match service::admission::admit_ingest(body_size) {
+        Ok(permit) => permit,
+        Err(e @ service::admission::AdmissionError::TooLarge(..)) => {
+            return Err(json_error(StatusCode::PAYLOAD_TOO_LARGE, e));
+        }
+        Err(e) => {
+            // a full wal drains at the pace of the uploads, not of the requests
+            let retry_after = match e {
+                service::admission::AdmissionError::WalFull(..) => {
+                    get_config().limit.ingester_wal_retry_after
+                }
+                _ => 1,
+            };
+            let resp = HttpResponse::ServiceUnavailable()
+                .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                .json(MetaHttpResponse::error(
+                    StatusCode::SERVICE_UNAVAILABLE.into(),
+                    e.to_string(),
+                ));
+            return Err(actix_web::error::InternalError::from_response(e, resp).into());
+        }
+    };
     next.call(req).await
 }
 
+/// The error of a middleware with the json body of the handler errors.
+fn json_error(status: StatusCode, error: impl ToString) -> actix_web::Error {
+    let error = error.to_string();
+    let resp =
+        HttpResponse::build(status).json(MetaHttpResponse::error(status.into(), error.clone()));
+    actix_web::error::InternalError::from_response(error, resp).into()
+}
+
 /// This is a very trivial proxy to overcome the cors errors while
 /// session-replay in rrweb.
 pub fn get_proxy_routes(cfg: &mut web::ServiceConfig) {
@@ -208,14 +511,23 @@ async fn proxy(
 
 pub fn get_basic_routes(cfg: &mut web::ServiceConfig) {
     let cors = get_cors();
-    cfg.service(status::healthz).service(status::schedulez);
+    cfg.service(status::healthz)
+        .service(status::readyz)
+        .service(status::schedulez)
+        .service(status::openmetrics);
     cfg.service(
         web::scope("/auth")
             .wrap(cors.clone())
             .service(users::authentication)
+            .service(users::reset_password)
             .service(users::get_presigned_url)
             .service(users::get_auth),
     );
+    cfg.service(
+        web::scope("/public")
+            .wrap(cors.clone())
+            .service(dashboards::snapshots::get_public_snapshot),
+    );
 
     cfg.service(
         web::scope("/node")
@@ -226,6 +538,7 @@ pub fn get_basic_routes(cfg: &mut web::ServiceConfig) {
             .service(status::cache_status)
             .service(status::enable_node)
             .service(status::flush_node)
+            .service(status::drain_node)
             .service(status::stream_fields),
     );
 
@@ -288,6 +601,9 @@ pub fn get_config_routes(cfg: &mut web::ServiceConfig) {
         web::scope("/config")
             .wrap(cors.clone())
             .service(status::zo_config)
+            .service(status::redirect)
+            .service(status::dex_login)
+            .service(status::refresh_token_with_dex)
             .service(status::logout)
             .service(web::scope("/reload").service(status::config_reload)),
     );
@@ -325,6 +641,8 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
             .wrap(from_fn(audit_middleware))
+            .wrap(from_fn(drain_middleware))
+            .wrap(from_fn(network_policy_middleware))
             .wrap(HttpAuthentication::with_fn(
                 super::auth::validator::oo_validator,
             ))
@@ -335,6 +653,8 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(users::delete)
             .service(users::update)
             .service(users::add_user_to_org)
+            .service(users::get_attributes)
+            .service(users::set_attributes)
             .service(organization::org::organizations)
             .service(organization::settings::get)
             .service(organization::settings::create)
@@ -342,6 +662,16 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(organization::settings::delete_logo)
             .service(organization::settings::set_logo_text)
             .service(organization::settings::delete_logo_text)
+            .service(organization::quota::get)
+            .service(organization::quota::set)
+            .service(organization::quota::delete)
+            .service(organization::usage::get)
+            .service(organization::ingestion_keys::list)
+            .service(organization::ingestion_keys::create)
+            .service(organization::ingestion_keys::revoke)
+            .service(organization::network_policy::get)
+            .service(organization::network_policy::set)
+            .service(organization::network_policy::delete)
             .service(organization::org::org_summary)
             .service(organization::org::get_user_passcode)
             .service(organization::org::update_user_passcode)
@@ -358,12 +688,30 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(stream::schema)
             .service(stream::settings)
             .service(stream::delete_fields)
+            .service(stream::set_hidden_fields)
+            .service(stream::set_field_aliases)
+            .service(stream::list_schema_changes)
+            .service(stream::approve_schema_change)
+            .service(stream::reject_schema_change)
+            .service(stream::get_index_advice)
+            .service(stream::apply_index_advice)
+            .service(stream::get_dlp)
+            .service(stream::set_dlp)
+            .service(stream::delete_dlp)
+            .service(stream::archive)
+            .service(stream::list_archives)
+            .service(stream::restore_archive)
+            .service(stream::create_from_template)
             .service(stream::delete)
             .service(stream::list)
             .service(logs::ingest::bulk)
             .service(logs::ingest::multi)
+            .service(logs::ingest::raw)
             .service(logs::ingest::json)
             .service(logs::ingest::otlp_logs_write)
+            .service(logs::backfill::backfill)
+            .service(logs::backfill::list_backfill)
+            .service(logs::backfill::get_backfill)
             .service(traces::traces_write)
             .service(traces::otlp_traces_write)
             .service(traces::get_latest_traces)
@@ -383,10 +731,20 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(prom::format_query_get)
             .service(prom::format_query_post)
             .service(enrichment_table::save_enrichment_table)
+            .service(enrichment_table::set_enrichment_table_source)
+            .service(enrichment_table::get_enrichment_table_source)
+            .service(enrichment_table::delete_enrichment_table_source)
+            .service(enrichment_table::set_enrichment_table_lookup)
+            .service(enrichment_table::get_enrichment_table_lookup)
+            .service(enrichment_table::delete_enrichment_table_lookup)
             .service(search::search)
             .service(search::job::cancel_query)
             .service(search::job::query_status)
+            .service(search::job::running_queries)
+            .service(search::job::kill_query)
             .service(search::search_partition)
+            .service(search::search_explain)
+            .service(search::streaming::search_stream)
             .service(search::around)
             .service(search::values)
             .service(search::saved_view::create_view)
@@ -397,6 +755,8 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(functions::save_function)
             .service(functions::list_functions)
             .service(functions::delete_function)
+            .service(functions::get_function_stats)
+            .service(functions::preview_function)
             .service(functions::update_function)
             .service(functions::add_function_to_stream)
             .service(functions::list_stream_functions)
@@ -406,7 +766,21 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(dashboards::list_dashboards)
             .service(dashboards::get_dashboard)
             .service(dashboards::delete_dashboard)
+            .service(dashboards::resolve_variables)
+            .service(dashboards::import_grafana_dashboard)
+            .service(dashboards::snapshots::create_snapshot)
+            .service(dashboards::snapshots::list_snapshots)
+            .service(dashboards::snapshots::delete_snapshot)
+            .service(dashboards::versions::list_versions)
+            .service(dashboards::versions::diff_versions)
+            .service(dashboards::versions::get_version)
+            .service(dashboards::versions::restore_version)
+            .service(bundles::export_bundle)
+            .service(bundles::import_bundle)
+            .service(provisioning::get_report)
+            .service(provisioning::sync)
             .service(dashboards::move_dashboard)
+            .service(dashboards::copy_dashboard)
             .service(dashboards::folders::create_folder)
             .service(dashboards::folders::list_folders)
             .service(dashboards::folders::update_folder)
@@ -458,6 +832,7 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(dashboards::move_dashboard)
             .service(traces::get_latest_traces)
             .service(logs::ingest::multi)
+            .service(logs::ingest::raw)
             .service(logs::ingest::json)
             .service(logs::ingest::handle_kinesis_request)
             .service(logs::ingest::handle_gcp_request)
@@ -476,10 +851,57 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(authz::fga::delete_group)
             .service(users::list_roles)
             .service(clusters::list_clusters)
+            .service(clusters::list_nodes)
+            .service(caches::list)
+            .service(caches::flush)
+            .service(runtime_config::get)
+            .service(runtime_config::update)
+            .service(backup::create)
+            .service(backup::list)
+            .service(backup::restore)
             .service(pipelines::save_pipeline)
             .service(pipelines::list_pipelines)
             .service(pipelines::delete_pipeline)
-            .service(pipelines::update_pipeline),
+            .service(pipelines::update_pipeline)
+            .service(pipelines::list_pipeline_versions)
+            .service(pipelines::test_pipeline)
+            .service(service_accounts::list)
+            .service(service_accounts::save)
+            .service(service_accounts::delete)
+            .service(service_accounts::create_token)
+            .service(service_accounts::rotate_token)
+            .service(service_accounts::delete_token)
+            .service(audit::list)
+            .service(annotations::list)
+            .service(annotations::create)
+            .service(annotations::delete)
+            .service(masking::list)
+            .service(masking::get)
+            .service(masking::save)
+            .service(masking::delete)
+            .service(derived_streams::list)
+            .service(derived_streams::get)
+            .service(derived_streams::create)
+            .service(derived_streams::update)
+            .service(derived_streams::delete)
+            .service(search::templates::list)
+            .service(search::templates::get)
+            .service(search::templates::create)
+            .service(search::templates::update)
+            .service(search::templates::delete)
+            .service(search::templates::run)
+            .service(scim::list_users)
+            .service(scim::get_user)
+            .service(scim::create_user)
+            .service(scim::update_user)
+            .service(scim::patch_user)
+            .service(scim::delete_user)
+            .service(scim::list_groups)
+            .service(scim::get_group)
+            .service(scim::create_group)
+            .service(scim::update_group)
+            .service(scim::patch_group)
+            .service(scim::delete_group),
     );
 }
 
@@ -489,6 +911,8 @@ pub fn get_other_service_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/aws")
             .wrap(cors.clone())
+            .wrap(from_fn(drain_middleware))
+            .wrap(from_fn(network_policy_middleware))
             .wrap(amz_auth)
             .service(logs::ingest::handle_kinesis_request),
     );
@@ -497,6 +921,8 @@ pub fn get_other_service_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/gcp")
             .wrap(cors.clone())
+            .wrap(from_fn(drain_middleware))
+            .wrap(from_fn(network_policy_middleware))
             .wrap(gcp_auth)
             .service(logs::ingest::handle_gcp_request),
     );
@@ -13,8 +13,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use config::{get_config, meta::stream::StreamType};
-use utoipa::{openapi::security::SecurityScheme, Modify, OpenApi};
+use config::{get_config, meta::stream::StreamType, utils::json};
+use utoipa::{
+    openapi::{security::SecurityScheme, Content, RefOr},
+    Modify, OpenApi,
+};
 
 use crate::{common::meta, handler::http::request};
 
@@ -22,12 +25,26 @@ use crate::{common::meta, handler::http::request};
 #[openapi(
     paths(
         request::status::healthz,
+        request::status::readyz,
+        request::status::schedulez,
+        request::status::openmetrics,
+        request::status::enable_node,
+        request::status::flush_node,
+        request::status::drain_node,
+        request::status::stream_fields,
         request::users::list,
         request::users::save,
         request::users::update,
         request::users::delete,
         request::users::add_user_to_org,
+        request::users::get_attributes,
+        request::users::set_attributes,
+        request::users::reset_password,
+        request::users::authentication,
+        request::users::get_presigned_url,
+        request::users::list_roles,
         request::organization::org::organizations,
+        request::organization::org::create_org,
         request::organization::org::org_summary,
         request::organization::org::get_user_passcode,
         request::organization::org::update_user_passcode,
@@ -36,31 +53,79 @@ use crate::{common::meta, handler::http::request};
         request::organization::org::create_user_rumtoken,
         request::organization::settings::get,
         request::organization::settings::create,
+        request::organization::quota::get,
+        request::organization::quota::set,
+        request::organization::quota::delete,
+        request::organization::usage::get,
+        request::organization::ingestion_keys::list,
+        request::organization::ingestion_keys::create,
+        request::organization::ingestion_keys::revoke,
+        request::organization::network_policy::get,
+        request::organization::network_policy::set,
+        request::organization::network_policy::delete,
         request::stream::list,
         request::stream::schema,
         request::stream::settings,
         request::stream::delete_fields,
+        request::stream::set_hidden_fields,
+        request::stream::set_field_aliases,
+        request::stream::list_schema_changes,
+        request::stream::approve_schema_change,
+        request::stream::reject_schema_change,
+        request::stream::get_index_advice,
+        request::stream::apply_index_advice,
+        request::stream::get_dlp,
+        request::stream::set_dlp,
+        request::stream::delete_dlp,
+        request::stream::archive,
+        request::stream::list_archives,
+        request::stream::restore_archive,
+        request::stream::create_from_template,
         request::stream::delete,
         request::logs::ingest::bulk,
         request::logs::ingest::multi,
+        request::logs::ingest::raw,
         request::logs::ingest::json,
+        request::logs::ingest::otlp_logs_write,
+        request::logs::ingest::handle_kinesis_request,
+        request::logs::ingest::handle_gcp_request,
+        request::logs::backfill::backfill,
+        request::logs::backfill::list_backfill,
+        request::logs::backfill::get_backfill,
         request::traces::traces_write,
+        request::traces::otlp_traces_write,
         request::traces::get_latest_traces,
         request::metrics::ingest::json,
+        request::metrics::ingest::otlp_metrics_write,
         request::prom::remote_write,
         request::prom::query_get,
+        request::prom::query_post,
         request::prom::query_range_get,
+        request::prom::query_range_post,
         request::prom::metadata,
         request::prom::series_get,
+        request::prom::series_post,
         request::prom::labels_get,
+        request::prom::labels_post,
         request::prom::label_values,
         request::prom::format_query_get,
+        request::prom::format_query_post,
         request::enrichment_table::save_enrichment_table,
+        request::enrichment_table::set_enrichment_table_source,
+        request::enrichment_table::get_enrichment_table_source,
+        request::enrichment_table::delete_enrichment_table_source,
+        request::enrichment_table::set_enrichment_table_lookup,
+        request::enrichment_table::get_enrichment_table_lookup,
+        request::enrichment_table::delete_enrichment_table_lookup,
         request::rum::ingest::log,
         request::rum::ingest::data,
         request::rum::ingest::sessionreplay,
         request::search::search,
         request::search::search_partition,
+        request::search::search_explain,
+        request::search::job::running_queries,
+        request::search::job::kill_query,
+        request::search::streaming::search_stream,
         request::search::around,
         request::search::values,
         request::search::saved_view::create_view,
@@ -72,6 +137,8 @@ use crate::{common::meta, handler::http::request};
         request::functions::update_function,
         request::functions::save_function,
         request::functions::delete_function,
+        request::functions::get_function_stats,
+        request::functions::preview_function,
         request::functions::list_stream_functions,
         request::functions::add_function_to_stream,
         request::functions::delete_stream_function,
@@ -80,12 +147,40 @@ use crate::{common::meta, handler::http::request};
         request::dashboards::list_dashboards,
         request::dashboards::get_dashboard,
         request::dashboards::delete_dashboard,
+        request::dashboards::resolve_variables,
+        request::dashboards::import_grafana_dashboard,
+        request::dashboards::snapshots::create_snapshot,
+        request::dashboards::snapshots::list_snapshots,
+        request::dashboards::snapshots::delete_snapshot,
+        request::dashboards::snapshots::get_public_snapshot,
+        request::dashboards::versions::list_versions,
+        request::dashboards::versions::diff_versions,
+        request::dashboards::versions::get_version,
+        request::dashboards::versions::restore_version,
+        request::bundles::export_bundle,
+        request::bundles::import_bundle,
+        request::provisioning::get_report,
+        request::provisioning::sync,
         request::dashboards::folders::delete_folder,
         request::dashboards::folders::create_folder,
         request::dashboards::folders::list_folders,
         request::dashboards::folders::get_folder,
         request::dashboards::folders::update_folder,
         request::dashboards::move_dashboard,
+        request::dashboards::copy_dashboard,
+        request::dashboards::reports::create_report,
+        request::dashboards::reports::update_report,
+        request::dashboards::reports::list_reports,
+        request::dashboards::reports::get_report,
+        request::dashboards::reports::delete_report,
+        request::dashboards::reports::enable_report,
+        request::dashboards::reports::trigger_report,
+        request::pipelines::save_pipeline,
+        request::pipelines::list_pipelines,
+        request::pipelines::delete_pipeline,
+        request::pipelines::update_pipeline,
+        request::pipelines::list_pipeline_versions,
+        request::pipelines::test_pipeline,
         request::alerts::save_alert,
         request::alerts::update_alert,
         request::alerts::list_stream_alerts,
@@ -112,24 +207,119 @@ use crate::{common::meta, handler::http::request};
         request::syslog::update_route,
         request::syslog::list_routes,
         request::syslog::delete_route,
+        request::syslog::toggle_state,
         request::clusters::list_clusters,
+        request::clusters::list_nodes,
+        request::caches::list,
+        request::caches::flush,
+        request::runtime_config::get,
+        request::runtime_config::update,
+        request::backup::create,
+        request::backup::list,
+        request::backup::restore,
+        request::service_accounts::list,
+        request::service_accounts::save,
+        request::service_accounts::delete,
+        request::service_accounts::create_token,
+        request::service_accounts::rotate_token,
+        request::service_accounts::delete_token,
+        request::audit::list,
+        request::annotations::list,
+        request::annotations::create,
+        request::annotations::delete,
+        request::masking::list,
+        request::masking::get,
+        request::masking::save,
+        request::masking::delete,
+        request::derived_streams::list,
+        request::derived_streams::get,
+        request::derived_streams::create,
+        request::derived_streams::update,
+        request::derived_streams::delete,
+        request::search::templates::list,
+        request::search::templates::get,
+        request::search::templates::create,
+        request::search::templates::update,
+        request::search::templates::delete,
+        request::search::templates::run,
+        request::scim::list_users,
+        request::scim::get_user,
+        request::scim::create_user,
+        request::scim::update_user,
+        request::scim::patch_user,
+        request::scim::delete_user,
+        request::scim::list_groups,
+        request::scim::get_group,
+        request::scim::create_group,
+        request::scim::update_group,
+        request::scim::patch_group,
+        request::scim::delete_group,
     ),
     components(
         schemas(
             meta::http::HttpResponse,
+            meta::http::ErrorInfo,
             StreamType,
             meta::stream::Stream,
             meta::stream::StreamProperty,
             meta::stream::StreamDeleteFields,
+            meta::stream::StreamHiddenFields,
+            meta::stream::StreamFieldAliases,
+            meta::stream::PendingSchemaChange,
+            meta::stream::FieldFilterUsage,
+            meta::stream::IndexAdvice,
+            meta::stream::StreamArchiveInfo,
+            meta::stream::ListStreamArchive,
+            meta::stream::StreamRestoreRequest,
+            meta::stream::StreamTemplate,
+            meta::stream::StreamTemplateField,
+            meta::stream::StreamTemplateFieldType,
+            meta::stream::StreamTemplateFunction,
+            meta::stream::StreamTemplateResponse,
             meta::stream::ListStream,
+            meta::dlp::StreamDlp,
+            meta::dlp::DlpRule,
+            meta::dlp::DlpDetector,
+            meta::dlp::DlpAction,
             config::meta::stream::StreamSettings,
             config::meta::stream::StreamPartition,
             config::meta::stream::StreamPartitionType,
             config::meta::stream::StreamStats,
             config::meta::stream::PartitionTimeLevel,
+            config::meta::stream::FlushPolicy,
+            config::meta::stream::LogFormat,
+            config::meta::cluster::NodeInfo,
+            config::meta::cluster::NodeMetrics,
             meta::ingestion::RecordStatus,
             meta::ingestion::StreamStatus,
             meta::ingestion::IngestionResponse,
+            meta::ingestion::KinesisFHRequest,
+            meta::ingestion::KFHRecordRequest,
+            meta::ingestion::KinesisFHIngestionResponse,
+            meta::ingestion::GCPIngestionRequest,
+            meta::ingestion::GCPMessage,
+            meta::ingestion::GCPAttributes,
+            meta::ingestion::BackfillJob,
+            meta::ingestion::BackfillStatus,
+            meta::ingestion::ListBackfillJobs,
+            meta::pipelines::PipeLine,
+            meta::pipelines::PipelineStep,
+            meta::pipelines::PipeLineResponse,
+            meta::pipelines::PipeLineList,
+            meta::pipelines::PipelineVersionList,
+            meta::pipelines::PipelineTestRequest,
+            meta::pipelines::PipelineTestOutput,
+            meta::pipelines::PipelineTestResponse,
+            meta::dashboards::reports::Report,
+            meta::dashboards::reports::ReportDestination,
+            meta::dashboards::reports::ReportMediaType,
+            meta::dashboards::reports::ReportDashboard,
+            meta::dashboards::reports::ReportDashboardVariable,
+            meta::dashboards::reports::ReportTimerange,
+            meta::dashboards::reports::ReportTimerangeType,
+            meta::dashboards::reports::ReportFrequency,
+            meta::dashboards::reports::ReportFrequencyType,
+            config::meta::stream::RoutingCondition,
             meta::dashboards::Dashboard,
             meta::dashboards::Dashboards,
             meta::dashboards::v1::AxisItem,
@@ -146,7 +336,39 @@ use crate::{common::meta, handler::http::request};
             meta::dashboards::v1::VariableList,
             meta::dashboards::Folder,
             meta::dashboards::MoveDashboard,
+            meta::dashboards::CopyDashboard,
             meta::dashboards::FolderList,
+            meta::dashboards::variables::ResolveVariablesRequest,
+            meta::dashboards::variables::ResolvedVariable,
+            meta::dashboards::variables::ResolveVariablesResponse,
+            meta::dashboards::grafana::UnsupportedItem,
+            meta::dashboards::grafana::GrafanaImportResponse,
+            meta::dashboards::snapshots::CreateSnapshotRequest,
+            meta::dashboards::snapshots::SnapshotQueryResult,
+            meta::dashboards::snapshots::Snapshot,
+            meta::dashboards::snapshots::SnapshotInfo,
+            meta::dashboards::snapshots::SnapshotList,
+            meta::dashboards::versions::SavedVersion,
+            meta::dashboards::versions::SavedVersionInfo,
+            meta::dashboards::versions::SavedVersionList,
+            meta::dashboards::versions::PatchOperation,
+            meta::dashboards::versions::VersionDiff,
+            meta::bundles::Bundle,
+            meta::bundles::BundleDashboard,
+            meta::bundles::ConflictStrategy,
+            meta::bundles::ImportAction,
+            meta::bundles::ImportResult,
+            meta::bundles::ImportReport,
+            meta::provisioning::DriftState,
+            meta::provisioning::DriftItem,
+            meta::provisioning::ProvisioningReport,
+            meta::caches::CacheInfo,
+            meta::runtime_config::RuntimeSetting,
+            meta::runtime_config::RuntimeConfig,
+            meta::runtime_config::RuntimeConfigRequest,
+            meta::backup::BackupManifest,
+            meta::backup::BackupList,
+            meta::backup::RestoreReport,
             config::meta::search::Query,
             config::meta::search::Request,
             config::meta::search::RequestEncoding,
@@ -155,7 +377,13 @@ use crate::{common::meta, handler::http::request};
             config::meta::search::ResponseNodeTook,
             config::meta::search::SearchPartitionRequest,
             config::meta::search::SearchPartitionResponse,
+            config::meta::search::SearchExplainResponse,
+            config::meta::search::SearchExplainPartition,
+            config::meta::search::SearchExplainFile,
+            config::meta::search::StreamingEvent,
             config::meta::search::CancelQueryResponse,
+            config::meta::search::RunningQueriesResponse,
+            config::meta::search::RunningQuery,
             config::meta::search::QueryStatusResponse,
             config::meta::search::QueryStatus,
             config::meta::search::QueryInfo,
@@ -182,7 +410,14 @@ use crate::{common::meta, handler::http::request};
             meta::alerts::destinations::DestinationType,
             meta::alerts::templates::Template,
             meta::functions::Transform,
+            meta::enrichment_table::EnrichmentTableSource,
+            meta::enrichment_table::EnrichmentTableLookup,
+            meta::enrichment_table::LookupMode,
             meta::functions::FunctionList,
+            meta::functions::FunctionStats,
+            meta::functions::FunctionPreviewRequest,
+            meta::functions::FunctionPreviewResult,
+            meta::functions::FunctionPreviewResponse,
             meta::functions::StreamFunctionsList,
             meta::functions::StreamTransform,
             meta::functions::StreamOrder,
@@ -193,7 +428,11 @@ use crate::{common::meta, handler::http::request};
             meta::user::UserList,
             meta::user::UserResponse,
             meta::user::UpdateUser,
+            meta::user::SignInUser,
             meta::user::SignInResponse,
+            meta::user::RolesResponse,
+            meta::user::PasswordResetRequest,
+            meta::organization::Organization,
             meta::organization::OrgSummary,
             meta::organization::StreamSummary,
             meta::organization::OrganizationResponse,
@@ -201,22 +440,66 @@ use crate::{common::meta, handler::http::request};
             meta::organization::OrgUser,
             meta::organization::IngestionPasscode,
             meta::organization::PasscodeResponse,
+            meta::organization::IngestionKey,
+            meta::organization::IngestionKeyRequest,
+            meta::organization::IngestionKeyResponse,
             meta::organization::OrganizationSetting,
             meta::organization::OrganizationSettingResponse,
+            meta::organization::OrgQuota,
+            meta::organization::OrgQuotaUsage,
+            meta::organization::OrgQuotaResponse,
+            meta::organization::OrgUsageResponse,
+            config::meta::usage::UsageRollup,
+            meta::organization::OrgNetworkPolicy,
             meta::organization::RumIngestionResponse,
             meta::organization::RumIngestionToken,
             request::status::HealthzResponse,
+            request::status::ReadyzResponse,
+            request::status::DependencyStatus,
             meta::ingestion::BulkResponse,
             meta::ingestion::BulkResponseItem,
             meta::ingestion::ShardResponse,
             meta::ingestion::BulkResponseError,
             meta::syslog::SyslogRoute,
             meta::syslog::SyslogRoutes,
+            meta::syslog::SyslogServer,
             meta::prom::Metadata,
             meta::prom::MetricType,
+            meta::service_account::ServiceAccount,
+            meta::service_account::ServiceAccountRequest,
+            meta::audit::AuditEntry,
+            meta::audit::AuditList,
+            meta::annotations::Annotation,
+            meta::annotations::AnnotationList,
+            meta::masking::MaskingAction,
+            meta::masking::MaskingRule,
+            meta::masking::MaskingPolicy,
+            meta::masking::MaskingPolicyList,
+            meta::derived_streams::DerivedStream,
+            meta::derived_streams::DerivedStreamList,
+            meta::search_templates::SearchTemplate,
+            meta::search_templates::SearchTemplateParam,
+            meta::search_templates::SearchTemplateParamType,
+            meta::search_templates::SearchTemplateList,
+            meta::search_templates::SearchTemplateRunRequest,
+            meta::scim::ScimUser,
+            meta::scim::ScimName,
+            meta::scim::ScimEmail,
+            meta::scim::ScimMember,
+            meta::scim::ScimMeta,
+            meta::scim::ScimGroup,
+            meta::scim::ScimListResponse,
+            meta::scim::ScimPatchRequest,
+            meta::scim::ScimPatchOperation,
+            meta::scim::ScimError,
+            meta::service_account::ApiToken,
+            meta::service_account::ApiTokenRequest,
+            meta::service_account::ApiTokenResponse,
+            meta::service_account::RotateTokenRequest,
+            meta::service_account::TokenScope,
          ),
     ),
-    modifiers(&SecurityAddon),
+    modifiers(&SecurityAddon, &ExamplesAddon),
     tags(
         (name = "Meta", description = "Meta details about the OpenObserve state itself. e.g. healthz"),
         (name = "Auth", description = "User login authentication"),
@@ -233,7 +516,21 @@ use crate::{common::meta, handler::http::request};
         (name = "Metrics", description = "Metrics data ingestion operations"),
         (name = "Traces", description = "Traces data ingestion operations"),
         (name = "Syslog Routes", description = "Syslog Routes retrieval & management operations"),
+        (name = "Pipelines", description = "Stream pipelines retrieval & management operations"),
+        (name = "Reports", description = "Scheduled dashboard reports retrieval & management operations"),
         (name = "Clusters", description = "Super cluster operations"),
+        (name = "Caches", description = "In-process caches of the node"),
+        (name = "Config", description = "Settings changed at runtime without a restart"),
+        (name = "Backup", description = "Backup and restore of the meta store"),
+        (name = "Service Accounts", description = "Service accounts and api tokens management operations"),
+        (name = "Audit", description = "Audit trail of management operations"),
+        (name = "Annotations", description = "Event markers drawn on the charts"),
+        (name = "Masking", description = "Field masking policies applied to search results"),
+        (name = "Derived Streams", description = "SQL queries continuously materialized into streams"),
+        (name = "Search Templates", description = "Saved SQL queries with parameters, run by name"),
+        (name = "SCIM", description = "SCIM 2.0 user and group provisioning"),
+        (name = "Bundles", description = "Export and import of dashboards, alerts and their dependencies"),
+        (name = "Provisioning", description = "Reconciliation of the resources with their definitions as code"),
     ),
     info(
         description = "OpenObserve API documents [https://openobserve.ai/docs/](https://openobserve.ai/docs/)",
@@ -259,3 +556,115 @@ impl Modify for SecurityAddon {
         );
     }
 }
+
+/// Example payloads of the endpoints by operation id, the request body and the
+/// success response, for the endpoints whose annotations have none.
+const EXAMPLES: &[(&str, Option<&str>, Option<&str>)] = &[
+    (
+        "StreamSetHiddenFields",
+        Some(r#"{"fields": ["password", "session_token"]}"#),
+        None,
+    ),
+    (
+        "StreamSetFieldAliases",
+        Some(r#"{"aliases": {"ns": "kubernetes_namespace_name"}}"#),
+        None,
+    ),
+    (
+        "StreamListSchemaChanges",
+        None,
+        Some(
+            r#"[{"stream_type": "logs", "stream_name": "default", "field": "code", "from_type": "Int64", "to_type": "Utf8", "first_seen": 1712132400000000, "last_seen": 1712136000000000}]"#,
+        ),
+    ),
+    (
+        "StreamIndexAdvice",
+        None,
+        Some(
+            r#"{"stream_type": "logs", "stream_name": "default", "queries": 120, "partition_keys": [{"field": "kubernetes_namespace_name", "queries": 80, "distinct_values": 6, "avg_hits": 250.0}], "bloom_filter_fields": [{"field": "trace_id", "queries": 40, "distinct_values": 40, "avg_hits": 3.5}]}"#,
+        ),
+    ),
+    (
+        "CreateOrganization",
+        None,
+        Some(r#"{"identifier": "acme", "label": "Acme"}"#),
+    ),
+    (
+        "PostOtlpTraces",
+        Some(
+            r#"{"resourceSpans": [{"resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "checkout"}}]}, "scopeSpans": [{"spans": [{"traceId": "5b8efff798038103d269b633813fc60c", "spanId": "eee19b7ec3c1b174", "name": "GET /cart", "kind": 2, "startTimeUnixNano": "1712132400000000000", "endTimeUnixNano": "1712132400120000000"}]}]}]}"#,
+        ),
+        None,
+    ),
+];
+
+pub struct ExamplesAddon;
+
+impl Modify for ExamplesAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for item in openapi.paths.paths.values_mut() {
+            for operation in item.operations.values_mut() {
+                let Some((_, request, response)) = EXAMPLES
+                    .iter()
+                    .find(|(id, ..)| operation.operation_id.as_deref() == Some(*id))
+                else {
+                    continue;
+                };
+                if let (Some(example), Some(body)) = (request, operation.request_body.as_mut()) {
+                    set_example(body.content.values_mut(), example);
+                }
+                if let (Some(example), Some(RefOr::T(res))) =
+                    (response, operation.responses.responses.get_mut("200"))
+                {
+                    set_example(res.content.values_mut(), example);
+                }
+            }
+        }
+    }
+}
+
+fn set_example<'a>(contents: impl Iterator<Item = &'a mut Content>, example: &str) {
+    let example: json::Value = json::from_str(example).expect("invalid example payload");
+    for content in contents {
+        if content.example.is_none() {
+            content.example = Some(example.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn operation_ids() -> Vec<String> {
+        ApiDoc::openapi()
+            .paths
+            .paths
+            .values()
+            .flat_map(|item| item.operations.values())
+            .filter_map(|operation| operation.operation_id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_operation_ids_are_unique() {
+        let ids = operation_ids();
+        let mut seen = HashSet::new();
+        for id in ids.iter() {
+            assert!(seen.insert(id), "operation id {id} is used twice");
+        }
+    }
+
+    #[test]
+    fn test_examples_match_operations() {
+        let ids = operation_ids();
+        for (id, request, response) in EXAMPLES {
+            assert!(ids.contains(&id.to_string()), "no operation {id}");
+            for example in request.iter().chain(response.iter()) {
+                assert!(json::from_str::<json::Value>(example).is_ok(), "{id}");
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cluster event bus on core nats subjects. Unlike the queue, where the
+//! consumers of a cluster share the messages, every subscribed node receives
+//! every message, except the node that published it.
+
+use async_nats::{Client, HeaderMap};
+use bytes::Bytes;
+use config::{cluster::LOCAL_NODE_UUID, get_config};
+use futures::StreamExt;
+use tokio::sync::{mpsc, OnceCell};
+
+use crate::{
+    db::nats::{connect_to, get_nats_client},
+    errors::{Error, Result},
+};
+
+const NODE_HEADER: &str = "node";
+
+static PRIMARY_CLIENT: OnceCell<Client> = OnceCell::const_new();
+
+fn subject(topic: &str) -> String {
+    format!("{}bus.{}", get_config().nats.prefix, topic)
+}
+
+pub async fn publish(topic: &str, value: Bytes) -> Result<()> {
+    let mut headers = HeaderMap::new();
+    headers.insert(NODE_HEADER, LOCAL_NODE_UUID.as_str());
+    get_nats_client()
+        .await
+        .publish_with_headers(subject(topic), headers, value)
+        .await
+        .map_err(|e| Error::Message(format!("nats bus publish error: {e}")))?;
+    Ok(())
+}
+
+pub async fn subscribe(topic: &str) -> Result<mpsc::Receiver<Bytes>> {
+    subscribe_with(get_nats_client().await, subject(topic)).await
+}
+
+/// Subscribes to a topic on the bus of the primary cluster, used by the read
+/// replicas to follow the events of the cluster they replicate.
+pub async fn subscribe_primary(topic: &str) -> Result<mpsc::Receiver<Bytes>> {
+    let cfg = get_config();
+    let client = PRIMARY_CLIENT
+        .get_or_init(|| connect_to(&cfg.read_replica.primary_nats_addr))
+        .await;
+    let subject = format!("{}bus.{}", cfg.read_replica.primary_nats_prefix, topic);
+    subscribe_with(client, subject).await
+}
+
+async fn subscribe_with(client: &Client, subject: String) -> Result<mpsc::Receiver<Bytes>> {
+    let mut subscriber = client
+        .subscribe(subject)
+        .await
+        .map_err(|e| Error::Message(format!("nats bus subscribe error: {e}")))?;
+    let (tx, rx) = mpsc::channel(1024);
+    tokio::task::spawn(async move {
+        while let Some(message) = subscriber.next().await {
+            let from_local = message
+                .headers
+                .as_ref()
+                .and_then(|h| h.get(NODE_HEADER))
+                .is_some_and(|v| v.as_str() == LOCAL_NODE_UUID.as_str());
+            if from_local {
+                continue;
+            }
+            if tx.send(message.payload).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(rx)
+}
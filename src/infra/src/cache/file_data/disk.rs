@@ -17,6 +17,7 @@ use std::{
     cmp::{max, min},
     ops::Range,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 use async_recursion::async_recursion;
@@ -34,6 +35,9 @@ use tokio::{fs, sync::RwLock};
 use super::CacheStrategy;
 use crate::storage;
 
+/// Whether the files of the cache dir were loaded at startup.
+static LOADED: AtomicBool = AtomicBool::new(false);
+
 static FILES: Lazy<Vec<RwLock<FileData>>> = Lazy::new(|| {
     let cfg = get_config();
     let mut files = Vec::with_capacity(cfg.disk_cache.bucket_num);
@@ -118,7 +122,8 @@ impl FileData {
                 self.max_size,
                 max(get_config().disk_cache.release_size, data_size * 100),
             );
-            self.gc(trace_id, need_release_size).await?;
+            let release_files = self.gc(trace_id, need_release_size).await?;
+            crate::cache::record_evictions(crate::cache::FILE_DATA_DISK, release_files);
         }
 
         self.cur_size += data_size;
@@ -140,7 +145,11 @@ impl FileData {
         Ok(())
     }
 
-    async fn gc(&mut self, trace_id: &str, need_release_size: usize) -> Result<(), anyhow::Error> {
+    async fn gc(
+        &mut self,
+        trace_id: &str,
+        need_release_size: usize,
+    ) -> Result<usize, anyhow::Error> {
         log::info!(
             "[trace_id {trace_id}] File disk cache start gc {}/{}, need to release {} bytes",
             self.cur_size,
@@ -148,6 +157,7 @@ impl FileData {
             need_release_size
         );
         let mut release_size = 0;
+        let mut release_files = 0;
         loop {
             let item = self.data.remove();
             if item.is_none() {
@@ -157,6 +167,7 @@ impl FileData {
                 break;
             }
             let (key, data_size) = item.unwrap();
+            release_files += 1;
             // delete file from local disk
             let file_path = format!(
                 "{}{}{}",
@@ -185,7 +196,7 @@ impl FileData {
             "[trace_id {trace_id}] File disk cache gc done, released {} bytes",
             release_size
         );
-        Ok(())
+        Ok(release_files)
     }
 
     fn choose_multi_dir(&self, file: &str) -> String {
@@ -226,6 +237,7 @@ pub async fn init() -> Result<(), anyhow::Error> {
             log::error!("load disk cache error: {}", e);
         }
         log::info!("Loading disk cache done, total files: {} ", len().await);
+        LOADED.store(true, Ordering::Relaxed);
     });
 
     tokio::task::spawn(async move {
@@ -245,6 +257,12 @@ pub async fn init() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Whether the cache is ready, i.e. it is disabled or it finished loading the
+/// files already on disk.
+pub fn is_loaded() -> bool {
+    !get_config().disk_cache.enabled || LOADED.load(Ordering::Relaxed)
+}
+
 #[inline]
 pub async fn get(file: &str, range: Option<Range<usize>>) -> Option<Bytes> {
     if !get_config().disk_cache.enabled {
@@ -252,7 +270,9 @@ pub async fn get(file: &str, range: Option<Range<usize>>) -> Option<Bytes> {
     }
     let idx = get_bucket_idx(file);
     let files = FILES[idx].read().await;
-    files.get(file, range).await
+    let data = files.get(file, range).await;
+    crate::cache::record_lookup(crate::cache::FILE_DATA_DISK, data.is_some());
+    data
 }
 
 #[inline]
@@ -366,12 +386,40 @@ async fn gc() -> Result<(), anyhow::Error> {
         }
         drop(r);
         let mut w = file.write().await;
-        w.gc("global", cfg.disk_cache.gc_size).await?;
+        let release_files = w.gc("global", cfg.disk_cache.gc_size).await?;
+        crate::cache::record_evictions(crate::cache::FILE_DATA_DISK, release_files);
         drop(w);
     }
     Ok(())
 }
 
+/// Deletes all the cached files, returns the number of files deleted.
+pub async fn clear() -> Result<usize, anyhow::Error> {
+    let mut total = 0;
+    for file in FILES.iter() {
+        let mut w = file.write().await;
+        if w.cur_size == 0 {
+            continue;
+        }
+        let cur_size = w.cur_size;
+        total += w.gc("flush", cur_size).await?;
+    }
+    Ok(total)
+}
+
+/// Changes the size of each bucket, the files over it are deleted.
+pub async fn resize(max_size: usize) -> Result<(), anyhow::Error> {
+    for file in FILES.iter() {
+        let mut w = file.write().await;
+        w.max_size = max_size;
+        if w.cur_size > max_size {
+            let need_release_size = w.cur_size - max_size;
+            w.gc("resize", need_release_size).await?;
+        }
+    }
+    Ok(())
+}
+
 #[inline]
 pub async fn stats() -> (usize, usize) {
     let mut total_size = 0;
@@ -407,18 +455,22 @@ pub async fn is_empty() -> bool {
 }
 
 pub async fn download(trace_id: &str, file: &str) -> Result<(), anyhow::Error> {
-    let data = storage::get(file).await?;
-    if data.is_empty() {
-        return Err(anyhow::anyhow!("file {} data size is zero", file));
-    }
-    if let Err(e) = set(trace_id, file, data).await {
-        return Err(anyhow::anyhow!(
-            "set file {} to disk cache failed: {}",
-            file,
-            e
-        ));
-    };
-    Ok(())
+    let (trace_id, file_name) = (trace_id.to_string(), file.to_string());
+    super::download_once(super::CacheType::Disk, file, async move {
+        let data = super::get_object(&trace_id, &file_name).await?;
+        if data.is_empty() {
+            return Err(anyhow::anyhow!("file {} data size is zero", file_name));
+        }
+        if let Err(e) = set(&trace_id, &file_name, data).await {
+            return Err(anyhow::anyhow!(
+                "set file {} to disk cache failed: {}",
+                file_name,
+                e
+            ));
+        };
+        Ok(())
+    })
+    .await
 }
 
 fn get_bucket_idx(file: &str) -> usize {
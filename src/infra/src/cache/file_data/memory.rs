@@ -28,7 +28,6 @@ use once_cell::sync::Lazy;
 use tokio::sync::RwLock;
 
 use super::CacheStrategy;
-use crate::storage;
 
 static FILES: Lazy<Vec<RwLock<FileData>>> = Lazy::new(|| {
     let cfg = get_config();
@@ -131,6 +130,7 @@ impl FileData {
             need_release_size
         );
         let mut release_size = 0;
+        let mut release_files = 0;
         loop {
             let item = self.data.remove();
             if item.is_none() {
@@ -145,6 +145,7 @@ impl FileData {
             if let Some((key, data)) = DATA[idx].remove(&key) {
                 _ = super::disk::set(trace_id, &key, data).await;
             }
+            release_files += 1;
             // metrics
             let columns = key.split('/').collect::<Vec<&str>>();
             if columns[0] == "files" {
@@ -162,6 +163,7 @@ impl FileData {
         }
         self.cur_size -= release_size;
         let _ = DATA.iter().map(|c| c.shrink_to_fit()).collect::<Vec<_>>();
+        crate::cache::record_evictions(crate::cache::FILE_DATA_MEMORY, release_files);
         log::info!(
             "[trace_id {trace_id}] File memory cache gc done, released {} bytes",
             release_size
@@ -169,6 +171,28 @@ impl FileData {
         Ok(())
     }
 
+    /// Drops all the files, without moving them to the disk cache.
+    fn clear(&mut self) -> usize {
+        let mut release_files = 0;
+        while let Some((key, data_size)) = self.data.remove() {
+            let idx = get_bucket_idx(&key);
+            DATA[idx].remove(&key);
+            // metrics
+            let columns = key.split('/').collect::<Vec<&str>>();
+            if columns[0] == "files" {
+                metrics::QUERY_MEMORY_CACHE_FILES
+                    .with_label_values(&[columns[1], columns[2]])
+                    .dec();
+                metrics::QUERY_MEMORY_CACHE_USED_BYTES
+                    .with_label_values(&[columns[1], columns[2]])
+                    .sub(data_size as i64);
+            }
+            release_files += 1;
+        }
+        self.cur_size = 0;
+        release_files
+    }
+
     fn size(&self) -> (usize, usize) {
         (self.max_size, self.cur_size)
     }
@@ -214,7 +238,9 @@ pub async fn get(file: &str, range: Option<Range<usize>>) -> Option<Bytes> {
     }
     let idx = get_bucket_idx(file);
     let files = FILES[idx].read().await;
-    files.get(file, range).await
+    let data = files.get(file, range).await;
+    crate::cache::record_lookup(crate::cache::FILE_DATA_MEMORY, data.is_some());
+    data
 }
 
 #[inline]
@@ -261,6 +287,30 @@ async fn gc() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Drops all the cached files, returns the number of files dropped.
+pub async fn clear() -> usize {
+    let mut total = 0;
+    for file in FILES.iter() {
+        let mut w = file.write().await;
+        total += w.clear();
+    }
+    let _ = DATA.iter().map(|c| c.shrink_to_fit()).collect::<Vec<_>>();
+    total
+}
+
+/// Changes the size of each bucket, the files over it are dropped.
+pub async fn resize(max_size: usize) -> Result<(), anyhow::Error> {
+    for file in FILES.iter() {
+        let mut w = file.write().await;
+        w.max_size = max_size;
+        if w.cur_size > max_size {
+            let need_release_size = w.cur_size - max_size;
+            w.gc("resize", need_release_size).await?;
+        }
+    }
+    Ok(())
+}
+
 #[inline]
 pub async fn stats() -> (usize, usize) {
     let mut total_size = 0;
@@ -296,18 +346,22 @@ pub async fn is_empty() -> bool {
 }
 
 pub async fn download(trace_id: &str, file: &str) -> Result<(), anyhow::Error> {
-    let data = storage::get(file).await?;
-    if data.is_empty() {
-        return Err(anyhow::anyhow!("file {} data size is zero", file));
-    }
-    if let Err(e) = set(trace_id, file, data).await {
-        return Err(anyhow::anyhow!(
-            "set file {} to memory cache failed: {}",
-            file,
-            e
-        ));
-    };
-    Ok(())
+    let (trace_id, file_name) = (trace_id.to_string(), file.to_string());
+    super::download_once(super::CacheType::Memory, file, async move {
+        let data = super::get_object(&trace_id, &file_name).await?;
+        if data.is_empty() {
+            return Err(anyhow::anyhow!("file {} data size is zero", file_name));
+        }
+        if let Err(e) = set(&trace_id, &file_name, data).await {
+            return Err(anyhow::anyhow!(
+                "set file {} to memory cache failed: {}",
+                file_name,
+                e
+            ));
+        };
+        Ok(())
+    })
+    .await
 }
 
 fn get_bucket_idx(file: &str) -> usize {
@@ -16,13 +16,51 @@
 pub mod disk;
 pub mod memory;
 
-use std::collections::VecDeque;
+use std::{collections::VecDeque, future::Future};
 
-use hashbrown::HashSet;
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use hashbrown::{HashMap, HashSet};
 use hashlink::lru_cache::LruCache;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 
 const INITIAL_CACHE_SIZE: usize = 128;
 
+type Download = Shared<BoxFuture<'static, Result<(), String>>>;
+
+// the downloads in flight, a file requested by several searches at the same
+// time is downloaded once
+static DOWNLOADS: Lazy<Mutex<HashMap<(CacheType, String), Download>>> = Lazy::new(Default::default);
+
+/// Runs the download of a file into a cache, or waits for the one already in
+/// flight for the same file and cache.
+async fn download_once<F>(cache_type: CacheType, file: &str, download: F) -> anyhow::Result<()>
+where
+    F: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let key = (cache_type, file.to_string());
+    let task = DOWNLOADS
+        .lock()
+        .entry(key.clone())
+        .or_insert_with(|| {
+            download
+                .map(|ret| ret.map_err(|e| e.to_string()))
+                .boxed()
+                .shared()
+        })
+        .clone();
+    let ret = task.clone().await;
+    let mut downloads = DOWNLOADS.lock();
+    if downloads.get(&key).is_some_and(|v| v.ptr_eq(&task)) {
+        downloads.remove(&key);
+    }
+    drop(downloads);
+    ret.map_err(|e| anyhow::anyhow!(e))
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum CacheType {
     Disk,
@@ -101,6 +139,25 @@ pub async fn init() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Gets the file from the object store in a download slot of the search, the
+/// files not found are remembered for a while instead of being requested again.
+async fn get_object(trace_id: &str, file: &str) -> Result<bytes::Bytes, anyhow::Error> {
+    let key = super::negative::object_key(file);
+    if super::negative::is_cached(&key) {
+        return Err(anyhow::anyhow!("file {} not found", file));
+    }
+    let _permit = crate::storage::limiter::acquire(trace_id).await;
+    match crate::storage::get(file).await {
+        Ok(data) => Ok(data),
+        Err(e) => {
+            if super::negative::is_not_found(&e) {
+                super::negative::set(key);
+            }
+            Err(e)
+        }
+    }
+}
+
 pub async fn download(trace_id: &str, file: &str) -> Result<(), anyhow::Error> {
     let cfg = config::get_config();
     if cfg.memory_cache.enabled {
@@ -141,4 +198,33 @@ mod tests {
         assert!(!cache.contains_key(key1));
         assert!(cache.contains_key(key2));
     }
+
+    #[tokio::test]
+    async fn test_download_once() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let download = |calls: Arc<AtomicUsize>| async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        };
+        let file = "files/default/logs/olympics/2022/10/03/10/1.parquet";
+        let (a, b) = tokio::join!(
+            download_once(CacheType::Disk, file, download(calls.clone())),
+            download_once(CacheType::Disk, file, download(calls.clone()))
+        );
+        assert!(a.is_ok() && b.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(DOWNLOADS.lock().is_empty());
+
+        // a finished download doesn't stay in flight
+        download_once(CacheType::Disk, file, download(calls.clone()))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
 }
@@ -13,10 +13,48 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use config::{get_config, metrics};
+
 pub mod file_data;
+pub mod negative;
+pub mod record_batch;
 pub mod stats;
 pub mod tmpfs;
 
+/// Names of the caches in the cache metrics and the admin API.
+pub const FILE_DATA_MEMORY: &str = "file_data_memory";
+pub const FILE_DATA_DISK: &str = "file_data_disk";
+pub const RECORD_BATCH: &str = "record_batch";
+pub const SCHEMA: &str = "schema";
+pub const STREAM_STATS: &str = "stream_stats";
+pub const FILE_LIST: &str = "file_list";
+pub const FILE_STATISTICS: &str = "file_statistics";
+pub const NEGATIVE: &str = "negative";
+
+/// Records a lookup of the cache as a hit or a miss.
+#[inline]
+pub fn record_lookup(cache: &str, hit: bool) {
+    if !get_config().common.cache_metrics_enabled {
+        return;
+    }
+    if hit {
+        metrics::CACHE_HITS.with_label_values(&[cache]).inc();
+    } else {
+        metrics::CACHE_MISSES.with_label_values(&[cache]).inc();
+    }
+}
+
+/// Records the entries evicted from the cache to make room for new ones.
+#[inline]
+pub fn record_evictions(cache: &str, num: usize) {
+    if num == 0 || !get_config().common.cache_metrics_enabled {
+        return;
+    }
+    metrics::CACHE_EVICTIONS
+        .with_label_values(&[cache])
+        .inc_by(num as u64);
+}
+
 pub async fn init() -> Result<(), anyhow::Error> {
     file_data::init().await?;
     Ok(())
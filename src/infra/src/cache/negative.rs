@@ -0,0 +1,164 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Remembers for a few seconds the lookups that found nothing, the objects
+//! not found in the object store and the empty file lists of the searches, so
+//! that refreshing the same empty paths does not hit the object store again.
+
+use chrono::Utc;
+use config::{get_config, meta::stream::StreamType, RwHashMap};
+use once_cell::sync::Lazy;
+
+static ENTRIES: Lazy<NegativeCache> = Lazy::new(NegativeCache::default);
+
+#[derive(Default)]
+struct NegativeCache {
+    /// key -> expiration time in microseconds
+    data: RwHashMap<String, i64>,
+}
+
+impl NegativeCache {
+    fn contains(&self, key: &str, now: i64) -> bool {
+        let Some(expires_at) = self.data.get(key).map(|v| *v.value()) else {
+            return false;
+        };
+        if expires_at > now {
+            return true;
+        }
+        self.data.remove_if(key, |_, v| *v <= now);
+        false
+    }
+
+    fn insert(&self, key: String, now: i64, ttl: i64, max_entries: usize) {
+        if self.data.len() >= max_entries {
+            self.data.retain(|_, v| *v > now);
+            if self.data.len() >= max_entries {
+                return;
+            }
+        }
+        self.data.insert(key, now + ttl);
+    }
+}
+
+fn ttl() -> i64 {
+    get_config().limit.query_negative_cache_ttl * 1_000_000
+}
+
+/// Returns true if the key was found empty less than the ttl ago.
+pub fn is_cached(key: &str) -> bool {
+    if ttl() <= 0 {
+        return false;
+    }
+    let hit = ENTRIES.contains(key, Utc::now().timestamp_micros());
+    super::record_lookup(super::NEGATIVE, hit);
+    hit
+}
+
+/// Remembers the key as empty for the ttl.
+pub fn set(key: String) {
+    let ttl = ttl();
+    if ttl <= 0 {
+        return;
+    }
+    let cfg = get_config();
+    ENTRIES.insert(
+        key,
+        Utc::now().timestamp_micros(),
+        ttl,
+        cfg.limit.query_negative_cache_max_entries,
+    );
+}
+
+/// Forgets the key, e.g. when it is written.
+pub fn remove(key: &str) {
+    ENTRIES.data.remove(key);
+}
+
+pub fn len() -> usize {
+    ENTRIES.data.len()
+}
+
+/// Forgets all the keys, returns the number of keys dropped.
+pub fn clear() -> usize {
+    let num = ENTRIES.data.len();
+    ENTRIES.data.clear();
+    num
+}
+
+/// The key of an object of the object store.
+pub fn object_key(file: &str) -> String {
+    format!("object/{file}")
+}
+
+/// The key of the file list of a stream in a time range.
+pub fn file_list_key(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    time_min: i64,
+    time_max: i64,
+) -> String {
+    format!("file_list/{org_id}/{stream_type}/{stream_name}/{time_min}/{time_max}")
+}
+
+/// Returns true if the error says the object does not exist.
+pub fn is_not_found(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<object_store::Error>(),
+        Some(object_store::Error::NotFound { .. })
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negative_cache_expires() {
+        let cache = NegativeCache::default();
+        cache.insert("a".to_string(), 100, 10, 10);
+        assert!(cache.contains("a", 105));
+        assert!(!cache.contains("b", 105));
+        assert!(!cache.contains("a", 110));
+        // the expired key is dropped on lookup
+        assert!(cache.data.is_empty());
+    }
+
+    #[test]
+    fn test_negative_cache_max_entries() {
+        let cache = NegativeCache::default();
+        cache.insert("a".to_string(), 100, 10, 2);
+        cache.insert("b".to_string(), 100, 10, 2);
+        // full of live keys, the new one is not remembered
+        cache.insert("c".to_string(), 105, 10, 2);
+        assert!(!cache.contains("c", 105));
+        // the expired keys make room
+        cache.insert("c".to_string(), 120, 10, 2);
+        assert!(cache.contains("c", 125));
+        assert_eq!(cache.data.len(), 1);
+    }
+
+    #[test]
+    fn test_object_key() {
+        assert_eq!(
+            object_key("files/default/logs/app/2024/06/01/00/1.parquet"),
+            "object/files/default/logs/app/2024/06/01/00/1.parquet"
+        );
+        assert_eq!(
+            file_list_key("default", StreamType::Logs, "app", 1, 2),
+            "file_list/default/logs/app/1/2"
+        );
+    }
+}
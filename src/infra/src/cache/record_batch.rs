@@ -0,0 +1,190 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Memory bounded LRU of the decoded record batches of parquet row groups,
+//! shared by the searches on the files of the memory cache so a refreshed
+//! dashboard skips the decompression of the same row groups.
+
+use config::{
+    get_config, metrics,
+    utils::hash::{gxhash, Sum64},
+};
+use datafusion::arrow::record_batch::RecordBatch;
+use hashlink::lru_cache::LruCache;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+static BATCHES: Lazy<Mutex<RecordBatchCache>> = Lazy::new(|| {
+    Mutex::new(RecordBatchCache::new(
+        get_config().memory_cache.batch_max_size,
+    ))
+});
+
+struct RecordBatchCache {
+    max_size: usize,
+    cur_size: usize,
+    data: LruCache<String, (Vec<RecordBatch>, usize)>,
+}
+
+impl RecordBatchCache {
+    fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            cur_size: 0,
+            data: LruCache::new_unbounded(),
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<Vec<RecordBatch>> {
+        self.data.get(key).map(|(batches, _)| batches.clone())
+    }
+
+    fn set(&mut self, key: String, batches: Vec<RecordBatch>) -> bool {
+        let size = batches
+            .iter()
+            .map(|b| b.get_array_memory_size())
+            .sum::<usize>();
+        // a row group taking a good part of the cache would evict all the others
+        if size == 0 || size > self.max_size / 10 {
+            return false;
+        }
+        if let Some((_, old_size)) = self.data.remove(&key) {
+            self.cur_size -= old_size;
+        }
+        let mut evicted = 0;
+        while self.cur_size + size > self.max_size {
+            let Some((_, (_, old_size))) = self.data.remove_lru() else {
+                break;
+            };
+            self.cur_size -= old_size;
+            evicted += 1;
+        }
+        super::record_evictions(super::RECORD_BATCH, evicted);
+        self.cur_size += size;
+        self.data.insert(key, (batches, size));
+        true
+    }
+}
+
+/// Returns true if the decoded row groups are cached.
+pub fn is_enabled() -> bool {
+    get_config().memory_cache.batch_max_size > 0
+}
+
+/// Builds the key of a row group of a file read with the given columns.
+pub fn key(file: &str, row_group: usize, columns: &[&str]) -> String {
+    let columns = gxhash::new().sum64(&columns.join(","));
+    format!("{file}/{row_group}/{columns}")
+}
+
+pub fn get(key: &str) -> Option<Vec<RecordBatch>> {
+    let ret = BATCHES.lock().get(key);
+    super::record_lookup(super::RECORD_BATCH, ret.is_some());
+    if ret.is_some() {
+        metrics::QUERY_BATCH_CACHE_HITS.with_label_values(&[]).inc();
+    } else {
+        metrics::QUERY_BATCH_CACHE_MISSES
+            .with_label_values(&[])
+            .inc();
+    }
+    ret
+}
+
+pub fn set(key: String, batches: Vec<RecordBatch>) {
+    let mut w = BATCHES.lock();
+    w.set(key, batches);
+    metrics::QUERY_BATCH_CACHE_USED_BYTES
+        .with_label_values(&[])
+        .set(w.cur_size as i64);
+}
+
+/// Returns the (max, used) bytes of the cache.
+pub fn stats() -> (usize, usize) {
+    let r = BATCHES.lock();
+    (r.max_size, r.cur_size)
+}
+
+pub fn len() -> usize {
+    BATCHES.lock().data.len()
+}
+
+/// Drops all the decoded row groups, returns the number of row groups dropped.
+pub fn clear() -> usize {
+    let mut w = BATCHES.lock();
+    let num = w.data.len();
+    w.data.clear();
+    w.cur_size = 0;
+    metrics::QUERY_BATCH_CACHE_USED_BYTES
+        .with_label_values(&[])
+        .set(0);
+    num
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::{
+        array::Int64Array,
+        datatypes::{DataType, Field, Schema},
+    };
+
+    use super::*;
+
+    fn batch(rows: i64) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int64Array::from_iter_values(0..rows))],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_record_batch_cache_evicts_lru() {
+        let size = batch(100).get_array_memory_size();
+        let mut cache = RecordBatchCache::new(size * 25);
+        assert!(cache.set("a".to_string(), vec![batch(100)]));
+        assert!(cache.set("b".to_string(), vec![batch(100)]));
+        assert!(cache.get("a").is_some());
+        for i in 0..23 {
+            assert!(cache.set(format!("c{i}"), vec![batch(100)]));
+        }
+        // "b" is the least recently used one
+        assert!(cache.set("d".to_string(), vec![batch(100)]));
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.cur_size <= cache.max_size);
+
+        // too large for the cache
+        assert!(!cache.set("e".to_string(), vec![batch(1000)]));
+    }
+
+    #[test]
+    fn test_record_batch_cache_key() {
+        assert_eq!(
+            key("f.parquet", 0, &["a", "b"]),
+            key("f.parquet", 0, &["a", "b"])
+        );
+        assert_ne!(
+            key("f.parquet", 0, &["a", "b"]),
+            key("f.parquet", 1, &["a", "b"])
+        );
+        assert_ne!(
+            key("f.parquet", 0, &["a", "b"]),
+            key("f.parquet", 0, &["a"])
+        );
+    }
+}
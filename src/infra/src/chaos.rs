@@ -0,0 +1,123 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fault injection for testing the failure paths. With ZO_CHAOS_ENABLED the
+//! object store and the meta store are wrapped, their calls are delayed or
+//! fail at random with the configured rates, so the retries of compaction,
+//! deletion and search run the way they would on a flaky bucket.
+
+use std::time::Duration;
+
+use config::{get_config, metrics, utils::rand::get_rand_float, Chaos};
+
+pub const STORAGE: &str = "storage";
+pub const DB: &str = "db";
+
+#[derive(Debug, Default, PartialEq)]
+struct Faults {
+    latency: Option<Duration>,
+    error: bool,
+}
+
+/// Whether the calls of the target get faults injected.
+pub fn enabled(target: &str) -> bool {
+    let cfg = get_config();
+    cfg.chaos.enabled && cfg.chaos.targets.split(',').any(|v| v.trim() == target)
+}
+
+fn matches(operations: &str, operation: &str) -> bool {
+    operations.trim().is_empty() || operations.split(',').any(|v| v.trim() == operation)
+}
+
+/// Draws the faults of a call from the rolls, each in `[0, 1)`.
+fn draw(cfg: &Chaos, latency_roll: f64, delay_roll: f64, error_roll: f64) -> Faults {
+    Faults {
+        latency: (latency_roll < cfg.latency_rate)
+            .then(|| Duration::from_millis((delay_roll * cfg.latency_max as f64) as u64)),
+        error: error_roll < cfg.error_rate,
+    }
+}
+
+/// Injects the faults into a call of the target, an error comes back as its
+/// message for the caller to wrap in its own error type.
+pub async fn inject(target: &str, operation: &str) -> Result<(), String> {
+    let faults = {
+        let cfg = get_config();
+        if !matches(&cfg.chaos.operations, operation) {
+            return Ok(());
+        }
+        draw(
+            &cfg.chaos,
+            get_rand_float(),
+            get_rand_float(),
+            get_rand_float(),
+        )
+    };
+    if let Some(latency) = faults.latency {
+        metrics::CHAOS_FAULTS
+            .with_label_values(&[target, operation, "latency"])
+            .inc();
+        tokio::time::sleep(latency).await;
+    }
+    if faults.error {
+        metrics::CHAOS_FAULTS
+            .with_label_values(&[target, operation, "error"])
+            .inc();
+        return Err(format!("chaos: injected {target} {operation} error"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chaos(error_rate: f64, latency_rate: f64) -> Chaos {
+        Chaos {
+            enabled: true,
+            targets: "storage,db".to_string(),
+            operations: "".to_string(),
+            error_rate,
+            latency_rate,
+            latency_max: 1000,
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("", "get"));
+        assert!(matches("get, put", "put"));
+        assert!(!matches("get,put", "delete"));
+    }
+
+    #[test]
+    fn test_draw() {
+        assert_eq!(draw(&chaos(0.0, 0.0), 0.0, 0.5, 0.0), Faults::default());
+        assert_eq!(
+            draw(&chaos(0.1, 0.5), 0.4, 0.5, 0.2),
+            Faults {
+                latency: Some(Duration::from_millis(500)),
+                error: false,
+            }
+        );
+        assert_eq!(
+            draw(&chaos(1.0, 0.5), 0.6, 0.5, 0.99),
+            Faults {
+                latency: None,
+                error: true,
+            }
+        );
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hashbrown::HashMap;
+use tokio::sync::mpsc;
+
+use super::{Db, Event, Stats, UpdateFn};
+use crate::{
+    chaos,
+    errors::{Error, Result},
+};
+
+/// Meta store injecting the faults of ZO_CHAOS_* into the calls of the
+/// wrapped one. The setup calls and the watches are left alone, a failed
+/// watch isn't retried by the watchers.
+pub struct ChaosDb {
+    inner: Box<dyn Db>,
+}
+
+impl ChaosDb {
+    pub fn new(inner: Box<dyn Db>) -> Self {
+        Self { inner }
+    }
+}
+
+async fn inject(operation: &str) -> Result<()> {
+    chaos::inject(chaos::DB, operation)
+        .await
+        .map_err(Error::Message)
+}
+
+#[async_trait]
+impl Db for ChaosDb {
+    async fn create_table(&self) -> Result<()> {
+        self.inner.create_table().await
+    }
+
+    async fn stats(&self) -> Result<Stats> {
+        self.inner.stats().await
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes> {
+        inject("get").await?;
+        self.inner.get(key).await
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        value: Bytes,
+        need_watch: bool,
+        start_dt: Option<i64>,
+    ) -> Result<()> {
+        inject("put").await?;
+        self.inner.put(key, value, need_watch, start_dt).await
+    }
+
+    async fn get_for_update(
+        &self,
+        key: &str,
+        need_watch: bool,
+        start_dt: Option<i64>,
+        update_fn: Box<UpdateFn>,
+    ) -> Result<()> {
+        inject("put").await?;
+        self.inner
+            .get_for_update(key, need_watch, start_dt, update_fn)
+            .await
+    }
+
+    async fn delete(
+        &self,
+        key: &str,
+        with_prefix: bool,
+        need_watch: bool,
+        start_dt: Option<i64>,
+    ) -> Result<()> {
+        inject("delete").await?;
+        self.inner
+            .delete(key, with_prefix, need_watch, start_dt)
+            .await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<HashMap<String, Bytes>> {
+        inject("list").await?;
+        self.inner.list(prefix).await
+    }
+
+    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        inject("list").await?;
+        self.inner.list_keys(prefix).await
+    }
+
+    async fn list_values(&self, prefix: &str) -> Result<Vec<Bytes>> {
+        inject("list").await?;
+        self.inner.list_values(prefix).await
+    }
+
+    async fn list_values_by_start_dt(
+        &self,
+        prefix: &str,
+        start_dt: Option<(i64, i64)>,
+    ) -> Result<Vec<(i64, Bytes)>> {
+        inject("list").await?;
+        self.inner.list_values_by_start_dt(prefix, start_dt).await
+    }
+
+    async fn count(&self, prefix: &str) -> Result<i64> {
+        inject("list").await?;
+        self.inner.count(prefix).await
+    }
+
+    async fn watch(&self, prefix: &str) -> Result<Arc<mpsc::Receiver<Event>>> {
+        self.inner.watch(prefix).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
+
+    async fn add_start_dt_column(&self) -> Result<()> {
+        self.inner.add_start_dt_column().await
+    }
+}
@@ -14,7 +14,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
-    cmp::min,
+    cmp::{max, min},
     sync::{
         atomic::{AtomicU8, Ordering},
         Arc,
@@ -25,7 +25,8 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use config::{cluster, get_config};
 use etcd_client::{
-    Certificate, DeleteOptions, EventType, GetOptions, Identity, SortOrder, SortTarget, TlsOptions,
+    Certificate, Compare, CompareOp, DeleteOptions, EventType, GetOptions, Identity, PutOptions,
+    SortOrder, SortTarget, TlsOptions, Txn, TxnOp,
 };
 use hashbrown::HashMap;
 use tokio::{
@@ -48,7 +49,7 @@ pub async fn get_etcd_client() -> &'static etcd_client::Client {
 
 pub async fn init() {
     let cfg = get_config();
-    if cfg.common.local_mode || cfg.common.cluster_coordinator.to_lowercase() == "nats" {
+    if cfg.common.local_mode || cfg.common.cluster_coordinator.to_lowercase() != "etcd" {
         return;
     }
     // enable keep alive for auth token
@@ -437,12 +438,17 @@ impl super::Db for Etcd {
         let key = format!("{}{}", &self.prefix, prefix);
         let self_prefix = self.prefix.to_string();
         let _task: JoinHandle<Result<()>> = tokio::task::spawn(async move {
+            // the revision to resume from after reconnecting, so that no event is missed
+            let mut next_revision = None;
             loop {
                 if cluster::is_offline() {
                     break;
                 }
                 let mut client = get_etcd_client().await.clone();
-                let opt = etcd_client::WatchOptions::new().with_prefix();
+                let mut opt = etcd_client::WatchOptions::new().with_prefix();
+                if let Some(revision) = next_revision {
+                    opt = opt.with_start_revision(revision);
+                }
                 let (mut _watcher, mut stream) =
                     match client.watch(key.clone(), Some(opt.clone())).await {
                         Ok((watcher, stream)) => (watcher, stream),
@@ -461,8 +467,27 @@ impl super::Db for Etcd {
                         }
                     };
                     if let Some(ev) = resp {
+                        if ev.canceled() && ev.compact_revision() > 0 {
+                            // the events since the revision are compacted, put all the keys again
+                            log::warn!(
+                                "watching prefix: {}, revision compacted to {}, resync",
+                                key,
+                                ev.compact_revision()
+                            );
+                            next_revision = match resync(&mut client, &key, &self_prefix, &tx).await
+                            {
+                                Ok(revision) if revision > 0 => Some(revision + 1),
+                                Ok(_) => None,
+                                Err(e) => {
+                                    log::error!("watching prefix: {}, resync error: {}", key, e);
+                                    None
+                                }
+                            };
+                            break;
+                        }
                         for ev in ev.events() {
                             let kv = ev.kv().unwrap();
+                            next_revision = Some(kv.mod_revision() + 1);
                             let item_key = kv.key_str().unwrap();
                             let item_key = item_key.strip_prefix(&self_prefix).unwrap();
                             match ev.event_type() {
@@ -500,6 +525,31 @@ impl super::Db for Etcd {
     }
 }
 
+/// Sends a put event for every key under the prefix, returns the revision of
+/// the snapshot to watch from.
+async fn resync(
+    client: &mut etcd_client::Client,
+    key: &str,
+    self_prefix: &str,
+    tx: &mpsc::Sender<Event>,
+) -> Result<i64> {
+    let resp = client
+        .get(key, Some(GetOptions::new().with_prefix()))
+        .await?;
+    for kv in resp.kvs() {
+        let item_key = kv.key_str().unwrap();
+        let item_key = item_key.strip_prefix(self_prefix).unwrap();
+        tx.send(Event::Put(EventData {
+            key: item_key.to_string(),
+            value: Some(Bytes::from(kv.value().to_vec())),
+            start_dt: None,
+        }))
+        .await
+        .map_err(|e| Error::Message(e.to_string()))?;
+    }
+    Ok(resp.header().map(|h| h.revision()).unwrap_or_default())
+}
+
 pub async fn create_table() -> Result<()> {
     Ok(())
 }
@@ -613,6 +663,54 @@ where
     Ok(())
 }
 
+/// Puts the key with a new lease unless another lease holds it, returns the
+/// lease id when the local node acquired the key.
+pub async fn campaign(key: &str, value: &str, ttl: i64) -> Result<Option<i64>> {
+    let key = format!("{}{}", get_config().etcd.prefix, key);
+    let mut client = get_etcd_client().await.clone();
+    let lease_id = client.lease_grant(ttl, None).await?.id();
+    let txn = Txn::new()
+        .when([Compare::create_revision(key.as_str(), CompareOp::Equal, 0)])
+        .and_then([TxnOp::put(
+            key.as_str(),
+            value,
+            Some(PutOptions::new().with_lease(lease_id)),
+        )]);
+    if client.txn(txn).await?.succeeded() {
+        return Ok(Some(lease_id));
+    }
+    client.lease_revoke(lease_id).await?;
+    Ok(None)
+}
+
+/// Keeps the lease alive until the stopper is true, unlike
+/// `keepalive_lease_id` it gives up on the first error, the holder can not
+/// tell whether the lease is still alive without a reply of etcd.
+pub async fn hold_lease_id<F>(id: i64, ttl: i64, stopper: F) -> Result<()>
+where
+    F: Fn() -> bool,
+{
+    let mut client = get_etcd_client().await.clone();
+    let (mut keeper, mut stream) = client.lease_keep_alive(id).await?;
+    let ttl_keep_alive = max(1, ttl / 3) as u64;
+    loop {
+        if stopper() {
+            client.lease_revoke(id).await?;
+            return Ok(());
+        }
+        time::sleep(time::Duration::from_secs(ttl_keep_alive)).await;
+        keeper.keep_alive().await?;
+        match stream.message().await? {
+            Some(v) if v.ttl() > 0 => {}
+            _ => {
+                return Err(Error::from(etcd_client::Error::LeaseKeepAliveError(
+                    "lease expired or revoked".to_string(),
+                )));
+            }
+        }
+    }
+}
+
 pub(crate) struct Locker {
     key: String,
     lock_id: String,
@@ -23,6 +23,7 @@ use tokio::sync::{mpsc, OnceCell};
 
 use crate::errors::{DbError, Error, Result};
 
+pub mod chaos;
 pub mod etcd;
 pub mod mysql;
 pub mod nats;
@@ -64,12 +65,18 @@ async fn default() -> Box<dyn Db> {
         panic!("cluster mode is not supported for ZO_META_STORE=sqlite");
     }
 
-    match cfg.common.meta_store.as_str().into() {
+    let db: Box<dyn Db> = match cfg.common.meta_store.as_str().into() {
         MetaStore::Sqlite => Box::<sqlite::SqliteDb>::default(),
         MetaStore::Etcd => Box::<etcd::Etcd>::default(),
         MetaStore::Nats => Box::<nats::NatsDb>::default(),
         MetaStore::MySQL => Box::<mysql::MysqlDb>::default(),
         MetaStore::PostgreSQL => Box::<postgres::PostgresDb>::default(),
+    };
+    if crate::chaos::enabled(crate::chaos::DB) {
+        log::warn!("[CHAOS] injecting faults into the meta store");
+        Box::new(chaos::ChaosDb::new(db))
+    } else {
+        db
     }
 }
 
@@ -83,6 +90,7 @@ async fn init_cluster_coordinator() -> Box<dyn Db> {
     } else {
         match cfg.common.cluster_coordinator.as_str().into() {
             MetaStore::Nats => Box::<nats::NatsDb>::default(),
+            MetaStore::PostgreSQL => Box::<postgres::PostgresDb>::default(),
             _ => Box::<etcd::Etcd>::default(),
         }
     }
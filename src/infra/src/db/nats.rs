@@ -553,6 +553,8 @@ impl super::Db for NatsDb {
         let prefix = prefix.to_string();
         let self_prefix = self.prefix.to_string();
         let _task: JoinHandle<Result<()>> = tokio::task::spawn(async move {
+            // the revision to resume from after reconnecting, so that no event is missed
+            let mut next_revision = None;
             loop {
                 if cluster::is_offline() {
                     break;
@@ -566,7 +568,11 @@ impl super::Db for NatsDb {
                     })?
                     .bucket;
                 let bucket_prefix = "/".to_string() + bucket_name.trim_start_matches(&self_prefix);
-                let mut entries = bucket.watch_all().await.map_err(|e| {
+                let entries = match next_revision {
+                    Some(revision) => bucket.watch_all_from_revision(revision).await,
+                    None => bucket.watch_all().await,
+                };
+                let mut entries = entries.map_err(|e| {
                     Error::Message(format!("[NATS:watch] bucket.watch_all error: {}", e))
                 })?;
                 loop {
@@ -587,6 +593,7 @@ impl super::Db for NatsDb {
                                     break;
                                 }
                             };
+                            next_revision = Some(entry.revision + 1);
                             let item_key = key_decode(&entry.key);
                             if !item_key.starts_with(new_key) {
                                 continue;
@@ -636,15 +643,18 @@ pub async fn connect() -> async_nats::Client {
     if cfg.common.print_key_config {
         log::info!("Nats init get_config(): {:?}", cfg.nats);
     }
+    connect_to(&cfg.nats.addr).await
+}
 
+/// Connects to the given nats address(es) with the credentials of ZO_NATS_*.
+pub async fn connect_to(addr: &str) -> async_nats::Client {
+    let cfg = get_config();
     let mut opts = async_nats::ConnectOptions::new()
         .connection_timeout(Duration::from_secs(cfg.nats.connect_timeout));
     if !cfg.nats.user.is_empty() {
         opts = opts.user_and_password(cfg.nats.user.to_string(), cfg.nats.password.to_string());
     }
-    let addrs = cfg
-        .nats
-        .addr
+    let addrs = addr
         .split(',')
         .map(|a| a.parse().unwrap())
         .collect::<Vec<ServerAddr>>();
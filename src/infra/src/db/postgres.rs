@@ -13,23 +13,53 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use bytes::Bytes;
-use config::utils::hash::Sum64;
+use config::{
+    get_config,
+    meta::meta_store::MetaStore,
+    utils::{hash::Sum64, json},
+    FxIndexMap,
+};
 use hashbrown::HashMap;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use sqlx::{
-    postgres::{PgConnectOptions, PgPoolOptions},
+    pool::PoolConnection,
+    postgres::{PgConnectOptions, PgListener, PgPoolOptions},
     ConnectOptions, Pool, Postgres,
 };
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex, OnceCell, RwLock};
 
-use crate::errors::*;
+use crate::{
+    db::{Event, EventData},
+    errors::*,
+};
 
 pub static CLIENT: Lazy<Pool<Postgres>> = Lazy::new(connect);
 
+static WATCHERS: Lazy<RwLock<FxIndexMap<String, EventChannel>>> =
+    Lazy::new(|| RwLock::new(Default::default()));
+static LISTENER: OnceCell<()> = OnceCell::const_new();
+
+type EventChannel = Arc<mpsc::Sender<Event>>;
+
+const WATCH_CHANNEL: &str = "meta_watch";
+// a notification payload must be shorter than 8000 bytes, larger values are
+// sent without the value and the watchers read them from the meta table
+const MAX_EVENT_VALUE_SIZE: usize = 7000;
+
+/// Event sent with `pg_notify` to the nodes watching the meta table.
+#[derive(Debug, Serialize, Deserialize)]
+struct WatchEvent {
+    delete: bool,
+    key: String,
+    value: Option<String>,
+    start_dt: Option<i64>,
+}
+
 fn connect() -> Pool<Postgres> {
     let cfg = config::get_config();
     let db_opts = PgConnectOptions::from_str(&cfg.common.meta_postgres_dsn)
@@ -143,10 +173,14 @@ impl super::Db for PostgresDb {
 
         // event watch
         if need_watch {
-            let cluster_coordinator = super::get_coordinator().await;
-            cluster_coordinator
-                .put(key, Bytes::from(""), true, start_dt)
-                .await?;
+            if is_cluster_coordinator() {
+                notify(false, key, Some(value), start_dt).await?;
+            } else {
+                let cluster_coordinator = super::get_coordinator().await;
+                cluster_coordinator
+                    .put(key, Bytes::from(""), true, start_dt)
+                    .await?;
+            }
         }
 
         Ok(())
@@ -162,13 +196,7 @@ impl super::Db for PostgresDb {
         let (module, key1, key2) = super::parse_key(key);
         let pool = CLIENT.clone();
         let mut tx = pool.begin().await?;
-        let lock_key = format!("get_for_update_{}", key);
-        let lock_id = config::utils::hash::gxhash::new().sum64(&lock_key);
-        let lock_id = if lock_id > i64::MAX as u64 {
-            (lock_id >> 1) as i64
-        } else {
-            lock_id as i64
-        };
+        let lock_id = advisory_lock_id(&format!("get_for_update_{}", key));
         let lock_sql = format!("SELECT pg_advisory_xact_lock({lock_id})");
         if let Err(e) = sqlx::query(&lock_sql).execute(&mut *tx).await {
             if let Err(e) = tx.rollback().await {
@@ -304,10 +332,14 @@ impl super::Db for PostgresDb {
             } else {
                 start_dt
             };
-            let cluster_coordinator = super::get_coordinator().await;
-            cluster_coordinator
-                .put(key, Bytes::from(""), true, start_dt)
-                .await?;
+            if is_cluster_coordinator() {
+                notify(false, key, None, start_dt).await?;
+            } else {
+                let cluster_coordinator = super::get_coordinator().await;
+                cluster_coordinator
+                    .put(key, Bytes::from(""), true, start_dt)
+                    .await?;
+            }
         }
 
         Ok(())
@@ -328,17 +360,27 @@ impl super::Db for PostgresDb {
             } else {
                 vec![key.to_string()]
             };
-            let cluster_coordinator = super::get_coordinator().await;
-            tokio::task::spawn(async move {
-                for key in items {
-                    if let Err(e) = cluster_coordinator
-                        .delete(&key, false, true, start_dt)
-                        .await
-                    {
-                        log::error!("[POSTGRES] send event error: {}", e);
+            if is_cluster_coordinator() {
+                tokio::task::spawn(async move {
+                    for key in items {
+                        if let Err(e) = notify(true, &key, None, start_dt).await {
+                            log::error!("[POSTGRES] send event error: {}", e);
+                        }
                     }
-                }
-            });
+                });
+            } else {
+                let cluster_coordinator = super::get_coordinator().await;
+                tokio::task::spawn(async move {
+                    for key in items {
+                        if let Err(e) = cluster_coordinator
+                            .delete(&key, false, true, start_dt)
+                            .await
+                        {
+                            log::error!("[POSTGRES] send event error: {}", e);
+                        }
+                    }
+                });
+            }
         }
 
         let (module, key1, key2) = super::parse_key(key);
@@ -492,8 +534,21 @@ impl super::Db for PostgresDb {
         Ok(count)
     }
 
-    async fn watch(&self, _prefix: &str) -> Result<Arc<mpsc::Receiver<super::Event>>> {
-        Err(Error::NotImplemented)
+    async fn watch(&self, prefix: &str) -> Result<Arc<mpsc::Receiver<super::Event>>> {
+        if !is_cluster_coordinator() {
+            return Err(Error::NotImplemented);
+        }
+        LISTENER
+            .get_or_init(|| async {
+                tokio::task::spawn(async move { listen().await });
+            })
+            .await;
+        let (tx, rx) = mpsc::channel(1024);
+        WATCHERS
+            .write()
+            .await
+            .insert(prefix.to_string(), Arc::new(tx));
+        Ok(Arc::new(rx))
     }
 
     async fn close(&self) -> Result<()> {
@@ -507,6 +562,211 @@ impl super::Db for PostgresDb {
     }
 }
 
+/// Whether the meta table also coordinates the cluster, instead of etcd or nats.
+fn is_cluster_coordinator() -> bool {
+    let cfg = get_config();
+    !cfg.common.local_mode
+        && MetaStore::from(cfg.common.cluster_coordinator.as_str()) == MetaStore::PostgreSQL
+}
+
+fn advisory_lock_id(key: &str) -> i64 {
+    let lock_id = config::utils::hash::gxhash::new().sum64(key);
+    if lock_id > i64::MAX as u64 {
+        (lock_id >> 1) as i64
+    } else {
+        lock_id as i64
+    }
+}
+
+async fn notify(
+    delete: bool,
+    key: &str,
+    value: Option<Bytes>,
+    start_dt: Option<i64>,
+) -> Result<()> {
+    let value = value
+        .filter(|v| v.len() <= MAX_EVENT_VALUE_SIZE)
+        .map(|v| String::from_utf8_lossy(&v).to_string());
+    let event = WatchEvent {
+        delete,
+        key: key.to_string(),
+        value,
+        start_dt,
+    };
+    let pool = CLIENT.clone();
+    sqlx::query("SELECT pg_notify($1, $2);")
+        .bind(WATCH_CHANNEL)
+        .bind(json::to_string(&event).unwrap())
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
+
+/// Receives the events of all the nodes and sends them to the local watchers.
+async fn listen() {
+    loop {
+        let mut listener = match PgListener::connect_with(&CLIENT).await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("[POSTGRES] connect listener error: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        if let Err(e) = listener.listen(WATCH_CHANNEL).await {
+            log::error!("[POSTGRES] listen channel error: {}", e);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            continue;
+        }
+        log::info!("[POSTGRES] start listening watch events");
+        loop {
+            // the listener reconnects by itself when the connection is lost
+            let notification = match listener.recv().await {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("[POSTGRES] receive watch event error: {}", e);
+                    break;
+                }
+            };
+            let event: WatchEvent = match json::from_str(notification.payload()) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("[POSTGRES] parse watch event error: {}", e);
+                    continue;
+                }
+            };
+            if get_config().common.print_key_event {
+                log::info!("[POSTGRES] watch event: {:?}", event);
+            }
+            let data = EventData {
+                key: event.key,
+                value: event.value.map(Bytes::from),
+                start_dt: event.start_dt,
+            };
+            for (prefix, tx) in WATCHERS.read().await.iter() {
+                if !data.key.starts_with(prefix) {
+                    continue;
+                }
+                let event = if event.delete {
+                    Event::Delete(data.clone())
+                } else {
+                    Event::Put(data.clone())
+                };
+                let tx = tx.clone();
+                tokio::task::spawn(async move {
+                    if let Err(e) = tx.send(event).await {
+                        log::error!("[POSTGRES] send watch event error: {}", e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Extends the lease of a key, the key is deleted once its lease expires.
+/// The meta table has no ttl, so this replaces the etcd leases of the nodes.
+pub async fn keepalive(key: &str, ttl: i64) -> Result<()> {
+    let expire_at = chrono::Utc::now().timestamp_micros() + ttl * 1_000_000;
+    let pool = CLIENT.clone();
+    sqlx::query(
+        r#"INSERT INTO meta_lease (key, expire_at) VALUES ($1, $2) ON CONFLICT (key) DO UPDATE SET expire_at = EXCLUDED.expire_at;"#,
+    )
+    .bind(key)
+    .bind(expire_at)
+    .execute(&pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn revoke(key: &str) -> Result<()> {
+    let pool = CLIENT.clone();
+    sqlx::query(r#"DELETE FROM meta_lease WHERE key = $1;"#)
+        .bind(key)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
+
+/// Deletes the keys whose lease expired, any node can run it.
+pub async fn delete_expired() -> Result<()> {
+    let pool = CLIENT.clone();
+    let keys: Vec<String> =
+        sqlx::query_scalar(r#"DELETE FROM meta_lease WHERE expire_at < $1 RETURNING key;"#)
+            .bind(chrono::Utc::now().timestamp_micros())
+            .fetch_all(&pool)
+            .await?;
+    let db = PostgresDb::new();
+    for key in keys {
+        log::info!("[POSTGRES] lease of key {} expired", key);
+        super::Db::delete(&db, &key, false, true, None).await?;
+    }
+    Ok(())
+}
+
+pub(crate) struct Locker {
+    key: String,
+    lock_id: i64,
+    // advisory locks belong to the session, so the connection is kept until unlock
+    conn: Mutex<Option<PoolConnection<Postgres>>>,
+}
+
+impl Locker {
+    pub(crate) fn new(key: &str) -> Self {
+        let key = format!("/locker{}", key);
+        Self {
+            lock_id: advisory_lock_id(&key),
+            key,
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// lock with timeout, 0 means use default timeout, unit: second
+    pub(crate) async fn lock(&mut self, timeout: u64) -> Result<()> {
+        let timeout = if timeout == 0 {
+            get_config().common.meta_postgres_lock_wait_timeout
+        } else {
+            timeout
+        };
+        let mut conn = CLIENT.acquire().await?;
+        let deadline = std::time::Instant::now() + Duration::from_secs(timeout);
+        loop {
+            let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1);")
+                .bind(self.lock_id)
+                .fetch_one(&mut *conn)
+                .await?;
+            if locked {
+                break;
+            }
+            if std::time::Instant::now() > deadline {
+                return Err(Error::Message(format!(
+                    "postgres lock for key: {}, accquire timeout in {timeout}s",
+                    self.key
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        *self.conn.lock().await = Some(conn);
+        Ok(())
+    }
+
+    pub(crate) async fn unlock(&self) -> Result<()> {
+        let Some(mut conn) = self.conn.lock().await.take() else {
+            return Ok(());
+        };
+        if let Err(err) = sqlx::query("SELECT pg_advisory_unlock($1);")
+            .bind(self.lock_id)
+            .execute(&mut *conn)
+            .await
+        {
+            log::error!("postgres unlock for key: {}, error: {}", self.key, err);
+            // closing the session releases the lock
+            _ = conn.detach();
+            return Err(Error::Message("postgres unlock error".to_string()));
+        }
+        Ok(())
+    }
+}
+
 pub async fn create_table() -> Result<()> {
     let pool = CLIENT.clone();
 
@@ -527,6 +787,19 @@ CREATE TABLE IF NOT EXISTS meta
     .execute(&pool)
     .await?;
 
+    // leases of the keys, only used when postgres coordinates the cluster
+    _ = sqlx::query(
+        r#"
+CREATE TABLE IF NOT EXISTS meta_lease
+(
+    key       VARCHAR(256) not null primary key,
+    expire_at BIGINT not null
+);
+    "#,
+    )
+    .execute(&pool)
+    .await?;
+
     // create start_dt column for old version <= 0.9.2
     let has_start_dt = sqlx::query_scalar::<_,i64>("SELECT count(*) FROM INFORMATION_SCHEMA.COLUMNS WHERE table_name='meta' AND column_name='start_dt';")
             .fetch_one(&pool)
@@ -14,7 +14,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
-    db::{etcd, nats},
+    db::{etcd, nats, postgres},
     errors::Result,
 };
 
@@ -23,6 +23,7 @@ pub struct Locker(LockerStore);
 enum LockerStore {
     Etcd(etcd::Locker),
     Nats(nats::Locker),
+    Postgres(postgres::Locker),
 }
 
 /// lock key in etcd, wait_ttl is 0 means wait forever
@@ -38,6 +39,11 @@ pub async fn lock(key: &str, wait_ttl: u64) -> Result<Option<Locker>> {
             lock.lock(wait_ttl).await?;
             Ok(Some(Locker(LockerStore::Nats(lock))))
         }
+        "postgres" | "postgresql" => {
+            let mut lock = postgres::Locker::new(key);
+            lock.lock(wait_ttl).await?;
+            Ok(Some(Locker(LockerStore::Postgres(lock))))
+        }
         _ => {
             let mut lock = etcd::Locker::new(key);
             lock.lock(wait_ttl).await?;
@@ -52,6 +58,7 @@ pub async fn unlock(locker: &Option<Locker>) -> Result<()> {
         match &locker.0 {
             LockerStore::Etcd(locker) => locker.unlock().await,
             LockerStore::Nats(locker) => locker.unlock().await,
+            LockerStore::Postgres(locker) => locker.unlock().await,
         }
     } else {
         Ok(())
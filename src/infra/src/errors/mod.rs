@@ -15,6 +15,7 @@
 
 use async_nats::{error::Error as NatsError, jetstream};
 use config::utils::json;
+use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 pub mod grpc;
 
@@ -100,6 +101,68 @@ pub enum ErrorCodes {
     SearchCancelQuery(String),
 }
 
+/// What went wrong with a request, for the clients to branch on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// the request is malformed or refers to something invalid
+    Validation,
+    Unauthenticated,
+    PermissionDenied,
+    NotFound,
+    Conflict,
+    RateLimited,
+    Cancelled,
+    Timeout,
+    /// the service or a dependency can't serve the request right now
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCategory {
+    /// Returns the category of the errors answered with an http status.
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 => ErrorCategory::Unauthenticated,
+            403 => ErrorCategory::PermissionDenied,
+            404 => ErrorCategory::NotFound,
+            409 => ErrorCategory::Conflict,
+            408 | 504 => ErrorCategory::Timeout,
+            429 => ErrorCategory::RateLimited,
+            502 | 503 => ErrorCategory::Unavailable,
+            400..=499 => ErrorCategory::Validation,
+            _ => ErrorCategory::Internal,
+        }
+    }
+
+    /// Whether sending the same request again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCategory::RateLimited | ErrorCategory::Timeout | ErrorCategory::Unavailable
+        )
+    }
+
+    /// Returns the stable name of the errors of an http status.
+    pub fn status_name(status: u16) -> &'static str {
+        match status {
+            400 => "bad_request",
+            401 => "unauthorized",
+            403 => "forbidden",
+            404 => "not_found",
+            408 => "request_timeout",
+            409 => "conflict",
+            413 => "payload_too_large",
+            429 => "too_many_requests",
+            502 => "bad_gateway",
+            503 => "service_unavailable",
+            504 => "gateway_timeout",
+            400..=499 => "client_error",
+            _ => "internal_error",
+        }
+    }
+}
+
 impl std::fmt::Display for ErrorCodes {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -127,6 +190,42 @@ impl ErrorCodes {
         }
     }
 
+    /// Returns the stable name of the error, the numeric codes stay for the
+    /// existing clients.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ErrorCodes::ServerInternalError(_) => "server_internal_error",
+            ErrorCodes::SearchSQLNotValid(_) => "search_sql_not_valid",
+            ErrorCodes::SearchStreamNotFound(_) => "search_stream_not_found",
+            ErrorCodes::FullTextSearchFieldNotFound => "full_text_search_field_not_found",
+            ErrorCodes::SearchFieldNotFound(_) => "search_field_not_found",
+            ErrorCodes::SearchFunctionNotDefined(_) => "search_function_not_defined",
+            ErrorCodes::SearchParquetFileNotFound => "search_parquet_file_not_found",
+            ErrorCodes::SearchFieldHasNoCompatibleDataType(_) => {
+                "search_field_has_no_compatible_data_type"
+            }
+            ErrorCodes::SearchSQLExecuteError(_) => "search_sql_execute_error",
+            ErrorCodes::SearchCancelQuery(_) => "search_cancelled",
+        }
+    }
+
+    pub fn get_category(&self) -> ErrorCategory {
+        match self {
+            ErrorCodes::ServerInternalError(_) | ErrorCodes::SearchSQLExecuteError(_) => {
+                ErrorCategory::Internal
+            }
+            ErrorCodes::SearchSQLNotValid(_)
+            | ErrorCodes::FullTextSearchFieldNotFound
+            | ErrorCodes::SearchFieldNotFound(_)
+            | ErrorCodes::SearchFunctionNotDefined(_)
+            | ErrorCodes::SearchFieldHasNoCompatibleDataType(_) => ErrorCategory::Validation,
+            ErrorCodes::SearchStreamNotFound(_) => ErrorCategory::NotFound,
+            // the file was compacted away during the search, the next search reads the new one
+            ErrorCodes::SearchParquetFileNotFound => ErrorCategory::Unavailable,
+            ErrorCodes::SearchCancelQuery(_) => ErrorCategory::Cancelled,
+        }
+    }
+
     pub fn get_message(&self) -> String {
         match self {
             ErrorCodes::ServerInternalError(_) => "Server Internal Error".to_string(),
@@ -256,4 +355,28 @@ mod tests {
             &err.to_string()
         );
     }
+
+    #[test]
+    fn test_error_category() {
+        assert_eq!(ErrorCategory::from_status(400), ErrorCategory::Validation);
+        assert_eq!(ErrorCategory::from_status(422), ErrorCategory::Validation);
+        assert_eq!(ErrorCategory::from_status(404), ErrorCategory::NotFound);
+        assert_eq!(ErrorCategory::from_status(429), ErrorCategory::RateLimited);
+        assert_eq!(ErrorCategory::from_status(500), ErrorCategory::Internal);
+        assert!(ErrorCategory::from_status(503).is_retryable());
+        assert!(!ErrorCategory::from_status(403).is_retryable());
+        assert_eq!(
+            serde_json::to_string(&ErrorCategory::PermissionDenied).unwrap(),
+            "\"permission_denied\""
+        );
+
+        let err = ErrorCodes::SearchStreamNotFound("logs".to_string());
+        assert_eq!(err.get_name(), "search_stream_not_found");
+        assert_eq!(err.get_category(), ErrorCategory::NotFound);
+        assert!(
+            ErrorCodes::SearchParquetFileNotFound
+                .get_category()
+                .is_retryable()
+        );
+    }
 }
@@ -13,7 +13,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod bus;
 pub mod cache;
+pub mod chaos;
 pub mod db;
 pub mod dist_lock;
 pub mod errors;
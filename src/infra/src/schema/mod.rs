@@ -57,6 +57,7 @@ pub async fn get(org_id: &str, stream_name: &str, stream_type: StreamType) -> Re
     let cache_key = key.strip_prefix("/schema/").unwrap();
 
     let r = STREAM_SCHEMAS_LATEST.read().await;
+    crate::cache::record_lookup(crate::cache::SCHEMA, r.contains_key(cache_key));
     if let Some(schema) = r.get(cache_key) {
         return Ok(schema.schema.clone());
     }
@@ -74,6 +75,7 @@ pub async fn get_cache(
     let cache_key = key.strip_prefix("/schema/").unwrap();
 
     let r = STREAM_SCHEMAS_LATEST.read().await;
+    crate::cache::record_lookup(crate::cache::SCHEMA, r.contains_key(cache_key));
     if let Some(schema) = r.get(cache_key) {
         return Ok(schema.clone());
     }
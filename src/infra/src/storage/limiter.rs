@@ -0,0 +1,168 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Limits the concurrent downloads from the object store of a node.
+//!
+//! A download takes a slot of the search first, at most
+//! `ZO_STORAGE_MAX_CONCURRENT_GETS_PER_QUERY`, and then one of the node, at
+//! most `ZO_STORAGE_MAX_CONCURRENT_GETS`. The slots are given in the order they
+//! were asked for, so a search fanning out to many files queues behind its own
+//! share instead of flooding the object store and starving the other searches.
+
+use std::sync::Arc;
+
+use config::{get_config, metrics};
+use hashbrown::HashMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+static NODE_SLOTS: Lazy<Arc<Semaphore>> = Lazy::new(|| {
+    Arc::new(Semaphore::new(
+        get_config().limit.storage_max_concurrent_gets,
+    ))
+});
+
+// the slots of the searches downloading right now, by trace_id
+static QUERY_SLOTS: Lazy<Mutex<HashMap<String, Arc<Semaphore>>>> = Lazy::new(Default::default);
+
+/// Holds the download slots until it is dropped.
+pub struct GetPermit {
+    trace_id: String,
+    query: Option<OwnedSemaphorePermit>,
+    _node: OwnedSemaphorePermit,
+}
+
+impl Drop for GetPermit {
+    fn drop(&mut self) {
+        let Some(query) = self.query.take() else {
+            return;
+        };
+        let mut slots = QUERY_SLOTS.lock();
+        drop(query);
+        // only the map holds the slots once the last download of the search
+        // is done and no other one waits
+        if slots
+            .get(&self.trace_id)
+            .is_some_and(|v| Arc::strong_count(v) == 1)
+        {
+            slots.remove(&self.trace_id);
+        }
+    }
+}
+
+/// Waits for a download slot of the search and of the node. An empty trace_id
+/// only takes a slot of the node.
+pub async fn acquire(trace_id: &str) -> GetPermit {
+    let cfg = get_config();
+    acquire_with(
+        trace_id,
+        &NODE_SLOTS,
+        cfg.limit.storage_max_concurrent_gets_per_query,
+    )
+    .await
+}
+
+// counts a download waiting for its slots, also when the wait is cancelled
+struct Queued;
+
+impl Queued {
+    fn new() -> Self {
+        metrics::STORAGE_GETS_QUEUED.with_label_values(&[]).inc();
+        Self
+    }
+}
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        metrics::STORAGE_GETS_QUEUED.with_label_values(&[]).dec();
+    }
+}
+
+async fn acquire_with(trace_id: &str, node: &Arc<Semaphore>, per_query: usize) -> GetPermit {
+    let queued = Queued::new();
+    let query = if trace_id.is_empty() {
+        None
+    } else {
+        let slots = QUERY_SLOTS
+            .lock()
+            .entry(trace_id.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(per_query.max(1))))
+            .clone();
+        slots.acquire_owned().await.ok()
+    };
+    let node = node
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("storage download semaphore closed");
+    drop(queued);
+    GetPermit {
+        trace_id: trace_id.to_string(),
+        query,
+        _node: node,
+    }
+}
+
+/// Gets the search of an object path of the datafusion object stores, the
+/// paths look like `/{trace_id}/schema={key}/$$/{file}`.
+pub fn trace_id_of_location(location: &str) -> &str {
+    if !location.contains("/$$/") {
+        return "";
+    }
+    let path = location.trim_start_matches('/');
+    match path.find("/schema=") {
+        Some(p) => &path[..p],
+        None => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_with() {
+        let node = Arc::new(Semaphore::new(3));
+        let a1 = acquire_with("trace_a", &node, 2).await;
+        let _a2 = acquire_with("trace_a", &node, 2).await;
+        // the search is at its share, the node has one more slot
+        assert!(
+            tokio::time::timeout(
+                std::time::Duration::from_millis(20),
+                acquire_with("trace_a", &node, 2)
+            )
+            .await
+            .is_err()
+        );
+        let b1 = acquire_with("trace_b", &node, 2).await;
+        assert_eq!(node.available_permits(), 0);
+
+        drop(a1);
+        let _a3 = acquire_with("trace_a", &node, 2).await;
+        drop(b1);
+        assert!(!QUERY_SLOTS.lock().contains_key("trace_b"));
+        assert!(QUERY_SLOTS.lock().contains_key("trace_a"));
+    }
+
+    #[test]
+    fn test_trace_id_of_location() {
+        assert_eq!(
+            trace_id_of_location("/abc-1/schema=123/$$/files/default/logs/a/1.parquet"),
+            "abc-1"
+        );
+        assert_eq!(trace_id_of_location("files/default/logs/a/1.parquet"), "");
+    }
+}
@@ -18,6 +18,8 @@ use futures::{StreamExt, TryStreamExt};
 use object_store::ObjectStore;
 use once_cell::sync::Lazy;
 
+pub mod chaos;
+pub mod limiter;
 pub mod local;
 pub mod remote;
 
@@ -29,7 +31,8 @@ pub static LOCAL_WAL: Lazy<Box<dyn ObjectStore>> = Lazy::new(local_wal);
 
 /// Returns the default object store based on the configuration.
 /// If the local disk storage is enabled, it creates a local object store.
-/// Otherwise, it creates a remote object store.
+/// Otherwise, it creates a remote object store. With ZO_CHAOS_ENABLED the
+/// store is wrapped to inject faults into its calls.
 ///
 /// # Examples
 ///
@@ -39,12 +42,18 @@ pub static LOCAL_WAL: Lazy<Box<dyn ObjectStore>> = Lazy::new(local_wal);
 /// let object_store = default();
 /// ```
 fn default() -> Box<dyn ObjectStore> {
-    if is_local_disk_storage() {
+    let store: Box<dyn ObjectStore> = if is_local_disk_storage() {
         std::fs::create_dir_all(&get_config().common.data_stream_dir)
             .expect("create stream data dir success");
         Box::<local::Local>::default()
     } else {
         Box::<remote::Remote>::default()
+    };
+    if crate::chaos::enabled(crate::chaos::STORAGE) {
+        log::warn!("[CHAOS] injecting faults into the object store");
+        Box::new(chaos::ChaosStore::new(store))
+    } else {
+        store
     }
 }
 
@@ -70,12 +79,41 @@ pub async fn list(prefix: &str) -> Result<Vec<String>, anyhow::Error> {
     Ok(files)
 }
 
+/// List the objects under the prefix last modified at or after `since`, in
+/// microseconds.
+pub async fn list_modified_since(prefix: &str, since: i64) -> Result<Vec<String>, anyhow::Error> {
+    let files = DEFAULT
+        .list(Some(&prefix.into()))
+        .try_filter(|meta| futures::future::ready(meta.last_modified.timestamp_micros() >= since))
+        .map_ok(|meta| meta.location.to_string())
+        .try_collect::<Vec<String>>()
+        .await?;
+    Ok(files)
+}
+
+/// List the objects under the prefix with their size in bytes.
+pub async fn list_with_size(prefix: &str) -> Result<Vec<(String, usize)>, anyhow::Error> {
+    let files = DEFAULT
+        .list(Some(&prefix.into()))
+        .map_ok(|meta| (meta.location.to_string(), meta.size))
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(files)
+}
+
 pub async fn get(file: &str) -> Result<bytes::Bytes, anyhow::Error> {
     let data = DEFAULT.get(&file.into()).await?;
     let data = data.bytes().await?;
     Ok(data)
 }
 
+pub async fn get_range(
+    file: &str,
+    range: std::ops::Range<usize>,
+) -> Result<bytes::Bytes, anyhow::Error> {
+    Ok(DEFAULT.get_range(&file.into(), range).await?)
+}
+
 pub async fn put(file: &str, data: bytes::Bytes) -> Result<(), anyhow::Error> {
     DEFAULT.put(&file.into(), data).await?;
     Ok(())
@@ -116,6 +154,14 @@ pub async fn del(files: &[&str]) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Checks that the object store answers, a missing object is an answer.
+pub async fn check() -> Result<(), anyhow::Error> {
+    match DEFAULT.head(&format_key("healthz", true).into()).await {
+        Ok(_) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
 pub fn format_key(key: &str, with_prefix: bool) -> String {
     let cfg = get_config();
     if !is_local_disk_storage()
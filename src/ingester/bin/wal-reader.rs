@@ -30,12 +30,8 @@ fn main() -> Result<()> {
     loop {
         let entry = match reader.read_entry() {
             Ok(entry) => entry,
-            Err(wal::Error::UnableToReadData { source }) => {
-                println!("Unable to read entry from: {}, skip the entry", source);
-                continue;
-            }
             Err(e) => {
-                println!("Error: {}", e);
+                println!("Error at offset {}: {}", reader.offset(), e);
                 break;
             }
         };
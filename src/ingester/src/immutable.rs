@@ -18,6 +18,7 @@ use std::{path::PathBuf, sync::Arc};
 use arrow_schema::Schema;
 use config::metrics;
 use futures::future::join_all;
+use indexmap::IndexMap;
 use once_cell::sync::Lazy;
 use snafu::ResultExt;
 use tokio::{fs, sync::Semaphore, task};
@@ -104,29 +105,31 @@ impl Immutable {
 }
 
 pub(crate) async fn persist() -> Result<()> {
+    // the wal files of a writer are persisted one after another in the order they
+    // were rotated, so the parquet files of a stream are generated in order, while
+    // different writers are persisted in parallel
     let r = IMMUTABLES.read().await;
-    let n = r.len();
-    let mut paths = Vec::with_capacity(n);
-    for item in r.iter() {
-        if paths.len() >= n {
-            break;
-        }
-        paths.push(item.0.clone());
+    let mut writers: IndexMap<(usize, WriterKey), Vec<PathBuf>> = IndexMap::new();
+    for (path, immutable) in r.iter() {
+        writers
+            .entry((immutable.thread_id, immutable.key.clone()))
+            .or_default()
+            .push(path.clone());
     }
     drop(r);
 
-    let mut tasks = Vec::with_capacity(paths.len());
+    let mut tasks = Vec::with_capacity(writers.len());
     let semaphore = Arc::new(Semaphore::new(
         config::get_config().limit.file_move_thread_num,
     ));
-    for path in paths {
+    for (_, paths) in writers {
         let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let task: task::JoinHandle<Result<Option<(PathBuf, PersistStat)>>> =
-            task::spawn(async move {
+        let task: task::JoinHandle<Vec<(PathBuf, PersistStat)>> = task::spawn(async move {
+            let mut done = Vec::with_capacity(paths.len());
+            for path in paths {
                 let r = IMMUTABLES.read().await;
                 let Some(immutable) = r.get(&path) else {
-                    drop(permit);
-                    return Ok(None);
+                    continue;
                 };
                 log::info!(
                     "[INGESTER:WAL] start persist file: {}",
@@ -135,43 +138,45 @@ pub(crate) async fn persist() -> Result<()> {
                 // persist entry to local disk
                 let immutable = immutable.clone();
                 drop(r);
-                let ret = immutable.persist(&path).await;
-                drop(permit);
-                ret.map(|stat| Some((path, stat)))
-            });
+                match immutable.persist(&path).await {
+                    Ok(stat) => done.push((path, stat)),
+                    Err(e) => {
+                        // keep the newer files for the next run to preserve the order
+                        log::error!("[INGESTER:WAL] persist error: {}", e);
+                        break;
+                    }
+                }
+            }
+            drop(permit);
+            done
+        });
         tasks.push(task);
     }
 
     // remove entry from IMMUTABLES
     let tasks = join_all(tasks).await;
     for task in tasks {
-        match task.context(TokioJoinSnafu {})? {
-            Err(e) => {
-                log::error!("[INGESTER:WAL] persist error: {}", e);
-            }
-            Ok(None) => {}
-            Ok(Some((path, stat))) => {
-                log::info!(
-                    "[INGESTER:WAL] done  persist file: {}, json_size: {}, arrow_size: {}, file_num: {} batch_num: {}",
-                    path.to_string_lossy(),
-                    stat.json_size,
-                    stat.arrow_size,
-                    stat.file_num,
-                    stat.batch_num,
-                );
-                // remove entry
-                let mut rw = IMMUTABLES.write().await;
-                rw.remove(&path);
-                drop(rw);
-                // update metrics
-                metrics::INGEST_MEMTABLE_BYTES
-                    .with_label_values(&[])
-                    .sub(stat.json_size);
-                metrics::INGEST_MEMTABLE_ARROW_BYTES
-                    .with_label_values(&[])
-                    .sub(stat.arrow_size as i64);
-                metrics::INGEST_MEMTABLE_FILES.with_label_values(&[]).dec();
-            }
+        for (path, stat) in task.context(TokioJoinSnafu {})? {
+            log::info!(
+                "[INGESTER:WAL] done  persist file: {}, json_size: {}, arrow_size: {}, file_num: {} batch_num: {}",
+                path.to_string_lossy(),
+                stat.json_size,
+                stat.arrow_size,
+                stat.file_num,
+                stat.batch_num,
+            );
+            // remove entry
+            let mut rw = IMMUTABLES.write().await;
+            rw.remove(&path);
+            drop(rw);
+            // update metrics
+            metrics::INGEST_MEMTABLE_BYTES
+                .with_label_values(&[])
+                .sub(stat.json_size);
+            metrics::INGEST_MEMTABLE_ARROW_BYTES
+                .with_label_values(&[])
+                .sub(stat.arrow_size as i64);
+            metrics::INGEST_MEMTABLE_FILES.with_label_values(&[]).dec();
         }
     }
     let mut rw = IMMUTABLES.write().await;
@@ -19,6 +19,7 @@ mod immutable;
 mod memtable;
 mod partition;
 mod rwmap;
+mod spill;
 mod stream;
 mod wal;
 mod writer;
@@ -33,6 +34,13 @@ pub use writer::{check_memtable_size, flush_all, get_writer, read_from_memtable,
 pub static WAL_PARQUET_METADATA: Lazy<RwAHashMap<String, config::meta::stream::FileMeta>> =
     Lazy::new(Default::default);
 
+/// Moves all memtables to immutables and persists them as parquet files in
+/// the wal, used to drain the node before shutdown.
+pub async fn flush_and_persist() -> errors::Result<()> {
+    writer::flush_all().await?;
+    immutable::persist().await
+}
+
 pub async fn init() -> errors::Result<()> {
     // check uncompleted parquet files, need delete those files
     wal::check_uncompleted_parquet_files().await?;
@@ -40,6 +48,9 @@ pub async fn init() -> errors::Result<()> {
     // replay wal files to create immutable
     wal::replay_wal_files().await?;
 
+    // count the spill files left by the last run, the persist job writes them back
+    spill::init().await?;
+
     // start a job to dump immutable data to disk
     tokio::task::spawn(async move {
         loop {
@@ -51,6 +62,10 @@ pub async fn init() -> errors::Result<()> {
             if let Err(e) = immutable::persist().await {
                 log::error!("immutable persist error: {}", e);
             }
+            // write the spilled data back into the memtables
+            if let Err(e) = spill::drain().await {
+                log::error!("spill drain error: {}", e);
+            }
             // shrink metadata cache
             WAL_PARQUET_METADATA.write().await.shrink_to_fit();
         }
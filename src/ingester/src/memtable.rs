@@ -22,19 +22,23 @@ use std::{
 };
 
 use arrow_schema::Schema;
-use config::metrics;
+use chrono::Utc;
+use config::{meta::stream::StreamType, metrics};
+use futures::future::join_all;
 
 use crate::{
     entry::{Entry, PersistStat, RecordBatchEntry},
     errors::Result,
     rwmap::RwMap,
     stream::Stream,
+    writer::WriterKey,
 };
 
 pub(crate) struct MemTable {
     streams: RwMap<Arc<str>, Arc<Stream>>, // key: schema name, val: stream
     json_bytes_written: AtomicU64,
     arrow_bytes_written: AtomicU64,
+    records_written: AtomicU64,
 }
 
 impl MemTable {
@@ -44,29 +48,52 @@ impl MemTable {
             streams: RwMap::default(),
             json_bytes_written: AtomicU64::new(0),
             arrow_bytes_written: AtomicU64::new(0),
+            records_written: AtomicU64::new(0),
         }
     }
 
-    pub(crate) async fn write(&self, schema: Arc<Schema>, entry: Entry) -> Result<()> {
+    pub(crate) async fn write(
+        &self,
+        key: &WriterKey,
+        schema: Arc<Schema>,
+        entry: Entry,
+    ) -> Result<()> {
         let partitions = self.streams.read().await.get(&entry.stream).cloned();
         let partitions = match partitions {
             Some(v) => v,
             None => {
+                let flush_policy = infra::schema::get_settings(
+                    &key.org_id,
+                    &entry.stream,
+                    StreamType::from(key.stream_type.as_ref()),
+                )
+                .await
+                .and_then(|settings| settings.flush_policy);
                 let mut w = self.streams.write().await;
                 w.entry(entry.stream.clone())
-                    .or_insert_with(|| Arc::new(Stream::new()))
+                    .or_insert_with(|| Arc::new(Stream::new(flush_policy)))
                     .clone()
             }
         };
         let json_size = entry.data_size;
+        let records = entry.data.len();
         let arrow_size = partitions.write(schema, entry).await?;
         self.json_bytes_written
             .fetch_add(json_size as u64, Ordering::SeqCst);
         self.arrow_bytes_written
             .fetch_add(arrow_size as u64, Ordering::SeqCst);
+        self.records_written
+            .fetch_add(records as u64, Ordering::SeqCst);
         Ok(())
     }
 
+    /// Check if any stream is over its own flush policy
+    pub(crate) async fn is_over_flush_policy(&self) -> bool {
+        let now = Utc::now().timestamp_micros();
+        let r = self.streams.read().await;
+        r.values().any(|stream| stream.is_over_flush_policy(now))
+    }
+
     pub(crate) async fn read(
         &self,
         stream_name: &str,
@@ -85,13 +112,22 @@ impl MemTable {
         org_id: &str,
         stream_type: &str,
     ) -> Result<(usize, Vec<(PathBuf, PersistStat)>)> {
+        // all the streams are persisted at the same time, the partitions are
+        // converted by a bounded pool of tasks
+        let r = self.streams.read().await;
+        let tasks = r
+            .iter()
+            .map(|(stream_name, stream)| {
+                stream.persist(thread_id, org_id, stream_type, stream_name)
+            })
+            .collect::<Vec<_>>();
+        let results = join_all(tasks).await;
+        drop(r);
+
         let mut schema_size = 0;
         let mut paths = Vec::new();
-        let r = self.streams.read().await;
-        for (stream_name, stream) in r.iter() {
-            let (part_schema_size, partitions) = stream
-                .persist(thread_id, org_id, stream_type, stream_name)
-                .await?;
+        for ret in results {
+            let (part_schema_size, partitions) = ret?;
             schema_size += part_schema_size;
             paths.extend(partitions);
         }
@@ -105,4 +141,9 @@ impl MemTable {
             self.arrow_bytes_written.load(Ordering::SeqCst) as usize,
         )
     }
+
+    // Return the number of records written
+    pub(crate) fn records(&self) -> usize {
+        self.records_written.load(Ordering::SeqCst) as usize
+    }
 }
@@ -0,0 +1,264 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Spill of the ingested data to disk while the memtables are over
+//! `ZO_INGESTER_SPILL_THRESHOLD` percent of `ZO_MEM_TABLE_MAX_SIZE`.
+//!
+//! The entries are appended to compressed wal segments under
+//! `{data_wal_dir}/spill/{org_id}/{stream_type}` and written back into the
+//! memtables, oldest segment first, once the memory is released. A segment is
+//! deleted after all its entries are written into the wal of a writer, so a
+//! crash in between writes the segment again on the next start.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use chrono::Utc;
+use config::{
+    get_config, metrics,
+    utils::{schema::infer_json_schema_from_values, schema_ext::SchemaExt},
+};
+use hashbrown::HashMap;
+use once_cell::sync::Lazy;
+use snafu::ResultExt;
+use tokio::sync::Mutex;
+use wal::Writer as WalWriter;
+
+use crate::{entry::Entry, errors::*, writer::WriterKey};
+
+// compressed bytes of a spill segment, small enough that a segment written
+// back at once doesn't push the memtables far over the threshold
+const SEGMENT_SIZE: usize = 32 * 1024 * 1024;
+
+static WRITERS: Lazy<Mutex<HashMap<WriterKey, WalWriter>>> = Lazy::new(Default::default);
+
+static NEXT_SEQ: Lazy<AtomicU64> =
+    Lazy::new(|| AtomicU64::new(Utc::now().timestamp_micros() as u64));
+
+fn spill_dir() -> PathBuf {
+    PathBuf::from(&get_config().common.data_wal_dir).join("spill")
+}
+
+fn is_over_threshold(used: i64, max: usize, threshold: usize) -> bool {
+    used.max(0) as u128 * 100 >= max as u128 * threshold as u128
+}
+
+/// Returns true if the ingested data has to be spilled to disk instead of
+/// being written into the memtables.
+pub(crate) fn should_spill() -> bool {
+    let cfg = get_config();
+    cfg.limit.ingester_spill_enabled
+        && is_over_threshold(
+            metrics::INGEST_MEMTABLE_ARROW_BYTES
+                .with_label_values(&[])
+                .get(),
+            cfg.limit.mem_table_max_size,
+            cfg.limit.ingester_spill_threshold,
+        )
+}
+
+/// Returns true if the spill takes more data.
+pub(crate) fn has_capacity() -> bool {
+    let cfg = get_config();
+    cfg.limit.ingester_spill_enabled
+        && (cfg.limit.ingester_spill_max_size == 0
+            || metrics::INGEST_SPILL_BYTES.with_label_values(&[]).get()
+                < cfg.limit.ingester_spill_max_size as i64)
+}
+
+/// Appends an entry to the spill segment of the writer.
+pub(crate) async fn write(key: &WriterKey, data: &[u8]) -> Result<()> {
+    let mut writers = WRITERS.lock().await;
+    if writers
+        .get(key)
+        .is_some_and(|w| w.size().0 + data.len() > SEGMENT_SIZE)
+    {
+        let w = writers.remove(key).unwrap();
+        w.sync().context(WalSnafu)?;
+    }
+    if !writers.contains_key(key) {
+        let seq = NEXT_SEQ.fetch_add(1, Ordering::SeqCst);
+        let w =
+            WalWriter::new(spill_dir(), &key.org_id, &key.stream_type, seq, 0).context(WalSnafu)?;
+        log::info!(
+            "[INGESTER:SPILL] create file: {}",
+            w.path().display().to_string()
+        );
+        metrics::INGEST_SPILL_FILES.with_label_values(&[]).inc();
+        metrics::INGEST_SPILL_BYTES
+            .with_label_values(&[])
+            .add(w.size().0 as i64);
+        writers.insert(key.clone(), w);
+    }
+    let w = writers.get_mut(key).unwrap();
+    let written = w.size().0;
+    w.write(data, false).context(WalSnafu)?;
+    metrics::INGEST_SPILL_BYTES
+        .with_label_values(&[])
+        .add((w.size().0 - written) as i64);
+    Ok(())
+}
+
+/// Counts the spill segments left by the last run.
+pub(crate) async fn init() -> Result<()> {
+    let dir = spill_dir();
+    fs::create_dir_all(&dir).context(OpenDirSnafu { path: dir.clone() })?;
+    let files = super::wal::wal_scan_files(&dir, "wal")
+        .await
+        .unwrap_or_default();
+    let size = files
+        .iter()
+        .map(|f| fs::metadata(f).map(|m| m.len()).unwrap_or_default())
+        .sum::<u64>();
+    if !files.is_empty() {
+        log::warn!(
+            "[INGESTER:SPILL] found {} spill files, {} bytes",
+            files.len(),
+            size
+        );
+    }
+    metrics::INGEST_SPILL_FILES
+        .with_label_values(&[])
+        .set(files.len() as i64);
+    metrics::INGEST_SPILL_BYTES
+        .with_label_values(&[])
+        .set(size as i64);
+    Ok(())
+}
+
+/// Writes the spilled entries back into the memtables while they are under
+/// the spill threshold.
+pub(crate) async fn drain() -> Result<()> {
+    if should_spill() {
+        return Ok(());
+    }
+    // close the open segments, the new data goes into the memtables again
+    let mut writers = WRITERS.lock().await;
+    for (_, w) in writers.drain() {
+        w.sync().context(WalSnafu)?;
+    }
+    drop(writers);
+
+    let dir = spill_dir();
+    let mut files = super::wal::wal_scan_files(&dir, "wal")
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|f| Some((segment_seq(&f)?, f)))
+        .collect::<Vec<_>>();
+    files.sort_by_key(|(seq, _)| *seq);
+    for (_, file) in files {
+        if should_spill() {
+            break;
+        }
+        // a segment opened since the ones above were closed
+        if WRITERS.lock().await.values().any(|w| w.path() == &file) {
+            continue;
+        }
+        drain_file(&dir, &file).await?;
+    }
+    Ok(())
+}
+
+async fn drain_file(dir: &Path, file: &PathBuf) -> Result<()> {
+    let file_str = file
+        .strip_prefix(dir)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .replace('\\', "/");
+    let columns = file_str.split('/').collect::<Vec<_>>();
+    if columns.len() != 3 {
+        log::error!("[INGESTER:SPILL] invalid spill file: {}, skip", file_str);
+        return Ok(());
+    }
+    let (org_id, stream_type) = (columns[0], columns[1]);
+    let writer = super::get_writer(0, org_id, stream_type).await;
+    let mut reader = wal::Reader::from_path(file).context(WalSnafu)?;
+    let mut records = 0;
+    loop {
+        let data = match reader.read_entry() {
+            Ok(Some(v)) => v,
+            Ok(None) => break,
+            Err(e) => {
+                // a torn write of an unclean shutdown, the rest can't be framed
+                log::error!(
+                    "[INGESTER:SPILL] read file: {} at offset {} error: {}, skip the rest",
+                    file_str,
+                    reader.offset(),
+                    e
+                );
+                break;
+            }
+        };
+        let mut entry = match Entry::from_bytes(&data) {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("[INGESTER:SPILL] read entry error: {}, skip the entry", e);
+                continue;
+            }
+        };
+        records += entry.data.len();
+        let schema = infer_json_schema_from_values(entry.data.iter().cloned(), stream_type)
+            .context(InferJsonSchemaSnafu)?;
+        let schema = Arc::new(schema);
+        entry.schema_key = schema.hash_key().into();
+        writer.write_memtable(schema, entry, false).await?;
+    }
+
+    let size = fs::metadata(file).map(|m| m.len()).unwrap_or_default();
+    fs::remove_file(file).context(DeleteFileSnafu { path: file })?;
+    metrics::INGEST_SPILL_FILES.with_label_values(&[]).dec();
+    metrics::INGEST_SPILL_BYTES
+        .with_label_values(&[])
+        .sub(size as i64);
+    log::info!(
+        "[INGESTER:SPILL] done write back file: {}, records: {}",
+        file_str,
+        records
+    );
+    Ok(())
+}
+
+fn segment_seq(file: &Path) -> Option<u64> {
+    file.file_stem()?.to_str()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_over_threshold() {
+        assert!(!is_over_threshold(79, 100, 80));
+        assert!(is_over_threshold(80, 100, 80));
+        assert!(!is_over_threshold(-1, 100, 80));
+        assert!(!is_over_threshold(i64::MAX, usize::MAX, 100));
+        assert_eq!(
+            segment_seq(Path::new("/data/spill/default/logs/1712345678.wal")),
+            Some(1712345678)
+        );
+        assert_eq!(
+            segment_seq(Path::new("/data/spill/default/logs/x.wal")),
+            None
+        );
+    }
+}
@@ -13,10 +13,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use arrow_schema::Schema;
-use config::utils::schema_ext::SchemaExt;
+use chrono::Utc;
+use config::{meta::stream::FlushPolicy, utils::schema_ext::SchemaExt};
+use futures::future::join_all;
+use once_cell::sync::Lazy;
+use snafu::ResultExt;
+use tokio::{sync::Semaphore, task};
 
 use crate::{
     entry::{Entry, PersistStat, RecordBatchEntry},
@@ -25,17 +36,42 @@ use crate::{
     rwmap::RwMap,
 };
 
+// bounds the number of partitions converted to parquet at the same time, shared
+// by all the memtables being persisted
+static PERSIST_SEMAPHORE: Lazy<Semaphore> =
+    Lazy::new(|| Semaphore::new(config::get_config().limit.mem_persist_thread_num));
+
 pub(crate) struct Stream {
     partitions: RwMap<Arc<str>, Arc<Partition>>, // key: schema hash, val: partitions
+    flush_policy: Option<FlushPolicy>,
+    created_at: i64,
+    json_bytes_written: AtomicUsize,
+    records_written: AtomicUsize,
 }
 
 impl Stream {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(flush_policy: Option<FlushPolicy>) -> Self {
         Self {
             partitions: RwMap::default(),
+            flush_policy,
+            created_at: Utc::now().timestamp_micros(),
+            json_bytes_written: AtomicUsize::new(0),
+            records_written: AtomicUsize::new(0),
         }
     }
 
+    /// Check if the buffered data is over the flush policy of the stream
+    pub(crate) fn is_over_flush_policy(&self, now: i64) -> bool {
+        let Some(policy) = self.flush_policy.as_ref() else {
+            return false;
+        };
+        policy.is_exceeded(
+            self.json_bytes_written.load(Ordering::Relaxed),
+            self.records_written.load(Ordering::Relaxed),
+            now - self.created_at,
+        )
+    }
+
     pub(crate) async fn write(&self, schema: Arc<Schema>, entry: Entry) -> Result<usize> {
         let mut arrow_size = 0;
         let partition = self.partitions.read().await.get(&entry.stream).cloned();
@@ -49,7 +85,11 @@ impl Stream {
                     .clone()
             }
         };
+        let (json_size, records) = (entry.data_size, entry.data.len());
         arrow_size += partition.write(entry).await?;
+        self.json_bytes_written
+            .fetch_add(json_size, Ordering::Relaxed);
+        self.records_written.fetch_add(records, Ordering::Relaxed);
         Ok(arrow_size)
     }
 
@@ -73,13 +113,32 @@ impl Stream {
         stream_type: &str,
         stream_name: &str,
     ) -> Result<(usize, Vec<(PathBuf, PersistStat)>)> {
+        let r = self.partitions.read().await;
+        let partitions = r.values().cloned().collect::<Vec<_>>();
+        drop(r);
+
+        let org_id: Arc<str> = org_id.into();
+        let stream_type: Arc<str> = stream_type.into();
+        let stream_name: Arc<str> = stream_name.into();
+        let mut tasks = Vec::with_capacity(partitions.len());
+        for partition in partitions {
+            let org_id = org_id.clone();
+            let stream_type = stream_type.clone();
+            let stream_name = stream_name.clone();
+            let task: task::JoinHandle<Result<(usize, Vec<(PathBuf, PersistStat)>)>> =
+                task::spawn(async move {
+                    let _permit = PERSIST_SEMAPHORE.acquire().await.unwrap();
+                    partition
+                        .persist(thread_id, &org_id, &stream_type, &stream_name)
+                        .await
+                });
+            tasks.push(task);
+        }
+
         let mut schema_size = 0;
         let mut paths = Vec::new();
-        let r = self.partitions.read().await;
-        for (_, partition) in r.iter() {
-            let (part_schema_size, partitions) = partition
-                .persist(thread_id, org_id, stream_type, stream_name)
-                .await?;
+        for task in join_all(tasks).await {
+            let (part_schema_size, partitions) = task.context(TokioJoinSnafu {})??;
             schema_size += part_schema_size;
             paths.extend(partitions);
         }
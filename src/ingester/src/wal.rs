@@ -148,25 +148,32 @@ pub(crate) async fn replay_wal_files() -> Result<()> {
             }
             let entry = match reader.read_entry() {
                 Ok(entry) => entry,
-                Err(wal::Error::UnableToReadData { source }) => {
-                    log::error!("Unable to read entry from: {}, skip the entry", source);
-                    continue;
-                }
-                Err(wal::Error::LengthMismatch { expected, actual }) => {
-                    log::error!(
-                        "Unable to read entry: Length mismatch: expected {}, actual {}, skip the entry",
-                        expected,
-                        actual
-                    );
-                    continue;
-                }
-                Err(wal::Error::ChecksumMismatch { expected, actual }) => {
-                    log::error!(
-                        "Unable to read entry: Checksum mismatch: expected {}, actual {}, skip the entry",
-                        expected,
-                        actual
-                    );
-                    continue;
+                Err(
+                    e @ (wal::Error::UnableToReadData { .. }
+                    | wal::Error::UnableToReadLength { .. }
+                    | wal::Error::LengthMismatch { .. }
+                    | wal::Error::ChecksumMismatch { .. }),
+                ) => {
+                    // the entries after a corrupt one can't be framed, they
+                    // are the torn writes of an unclean shutdown
+                    let offset = reader.offset();
+                    match wal::truncate(wal_file, offset) {
+                        Ok(dropped) => log::error!(
+                            "replay wal file: {:?}, corrupt entry at offset {}: {}, truncated the file, dropped {} bytes",
+                            wal_file,
+                            offset,
+                            e,
+                            dropped
+                        ),
+                        Err(te) => log::error!(
+                            "replay wal file: {:?}, corrupt entry at offset {}: {}, failed to truncate the file: {}",
+                            wal_file,
+                            offset,
+                            e,
+                            te
+                        ),
+                    }
+                    break;
                 }
                 Err(e) => {
                     return Err(Error::WalError { source: e });
@@ -192,7 +199,7 @@ pub(crate) async fn replay_wal_files() -> Result<()> {
                     .context(InferJsonSchemaSnafu)?;
             let infer_schema = Arc::new(infer_schema);
             entry.schema_key = infer_schema.hash_key().into();
-            memtable.write(infer_schema, entry).await?;
+            memtable.write(&key, infer_schema, entry).await?;
         }
         log::warn!(
             "replay wal file: {:?}, entries: {}, records: {}",
@@ -210,7 +217,10 @@ pub(crate) async fn replay_wal_files() -> Result<()> {
     Ok(())
 }
 
-async fn wal_scan_files(root_dir: impl Into<PathBuf>, ext: &str) -> Result<Vec<PathBuf>> {
+pub(crate) async fn wal_scan_files(
+    root_dir: impl Into<PathBuf>,
+    ext: &str,
+) -> Result<Vec<PathBuf>> {
     Ok(WalkDir::new(root_dir.into())
         .filter_map(|entry| async move {
             let entry = entry.ok()?;
@@ -36,6 +36,7 @@ use crate::{
     immutable::IMMUTABLES,
     memtable::MemTable,
     rwmap::RwMap,
+    spill,
 };
 
 static WRITERS: Lazy<Vec<RwMap<WriterKey, Arc<Writer>>>> = Lazy::new(|| {
@@ -61,12 +62,13 @@ pub struct Writer {
     created_at: AtomicI64,
 }
 
-// check total memory size
+// check total memory size, the ingestion goes on while the spill takes more data
 pub fn check_memtable_size() -> Result<()> {
     let total_mem_size = metrics::INGEST_MEMTABLE_ARROW_BYTES
         .with_label_values(&[])
         .get();
-    if total_mem_size >= get_config().limit.mem_table_max_size as i64 {
+    let is_full = total_mem_size >= get_config().limit.mem_table_max_size as i64;
+    if (is_full || spill::should_spill()) && !spill::has_capacity() {
         Err(Error::MemoryTableOverflowError {})
     } else {
         Ok(())
@@ -177,6 +179,20 @@ impl Writer {
         schema: Arc<Schema>,
         mut entry: Entry,
         check_ttl: bool,
+    ) -> Result<()> {
+        if !check_ttl && !entry.data.is_empty() && spill::should_spill() {
+            let entry_bytes = entry.into_bytes()?;
+            return spill::write(&self.key, &entry_bytes).await;
+        }
+        self.write_memtable(schema, entry, check_ttl).await
+    }
+
+    // write into the wal and the memtable, regardless of the memory used
+    pub(crate) async fn write_memtable(
+        &self,
+        schema: Arc<Schema>,
+        mut entry: Entry,
+        check_ttl: bool,
     ) -> Result<()> {
         if entry.data.is_empty() && !check_ttl {
             return Ok(());
@@ -187,9 +203,12 @@ impl Writer {
             Vec::new()
         };
         let mut wal = self.wal.lock().await;
-        if self.check_wal_threshold(wal.size(), entry_bytes.len())
-            || self.check_mem_threshold(self.memtable.read().await.size(), entry.data_size)
-        {
+        let mem = self.memtable.read().await;
+        let need_rotate = self.check_wal_threshold(wal.size(), entry_bytes.len())
+            || self.check_mem_threshold(mem.size(), mem.records(), entry.data_size)
+            || mem.is_over_flush_policy().await;
+        drop(mem);
+        if need_rotate {
             let cfg = get_config();
             // sync wal before rotation
             wal.sync().context(WalSnafu)?;
@@ -244,7 +263,7 @@ impl Writer {
             drop(wal);
             // write into memtable
             let mem = self.memtable.write().await;
-            mem.write(schema, entry).await?;
+            mem.write(&self.key, schema, entry).await?;
             drop(mem);
         }
 
@@ -273,6 +292,14 @@ impl Writer {
         Ok(())
     }
 
+    pub fn org_id(&self) -> &str {
+        &self.key.org_id
+    }
+
+    pub fn stream_type(&self) -> &str {
+        &self.key.stream_type
+    }
+
     pub async fn sync(&self) -> Result<()> {
         let wal = self.wal.lock().await;
         wal.sync().context(WalSnafu)
@@ -301,13 +328,20 @@ impl Writer {
                     <= Utc::now().timestamp_micros())
     }
 
-    /// Check if the memtable size is over the threshold
-    fn check_mem_threshold(&self, written_size: (usize, usize), data_size: usize) -> bool {
+    /// Check if the memtable size or records is over the threshold
+    fn check_mem_threshold(
+        &self,
+        written_size: (usize, usize),
+        written_records: usize,
+        data_size: usize,
+    ) -> bool {
         let cfg = get_config();
         let (json_size, arrow_size) = written_size;
         json_size > 0
             && (json_size + data_size > cfg.limit.max_file_size_in_memory
-                || arrow_size + data_size > cfg.limit.max_file_size_in_memory)
+                || arrow_size + data_size > cfg.limit.max_file_size_in_memory
+                || (cfg.limit.max_file_records_in_memory > 0
+                    && written_records >= cfg.limit.max_file_records_in_memory))
     }
 }
 
@@ -25,9 +25,12 @@ use tokio::{
     time,
 };
 
-use crate::service::compact::{
-    self,
-    merge::{MergeBatch, MergeSender},
+use crate::{
+    common::infra::cluster::leader,
+    service::compact::{
+        self,
+        merge::{MergeBatch, MergeSender},
+    },
 };
 
 pub async fn run() -> Result<(), anyhow::Error> {
@@ -91,6 +94,8 @@ pub async fn run() -> Result<(), anyhow::Error> {
         });
     }
 
+    leader::campaign(leader::COMPACTOR);
+    leader::campaign(leader::RETENTION);
     tokio::task::spawn(async move { run_merge(tx).await });
     tokio::task::spawn(async move { run_retention().await });
     tokio::task::spawn(async move { run_delay_deletion().await });
@@ -103,6 +108,9 @@ pub async fn run() -> Result<(), anyhow::Error> {
 async fn run_merge(tx: mpsc::Sender<(MergeSender, MergeBatch)>) -> Result<(), anyhow::Error> {
     loop {
         time::sleep(time::Duration::from_secs(get_config().compact.interval)).await;
+        if !leader::is_leader(leader::COMPACTOR) {
+            continue; // standby
+        }
         let locker = compact::QUEUE_LOCKER.clone();
         let locker = locker.lock().await;
         log::debug!("[COMPACTOR] Running data merge");
@@ -118,6 +126,9 @@ async fn run_merge(tx: mpsc::Sender<(MergeSender, MergeBatch)>) -> Result<(), an
 async fn run_retention() -> Result<(), anyhow::Error> {
     loop {
         time::sleep(time::Duration::from_secs(get_config().compact.interval + 1)).await;
+        if !leader::is_leader(leader::RETENTION) {
+            continue; // standby
+        }
         let locker = compact::QUEUE_LOCKER.clone();
         let locker = locker.lock().await;
         log::debug!("[COMPACTOR] Running data retention");
@@ -133,6 +144,9 @@ async fn run_retention() -> Result<(), anyhow::Error> {
 async fn run_delay_deletion() -> Result<(), anyhow::Error> {
     loop {
         time::sleep(time::Duration::from_secs(get_config().compact.interval + 2)).await;
+        if !leader::is_leader(leader::RETENTION) {
+            continue; // standby
+        }
         let locker = compact::QUEUE_LOCKER.clone();
         let locker = locker.lock().await;
         log::debug!("[COMPACTOR] Running data delay deletion");
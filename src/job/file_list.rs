@@ -52,6 +52,9 @@ pub async fn run_move_file_to_s3() -> Result<(), anyhow::Error> {
     interval.tick().await; // trigger the first run
     loop {
         interval.tick().await;
+        if config::cluster::is_degraded() {
+            continue;
+        }
         if let Err(e) = move_file_list_to_storage(true).await {
             log::error!("Error moving file_list to remote: {}", e);
         }
@@ -0,0 +1,40 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use tokio::time;
+
+use crate::{common::infra::cluster::leader, service::db};
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    let cfg = config::get_config();
+    if !cfg.compact.file_list_snapshot_enabled || cfg.common.meta_store_external {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(
+        cfg.compact.file_list_snapshot_interval,
+    ));
+    interval.tick().await; // the file list was just loaded, trigger next time
+    leader::campaign(leader::FILE_LIST_SNAPSHOT);
+    loop {
+        interval.tick().await;
+        if !leader::is_leader(leader::FILE_LIST_SNAPSHOT) {
+            continue; // the leader snapshots for the cluster
+        }
+        if let Err(e) = db::file_list::snapshot::create().await {
+            log::error!("[FILE_LIST_SNAPSHOT] create error: {}", e);
+        }
+    }
+}
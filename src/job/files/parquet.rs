@@ -100,6 +100,10 @@ pub async fn run() -> Result<(), anyhow::Error> {
             break;
         }
         time::sleep(time::Duration::from_secs(cfg.limit.file_push_interval)).await;
+        // the files stay in the wal until the stores answer again
+        if cluster::is_degraded() {
+            continue;
+        }
         if let Err(e) = scan_wal_files(tx.clone()).await {
             log::error!("[INGESTER:JOB] Error prepare parquet files: {}", e);
         }
@@ -108,6 +112,37 @@ pub async fn run() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Uploads all the parquet files of the wal regardless of their size and age,
+/// files that are still being moved by the job are waited for.
+pub async fn flush_all(deadline: std::time::Instant) -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    let wal_dir = Path::new(&cfg.common.data_wal_dir).canonicalize()?;
+    let pattern = wal_dir.join("files/");
+    loop {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<(String, Vec<FileKey>)>(1);
+        let scan = tokio::spawn(async move { scan_wal_files(tx).await });
+        while let Some((prefix, files)) = rx.recv().await {
+            if let Err(e) = move_files(0, &prefix, files).await {
+                log::error!("[INGESTER:JOB] Error moving parquet files to remote: {}", e);
+            }
+        }
+        scan.await??;
+
+        let remaining = config::utils::file::scan_files(&pattern, "parquet", None)
+            .map(|files| files.len())
+            .unwrap_or_default();
+        if remaining == 0 {
+            return Ok(());
+        }
+        if std::time::Instant::now() >= deadline {
+            return Err(anyhow::anyhow!(
+                "{remaining} parquet files are left in the wal"
+            ));
+        }
+        time::sleep(time::Duration::from_secs(1)).await;
+    }
+}
+
 async fn scan_wal_files(
     worker_tx: tokio::sync::mpsc::Sender<(String, Vec<FileKey>)>,
 ) -> Result<(), anyhow::Error> {
@@ -319,11 +354,12 @@ async fn move_files(
         .iter()
         .map(|f| f.meta.original_size)
         .sum::<i64>();
-    if total_original_size
-        < std::cmp::min(
-            cfg.limit.max_file_size_on_disk as i64,
-            cfg.compact.max_file_size as i64,
-        )
+    if !cluster::is_draining()
+        && total_original_size
+            < std::cmp::min(
+                cfg.limit.max_file_size_on_disk as i64,
+                cfg.compact.max_file_size as i64,
+            )
         && (cfg.limit.file_move_fields_limit == 0
             || group_schema_field_num < cfg.limit.file_move_fields_limit)
     {
@@ -368,7 +404,7 @@ async fn move_files(
         // yield to other tasks
         tokio::task::yield_now().await;
         // merge file and get the big file key
-        let (new_file_name, new_file_meta, new_file_list) = match merge_files(
+        let (new_file_name, new_file_meta, new_file_list, deduplicated) = match merge_files(
             thread_id,
             latest_schema.clone(),
             group_schema_field_num,
@@ -395,8 +431,26 @@ async fn move_files(
             }
         }
 
-        // write file list to storage
-        let ret = db::file_list::local::set(&new_file_name, Some(new_file_meta), false).await;
+        // write file list to storage, the file with the content of an uploaded
+        // file is in the file list already
+        let file_min_ts = new_file_meta.min_ts;
+        let ret = if deduplicated {
+            Ok(())
+        } else {
+            db::file_list::local::set(&new_file_name, Some(new_file_meta), false).await
+        };
+        if ret.is_ok() && !deduplicated {
+            // the compactor may have passed the hour of the file already
+            if let Err(e) =
+                db::compact::files::mark_late(&org_id, stream_type, &stream_name, file_min_ts).await
+            {
+                log::error!(
+                    "[INGESTER:JOB] Failed to mark the late file: {}, error: {}",
+                    new_file_name,
+                    e
+                );
+            }
+        }
         if let Err(e) = ret {
             log::error!(
                 "[INGESTER:JOB] Failed write parquet file meta: {}, error: {}",
@@ -465,16 +519,17 @@ async fn move_files(
 }
 
 /// merge some small files into one big file, upload to storage, returns the big
-/// file key and merged files
+/// file key and merged files, and whether the big file had the content of an
+/// uploaded file and wasn't uploaded again
 async fn merge_files(
     thread_id: usize,
     latest_schema: Arc<Schema>,
     group_schema_field_num: usize,
     wal_dir: &Path,
     files_with_size: &[FileKey],
-) -> Result<(String, FileMeta, Vec<FileKey>), anyhow::Error> {
+) -> Result<(String, FileMeta, Vec<FileKey>, bool), anyhow::Error> {
     if files_with_size.is_empty() {
-        return Ok((String::from(""), FileMeta::default(), Vec::new()));
+        return Ok((String::from(""), FileMeta::default(), Vec::new(), false));
     }
 
     let cfg = get_config();
@@ -531,7 +586,12 @@ async fn merge_files(
         new_file_list.retain(|f| !deleted_files.contains(&f.key));
     }
     if new_file_list.is_empty() {
-        return Ok((String::from(""), FileMeta::default(), retain_file_list));
+        return Ok((
+            String::from(""),
+            FileMeta::default(),
+            retain_file_list,
+            false,
+        ));
     }
 
     // eg: files/default/logs/olympics/0/2023/08/21/08/8b8a5451bbe1c44b/
@@ -650,10 +710,53 @@ async fn merge_files(
         &full_text_search_fields,
     );
 
+    // the replays of whole files by the agents write the same content again
+    let content_hash = cfg
+        .common
+        .feature_file_dedup_enabled
+        .then(|| blake3::hash(&buf).to_hex().to_string());
+    if let Some(hash) = content_hash.as_ref() {
+        if let Some(file_key) =
+            db::file_list::dedup::get(&org_id, stream_type, &stream_name, min_ts, hash).await
+        {
+            if infra::file_list::contains(&file_key)
+                .await
+                .unwrap_or_default()
+            {
+                log::info!(
+                    "[INGESTER:JOB:{thread_id}] merged file has the content of the file: {}, skip the upload",
+                    file_key
+                );
+                metrics::INGEST_DEDUP_BYTES
+                    .with_label_values(&[&org_id, stream_type.to_string().as_str()])
+                    .inc_by(new_file_meta.compressed_size as u64);
+                return Ok((file_key, new_file_meta, retain_file_list, true));
+            }
+        }
+    }
+
     // upload file
     let buf = Bytes::from(buf);
     match storage::put(&new_file_key, buf).await {
         Ok(_) => {
+            if let Some(hash) = content_hash {
+                if let Err(e) = db::file_list::dedup::set(
+                    &org_id,
+                    stream_type,
+                    &stream_name,
+                    min_ts,
+                    &hash,
+                    &new_file_key,
+                )
+                .await
+                {
+                    log::error!(
+                        "[INGESTER:JOB:{thread_id}] Failed to save the content hash of the file: {}, error: {}",
+                        new_file_key,
+                        e
+                    );
+                }
+            }
             if cfg.common.inverted_index_enabled && stream_type != StreamType::Index {
                 generate_index_on_ingester(
                     inverted_idx_batches,
@@ -664,7 +767,7 @@ async fn merge_files(
                 .await
                 .map_err(|e| anyhow::anyhow!("generate_index_on_ingester error: {}", e))?;
             }
-            Ok((new_file_key, new_file_meta, retain_file_list))
+            Ok((new_file_key, new_file_meta, retain_file_list, false))
         }
         Err(e) => Err(e),
     }
@@ -742,7 +845,9 @@ pub(crate) async fn generate_index_on_ingester(
         hour_buf.records_size += record_size;
     }
     let writer = ingester::get_writer(0, org_id, &StreamType::Index.to_string()).await;
-    let _ = crate::service::ingestion::write_file(&writer, stream_name, data_buf).await;
+    if let Err(e) = crate::service::ingestion::write_file(&writer, stream_name, data_buf).await {
+        log::error!("[INGESTER:JOB] write index wal file error: {}", e);
+    }
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
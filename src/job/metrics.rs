@@ -48,6 +48,10 @@ pub async fn run() -> Result<(), anyhow::Error> {
         if let Err(e) = update_memory_usage().await {
             log::error!("Error update memory_usage metrics: {}", e);
         }
+        if get_config().common.cache_metrics_enabled {
+            // refreshes the entries and bytes gauges of the caches
+            crate::service::caches::list().await;
+        }
         interval.tick().await;
     }
 }
@@ -24,17 +24,26 @@ use crate::{
         infra::config::SYSLOG_ENABLED,
         meta::{organization::DEFAULT_ORG, user::UserRequest},
     },
-    service::{compact::stats::update_stats_from_file_list, db, usage, users},
+    service::{
+        audit, bucket_ingest, compact::stats::update_stats_from_file_list, db, ingestion, quota,
+        self_monitoring, usage, users,
+    },
 };
 
 mod alert_manager;
 mod compactor;
+mod enrichment_table;
 pub(crate) mod file_list;
+mod file_list_snapshot;
 pub(crate) mod files;
 mod flatten_compactor;
+mod heartbeat;
 mod metrics;
 mod mmdb_downloader;
+mod patterns;
 mod prom;
+mod provisioning;
+mod replication;
 mod stats;
 pub(crate) mod syslog_server;
 mod telemetry;
@@ -82,6 +91,50 @@ pub async fn init() -> Result<(), anyhow::Error> {
         .await
         .expect("organization cache sync failed");
 
+    // cache organization quotas
+    tokio::task::spawn(async move { db::organization::watch_quotas().await });
+    db::organization::cache_quotas()
+        .await
+        .expect("organization quotas cache failed");
+
+    // cache organization network policies
+    tokio::task::spawn(async move { db::organization::watch_network_policies().await });
+    db::organization::cache_network_policies()
+        .await
+        .expect("organization network policies cache failed");
+
+    // cache roles
+    tokio::task::spawn(async move { db::rbac::watch().await });
+    db::rbac::cache().await.expect("roles cache failed");
+
+    // cache service accounts
+    tokio::task::spawn(async move { db::service_accounts::watch().await });
+    db::service_accounts::cache()
+        .await
+        .expect("service accounts cache failed");
+
+    // cache runtime config overrides
+    tokio::task::spawn(async move { db::runtime_config::watch().await });
+    db::runtime_config::cache()
+        .await
+        .expect("runtime config cache failed");
+
+    // cache masking policies
+    tokio::task::spawn(async move { db::masking::watch().await });
+    db::masking::cache()
+        .await
+        .expect("masking policies cache failed");
+
+    // cache ingest dlp rules
+    tokio::task::spawn(async move { db::dlp::watch().await });
+    db::dlp::cache().await.expect("dlp rules cache failed");
+
+    // cache ingestion keys
+    tokio::task::spawn(async move { db::ingestion_keys::watch().await });
+    db::ingestion_keys::cache()
+        .await
+        .expect("ingestion keys cache failed");
+
     // check version
     db::version::set().await.expect("db version set failed");
 
@@ -94,12 +147,25 @@ pub async fn init() -> Result<(), anyhow::Error> {
     if cfg.common.telemetry_enabled && cluster::is_querier(&cluster::LOCAL_NODE_ROLE) {
         tokio::task::spawn(async move { telemetry::run().await });
     }
+    // log patterns run
+    if cfg.patterns.enabled && cluster::is_querier(&cluster::LOCAL_NODE_ROLE) {
+        tokio::task::spawn(async move { patterns::run().await });
+    }
+    // provisioning run
+    if cfg.provisioning.enabled && cluster::is_querier(&cluster::LOCAL_NODE_ROLE) {
+        tokio::task::spawn(async move { provisioning::run().await });
+    }
 
     tokio::task::spawn(async move { usage::run().await });
+    tokio::task::spawn(async move { usage::metering::run().await });
+    tokio::task::spawn(async move { self_monitoring::run().await });
+    tokio::task::spawn(async move { audit::run().await });
+    tokio::task::spawn(async move { quota::run().await });
 
     // initialize metadata watcher
     tokio::task::spawn(async move { db::schema::watch().await });
     tokio::task::spawn(async move { db::functions::watch().await });
+    tokio::task::spawn(async move { db::enrichment_table::watch_lookups().await });
     tokio::task::spawn(async move { db::compact::retention::watch().await });
     tokio::task::spawn(async move { db::metrics::watch_prom_cluster_leader().await });
     tokio::task::spawn(async move { db::alerts::templates::watch().await });
@@ -112,6 +178,9 @@ pub async fn init() -> Result<(), anyhow::Error> {
     tokio::task::spawn(async move { db::ofga::watch().await });
     if cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
         tokio::task::spawn(async move { db::pipelines::watch().await });
+        if let Err(e) = crate::service::logs::backfill::fail_interrupted().await {
+            log::error!("[BACKFILL] fail the interrupted jobs error: {e}");
+        }
     }
 
     #[cfg(feature = "enterprise")]
@@ -120,6 +189,10 @@ pub async fn init() -> Result<(), anyhow::Error> {
     {
         tokio::task::spawn(async move { db::session::watch().await });
     }
+    #[cfg(not(feature = "enterprise"))]
+    if cfg.oidc.enabled {
+        tokio::task::spawn(async move { db::session::watch().await });
+    }
 
     tokio::task::yield_now().await; // yield let other tasks run
 
@@ -150,6 +223,9 @@ pub async fn init() -> Result<(), anyhow::Error> {
         .await
         .expect("reports cache failed");
     db::syslog::cache().await.expect("syslog cache failed");
+    db::enrichment_table::cache_lookups()
+        .await
+        .expect("enrichment table lookups cache failed");
     db::syslog::cache_syslog_settings()
         .await
         .expect("syslog settings cache failed");
@@ -168,9 +244,15 @@ pub async fn init() -> Result<(), anyhow::Error> {
         if cluster::is_querier(&cluster::LOCAL_NODE_ROLE)
             || cluster::is_compactor(&cluster::LOCAL_NODE_ROLE)
         {
-            db::file_list::remote::cache("", false)
-                .await
-                .expect("file list remote cache failed");
+            if cfg.compact.file_list_snapshot_enabled {
+                db::file_list::remote::cache_from_snapshot()
+                    .await
+                    .expect("file list remote cache from snapshot failed");
+            } else {
+                db::file_list::remote::cache("", false)
+                    .await
+                    .expect("file list remote cache failed");
+            }
             infra_file_list::create_table_index()
                 .await
                 .expect("file list create table index failed");
@@ -181,6 +263,19 @@ pub async fn init() -> Result<(), anyhow::Error> {
     }
 
     infra_file_list::create_table_index().await?;
+
+    // rebuild the file list from the bucket after the loss of the meta store
+    if cfg.compact.file_list_recovery_enabled
+        && cluster::is_compactor(&cluster::LOCAL_NODE_ROLE)
+        && infra_file_list::is_empty().await
+    {
+        tokio::task::spawn(async move {
+            if let Err(e) = crate::service::file_list::recover().await {
+                log::error!("[FILE_LIST] recover from the bucket error: {}", e);
+            }
+        });
+    }
+
     db::file_list::remote::cache_stats()
         .await
         .expect("Load stream stats failed");
@@ -194,6 +289,12 @@ pub async fn init() -> Result<(), anyhow::Error> {
             .await
             .expect("user session cache failed");
     }
+    #[cfg(not(feature = "enterprise"))]
+    if cfg.oidc.enabled {
+        db::session::cache()
+            .await
+            .expect("user session cache failed");
+    }
 
     // check wal directory
     if cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
@@ -206,12 +307,27 @@ pub async fn init() -> Result<(), anyhow::Error> {
         }
     }
 
+    tokio::task::spawn(async move { db::file_list::broadcast::watch().await });
+    tokio::task::spawn(async move { db::file_list::replica::watch().await });
     tokio::task::spawn(async move { files::run().await });
     tokio::task::spawn(async move { file_list::run().await });
+    if cluster::is_compactor(&cluster::LOCAL_NODE_ROLE) {
+        tokio::task::spawn(async move { file_list_snapshot::run().await });
+    }
+    tokio::task::spawn(async move { replication::run().await });
+    if cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        tokio::task::spawn(async move { enrichment_table::run().await });
+        tokio::task::spawn(async move { ingestion::function_stats::run().await });
+        tokio::task::spawn(async move { ingestion::breaker::run().await });
+        if cfg.bucket_ingest.enabled {
+            tokio::task::spawn(async move { bucket_ingest::run().await });
+        }
+    }
     tokio::task::spawn(async move { stats::run().await });
     tokio::task::spawn(async move { compactor::run().await });
     tokio::task::spawn(async move { flatten_compactor::run().await });
     tokio::task::spawn(async move { metrics::run().await });
+    tokio::task::spawn(async move { heartbeat::run().await });
     tokio::task::spawn(async move { prom::run().await });
     tokio::task::spawn(async move { alert_manager::run().await });
 
@@ -0,0 +1,39 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::Utc;
+use tokio::time;
+
+use crate::{common::infra::cluster::leader, service::patterns};
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    let cfg = config::get_config();
+    if !cfg.patterns.enabled {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(cfg.patterns.interval));
+    leader::campaign(leader::PATTERNS);
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        if !leader::is_leader(leader::PATTERNS) {
+            continue; // the leader mines for the cluster
+        }
+        let end_time = Utc::now().timestamp_micros();
+        let start_time = end_time - cfg.patterns.interval as i64 * 1_000_000;
+        patterns::mine(start_time, end_time).await;
+    }
+}
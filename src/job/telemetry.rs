@@ -15,7 +15,7 @@
 
 use tokio::time;
 
-use crate::common::meta::telemetry::Telemetry;
+use crate::common::{infra::cluster::leader, meta::telemetry::Telemetry};
 
 pub async fn run() -> Result<(), anyhow::Error> {
     let cfg = config::get_config();
@@ -26,9 +26,13 @@ pub async fn run() -> Result<(), anyhow::Error> {
     let mut interval = time::interval(time::Duration::from_secs(
         (cfg.common.telemetry_heartbeat).try_into().unwrap(),
     ));
+    leader::campaign(leader::REPORTING);
     interval.tick().await;
     loop {
         interval.tick().await;
+        if !leader::is_leader(leader::REPORTING) {
+            continue; // the leader reports for the cluster
+        }
         Telemetry::new()
             .heart_beat("OpenObserve - heartbeat", None)
             .await;
@@ -26,6 +26,7 @@ use std::{
 };
 
 use actix_web::{dev::ServerHandle, http::KeepAlive, middleware, web, App, HttpServer};
+use actix_web_lab::middleware::from_fn;
 use actix_web_opentelemetry::RequestTracing;
 use config::{
     cluster::{is_router, LOCAL_NODE_ROLE},
@@ -42,19 +43,22 @@ use openobserve::{
     handler::{
         grpc::{
             auth::check_auth,
+            health,
             request::{
                 event::Eventer,
                 file_list::Filelister,
                 logs::LogsServer,
                 metrics::{ingester::Ingester, querier::Querier},
+                replication::Replicator,
                 traces::TraceServer,
                 usage::UsageServerImpl,
             },
+            request_id::RequestIdService,
         },
         http::router::*,
     },
     job, router,
-    service::{db, metadata, search::SEARCH_SERVER, usage},
+    service::{db, metadata, node, search::SEARCH_SERVER, usage},
 };
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
@@ -65,7 +69,8 @@ use opentelemetry_proto::tonic::collector::{
 };
 use opentelemetry_sdk::{propagation::TraceContextPropagator, trace as sdktrace, Resource};
 use proto::cluster_rpc::{
-    event_server::EventServer, filelist_server::FilelistServer, metrics_server::MetricsServer,
+    self, event_server::EventServer, filelist_server::FilelistServer,
+    metrics_server::MetricsServer, replication_server::ReplicationServer,
     search_server::SearchServer, usage_server::UsageServer,
 };
 #[cfg(feature = "profiling")]
@@ -73,7 +78,8 @@ use pyroscope::PyroscopeAgent;
 #[cfg(feature = "profiling")]
 use pyroscope_pprofrs::{pprof_backend, PprofConfig};
 use tokio::sync::oneshot;
-use tonic::codec::CompressionEncoding;
+use tonic::{codec::CompressionEncoding, service::interceptor::InterceptedService};
+use tonic_health::ServingStatus;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::Registry;
 
@@ -196,8 +202,8 @@ async fn main() -> Result<(), anyhow::Error> {
     // check version upgrade
     let old_version = db::version::get().await.unwrap_or("v0.0.0".to_string());
     migration::check_upgrade(&old_version, VERSION).await?;
-    // migrate dashboards
-    migration::dashboards::run().await?;
+    // migrate the layout of the meta store
+    migration::layout::run(old_version == "v0.0.0").await?;
 
     // ingester init
     ingester::init().await.expect("ingester init failed");
@@ -310,6 +316,10 @@ fn init_common_grpc_server(
     let metrics_ingest_svc = MetricsServiceServer::new(Ingester)
         .send_compressed(CompressionEncoding::Gzip)
         .accept_compressed(CompressionEncoding::Gzip);
+    let replication_svc = ReplicationServer::new(Replicator)
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024);
     let usage_svc = UsageServer::new(UsageServerImpl)
         .send_compressed(CompressionEncoding::Gzip)
         .accept_compressed(CompressionEncoding::Gzip);
@@ -321,24 +331,96 @@ fn init_common_grpc_server(
         .send_compressed(CompressionEncoding::Gzip)
         .accept_compressed(CompressionEncoding::Gzip);
 
+    let health_services = [
+        health::service_name(&event_svc),
+        health::service_name(&search_svc),
+        health::service_name(&filelist_svc),
+        health::service_name(&metrics_svc),
+        health::service_name(&metrics_ingest_svc),
+        health::service_name(&replication_svc),
+        health::service_name(&trace_svc),
+        health::service_name(&usage_svc),
+        health::service_name(&logs_svc),
+    ];
+    let reflection_svc = if cfg.grpc.reflection_enabled {
+        Some(
+            tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(cluster_rpc::FILE_DESCRIPTOR_SET)
+                .build()?,
+        )
+    } else {
+        None
+    };
+
+    let tls_enabled = cfg.grpc.tls_enabled;
     tokio::task::spawn(async move {
         log::info!("starting gRPC server at {}", gaddr);
-        tonic::transport::Server::builder()
-            .layer(tonic::service::interceptor(check_auth))
-            .add_service(event_svc)
-            .add_service(search_svc)
-            .add_service(filelist_svc)
-            .add_service(metrics_svc)
-            .add_service(metrics_ingest_svc)
-            .add_service(trace_svc)
-            .add_service(usage_svc)
-            .add_service(logs_svc)
-            .serve_with_shutdown(gaddr, async {
-                shutdown_rx.await.ok();
-                log::info!("gRPC server starts shutting down");
-            })
-            .await
-            .expect("gRPC server init failed");
+        let (mut health_reporter, health_svc) = tonic_health::server::health_reporter();
+        health::set_status(
+            &mut health_reporter,
+            &health_services,
+            ServingStatus::Serving,
+        )
+        .await;
+        let server = tonic::transport::Server::builder()
+            .add_service(health_svc)
+            .add_service(RequestIdService::new(InterceptedService::new(
+                event_svc, check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                search_svc, check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                filelist_svc,
+                check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                metrics_svc,
+                check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                metrics_ingest_svc,
+                check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                replication_svc,
+                check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                trace_svc, check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                usage_svc, check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                logs_svc, check_auth,
+            )))
+            .add_optional_service(
+                reflection_svc.map(|svc| InterceptedService::new(svc, check_auth)),
+            );
+        let shutdown = async move {
+            shutdown_rx.await.ok();
+            health::set_status(
+                &mut health_reporter,
+                &health_services,
+                ServingStatus::NotServing,
+            )
+            .await;
+            log::info!("gRPC server starts shutting down");
+        };
+        let ret = if tls_enabled {
+            let listener = tokio::net::TcpListener::bind(gaddr)
+                .await
+                .expect("gRPC server bind failed");
+            let incoming =
+                common_infra::tls::grpc_incoming(listener).expect("gRPC server tls init failed");
+            server
+                .serve_with_incoming_shutdown(incoming, shutdown)
+                .await
+        } else {
+            server.serve_with_shutdown(gaddr, shutdown).await
+        };
+        ret.expect("gRPC server init failed");
         stopped_tx.send(()).ok();
     });
     Ok(())
@@ -362,19 +444,57 @@ fn init_router_grpc_server(
         .send_compressed(CompressionEncoding::Gzip)
         .accept_compressed(CompressionEncoding::Gzip);
 
+    let health_services = [
+        health::service_name(&logs_svc),
+        health::service_name(&metrics_svc),
+        health::service_name(&traces_svc),
+    ];
+
+    let tls_enabled = cfg.grpc.tls_enabled;
     tokio::task::spawn(async move {
         log::info!("starting gRPC server at {}", gaddr);
-        tonic::transport::Server::builder()
-            .layer(tonic::service::interceptor(check_auth))
-            .add_service(logs_svc)
-            .add_service(metrics_svc)
-            .add_service(traces_svc)
-            .serve_with_shutdown(gaddr, async {
-                shutdown_rx.await.ok();
-                log::info!("gRPC server starts shutting down");
-            })
-            .await
-            .expect("gRPC server init failed");
+        let (mut health_reporter, health_svc) = tonic_health::server::health_reporter();
+        health::set_status(
+            &mut health_reporter,
+            &health_services,
+            ServingStatus::Serving,
+        )
+        .await;
+        let server = tonic::transport::Server::builder()
+            .add_service(health_svc)
+            .add_service(RequestIdService::new(InterceptedService::new(
+                logs_svc, check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                metrics_svc,
+                check_auth,
+            )))
+            .add_service(RequestIdService::new(InterceptedService::new(
+                traces_svc, check_auth,
+            )));
+        let shutdown = async move {
+            shutdown_rx.await.ok();
+            health::set_status(
+                &mut health_reporter,
+                &health_services,
+                ServingStatus::NotServing,
+            )
+            .await;
+            log::info!("gRPC server starts shutting down");
+        };
+        let ret = if tls_enabled {
+            let listener = tokio::net::TcpListener::bind(gaddr)
+                .await
+                .expect("gRPC server bind failed");
+            let incoming =
+                common_infra::tls::grpc_incoming(listener).expect("gRPC server tls init failed");
+            server
+                .serve_with_incoming_shutdown(incoming, shutdown)
+                .await
+        } else {
+            server.serve_with_shutdown(gaddr, shutdown).await
+        };
+        ret.expect("gRPC server init failed");
         stopped_tx.send(()).ok();
     });
     Ok(())
@@ -439,13 +559,21 @@ async fn init_http_server() -> Result<(), anyhow::Error> {
                     .configure(get_proxy_routes),
             )
         }
-        app.app_data(web::JsonConfig::default().limit(cfg.limit.req_json_limit))
+        app.app_data(
+            web::JsonConfig::default()
+                .limit(cfg.limit.req_json_limit)
+                .error_handler(extractor_error_handler),
+        )
+            .app_data(web::QueryConfig::default().error_handler(extractor_error_handler))
+            .app_data(web::PathConfig::default().error_handler(extractor_error_handler))
             .app_data(web::PayloadConfig::new(cfg.limit.req_payload_limit)) // size is in bytes
             .app_data(web::Data::new(local_id%10))
             .wrap(middleware::Compress::default())
             .wrap(middleware::Logger::new(
-                r#"%a "%r" %s %b "%{Content-Length}i" "%{Referer}i" "%{User-Agent}i" %T"#,
+                r#"%a "%r" %s %b "%{Content-Length}i" "%{Referer}i" "%{User-Agent}i" "%{x-request-id}i" %T"#,
             ))
+            .wrap(from_fn(http_metrics_middleware))
+            .wrap(from_fn(request_id_middleware))
             .wrap(RequestTracing::new())
     })
     .keep_alive(KeepAlive::Timeout(Duration::from_secs(max(
@@ -453,8 +581,12 @@ async fn init_http_server() -> Result<(), anyhow::Error> {
         cfg.limit.keep_alive,
     ))))
     .client_request_timeout(Duration::from_secs(max(5, cfg.limit.request_timeout)))
-    .shutdown_timeout(max(1, cfg.limit.shutdown_timeout))
-    .bind(haddr)?;
+    .shutdown_timeout(max(1, cfg.limit.shutdown_timeout));
+    let server = if cfg.http.tls_enabled {
+        server.bind_rustls_0_22(haddr, common_infra::tls::http_server_config()?)?
+    } else {
+        server.bind(haddr)?
+    };
 
     let server = server
         .workers(cfg.limit.http_worker_num)
@@ -528,21 +660,33 @@ async fn init_http_server_without_tracing() -> Result<(), anyhow::Error> {
                     .configure(get_proxy_routes),
             )
         }
-        app.app_data(web::JsonConfig::default().limit(cfg.limit.req_json_limit))
+        app.app_data(
+            web::JsonConfig::default()
+                .limit(cfg.limit.req_json_limit)
+                .error_handler(extractor_error_handler),
+        )
+            .app_data(web::QueryConfig::default().error_handler(extractor_error_handler))
+            .app_data(web::PathConfig::default().error_handler(extractor_error_handler))
             .app_data(web::PayloadConfig::new(cfg.limit.req_payload_limit)) // size is in bytes
             .app_data(web::Data::new(local_id%10))
             .wrap(middleware::Compress::default())
             .wrap(middleware::Logger::new(
-                r#"%a "%r" %s %b "%{Content-Length}i" "%{Referer}i" "%{User-Agent}i" %T"#,
+                r#"%a "%r" %s %b "%{Content-Length}i" "%{Referer}i" "%{User-Agent}i" "%{x-request-id}i" %T"#,
             ))
+            .wrap(from_fn(http_metrics_middleware))
+            .wrap(from_fn(request_id_middleware))
     })
     .keep_alive(KeepAlive::Timeout(Duration::from_secs(max(
         15,
         cfg.limit.keep_alive,
     ))))
     .client_request_timeout(Duration::from_secs(max(5, cfg.limit.request_timeout)))
-    .shutdown_timeout(max(1, cfg.limit.shutdown_timeout))
-    .bind(haddr)?;
+    .shutdown_timeout(max(1, cfg.limit.shutdown_timeout));
+    let server = if cfg.http.tls_enabled {
+        server.bind_rustls_0_22(haddr, common_infra::tls::http_server_config()?)?
+    } else {
+        server.bind(haddr)?
+    };
 
     let server = server
         .workers(cfg.limit.http_worker_num)
@@ -592,11 +736,19 @@ async fn graceful_shutdown(handle: ServerHandle) {
     // tokio::signal::ctrl_c().await.unwrap();
     // println!("ctrl-c received!");
 
+    // drain the ingestion before leaving the cluster
+    if let Err(e) = node::drain().await {
+        log::error!("drain node failed: {}", e);
+    }
+
     // offline the node
     if let Err(e) = cluster::set_offline(true).await {
         log::error!("set offline failed: {}", e);
     }
     log::info!("Node is offline");
+    if let Err(e) = db::node::delete_heartbeat(&config::cluster::LOCAL_NODE_UUID).await {
+        log::error!("delete heartbeat failed: {}", e);
+    }
 
     handle.stop(true).await;
 }
@@ -664,11 +816,18 @@ fn enable_tracing() -> Result<(), anyhow::Error> {
                 .with_endpoint(&cfg.common.otel_otlp_url)
                 .with_headers(headers),
         )
-        .with_trace_config(sdktrace::config().with_resource(Resource::new(vec![
-            KeyValue::new("service.name", cfg.common.node_role.to_string()),
-            KeyValue::new("service.instance", cfg.common.instance_name.to_string()),
-            KeyValue::new("service.version", VERSION),
-        ])))
+        .with_trace_config(
+            sdktrace::config()
+                // honor the sampling decision of an incoming `traceparent`
+                .with_sampler(sdktrace::Sampler::ParentBased(Box::new(
+                    sdktrace::Sampler::AlwaysOn,
+                )))
+                .with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", cfg.common.node_role.to_string()),
+                    KeyValue::new("service.instance", cfg.common.instance_name.to_string()),
+                    KeyValue::new("service.version", VERSION),
+                ])),
+        )
         .install_batch(opentelemetry_sdk::runtime::Tokio)?;
 
     let layer = if cfg.log.json_format {
@@ -13,13 +13,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Result;
+use std::{env, io::Result, path::PathBuf};
 
 fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=proto");
 
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("cluster_descriptor.bin"))
         .type_attribute("FileList", "#[derive(Eq)]")
         .type_attribute("FileList", "#[derive(serde::Serialize)]")
         .type_attribute("FileKey", "#[derive(Eq)]")
@@ -53,6 +55,7 @@ fn main() -> Result<()> {
                 "proto/cluster/event.proto",
                 "proto/cluster/filelist.proto",
                 "proto/cluster/metrics.proto",
+                "proto/cluster/replication.proto",
                 "proto/cluster/search.proto",
                 "proto/cluster/usage.proto",
             ],
@@ -15,6 +15,10 @@
 
 pub mod cluster_rpc {
     tonic::include_proto!("cluster");
+
+    /// The descriptors of the cluster services, served by the gRPC reflection
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("cluster_descriptor");
 }
 
 pub mod prometheus_rpc {
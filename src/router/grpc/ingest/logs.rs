@@ -55,7 +55,8 @@ impl LogsService for LogsServer {
         let token: MetadataValue<_> = cluster::get_internal_grpc_token()
             .parse()
             .map_err(|_| Status::internal("invalid token".to_string()))?;
-        let channel = super::get_ingester_channel().await?;
+        let shard_key = super::get_shard_key(request.metadata(), "logs");
+        let channel = super::get_ingester_channel(shard_key).await?;
         let client = LogsServiceClient::with_interceptor(channel, move |mut req: Request<()>| {
             req.metadata_mut().insert("authorization", token.clone());
             Ok(req)
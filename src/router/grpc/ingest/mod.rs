@@ -17,16 +17,37 @@ use config::{utils::rand::get_rand_element, RwAHashMap};
 use once_cell::sync::Lazy;
 use tonic::{transport::Channel, Status};
 
-use crate::common::infra::cluster;
+use crate::common::infra::{cluster, tls};
 
 pub mod logs;
 pub mod metrics;
 pub mod traces;
 
+/// Returns the shard key of an otlp request, which writes the stream named by
+/// the stream header.
+pub(crate) fn get_shard_key(
+    metadata: &tonic::metadata::MetadataMap,
+    stream_type: &str,
+) -> Option<String> {
+    let cfg = config::get_config();
+    let org_id = metadata.get(&cfg.grpc.org_header_key)?.to_str().ok()?;
+    let stream_name = metadata
+        .get(&cfg.grpc.stream_header_key)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("default");
+    Some(crate::router::ingest_shard_key(
+        org_id,
+        stream_type,
+        stream_name,
+    ))
+}
+
 static CHANNELS: Lazy<RwAHashMap<String, Channel>> = Lazy::new(Default::default);
 
-pub(crate) async fn get_ingester_channel() -> Result<Channel, tonic::Status> {
-    let grpc_addr = get_rand_ingester_addr().await?;
+pub(crate) async fn get_ingester_channel(
+    shard_key: Option<String>,
+) -> Result<Channel, tonic::Status> {
+    let grpc_addr = get_ingester_addr(shard_key).await?;
     // cache hit
     let r = CHANNELS.read().await;
     if let Some(channel) = r.get(&grpc_addr) {
@@ -35,7 +56,7 @@ pub(crate) async fn get_ingester_channel() -> Result<Channel, tonic::Status> {
     drop(r);
 
     // cache miss, connect to ingester
-    let channel = Channel::from_shared(grpc_addr.clone())
+    let channel = tls::grpc_endpoint(grpc_addr.clone())
         .unwrap()
         .connect_timeout(std::time::Duration::from_secs(
             config::get_config().grpc.connect_timeout,
@@ -57,8 +78,14 @@ pub(crate) async fn get_ingester_channel() -> Result<Channel, tonic::Status> {
     Ok(channel)
 }
 
-async fn get_rand_ingester_addr() -> Result<String, tonic::Status> {
+async fn get_ingester_addr(shard_key: Option<String>) -> Result<String, tonic::Status> {
     let cfg = config::get_config();
+    // keep all the data of a stream on the ingester owning it
+    if let Some(key) = shard_key.filter(|_| cfg.route.ingest_shard_by_stream) {
+        if let Some(node) = cluster::get_ingester_from_rendezvous_hash(&key).await {
+            return Ok(node.grpc_addr);
+        }
+    }
     let nodes = cluster::get_cached_online_ingester_nodes().await;
     if nodes.is_none() || nodes.as_ref().unwrap().is_empty() {
         if !cfg.route.ingester_srv_url.is_empty() {
@@ -18,8 +18,9 @@ use actix_web::{http::Error, route, web, HttpRequest, HttpResponse};
 
 use crate::common::infra::cluster;
 
-const QUERIER_ROUTES: [&str; 18] = [
+const QUERIER_ROUTES: [&str; 19] = [
     "/config",
+    "/metering",
     "/summary",
     "/organizations",
     "/settings",
@@ -146,7 +147,11 @@ async fn dispatch(
 ) -> actix_web::Result<HttpResponse, Error> {
     // get online nodes
     let path = req.uri().path_and_query().map(|x| x.as_str()).unwrap_or("");
-    let new_url = get_url(path).await;
+    let stream_header = req
+        .headers()
+        .get(&get_config().grpc.stream_header_key)
+        .and_then(|v| v.to_str().ok());
+    let new_url = get_url(path, stream_header).await;
     if new_url.is_error {
         return Ok(HttpResponse::ServiceUnavailable().body(new_url.value));
     }
@@ -187,7 +192,7 @@ async fn dispatch(
     Ok(new_resp.body(body))
 }
 
-async fn get_url(path: &str) -> URLDetails {
+async fn get_url(path: &str, stream_header: Option<&str>) -> URLDetails {
     let node_type;
     let is_querier_path = check_querier_route(path);
 
@@ -220,6 +225,19 @@ async fn get_url(path: &str) -> URLDetails {
         };
     }
 
+    // keep all the data of a stream on the ingester owning it
+    if node_type == Role::Ingester && get_config().route.ingest_shard_by_stream {
+        if let Some((org_id, stream_type, stream_name)) = get_ingest_stream(path, stream_header) {
+            let key = super::ingest_shard_key(org_id, stream_type, stream_name);
+            if let Some(node) = cluster::get_ingester_from_rendezvous_hash(&key).await {
+                return URLDetails {
+                    is_error: false,
+                    value: format!("{}{}", node.http_addr, path),
+                };
+            }
+        }
+    }
+
     let nodes = nodes.unwrap();
     let node = get_rand_element(&nodes);
     URLDetails {
@@ -228,6 +246,34 @@ async fn get_url(path: &str) -> URLDetails {
     }
 }
 
+/// Returns the org, stream type and stream of an ingestion request that writes
+/// a single stream.
+fn get_ingest_stream<'a>(
+    path: &'a str,
+    stream_header: Option<&'a str>,
+) -> Option<(&'a str, &'a str, &'a str)> {
+    let path = path.split('?').next().unwrap_or_default();
+    let path = path
+        .strip_prefix(get_config().common.base_uri.as_str())
+        .unwrap_or(path);
+    let columns = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+    match columns.as_slice() {
+        [
+            "api" | "aws" | "gcp",
+            org_id,
+            stream_name,
+            "_json" | "_multi" | "_raw" | "_kinesis_firehose" | "_sub" | "_backfill",
+        ] => Some((*org_id, "logs", *stream_name)),
+        ["api", org_id, "v1", "logs"] => {
+            Some((*org_id, "logs", stream_header.unwrap_or("default")))
+        }
+        ["api", org_id, "traces"] | ["api", org_id, "v1", "traces"] => {
+            Some((*org_id, "traces", stream_header.unwrap_or("default")))
+        }
+        _ => None,
+    }
+}
+
 struct URLDetails {
     is_error: bool,
     value: String,
@@ -242,5 +288,37 @@ mod tests {
         assert!(check_querier_route("/api/_search"));
         assert!(check_querier_route("/api/_around"));
         assert!(!check_querier_route("/api/_bulk"));
+        assert!(check_querier_route("/api/default/metering"));
+        assert!(!check_querier_route("/api/_meta/usage/_json"));
+    }
+
+    #[test]
+    fn test_get_ingest_stream() {
+        assert_eq!(
+            get_ingest_stream("/api/default/app/_json", None),
+            Some(("default", "logs", "app"))
+        );
+        assert_eq!(
+            get_ingest_stream("/aws/default/app/_kinesis_firehose?x=1", None),
+            Some(("default", "logs", "app"))
+        );
+        assert_eq!(
+            get_ingest_stream("/api/default/app/_backfill", None),
+            Some(("default", "logs", "app"))
+        );
+        assert_eq!(
+            get_ingest_stream("/api/default/v1/logs", Some("app")),
+            Some(("default", "logs", "app"))
+        );
+        assert_eq!(
+            get_ingest_stream("/api/default/v1/traces", None),
+            Some(("default", "traces", "default"))
+        );
+        // a bulk request writes many streams
+        assert_eq!(get_ingest_stream("/api/default/_bulk", None), None);
+        assert_eq!(
+            get_ingest_stream("/api/default/prometheus/api/v1/write", None),
+            None
+        );
     }
 }
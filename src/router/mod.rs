@@ -13,5 +13,19 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use config::get_config;
+
 pub mod grpc;
 pub mod http;
+
+/// The key an ingested stream is sharded by, it changes every
+/// `ZO_ROUTE_INGEST_SHARD_WINDOW` seconds when that is set.
+pub(crate) fn ingest_shard_key(org_id: &str, stream_type: &str, stream_name: &str) -> String {
+    let window = get_config().route.ingest_shard_window;
+    if window == 0 {
+        format!("{org_id}/{stream_type}/{stream_name}")
+    } else {
+        let now = chrono::Utc::now().timestamp() as u64;
+        format!("{org_id}/{stream_type}/{stream_name}/{}", now / window)
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Admission control of the work a node takes, so an overloaded node queues or
+//! sheds the requests instead of running out of memory.
+//!
+//! A querier admits `ZO_QUERIER_MAX_CONCURRENT_SEARCHES` searches, and when
+//! `ZO_QUERY_MEMORY_BUDGET` is set only the searches the datafusion memory of
+//! the node fits at that budget, the others wait up to
+//! `ZO_ADMISSION_QUEUE_TIMEOUT`. An ingester rejects the requests over
+//! `ZO_INGESTER_MAX_CONCURRENT_REQUESTS` or `ZO_INGESTER_MAX_BODY_SIZE` right
+//! away, the clients retry them on another ingester. It also rejects them
+//! while its wal and the files waiting for upload are over the high watermark
+//! of `ZO_INGESTER_WAL_MAX_SIZE`, so an object store outage fills the budget
+//! instead of the disk.
+
+use std::{sync::Arc, time::Duration};
+
+use config::{get_config, metrics};
+use once_cell::sync::Lazy;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const MB: usize = 1024 * 1024;
+
+static SEARCH_SLOTS: Lazy<Option<Arc<Semaphore>>> = Lazy::new(|| {
+    let max = get_config().limit.querier_max_concurrent_searches;
+    (max > 0).then(|| Arc::new(Semaphore::new(max)))
+});
+
+// permits are MB of the datafusion memory
+static SEARCH_MEMORY: Lazy<Option<Arc<Semaphore>>> = Lazy::new(|| {
+    let cfg = get_config();
+    if cfg.limit.query_memory_budget == 0 || cfg.memory_cache.datafusion_max_size == 0 {
+        return None;
+    }
+    Some(Arc::new(Semaphore::new(
+        (cfg.memory_cache.datafusion_max_size / MB).max(1),
+    )))
+});
+
+static INGEST_SLOTS: Lazy<Option<Arc<Semaphore>>> = Lazy::new(|| {
+    let max = get_config().limit.ingester_max_concurrent_requests;
+    (max > 0).then(|| Arc::new(Semaphore::new(max)))
+});
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdmissionError {
+    #[error("Node is overloaded: {0}, retry later")]
+    Overloaded(String),
+    #[error("Request body of {0} bytes is over the limit of {1} bytes")]
+    TooLarge(usize, usize),
+    #[error("Wal of the node is full: {0} bytes of {1} bytes used, retry later")]
+    WalFull(u64, u64),
+}
+
+/// Holds the resources of an admitted search until it is dropped.
+#[derive(Default)]
+pub struct SearchPermit {
+    _slot: Option<OwnedSemaphorePermit>,
+    _memory: Option<OwnedSemaphorePermit>,
+}
+
+/// Waits until the querier can run one more search.
+pub async fn admit_search() -> Result<SearchPermit, AdmissionError> {
+    if SEARCH_SLOTS.is_none() && SEARCH_MEMORY.is_none() {
+        return Ok(SearchPermit::default());
+    }
+    let cfg = get_config();
+    metrics::QUERY_QUEUED_NUMS.with_label_values(&[]).inc();
+    let ret = tokio::time::timeout(
+        Duration::from_secs(cfg.limit.admission_queue_timeout),
+        acquire_search(
+            cfg.limit.query_memory_budget,
+            cfg.memory_cache.datafusion_max_size,
+        ),
+    )
+    .await;
+    metrics::QUERY_QUEUED_NUMS.with_label_values(&[]).dec();
+    match ret {
+        Ok(permit) => Ok(permit),
+        Err(_) => {
+            metrics::ADMISSION_REJECTED
+                .with_label_values(&["search"])
+                .inc();
+            Err(AdmissionError::Overloaded(format!(
+                "search waited {}s for admission",
+                cfg.limit.admission_queue_timeout
+            )))
+        }
+    }
+}
+
+async fn acquire_search(budget: usize, total: usize) -> SearchPermit {
+    let slot = match SEARCH_SLOTS.as_ref() {
+        Some(slots) => slots.clone().acquire_owned().await.ok(),
+        None => None,
+    };
+    let memory = match SEARCH_MEMORY.as_ref() {
+        Some(memory) => memory
+            .clone()
+            .acquire_many_owned(memory_permits(budget, total))
+            .await
+            .ok(),
+        None => None,
+    };
+    SearchPermit {
+        _slot: slot,
+        _memory: memory,
+    }
+}
+
+// a budget over the memory of the node takes all of it, so the search still
+// runs alone instead of waiting forever
+fn memory_permits(budget: usize, total: usize) -> u32 {
+    let total = (total / MB).max(1);
+    budget.div_ceil(MB).clamp(1, total) as u32
+}
+
+/// Admits an ingestion request of the given body size, without queueing.
+pub fn admit_ingest(
+    body_size: Option<usize>,
+) -> Result<Option<OwnedSemaphorePermit>, AdmissionError> {
+    let cfg = get_config();
+    let max_size = if cfg.limit.ingester_max_body_size > 0 {
+        cfg.limit.ingester_max_body_size
+    } else {
+        cfg.limit.req_payload_limit
+    };
+    if let Some(size) = body_size.filter(|size| *size > max_size) {
+        metrics::ADMISSION_REJECTED
+            .with_label_values(&["ingest_body_size"])
+            .inc();
+        return Err(AdmissionError::TooLarge(size, max_size));
+    }
+    if cfg.limit.ingester_wal_max_size > 0 {
+        let used = wal_backlog_bytes();
+        metrics::INGEST_WAL_BUDGET_USED_BYTES
+            .with_label_values(&[])
+            .set(used as i64);
+        let budget = cfg.limit.ingester_wal_max_size as u64;
+        if is_over_watermark(used, budget, cfg.limit.ingester_wal_high_watermark as u64) {
+            metrics::ADMISSION_REJECTED
+                .with_label_values(&["ingest_wal"])
+                .inc();
+            return Err(AdmissionError::WalFull(used, budget));
+        }
+    }
+    let Some(slots) = INGEST_SLOTS.as_ref() else {
+        return Ok(None);
+    };
+    match slots.clone().try_acquire_owned() {
+        Ok(permit) => Ok(Some(permit)),
+        Err(_) => {
+            metrics::ADMISSION_REJECTED
+                .with_label_values(&["ingest"])
+                .inc();
+            Err(AdmissionError::Overloaded(format!(
+                "{} ingestion requests are running",
+                cfg.limit.ingester_max_concurrent_requests
+            )))
+        }
+    }
+}
+
+/// Bytes of the memtables, which are in the wal, and of the files waiting for
+/// upload to the object store.
+pub fn wal_backlog_bytes() -> u64 {
+    let memtable = metrics::INGEST_MEMTABLE_BYTES.with_label_values(&[]).get();
+    let files: i64 = metrics::INGEST_WAL_USED_BYTES
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric().iter())
+        .map(|metric| metric.get_gauge().get_value() as i64)
+        .sum();
+    (memtable + files).max(0) as u64
+}
+
+fn is_over_watermark(used: u64, budget: u64, watermark: u64) -> bool {
+    used * 100 >= budget * watermark
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_permits() {
+        assert_eq!(memory_permits(256 * MB, 1024 * MB), 256);
+        assert_eq!(memory_permits(MB / 2, 1024 * MB), 1);
+        assert_eq!(memory_permits(2048 * MB, 1024 * MB), 1024);
+    }
+
+    #[test]
+    fn test_is_over_watermark() {
+        assert!(!is_over_watermark(899, 1000, 90));
+        assert!(is_over_watermark(900, 1000, 90));
+        assert!(is_over_watermark(1200, 1000, 100));
+    }
+}
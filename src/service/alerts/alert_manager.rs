@@ -13,9 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::str::FromStr;
-
-use chrono::{Duration, FixedOffset, Utc};
+use chrono::{Duration, Utc};
 use config::{
     get_config,
     meta::{
@@ -23,11 +21,13 @@ use config::{
         usage::{TriggerData, TriggerDataStatus, TriggerDataType},
     },
 };
-use cron::Schedule;
 
 use crate::{
-    common::meta::{alerts::AlertFrequencyType, dashboards::reports::ReportFrequencyType},
-    service::{db, usage::publish_triggers_usage},
+    common::{
+        meta::{alerts::AlertFrequencyType, dashboards::reports::ReportFrequencyType},
+        utils::schedule::{next_calendar_run, next_cron_run, CalendarInterval, ScheduleTz},
+    },
+    service::{db, derived_streams, usage::publish_triggers_usage},
 };
 
 pub async fn run() -> Result<(), anyhow::Error> {
@@ -60,6 +60,9 @@ pub async fn handle_triggers(trigger: db::scheduler::Trigger) -> Result<(), anyh
     match trigger.module {
         db::scheduler::TriggerModule::Report => handle_report_triggers(trigger).await,
         db::scheduler::TriggerModule::Alert => handle_alert_triggers(trigger).await,
+        db::scheduler::TriggerModule::DerivedStream => {
+            derived_streams::handle_trigger(trigger).await
+        }
     }
 }
 
@@ -134,14 +137,12 @@ async fn handle_alert_triggers(trigger: db::scheduler::Trigger) -> Result<(), an
             .unwrap();
         new_trigger.is_silenced = true;
     } else if alert.trigger_condition.frequency_type == AlertFrequencyType::Cron {
-        let schedule = Schedule::from_str(&alert.trigger_condition.cron)?;
-        // tz_offset is in minutes
-        let tz_offset = FixedOffset::east_opt(alert.tz_offset * 60).unwrap();
-        new_trigger.next_run_at = schedule
-            .upcoming(tz_offset)
-            .next()
-            .unwrap()
-            .timestamp_micros();
+        let tz = ScheduleTz::new_or_fixed(&alert.timezone, alert.tz_offset)?;
+        new_trigger.next_run_at = next_cron_run(
+            &alert.trigger_condition.cron,
+            tz,
+            Utc::now().timestamp_micros(),
+        )?;
     } else {
         new_trigger.next_run_at += Duration::try_seconds(alert.trigger_condition.frequency)
             .unwrap()
@@ -259,24 +260,24 @@ async fn handle_report_triggers(trigger: db::scheduler::Trigger) -> Result<(), a
                 .num_microseconds()
                 .unwrap();
         }
-        ReportFrequencyType::Days => {
-            new_trigger.next_run_at += Duration::try_days(report.frequency.interval)
-                .unwrap()
-                .num_microseconds()
-                .unwrap();
-        }
-        ReportFrequencyType::Weeks => {
-            new_trigger.next_run_at += Duration::try_weeks(report.frequency.interval)
-                .unwrap()
-                .num_microseconds()
-                .unwrap();
-        }
-        ReportFrequencyType::Months => {
-            // Assumes each month to be of 30 days.
-            new_trigger.next_run_at += Duration::try_days(report.frequency.interval * 30)
-                .unwrap()
-                .num_microseconds()
-                .unwrap();
+        ReportFrequencyType::Days
+        | ReportFrequencyType::Weeks
+        | ReportFrequencyType::Months
+        | ReportFrequencyType::FirstOfMonth
+        | ReportFrequencyType::BusinessDays => {
+            // Calendar intervals run on the wall clock of the report timezone,
+            // counted from the start of the report
+            let interval = report.frequency.interval.max(1) as u32;
+            let interval = match report.frequency.frequency_type {
+                ReportFrequencyType::Days => CalendarInterval::Days(interval),
+                ReportFrequencyType::Weeks => CalendarInterval::Weeks(interval),
+                ReportFrequencyType::Months => CalendarInterval::Months(interval),
+                ReportFrequencyType::FirstOfMonth => CalendarInterval::FirstOfMonth(interval),
+                _ => CalendarInterval::BusinessDays,
+            };
+            let tz = ScheduleTz::new_or_fixed(&report.timezone, report.tz_offset)?;
+            new_trigger.next_run_at =
+                next_calendar_run(interval, tz, report.start, new_trigger.next_run_at)?;
         }
         ReportFrequencyType::Once => {
             // Check on next week
@@ -286,14 +287,9 @@ async fn handle_report_triggers(trigger: db::scheduler::Trigger) -> Result<(), a
             run_once = true;
         }
         ReportFrequencyType::Cron => {
-            let schedule = Schedule::from_str(&report.frequency.cron)?;
-            // tz_offset is in minutes
-            let tz_offset = FixedOffset::east_opt(report.tz_offset * 60).unwrap();
-            new_trigger.next_run_at = schedule
-                .upcoming(tz_offset)
-                .next()
-                .unwrap()
-                .timestamp_micros();
+            let tz = ScheduleTz::new_or_fixed(&report.timezone, report.tz_offset)?;
+            new_trigger.next_run_at =
+                next_cron_run(&report.frequency.cron, tz, new_trigger.next_run_at)?;
         }
     }
 
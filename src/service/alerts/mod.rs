@@ -44,7 +44,10 @@ use crate::{
             },
             authz::Authz,
         },
-        utils::auth::{remove_ownership, set_ownership},
+        utils::{
+            auth::{remove_ownership, set_ownership},
+            schedule::ScheduleTz,
+        },
     },
     service::{db, search as SearchService},
 };
@@ -79,6 +82,7 @@ pub async fn save(
             if !create {
                 return Err(anyhow::anyhow!("Alert not found"));
             }
+            crate::service::quota::check_alert_quota(org_id).await?;
         }
         Err(e) => {
             return Err(e);
@@ -88,6 +92,8 @@ pub async fn save(
     if alert.trigger_condition.frequency_type == AlertFrequencyType::Cron {
         // Check the cron expression
         Schedule::from_str(&alert.trigger_condition.cron)?;
+        // Check the timezone of the cron expression
+        ScheduleTz::new(&alert.timezone, alert.tz_offset)?;
     } else if alert.trigger_condition.frequency == 0 {
         // default frequency is 60 seconds
         alert.trigger_condition.frequency =
@@ -459,6 +465,7 @@ impl QueryCondition {
                 query_context: None,
                 query_fn: None,
                 skip_wal: false,
+                highlight: false,
             },
             aggs: HashMap::new(),
             encoding: config::meta::search::RequestEncoding::Empty,
@@ -468,15 +475,19 @@ impl QueryCondition {
             search_type: Some(SearchEventType::Alerts),
         };
         let trace_id = ider::uuid();
-        let resp =
-            match SearchService::search(&trace_id, &alert.org_id, alert.stream_type, None, &req)
-                .await
-            {
-                Ok(v) => v,
-                Err(_) => {
-                    return Ok(None);
-                }
-            };
+        let resp = match SearchService::search_as_system(
+            &trace_id,
+            &alert.org_id,
+            alert.stream_type,
+            &req,
+        )
+        .await
+        {
+            Ok(v) => v,
+            Err(_) => {
+                return Ok(None);
+            }
+        };
         if resp.total < alert.trigger_condition.threshold as usize {
             Ok(None)
         } else {
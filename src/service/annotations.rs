@@ -0,0 +1,274 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{http, HttpResponse};
+use config::{
+    get_config, ider,
+    meta::{stream::StreamType, usage::TRIGGERS_USAGE_STREAM},
+    utils::json,
+};
+
+use crate::{
+    common::meta::{
+        annotations::{Annotation, AnnotationList, AnnotationQuery, SOURCE_ALERT, SOURCE_MANUAL},
+        http::HttpResponse as MetaHttpResponse,
+    },
+    service::{db, search as SearchService},
+};
+
+/// Maximum number of alert evaluations read for one request
+const MAX_ALERT_EVALUATIONS: i64 = 10000;
+
+/// Evaluation of an alert, read back from the triggers stream
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+struct AlertEvaluation {
+    key: String,
+    status: String,
+    end_time: i64,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Lists the manual annotations and the alert events drawn on the charts of
+/// the query, sorted by time.
+pub async fn list(org_id: &str, query: AnnotationQuery) -> Result<HttpResponse, Error> {
+    if query.start_time > query.end_time {
+        return Ok(MetaHttpResponse::bad_request(
+            "start_time should be earlier than end_time",
+        ));
+    }
+    let mut list = match db::annotations::list(org_id).await {
+        Ok(list) => list,
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    match alert_annotations(org_id, &query).await {
+        Ok(alerts) => list.extend(alerts),
+        Err(e) => {
+            log::error!("Error reading the alert history of {org_id}: {e}");
+            return Ok(
+                HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                    http::StatusCode::INTERNAL_SERVER_ERROR.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    }
+    let mut list = list
+        .into_iter()
+        .filter(|annotation| annotation.matches(&query))
+        .collect::<Vec<_>>();
+    list.sort_by_key(|annotation| annotation.start_time);
+    Ok(HttpResponse::Ok().json(AnnotationList { list }))
+}
+
+pub async fn create(
+    org_id: &str,
+    user_id: &str,
+    mut annotation: Annotation,
+) -> Result<HttpResponse, Error> {
+    if annotation.title.trim().is_empty() {
+        return Ok(MetaHttpResponse::bad_request(
+            "Annotation title should not be empty",
+        ));
+    }
+    if annotation
+        .end_time
+        .is_some_and(|end_time| end_time < annotation.start_time)
+    {
+        return Ok(MetaHttpResponse::bad_request(
+            "end_time should not be earlier than start_time",
+        ));
+    }
+    if annotation.panel_id.is_some() && annotation.dashboard_id.is_none() {
+        return Ok(MetaHttpResponse::bad_request(
+            "panel_id requires dashboard_id",
+        ));
+    }
+    annotation.annotation_id = ider::generate();
+    annotation.source = SOURCE_MANUAL.to_string();
+    annotation.created_by = user_id.to_string();
+    annotation.created_at = chrono::Utc::now().timestamp_micros();
+    match db::annotations::put(org_id, &annotation).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(annotation)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn delete(org_id: &str, annotation_id: &str) -> Result<HttpResponse, Error> {
+    if db::annotations::get(org_id, annotation_id).await.is_err() {
+        return Ok(MetaHttpResponse::not_found("Annotation not found"));
+    }
+    match db::annotations::delete(org_id, annotation_id).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Annotation deleted")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Reads the alert evaluations of the time range from the triggers stream,
+/// the history is recorded only when the usage reporting is enabled.
+async fn alert_annotations(
+    org_id: &str,
+    query: &AnnotationQuery,
+) -> Result<Vec<Annotation>, infra::errors::Error> {
+    let cfg = get_config();
+    if !cfg.common.usage_enabled {
+        return Ok(vec![]);
+    }
+    let mut filters = vec![
+        format!("org = '{}'", escape(org_id)),
+        "module = 'alert'".to_string(),
+    ];
+    // the trigger key of an alert is {stream_type}/{stream_name}/{alert_name}
+    match (&query.stream_type, &query.stream_name) {
+        (Some(stream_type), Some(stream_name)) => filters.push(format!(
+            "key LIKE '{}/{}/%'",
+            escape(stream_type),
+            escape(stream_name)
+        )),
+        (Some(stream_type), None) => filters.push(format!("key LIKE '{}/%'", escape(stream_type))),
+        _ => {}
+    }
+    let sql = format!(
+        "SELECT key, status, end_time, error FROM \"{TRIGGERS_USAGE_STREAM}\" WHERE {} ORDER BY end_time",
+        filters.join(" AND ")
+    );
+    let req = config::meta::search::Request {
+        query: config::meta::search::Query {
+            sql,
+            sql_mode: "full".to_owned(),
+            from: 0,
+            size: MAX_ALERT_EVALUATIONS,
+            start_time: query.start_time,
+            end_time: query.end_time,
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+    };
+    let hits =
+        match SearchService::search_as_system("", &cfg.common.usage_org, StreamType::Logs, &req)
+            .await
+        {
+            Ok(res) => res.hits,
+            Err(infra::errors::Error::ErrorCode(
+                infra::errors::ErrorCodes::SearchStreamNotFound(_),
+            )) => vec![],
+            Err(e) => return Err(e),
+        };
+    let evaluations = hits
+        .into_iter()
+        .filter_map(|hit| json::from_value::<AlertEvaluation>(hit).ok())
+        .collect::<Vec<_>>();
+    Ok(alert_events(&evaluations))
+}
+
+/// Turns the evaluations of the alerts, sorted by time, into one annotation
+/// per firing: from the first evaluation that fired to the evaluation that
+/// resolved it, or to the last evaluation that fired when it is still firing.
+fn alert_events(evaluations: &[AlertEvaluation]) -> Vec<Annotation> {
+    let mut firing: HashMap<&str, Annotation> = HashMap::new();
+    let mut events = vec![];
+    for evaluation in evaluations {
+        let fired = evaluation.status != "condition_not_satisfied";
+        match (fired, firing.remove(evaluation.key.as_str())) {
+            (true, Some(mut event)) => {
+                event.end_time = Some(evaluation.end_time);
+                firing.insert(&evaluation.key, event);
+            }
+            (true, None) => {
+                let mut columns = evaluation.key.splitn(3, '/');
+                let (Some(stream_type), Some(stream_name), Some(alert_name)) =
+                    (columns.next(), columns.next(), columns.next())
+                else {
+                    continue;
+                };
+                let event = Annotation {
+                    annotation_id: format!("alert/{}/{}", evaluation.key, evaluation.end_time),
+                    source: SOURCE_ALERT.to_string(),
+                    start_time: evaluation.end_time,
+                    end_time: Some(evaluation.end_time),
+                    title: format!("Alert {alert_name} fired"),
+                    text: evaluation.error.clone().unwrap_or_default(),
+                    tags: vec![SOURCE_ALERT.to_string(), alert_name.to_string()],
+                    stream_type: Some(stream_type.to_string()),
+                    stream_name: Some(stream_name.to_string()),
+                    ..Default::default()
+                };
+                firing.insert(&evaluation.key, event);
+            }
+            (false, Some(mut event)) => {
+                event.end_time = Some(evaluation.end_time);
+                event.text = "resolved".to_string();
+                events.push(event);
+            }
+            (false, None) => {}
+        }
+    }
+    events.extend(firing.into_values());
+    events.sort_by_key(|event| event.start_time);
+    events
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluation(key: &str, status: &str, end_time: i64) -> AlertEvaluation {
+        AlertEvaluation {
+            key: key.to_string(),
+            status: status.to_string(),
+            end_time,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_alert_events() {
+        let evaluations = vec![
+            evaluation("logs/default/errors", "condition_not_satisfied", 10),
+            evaluation("logs/default/errors", "completed", 20),
+            evaluation("logs/k8s/restarts", "completed", 25),
+            evaluation("logs/default/errors", "failed", 30),
+            evaluation("logs/default/errors", "condition_not_satisfied", 40),
+            evaluation("logs/default/errors", "completed", 50),
+        ];
+        let events = alert_events(&evaluations);
+        let spans = events
+            .iter()
+            .map(|e| (e.stream_name.as_deref().unwrap(), e.start_time, e.end_time))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec![
+                ("default", 20, Some(40)),
+                ("k8s", 25, Some(25)),
+                ("default", 50, Some(50)),
+            ]
+        );
+        assert_eq!(events[0].title, "Alert errors fired");
+        assert_eq!(events[0].text, "resolved");
+        assert_eq!(events[0].source, SOURCE_ALERT);
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error, sync::Arc};
+
+use actix_web::{http, web, HttpResponse};
+use config::{get_config, meta::stream::StreamType, utils::json};
+use once_cell::sync::Lazy;
+use proto::cluster_rpc;
+use tokio::{sync::RwLock, time};
+
+use crate::{
+    common::{
+        infra::config::{ORGANIZATION_NETWORK_POLICIES, ORGANIZATION_QUOTAS},
+        meta::{
+            audit::{
+                self, AuditAction, AuditEntry, AuditList, AuditQuery, AuditResource, AuditTarget,
+                AUDIT_STREAM,
+            },
+            http::HttpResponse as MetaHttpResponse,
+        },
+        utils::http::{get_folder, get_stream_type_from_request},
+    },
+    service::{db, search as SearchService, usage::ingestion_service},
+};
+
+pub static AUDIT_DATA: Lazy<Arc<RwLock<Vec<AuditEntry>>>> =
+    Lazy::new(|| Arc::new(RwLock::new(vec![])));
+
+/// Builds the audit entry of a successful management request from the state
+/// of the resource before and after it. The request body stands in for the
+/// new state when the resource can't be read back, e.g. on creation.
+#[allow(clippy::too_many_arguments)]
+pub fn new_entry(
+    target: &AuditTarget,
+    user_email: &str,
+    method: &str,
+    path: &str,
+    query_params: &str,
+    response_code: u16,
+    before: Option<json::Value>,
+    after: Option<json::Value>,
+    body: &[u8],
+) -> AuditEntry {
+    let after = after.or_else(|| {
+        if method.eq("DELETE") {
+            None
+        } else {
+            json::from_slice::<json::Value>(body).ok().map(|mut v| {
+                audit::redact(&mut v);
+                v
+            })
+        }
+    });
+    let action = AuditAction::new(method, before.as_ref(), after.as_ref());
+    let diff = match (&before, &after) {
+        (Some(before), Some(after)) => json::Value::Object(audit::diff(before, after)).to_string(),
+        _ => String::new(),
+    };
+    AuditEntry {
+        _timestamp: chrono::Utc::now().timestamp_micros(),
+        org_id: target.org_id.clone(),
+        user_email: user_email.to_string(),
+        method: method.to_string(),
+        path: path.to_string(),
+        query_params: query_params.to_string(),
+        resource: target.resource.to_string(),
+        resource_id: target.resource_id.clone(),
+        action: action.to_string(),
+        response_code,
+        before: before.map(|v| v.to_string()).unwrap_or_default(),
+        after: after.map(|v| v.to_string()).unwrap_or_default(),
+        diff,
+        client_ip: String::new(),
+    }
+}
+
+/// Reads the current state of the audited resource, credentials are removed.
+pub async fn snapshot(
+    target: &AuditTarget,
+    query: &web::Query<HashMap<String, String>>,
+) -> Option<json::Value> {
+    if target.resource_id.is_empty() && target.resource != AuditResource::Setting {
+        return None;
+    }
+    let org_id = target.org_id.as_str();
+    let id = target.resource_id.as_str();
+    let sub_path = target
+        .sub_path
+        .iter()
+        .map(|v| v.as_str())
+        .collect::<Vec<_>>();
+    let mut value = match target.resource {
+        AuditResource::User => db::user::get_db_user(id)
+            .await
+            .ok()
+            .and_then(|user| user.get_user(org_id.to_string()))
+            .and_then(|user| json::to_value(user).ok()),
+        AuditResource::Stream => {
+            let stream_type = get_stream_type_from_request(query)
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+            infra::schema::get_settings(org_id, id, stream_type)
+                .await
+                .and_then(|settings| json::to_value(settings).ok())
+        }
+        AuditResource::Alert => match sub_path.as_slice() {
+            ["templates"] => db::alerts::templates::get(org_id, id)
+                .await
+                .ok()
+                .and_then(|v| json::to_value(v).ok()),
+            ["destinations"] => db::alerts::destinations::get(org_id, id)
+                .await
+                .ok()
+                .and_then(|v| json::to_value(v).ok()),
+            [stream_name] => {
+                let stream_type = get_stream_type_from_request(query)
+                    .ok()
+                    .flatten()
+                    .unwrap_or(StreamType::Logs);
+                db::alerts::get(org_id, stream_type, stream_name, id)
+                    .await
+                    .ok()
+                    .flatten()
+                    .and_then(|v| json::to_value(v).ok())
+            }
+            _ => None,
+        },
+        AuditResource::Dashboard => match sub_path.as_slice() {
+            ["folders"] => db::dashboards::folders::get(org_id, id)
+                .await
+                .ok()
+                .and_then(|v| json::to_value(v).ok()),
+            _ => db::dashboards::get(org_id, id, &get_folder(query))
+                .await
+                .ok()
+                .and_then(|v| json::to_value(v).ok()),
+        },
+        AuditResource::Function => db::functions::get(org_id, id)
+            .await
+            .ok()
+            .and_then(|v| json::to_value(v).ok()),
+        AuditResource::Setting => match sub_path.as_slice() {
+            ["quotas"] => ORGANIZATION_QUOTAS
+                .get(org_id)
+                .and_then(|v| json::to_value(v.value()).ok()),
+            ["network_policy"] => ORGANIZATION_NETWORK_POLICIES
+                .get(org_id)
+                .and_then(|v| json::to_value(v.value()).ok()),
+            ["masking_policies"] => db::masking::get(org_id, id)
+                .await
+                .ok()
+                .and_then(|v| json::to_value(v).ok()),
+            ["ingestion_keys"] => db::ingestion_keys::get(org_id, id)
+                .await
+                .ok()
+                .and_then(|v| json::to_value(v).ok()),
+            ["config"] => json::to_value(config::get_config_overrides()).ok(),
+            _ => db::organization::get_org_setting(org_id)
+                .await
+                .ok()
+                .and_then(|v| json::from_slice(&v).ok()),
+        },
+        AuditResource::Role => db::rbac::get(org_id, id)
+            .await
+            .ok()
+            .and_then(|v| json::to_value(v).ok()),
+        AuditResource::ServiceAccount => db::service_accounts::get(org_id, id)
+            .await
+            .ok()
+            .and_then(|v| json::to_value(v).ok()),
+    }?;
+    audit::redact(&mut value);
+    Some(value)
+}
+
+pub async fn audit(entry: AuditEntry) {
+    let cfg = get_config();
+    if !cfg.common.audit_enabled {
+        return;
+    }
+
+    let mut entries = AUDIT_DATA.write().await;
+    entries.push(entry);
+    if entries.len() < cfg.common.audit_batch_size {
+        return;
+    }
+
+    let curr_entries = std::mem::take(&mut *entries);
+    // release the write lock
+    drop(entries);
+
+    publish(curr_entries).await
+}
+
+pub async fn flush() {
+    let mut entries = AUDIT_DATA.write().await;
+    if entries.is_empty() {
+        return;
+    }
+
+    let curr_entries = std::mem::take(&mut *entries);
+    // release the write lock
+    drop(entries);
+
+    publish(curr_entries).await
+}
+
+async fn publish(curr_entries: Vec<AuditEntry>) {
+    let data = curr_entries
+        .iter()
+        .map(|entry| json::to_value(entry).unwrap())
+        .collect::<Vec<_>>();
+    let req = cluster_rpc::UsageRequest {
+        stream_name: AUDIT_STREAM.to_owned(),
+        data: Some(cluster_rpc::UsageData::from(data)),
+    };
+    if let Err(e) = ingestion_service::ingest(&get_config().common.usage_org, req).await {
+        log::error!("Error in ingesting audit data {:?}", e);
+        // on error in ingesting audit data, push back the data
+        let mut entries = AUDIT_DATA.write().await;
+        let mut curr_entries = curr_entries;
+        entries.append(&mut curr_entries);
+        drop(entries);
+    }
+}
+
+pub async fn run() {
+    let cfg = get_config();
+    if !cfg.common.audit_enabled {
+        return;
+    }
+    let mut interval = time::interval(time::Duration::from_secs(
+        cfg.common.audit_publish_interval.try_into().unwrap(),
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        flush().await;
+    }
+}
+
+/// Lists the audit entries of an organization, newest first.
+pub async fn list(org_id: &str, query: AuditQuery) -> Result<HttpResponse, Error> {
+    let cfg = get_config();
+    let end_time = query
+        .end_time
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_micros());
+    let start_time = query.start_time.unwrap_or(
+        end_time
+            - chrono::Duration::try_days(1)
+                .unwrap()
+                .num_microseconds()
+                .unwrap(),
+    );
+    if start_time > end_time {
+        return Ok(MetaHttpResponse::bad_request(
+            "start_time should be earlier than end_time",
+        ));
+    }
+
+    let mut filters = vec![format!("org_id = '{}'", escape(org_id))];
+    for (field, value) in [
+        ("user_email", &query.user),
+        ("resource", &query.resource),
+        ("resource_id", &query.resource_id),
+        ("action", &query.action),
+    ] {
+        if let Some(value) = value {
+            filters.push(format!("{field} = '{}'", escape(value)));
+        }
+    }
+    let sql = format!(
+        "SELECT * FROM \"{AUDIT_STREAM}\" WHERE {} ORDER BY _timestamp DESC",
+        filters.join(" AND ")
+    );
+
+    let req = config::meta::search::Request {
+        query: config::meta::search::Query {
+            sql,
+            sql_mode: "full".to_owned(),
+            from: query.from.unwrap_or(0),
+            size: query.size.unwrap_or(100),
+            start_time,
+            end_time,
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+    };
+    let hits =
+        match SearchService::search_as_system("", &cfg.common.usage_org, StreamType::Logs, &req)
+            .await
+        {
+            Ok(res) => res.hits,
+            Err(infra::errors::Error::ErrorCode(
+                infra::errors::ErrorCodes::SearchStreamNotFound(_),
+            )) => vec![],
+            Err(e) => {
+                log::error!("Error listing audit entries for {org_id}: {e}");
+                return Ok(
+                    HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                        http::StatusCode::INTERNAL_SERVER_ERROR.into(),
+                        e.to_string(),
+                    )),
+                );
+            }
+        };
+    let list = hits
+        .into_iter()
+        .filter_map(|hit| json::from_value::<AuditEntry>(hit).ok())
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(AuditList { list }))
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\'', "''")
+}
@@ -0,0 +1,247 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Backups of the meta store to the object store, so that a cluster which lost
+//! its meta store can get its configuration back from the data bucket.
+//!
+//! A full backup holds every key, an incremental backup only the keys changed
+//! since its base and the keys deleted since. Each backup lives under
+//! `backup/meta/{id}/` with its manifest, its entries and the hashes of all
+//! the keys at the time, which the next incremental backup compares against.
+
+use std::io::{Read, Write};
+
+use config::utils::{
+    base64,
+    hash::{fnv, Sum64},
+    json,
+};
+use hashbrown::HashMap;
+use infra::{db as infra_db, file_list as infra_file_list, storage};
+
+use crate::{
+    common::meta::backup::{
+        diff, is_backed_up, split_start_dt, BackupEntry, BackupManifest, RestoreReport,
+        RestoreRequest,
+    },
+    service::db,
+};
+
+const BACKUP_PREFIX: &str = "backup/meta/";
+const LATEST_KEY: &str = "backup/meta/latest.json";
+
+fn manifest_key(id: &str) -> String {
+    format!("{BACKUP_PREFIX}{id}/manifest.json")
+}
+
+fn entries_key(id: &str) -> String {
+    format!("{BACKUP_PREFIX}{id}/entries.json.zst")
+}
+
+fn hashes_key(id: &str) -> String {
+    format!("{BACKUP_PREFIX}{id}/hashes.json.zst")
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(key: &str) -> Result<T, anyhow::Error> {
+    Ok(json::from_slice(&storage::get(key).await?)?)
+}
+
+async fn put_zst(key: &str, data: &[u8]) -> Result<(), anyhow::Error> {
+    let mut encoder = zstd::Encoder::new(Vec::new(), 3)?;
+    encoder.write_all(data)?;
+    storage::put(key, bytes::Bytes::from(encoder.finish()?)).await
+}
+
+async fn get_zst(key: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let data = storage::get(key).await?;
+    let mut buf = Vec::new();
+    zstd::Decoder::new(&data[..])?.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+pub async fn get_manifest(id: &str) -> Result<BackupManifest, anyhow::Error> {
+    get_json(&manifest_key(id)).await
+}
+
+async fn latest() -> Option<String> {
+    get_json::<String>(LATEST_KEY).await.ok()
+}
+
+/// Lists the backups, newest first.
+pub async fn list() -> Result<Vec<BackupManifest>, anyhow::Error> {
+    let mut list = Vec::new();
+    for file in storage::list(BACKUP_PREFIX).await? {
+        if file.ends_with("/manifest.json") {
+            list.push(json::from_slice::<BackupManifest>(
+                &storage::get(&file).await?,
+            )?);
+        }
+    }
+    list.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(list)
+}
+
+/// Backs up the meta store, read in one listing, along with a snapshot of the
+/// file list. An incremental backup falls back to a full one when there is
+/// no backup yet.
+pub async fn create(incremental: bool) -> Result<BackupManifest, anyhow::Error> {
+    let start = std::time::Instant::now();
+    let now = chrono::Utc::now().timestamp_micros();
+    let id = now.to_string();
+    let base = if incremental { latest().await } else { None };
+    let previous: HashMap<String, u64> = match base.as_deref() {
+        Some(base) => json::from_slice(&get_zst(&hashes_key(base)).await?)?,
+        None => HashMap::new(),
+    };
+
+    let values = infra_db::get_db()
+        .await
+        .list("/")
+        .await?
+        .into_iter()
+        .filter(|(k, _)| is_backed_up(k))
+        .map(|(k, v)| (k, base64::encode_raw(&v)))
+        .collect::<HashMap<_, _>>();
+    let hashes = values
+        .iter()
+        .map(|(k, v)| (k.clone(), fnv::new().sum64(v)))
+        .collect::<HashMap<_, _>>();
+    let (changed, deleted) = diff(&previous, &hashes);
+
+    let mut entries = Vec::new();
+    for key in changed.iter() {
+        let entry = BackupEntry {
+            key: key.clone(),
+            value: values[key].clone(),
+        };
+        entries.extend(json::to_vec(&entry)?);
+        entries.push(b'\n');
+    }
+
+    // the file list lives in its own store, its snapshot covers it. A node
+    // which didn't load the file list, such as the cli, must not replace the
+    // latest snapshot with an empty one
+    let file_list_snapshot = if infra_file_list::is_empty().await {
+        None
+    } else {
+        match db::file_list::snapshot::create().await {
+            Ok(snapshot) => Some(snapshot.key),
+            Err(e) => {
+                log::error!("[BACKUP] file list snapshot error: {}", e);
+                None
+            }
+        }
+    };
+
+    let manifest = BackupManifest {
+        id: id.clone(),
+        base,
+        created_at: now,
+        keys: hashes.len(),
+        changed: changed.len(),
+        deleted,
+        file_list_snapshot,
+    };
+    put_zst(&entries_key(&id), &entries).await?;
+    put_zst(&hashes_key(&id), &json::to_vec(&hashes)?).await?;
+    storage::put(&manifest_key(&id), json::to_vec(&manifest)?.into()).await?;
+    storage::put(LATEST_KEY, json::to_vec(&id)?.into()).await?;
+    log::info!(
+        "[BACKUP] create [{}] with {} of {} keys done, took: {}ms",
+        id,
+        manifest.changed,
+        manifest.keys,
+        start.elapsed().as_millis()
+    );
+    Ok(manifest)
+}
+
+/// Restores the meta store from a backup and the backups it is incremental
+/// to, then loads the file list snapshot. The nodes running already should be
+/// restarted to load the restored configuration.
+pub async fn restore(req: RestoreRequest) -> Result<RestoreReport, anyhow::Error> {
+    let backup = match req.backup {
+        Some(id) => Some(id),
+        None => latest().await,
+    };
+    let Some(id) = backup else {
+        return Err(anyhow::anyhow!("no backup found"));
+    };
+
+    // the chain of backups, from the full one to the requested one
+    let mut chain = vec![get_manifest(&id).await?];
+    while let Some(base) = chain.last().unwrap().base.clone() {
+        chain.push(get_manifest(&base).await?);
+    }
+    chain.reverse();
+
+    let mut state: HashMap<String, String> = HashMap::new();
+    for manifest in chain.iter() {
+        for key in manifest.deleted.iter() {
+            state.remove(key);
+        }
+        let data = get_zst(&entries_key(&manifest.id)).await?;
+        for line in data.split(|c| *c == b'\n').filter(|l| !l.is_empty()) {
+            let entry: BackupEntry = json::from_slice(line)?;
+            state.insert(entry.key, entry.value);
+        }
+    }
+
+    let mut report = RestoreReport {
+        backup: id,
+        ..Default::default()
+    };
+    let client = infra_db::get_db().await;
+    for (key, value) in state {
+        let (key, start_dt) = split_start_dt(&key);
+        if !req.overwrite && client.get(key).await.is_ok() {
+            report.skipped += 1;
+            continue;
+        }
+        let value = match base64::decode_raw(&value) {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("[BACKUP] restore [{}] decode error: {}", key, e);
+                report.failed += 1;
+                continue;
+            }
+        };
+        match client
+            .put(key, value.into(), infra_db::NEED_WATCH, start_dt)
+            .await
+        {
+            Ok(_) => report.restored += 1,
+            Err(e) => {
+                log::error!("[BACKUP] restore [{}] error: {}", key, e);
+                report.failed += 1;
+            }
+        }
+    }
+
+    if chain.last().unwrap().file_list_snapshot.is_some() {
+        match db::file_list::remote::cache_from_snapshot().await {
+            Ok(_) => report.file_list_loaded = true,
+            Err(e) => log::error!("[BACKUP] restore file list error: {}", e),
+        }
+    }
+    log::info!(
+        "[BACKUP] restore [{}] restored {}, skipped {}, failed {}",
+        report.backup,
+        report.restored,
+        report.skipped,
+        report.failed
+    );
+    Ok(report)
+}
@@ -0,0 +1,533 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Ingestion of the objects dropped in a landing bucket. The bucket sends its
+//! s3:ObjectCreated notifications to an SQS queue, the ingesters poll it,
+//! download the objects and ingest their records into the logs stream mapped
+//! from the key. The progress in an object is checkpointed, a notification
+//! delivered again is skipped or resumes where the last attempt stopped. A
+//! message failing ZO_BUCKET_INGEST_MAX_RECEIVES times is moved to the dead
+//! letter queue.
+
+use std::{io::Read, sync::Arc};
+
+use aws_sdk_sqs::{
+    config::Region,
+    types::{Message, MessageSystemAttributeName, QueueAttributeName},
+    Client,
+};
+use bytes::Bytes;
+use config::{
+    get_config, metrics,
+    utils::{arrow::record_batches_to_json_rows, json, parquet::read_recordbatch_from_bytes},
+};
+use flate2::read::GzDecoder;
+use hashbrown::HashMap;
+use object_store::{aws::AmazonS3Builder, ObjectStore};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::Deserialize;
+
+use crate::{
+    common::meta::ingestion::{BucketIngestCheckpoint, IngestionRequest},
+    service::{db, format_stream_name, logs},
+};
+
+/// Max messages of a receive, the limit of SQS.
+const RECEIVE_BATCH_SIZE: i32 = 10;
+/// Seconds of the long polling, the limit of SQS.
+const RECEIVE_WAIT_TIME: i32 = 20;
+/// The checkpoints of the ingested objects are kept for the max retention of
+/// a SQS message.
+const CHECKPOINT_TTL_DAYS: i64 = 14;
+const CLEANUP_INTERVAL: u64 = 3600;
+
+static STORES: Lazy<RwLock<HashMap<String, Arc<dyn ObjectStore>>>> = Lazy::new(Default::default);
+
+/// An object of a notification.
+#[derive(Clone, Debug, PartialEq)]
+struct S3Object {
+    bucket: String,
+    key: String,
+    etag: String,
+    size: usize,
+}
+
+#[derive(Deserialize)]
+struct Notification {
+    #[serde(rename = "Records", default)]
+    records: Vec<NotificationRecord>,
+    #[serde(rename = "Event")]
+    event: Option<String>,
+    /// the notification when it comes through SNS
+    #[serde(rename = "Message")]
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NotificationRecord {
+    event_name: String,
+    s3: NotificationEntity,
+}
+
+#[derive(Deserialize)]
+struct NotificationEntity {
+    bucket: NotificationBucket,
+    object: NotificationObject,
+}
+
+#[derive(Deserialize)]
+struct NotificationBucket {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NotificationObject {
+    key: String,
+    #[serde(default)]
+    size: usize,
+    #[serde(default)]
+    e_tag: String,
+}
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    if !cfg.bucket_ingest.enabled || cfg.bucket_ingest.queue_url.is_empty() {
+        return Ok(());
+    }
+    let sdk_config = aws_config::from_env()
+        .region(Region::new(cfg.bucket_ingest.region.clone()))
+        .load()
+        .await;
+    let client = Client::new(&sdk_config);
+    log::info!(
+        "[BUCKET_INGEST] polling the notifications of {}",
+        cfg.bucket_ingest.queue_url
+    );
+
+    let mut last_cleanup = std::time::Instant::now();
+    loop {
+        if let Err(e) = poll(&client).await {
+            log::error!("[BUCKET_INGEST] receive messages error: {}", e);
+            tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+        }
+        if last_cleanup.elapsed().as_secs() >= CLEANUP_INTERVAL {
+            last_cleanup = std::time::Instant::now();
+            if let Err(e) = cleanup_checkpoints().await {
+                log::error!("[BUCKET_INGEST] clean up checkpoints error: {}", e);
+            }
+        }
+    }
+}
+
+async fn poll(client: &Client) -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    let resp = client
+        .receive_message()
+        .queue_url(&cfg.bucket_ingest.queue_url)
+        .max_number_of_messages(RECEIVE_BATCH_SIZE)
+        .wait_time_seconds(RECEIVE_WAIT_TIME)
+        .visibility_timeout(cfg.bucket_ingest.visibility_timeout)
+        .attribute_names(QueueAttributeName::ApproximateReceiveCount)
+        .send()
+        .await?;
+    for message in resp.messages().unwrap_or_default() {
+        if let Err(e) = handle(client, message).await {
+            log::error!(
+                "[BUCKET_INGEST] handle message {} error: {}",
+                message.message_id().unwrap_or_default(),
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn handle(client: &Client, message: &Message) -> Result<(), anyhow::Error> {
+    let objects = match parse_notification(message.body().unwrap_or_default()) {
+        Ok(objects) => objects,
+        // it never parses, no need to wait for the redeliveries
+        Err(e) => return poison(client, message, &e.to_string()).await,
+    };
+    let mut ret = Ok(());
+    for object in objects.iter() {
+        ret = ingest_object(object).await;
+        if ret.is_err() {
+            break;
+        }
+    }
+
+    let receives = message
+        .attributes()
+        .and_then(|v| v.get(&MessageSystemAttributeName::ApproximateReceiveCount))
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(1);
+    match ret {
+        Ok(()) => delete(client, message).await,
+        Err(e) if receives >= get_config().bucket_ingest.max_receives => {
+            poison(client, message, &e.to_string()).await
+        }
+        Err(e) => {
+            // the message shows again after the visibility timeout
+            log::warn!(
+                "[BUCKET_INGEST] message {} failed on receive {receives}, retrying later: {e}",
+                message.message_id().unwrap_or_default()
+            );
+            Ok(())
+        }
+    }
+}
+
+async fn delete(client: &Client, message: &Message) -> Result<(), anyhow::Error> {
+    client
+        .delete_message()
+        .queue_url(&get_config().bucket_ingest.queue_url)
+        .receipt_handle(message.receipt_handle().unwrap_or_default())
+        .send()
+        .await?;
+    Ok(())
+}
+
+/// Moves the message to the dead letter queue, or only logs it without one.
+async fn poison(client: &Client, message: &Message, error: &str) -> Result<(), anyhow::Error> {
+    metrics::BUCKET_INGEST_POISON_MESSAGES
+        .with_label_values(&[])
+        .inc();
+    log::error!(
+        "[BUCKET_INGEST] message {} can't be ingested: {error}, body: {}",
+        message.message_id().unwrap_or_default(),
+        message.body().unwrap_or_default()
+    );
+    let cfg = get_config();
+    if !cfg.bucket_ingest.dead_letter_queue_url.is_empty() {
+        client
+            .send_message()
+            .queue_url(&cfg.bucket_ingest.dead_letter_queue_url)
+            .message_body(message.body().unwrap_or_default())
+            .send()
+            .await?;
+    }
+    delete(client, message).await
+}
+
+/// The created objects of a notification, sent by the bucket or through SNS.
+/// The test event of a new notification configuration has none.
+fn parse_notification(body: &str) -> Result<Vec<S3Object>, anyhow::Error> {
+    let mut notification: Notification = json::from_str(body)?;
+    if let Some(message) = notification.message.take() {
+        notification = json::from_str(&message)?;
+    }
+    if notification.records.is_empty() && notification.event.is_none() {
+        return Err(anyhow::anyhow!("not a bucket notification"));
+    }
+    Ok(notification
+        .records
+        .into_iter()
+        .filter(|r| r.event_name.starts_with("ObjectCreated:"))
+        .map(|r| S3Object {
+            bucket: r.s3.bucket.name,
+            key: decode_key(&r.s3.object.key),
+            etag: r.s3.object.e_tag,
+            size: r.s3.object.size,
+        })
+        .collect())
+}
+
+/// The keys of the notifications are url encoded, spaces as `+`.
+fn decode_key(key: &str) -> String {
+    let bytes = key.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        decoded.push(b);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The stream of the longest prefix of the mapping matching the key.
+fn stream_for_key(key: &str, mapping: &str, default: &str) -> String {
+    mapping
+        .split(',')
+        .filter_map(|v| v.split_once('='))
+        .map(|(prefix, stream)| (prefix.trim(), stream.trim()))
+        .filter(|(prefix, stream)| key.starts_with(prefix) && !stream.is_empty())
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, stream)| stream)
+        .unwrap_or(default)
+        .to_string()
+}
+
+fn landing_store(bucket: &str) -> Result<Arc<dyn ObjectStore>, anyhow::Error> {
+    if let Some(store) = STORES.read().get(bucket) {
+        return Ok(store.clone());
+    }
+    let store: Arc<dyn ObjectStore> = Arc::new(
+        AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .with_region(&get_config().bucket_ingest.region)
+            .build()?,
+    );
+    STORES.write().insert(bucket.to_string(), store.clone());
+    Ok(store)
+}
+
+async fn ingest_object(object: &S3Object) -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    let org_id = cfg.bucket_ingest.org.as_str();
+    let stream_name = format_stream_name(&stream_for_key(
+        &object.key,
+        &cfg.bucket_ingest.stream_mapping,
+        &cfg.bucket_ingest.stream,
+    ));
+    let mut checkpoint = db::bucket_ingest::get_checkpoint(&object.bucket, &object.key)
+        .await?
+        .filter(|c| c.etag == object.etag)
+        .unwrap_or_else(|| BucketIngestCheckpoint {
+            bucket: object.bucket.clone(),
+            key: object.key.clone(),
+            etag: object.etag.clone(),
+            ..Default::default()
+        });
+    if checkpoint.done {
+        metrics::BUCKET_INGEST_OBJECTS
+            .with_label_values(&[org_id, &stream_name, "skipped"])
+            .inc();
+        return Ok(());
+    }
+    if object.size > cfg.bucket_ingest.max_object_size * 1024 * 1024 {
+        return Err(anyhow::anyhow!(
+            "object {}/{} of {} bytes is bigger than ZO_BUCKET_INGEST_MAX_OBJECT_SIZE",
+            object.bucket,
+            object.key,
+            object.size
+        ));
+    }
+
+    let start = std::time::Instant::now();
+    let data = landing_store(&object.bucket)?
+        .get(&object.key.as_str().into())
+        .await?
+        .bytes()
+        .await?;
+    let records = match parse_object(&object.key, data).await {
+        Ok(v) => v,
+        Err(e) => {
+            metrics::BUCKET_INGEST_OBJECTS
+                .with_label_values(&[org_id, &stream_name, "failed"])
+                .inc();
+            return Err(e);
+        }
+    };
+    for batch in records
+        .get(checkpoint.records..)
+        .unwrap_or_default()
+        .chunks(cfg.bucket_ingest.batch_records)
+    {
+        let body = Bytes::from(json::to_vec(batch)?);
+        let resp = logs::ingest::ingest(org_id, &stream_name, IngestionRequest::JSON(&body), 0, "")
+            .await?;
+        if resp.code != 200 {
+            return Err(anyhow::anyhow!(
+                "ingest into {org_id}/{stream_name} error: {}",
+                resp.error.unwrap_or_default()
+            ));
+        }
+        checkpoint.records += batch.len();
+        checkpoint.updated_at = chrono::Utc::now().timestamp_micros();
+        db::bucket_ingest::set_checkpoint(&checkpoint).await?;
+    }
+    checkpoint.done = true;
+    checkpoint.updated_at = chrono::Utc::now().timestamp_micros();
+    db::bucket_ingest::set_checkpoint(&checkpoint).await?;
+
+    metrics::BUCKET_INGEST_OBJECTS
+        .with_label_values(&[org_id, &stream_name, "ingested"])
+        .inc();
+    log::info!(
+        "[BUCKET_INGEST] ingest {}/{} into {org_id}/{stream_name} with {} records done, took: {}ms",
+        object.bucket,
+        object.key,
+        records.len(),
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+/// The records of an object, decompressed by the `.gz` or `.zst` extension.
+/// Parquet files are read by their `.parquet` extension, the others are a
+/// json array, a json document such as a CloudTrail file, or lines of json
+/// or plain text, the plain text lines go into the `message` field.
+async fn parse_object(key: &str, data: Bytes) -> Result<Vec<json::Value>, anyhow::Error> {
+    let mut name = key.to_lowercase();
+    let data = if let Some(v) = name.strip_suffix(".gz") {
+        name = v.to_string();
+        let mut buf = Vec::new();
+        GzDecoder::new(&data[..]).read_to_end(&mut buf)?;
+        Bytes::from(buf)
+    } else if let Some(v) = name
+        .strip_suffix(".zst")
+        .or_else(|| name.strip_suffix(".zstd"))
+    {
+        name = v.to_string();
+        Bytes::from(zstd::decode_all(&data[..])?)
+    } else {
+        data
+    };
+
+    if name.ends_with(".parquet") {
+        let (_, batches) = read_recordbatch_from_bytes(&data).await?;
+        let batches = batches.iter().collect::<Vec<_>>();
+        return Ok(record_batches_to_json_rows(&batches)?
+            .into_iter()
+            .map(json::Value::Object)
+            .collect());
+    }
+
+    let text = String::from_utf8_lossy(&data);
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        match json::from_str::<json::Value>(trimmed) {
+            Ok(json::Value::Array(records)) => return Ok(records),
+            Ok(record @ json::Value::Object(_)) => return Ok(vec![record]),
+            // more than one document, json lines
+            _ => {}
+        }
+    }
+    Ok(text
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match json::from_str::<json::Value>(line) {
+            Ok(record @ json::Value::Object(_)) => record,
+            _ => {
+                let mut record = json::Map::new();
+                record.insert(logs::raw::MESSAGE_FIELD.to_string(), line.into());
+                json::Value::Object(record)
+            }
+        })
+        .collect())
+}
+
+async fn cleanup_checkpoints() -> Result<(), anyhow::Error> {
+    let expired = chrono::Utc::now().timestamp_micros()
+        - chrono::Duration::try_days(CHECKPOINT_TTL_DAYS)
+            .unwrap()
+            .num_microseconds()
+            .unwrap();
+    for checkpoint in db::bucket_ingest::list_checkpoints().await? {
+        if checkpoint.done && checkpoint.updated_at < expired {
+            db::bucket_ingest::delete_checkpoint(&checkpoint.bucket, &checkpoint.key).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_notification() {
+        let body = r#"{"Records":[
+            {"eventName":"ObjectCreated:Put","s3":{"bucket":{"name":"landing"},"object":{"key":"app/access+log%3D1.gz","size":1024,"eTag":"abc"}}},
+            {"eventName":"ObjectRemoved:Delete","s3":{"bucket":{"name":"landing"},"object":{"key":"app/old.gz"}}}
+        ]}"#;
+        let objects = parse_notification(body).unwrap();
+        assert_eq!(
+            objects,
+            vec![S3Object {
+                bucket: "landing".to_string(),
+                key: "app/access log=1.gz".to_string(),
+                etag: "abc".to_string(),
+                size: 1024,
+            }]
+        );
+
+        // through sns
+        let sns = json::json!({"Type": "Notification", "Message": body}).to_string();
+        assert_eq!(parse_notification(&sns).unwrap(), objects);
+
+        let test_event = r#"{"Service":"Amazon S3","Event":"s3:TestEvent","Bucket":"landing"}"#;
+        assert!(parse_notification(test_event).unwrap().is_empty());
+        assert!(parse_notification(r#"{"foo":"bar"}"#).is_err());
+        assert!(parse_notification("not json").is_err());
+    }
+
+    #[test]
+    fn test_decode_key() {
+        assert_eq!(decode_key("a/b+c%2Fd.json"), "a/b c/d.json");
+        assert_eq!(decode_key("100%"), "100%");
+        assert_eq!(decode_key("%zz"), "%zz");
+    }
+
+    #[test]
+    fn test_stream_for_key() {
+        let mapping = "logs/=app, logs/nginx/=nginx,trail/=cloudtrail";
+        assert_eq!(
+            stream_for_key("logs/nginx/a.gz", mapping, "default"),
+            "nginx"
+        );
+        assert_eq!(stream_for_key("logs/api/a.gz", mapping, "default"), "app");
+        assert_eq!(stream_for_key("other/a.gz", mapping, "default"), "default");
+        assert_eq!(stream_for_key("logs/a.gz", "", "default"), "default");
+    }
+
+    #[tokio::test]
+    async fn test_parse_object() {
+        let lines = "{\"a\":1}\n\nplain line\n{\"a\":2}\n";
+        let records = parse_object("x.ndjson", Bytes::from(lines)).await.unwrap();
+        assert_eq!(
+            records,
+            vec![
+                json::json!({"a": 1}),
+                json::json!({"message": "plain line"}),
+                json::json!({"a": 2}),
+            ]
+        );
+
+        let array = Bytes::from(r#"[{"a":1},{"a":2}]"#);
+        assert_eq!(parse_object("x.json", array).await.unwrap().len(), 2);
+
+        let trail = r#"{"Records":[{"eventName":"GetObject"}]}"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(trail.as_bytes()).unwrap();
+        let data = Bytes::from(encoder.finish().unwrap());
+        let records = parse_object("trail/x.json.gz", data).await.unwrap();
+        assert_eq!(records, vec![json::from_str::<json::Value>(trail).unwrap()]);
+
+        let data = Bytes::from(zstd::encode_all(lines.as_bytes(), 0).unwrap());
+        assert_eq!(parse_object("x.log.zst", data).await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,500 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Export and import of the dashboards, alerts and their dependencies, to
+//! promote them between environments.
+//!
+//! The items are imported in the order of their dependencies: templates,
+//! destinations, functions, folders, dashboards and alerts. With the `rename`
+//! strategy the references of the destinations to the renamed templates, and
+//! of the alerts to the renamed destinations, follow the new names.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+};
+
+use actix_web::{body::MessageBody, HttpResponse};
+use config::{ider, utils::json};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{
+    common::{
+        meta::{
+            authz::Authz,
+            bundles::{
+                rename_candidate, Bundle, BundleDashboard, ConflictStrategy, ImportAction,
+                ImportReport, ImportResult, BUNDLE_VERSION,
+            },
+            dashboards::Dashboard,
+            http::HttpResponse as MetaHttpResponse,
+        },
+        utils::auth::set_ownership,
+    },
+    service::{alerts, db, functions},
+};
+
+pub const KIND_FOLDER: &str = "folder";
+pub const KIND_DASHBOARD: &str = "dashboard";
+pub const KIND_TEMPLATE: &str = "template";
+pub const KIND_DESTINATION: &str = "destination";
+pub const KIND_ALERT: &str = "alert";
+pub const KIND_FUNCTION: &str = "function";
+pub const ALL_KINDS: [&str; 6] = [
+    KIND_FOLDER,
+    KIND_DASHBOARD,
+    KIND_TEMPLATE,
+    KIND_DESTINATION,
+    KIND_ALERT,
+    KIND_FUNCTION,
+];
+
+/// Exports the items of the `kinds` of the organization, the folders go with
+/// the dashboards and the templates with the destinations.
+pub async fn export(org_id: &str, kinds: &[&str]) -> Result<Bundle, anyhow::Error> {
+    let mut bundle = Bundle {
+        version: BUNDLE_VERSION,
+        org_id: org_id.to_string(),
+        exported_at: chrono::Utc::now().timestamp_micros(),
+        ..Default::default()
+    };
+    if kinds.contains(&KIND_FOLDER) || kinds.contains(&KIND_DASHBOARD) {
+        bundle.folders = db::dashboards::folders::list(org_id).await?;
+    }
+    if kinds.contains(&KIND_DASHBOARD) {
+        bundle.dashboards = db::dashboards::list_all(org_id)
+            .await?
+            .into_iter()
+            .filter_map(|d| {
+                Some(BundleDashboard {
+                    folder_id: d.folder_id.clone()?,
+                    dashboard: stored_dashboard(&d)?,
+                })
+            })
+            .collect();
+    }
+    if kinds.contains(&KIND_TEMPLATE) || kinds.contains(&KIND_DESTINATION) {
+        bundle.templates = db::alerts::templates::list(org_id).await?;
+    }
+    if kinds.contains(&KIND_DESTINATION) {
+        bundle.destinations = db::alerts::destinations::list(org_id).await?;
+    }
+    if kinds.contains(&KIND_ALERT) {
+        bundle.alerts = db::alerts::list(org_id, None, None).await?;
+    }
+    if kinds.contains(&KIND_FUNCTION) {
+        bundle.functions = db::functions::list(org_id)
+            .await?
+            .into_iter()
+            .map(|mut f| {
+                // the streams are specific to the environment
+                f.streams = None;
+                f
+            })
+            .collect();
+    }
+    Ok(bundle)
+}
+
+pub fn encode(bundle: &Bundle, gzip: bool) -> Result<Vec<u8>, anyhow::Error> {
+    let data = json::to_vec(bundle)?;
+    if !gzip {
+        return Ok(data);
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Decodes a bundle, compressed or not.
+pub fn decode(data: &[u8]) -> Result<Bundle, anyhow::Error> {
+    let bundle: Bundle = if data.starts_with(&[0x1f, 0x8b]) {
+        let mut buf = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut buf)?;
+        json::from_slice(&buf)?
+    } else {
+        json::from_slice(data)?
+    };
+    if bundle.version == 0 || bundle.version > BUNDLE_VERSION {
+        return Err(anyhow::anyhow!(
+            "unsupported bundle version {}, expected at most {BUNDLE_VERSION}",
+            bundle.version
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Imports the bundle into the organization, items failing to import are
+/// reported and don't stop the import of the others.
+pub async fn import(org_id: &str, bundle: Bundle, strategy: ConflictStrategy) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    // templates
+    let existing = names(db::alerts::templates::list(org_id).await, |t| {
+        t.name.clone()
+    });
+    let mut template_names = HashMap::new();
+    for mut template in bundle.templates {
+        let name = template.name.clone();
+        let Some((action, target)) = resolve(&name, &existing, strategy) else {
+            report.push(KIND_TEMPLATE, &name, ImportAction::Skipped, None, None);
+            continue;
+        };
+        template.name = target.clone();
+        let ret = match action {
+            ImportAction::Overwritten => {
+                alerts::templates::save(org_id, &target, template, false).await
+            }
+            _ => alerts::templates::save(org_id, "", template, true).await,
+        };
+        match ret {
+            Ok(_) => {
+                report.push(KIND_TEMPLATE, &name, action, renamed(&name, &target), None);
+                template_names.insert(name, target);
+            }
+            Err(e) => report.failed(KIND_TEMPLATE, &name, e),
+        }
+    }
+
+    // destinations
+    let existing = names(db::alerts::destinations::list(org_id).await, |d| {
+        d.name.clone()
+    });
+    let mut destination_names = HashMap::new();
+    for mut destination in bundle.destinations {
+        let name = destination.name.clone();
+        let Some((action, target)) = resolve(&name, &existing, strategy) else {
+            report.push(KIND_DESTINATION, &name, ImportAction::Skipped, None, None);
+            continue;
+        };
+        destination.name = target.clone();
+        if let Some(template) = template_names.get(&destination.template) {
+            destination.template = template.clone();
+        }
+        let ret = match action {
+            ImportAction::Overwritten => {
+                alerts::destinations::save(org_id, &target, destination, false).await
+            }
+            _ => alerts::destinations::save(org_id, "", destination, true).await,
+        };
+        match ret {
+            Ok(_) => {
+                report.push(
+                    KIND_DESTINATION,
+                    &name,
+                    action,
+                    renamed(&name, &target),
+                    None,
+                );
+                destination_names.insert(name, target);
+            }
+            Err((_, e)) => report.failed(KIND_DESTINATION, &name, e),
+        }
+    }
+
+    // functions
+    let existing = names(db::functions::list(org_id).await, |f| f.name.clone());
+    for mut function in bundle.functions {
+        let name = function.name.clone();
+        let Some((action, target)) = resolve(&name, &existing, strategy) else {
+            report.push(KIND_FUNCTION, &name, ImportAction::Skipped, None, None);
+            continue;
+        };
+        function.name = target.clone();
+        function.streams = None;
+        let resp = match action {
+            ImportAction::Overwritten => {
+                functions::update_function(org_id, &target, function).await
+            }
+            _ => functions::save_function(org_id.to_string(), function).await,
+        };
+        match response_error(resp) {
+            None => report.push(KIND_FUNCTION, &name, action, renamed(&name, &target), None),
+            Some(e) => report.failed(KIND_FUNCTION, &name, e),
+        }
+    }
+
+    // folders are merged by id, renaming a folder would split its dashboards
+    let existing = names(db::dashboards::folders::list(org_id).await, |f| {
+        f.folder_id.clone()
+    });
+    for folder in bundle.folders {
+        let id = folder.folder_id.clone();
+        let exists = existing.contains(&id);
+        if exists && strategy != ConflictStrategy::Overwrite {
+            report.push(KIND_FOLDER, &id, ImportAction::Skipped, None, None);
+            continue;
+        }
+        match db::dashboards::folders::put(org_id, folder).await {
+            Ok(_) => {
+                let action = if exists {
+                    ImportAction::Overwritten
+                } else {
+                    set_ownership(org_id, "folders", Authz::new(&id)).await;
+                    ImportAction::Created
+                };
+                report.push(KIND_FOLDER, &id, action, None, None);
+            }
+            Err(e) => report.failed(KIND_FOLDER, &id, e),
+        }
+    }
+
+    // dashboards keep their ids, a renamed dashboard gets a new id
+    let existing = match db::dashboards::list_all(org_id).await {
+        Ok(list) => list
+            .iter()
+            .map(|d| d.dashboard_id().to_string())
+            .collect::<HashSet<_>>(),
+        Err(_) => HashSet::new(),
+    };
+    for BundleDashboard {
+        folder_id,
+        mut dashboard,
+    } in bundle.dashboards
+    {
+        let id = dashboard
+            .get("dashboardId")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let (action, target) = if id.is_empty() {
+            (ImportAction::Created, ider::generate())
+        } else if !existing.contains(&id) {
+            (ImportAction::Created, id.clone())
+        } else {
+            match strategy {
+                ConflictStrategy::Skip => {
+                    report.push(KIND_DASHBOARD, &id, ImportAction::Skipped, None, None);
+                    continue;
+                }
+                ConflictStrategy::Overwrite => (ImportAction::Overwritten, id.clone()),
+                ConflictStrategy::Rename => (ImportAction::Renamed, ider::generate()),
+            }
+        };
+        if db::dashboards::folders::get(org_id, &folder_id)
+            .await
+            .is_err()
+        {
+            report.failed(KIND_DASHBOARD, &id, format!("folder {folder_id} not found"));
+            continue;
+        }
+        if action == ImportAction::Overwritten {
+            // the dashboard may live in another folder of the organization
+            if let Ok(list) = db::dashboards::list_all(org_id).await {
+                for d in list.iter().filter(|d| d.dashboard_id() == id) {
+                    let folder = d.folder_id.as_deref().unwrap_or_default();
+                    if folder != folder_id {
+                        let _ = db::dashboards::delete(org_id, &id, folder).await;
+                    }
+                }
+            }
+        }
+        if let Some(obj) = dashboard.as_object_mut() {
+            obj.insert("dashboardId".to_string(), json::Value::from(target.clone()));
+        }
+        let body = json::to_vec(&dashboard).unwrap();
+        match db::dashboards::put(org_id, &target, &folder_id, body.into()).await {
+            Ok(saved) => {
+                let _ = crate::service::dashboards::versions::record(org_id, &saved, "").await;
+                if action != ImportAction::Overwritten {
+                    set_ownership(
+                        org_id,
+                        "dashboards",
+                        Authz {
+                            obj_id: target.clone(),
+                            parent_type: "folders".to_owned(),
+                            parent: folder_id.clone(),
+                        },
+                    )
+                    .await;
+                }
+                report.push(KIND_DASHBOARD, &id, action, renamed(&id, &target), None);
+            }
+            Err(e) => report.failed(KIND_DASHBOARD, &id, e),
+        }
+    }
+
+    // alerts are unique per stream
+    let existing = names(db::alerts::list(org_id, None, None).await, alert_key);
+    for mut alert in bundle.alerts {
+        let name = alert.name.clone();
+        let key = alert_key(&alert);
+        let action = if !existing.contains(&key) {
+            ImportAction::Created
+        } else {
+            match strategy {
+                ConflictStrategy::Skip => {
+                    report.push(KIND_ALERT, &name, ImportAction::Skipped, None, None);
+                    continue;
+                }
+                ConflictStrategy::Overwrite => ImportAction::Overwritten,
+                ConflictStrategy::Rename => {
+                    alert.name = rename_candidate(&name, |n| {
+                        existing
+                            .contains(&format!("{}/{}/{n}", alert.stream_type, alert.stream_name))
+                    });
+                    ImportAction::Renamed
+                }
+            }
+        };
+        for dest in alert.destinations.iter_mut() {
+            if let Some(new_name) = destination_names.get(dest) {
+                *dest = new_name.clone();
+            }
+        }
+        let stream_name = alert.stream_name.clone();
+        let target = alert.name.clone();
+        let ret = match action {
+            ImportAction::Overwritten => {
+                alerts::save(org_id, &stream_name, &target, alert, false).await
+            }
+            _ => alerts::save(org_id, &stream_name, "", alert, true).await,
+        };
+        match ret {
+            Ok(_) => report.push(KIND_ALERT, &name, action, renamed(&name, &target), None),
+            Err(e) => report.failed(KIND_ALERT, &name, e),
+        }
+    }
+
+    report
+}
+
+/// The action for an item named `name`, and the name it is imported under,
+/// None when it is skipped.
+fn resolve(
+    name: &str,
+    existing: &HashSet<String>,
+    strategy: ConflictStrategy,
+) -> Option<(ImportAction, String)> {
+    if !existing.contains(name) {
+        return Some((ImportAction::Created, name.to_string()));
+    }
+    match strategy {
+        ConflictStrategy::Skip => None,
+        ConflictStrategy::Overwrite => Some((ImportAction::Overwritten, name.to_string())),
+        ConflictStrategy::Rename => Some((
+            ImportAction::Renamed,
+            rename_candidate(name, |n| existing.contains(n)),
+        )),
+    }
+}
+
+fn renamed(name: &str, target: &str) -> Option<String> {
+    (name != target).then(|| target.to_string())
+}
+
+fn names<T>(list: Result<Vec<T>, anyhow::Error>, f: impl Fn(&T) -> String) -> HashSet<String> {
+    list.map(|list| list.iter().map(f).collect())
+        .unwrap_or_default()
+}
+
+fn alert_key(alert: &crate::common::meta::alerts::Alert) -> String {
+    format!("{}/{}/{}", alert.stream_type, alert.stream_name, alert.name)
+}
+
+// the dashboard as stored, without the version wrapper
+fn stored_dashboard(dashboard: &Dashboard) -> Option<json::Value> {
+    match dashboard.version {
+        1 => json::to_value(dashboard.v1.as_ref()?).ok(),
+        2 => json::to_value(dashboard.v2.as_ref()?).ok(),
+        _ => json::to_value(dashboard.v3.as_ref()?).ok(),
+    }
+}
+
+// the message of a failed response of the functions service
+fn response_error(resp: Result<HttpResponse, std::io::Error>) -> Option<String> {
+    let resp = match resp {
+        Ok(resp) => resp,
+        Err(e) => return Some(e.to_string()),
+    };
+    if resp.status().is_success() {
+        return None;
+    }
+    let status = resp.status();
+    let body = resp.into_body().try_into_bytes().unwrap_or_default();
+    let message = json::from_slice::<MetaHttpResponse>(&body)
+        .ok()
+        .map(|r| r.message)
+        .unwrap_or_else(|| status.to_string());
+    Some(message)
+}
+
+impl ImportReport {
+    fn push(
+        &mut self,
+        kind: &str,
+        name: &str,
+        action: ImportAction,
+        new_name: Option<String>,
+        error: Option<String>,
+    ) {
+        self.results.push(ImportResult {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            action,
+            new_name,
+            error,
+        });
+    }
+
+    fn failed(&mut self, kind: &str, name: &str, error: impl ToString) {
+        self.push(
+            kind,
+            name,
+            ImportAction::Failed,
+            None,
+            Some(error.to_string()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let existing = HashSet::from(["a".to_string(), "a_copy".to_string()]);
+        assert_eq!(
+            resolve("b", &existing, ConflictStrategy::Skip),
+            Some((ImportAction::Created, "b".to_string()))
+        );
+        assert_eq!(resolve("a", &existing, ConflictStrategy::Skip), None);
+        assert_eq!(
+            resolve("a", &existing, ConflictStrategy::Overwrite),
+            Some((ImportAction::Overwritten, "a".to_string()))
+        );
+        assert_eq!(
+            resolve("a", &existing, ConflictStrategy::Rename),
+            Some((ImportAction::Renamed, "a_copy_2".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let bundle = Bundle {
+            version: BUNDLE_VERSION,
+            org_id: "dev".to_string(),
+            ..Default::default()
+        };
+        for gzip in [false, true] {
+            let data = encode(&bundle, gzip).unwrap();
+            assert_eq!(data.starts_with(&[0x1f, 0x8b]), gzip);
+            assert_eq!(decode(&data).unwrap().org_id, "dev");
+        }
+        assert!(decode(br#"{"version":99}"#).is_err());
+        assert!(decode(b"{}").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Usage and flushing of the in-process caches of the local node.
+
+use config::{metrics, utils::schema_ext::SchemaExt};
+use datafusion::execution::cache::CacheAccessor;
+use infra::{
+    cache::{self, file_data},
+    file_list,
+    schema::STREAM_SCHEMAS_LATEST,
+};
+
+use crate::{
+    common::meta::caches::CacheInfo,
+    service::{db, search::datafusion::storage::file_statistics_cache},
+};
+
+/// Lists the caches of the node and refreshes their entries and bytes gauges.
+pub async fn list() -> Vec<CacheInfo> {
+    let mut caches = Vec::with_capacity(8);
+
+    let (max_bytes, bytes) = file_data::memory::stats().await;
+    caches.push(CacheInfo {
+        name: cache::FILE_DATA_MEMORY.to_string(),
+        entries: file_data::memory::len().await,
+        bytes,
+        max_bytes,
+        flushable: true,
+    });
+    let (max_bytes, bytes) = file_data::disk::stats().await;
+    caches.push(CacheInfo {
+        name: cache::FILE_DATA_DISK.to_string(),
+        entries: file_data::disk::len().await,
+        bytes,
+        max_bytes,
+        flushable: true,
+    });
+    let (max_bytes, bytes) = cache::record_batch::stats();
+    caches.push(CacheInfo {
+        name: cache::RECORD_BATCH.to_string(),
+        entries: cache::record_batch::len(),
+        bytes,
+        max_bytes,
+        flushable: true,
+    });
+
+    let r = STREAM_SCHEMAS_LATEST.read().await;
+    let bytes = r
+        .iter()
+        .map(|(key, schema)| key.len() + schema.schema().size())
+        .sum();
+    caches.push(CacheInfo {
+        name: cache::SCHEMA.to_string(),
+        entries: r.len(),
+        bytes,
+        max_bytes: 0,
+        flushable: true,
+    });
+    drop(r);
+
+    caches.push(CacheInfo {
+        name: cache::STREAM_STATS.to_string(),
+        entries: cache::stats::get_stream_stats_len(),
+        bytes: cache::stats::get_stream_stats_in_memory_size(),
+        max_bytes: 0,
+        flushable: true,
+    });
+    caches.push(CacheInfo {
+        name: cache::FILE_STATISTICS.to_string(),
+        entries: file_statistics_cache::GLOBAL_CACHE.len(),
+        bytes: 0,
+        max_bytes: 0,
+        flushable: true,
+    });
+    caches.push(CacheInfo {
+        name: cache::NEGATIVE.to_string(),
+        entries: cache::negative::len(),
+        bytes: 0,
+        max_bytes: 0,
+        flushable: true,
+    });
+    // the local file list is the source of the file list, not a copy to drop
+    caches.push(CacheInfo {
+        name: cache::FILE_LIST.to_string(),
+        entries: file_list::len().await,
+        bytes: 0,
+        max_bytes: 0,
+        flushable: false,
+    });
+
+    for info in caches.iter() {
+        metrics::CACHE_ENTRIES
+            .with_label_values(&[&info.name])
+            .set(info.entries as i64);
+        metrics::CACHE_BYTES
+            .with_label_values(&[&info.name])
+            .set(info.bytes as i64);
+    }
+    caches
+}
+
+/// Flushes a cache of the node, returns the number of entries dropped. The
+/// schema and stream stats caches are loaded again from the meta store in
+/// place, so that nothing reads them empty.
+pub async fn flush(name: &str) -> Result<usize, anyhow::Error> {
+    let entries = match name {
+        cache::FILE_DATA_MEMORY => file_data::memory::clear().await,
+        cache::FILE_DATA_DISK => file_data::disk::clear().await?,
+        cache::RECORD_BATCH => cache::record_batch::clear(),
+        cache::SCHEMA => {
+            let entries = STREAM_SCHEMAS_LATEST.read().await.len();
+            db::schema::cache().await?;
+            entries
+        }
+        cache::STREAM_STATS => {
+            let entries = cache::stats::get_stream_stats_len();
+            db::file_list::remote::cache_stats().await?;
+            entries
+        }
+        cache::FILE_STATISTICS => {
+            let entries = file_statistics_cache::GLOBAL_CACHE.len();
+            file_statistics_cache::GLOBAL_CACHE.clear();
+            entries
+        }
+        cache::NEGATIVE => cache::negative::clear(),
+        _ => return Err(anyhow::anyhow!("Cache {name} can not be flushed")),
+    };
+    log::info!("[CACHES] flushed {name}, {entries} entries");
+    Ok(entries)
+}
@@ -57,6 +57,9 @@ use crate::{
 };
 
 #[derive(Clone)]
+// late partitions merged by a run of the compactor on a stream
+const LATE_PARTITIONS_PER_RUN: usize = 24;
+
 pub struct MergeBatch {
     pub batch_id: usize,
     pub org_id: String,
@@ -134,6 +137,25 @@ pub async fn merge_by_stream(
         return Ok(()); // no data
     }
 
+    // merge the files written into the partitions already compacted
+    if let Err(e) = merge_late_partitions(
+        worker_tx.clone(),
+        org_id,
+        stream_type,
+        stream_name,
+        partition_time_level,
+    )
+    .await
+    {
+        log::error!(
+            "[COMPACTOR] merge late files [{}/{}/{}] error: {}",
+            org_id,
+            stream_type,
+            stream_name,
+            e
+        );
+    }
+
     log::debug!(
         "[COMPACTOR] merge_by_stream [{}/{}/{}] offset: {}",
         org_id,
@@ -168,6 +190,23 @@ pub async fn merge_by_stream(
         .timestamp_micros();
 
     let cfg = get_config();
+    // the files of the hours compacted already were merged, their content
+    // hashes don't match any file anymore
+    if cfg.common.feature_file_dedup_enabled {
+        if let Err(e) =
+            db::file_list::dedup::del_before(org_id, stream_type, stream_name, offset_time_hour)
+                .await
+        {
+            log::error!(
+                "[COMPACTOR] delete content hashes [{}/{}/{}] error: {}",
+                org_id,
+                stream_type,
+                stream_name,
+                e
+            );
+        }
+    }
+
     // check offset
     let time_now: DateTime<Utc> = Utc::now();
     let time_now_hour = Utc
@@ -288,6 +327,59 @@ pub async fn merge_by_stream(
         return Ok(());
     }
 
+    let stream_stats = merge_partitions(worker_tx, org_id, stream_type, stream_name, files).await?;
+
+    // write new offset
+    let offset = offset
+        + Duration::try_seconds(cfg.compact.step_secs)
+            .unwrap()
+            .num_microseconds()
+            .unwrap();
+    db::compact::files::set_offset(
+        org_id,
+        stream_type,
+        stream_name,
+        offset,
+        Some(&LOCAL_NODE_UUID.clone()),
+    )
+    .await?;
+
+    // update stream stats
+    if stream_stats.doc_num != 0 {
+        infra_file_list::set_stream_stats(
+            org_id,
+            &[(
+                format!("{org_id}/{stream_type}/{stream_name}"),
+                stream_stats,
+            )],
+        )
+        .await?;
+    }
+
+    // metrics
+    let time = start.elapsed().as_secs_f64();
+    metrics::COMPACT_USED_TIME
+        .with_label_values(&[org_id, stream_type.to_string().as_str()])
+        .inc_by(time);
+    metrics::COMPACT_DELAY_HOURS
+        .with_label_values(&[org_id, stream_name, stream_type.to_string().as_str()])
+        .set(
+            (time_now_hour - offset_time_hour)
+                / Duration::try_hours(1).unwrap().num_microseconds().unwrap(),
+        );
+
+    Ok(())
+}
+
+/// Merges the small files of each partition key prefix, returns the stats
+/// of the merged files.
+async fn merge_partitions(
+    worker_tx: mpsc::Sender<(MergeSender, MergeBatch)>,
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    files: Vec<FileKey>,
+) -> Result<StreamStats, anyhow::Error> {
     // do partition by partition key
     let mut partition_files_with_size: HashMap<String, Vec<FileKey>> = HashMap::default();
     for file in files {
@@ -301,7 +393,7 @@ pub async fn merge_by_stream(
     let mut stream_stats = StreamStats::default();
 
     // use mutiple threads to merge
-    let semaphore = std::sync::Arc::new(Semaphore::new(cfg.limit.file_move_thread_num));
+    let semaphore = std::sync::Arc::new(Semaphore::new(get_config().limit.file_move_thread_num));
     let mut tasks = Vec::with_capacity(partition_files_with_size.len());
     for (prefix, files_with_size) in partition_files_with_size.into_iter() {
         let org_id = org_id.to_string();
@@ -445,48 +537,77 @@ pub async fn merge_by_stream(
         task.await??;
     }
 
-    // write new offset
-    let offset = offset
-        + Duration::try_seconds(cfg.compact.step_secs)
-            .unwrap()
-            .num_microseconds()
-            .unwrap();
-    db::compact::files::set_offset(
-        org_id,
-        stream_type,
-        stream_name,
-        offset,
-        Some(&LOCAL_NODE_UUID.clone()),
-    )
-    .await?;
+    Ok(stream_stats)
+}
 
-    // update stream stats
-    if stream_stats.doc_num != 0 {
-        infra_file_list::set_stream_stats(
+/// Merges again the partitions where files were written after the compactor
+/// passed them, such as backfilled data, so that they don't stay as small
+/// files next to the merged ones.
+async fn merge_late_partitions(
+    worker_tx: mpsc::Sender<(MergeSender, MergeBatch)>,
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    partition_time_level: PartitionTimeLevel,
+) -> Result<(), anyhow::Error> {
+    let late = db::compact::files::list_late(org_id, stream_type, stream_name).await?;
+    let mut merged = HashSet::new();
+    for hour in late.into_iter().take(LATE_PARTITIONS_PER_RUN) {
+        // unmark first, a file written during the merge marks the hour again
+        db::compact::files::del_late(org_id, stream_type, stream_name, hour).await?;
+        let (start, end) = partition_range(hour, partition_time_level);
+        if !merged.insert(start) {
+            continue;
+        }
+        let files = file_list::query(
             org_id,
-            &[(
-                format!("{org_id}/{stream_type}/{stream_name}"),
-                stream_stats,
-            )],
+            stream_name,
+            stream_type,
+            partition_time_level,
+            start,
+            end,
+            true,
         )
-        .await?;
-    }
-
-    // metrics
-    let time = start.elapsed().as_secs_f64();
-    metrics::COMPACT_USED_TIME
-        .with_label_values(&[org_id, stream_type.to_string().as_str()])
-        .inc_by(time);
-    metrics::COMPACT_DELAY_HOURS
-        .with_label_values(&[org_id, stream_name, stream_type.to_string().as_str()])
-        .set(
-            (time_now_hour - offset_time_hour)
-                / Duration::try_hours(1).unwrap().num_microseconds().unwrap(),
+        .await
+        .map_err(|e| anyhow::anyhow!("query late file list failed: {}", e))?;
+        log::info!(
+            "[COMPACTOR] merge late files [{}/{}/{}] time range: [{},{}], files: {}",
+            org_id,
+            stream_type,
+            stream_name,
+            start,
+            end,
+            files.len(),
         );
-
+        let stream_stats =
+            merge_partitions(worker_tx.clone(), org_id, stream_type, stream_name, files).await?;
+        if stream_stats.doc_num != 0 {
+            infra_file_list::set_stream_stats(
+                org_id,
+                &[(
+                    format!("{org_id}/{stream_type}/{stream_name}"),
+                    stream_stats,
+                )],
+            )
+            .await?;
+        }
+    }
     Ok(())
 }
 
+/// The time range of the hour or the day partition holding `ts`.
+fn partition_range(ts: i64, partition_time_level: PartitionTimeLevel) -> (i64, i64) {
+    let len = if partition_time_level == PartitionTimeLevel::Daily {
+        Duration::try_hours(24).unwrap()
+    } else {
+        Duration::try_hours(1).unwrap()
+    }
+    .num_microseconds()
+    .unwrap();
+    let start = ts - ts.rem_euclid(len);
+    (start, start + len - 1)
+}
+
 /// merge some small files into one big file, upload to storage, returns the big
 /// file key and merged files
 pub async fn merge_files(
@@ -792,7 +913,7 @@ pub async fn merge_files(
     }
 }
 
-async fn write_file_list(org_id: &str, events: &[FileKey]) -> Result<(), anyhow::Error> {
+pub(crate) async fn write_file_list(org_id: &str, events: &[FileKey]) -> Result<(), anyhow::Error> {
     if events.is_empty() {
         return Ok(());
     }
@@ -1214,3 +1335,26 @@ pub async fn merge_parquet_files_v2(
 
     Ok((schema, vec![final_record_batch]))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_range() {
+        let hour = 3_600_000_000;
+        let ts = 1_700_000_000_000_000; // 2023-11-14T22:13:20Z
+        assert_eq!(
+            partition_range(ts, PartitionTimeLevel::Hourly),
+            (1_699_999_200_000_000, 1_699_999_200_000_000 + hour - 1)
+        );
+        assert_eq!(
+            partition_range(ts, PartitionTimeLevel::Daily),
+            (1_699_920_000_000_000, 1_699_920_000_000_000 + 24 * hour - 1)
+        );
+        assert_eq!(
+            partition_range(1_699_999_200_000_000, PartitionTimeLevel::Hourly).0,
+            1_699_999_200_000_000
+        );
+    }
+}
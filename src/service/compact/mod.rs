@@ -130,6 +130,11 @@ pub async fn run_retention() -> Result<(), anyhow::Error> {
         }
     }
 
+    // delete the expired ephemeral streams
+    if let Err(e) = delete_expired_streams().await {
+        log::error!("[COMPACTOR] delete expired streams error: {}", e);
+    }
+
     // delete files
     let jobs = db::compact::retention::list().await?;
     for job in jobs {
@@ -167,6 +172,40 @@ pub async fn run_retention() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Deletes the data, schema and settings of the ephemeral streams past their
+/// `expires_at`.
+async fn delete_expired_streams() -> Result<(), anyhow::Error> {
+    let now = Utc::now().timestamp_micros();
+    let expired = infra::schema::STREAM_SETTINGS
+        .read()
+        .await
+        .iter()
+        .filter(|(_, settings)| settings.is_expired(now))
+        .map(|(key, _)| key.to_string())
+        .collect::<Vec<_>>();
+    for key in expired {
+        let columns = key.splitn(3, '/').collect::<Vec<&str>>();
+        if columns.len() != 3 {
+            continue;
+        }
+        let org_id = columns[0];
+        let stream_type = StreamType::from(columns[1]);
+        let stream_name = columns[2];
+        if db::compact::retention::is_deleting_stream(org_id, stream_type, stream_name, None) {
+            continue;
+        }
+        log::info!("[COMPACTOR] deleting the expired stream [{key}]");
+        let resp = super::stream::delete_stream(org_id, stream_name, stream_type).await?;
+        if !resp.status().is_success() {
+            log::error!(
+                "[COMPACTOR] delete the expired stream [{key}] error: {}",
+                resp.status()
+            );
+        }
+    }
+    Ok(())
+}
+
 /// compactor merge run steps:
 /// 1. get all organization
 /// 2. range streams by organization & stream_type
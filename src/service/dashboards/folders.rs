@@ -22,7 +22,7 @@ use crate::{
     common::{
         meta::{
             authz::Authz,
-            dashboards::{Folder, FolderList, DEFAULT_FOLDER},
+            dashboards::{creates_cycle, Folder, FolderList, DEFAULT_FOLDER},
             http::HttpResponse as MetaHttpResponse,
         },
         utils::auth::{remove_ownership, set_ownership},
@@ -57,6 +57,9 @@ pub async fn save_folder(
     if folder.folder_id != DEFAULT_FOLDER {
         folder.folder_id = ider::generate();
     }
+    if let Err(e) = check_parent(org_id, &folder).await {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
 
     match db::dashboards::folders::put(org_id, folder).await {
         Ok(folder) => {
@@ -87,6 +90,9 @@ pub async fn update_folder(
         );
     }
     folder.folder_id = folder_id.to_string();
+    if let Err(e) = check_parent(org_id, &folder).await {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
 
     if let Err(error) = db::dashboards::folders::put(org_id, folder).await {
         return Ok(
@@ -156,6 +162,19 @@ pub async fn delete_folder(org_id: &str, folder_id: &str) -> Result<HttpResponse
         )));
     }
 
+    let folders = db::dashboards::folders::list(org_id)
+        .await
+        .unwrap_or_default();
+    if folders
+        .iter()
+        .any(|f| f.parent_id.as_deref() == Some(folder_id))
+    {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            "Dashboard folder contains folders, please move/delete them first".to_string(),
+        )));
+    }
+
     if db::dashboards::folders::get(org_id, folder_id)
         .await
         .is_err()
@@ -181,3 +200,23 @@ pub async fn delete_folder(org_id: &str, folder_id: &str) -> Result<HttpResponse
         ),
     }
 }
+
+// the parent folder must exist and can't be the folder or one of its subfolders
+async fn check_parent(org_id: &str, folder: &Folder) -> Result<(), String> {
+    let Some(parent_id) = folder.parent_id.as_deref() else {
+        return Ok(());
+    };
+    if folder.folder_id == DEFAULT_FOLDER {
+        return Err("default Dashboard folder can't have a parent".to_string());
+    }
+    let folders = db::dashboards::folders::list(org_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !folders.iter().any(|f| f.folder_id == parent_id) {
+        return Err(format!("parent folder {parent_id} not found"));
+    }
+    if creates_cycle(&folders, &folder.folder_id, parent_id) {
+        return Err("a folder can't be moved into itself or one of its subfolders".to_string());
+    }
+    Ok(())
+}
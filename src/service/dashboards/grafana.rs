@@ -0,0 +1,826 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Conversion of the Grafana dashboards into native dashboards.
+//!
+//! Only the Prometheus targets are translated, their PromQL is used as is. The
+//! panels, targets and variables that can't be translated are left out and
+//! reported in the response.
+
+use std::io;
+
+use actix_web::{web, HttpResponse};
+use config::{
+    ider,
+    utils::json::{self, json, Map, Value},
+};
+
+use crate::{
+    common::{
+        meta::{
+            authz::Authz,
+            dashboards::{
+                grafana::{GrafanaImportResponse, UnsupportedItem},
+                v3, Dashboard, Folder, DEFAULT_FOLDER,
+            },
+            http::HttpResponse as MetaHttpResponse,
+        },
+        utils::auth::set_ownership,
+    },
+    service::db::dashboards,
+};
+
+/// Grafana grid has 24 columns, ours has 48, both use 30px rows.
+const GRID_COLUMN_RATIO: i64 = 2;
+
+/// Imports the Grafana dashboard into the folder, `body` is the dashboard
+/// JSON, or the response of the Grafana dashboard API which wraps it in a
+/// `dashboard` field.
+#[tracing::instrument(skip(body))]
+pub async fn import(
+    org_id: &str,
+    folder_id: &str,
+    body: web::Bytes,
+) -> Result<HttpResponse, io::Error> {
+    let grafana: Value = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let grafana = match grafana.get("dashboard") {
+        Some(dashboard) if dashboard.is_object() => dashboard,
+        _ => &grafana,
+    };
+    let (dashboard, unsupported) = match convert(grafana) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+
+    if dashboards::folders::get(org_id, folder_id).await.is_err() {
+        if folder_id != DEFAULT_FOLDER {
+            return Ok(MetaHttpResponse::not_found("folder not found"));
+        }
+        let folder = Folder {
+            folder_id: DEFAULT_FOLDER.to_string(),
+            name: DEFAULT_FOLDER.to_string(),
+            description: DEFAULT_FOLDER.to_string(),
+            parent_id: None,
+        };
+        super::folders::save_folder(org_id, folder, true).await?;
+    }
+
+    let dashboard_id = ider::generate();
+    let body = json::to_vec(&dashboard).unwrap();
+    let dashboard = match dashboards::put(org_id, &dashboard_id, folder_id, body.into()).await {
+        Ok(dashboard) => dashboard,
+        Err(e) => {
+            tracing::error!(%e, dashboard_id, "Failed to store the imported dashboard");
+            return Ok(MetaHttpResponse::internal_error(e));
+        }
+    };
+    set_ownership(
+        org_id,
+        "dashboards",
+        Authz {
+            obj_id: dashboard_id,
+            parent_type: "folders".to_owned(),
+            parent: folder_id.to_owned(),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(GrafanaImportResponse {
+        dashboard,
+        unsupported,
+    }))
+}
+
+/// Converts the Grafana dashboard into a v3 dashboard, returns the dashboard
+/// and the items that could not be converted.
+pub fn convert(grafana: &Value) -> Result<(Dashboard, Vec<UnsupportedItem>), String> {
+    let Some(grafana) = grafana.as_object() else {
+        return Err("Grafana dashboard should be a JSON object".to_string());
+    };
+    let title = str_field(grafana, "title").trim().to_string();
+    if title.is_empty() {
+        return Err("Grafana dashboard should have a title".to_string());
+    }
+    let inputs = grafana
+        .get("__inputs")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let mut unsupported = Vec::new();
+
+    let mut panels = Vec::new();
+    let grafana_panels = grafana
+        .get("panels")
+        .and_then(|v| v.as_array())
+        .map(|v| flatten_panels(v))
+        .unwrap_or_default();
+    for (i, panel) in grafana_panels.into_iter().enumerate() {
+        match convert_panel(panel, i, &inputs, &mut unsupported) {
+            Ok(panel) => panels.push(panel),
+            Err(item) => unsupported.push(item),
+        }
+    }
+
+    let mut variables = Vec::new();
+    let list = grafana
+        .get("templating")
+        .and_then(|v| v.get("list"))
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    for var in list.iter().filter_map(|v| v.as_object()) {
+        match convert_variable(var) {
+            Ok(var) => variables.push(var),
+            Err(reason) => unsupported.push(UnsupportedItem {
+                kind: "variable".to_string(),
+                name: str_field(var, "name").to_string(),
+                type_field: str_field(var, "type").to_string(),
+                reason,
+            }),
+        }
+    }
+
+    let tags = grafana
+        .get("tags")
+        .and_then(|v| v.as_array())
+        .map(|v| {
+            v.iter()
+                .filter_map(|t| t.as_str().map(|t| t.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut dashboard = json!({
+        "version": 3,
+        "title": title,
+        "description": str_field(grafana, "description"),
+        "tags": tags,
+        "tabs": [{"tabId": "default", "name": "Default", "panels": panels}],
+        "variables": {"list": variables, "showDynamicFilters": false},
+    });
+    if let Some(duration) = grafana.get("time").and_then(relative_time) {
+        dashboard["defaultDatetimeDuration"] =
+            json!({"type": "relative", "relativeTimePeriod": duration});
+    }
+    let dashboard: v3::Dashboard = json::from_value(dashboard).map_err(|e| e.to_string())?;
+    Ok((
+        Dashboard {
+            v3: Some(dashboard),
+            version: 3,
+            ..Default::default()
+        },
+        unsupported,
+    ))
+}
+
+// the panels of the rows, collapsed rows keep them in their `panels` field
+fn flatten_panels(panels: &[Value]) -> Vec<&Value> {
+    let mut flattened = Vec::new();
+    for panel in panels {
+        if panel.get("type").and_then(|v| v.as_str()) == Some("row") {
+            if let Some(nested) = panel.get("panels").and_then(|v| v.as_array()) {
+                flattened.extend(nested.iter());
+            }
+        } else {
+            flattened.push(panel);
+        }
+    }
+    flattened
+}
+
+fn panel_type(panel: &Map<String, Value>) -> Option<&'static str> {
+    let typ = match str_field(panel, "type") {
+        "graph" => {
+            if panel.get("bars").and_then(|v| v.as_bool()) == Some(true) {
+                "bar"
+            } else {
+                "line"
+            }
+        }
+        "timeseries" => {
+            let style = panel
+                .get("fieldConfig")
+                .and_then(|v| v.pointer("/defaults/custom/drawStyle"))
+                .and_then(|v| v.as_str());
+            if style == Some("bars") { "bar" } else { "line" }
+        }
+        "barchart" => "bar",
+        "bargauge" => "h-bar",
+        "stat" | "singlestat" => "metric",
+        "gauge" => "gauge",
+        "piechart" | "grafana-piechart-panel" => "pie",
+        "table" | "table-old" => "table",
+        "heatmap" => "heatmap",
+        "text" => "markdown",
+        _ => return None,
+    };
+    Some(typ)
+}
+
+fn convert_panel(
+    panel: &Value,
+    index: usize,
+    inputs: &[Value],
+    unsupported: &mut Vec<UnsupportedItem>,
+) -> Result<Value, UnsupportedItem> {
+    let empty = Map::new();
+    let panel = panel.as_object().unwrap_or(&empty);
+    let title = str_field(panel, "title").to_string();
+    let grafana_type = str_field(panel, "type").to_string();
+    let Some(typ) = panel_type(panel) else {
+        return Err(UnsupportedItem {
+            kind: "panel".to_string(),
+            name: title,
+            type_field: grafana_type,
+            reason: "panel type is not supported".to_string(),
+        });
+    };
+
+    let id = match panel.get("id").and_then(|v| v.as_i64()) {
+        Some(id) => format!("Panel_ID{id}"),
+        None => format!("Panel_ID{}", index + 1),
+    };
+    let grid = panel.get("gridPos");
+    let grid = |field: &str, default: i64| {
+        grid.and_then(|v| v.get(field))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(default)
+    };
+    let layout = json!({
+        "x": grid("x", 0) * GRID_COLUMN_RATIO,
+        "y": grid("y", 0),
+        "w": grid("w", 12) * GRID_COLUMN_RATIO,
+        "h": grid("h", 8),
+        "i": index + 1,
+    });
+    let mut native = json!({
+        "id": id,
+        "type": typ,
+        "title": title,
+        "description": str_field(panel, "description"),
+        "config": panel_config(panel),
+        "queryType": "promql",
+        "queries": [],
+        "layout": layout,
+    });
+
+    if typ == "markdown" {
+        let content = panel
+            .get("options")
+            .and_then(|v| v.get("content"))
+            .or_else(|| panel.get("content"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        native["queryType"] = json!("sql");
+        native["markdownContent"] = json!(content);
+        return Ok(native);
+    }
+
+    let panel_ds = datasource_type(panel.get("datasource"), inputs);
+    let mut queries = Vec::new();
+    for target in panel
+        .get("targets")
+        .and_then(|v| v.as_array())
+        .map(|v| v.as_slice())
+        .unwrap_or_default()
+    {
+        let target_ds = datasource_type(target.get("datasource"), inputs);
+        let ds = match (target_ds, panel_ds.as_deref()) {
+            (Some(ds), _) => Some(ds),
+            (None, Some(ds)) if ds != "mixed" => Some(ds.to_string()),
+            _ => None,
+        };
+        match convert_target(target, ds.as_deref()) {
+            Ok(query) => queries.push(query),
+            Err(reason) => unsupported.push(UnsupportedItem {
+                kind: "target".to_string(),
+                name: title.clone(),
+                type_field: ds.unwrap_or_default(),
+                reason,
+            }),
+        }
+    }
+    if queries.is_empty() {
+        return Err(UnsupportedItem {
+            kind: "panel".to_string(),
+            name: title,
+            type_field: grafana_type,
+            reason: "panel has no PromQL query".to_string(),
+        });
+    }
+    native["queries"] = json!(queries);
+    Ok(native)
+}
+
+fn convert_target(target: &Value, datasource: Option<&str>) -> Result<Value, String> {
+    if let Some(ds) = datasource {
+        if ds != "prometheus" {
+            return Err(format!("datasource {ds} is not supported"));
+        }
+    }
+    if target.get("hide").and_then(|v| v.as_bool()) == Some(true) {
+        return Err("hidden target".to_string());
+    }
+    let expr = target
+        .get("expr")
+        .and_then(|v| v.as_str())
+        .map(|v| v.trim())
+        .unwrap_or_default();
+    if expr.is_empty() {
+        return Err("target has no PromQL expression".to_string());
+    }
+    let legend = target
+        .get("legendFormat")
+        .and_then(|v| v.as_str())
+        .map(legend_format)
+        .unwrap_or_default();
+    Ok(json!({
+        "query": expr,
+        "customQuery": true,
+        "fields": {
+            "stream": metric_name(expr).unwrap_or_default(),
+            "stream_type": "metrics",
+            "x": [],
+            "y": [],
+            "z": [],
+            "filter": [],
+        },
+        "config": {"promql_legend": legend},
+    }))
+}
+
+fn panel_config(panel: &Map<String, Value>) -> Value {
+    let legend = panel.get("options").and_then(|v| v.get("legend"));
+    let show_legends = legend
+        .and_then(|v| v.get("showLegend"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    let position = match legend
+        .and_then(|v| v.get("placement"))
+        .and_then(|v| v.as_str())
+    {
+        Some("right") => json!("right"),
+        _ => Value::Null,
+    };
+    let mut config = json!({
+        "show_legends": show_legends,
+        "legends_position": position,
+    });
+    let defaults = panel.get("fieldConfig").and_then(|v| v.get("defaults"));
+    if let Some(grafana_unit) = defaults
+        .and_then(|v| v.get("unit"))
+        .and_then(|v| v.as_str())
+    {
+        match unit(grafana_unit) {
+            // our default unit
+            _ if matches!(grafana_unit, "" | "short" | "none") => {}
+            Some(unit) => config["unit"] = json!(unit),
+            None => {
+                config["unit"] = json!("custom");
+                config["unit_custom"] = json!(grafana_unit);
+            }
+        }
+    }
+    if let Some(decimals) = defaults
+        .and_then(|v| v.get("decimals"))
+        .and_then(|v| v.as_f64())
+    {
+        config["decimals"] = json!(decimals);
+    }
+    config
+}
+
+// maps the Grafana units having an equivalent
+fn unit(grafana: &str) -> Option<&'static str> {
+    let unit = match grafana {
+        "bytes" | "decbytes" => "bytes",
+        "kbytes" | "deckbytes" => "kilobytes",
+        "mbytes" | "decmbytes" => "megabytes",
+        "Bps" | "binBps" => "bps",
+        "s" => "seconds",
+        "ms" => "milliseconds",
+        "µs" | "us" => "microseconds",
+        "ns" => "nanoseconds",
+        "percent" => "percent",
+        "percentunit" => "percent-1",
+        _ => return None,
+    };
+    Some(unit)
+}
+
+/// Type of the datasource, the exported dashboards reference their
+/// datasources with `${DS_NAME}` inputs.
+fn datasource_type(datasource: Option<&Value>, inputs: &[Value]) -> Option<String> {
+    match datasource? {
+        Value::Object(ds) => ds
+            .get("type")
+            .and_then(|v| v.as_str())
+            .map(normalize_datasource),
+        Value::String(name) => {
+            let name = name.trim_start_matches("${").trim_end_matches('}');
+            if name.eq_ignore_ascii_case("-- mixed --") {
+                return Some("mixed".to_string());
+            }
+            inputs
+                .iter()
+                .find(|i| i.get("name").and_then(|v| v.as_str()) == Some(name))
+                .and_then(|i| i.get("pluginId"))
+                .and_then(|v| v.as_str())
+                .map(normalize_datasource)
+        }
+        _ => None,
+    }
+}
+
+fn normalize_datasource(typ: &str) -> String {
+    match typ {
+        "-- Mixed --" | "datasource" => "mixed".to_string(),
+        _ => typ.to_lowercase(),
+    }
+}
+
+fn convert_variable(var: &Map<String, Value>) -> Result<Value, String> {
+    let name = str_field(var, "name");
+    if name.is_empty() {
+        return Err("variable has no name".to_string());
+    }
+    let label = match str_field(var, "label") {
+        "" => name,
+        label => label,
+    };
+    let current = var
+        .get("current")
+        .and_then(|v| v.get("value"))
+        .and_then(|v| match v {
+            Value::String(v) => Some(v.clone()),
+            Value::Array(v) => v.first().and_then(|v| v.as_str()).map(|v| v.to_string()),
+            _ => None,
+        });
+    let multi_select = var.get("multi").and_then(|v| v.as_bool()) == Some(true);
+    let mut native = json!({
+        "name": name,
+        "label": label,
+        "query_data": null,
+        "value": current.clone(),
+        "options": null,
+    });
+    match str_field(var, "type") {
+        "query" => {
+            let query = match var.get("query") {
+                Some(Value::String(q)) => q.as_str(),
+                Some(Value::Object(q)) => q.get("query").and_then(|v| v.as_str()).unwrap_or(""),
+                _ => "",
+            };
+            let Some((stream, field)) = label_values(query) else {
+                return Err(format!(
+                    "query {query} is not supported, only label_values(metric, label) is"
+                ));
+            };
+            native["type"] = json!(v3::VARIABLE_QUERY_VALUES);
+            native["query_data"] = json!({
+                "stream_type": "metrics",
+                "stream": stream,
+                "field": field,
+                "max_record_size": null,
+            });
+            native["value"] = json!(current.filter(|v| v != "$__all"));
+            if multi_select {
+                native["multiSelect"] = json!(true);
+            }
+        }
+        "custom" | "interval" => {
+            let options = str_field(var, "query")
+                .split(',')
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(|v| json!({"label": v, "value": v}))
+                .collect::<Vec<_>>();
+            native["type"] = if str_field(var, "type") == "custom" {
+                json!(v3::VARIABLE_CUSTOM)
+            } else {
+                json!(v3::VARIABLE_INTERVAL)
+            };
+            native["options"] = json!(options);
+            if multi_select {
+                native["multiSelect"] = json!(true);
+            }
+        }
+        "constant" => {
+            native["type"] = json!(v3::VARIABLE_CONSTANT);
+            native["value"] = json!(str_field(var, "query"));
+        }
+        "textbox" => {
+            native["type"] = json!(v3::VARIABLE_TEXTBOX);
+            if current.is_none() {
+                native["value"] = json!(str_field(var, "query"));
+            }
+        }
+        typ => return Err(format!("variable type {typ} is not supported")),
+    }
+    Ok(native)
+}
+
+/// Parses `label_values(metric{selector}, label)` into the metric and the
+/// label, a metric is needed to know the stream of the values.
+fn label_values(query: &str) -> Option<(String, String)> {
+    let args = query
+        .trim()
+        .strip_prefix("label_values(")?
+        .strip_suffix(')')?;
+    let (metric, label) = args.rsplit_once(',')?;
+    let metric = metric_name(metric.trim())?;
+    let label = label.trim();
+    if label.is_empty() {
+        None
+    } else {
+        Some((metric, label.to_string()))
+    }
+}
+
+/// First metric name of the PromQL expression.
+fn metric_name(expr: &str) -> Option<String> {
+    const KEYWORDS: [&str; 8] = [
+        "by",
+        "without",
+        "on",
+        "ignoring",
+        "group_left",
+        "group_right",
+        "offset",
+        "bool",
+    ];
+    const AGGREGATIONS: [&str; 12] = [
+        "sum",
+        "min",
+        "max",
+        "avg",
+        "group",
+        "stddev",
+        "stdvar",
+        "count",
+        "count_values",
+        "bottomk",
+        "topk",
+        "quantile",
+    ];
+    let mut chars = expr.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c == '"' || c == '\'' {
+            // skip the label values
+            for (_, n) in chars.by_ref() {
+                if n == c {
+                    break;
+                }
+            }
+            continue;
+        }
+        if c == '{' {
+            // skip the label matchers
+            for (_, n) in chars.by_ref() {
+                if n == '}' {
+                    break;
+                }
+            }
+            continue;
+        }
+        if c == '$' {
+            // skip the dashboard variables
+            while let Some(&(_, n)) = chars.peek() {
+                if n.is_ascii_alphanumeric() || matches!(n, '_' | '{' | '}') {
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            continue;
+        }
+        if c == '[' {
+            for (_, n) in chars.by_ref() {
+                if n == ']' {
+                    break;
+                }
+            }
+            continue;
+        }
+        if !(c.is_ascii_alphabetic() || c == '_' || c == ':') {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, n)) = chars.peek() {
+            if n.is_ascii_alphanumeric() || n == '_' || n == ':' {
+                end = i + n.len_utf8();
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let word = &expr[start..end];
+        let next = expr[end..].trim_start().chars().next();
+        // functions are followed by a parenthesis, aggregations can be
+        // followed by their grouping
+        if next == Some('(') || KEYWORDS.contains(&word) || AGGREGATIONS.contains(&word) {
+            if matches!(
+                word,
+                "by" | "without" | "on" | "ignoring" | "group_left" | "group_right"
+            ) {
+                // skip the label list
+                for (_, n) in chars.by_ref() {
+                    if n == ')' {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        return Some(word.to_string());
+    }
+    None
+}
+
+/// Grafana legends reference the labels with `{{label}}`, ours with `{label}`.
+fn legend_format(format: &str) -> String {
+    let mut legend = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(start) = rest.find("{{") {
+        legend.push_str(&rest[..start]);
+        match rest[start + 2..].find("}}") {
+            Some(end) => {
+                legend.push('{');
+                legend.push_str(rest[start + 2..start + 2 + end].trim());
+                legend.push('}');
+                rest = &rest[start + 2 + end + 2..];
+            }
+            None => {
+                legend.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    legend.push_str(rest);
+    legend
+}
+
+/// Relative period of the Grafana time range, like `6h` for `now-6h` to `now`.
+fn relative_time(time: &Value) -> Option<String> {
+    let from = time.get("from")?.as_str()?;
+    let to = time.get("to")?.as_str()?;
+    if to != "now" {
+        return None;
+    }
+    let period = from.strip_prefix("now-")?;
+    let unit_at = period.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = period.split_at(unit_at);
+    if value.is_empty() || !matches!(unit, "s" | "m" | "h" | "d" | "w" | "M") {
+        return None;
+    }
+    Some(period.to_string())
+}
+
+fn str_field<'a>(obj: &'a Map<String, Value>, field: &str) -> &'a str {
+    obj.get(field).and_then(|v| v.as_str()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metric_name() {
+        assert_eq!(metric_name("up").as_deref(), Some("up"));
+        assert_eq!(
+            metric_name(
+                r#"sum by (job) (rate(http_requests_total{job="$job"}[$__rate_interval]))"#
+            )
+            .as_deref(),
+            Some("http_requests_total")
+        );
+        assert_eq!(
+            metric_name("histogram_quantile(0.99, sum(rate(latency_bucket[5m])) by (le))")
+                .as_deref(),
+            Some("latency_bucket")
+        );
+        assert_eq!(metric_name("$metric{a=\"b\"}"), None);
+        assert_eq!(metric_name("vector(1)"), None);
+    }
+
+    #[test]
+    fn test_label_values() {
+        assert_eq!(
+            label_values(r#"label_values(kube_pod_info{namespace="$ns"}, pod)"#),
+            Some(("kube_pod_info".to_string(), "pod".to_string()))
+        );
+        assert_eq!(label_values("label_values(pod)"), None);
+        assert_eq!(label_values("query_result(up)"), None);
+    }
+
+    #[test]
+    fn test_legend_format() {
+        assert_eq!(
+            legend_format("{{ pod }} - {{container}}"),
+            "{pod} - {container}"
+        );
+        assert_eq!(legend_format("total"), "total");
+        assert_eq!(legend_format("{{broken"), "{{broken");
+    }
+
+    #[test]
+    fn test_relative_time() {
+        assert_eq!(
+            relative_time(&json!({"from": "now-6h", "to": "now"})).as_deref(),
+            Some("6h")
+        );
+        assert_eq!(
+            relative_time(&json!({"from": "now-6h", "to": "now-1h"})),
+            None
+        );
+        assert_eq!(
+            relative_time(&json!({"from": "2024-01-01", "to": "now"})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_convert() {
+        let grafana = json!({
+            "title": "Node",
+            "tags": ["linux"],
+            "time": {"from": "now-1h", "to": "now"},
+            "__inputs": [{"name": "DS_PROMETHEUS", "pluginId": "prometheus"}],
+            "panels": [
+                {
+                    "id": 2,
+                    "type": "timeseries",
+                    "title": "CPU",
+                    "datasource": "${DS_PROMETHEUS}",
+                    "gridPos": {"x": 12, "y": 0, "w": 12, "h": 8},
+                    "fieldConfig": {"defaults": {"unit": "percentunit"}},
+                    "targets": [
+                        {"expr": "rate(node_cpu_seconds_total[5m])", "legendFormat": "{{cpu}}"},
+                        {"datasource": {"type": "loki"}, "expr": "{job=\"x\"}"},
+                    ],
+                },
+                {
+                    "type": "row",
+                    "title": "Logs",
+                    "collapsed": true,
+                    "panels": [
+                        {"id": 3, "type": "logs", "title": "Logs", "targets": []},
+                    ],
+                },
+            ],
+            "templating": {"list": [
+                {"name": "instance", "type": "query", "query": "label_values(up, instance)", "multi": true},
+                {"name": "ds", "type": "datasource"},
+            ]},
+        });
+        let (dashboard, unsupported) = convert(&grafana).unwrap();
+        let dashboard = dashboard.v3.unwrap();
+        assert_eq!(dashboard.title, "Node");
+        assert_eq!(dashboard.tags, vec!["linux"]);
+        let panels = &dashboard.tabs[0].panels;
+        assert_eq!(panels.len(), 1);
+        assert_eq!(panels[0].typ, "line");
+        assert_eq!(panels[0].query_type, "promql");
+        assert_eq!(panels[0].layout.x, 24);
+        assert_eq!(panels[0].layout.w, 24);
+        assert_eq!(panels[0].queries.len(), 1);
+        assert_eq!(panels[0].queries[0].fields.stream, "node_cpu_seconds_total");
+        let variables = dashboard.variables.unwrap().list;
+        assert_eq!(variables.len(), 1);
+        assert_eq!(variables[0].type_field, v3::VARIABLE_QUERY_VALUES);
+        assert!(variables[0].is_multi_select());
+        assert_eq!(variables[0].query_data.as_ref().unwrap().field, "instance");
+
+        let kinds = unsupported
+            .iter()
+            .map(|i| (i.kind.as_str(), i.type_field.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ("target", "loki"),
+                ("panel", "logs"),
+                ("variable", "datasource")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_convert_requires_title() {
+        assert!(convert(&json!({"panels": []})).is_err());
+        assert!(convert(&json!([])).is_err());
+    }
+}
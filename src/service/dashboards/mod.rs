@@ -22,8 +22,12 @@ use crate::{
     common::{
         meta::{
             authz::Authz,
-            dashboards::{Dashboards, Folder, DEFAULT_FOLDER},
+            dashboards::{
+                folder_with_ancestors, folder_with_descendants, DashboardFilter, Dashboards,
+                Folder, DEFAULT_FOLDER,
+            },
             http::HttpResponse as MetaHttpResponse,
+            rbac::Permission,
         },
         utils::auth::{remove_ownership, set_ownership},
     },
@@ -31,13 +35,18 @@ use crate::{
 };
 
 pub mod folders;
+pub mod grafana;
 pub mod reports;
+pub mod snapshots;
+pub mod variables;
+pub mod versions;
 
 #[tracing::instrument(skip(body))]
 pub async fn create_dashboard(
     org_id: &str,
     folder_id: &str,
     body: web::Bytes,
+    user_id: &str,
 ) -> Result<HttpResponse, io::Error> {
     // NOTE: Overwrite whatever `dashboard_id` the client has sent us
     // If folder is default folder & doesn't exist then create it
@@ -45,7 +54,7 @@ pub async fn create_dashboard(
     match dashboards::folders::get(org_id, folder_id).await {
         Ok(_) => {
             let dashboard_id = ider::generate();
-            match save_dashboard(org_id, &dashboard_id, folder_id, body).await {
+            match save_dashboard(org_id, &dashboard_id, folder_id, body, user_id).await {
                 Ok(res) => {
                     set_ownership(
                         org_id,
@@ -68,10 +77,11 @@ pub async fn create_dashboard(
                     folder_id: DEFAULT_FOLDER.to_string(),
                     name: DEFAULT_FOLDER.to_string(),
                     description: DEFAULT_FOLDER.to_string(),
+                    parent_id: None,
                 };
                 folders::save_folder(org_id, folder, true).await?;
                 let dashboard_id = ider::generate();
-                match save_dashboard(org_id, &dashboard_id, folder_id, body).await {
+                match save_dashboard(org_id, &dashboard_id, folder_id, body, user_id).await {
                     Ok(res) => {
                         set_ownership(
                             org_id,
@@ -108,16 +118,94 @@ pub async fn update_dashboard(
     dashboard_id: &str,
     folder_id: &str,
     body: web::Bytes,
+    user_id: &str,
 ) -> Result<HttpResponse, io::Error> {
     // Store new dashboard in the database
-    save_dashboard(org_id, dashboard_id, folder_id, body).await
+    save_dashboard(org_id, dashboard_id, folder_id, body, user_id).await
 }
 
-#[tracing::instrument]
-pub async fn list_dashboards(org_id: &str, folder_id: &str) -> Result<HttpResponse, io::Error> {
-    Ok(HttpResponse::Ok().json(Dashboards {
-        dashboards: dashboards::list(org_id, folder_id).await.unwrap(),
-    }))
+/// Lists the dashboards of the folder, or of every folder when `folder_id` is
+/// `None`, matching the filter.
+///
+/// With `recursive` the dashboards of the subfolders are listed too, only the
+/// dashboards of the folders the user may read are returned.
+#[tracing::instrument(skip(permitted_folders))]
+pub async fn list_dashboards(
+    org_id: &str,
+    folder_id: Option<&str>,
+    recursive: bool,
+    filter: &DashboardFilter,
+    user_id: &str,
+    permitted_folders: Option<Vec<String>>,
+) -> Result<HttpResponse, io::Error> {
+    let folders = dashboards::folders::list(org_id).await.unwrap_or_default();
+    let dashboards = match folder_id {
+        // the listing of a single folder keeps the previous response shape
+        Some(folder_id) if !recursive => dashboards::list(org_id, folder_id).await,
+        Some(folder_id) => {
+            let ids = folder_with_descendants(&folders, folder_id);
+            dashboards::list_all(org_id).await.map(|list| {
+                list.into_iter()
+                    .filter(|d| d.folder_id.as_ref().is_some_and(|f| ids.contains(f)))
+                    .collect()
+            })
+        }
+        None => dashboards::list_all(org_id).await,
+    };
+    let dashboards = match dashboards {
+        Ok(dashboards) => dashboards,
+        Err(error) => return Ok(Response::InternalServerError(error).into()),
+    };
+
+    let dashboards = dashboards
+        .into_iter()
+        .filter(|d| d.matches(filter))
+        .filter(|d| {
+            let folder_id = d
+                .folder_id
+                .as_deref()
+                .or(folder_id)
+                .unwrap_or(DEFAULT_FOLDER);
+            match &permitted_folders {
+                Some(permitted) => {
+                    permitted.contains(&format!("dfolder:_all_{org_id}"))
+                        || permitted.contains(&format!("dfolder:{folder_id}"))
+                }
+                None => {
+                    cfg!(feature = "enterprise")
+                        || is_folder_allowed(org_id, user_id, &folders, folder_id, Permission::Read)
+                        || crate::service::rbac::is_allowed(
+                            org_id,
+                            user_id,
+                            crate::common::meta::rbac::Resource::Dashboard,
+                            d.dashboard_id(),
+                            Permission::Read,
+                        )
+                }
+            }
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(Dashboards { dashboards }))
+}
+
+/// Returns true when the user has the permission on the folder, a grant on a
+/// folder id covers its subfolders.
+pub fn is_folder_allowed(
+    org_id: &str,
+    user_id: &str,
+    folders: &[Folder],
+    folder_id: &str,
+    permission: Permission,
+) -> bool {
+    folder_with_ancestors(folders, folder_id).iter().any(|id| {
+        crate::service::rbac::is_allowed(
+            org_id,
+            user_id,
+            crate::common::meta::rbac::Resource::Dashboard,
+            id,
+            permission,
+        )
+    })
 }
 
 #[tracing::instrument]
@@ -151,6 +239,7 @@ pub async fn delete_dashboard(
     }
     match dashboards::delete(org_id, dashboard_id, folder_id).await {
         Ok(_) => {
+            let _ = dashboards::versions::delete_all(org_id, dashboard_id).await;
             remove_ownership(
                 org_id,
                 "dashboards",
@@ -180,10 +269,15 @@ async fn save_dashboard(
     dashboard_id: &str,
     folder_id: &str,
     body: web::Bytes,
+    user_id: &str,
 ) -> Result<HttpResponse, io::Error> {
     match dashboards::put(org_id, dashboard_id, folder_id, body).await {
         Ok(dashboard) => {
             tracing::info!(dashboard_id, "Dashboard updated");
+            // the history is best effort, the save already succeeded
+            if let Err(error) = versions::record(org_id, &dashboard, user_id).await {
+                tracing::error!(%error, dashboard_id, "Failed to record the dashboard version");
+            }
             Ok(HttpResponse::Ok().json(dashboard))
         }
         Err(error) => {
@@ -226,6 +320,52 @@ pub async fn move_dashboard(
     }
 }
 
+/// Copies the dashboard into the `to_folder` with a new id, the copy is owned
+/// by the destination folder.
+#[tracing::instrument]
+pub async fn copy_dashboard(
+    org_id: &str,
+    dashboard_id: &str,
+    from_folder: &str,
+    to_folder: &str,
+    title: Option<&str>,
+    user_id: &str,
+) -> Result<HttpResponse, io::Error> {
+    let Ok(dashboard) = dashboards::get(org_id, dashboard_id, from_folder).await else {
+        return Ok(Response::NotFound("Dashboard".to_string()).into());
+    };
+    if dashboards::folders::get(org_id, to_folder).await.is_err() {
+        return Ok(Response::NotFound("Destination Folder".to_string()).into());
+    }
+    let mut dash = if dashboard.version == 1 {
+        json::to_value(dashboard.v1.unwrap()).unwrap()
+    } else if dashboard.version == 2 {
+        json::to_value(dashboard.v2.unwrap()).unwrap()
+    } else {
+        json::to_value(dashboard.v3.unwrap()).unwrap()
+    };
+    if let (Some(title), Some(obj)) = (title, dash.as_object_mut()) {
+        obj.insert("title".to_string(), json::Value::String(title.to_string()));
+    }
+
+    let new_id = ider::generate();
+    let body = json::to_vec(&dash).unwrap();
+    let res = save_dashboard(org_id, &new_id, to_folder, body.into(), user_id).await?;
+    if res.status().is_success() {
+        set_ownership(
+            org_id,
+            "dashboards",
+            Authz {
+                obj_id: new_id,
+                parent_type: "folders".to_owned(),
+                parent: to_folder.to_owned(),
+            },
+        )
+        .await;
+    }
+    Ok(res)
+}
+
 #[derive(Debug)]
 enum Response {
     OkMessage(String),
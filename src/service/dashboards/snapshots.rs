@@ -0,0 +1,319 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+
+use actix_web::HttpResponse;
+use config::{
+    get_config, ider,
+    meta::{search, sql::Sql, stream::StreamType},
+    utils::{json, rand::generate_random_string},
+};
+
+use crate::{
+    common::meta::{
+        dashboards::{
+            snapshots::{CreateSnapshotRequest, Snapshot, SnapshotList, SnapshotQueryResult},
+            v3::Panel,
+            variables::ResolveVariablesRequest,
+        },
+        http::HttpResponse as MetaHttpResponse,
+        organization::OrganizationSetting,
+    },
+    service::{
+        db::{self, dashboards},
+        masking, promql, search as SearchService,
+    },
+};
+
+/// Length of the random snapshot ids, they are the secret of the public links
+const SNAPSHOT_ID_LEN: usize = 32;
+
+/// Runs the queries of the dashboard over the time range and stores their
+/// results in a new snapshot.
+#[tracing::instrument(skip(req))]
+pub async fn create_snapshot(
+    org_id: &str,
+    dashboard_id: &str,
+    folder_id: &str,
+    user_id: &str,
+    req: CreateSnapshotRequest,
+) -> Result<HttpResponse, io::Error> {
+    if !is_enabled(org_id).await {
+        return Ok(MetaHttpResponse::forbidden(
+            "Dashboard snapshots are disabled for the organization",
+        ));
+    }
+    let Ok(dashboard) = dashboards::get(org_id, dashboard_id, folder_id).await else {
+        return Ok(MetaHttpResponse::not_found("Dashboard not found"));
+    };
+    let Some(dashboard) = dashboard.v3 else {
+        return Ok(MetaHttpResponse::bad_request(
+            "Snapshots are taken for version 3 dashboards only",
+        ));
+    };
+    if req.start_time >= req.end_time {
+        return Ok(MetaHttpResponse::bad_request(
+            "start_time should be before end_time",
+        ));
+    }
+    let cfg = get_config();
+    let ttl = req.expires_in.unwrap_or(cfg.limit.dashboard_snapshot_ttl);
+    if ttl <= 0 || ttl > cfg.limit.dashboard_snapshot_max_ttl {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "expires_in should be between 1 and {} seconds",
+            cfg.limit.dashboard_snapshot_max_ttl
+        )));
+    }
+
+    let resolve_req = ResolveVariablesRequest {
+        start_time: req.start_time,
+        end_time: req.end_time,
+        values: req.values,
+    };
+    let resolved = match super::variables::resolve(org_id, user_id, &dashboard, &resolve_req).await
+    {
+        Ok(resolved) => resolved,
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    let mut results = HashMap::new();
+    for panel in dashboard.tabs.iter().flat_map(|tab| tab.panels.iter()) {
+        let Some(queries) = resolved.panels.get(&panel.id) else {
+            continue;
+        };
+        results.insert(
+            panel.id.clone(),
+            run_panel(
+                org_id,
+                user_id,
+                panel,
+                queries,
+                req.start_time,
+                req.end_time,
+            )
+            .await,
+        );
+    }
+
+    let now = chrono::Utc::now().timestamp_micros();
+    let snapshot = Snapshot {
+        snapshot_id: generate_random_string(SNAPSHOT_ID_LEN),
+        dashboard_id: dashboard_id.to_string(),
+        folder_id: folder_id.to_string(),
+        title: dashboard.title.clone(),
+        dashboard: json::to_value(&dashboard).unwrap(),
+        variables: resolved.variables,
+        results,
+        start_time: req.start_time,
+        end_time: req.end_time,
+        created_by: user_id.to_string(),
+        created_at: now,
+        expires_at: now + ttl * 1_000_000,
+    };
+    match dashboards::snapshots::put(org_id, &snapshot).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(snapshot.info())),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Lists the snapshots of the organization, or of a dashboard, the expired
+/// snapshots are deleted.
+#[tracing::instrument]
+pub async fn list_snapshots(
+    org_id: &str,
+    dashboard_id: Option<&str>,
+) -> Result<HttpResponse, io::Error> {
+    let snapshots = match dashboards::snapshots::list(org_id).await {
+        Ok(snapshots) => snapshots,
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    let now = chrono::Utc::now().timestamp_micros();
+    let mut list = Vec::with_capacity(snapshots.len());
+    for snapshot in snapshots {
+        if snapshot.is_expired(now) {
+            let _ = dashboards::snapshots::delete(org_id, &snapshot.snapshot_id).await;
+            continue;
+        }
+        if dashboard_id.is_none() || dashboard_id == Some(snapshot.dashboard_id.as_str()) {
+            list.push(snapshot.info());
+        }
+    }
+    list.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(HttpResponse::Ok().json(SnapshotList { list }))
+}
+
+#[tracing::instrument]
+pub async fn delete_snapshot(org_id: &str, snapshot_id: &str) -> Result<HttpResponse, io::Error> {
+    if dashboards::snapshots::get(org_id, snapshot_id)
+        .await
+        .is_err()
+    {
+        return Ok(MetaHttpResponse::not_found("Snapshot not found"));
+    }
+    match dashboards::snapshots::delete(org_id, snapshot_id).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Snapshot deleted")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Returns the snapshot of the public link, disabling the snapshots of the
+/// organization revokes all its links.
+#[tracing::instrument(skip(snapshot_id))]
+pub async fn get_public_snapshot(
+    org_id: &str,
+    snapshot_id: &str,
+) -> Result<HttpResponse, io::Error> {
+    // the same response for every failure, not to leak which links exist
+    let not_found = || Ok(MetaHttpResponse::not_found("Snapshot not found"));
+    if !is_enabled(org_id).await {
+        return not_found();
+    }
+    let Ok(snapshot) = dashboards::snapshots::get(org_id, snapshot_id).await else {
+        return not_found();
+    };
+    if snapshot.is_expired(chrono::Utc::now().timestamp_micros()) {
+        let _ = dashboards::snapshots::delete(org_id, snapshot_id).await;
+        return not_found();
+    }
+    Ok(HttpResponse::Ok().json(snapshot))
+}
+
+async fn is_enabled(org_id: &str) -> bool {
+    db::organization::get_org_setting(org_id)
+        .await
+        .ok()
+        .and_then(|v| json::from_slice::<OrganizationSetting>(&v).ok())
+        .is_some_and(|s| s.enable_dashboard_snapshots)
+}
+
+// the errors are kept in the results, a failed query doesn't fail the snapshot
+async fn run_panel(
+    org_id: &str,
+    user_id: &str,
+    panel: &Panel,
+    queries: &[String],
+    start_time: i64,
+    end_time: i64,
+) -> Vec<SnapshotQueryResult> {
+    let mut results = Vec::with_capacity(queries.len());
+    let panel_queries = panel.queries.iter().filter(|q| q.query.is_some());
+    for (query, expanded) in panel_queries.zip(queries) {
+        let data = if panel.query_type == "promql" {
+            run_promql(org_id, user_id, expanded, start_time, end_time).await
+        } else {
+            run_sql(
+                org_id,
+                user_id,
+                query.fields.stream_type,
+                expanded,
+                start_time,
+                end_time,
+            )
+            .await
+        };
+        let (data, error) = match data {
+            Ok(data) => (data, None),
+            Err(e) => (json::Value::Null, Some(e.to_string())),
+        };
+        results.push(SnapshotQueryResult {
+            query: expanded.clone(),
+            data,
+            error,
+        });
+    }
+    results
+}
+
+/// Runs the SQL query of a panel as the creator of the snapshot, the results
+/// are public so they are masked before they are stored.
+async fn run_sql(
+    org_id: &str,
+    user_id: &str,
+    stream_type: StreamType,
+    sql: &str,
+    start_time: i64,
+    end_time: i64,
+) -> Result<json::Value, anyhow::Error> {
+    let stream_name = Sql::new(sql)?.source;
+    if !SearchService::can_read_stream(org_id, user_id, stream_type, &stream_name).await {
+        return Err(anyhow::anyhow!(
+            "Unauthorized Access to the stream {stream_name}"
+        ));
+    }
+    let req = search::Request {
+        query: search::Query {
+            sql: sql.to_string(),
+            from: 0,
+            size: get_config().limit.dashboard_snapshot_max_rows,
+            start_time,
+            end_time,
+            sql_mode: "full".to_string(),
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: Some(search::SearchEventType::Dashboards),
+    };
+    let trace_id = ider::uuid();
+    let mut resp = SearchService::search(
+        &trace_id,
+        org_id,
+        stream_type,
+        Some(user_id.to_string()),
+        &req,
+    )
+    .await?;
+    masking::mask_hits(org_id, user_id, stream_type, &stream_name, &mut resp.hits);
+    Ok(json::to_value(resp)?)
+}
+
+async fn run_promql(
+    org_id: &str,
+    user_id: &str,
+    query: &str,
+    start_time: i64,
+    end_time: i64,
+) -> Result<json::Value, anyhow::Error> {
+    let ast = promql_parser::parser::parse(query).map_err(|e| anyhow::anyhow!(e))?;
+    let mut visitor = promql::name_visitor::MetricNameVisitor {
+        name: HashSet::new(),
+    };
+    promql_parser::util::walk_expr(&mut visitor, &ast).map_err(|e| anyhow::anyhow!(e))?;
+    for name in visitor.name {
+        if !SearchService::can_read_stream(org_id, user_id, StreamType::Metrics, &name).await {
+            return Err(anyhow::anyhow!("Unauthorized Access to the stream {name}"));
+        }
+    }
+    let req = promql::MetricsQueryRequest {
+        query: query.to_string(),
+        start: start_time,
+        end: end_time,
+        step: std::cmp::max(
+            promql::micros(promql::MINIMAL_INTERVAL),
+            (end_time - start_time) / promql::MAX_DATA_POINTS,
+        ),
+    };
+    let data = promql::search::search(org_id, &req, 0, user_id).await?;
+    Ok(json::to_value(promql::QueryResult {
+        result_type: data.get_type().to_string(),
+        result: data,
+    })?)
+}
@@ -0,0 +1,409 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Resolves the values of the dashboard variables, and expands the `$name`
+//! and `${name}` placeholders of the panel queries with them.
+
+use std::{collections::HashMap, io};
+
+use actix_web::HttpResponse;
+use config::{ider, meta::search, utils::json};
+
+use crate::{
+    common::meta::{
+        dashboards::{
+            v3::{
+                Dashboard, Filters, VariableList, VARIABLE_CUSTOM, VARIABLE_INTERVAL,
+                VARIABLE_QUERY_VALUES,
+            },
+            variables::{ResolveVariablesRequest, ResolveVariablesResponse, ResolvedVariable},
+        },
+        http::HttpResponse as MetaHttpResponse,
+    },
+    service::{db::dashboards, masking, search as SearchService},
+};
+
+/// Number of values fetched for a query variable without `max_record_size`
+const DEFAULT_QUERY_VALUES: i64 = 10;
+
+/// Intervals picked by the `auto` interval variables, in seconds
+const AUTO_INTERVALS: [(i64, &str); 12] = [
+    (10, "10s"),
+    (30, "30s"),
+    (60, "1m"),
+    (300, "5m"),
+    (600, "10m"),
+    (1800, "30m"),
+    (3600, "1h"),
+    (3 * 3600, "3h"),
+    (6 * 3600, "6h"),
+    (12 * 3600, "12h"),
+    (86400, "1d"),
+    (7 * 86400, "7d"),
+];
+/// Number of buckets the `auto` interval aims for over the time range
+const AUTO_INTERVAL_BUCKETS: i64 = 100;
+
+pub async fn resolve_variables(
+    org_id: &str,
+    dashboard_id: &str,
+    folder_id: &str,
+    user_id: &str,
+    req: ResolveVariablesRequest,
+) -> Result<HttpResponse, io::Error> {
+    let dashboard = match dashboards::get(org_id, dashboard_id, folder_id).await {
+        Ok(dashboard) => dashboard,
+        Err(_) => return Ok(MetaHttpResponse::not_found("Dashboard not found")),
+    };
+    let Some(dashboard) = dashboard.v3 else {
+        return Ok(MetaHttpResponse::bad_request(
+            "Dashboard variables are resolved for version 3 dashboards only",
+        ));
+    };
+    if req.start_time >= req.end_time {
+        return Ok(MetaHttpResponse::bad_request(
+            "start_time should be before end_time",
+        ));
+    }
+    let streams = dashboard
+        .variables
+        .iter()
+        .flat_map(|v| v.list.iter())
+        .filter(|var| var.type_field == VARIABLE_QUERY_VALUES)
+        .filter_map(|var| var.query_data.as_ref());
+    for query_data in streams {
+        if !SearchService::can_read_stream(
+            org_id,
+            user_id,
+            query_data.stream_type,
+            &query_data.stream,
+        )
+        .await
+        {
+            return Ok(MetaHttpResponse::forbidden(format!(
+                "Unauthorized Access to the stream {}",
+                query_data.stream
+            )));
+        }
+    }
+    match resolve(org_id, user_id, &dashboard, &req).await {
+        Ok(resp) => Ok(HttpResponse::Ok().json(resp)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Resolves the variables as the user, the query variables only return the
+/// values of the streams the user can read.
+pub(crate) async fn resolve(
+    org_id: &str,
+    user_id: &str,
+    dashboard: &Dashboard,
+    req: &ResolveVariablesRequest,
+) -> Result<ResolveVariablesResponse, anyhow::Error> {
+    let list = dashboard
+        .variables
+        .as_ref()
+        .map(|v| v.list.as_slice())
+        .unwrap_or_default();
+
+    // the variables are resolved in order, so the filters of a query variable
+    // can depend on the variables before it
+    let mut values: HashMap<String, (Vec<String>, bool)> = HashMap::new();
+    let mut variables = Vec::with_capacity(list.len());
+    for var in list {
+        let options = match var.type_field.as_str() {
+            VARIABLE_QUERY_VALUES => query_values(org_id, user_id, var, &values, req).await?,
+            _ => var
+                .options
+                .as_ref()
+                .map(|options| options.iter().map(|o| o.value.clone()).collect())
+                .unwrap_or_default(),
+        };
+        let mut value = match req.values.get(&var.name) {
+            Some(v) => v.clone(),
+            None => match &var.value {
+                Some(v) if !v.is_empty() => vec![v.clone()],
+                _ => options.first().cloned().into_iter().collect(),
+            },
+        };
+        if var.type_field == VARIABLE_INTERVAL {
+            value = value
+                .into_iter()
+                .map(|v| {
+                    if v == "auto" {
+                        auto_interval(req.start_time, req.end_time).to_string()
+                    } else {
+                        v
+                    }
+                })
+                .collect();
+        }
+        if var.type_field == VARIABLE_CUSTOM {
+            // only the listed options can be picked
+            value.retain(|v| options.contains(v));
+        }
+        if !var.is_multi_select() {
+            value.truncate(1);
+        }
+        values.insert(var.name.clone(), (value.clone(), var.is_multi_select()));
+        variables.push(ResolvedVariable {
+            name: var.name.clone(),
+            type_field: var.type_field.clone(),
+            options,
+            value,
+        });
+    }
+
+    let mut panels = HashMap::new();
+    for panel in dashboard.tabs.iter().flat_map(|tab| tab.panels.iter()) {
+        let queries = panel
+            .queries
+            .iter()
+            .filter_map(|q| q.query.as_deref())
+            .map(|q| expand(q, &values))
+            .collect::<Vec<_>>();
+        panels.insert(panel.id.clone(), queries);
+    }
+    Ok(ResolveVariablesResponse { variables, panels })
+}
+
+/// The most frequent values of the field of a query variable in the time range.
+async fn query_values(
+    org_id: &str,
+    user_id: &str,
+    var: &VariableList,
+    values: &HashMap<String, (Vec<String>, bool)>,
+    req: &ResolveVariablesRequest,
+) -> Result<Vec<String>, anyhow::Error> {
+    let Some(query_data) = var.query_data.as_ref() else {
+        return Ok(vec![]);
+    };
+    if !SearchService::can_read_stream(org_id, user_id, query_data.stream_type, &query_data.stream)
+        .await
+    {
+        return Err(anyhow::anyhow!(
+            "Unauthorized Access to the stream {}",
+            query_data.stream
+        ));
+    }
+    let filters = query_data
+        .filter
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|f| filter_sql(f, values))
+        .collect::<Vec<_>>();
+    let where_sql = if filters.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", filters.join(" AND "))
+    };
+    let sql = format!(
+        "SELECT \"{field}\", COUNT(*) AS zo_sql_num FROM \"{}\"{where_sql} GROUP BY \"{field}\" ORDER BY zo_sql_num DESC",
+        query_data.stream,
+        field = query_data.field
+    );
+    let search_req = search::Request {
+        query: search::Query {
+            sql,
+            from: 0,
+            size: query_data.max_record_size.unwrap_or(DEFAULT_QUERY_VALUES),
+            start_time: req.start_time,
+            end_time: req.end_time,
+            sql_mode: "full".to_string(),
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: Some(search::SearchEventType::Dashboards),
+    };
+    let trace_id = ider::uuid();
+    let mut hits = SearchService::search(
+        &trace_id,
+        org_id,
+        query_data.stream_type,
+        Some(user_id.to_string()),
+        &search_req,
+    )
+    .await?
+    .hits;
+    // the values are selected under the name of the field so the masking rules
+    // of the field apply, masked values collapse into one option
+    masking::mask_hits(
+        org_id,
+        user_id,
+        query_data.stream_type,
+        &query_data.stream,
+        &mut hits,
+    );
+    let mut options: Vec<String> = Vec::with_capacity(hits.len());
+    for value in hits
+        .iter()
+        .filter_map(|hit| hit.get(&query_data.field))
+        .filter(|v| !v.is_null())
+        .map(json::get_string_value)
+    {
+        if !options.contains(&value) {
+            options.push(value);
+        }
+    }
+    Ok(options)
+}
+
+/// The condition of a filter of a query variable, None when the filter is not
+/// complete.
+fn filter_sql(filter: &Filters, values: &HashMap<String, (Vec<String>, bool)>) -> Option<String> {
+    let name = filter.name.as_deref().filter(|n| !n.is_empty())?;
+    let operator = filter.operator.as_deref().filter(|o| !o.is_empty())?;
+    let value = expand(&filter.value, values);
+    if value.is_empty() {
+        return None;
+    }
+    let op = operator.to_uppercase();
+    if op == "IN" || op == "NOT IN" {
+        // the value is a list, `'a','b'` once a multi select variable is expanded
+        Some(format!("\"{name}\" {op} ({value})"))
+    } else {
+        Some(format!("\"{name}\" {operator} '{value}'"))
+    }
+}
+
+/// Replaces the `$name` and `${name}` placeholders of the variables. The
+/// values of a single select variable are inserted as they are, the ones of a
+/// multi select variable as a quoted list, `'a','b'`, for the `IN` conditions.
+/// The quotes of the values are escaped, unknown placeholders are kept.
+pub fn expand(query: &str, values: &HashMap<String, (Vec<String>, bool)>) -> String {
+    let mut ret = String::with_capacity(query.len());
+    let mut rest = query;
+    while let Some(pos) = rest.find('$') {
+        ret.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, len) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+        match values.get(name) {
+            Some((value, multi_select)) if !name.is_empty() => {
+                let value = value.iter().map(|v| v.replace('\'', "''"));
+                if *multi_select {
+                    ret.push_str(
+                        &value
+                            .map(|v| format!("'{v}'"))
+                            .collect::<Vec<_>>()
+                            .join(","),
+                    );
+                } else {
+                    ret.push_str(&value.collect::<Vec<_>>().join(","));
+                }
+                rest = &after[len..];
+            }
+            _ => {
+                ret.push('$');
+                rest = after;
+            }
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// The smallest interval splitting the time range in at most
+/// `AUTO_INTERVAL_BUCKETS` buckets.
+fn auto_interval(start_time: i64, end_time: i64) -> &'static str {
+    let range = (end_time - start_time) / 1_000_000;
+    AUTO_INTERVALS
+        .iter()
+        .find(|(secs, _)| range / secs <= AUTO_INTERVAL_BUCKETS)
+        .map(|(_, interval)| *interval)
+        .unwrap_or(AUTO_INTERVALS[AUTO_INTERVALS.len() - 1].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> HashMap<String, (Vec<String>, bool)> {
+        HashMap::from([
+            ("host".to_string(), (vec!["web-1".to_string()], false)),
+            (
+                "namespace".to_string(),
+                (vec!["prod".to_string(), "o'brien".to_string()], true),
+            ),
+            ("interval".to_string(), (vec!["5m".to_string()], false)),
+        ])
+    }
+
+    #[test]
+    fn test_expand() {
+        let values = values();
+        assert_eq!(
+            expand(
+                "SELECT histogram(_timestamp, '${interval}') AS t FROM \"default\" WHERE host = '$host' AND namespace IN ($namespace)",
+                &values
+            ),
+            "SELECT histogram(_timestamp, '5m') AS t FROM \"default\" WHERE host = 'web-1' AND namespace IN ('prod','o''brien')"
+        );
+        // unknown placeholders and longer names are kept
+        assert_eq!(
+            expand("$hostname $unknown ${host} $", &values),
+            "$hostname $unknown web-1 $"
+        );
+    }
+
+    #[test]
+    fn test_filter_sql() {
+        let values = values();
+        let filter = Filters {
+            name: Some("k8s_namespace".to_string()),
+            operator: Some("IN".to_string()),
+            value: "$namespace".to_string(),
+        };
+        assert_eq!(
+            filter_sql(&filter, &values).unwrap(),
+            "\"k8s_namespace\" IN ('prod','o''brien')"
+        );
+        let filter = Filters {
+            name: Some("host".to_string()),
+            operator: Some("=".to_string()),
+            value: "$host".to_string(),
+        };
+        assert_eq!(filter_sql(&filter, &values).unwrap(), "\"host\" = 'web-1'");
+        let filter = Filters {
+            name: None,
+            operator: Some("=".to_string()),
+            value: "$host".to_string(),
+        };
+        assert!(filter_sql(&filter, &values).is_none());
+    }
+
+    #[test]
+    fn test_auto_interval() {
+        let hour = 3600 * 1_000_000;
+        assert_eq!(auto_interval(0, 15 * 60 * 1_000_000), "10s");
+        assert_eq!(auto_interval(0, hour), "1m");
+        assert_eq!(auto_interval(0, 24 * hour), "30m");
+        assert_eq!(auto_interval(0, 365 * 24 * hour), "7d");
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io;
+
+use actix_web::HttpResponse;
+use config::{get_config, utils::json};
+
+use crate::{
+    common::meta::{
+        dashboards::{
+            versions::{self, SavedVersion, SavedVersionList, VersionDiff},
+            Dashboard,
+        },
+        http::HttpResponse as MetaHttpResponse,
+    },
+    service::db::dashboards,
+};
+
+/// Records the saved dashboard as its newest version, the versions above
+/// `ZO_DASHBOARD_MAX_VERSIONS` are deleted, oldest first.
+pub(crate) async fn record(
+    org_id: &str,
+    dashboard: &Dashboard,
+    user_id: &str,
+) -> Result<(), anyhow::Error> {
+    let dashboard_id = dashboard.dashboard_id();
+    let existing = dashboards::versions::list(org_id, dashboard_id).await?;
+    let version = SavedVersion {
+        version_id: existing.last().map(|v| v.version_id + 1).unwrap_or(1),
+        dashboard_id: dashboard_id.to_string(),
+        title: dashboard.title().to_string(),
+        created_by: user_id.to_string(),
+        created_at: chrono::Utc::now().timestamp_micros(),
+        dashboard: versions::to_value(dashboard),
+    };
+    dashboards::versions::put(org_id, &version).await?;
+
+    let max_versions = get_config().limit.dashboard_max_versions as usize;
+    let kept = existing.len() + 1;
+    if kept > max_versions {
+        for old in existing.iter().take(kept - max_versions) {
+            dashboards::versions::delete(org_id, dashboard_id, old.version_id).await?;
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument]
+pub async fn list_versions(org_id: &str, dashboard_id: &str) -> Result<HttpResponse, io::Error> {
+    match dashboards::versions::list(org_id, dashboard_id).await {
+        Ok(list) => {
+            let list = list.iter().rev().map(|v| v.info()).collect();
+            Ok(HttpResponse::Ok().json(SavedVersionList { list }))
+        }
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+#[tracing::instrument]
+pub async fn get_version(
+    org_id: &str,
+    dashboard_id: &str,
+    version_id: i64,
+) -> Result<HttpResponse, io::Error> {
+    match dashboards::versions::get(org_id, dashboard_id, version_id).await {
+        Ok(version) => Ok(HttpResponse::Ok().json(version)),
+        Err(_) => Ok(MetaHttpResponse::not_found("Dashboard version not found")),
+    }
+}
+
+/// Returns the JSON patch from the `from` version to the `to` version, or to
+/// the newest version.
+#[tracing::instrument]
+pub async fn diff_versions(
+    org_id: &str,
+    dashboard_id: &str,
+    from: i64,
+    to: Option<i64>,
+) -> Result<HttpResponse, io::Error> {
+    let list = match dashboards::versions::list(org_id, dashboard_id).await {
+        Ok(list) => list,
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    let find = |version_id: Option<i64>| match version_id {
+        Some(version_id) => list.iter().find(|v| v.version_id == version_id),
+        None => list.last(),
+    };
+    let (Some(from), Some(to)) = (find(Some(from)), find(to)) else {
+        return Ok(MetaHttpResponse::not_found("Dashboard version not found"));
+    };
+    Ok(HttpResponse::Ok().json(VersionDiff {
+        from: from.version_id,
+        to: to.version_id,
+        patch: versions::diff(&from.dashboard, &to.dashboard),
+    }))
+}
+
+/// Saves the version as the current dashboard, which records it as the
+/// newest version, the later versions are kept.
+#[tracing::instrument]
+pub async fn restore_version(
+    org_id: &str,
+    dashboard_id: &str,
+    folder_id: &str,
+    version_id: i64,
+    user_id: &str,
+) -> Result<HttpResponse, io::Error> {
+    if dashboards::get(org_id, dashboard_id, folder_id)
+        .await
+        .is_err()
+    {
+        return Ok(MetaHttpResponse::not_found("Dashboard not found"));
+    }
+    let Ok(version) = dashboards::versions::get(org_id, dashboard_id, version_id).await else {
+        return Ok(MetaHttpResponse::not_found("Dashboard version not found"));
+    };
+    let body = json::to_vec(&version.dashboard).unwrap();
+    super::save_dashboard(org_id, dashboard_id, folder_id, body.into(), user_id).await
+}
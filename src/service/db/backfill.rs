@@ -0,0 +1,58 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{common::meta::ingestion::BackfillJob, service::db};
+
+const BACKFILL_PREFIX: &str = "/backfill/";
+
+fn job_key(org_id: &str, id: &str) -> String {
+    format!("{BACKFILL_PREFIX}{org_id}/{id}")
+}
+
+pub(crate) async fn get(org_id: &str, id: &str) -> Result<Option<BackfillJob>, anyhow::Error> {
+    match db::get(&job_key(org_id, id)).await {
+        Ok(val) => Ok(Some(json::from_slice(&val)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+pub(crate) async fn set(job: &BackfillJob) -> Result<(), anyhow::Error> {
+    db::put(
+        &job_key(&job.org_id, &job.id),
+        json::to_vec(job)?.into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn list_all() -> Result<Vec<BackfillJob>, anyhow::Error> {
+    db::list_values(BACKFILL_PREFIX)
+        .await?
+        .iter()
+        .map(|val| Ok(json::from_slice(val)?))
+        .collect()
+}
+
+pub(crate) async fn list(org_id: &str) -> Result<Vec<BackfillJob>, anyhow::Error> {
+    db::list_values(&format!("{BACKFILL_PREFIX}{org_id}/"))
+        .await?
+        .iter()
+        .map(|val| Ok(json::from_slice(val)?))
+        .collect()
+}
@@ -0,0 +1,68 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::{
+    hash::{fnv, Sum64},
+    json,
+};
+
+use crate::{common::meta::ingestion::BucketIngestCheckpoint, service::db};
+
+const CHECKPOINT_PREFIX: &str = "/bucket_ingest/checkpoint/";
+
+/// The keys of the objects can be longer than the keys of the meta store.
+fn checkpoint_key(bucket: &str, key: &str) -> String {
+    format!("{CHECKPOINT_PREFIX}{bucket}/{:x}", fnv::new().sum64(key))
+}
+
+pub(crate) async fn get_checkpoint(
+    bucket: &str,
+    key: &str,
+) -> Result<Option<BucketIngestCheckpoint>, anyhow::Error> {
+    match db::get(&checkpoint_key(bucket, key)).await {
+        Ok(val) => {
+            let checkpoint: BucketIngestCheckpoint = json::from_slice(&val)?;
+            Ok((checkpoint.key == key).then_some(checkpoint))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+pub(crate) async fn set_checkpoint(
+    checkpoint: &BucketIngestCheckpoint,
+) -> Result<(), anyhow::Error> {
+    let key = checkpoint_key(&checkpoint.bucket, &checkpoint.key);
+    db::put(
+        &key,
+        json::to_vec(checkpoint)?.into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn list_checkpoints() -> Result<Vec<BucketIngestCheckpoint>, anyhow::Error> {
+    db::list_values(CHECKPOINT_PREFIX)
+        .await?
+        .iter()
+        .map(|val| Ok(json::from_slice(val)?))
+        .collect()
+}
+
+pub(crate) async fn delete_checkpoint(bucket: &str, key: &str) -> Result<(), anyhow::Error> {
+    db::delete_if_exists(&checkpoint_key(bucket, key), false, db::NO_NEED_WATCH).await?;
+    Ok(())
+}
@@ -13,17 +13,25 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use config::{cluster::LOCAL_NODE_UUID, meta::stream::StreamType, RwAHashMap};
+use chrono::{Duration, Utc};
+use config::{cluster::LOCAL_NODE_UUID, get_config, meta::stream::StreamType, RwAHashMap};
 use once_cell::sync::Lazy;
 
 use crate::service::db;
 
 static CACHES: Lazy<RwAHashMap<String, (i64, String)>> = Lazy::new(Default::default);
 
+/// The offsets read by `mark_late`, with the time they were read
+static LATE_OFFSETS: Lazy<RwAHashMap<String, (i64, i64)>> = Lazy::new(Default::default);
+
 fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
     format!("/compact/files/{org_id}/{stream_type}/{stream_name}")
 }
 
+fn mk_late_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("/compact/late/{org_id}/{stream_type}/{stream_name}")
+}
+
 pub async fn get_offset_from_cache(
     org_id: &str,
     stream_type: StreamType,
@@ -42,25 +50,29 @@ pub async fn get_offset(org_id: &str, stream_type: StreamType, stream_name: &str
     }
     drop(r);
 
-    let value = match db::get(&key).await {
+    let (offset, node) = get_offset_from_db(&key).await;
+    // only cache the value if it's empty or it's from this node
+    if node.is_empty() || LOCAL_NODE_UUID.eq(&node) {
+        let mut w = CACHES.write().await;
+        w.insert(key.clone(), (offset, node.clone()));
+        drop(w);
+    }
+    (offset, node)
+}
+
+async fn get_offset_from_db(key: &str) -> (i64, String) {
+    let value = match db::get(key).await {
         Ok(ret) => String::from_utf8_lossy(&ret).to_string(),
         Err(_) => String::from("0"),
     };
-    let (offset, node) = if value.contains(';') {
+    if value.contains(';') {
         let mut parts = value.split(';');
         let offset: i64 = parts.next().unwrap().parse().unwrap();
         let node = parts.next().unwrap().to_string();
         (offset, node)
     } else {
         (value.parse().unwrap(), String::from(""))
-    };
-    // only cache the value if it's empty or it's from this node
-    if node.is_empty() || LOCAL_NODE_UUID.eq(&node) {
-        let mut w = CACHES.write().await;
-        w.insert(key.clone(), (offset, node.clone()));
-        drop(w);
     }
-    (offset, node)
 }
 
 pub async fn set_offset(
@@ -96,6 +108,89 @@ pub async fn del_offset(
     let mut w = CACHES.write().await;
     w.remove(&key);
     drop(w);
+    LATE_OFFSETS.write().await.remove(&key);
+    db::delete_if_exists(&key, false, db::NO_NEED_WATCH)
+        .await
+        .map_err(Into::into)
+}
+
+/// Marks the hour of a file written after the compactor passed it, the
+/// compactor merges the marked hours again. The offset in the db trails the
+/// compactor by up to `ZO_COMPACT_SYNC_TO_DB_INTERVAL` and the ingesters cache
+/// it as long again, the files within that slack after the offset are marked
+/// too since the compactor may have passed them already.
+pub async fn mark_late(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    min_ts: i64,
+) -> Result<(), anyhow::Error> {
+    let (offset, slack) = match get_offset_from_cache(org_id, stream_type, stream_name).await {
+        Some((offset, _)) => (offset, 0),
+        None => (
+            get_late_offset(&mk_key(org_id, stream_type, stream_name)).await,
+            2 * sync_interval_micros(),
+        ),
+    };
+    if offset == 0 || min_ts >= offset + slack {
+        return Ok(());
+    }
+    let hour_micros = Duration::try_hours(1).unwrap().num_microseconds().unwrap();
+    let hour = min_ts - min_ts.rem_euclid(hour_micros);
+    let key = format!("{}/{hour}", mk_late_key(org_id, stream_type, stream_name));
+    db::put(&key, "".into(), db::NO_NEED_WATCH, None).await?;
+    Ok(())
+}
+
+fn sync_interval_micros() -> i64 {
+    Duration::try_seconds(get_config().compact.sync_to_db_interval as i64)
+        .unwrap()
+        .num_microseconds()
+        .unwrap()
+}
+
+/// The offset of the stream from the db, cached for the sync interval of the
+/// compactor since the db is not updated more often than that.
+async fn get_late_offset(key: &str) -> i64 {
+    let now = Utc::now().timestamp_micros();
+    let ttl = sync_interval_micros();
+    if let Some((offset, updated_at)) = LATE_OFFSETS.read().await.get(key) {
+        if now - updated_at < ttl {
+            return *offset;
+        }
+    }
+    let (offset, _) = get_offset_from_db(key).await;
+    LATE_OFFSETS
+        .write()
+        .await
+        .insert(key.to_string(), (offset, now));
+    offset
+}
+
+/// The hours marked by `mark_late`, oldest first.
+pub async fn list_late(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<i64>, anyhow::Error> {
+    let key = mk_late_key(org_id, stream_type, stream_name);
+    let prefix = format!("{key}/");
+    let mut hours = db::list_keys(&key)
+        .await?
+        .into_iter()
+        .filter_map(|key| key.strip_prefix(&prefix)?.parse::<i64>().ok())
+        .collect::<Vec<_>>();
+    hours.sort();
+    Ok(hours)
+}
+
+pub async fn del_late(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    hour: i64,
+) -> Result<(), anyhow::Error> {
+    let key = format!("{}/{hour}", mk_late_key(org_id, stream_type, stream_name));
     db::delete_if_exists(&key, false, db::NO_NEED_WATCH)
         .await
         .map_err(Into::into)
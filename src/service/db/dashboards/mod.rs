@@ -23,6 +23,8 @@ use crate::{
 
 pub mod folders;
 pub mod reports;
+pub mod snapshots;
+pub mod versions;
 
 #[tracing::instrument]
 pub(crate) async fn get(
@@ -121,34 +123,55 @@ pub(crate) async fn list(org_id: &str, folder: &str) -> Result<Vec<Dashboard>, a
     db::list(&db_key)
         .await?
         .into_values()
-        .map(|val| {
-            let d_version: DashboardVersion = json::from_slice(&val).unwrap();
-            if d_version.version == 1 {
-                let dash: v1::Dashboard = json::from_slice(&val).unwrap();
-                Ok(Dashboard {
-                    v1: Some(dash),
-                    version: 1,
-                    ..Default::default()
-                })
-            } else if d_version.version == 2 {
-                let dash: v2::Dashboard = json::from_slice(&val).unwrap();
-                Ok(Dashboard {
-                    v2: Some(dash),
-                    version: 2,
-                    ..Default::default()
-                })
-            } else {
-                let dash: v3::Dashboard = json::from_slice(&val).unwrap();
-                Ok(Dashboard {
-                    v3: Some(dash),
-                    version: 3,
-                    ..Default::default()
-                })
-            }
+        .map(|val| parse_dashboard(&val))
+        .collect()
+}
+
+/// Lists the dashboards of every folder of the organization, `folder_id` is
+/// set on the returned dashboards.
+#[tracing::instrument]
+pub(crate) async fn list_all(org_id: &str) -> Result<Vec<Dashboard>, anyhow::Error> {
+    let db_key = format!("/dashboard/{org_id}/");
+    db::list(&db_key)
+        .await?
+        .into_iter()
+        .map(|(key, val)| {
+            let mut dash = parse_dashboard(&val)?;
+            dash.folder_id = key
+                .strip_prefix(&db_key)
+                .and_then(|k| k.split_once('/'))
+                .map(|(folder, _)| folder.to_string());
+            Ok(dash)
         })
         .collect()
 }
 
+fn parse_dashboard(val: &[u8]) -> Result<Dashboard, anyhow::Error> {
+    let d_version: DashboardVersion = json::from_slice(val)?;
+    if d_version.version == 1 {
+        let dash: v1::Dashboard = json::from_slice(val)?;
+        Ok(Dashboard {
+            v1: Some(dash),
+            version: 1,
+            ..Default::default()
+        })
+    } else if d_version.version == 2 {
+        let dash: v2::Dashboard = json::from_slice(val)?;
+        Ok(Dashboard {
+            v2: Some(dash),
+            version: 2,
+            ..Default::default()
+        })
+    } else {
+        let dash: v3::Dashboard = json::from_slice(val)?;
+        Ok(Dashboard {
+            v3: Some(dash),
+            version: 3,
+            ..Default::default()
+        })
+    }
+}
+
 #[tracing::instrument]
 pub(crate) async fn delete(
     org_id: &str,
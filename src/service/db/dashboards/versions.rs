@@ -0,0 +1,76 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{common::meta::dashboards::versions::SavedVersion, service::db};
+
+#[tracing::instrument]
+pub(crate) async fn get(
+    org_id: &str,
+    dashboard_id: &str,
+    version_id: i64,
+) -> Result<SavedVersion, anyhow::Error> {
+    let val = db::get(&format!(
+        "/dashboard_versions/{org_id}/{dashboard_id}/{version_id}"
+    ))
+    .await?;
+    Ok(json::from_slice(&val)?)
+}
+
+#[tracing::instrument(skip(version))]
+pub(crate) async fn put(org_id: &str, version: &SavedVersion) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "/dashboard_versions/{org_id}/{}/{}",
+        version.dashboard_id, version.version_id
+    );
+    match db::put(&key, json::to_vec(version)?.into(), db::NO_NEED_WATCH, None).await {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow::anyhow!("Failed to save dashboard version")),
+    }
+}
+
+/// Lists the versions of the dashboard, oldest first.
+#[tracing::instrument]
+pub(crate) async fn list(
+    org_id: &str,
+    dashboard_id: &str,
+) -> Result<Vec<SavedVersion>, anyhow::Error> {
+    let db_key = format!("/dashboard_versions/{org_id}/{dashboard_id}/");
+    let mut list = db::list(&db_key)
+        .await?
+        .into_values()
+        .map(|val| json::from_slice(&val).map_err(|e| anyhow::anyhow!(e)))
+        .collect::<Result<Vec<SavedVersion>, _>>()?;
+    list.sort_by_key(|v| v.version_id);
+    Ok(list)
+}
+
+#[tracing::instrument]
+pub(crate) async fn delete(
+    org_id: &str,
+    dashboard_id: &str,
+    version_id: i64,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/dashboard_versions/{org_id}/{dashboard_id}/{version_id}");
+    Ok(db::delete(&key, false, db::NO_NEED_WATCH, None).await?)
+}
+
+/// Deletes every version of the dashboard.
+#[tracing::instrument]
+pub(crate) async fn delete_all(org_id: &str, dashboard_id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/dashboard_versions/{org_id}/{dashboard_id}/");
+    Ok(db::delete(&key, true, db::NO_NEED_WATCH, None).await?)
+}
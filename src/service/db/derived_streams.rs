@@ -0,0 +1,98 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{common::meta::derived_streams::DerivedStream, service::db};
+
+const DERIVED_STREAMS_KEY: &str = "/derived_streams/";
+
+pub async fn get(org_id: &str, name: &str) -> Result<DerivedStream, anyhow::Error> {
+    let key = format!("{DERIVED_STREAMS_KEY}{org_id}/{name}");
+    match db::get(&key).await {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(_) => Err(anyhow::anyhow!("Derived stream not found")),
+    }
+}
+
+/// Saves the derived stream and schedules its next window.
+pub async fn set(org_id: &str, ds: &DerivedStream, create: bool) -> Result<(), anyhow::Error> {
+    set_without_updating_trigger(org_id, ds).await?;
+    let trigger = db::scheduler::Trigger {
+        org: org_id.to_string(),
+        module: db::scheduler::TriggerModule::DerivedStream,
+        module_key: ds.name.clone(),
+        next_run_at: ds.next_run_at(),
+        ..Default::default()
+    };
+    let ret = if !create
+        && db::scheduler::exists(
+            org_id,
+            db::scheduler::TriggerModule::DerivedStream,
+            &ds.name,
+        )
+        .await
+    {
+        db::scheduler::update_trigger(trigger).await
+    } else {
+        db::scheduler::push(trigger).await
+    };
+    if let Err(e) = ret {
+        log::error!("Failed to save trigger: {}", e);
+    }
+    Ok(())
+}
+
+pub async fn set_without_updating_trigger(
+    org_id: &str,
+    ds: &DerivedStream,
+) -> Result<(), anyhow::Error> {
+    let key = format!("{DERIVED_STREAMS_KEY}{org_id}/{}", ds.name);
+    match db::put(
+        &key,
+        json::to_vec(ds).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow::anyhow!("Error saving derived stream: {}", e)),
+    }
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{DERIVED_STREAMS_KEY}{org_id}/{name}");
+    if let Err(e) = db::delete(&key, false, db::NO_NEED_WATCH, None).await {
+        return Err(anyhow::anyhow!("Error deleting derived stream: {}", e));
+    }
+    if let Err(e) =
+        db::scheduler::delete(org_id, db::scheduler::TriggerModule::DerivedStream, name).await
+    {
+        log::error!("Failed to delete trigger: {}", e);
+    }
+    Ok(())
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<DerivedStream>, anyhow::Error> {
+    let key = format!("{DERIVED_STREAMS_KEY}{org_id}/");
+    let mut items = db::list_values(&key)
+        .await?
+        .iter()
+        .filter_map(|val| json::from_slice::<DerivedStream>(val).ok())
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(items)
+}
@@ -0,0 +1,132 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::utils::json;
+
+use crate::{
+    common::{infra::config::STREAM_DLP, meta::dlp::StreamDlp},
+    service::db,
+};
+
+const DLP_KEY: &str = "/dlp/";
+
+pub async fn set(org_id: &str, stream_name: &str, dlp: &StreamDlp) -> Result<(), anyhow::Error> {
+    let key = format!("{DLP_KEY}{org_id}/{stream_name}");
+    match db::put(
+        &key,
+        json::to_vec(dlp).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error saving dlp rules: {}", e);
+            return Err(anyhow::anyhow!("Error saving dlp rules: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn get(org_id: &str, stream_name: &str) -> Result<StreamDlp, anyhow::Error> {
+    let val = db::get(&format!("{DLP_KEY}{org_id}/{stream_name}")).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn delete(org_id: &str, stream_name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{DLP_KEY}{org_id}/{stream_name}");
+    match db::delete(&key, false, db::NEED_WATCH, None).await {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error deleting dlp rules: {}", e);
+            return Err(anyhow::anyhow!("Error deleting dlp rules: {}", e));
+        }
+    }
+    Ok(())
+}
+
+/// Parses and compiles the rules, the rules were checked when they were saved.
+fn parse(item_key: &str, val: &[u8]) -> Option<StreamDlp> {
+    let mut dlp: StreamDlp = match json::from_slice(val) {
+        Ok(val) => val,
+        Err(e) => {
+            log::error!("Error parsing dlp rules {}: {}", item_key, e);
+            return None;
+        }
+    };
+    if let Err(e) = dlp.compile() {
+        log::error!("Error compiling dlp rules {}: {}", item_key, e);
+        return None;
+    }
+    Some(dlp)
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = DLP_KEY;
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching dlp rules");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_dlp_rules: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let item_value = if config::get_config().common.meta_store_external {
+                    match db::get(&ev.key).await {
+                        Ok(val) => val,
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    ev.value.unwrap()
+                };
+                if let Some(dlp) = parse(item_key, &item_value) {
+                    STREAM_DLP.insert(item_key.to_owned(), dlp);
+                }
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                STREAM_DLP.remove(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let key = DLP_KEY;
+    let ret = db::list(key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        if let Some(dlp) = parse(item_key, &item_value) {
+            STREAM_DLP.insert(item_key.to_owned(), dlp);
+        }
+    }
+    log::info!("DLP rules Cached");
+    Ok(())
+}
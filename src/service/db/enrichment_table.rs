@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use chrono::Utc;
 use config::{
@@ -23,7 +23,13 @@ use config::{
 use infra::cache::stats;
 use vrl::prelude::NotNan;
 
-use crate::service::search as SearchService;
+use crate::{
+    common::{
+        infra::config::ENRICHMENT_TABLE_LOOKUPS,
+        meta::enrichment_table::{EnrichmentTableLookup, EnrichmentTableSource},
+    },
+    service::{db, search as SearchService},
+};
 
 pub async fn get(org_id: &str, name: &str) -> Result<Vec<vrl::value::Value>, anyhow::Error> {
     let stats = stats::get_stream_stats(org_id, name, StreamType::EnrichmentTables);
@@ -52,7 +58,7 @@ pub async fn get(org_id: &str, name: &str) -> Result<Vec<vrl::value::Value>, any
         search_type: None,
     };
     // do search
-    match SearchService::search("", org_id, StreamType::EnrichmentTables, None, &req).await {
+    match SearchService::search_as_system("", org_id, StreamType::EnrichmentTables, &req).await {
         Ok(res) => {
             if !res.hits.is_empty() {
                 Ok(res.hits.iter().map(convert_to_vrl).collect())
@@ -67,6 +73,139 @@ pub async fn get(org_id: &str, name: &str) -> Result<Vec<vrl::value::Value>, any
     }
 }
 
+pub async fn get_source(org_id: &str, name: &str) -> Result<EnrichmentTableSource, anyhow::Error> {
+    let val = db::get(&format!("/enrichment_table_source/{org_id}/{name}")).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set_source(
+    org_id: &str,
+    name: &str,
+    source: &EnrichmentTableSource,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/enrichment_table_source/{org_id}/{name}");
+    db::put(
+        &key,
+        json::to_vec(source).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_source(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/enrichment_table_source/{org_id}/{name}");
+    db::delete(&key, false, db::NO_NEED_WATCH, None).await?;
+    Ok(())
+}
+
+/// Returns all the sources, as `(org_id, table_name, source)`
+pub async fn list_sources() -> Result<Vec<(String, String, EnrichmentTableSource)>, anyhow::Error> {
+    let prefix = "/enrichment_table_source/";
+    let mut items = Vec::new();
+    for (key, val) in db::list(prefix).await? {
+        let Some((org_id, name)) = key.strip_prefix(prefix).and_then(|k| k.split_once('/')) else {
+            continue;
+        };
+        let source: EnrichmentTableSource = match json::from_slice(&val) {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("Error parsing enrichment table source {key}: {e}");
+                continue;
+            }
+        };
+        items.push((org_id.to_string(), name.to_string(), source));
+    }
+    Ok(items)
+}
+
+pub async fn get_lookup(org_id: &str, name: &str) -> Result<EnrichmentTableLookup, anyhow::Error> {
+    let val = db::get(&format!("/enrichment_table_lookup/{org_id}/{name}")).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set_lookup(
+    org_id: &str,
+    name: &str,
+    lookup: &EnrichmentTableLookup,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/enrichment_table_lookup/{org_id}/{name}");
+    db::put(
+        &key,
+        json::to_vec(lookup).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_lookup(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/enrichment_table_lookup/{org_id}/{name}");
+    db::delete(&key, false, db::NEED_WATCH, None).await?;
+    Ok(())
+}
+
+pub async fn watch_lookups() -> Result<(), anyhow::Error> {
+    let key = "/enrichment_table_lookup/";
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching enrichment table lookups");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_enrichment_table_lookups: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let item_value: EnrichmentTableLookup =
+                    if config::get_config().common.meta_store_external {
+                        match db::get(&ev.key).await {
+                            Ok(val) => match json::from_slice(&val) {
+                                Ok(val) => val,
+                                Err(e) => {
+                                    log::error!("Error getting value: {}", e);
+                                    continue;
+                                }
+                            },
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        }
+                    } else {
+                        json::from_slice(&ev.value.unwrap()).unwrap()
+                    };
+                ENRICHMENT_TABLE_LOOKUPS.insert(item_key.to_owned(), item_value);
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                ENRICHMENT_TABLE_LOOKUPS.remove(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache_lookups() -> Result<(), anyhow::Error> {
+    let key = "/enrichment_table_lookup/";
+    let ret = db::list(key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let json_val: EnrichmentTableLookup = json::from_slice(&item_value).unwrap();
+        ENRICHMENT_TABLE_LOOKUPS.insert(item_key.to_owned(), json_val);
+    }
+    log::info!("Enrichment table lookups Cached");
+    Ok(())
+}
+
 fn convert_to_vrl(value: &json::Value) -> vrl::value::Value {
     match value {
         json::Value::Null => vrl::value::Value::Null,
@@ -15,21 +15,30 @@
 
 use std::sync::Arc;
 
+use chrono::{Duration, Utc};
 use config::{
-    cluster::{is_compactor, is_ingester, is_querier, LOCAL_NODE_UUID},
+    cluster::{is_compactor, is_ingester, is_querier, LOCAL_NODE_ROLE, LOCAL_NODE_UUID},
     get_config,
     meta::{
-        cluster::{Node, NodeStatus},
+        cluster::{Node, NodeStatus, Role, GRPC_MIN_VERSION, GRPC_VERSION_HEADER},
         stream::FileKey,
     },
+    utils::parquet::read_recordbatch_from_bytes,
 };
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
+use infra::{bus, file_list as infra_file_list, schema::STREAM_SCHEMAS_FIELDS};
 use once_cell::sync::Lazy;
+use prost::Message;
 use proto::cluster_rpc;
 use tokio::sync::{mpsc, RwLock};
-use tonic::{codec::CompressionEncoding, metadata::MetadataValue, transport::Channel, Request};
+use tonic::{codec::CompressionEncoding, metadata::MetadataValue, Request};
 
-use crate::common::infra::cluster;
+use crate::common::infra::{
+    cluster::{self, get_node_from_consistent_hash},
+    tls,
+};
+
+pub(super) const BUS_TOPIC: &str = "file_list";
 
 static EVENTS: Lazy<RwLock<HashMap<String, EventChannel>>> =
     Lazy::new(|| RwLock::new(HashMap::new()));
@@ -42,6 +51,11 @@ pub async fn send(items: &[FileKey], node_uuid: Option<String>) -> Result<(), an
     if cfg.common.local_mode || items.is_empty() {
         return Ok(());
     }
+    // a single publish reaches all the nodes, only the nodes joining the
+    // cluster still get their events by grpc
+    if node_uuid.is_none() && is_bus_enabled() {
+        return publish(items).await;
+    }
     let nodes = if let Some(node_uuid) = node_uuid {
         cluster::get_node_by_uuid(&node_uuid)
             .await
@@ -154,7 +168,11 @@ async fn send_to_node(
         let token: MetadataValue<_> = cluster::get_internal_grpc_token()
             .parse()
             .expect("parse internal grpc token faile");
-        let channel = match Channel::from_shared(node.grpc_addr.clone())
+        let grpc_version: MetadataValue<_> = node
+            .negotiate_grpc_version()
+            .unwrap_or(GRPC_MIN_VERSION)
+            .into();
+        let channel = match tls::grpc_endpoint(node.grpc_addr.clone())
             .unwrap()
             .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
             .connect()
@@ -176,6 +194,8 @@ async fn send_to_node(
             channel,
             move |mut req: Request<()>| {
                 req.metadata_mut().insert("authorization", token.clone());
+                req.metadata_mut()
+                    .insert(GRPC_VERSION_HEADER, grpc_version.clone());
                 Ok(req)
             },
         );
@@ -237,3 +257,182 @@ async fn send_to_node(
         }
     }
 }
+
+#[inline]
+fn is_bus_enabled() -> bool {
+    get_config()
+        .common
+        .broadcast_transport
+        .eq_ignore_ascii_case("nats")
+}
+
+/// Whether the local node needs the file list events of the other nodes.
+fn is_receiver() -> bool {
+    let cfg = get_config();
+    if cfg.common.meta_store_external {
+        is_querier(&LOCAL_NODE_ROLE)
+    } else {
+        is_querier(&LOCAL_NODE_ROLE)
+            || is_compactor(&LOCAL_NODE_ROLE)
+            || is_ingester(&LOCAL_NODE_ROLE)
+    }
+}
+
+async fn publish(items: &[FileKey]) -> Result<(), anyhow::Error> {
+    if get_config().common.print_key_event {
+        items.iter().for_each(|item| {
+            log::info!(
+                "[broadcast] publish event: file: {}, deleted: {}",
+                item.key,
+                item.deleted,
+            );
+        });
+    }
+    let req = cluster_rpc::FileList {
+        items: items.iter().map(cluster_rpc::FileKey::from).collect(),
+    };
+    bus::publish(BUS_TOPIC, req.encode_to_vec().into()).await?;
+    Ok(())
+}
+
+/// Receives the file list events published to the bus by the other nodes.
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    // a read replica follows the bus of the primary instead, see replica.rs
+    if cfg.common.local_mode || cfg.read_replica.enabled || !is_bus_enabled() || !is_receiver() {
+        return Ok(());
+    }
+    let mut events = bus::subscribe(BUS_TOPIC).await?;
+    log::info!("Start watching file list events on the bus");
+    while let Some(payload) = events.recv().await {
+        let req = match cluster_rpc::FileList::decode(payload) {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("[broadcast] decode bus event failed: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = receive(&req).await {
+            log::error!("[broadcast] receive bus event failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Applies the file list events of another node to the local file list.
+pub async fn receive(req: &cluster_rpc::FileList) -> Result<(), anyhow::Error> {
+    let put_items = req
+        .items
+        .iter()
+        .filter(|v| !v.deleted)
+        .map(FileKey::from)
+        .collect::<Vec<_>>();
+    let del_items = req
+        .items
+        .iter()
+        .filter(|v| v.deleted)
+        .map(|v| v.key.clone())
+        .collect::<Vec<_>>();
+    let cfg = get_config();
+    // Warning: external meta store should not accept any file list
+    // querier and compactor can accept add new files
+    // ingester only accept remove old files
+    if !cfg.common.meta_store_external {
+        if is_querier(&LOCAL_NODE_ROLE) || is_compactor(&LOCAL_NODE_ROLE) {
+            infra_file_list::batch_add(&put_items).await?;
+        }
+        infra_file_list::batch_remove(&del_items).await?;
+    }
+
+    // cache latest files for querier
+    if cfg.memory_cache.cache_latest_files && is_querier(&LOCAL_NODE_ROLE) {
+        let mut cached_field_stream = HashSet::new();
+        for item in put_items.iter() {
+            let Some(node) = get_node_from_consistent_hash(&item.key, &Role::Querier).await else {
+                continue; // no querier node
+            };
+            if LOCAL_NODE_UUID.ne(&node) {
+                continue; // not this node
+            }
+            if infra::cache::file_data::download("download", &item.key)
+                .await
+                .is_ok()
+                && cfg.limit.quick_mode_file_list_enabled
+            {
+                let columns = item.key.split('/').collect::<Vec<&str>>();
+                if columns[2] != "logs" {
+                    continue; // only cache fields for logs
+                }
+                let stream_key = columns[1..4].join("/");
+                if cached_field_stream.contains(&stream_key) {
+                    continue;
+                }
+                if cache_latest_fields(&stream_key, &item.key).await.is_ok() {
+                    cached_field_stream.insert(stream_key);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn cache_latest_fields(stream: &str, file: &str) -> Result<(), anyhow::Error> {
+    let fr = STREAM_SCHEMAS_FIELDS.read().await;
+    let field_cache_time = fr.get(stream).map(|v| v.0).unwrap_or(0);
+    drop(fr);
+
+    if field_cache_time
+        + Duration::try_seconds(get_config().limit.quick_mode_file_list_interval)
+            .unwrap()
+            .num_microseconds()
+            .unwrap()
+        >= Utc::now().timestamp_micros()
+    {
+        return Ok(());
+    }
+
+    let buf = match infra::cache::file_data::memory::get(file, None).await {
+        Some(buf) => Some(buf),
+        _ => infra::cache::file_data::disk::get(file, None).await,
+    };
+    let Some(buf) = buf else {
+        return Ok(());
+    };
+
+    let (schema, batches) = read_recordbatch_from_bytes(&buf).await?;
+    let mut new_batch = arrow::compute::concat_batches(&schema, &batches)?;
+    // delete all null values column
+    let mut null_columns = Vec::new();
+    for i in 0..new_batch.num_columns() {
+        let fi = i - null_columns.len();
+        if new_batch.column(fi).null_count() == new_batch.num_rows() {
+            null_columns.push(i);
+            new_batch.remove_column(fi);
+        }
+    }
+    let new_chema = if null_columns.is_empty() {
+        schema
+    } else {
+        new_batch.schema()
+    };
+    let mut fields = new_chema
+        .fields()
+        .iter()
+        .map(|f| f.name().to_string())
+        .collect::<Vec<_>>();
+    fields.sort();
+    if fields.is_empty() {
+        return Ok(());
+    }
+
+    log::debug!("cached latest stream: {}, fields: {}", stream, fields.len());
+
+    let mut fw = STREAM_SCHEMAS_FIELDS.write().await;
+    let entry = fw.entry(stream.to_string()).or_insert((0, Vec::new()));
+    entry.0 = Utc::now().timestamp_micros();
+    entry.1 = fields;
+    drop(fw);
+
+    Ok(())
+}
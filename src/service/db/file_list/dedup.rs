@@ -0,0 +1,147 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The content hashes of the files uploaded by the ingesters, by hour, so that
+//! a file with the content of a file already in the file list isn't uploaded
+//! again. The hashes of an hour are dropped once the compactor passed it and
+//! merged its files.
+
+use chrono::Duration;
+use config::meta::stream::StreamType;
+
+use crate::service::db;
+
+fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("/file_hash/{org_id}/{stream_type}/{stream_name}")
+}
+
+fn mk_hour_key(org_id: &str, stream_type: StreamType, stream_name: &str, min_ts: i64) -> String {
+    let hour_micros = Duration::try_hours(1).unwrap().num_microseconds().unwrap();
+    let hour = min_ts - min_ts.rem_euclid(hour_micros);
+    format!("{}/{hour}", mk_key(org_id, stream_type, stream_name))
+}
+
+/// The file uploaded with the content hash, in the hour of `min_ts`.
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    min_ts: i64,
+    hash: &str,
+) -> Option<String> {
+    let key = format!(
+        "{}/{hash}",
+        mk_hour_key(org_id, stream_type, stream_name, min_ts)
+    );
+    db::get(&key)
+        .await
+        .ok()
+        .map(|value| String::from_utf8_lossy(&value).to_string())
+}
+
+pub async fn set(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    min_ts: i64,
+    hash: &str,
+    file: &str,
+) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "{}/{hash}",
+        mk_hour_key(org_id, stream_type, stream_name, min_ts)
+    );
+    db::put(&key, file.to_string().into(), db::NO_NEED_WATCH, None).await?;
+    Ok(())
+}
+
+/// Drops the hashes of the hours before `hour`.
+pub async fn del_before(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    hour: i64,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    let prefix = format!("{key}/");
+    let mut hours = db::list_keys(&key)
+        .await?
+        .into_iter()
+        .filter_map(|key| {
+            key.strip_prefix(&prefix)?
+                .split('/')
+                .next()?
+                .parse::<i64>()
+                .ok()
+        })
+        .filter(|h| *h < hour)
+        .collect::<Vec<_>>();
+    hours.sort();
+    hours.dedup();
+    for h in hours {
+        db::delete_if_exists(&format!("{key}/{h}/"), true, db::NO_NEED_WATCH).await?;
+    }
+    Ok(())
+}
+
+pub async fn delete_stream(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = format!("{}/", mk_key(org_id, stream_type, stream_name));
+    db::delete_if_exists(&key, true, db::NO_NEED_WATCH)
+        .await
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_hashes() {
+        let hour = Duration::try_hours(1).unwrap().num_microseconds().unwrap();
+        let hour_start = 1_699_999_200_000_000;
+        let min_ts = hour_start + 1_000_000;
+        let file = "files/default/logs/dedup/2023/11/14/22/1.parquet";
+        set("default", "logs".into(), "dedup", min_ts, "h1", file)
+            .await
+            .unwrap();
+        assert_eq!(
+            get("default", "logs".into(), "dedup", min_ts, "h1").await,
+            Some(file.to_string())
+        );
+        assert_eq!(
+            get("default", "logs".into(), "dedup", min_ts + hour, "h1").await,
+            None
+        );
+        del_before("default", "logs".into(), "dedup", min_ts)
+            .await
+            .unwrap();
+        assert!(
+            get("default", "logs".into(), "dedup", min_ts, "h1")
+                .await
+                .is_some()
+        );
+        del_before("default", "logs".into(), "dedup", hour_start + hour)
+            .await
+            .unwrap();
+        assert_eq!(
+            get("default", "logs".into(), "dedup", min_ts, "h1").await,
+            None
+        );
+    }
+}
@@ -19,8 +19,11 @@ use infra::{cache, file_list};
 use once_cell::sync::Lazy;
 
 pub mod broadcast;
+pub mod dedup;
 pub mod local;
 pub mod remote;
+pub mod replica;
+pub mod snapshot;
 
 pub static DEPULICATE_FILES: Lazy<RwHashSet<String>> =
     Lazy::new(|| DashSet::with_capacity_and_hasher(1024, Default::default()));
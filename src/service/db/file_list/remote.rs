@@ -27,8 +27,11 @@ use infra::{cache::stats, file_list as infra_file_list, storage};
 use once_cell::sync::Lazy;
 use tokio::{sync::RwLock, time};
 
+use super::snapshot;
 use crate::service::db;
 
+const SNAPSHOT_BATCH_SIZE: usize = 10_000;
+
 pub static LOADED_FILES: Lazy<RwLock<HashSet<String>>> =
     Lazy::new(|| RwLock::new(HashSet::with_capacity(24)));
 pub static LOADED_ALL_FILES: AtomicBool = AtomicBool::new(false);
@@ -40,19 +43,89 @@ pub async fn cache(prefix: &str, force: bool) -> Result<(), anyhow::Error> {
         return Ok(());
     }
 
+    let mut files = storage::list(&prefix).await?;
+    files.reverse(); // reverse order let deleted files be the first
+    log::info!("Load file_list [{prefix}] gets {} files", files.len());
+    if !files.is_empty() {
+        load_files(&prefix, files).await?;
+    }
+
+    // cache result
+    rw.insert(prefix.clone());
+    log::info!("Load file_list [{prefix}] done deleting done");
+    Ok(())
+}
+
+/// Load the latest file list snapshot and then only the file list objects
+/// written after its watermark, instead of all the file list history. Falls
+/// back to loading everything when there is no snapshot yet.
+pub async fn cache_from_snapshot() -> Result<(), anyhow::Error> {
+    let Some(manifest) = snapshot::get_manifest().await? else {
+        log::info!("Load file_list snapshot not found, load all the file_list");
+        return cache("", false).await;
+    };
+
     let start = std::time::Instant::now();
-    let mut stats = ProcessStats::default();
+    let files = process_file(&manifest.key).await?;
+    if files.is_empty() && manifest.files > 0 {
+        log::error!(
+            "Load file_list snapshot [{}] failed, load all the file_list",
+            manifest.key
+        );
+        return cache("", false).await;
+    }
 
-    let mut files = storage::list(&prefix).await?;
+    let mut rw = LOADED_FILES.write().await;
+    for chunk in files.chunks(SNAPSHOT_BATCH_SIZE) {
+        infra_file_list::batch_add(chunk).await?;
+        tokio::task::yield_now().await;
+    }
+    super::DEPULICATE_FILES.clear();
+    log::info!(
+        "Load file_list snapshot [{}] load {} files done, took: {}ms",
+        manifest.key,
+        files.len(),
+        start.elapsed().as_millis()
+    );
+    drop(files);
+
+    // catch up with the file list objects written since the snapshot, adding the
+    // files already in the snapshot again is a no-op
+    let since = manifest.watermark - snapshot::SAFETY_MARGIN;
+    let mut files = storage::list_modified_since("file_list/", since).await?;
     files.reverse(); // reverse order let deleted files be the first
-    let files_num = files.len();
-    log::info!("Load file_list [{prefix}] gets {} files", files_num);
-    if files.is_empty() {
-        // cache result
-        rw.insert(prefix);
-        return Ok(());
+    log::info!(
+        "Load file_list since snapshot [{}] gets {} files",
+        manifest.key,
+        files.len()
+    );
+    if !files.is_empty() {
+        load_files("file_list/", files).await?;
     }
 
+    // every prefix is loaded now
+    for file in storage::list("file_list/").await? {
+        if let Some(prefix) = file_list_prefix(&file) {
+            rw.insert(format!("file_list/{prefix}"));
+        }
+    }
+    rw.insert("file_list/".to_string());
+    LOADED_ALL_FILES.store(true, Ordering::Release);
+    log::info!(
+        "Load file_list from snapshot [{}] done, took: {}ms",
+        manifest.key,
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+/// Loads the file list objects into the local file list and drops the files
+/// deleted by them.
+async fn load_files(prefix: &str, files: Vec<String>) -> Result<(), anyhow::Error> {
+    let start = std::time::Instant::now();
+    let mut stats = ProcessStats::default();
+    let files_num = files.len();
+
     let cfg = config::get_config();
     let mut tasks = Vec::with_capacity(cfg.limit.query_thread_num + 1);
     let chunk_size = std::cmp::max(1, files_num / cfg.limit.query_thread_num);
@@ -112,10 +185,6 @@ pub async fn cache(prefix: &str, force: bool) -> Result<(), anyhow::Error> {
         .collect::<Vec<String>>();
     infra_file_list::batch_remove(&deleted_files).await?;
 
-    // cache result
-    rw.insert(prefix.clone());
-    log::info!("Load file_list [{prefix}] done deleting done");
-
     // clean depulicate files
     super::DEPULICATE_FILES.clear();
     super::DEPULICATE_FILES.shrink_to_fit();
@@ -239,13 +308,9 @@ pub async fn cache_all() -> Result<(), anyhow::Error> {
     let files = storage::list(&prefix).await?;
     let mut prefixes = HashSet::new();
     for file in files {
-        // file_list/2023/06/26/07/7078998136898850816tVckGD.json.zst
-        let columns = file.split('/').collect::<Vec<_>>();
-        let key = format!(
-            "{}/{}/{}/{}/",
-            columns[1], columns[2], columns[3], columns[4]
-        );
-        prefixes.insert(key);
+        if let Some(prefix) = file_list_prefix(&file) {
+            prefixes.insert(prefix);
+        }
     }
     for prefix in prefixes {
         cache(&prefix, false).await?;
@@ -268,6 +333,19 @@ pub async fn cache_latest_hour() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// The hour prefix of a file list object, as `2023/06/26/07/`.
+fn file_list_prefix(file: &str) -> Option<String> {
+    // file_list/2023/06/26/07/7078998136898850816tVckGD.json.zst
+    let columns = file.split('/').collect::<Vec<_>>();
+    if columns.len() < 6 {
+        return None;
+    }
+    Some(format!(
+        "{}/{}/{}/{}/",
+        columns[1], columns[2], columns[3], columns[4]
+    ))
+}
+
 #[derive(Debug, Clone, Default)]
 struct ProcessStats {
     pub file_count: usize,
@@ -286,3 +364,17 @@ impl std::ops::Add<ProcessStats> for ProcessStats {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_list_prefix() {
+        assert_eq!(
+            file_list_prefix("file_list/2023/06/26/07/7078998136898850816tVckGD.json.zst"),
+            Some("2023/06/26/07/".to_string())
+        );
+        assert_eq!(file_list_prefix("file_list/2023/06/26"), None);
+    }
+}
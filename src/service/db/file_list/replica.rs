@@ -0,0 +1,150 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Read replicas run a querier pool on a replica of the primary's bucket. The
+//! file list snapshot is loaded from the bucket at startup and reloaded
+//! periodically, meanwhile the file list events of the primary are followed on
+//! its bus. New files are held back for `ZO_READ_REPLICA_LAG` seconds so the
+//! bucket replication has copied them before they become searchable.
+
+use std::collections::VecDeque;
+
+use chrono::Utc;
+use config::get_config;
+use hashbrown::HashSet;
+use infra::bus;
+use prost::Message;
+use proto::cluster_rpc::{FileKey, FileList};
+use tokio::time;
+
+use super::{broadcast, remote};
+
+#[derive(Default)]
+struct Pending {
+    items: VecDeque<(i64, FileKey)>,
+}
+
+impl Pending {
+    fn push(&mut self, due: i64, items: Vec<FileKey>) {
+        self.items.extend(items.into_iter().map(|item| (due, item)));
+    }
+
+    /// drops the files deleted before they were applied
+    fn remove(&mut self, keys: &HashSet<String>) {
+        self.items.retain(|(_, item)| !keys.contains(&item.key));
+    }
+
+    fn take_due(&mut self, now: i64) -> Vec<FileKey> {
+        let mut due = Vec::new();
+        while self.items.front().is_some_and(|(ts, _)| *ts <= now) {
+            due.push(self.items.pop_front().unwrap().1);
+        }
+        due
+    }
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    // with an external meta store the replica reads the replicated file list
+    if !cfg.read_replica.enabled || cfg.common.meta_store_external {
+        return Ok(());
+    }
+    tokio::task::spawn(async move { reload_snapshot().await });
+
+    let mut events = bus::subscribe_primary(broadcast::BUS_TOPIC).await?;
+    log::info!(
+        "[READ_REPLICA] following the file list events of {}",
+        cfg.read_replica.primary_nats_addr
+    );
+    let lag = cfg.read_replica.lag as i64;
+    let mut pending = Pending::default();
+    let mut interval = time::interval(time::Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            payload = events.recv() => {
+                let Some(payload) = payload else {
+                    break;
+                };
+                let req = match FileList::decode(payload) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        log::error!("[READ_REPLICA] decode bus event failed: {}", e);
+                        continue;
+                    }
+                };
+                let (deleted, added): (Vec<_>, Vec<_>) =
+                    req.items.into_iter().partition(|v| v.deleted);
+                if !deleted.is_empty() {
+                    pending.remove(&deleted.iter().map(|v| v.key.clone()).collect());
+                    apply(deleted).await;
+                }
+                pending.push(Utc::now().timestamp() + lag, added);
+            }
+            _ = interval.tick() => {
+                let due = pending.take_due(Utc::now().timestamp());
+                if !due.is_empty() {
+                    apply(due).await;
+                }
+            }
+        }
+    }
+    log::error!("[READ_REPLICA] bus subscription of the primary closed");
+    Ok(())
+}
+
+async fn apply(items: Vec<FileKey>) {
+    if let Err(e) = broadcast::receive(&FileList { items }).await {
+        log::error!("[READ_REPLICA] apply file list events failed: {}", e);
+    }
+}
+
+/// The bus delivers at most once, the snapshot catches up the events missed
+/// while the replica was disconnected from the primary.
+async fn reload_snapshot() {
+    let mut interval = time::interval(time::Duration::from_secs(
+        get_config().read_replica.snapshot_interval,
+    ));
+    interval.tick().await; // the startup already loaded the snapshot
+    loop {
+        interval.tick().await;
+        if let Err(e) = remote::cache_latest_hour().await {
+            log::error!("[READ_REPLICA] reload file list snapshot failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(key: &str) -> FileKey {
+        FileKey {
+            key: key.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pending_take_due() {
+        let mut pending = Pending::default();
+        pending.push(10, vec![file("a"), file("b")]);
+        pending.push(20, vec![file("c")]);
+        assert!(pending.take_due(5).is_empty());
+        let due = pending.take_due(10);
+        assert_eq!(due.len(), 2);
+        pending.remove(&HashSet::from_iter(["c".to_string()]));
+        assert!(pending.take_due(30).is_empty());
+    }
+}
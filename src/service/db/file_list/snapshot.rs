@@ -0,0 +1,120 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Snapshots of the file list in the object store, so that a node can load
+//! the file list from the latest snapshot and the file list objects written
+//! after it instead of replaying all the file list history.
+
+use std::io::Write;
+
+use chrono::Utc;
+use config::{meta::stream::FileKey, utils::json};
+use infra::{file_list as infra_file_list, storage};
+use serde::{Deserialize, Serialize};
+
+const SNAPSHOT_PREFIX: &str = "file_list_snapshot/";
+const MANIFEST_KEY: &str = "file_list_snapshot/manifest.json";
+
+/// Microseconds of the file list objects before the watermark loaded again
+/// on top of the snapshot, covering the objects uploaded but not yet in the
+/// local file list of the node taking the snapshot.
+pub const SAFETY_MARGIN: i64 = 600_000_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub key: String,
+    /// Time in microseconds the file list was read at, the file list objects
+    /// written since are not in the snapshot.
+    pub watermark: i64,
+    pub files: usize,
+    pub created_at: i64,
+}
+
+pub async fn get_manifest() -> Result<Option<Manifest>, anyhow::Error> {
+    let data = match storage::get(MANIFEST_KEY).await {
+        Ok(data) => data,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(json::from_slice(&data)?))
+}
+
+/// Writes a snapshot of the local file list and points the manifest to it,
+/// the previous snapshot is kept for the nodes loading it right now.
+pub async fn create() -> Result<Manifest, anyhow::Error> {
+    let start = std::time::Instant::now();
+    let previous = get_manifest().await?;
+    let watermark = Utc::now().timestamp_micros();
+    let files = infra_file_list::list().await?;
+
+    let mut encoder = zstd::Encoder::new(Vec::new(), 3)?;
+    for (key, meta) in files.iter() {
+        let mut line = json::to_vec(&FileKey::new(key, meta.to_owned(), false))?;
+        line.push(b'\n');
+        encoder.write_all(&line)?;
+    }
+    let data = encoder.finish()?;
+
+    let manifest = Manifest {
+        key: snapshot_key(watermark),
+        watermark,
+        files: files.len(),
+        created_at: Utc::now().timestamp_micros(),
+    };
+    storage::put(&manifest.key, bytes::Bytes::from(data)).await?;
+    storage::put(MANIFEST_KEY, bytes::Bytes::from(json::to_vec(&manifest)?)).await?;
+
+    // drop the snapshots older than the previous one
+    let keep = previous.map(|m| m.key).unwrap_or_default();
+    let expired = storage::list(SNAPSHOT_PREFIX)
+        .await?
+        .into_iter()
+        .filter(|file| file != MANIFEST_KEY && *file != manifest.key && *file != keep)
+        .collect::<Vec<_>>();
+    let expired = expired.iter().map(|f| f.as_str()).collect::<Vec<_>>();
+    if let Err(e) = storage::del(&expired).await {
+        log::error!("[FILE_LIST_SNAPSHOT] delete expired snapshots error: {}", e);
+    }
+
+    log::info!(
+        "[FILE_LIST_SNAPSHOT] create [{}] with {} files done, took: {}ms",
+        manifest.key,
+        manifest.files,
+        start.elapsed().as_millis()
+    );
+    Ok(manifest)
+}
+
+fn snapshot_key(watermark: i64) -> String {
+    format!("{SNAPSHOT_PREFIX}{watermark}.json.zst")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let manifest = Manifest {
+            key: snapshot_key(1718000000000000),
+            watermark: 1718000000000000,
+            files: 3,
+            created_at: 1718000000100000,
+        };
+        assert_eq!(manifest.key, "file_list_snapshot/1718000000000000.json.zst");
+        let data = json::to_vec(&manifest).unwrap();
+        let decoded: Manifest = json::from_slice(&data).unwrap();
+        assert_eq!(decoded, manifest);
+    }
+}
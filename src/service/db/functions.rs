@@ -20,7 +20,7 @@ use config::utils::json;
 use crate::{
     common::{
         infra::config::{QUERY_FUNCTIONS, STREAM_FUNCTIONS},
-        meta::functions::{StreamFunctionsList, Transform},
+        meta::functions::{FunctionStats, StreamFunctionsList, Transform},
     },
     service::db,
 };
@@ -62,6 +62,41 @@ pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Saves the stats of the function on the given node
+pub async fn set_stats(
+    org_id: &str,
+    name: &str,
+    node: &str,
+    stats: &FunctionStats,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/function_stats/{org_id}/{name}/{node}");
+    db::put(
+        &key,
+        json::to_vec(stats).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Returns the stats of the function on every node
+pub async fn list_stats(org_id: &str, name: &str) -> Result<Vec<FunctionStats>, anyhow::Error> {
+    Ok(
+        db::list_values(&format!("/function_stats/{org_id}/{name}/"))
+            .await?
+            .iter()
+            .filter_map(|val| json::from_slice(val).ok())
+            .collect(),
+    )
+}
+
+pub async fn delete_stats(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/function_stats/{org_id}/{name}/");
+    db::delete(&key, true, db::NO_NEED_WATCH, None).await?;
+    Ok(())
+}
+
 pub async fn list(org_id: &str) -> Result<Vec<Transform>, anyhow::Error> {
     Ok(db::list(&format!("/function/{org_id}/"))
         .await?
@@ -125,6 +160,10 @@ pub async fn watch() -> Result<(), anyhow::Error> {
                             group.list.push(stream_fn);
                         }
                     }
+                    // the pipeline steps refer to the functions by name
+                    let mut func = item_value.clone();
+                    func.streams = None;
+                    QUERY_FUNCTIONS.insert(item_key.to_owned(), func);
                 } else {
                     QUERY_FUNCTIONS.insert(item_key.to_owned(), item_value);
                 }
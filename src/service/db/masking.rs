@@ -0,0 +1,133 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::utils::json;
+
+use crate::{
+    common::{infra::config::MASKING_POLICIES, meta::masking::MaskingPolicy},
+    service::db,
+};
+
+const MASKING_KEY: &str = "/masking/policies/";
+
+pub async fn set(org_id: &str, policy: &MaskingPolicy) -> Result<(), anyhow::Error> {
+    let key = format!("{MASKING_KEY}{org_id}/{}", policy.name);
+    match db::put(
+        &key,
+        json::to_vec(policy).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error saving masking policy: {}", e);
+            return Err(anyhow::anyhow!("Error saving masking policy: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<MaskingPolicy, anyhow::Error> {
+    let val = db::get(&format!("{MASKING_KEY}{org_id}/{name}")).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{MASKING_KEY}{org_id}/{name}");
+    match db::delete(&key, false, db::NEED_WATCH, None).await {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error deleting masking policy: {}", e);
+            return Err(anyhow::anyhow!("Error deleting masking policy: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<MaskingPolicy>, anyhow::Error> {
+    Ok(db::list(&format!("{MASKING_KEY}{org_id}/"))
+        .await?
+        .values()
+        .filter_map(|val| json::from_slice(val).ok())
+        .collect())
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = MASKING_KEY;
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching masking policies");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_masking_policies: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let item_value: MaskingPolicy = if config::get_config().common.meta_store_external {
+                    match db::get(&ev.key).await {
+                        Ok(val) => match json::from_slice(&val) {
+                            Ok(val) => val,
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        },
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    json::from_slice(&ev.value.unwrap()).unwrap()
+                };
+                MASKING_POLICIES.insert(item_key.to_owned(), item_value);
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                MASKING_POLICIES.remove(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let key = MASKING_KEY;
+    let ret = db::list(key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let json_val: MaskingPolicy = match json::from_slice(&item_value) {
+            Ok(val) => val,
+            Err(e) => {
+                log::error!("Error parsing masking policy {}: {}", item_key, e);
+                continue;
+            }
+        };
+        MASKING_POLICIES.insert(item_key.to_owned(), json_val);
+    }
+    log::info!("Masking policies Cached");
+    Ok(())
+}
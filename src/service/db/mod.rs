@@ -20,20 +20,34 @@ use infra::{db as infra_db, errors::Result};
 use {infra::errors::Error, o2_enterprise::enterprise::common::infra::config::O2_CONFIG};
 
 pub mod alerts;
+pub mod annotations;
+pub mod backfill;
+pub mod bucket_ingest;
 pub mod compact;
 pub mod dashboards;
+pub mod derived_streams;
+pub mod dlp;
 pub mod enrichment_table;
 pub mod file_list;
 pub mod functions;
+pub mod ingestion_keys;
 pub mod instance;
 pub mod kv;
+pub mod masking;
 pub mod metrics;
+pub mod node;
 pub mod ofga;
 pub mod organization;
 pub mod pipelines;
+pub mod provisioning;
+pub mod rbac;
+pub mod runtime_config;
 pub mod saved_view;
 pub mod scheduler;
 pub mod schema;
+pub mod schema_changes;
+pub mod search_templates;
+pub mod service_accounts;
 pub mod session;
 pub mod syslog;
 pub mod user;
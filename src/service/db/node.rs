@@ -0,0 +1,53 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{meta::cluster::NodeHeartbeat, utils::json};
+
+// heartbeats only concern the local cluster, so they go to the coordinator
+// and aren't replicated to the super cluster
+const HEARTBEAT_KEY: &str = "/nodes_heartbeat/";
+
+pub async fn set_heartbeat(heartbeat: &NodeHeartbeat) -> Result<(), anyhow::Error> {
+    let key = format!("{HEARTBEAT_KEY}{}", heartbeat.uuid);
+    super::get_coordinator()
+        .await
+        .put(
+            &key,
+            json::to_vec(heartbeat).unwrap().into(),
+            super::NO_NEED_WATCH,
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn list_heartbeats() -> Result<Vec<NodeHeartbeat>, anyhow::Error> {
+    Ok(super::get_coordinator()
+        .await
+        .list(HEARTBEAT_KEY)
+        .await?
+        .values()
+        .filter_map(|val| json::from_slice(val).ok())
+        .collect())
+}
+
+pub async fn delete_heartbeat(uuid: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{HEARTBEAT_KEY}{uuid}");
+    super::get_coordinator()
+        .await
+        .delete(&key, false, super::NO_NEED_WATCH, None)
+        .await?;
+    Ok(())
+}
@@ -21,8 +21,8 @@ use infra::errors::{self, Error};
 
 use crate::{
     common::{
-        infra::config::ORGANIZATION_SETTING,
-        meta::organization::{Organization, OrganizationSetting},
+        infra::config::{ORGANIZATION_NETWORK_POLICIES, ORGANIZATION_QUOTAS, ORGANIZATION_SETTING},
+        meta::organization::{OrgNetworkPolicy, OrgQuota, Organization, OrganizationSetting},
     },
     service::db,
 };
@@ -32,6 +32,10 @@ pub const ORG_SETTINGS_KEY_PREFIX: &str = "/organization/setting";
 
 pub const ORG_KEY_PREFIX: &str = "/organization/org";
 
+pub const ORG_QUOTA_KEY_PREFIX: &str = "/organization/quota";
+
+pub const ORG_NETWORK_POLICY_KEY_PREFIX: &str = "/organization/network_policy";
+
 pub async fn set_org_setting(org_name: &str, setting: &OrganizationSetting) -> errors::Result<()> {
     let key = format!("{}/{}", ORG_SETTINGS_KEY_PREFIX, org_name);
     db::put(
@@ -155,3 +159,195 @@ pub async fn delete(org_id: &str) -> Result<(), anyhow::Error> {
     }
     Ok(())
 }
+
+pub async fn set_quota(org_id: &str, quota: &OrgQuota) -> Result<(), anyhow::Error> {
+    let key = format!("{ORG_QUOTA_KEY_PREFIX}/{org_id}");
+    match db::put(
+        &key,
+        json::to_vec(quota).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error saving org quota: {}", e);
+            return Err(anyhow::anyhow!("Error saving org quota: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn delete_quota(org_id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{ORG_QUOTA_KEY_PREFIX}/{org_id}");
+    match db::delete(&key, false, db::NEED_WATCH, None).await {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error deleting org quota: {}", e);
+            return Err(anyhow::anyhow!("Error deleting org quota: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn watch_quotas() -> Result<(), anyhow::Error> {
+    let key = format!("{ORG_QUOTA_KEY_PREFIX}/");
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(&key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching organization quotas");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_org_quotas: event channel closed");
+                return Ok(());
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(&key).unwrap();
+                let item_value: OrgQuota = if config::get_config().common.meta_store_external {
+                    match db::get(&ev.key).await {
+                        Ok(val) => match json::from_slice(&val) {
+                            Ok(val) => val,
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        },
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    json::from_slice(&ev.value.unwrap()).unwrap()
+                };
+                ORGANIZATION_QUOTAS.insert(item_key.to_owned(), item_value);
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(&key).unwrap();
+                ORGANIZATION_QUOTAS.remove(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+}
+
+pub async fn cache_quotas() -> Result<(), anyhow::Error> {
+    let key = format!("{ORG_QUOTA_KEY_PREFIX}/");
+    let ret = db::list(&key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(&key).unwrap();
+        let json_val: OrgQuota = match json::from_slice(&item_value) {
+            Ok(val) => val,
+            Err(e) => {
+                log::error!("Error parsing org quota {}: {}", item_key, e);
+                continue;
+            }
+        };
+        ORGANIZATION_QUOTAS.insert(item_key.to_owned(), json_val);
+    }
+    log::info!("Organization quotas Cached");
+    Ok(())
+}
+
+pub async fn set_network_policy(
+    org_id: &str,
+    policy: &OrgNetworkPolicy,
+) -> Result<(), anyhow::Error> {
+    let key = format!("{ORG_NETWORK_POLICY_KEY_PREFIX}/{org_id}");
+    match db::put(
+        &key,
+        json::to_vec(policy).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error saving org network policy: {}", e);
+            return Err(anyhow::anyhow!("Error saving org network policy: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn delete_network_policy(org_id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{ORG_NETWORK_POLICY_KEY_PREFIX}/{org_id}");
+    match db::delete(&key, false, db::NEED_WATCH, None).await {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error deleting org network policy: {}", e);
+            return Err(anyhow::anyhow!("Error deleting org network policy: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn watch_network_policies() -> Result<(), anyhow::Error> {
+    let key = format!("{ORG_NETWORK_POLICY_KEY_PREFIX}/");
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(&key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching organization network policies");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_org_network_policies: event channel closed");
+                return Ok(());
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(&key).unwrap();
+                let item_value: OrgNetworkPolicy =
+                    if config::get_config().common.meta_store_external {
+                        match db::get(&ev.key).await {
+                            Ok(val) => match json::from_slice(&val) {
+                                Ok(val) => val,
+                                Err(e) => {
+                                    log::error!("Error getting value: {}", e);
+                                    continue;
+                                }
+                            },
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        }
+                    } else {
+                        json::from_slice(&ev.value.unwrap()).unwrap()
+                    };
+                ORGANIZATION_NETWORK_POLICIES.insert(item_key.to_owned(), item_value);
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(&key).unwrap();
+                ORGANIZATION_NETWORK_POLICIES.remove(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+}
+
+pub async fn cache_network_policies() -> Result<(), anyhow::Error> {
+    let key = format!("{ORG_NETWORK_POLICY_KEY_PREFIX}/");
+    let ret = db::list(&key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(&key).unwrap();
+        let json_val: OrgNetworkPolicy = match json::from_slice(&item_value) {
+            Ok(val) => val,
+            Err(e) => {
+                log::error!("Error parsing org network policy {}: {}", item_key, e);
+                continue;
+            }
+        };
+        ORGANIZATION_NETWORK_POLICIES.insert(item_key.to_owned(), json_val);
+    }
+    log::info!("Organization network policies Cached");
+    Ok(())
+}
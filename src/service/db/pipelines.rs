@@ -72,9 +72,47 @@ pub async fn delete(
             return Err(anyhow::anyhow!("Error deleting pipeline: {}", e));
         }
     }
+    let key = format!("/pipeline_version/{org_id}/{stream_type}/{stream_name}/{name}/");
+    if let Err(e) = db::delete(&key, true, db::NO_NEED_WATCH, None).await {
+        log::error!("Error deleting pipeline versions: {}", e);
+    }
     Ok(())
 }
 
+/// Keeps a replaced version of the pipeline
+pub async fn set_version(org_id: &str, pipeline: &PipeLine) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "/pipeline_version/{org_id}/{}/{}/{}/{}",
+        pipeline.stream_type, pipeline.stream_name, pipeline.name, pipeline.version
+    );
+    db::put(
+        &key,
+        json::to_vec(pipeline).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("Error saving pipeline version: {}", e))
+}
+
+/// Lists the replaced versions of the pipeline, the latest first
+pub async fn list_versions(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+) -> Result<Vec<PipeLine>, anyhow::Error> {
+    let mut versions: Vec<PipeLine> = db::list(&format!(
+        "/pipeline_version/{org_id}/{stream_type}/{stream_name}/{name}/"
+    ))
+    .await?
+    .values()
+    .map(|val| json::from_slice(val).unwrap())
+    .collect();
+    versions.sort_by(|a, b| b.version.cmp(&a.version));
+    Ok(versions)
+}
+
 pub async fn list(org_id: &str) -> Result<Vec<PipeLine>, anyhow::Error> {
     Ok(db::list(&format!("/pipeline/{org_id}/"))
         .await?
@@ -158,5 +196,7 @@ pub async fn cache() -> Result<(), anyhow::Error> {
 pub async fn reset() -> Result<(), anyhow::Error> {
     let key = "/pipeline/";
     db::delete(key, true, db::NO_NEED_WATCH, None).await?;
+    let key = "/pipeline_version/";
+    db::delete(key, true, db::NO_NEED_WATCH, None).await?;
     Ok(())
 }
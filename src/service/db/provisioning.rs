@@ -0,0 +1,37 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{common::meta::provisioning::ProvisioningReport, service::db};
+
+/// Returns the report of the last reconciliation of the organization.
+#[tracing::instrument]
+pub(crate) async fn get_report(org_id: &str) -> Result<ProvisioningReport, anyhow::Error> {
+    let val = db::get(&format!("/provisioning/{org_id}/report")).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+#[tracing::instrument(skip(report))]
+pub(crate) async fn put_report(
+    org_id: &str,
+    report: &ProvisioningReport,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/provisioning/{org_id}/report");
+    match db::put(&key, json::to_vec(report)?.into(), db::NO_NEED_WATCH, None).await {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow::anyhow!("Failed to save provisioning report")),
+    }
+}
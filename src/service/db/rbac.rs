@@ -0,0 +1,133 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::utils::json;
+
+use crate::{
+    common::{infra::config::ROLES, meta::rbac::Role},
+    service::db,
+};
+
+const ROLES_KEY: &str = "/rbac/roles/";
+
+pub async fn set(org_id: &str, role: &Role) -> Result<(), anyhow::Error> {
+    let key = format!("{ROLES_KEY}{org_id}/{}", role.name);
+    match db::put(
+        &key,
+        json::to_vec(role).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error saving role: {}", e);
+            return Err(anyhow::anyhow!("Error saving role: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<Role, anyhow::Error> {
+    let val = db::get(&format!("{ROLES_KEY}{org_id}/{name}")).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{ROLES_KEY}{org_id}/{name}");
+    match db::delete(&key, false, db::NEED_WATCH, None).await {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error deleting role: {}", e);
+            return Err(anyhow::anyhow!("Error deleting role: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<Role>, anyhow::Error> {
+    Ok(db::list(&format!("{ROLES_KEY}{org_id}/"))
+        .await?
+        .values()
+        .filter_map(|val| json::from_slice(val).ok())
+        .collect())
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = ROLES_KEY;
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching roles");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_roles: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let item_value: Role = if config::get_config().common.meta_store_external {
+                    match db::get(&ev.key).await {
+                        Ok(val) => match json::from_slice(&val) {
+                            Ok(val) => val,
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        },
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    json::from_slice(&ev.value.unwrap()).unwrap()
+                };
+                ROLES.insert(item_key.to_owned(), item_value);
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                ROLES.remove(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let key = ROLES_KEY;
+    let ret = db::list(key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let json_val: Role = match json::from_slice(&item_value) {
+            Ok(val) => val,
+            Err(e) => {
+                log::error!("Error parsing role {}: {}", item_key, e);
+                continue;
+            }
+        };
+        ROLES.insert(item_key.to_owned(), json_val);
+    }
+    log::info!("Roles Cached");
+    Ok(())
+}
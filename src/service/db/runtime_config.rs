@@ -0,0 +1,91 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use config::utils::json;
+
+use crate::service::{db, runtime_config};
+
+const RUNTIME_CONFIG_KEY: &str = "/runtime_config/overrides";
+
+pub async fn get() -> Result<BTreeMap<String, String>, anyhow::Error> {
+    match db::get(RUNTIME_CONFIG_KEY).await {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(_) => Ok(BTreeMap::new()),
+    }
+}
+
+pub async fn set(overrides: &BTreeMap<String, String>) -> Result<(), anyhow::Error> {
+    match db::put(
+        RUNTIME_CONFIG_KEY,
+        json::to_vec(overrides).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Error saving runtime config: {}", e);
+            return Err(anyhow::anyhow!("Error saving runtime config: {}", e));
+        }
+    }
+    Ok(())
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = RUNTIME_CONFIG_KEY;
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching runtime config");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_runtime_config: event channel closed");
+                break;
+            }
+        };
+        let overrides = match ev {
+            db::Event::Put(_) => match get().await {
+                Ok(val) => val,
+                Err(e) => {
+                    log::error!("Error getting runtime config: {}", e);
+                    continue;
+                }
+            },
+            db::Event::Delete(_) => BTreeMap::new(),
+            db::Event::Empty => continue,
+        };
+        if let Err(e) = runtime_config::apply(overrides).await {
+            log::error!("Error applying runtime config: {}", e);
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let overrides = get().await?;
+    // a node with a bad override still starts, with the settings of its env
+    if !overrides.is_empty() {
+        if let Err(e) = runtime_config::apply(overrides).await {
+            log::error!("Error applying runtime config: {}", e);
+        }
+    }
+    log::info!("Runtime config Cached");
+    Ok(())
+}
@@ -0,0 +1,87 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{meta::stream::StreamType, utils::json};
+
+use crate::{common::meta::stream::PendingSchemaChange, service::db};
+
+const SCHEMA_CHANGES_KEY: &str = "/schema_changes/";
+
+fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str, field: &str) -> String {
+    format!("{SCHEMA_CHANGES_KEY}{org_id}/{stream_type}/{stream_name}/{field}")
+}
+
+pub async fn set(org_id: &str, change: &PendingSchemaChange) -> Result<(), anyhow::Error> {
+    let key = mk_key(
+        org_id,
+        change.stream_type,
+        &change.stream_name,
+        &change.field,
+    );
+    if let Err(e) = db::put(
+        &key,
+        json::to_vec(change).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        log::error!("Error saving pending schema change: {}", e);
+        return Err(anyhow::anyhow!("Error saving pending schema change: {}", e));
+    }
+    Ok(())
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    field: &str,
+) -> Result<PendingSchemaChange, anyhow::Error> {
+    let val = db::get(&mk_key(org_id, stream_type, stream_name, field)).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    field: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name, field);
+    if let Err(e) = db::delete(&key, false, db::NO_NEED_WATCH, None).await {
+        log::error!("Error deleting pending schema change: {}", e);
+        return Err(anyhow::anyhow!(
+            "Error deleting pending schema change: {}",
+            e
+        ));
+    }
+    Ok(())
+}
+
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<PendingSchemaChange>, anyhow::Error> {
+    let prefix = format!("{SCHEMA_CHANGES_KEY}{org_id}/{stream_type}/{stream_name}/");
+    let mut changes = db::list(&prefix)
+        .await?
+        .values()
+        .filter_map(|val| json::from_slice::<PendingSchemaChange>(val).ok())
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| a.field.cmp(&b.field));
+    Ok(changes)
+}
@@ -0,0 +1,62 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{common::meta::search_templates::SearchTemplate, service::db};
+
+const SEARCH_TEMPLATES_KEY: &str = "/search_templates/";
+
+pub async fn get(org_id: &str, name: &str) -> Result<SearchTemplate, anyhow::Error> {
+    let key = format!("{SEARCH_TEMPLATES_KEY}{org_id}/{name}");
+    match db::get(&key).await {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(_) => Err(anyhow::anyhow!("Search template not found")),
+    }
+}
+
+pub async fn set(org_id: &str, template: &SearchTemplate) -> Result<(), anyhow::Error> {
+    let key = format!("{SEARCH_TEMPLATES_KEY}{org_id}/{}", template.name);
+    match db::put(
+        &key,
+        json::to_vec(template).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow::anyhow!("Error saving search template: {}", e)),
+    }
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{SEARCH_TEMPLATES_KEY}{org_id}/{name}");
+    match db::delete(&key, false, db::NO_NEED_WATCH, None).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow::anyhow!("Error deleting search template: {}", e)),
+    }
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<SearchTemplate>, anyhow::Error> {
+    let key = format!("{SEARCH_TEMPLATES_KEY}{org_id}/");
+    let mut items = db::list_values(&key)
+        .await?
+        .iter()
+        .filter_map(|val| json::from_slice::<SearchTemplate>(val).ok())
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(items)
+}
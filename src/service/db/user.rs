@@ -13,10 +13,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::bail;
 use config::utils::json;
+use infra::errors::{DbError, Error};
 
 use crate::{
     common::{
@@ -113,6 +114,8 @@ pub async fn set(user: &DBUser) -> Result<(), anyhow::Error> {
             salt: user.salt.clone(),
             is_external: user.is_external,
             password_ext: user.password_ext.clone(),
+            password_updated_at: user.password_updated_at,
+            force_password_reset: user.force_password_reset,
         };
         USERS.insert(
             format!("{}/{}", org.name.clone(), user.email.clone()),
@@ -140,6 +143,42 @@ pub async fn delete(name: &str) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Returns the attributes of the user in the organization, the row filters of
+/// the roles refer to them as `{{user.<attribute>}}`.
+pub async fn get_attributes(
+    org_id: &str,
+    name: &str,
+) -> Result<HashMap<String, String>, anyhow::Error> {
+    let key = format!("/user_attributes/{org_id}/{name}");
+    match db::get(&key).await {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(HashMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set_attributes(
+    org_id: &str,
+    name: &str,
+    attributes: &HashMap<String, String>,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/user_attributes/{org_id}/{name}");
+    db::put(
+        &key,
+        json::to_vec(attributes).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_attributes(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/user_attributes/{org_id}/{name}");
+    db::delete_if_exists(&key, false, db::NO_NEED_WATCH).await?;
+    Ok(())
+}
+
 pub async fn watch() -> Result<(), anyhow::Error> {
     let key = "/user/";
     let cluster_coordinator = db::get_coordinator().await;
@@ -314,6 +353,8 @@ mod tests {
                 rum_token: Some("rumAbcd".to_string()),
             }],
             password_ext: Some("pass".to_string()),
+            password_updated_at: 0,
+            force_password_reset: false,
         })
         .await;
         assert!(resp.is_ok());
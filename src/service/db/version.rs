@@ -15,6 +15,8 @@
 
 use crate::{common::infra::config, service::db};
 
+const LAYOUT_VERSION_KEY: &str = "/meta/kv/layout_version";
+
 pub async fn get() -> Result<String, anyhow::Error> {
     let ret = db::get("/meta/kv/version").await?;
     let version = std::str::from_utf8(&ret).unwrap();
@@ -31,3 +33,22 @@ pub async fn set() -> Result<(), anyhow::Error> {
     .await?;
     Ok(())
 }
+
+/// The layout version of the meta store, 0 before the versioned migrations.
+pub async fn get_layout() -> Result<u32, anyhow::Error> {
+    match db::get(LAYOUT_VERSION_KEY).await {
+        Ok(ret) => Ok(std::str::from_utf8(&ret)?.parse()?),
+        Err(_) => Ok(0),
+    }
+}
+
+pub async fn set_layout(version: u32) -> Result<(), anyhow::Error> {
+    db::put(
+        LAYOUT_VERSION_KEY,
+        bytes::Bytes::from(version.to_string()),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?;
+    Ok(())
+}
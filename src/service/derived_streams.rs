@@ -0,0 +1,371 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Derived streams materialize a SQL query into a stream. The windows of a
+//! derived stream are run one after the other by the scheduler, a window is
+//! committed by moving the watermark past it once its rows are ingested. The
+//! windows are written at least once: a crash between the ingestion and the
+//! watermark save writes the window again on the next run, its rows can be
+//! told apart by their `_window_start`.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{http::StatusCode, HttpResponse};
+use chrono::Utc;
+use config::{
+    get_config, ider,
+    meta::{
+        search::SearchEventType,
+        sql::Sql,
+        usage::{TriggerData, TriggerDataStatus, TriggerDataType},
+    },
+    utils::json::{self, Value},
+};
+use infra::dist_lock;
+use proto::cluster_rpc;
+
+use crate::{
+    common::meta::{
+        derived_streams::{DerivedStream, DerivedStreamList},
+        http::HttpResponse as MetaHttpResponse,
+        rbac::{Permission, Resource},
+    },
+    service::{
+        db, format_stream_name, masking, rbac, search as SearchService,
+        usage::{ingestion_service, publish_triggers_usage},
+    },
+};
+
+/// Minimum window length of a derived stream, in seconds
+const MIN_FREQUENCY: i64 = 60;
+
+const DERIVED_STREAM_NOT_FOUND: &str = "Derived stream not found";
+
+/// Saves the derived stream, its query runs as `user_id` from then on.
+pub async fn save(
+    org_id: &str,
+    name: &str,
+    mut ds: DerivedStream,
+    create: bool,
+    user_id: &str,
+) -> Result<HttpResponse, Error> {
+    ds.name = name.trim().to_string();
+    if !rbac::is_valid_role_name(&ds.name) {
+        return Ok(MetaHttpResponse::bad_request(
+            "Derived stream name can only contain alphanumeric characters, '_' and '-'",
+        ));
+    }
+    if ds.frequency < MIN_FREQUENCY {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "Derived stream frequency should be at least {MIN_FREQUENCY} seconds"
+        )));
+    }
+    if ds.delay < 0 {
+        return Ok(MetaHttpResponse::bad_request(
+            "Derived stream delay can not be negative",
+        ));
+    }
+    ds.destination = format_stream_name(ds.destination.trim());
+    if ds.destination.is_empty() {
+        return Ok(MetaHttpResponse::bad_request(
+            "Derived stream destination is required",
+        ));
+    }
+    let source = match Sql::new(&ds.query) {
+        Ok(sql) => sql.source,
+        Err(e) => {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "Invalid derived stream query: {e}"
+            )));
+        }
+    };
+    if source == ds.destination {
+        return Ok(MetaHttpResponse::bad_request(
+            "Derived stream can not write into the stream it reads",
+        ));
+    }
+    if !SearchService::can_read_stream(org_id, user_id, ds.stream_type, &source).await
+        || !rbac::is_allowed(
+            org_id,
+            user_id,
+            Resource::Stream,
+            &ds.destination,
+            Permission::Write,
+        )
+    {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+    ds.owner = user_id.to_string();
+
+    let existing = db::derived_streams::get(org_id, &ds.name).await.ok();
+    match (create, existing) {
+        (true, Some(_)) => {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "Derived stream {} already exists",
+                ds.name
+            )));
+        }
+        (false, None) => return Ok(MetaHttpResponse::not_found(DERIVED_STREAM_NOT_FOUND)),
+        // keep the windows already materialized
+        (false, Some(old)) => ds.watermark = old.watermark,
+        // start with the current window
+        (true, None) => {
+            let now = Utc::now().timestamp_micros();
+            ds.watermark = now - now % ds.period();
+        }
+    }
+    ds.last_error = None;
+
+    match db::derived_streams::set(org_id, &ds, create).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(ds)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    match db::derived_streams::get(org_id, name).await {
+        Ok(ds) => Ok(HttpResponse::Ok().json(ds)),
+        Err(_) => Ok(MetaHttpResponse::not_found(DERIVED_STREAM_NOT_FOUND)),
+    }
+}
+
+pub async fn list(org_id: &str) -> Result<HttpResponse, Error> {
+    match db::derived_streams::list(org_id).await {
+        Ok(list) => Ok(HttpResponse::Ok().json(DerivedStreamList { list })),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    if db::derived_streams::get(org_id, name).await.is_err() {
+        return Ok(MetaHttpResponse::not_found(DERIVED_STREAM_NOT_FOUND));
+    }
+    match db::derived_streams::delete(org_id, name).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "Derived stream deleted".to_string(),
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn handle_trigger(trigger: db::scheduler::Trigger) -> Result<(), anyhow::Error> {
+    let org_id = &trigger.org;
+    // For derived streams, trigger.module_key is the derived stream name
+    let name = &trigger.module_key;
+    let now = Utc::now().timestamp_micros();
+
+    let ds = db::derived_streams::get(org_id, name).await?;
+    let mut new_trigger = db::scheduler::Trigger {
+        next_run_at: now + ds.period(),
+        is_realtime: false,
+        is_silenced: false,
+        status: db::scheduler::TriggerStatus::Waiting,
+        retries: 0,
+        ..trigger.clone()
+    };
+    if !ds.enabled {
+        db::scheduler::update_trigger(new_trigger).await?;
+        return Ok(());
+    }
+
+    // only one node runs the windows of a derived stream at a time, even when
+    // a timed out trigger is pulled again while the first run goes on
+    let lock_key = format!("/derived_streams/{org_id}/{name}");
+    let locker = dist_lock::lock(&lock_key, 0).await?;
+    let ret = run_windows(org_id, name).await;
+    dist_lock::unlock(&locker).await?;
+    drop(locker);
+
+    let mut trigger_data_stream = TriggerData {
+        org: trigger.org.clone(),
+        module: TriggerDataType::DerivedStream,
+        key: trigger.module_key.clone(),
+        next_run_at: new_trigger.next_run_at,
+        is_realtime: trigger.is_realtime,
+        is_silenced: trigger.is_silenced,
+        status: TriggerDataStatus::Completed,
+        start_time: trigger.start_time.unwrap_or_default(),
+        end_time: trigger.end_time.unwrap_or_default(),
+        retries: trigger.retries,
+        error: None,
+    };
+    match ret {
+        Ok(ds) => {
+            new_trigger.next_run_at = ds.next_run_at();
+            trigger_data_stream.next_run_at = new_trigger.next_run_at;
+            db::scheduler::update_trigger(new_trigger).await?;
+        }
+        Err(e) => {
+            log::error!("[DERIVED_STREAM] {org_id}/{name} error: {e}");
+            if trigger.retries + 1 >= get_config().limit.scheduler_max_retries {
+                // keep the window, and try it again after a period
+                db::scheduler::update_trigger(new_trigger).await?;
+            } else {
+                db::scheduler::update_status(
+                    &new_trigger.org,
+                    new_trigger.module,
+                    &new_trigger.module_key,
+                    db::scheduler::TriggerStatus::Waiting,
+                    trigger.retries + 1,
+                )
+                .await?;
+            }
+            trigger_data_stream.status = TriggerDataStatus::Failed;
+            trigger_data_stream.error = Some(format!("error materializing derived stream: {e}"));
+        }
+    }
+    trigger_data_stream.end_time = Utc::now().timestamp_micros();
+    publish_triggers_usage(trigger_data_stream).await;
+
+    Ok(())
+}
+
+/// Materializes the closed windows of the derived stream, the watermark is
+/// saved after each window.
+async fn run_windows(org_id: &str, name: &str) -> Result<DerivedStream, anyhow::Error> {
+    let cfg = get_config();
+    let now = Utc::now().timestamp_micros();
+    // read under the lock, the watermark may have moved since the trigger fired
+    let mut ds = db::derived_streams::get(org_id, name).await?;
+    for _ in 0..cfg.limit.derived_stream_max_windows {
+        let Some((start, end)) = ds.next_window(now) else {
+            break;
+        };
+        let ret = materialize_window(org_id, &ds, start, end).await;
+        // re-read to keep the changes saved while the window ran
+        let mut latest = db::derived_streams::get(org_id, name).await?;
+        match ret {
+            Ok(num) => {
+                log::info!(
+                    "[DERIVED_STREAM] {org_id}/{name} window [{start}, {end}) wrote {num} rows into {}",
+                    ds.destination
+                );
+                latest.watermark = end;
+                latest.last_error = None;
+                db::derived_streams::set_without_updating_trigger(org_id, &latest).await?;
+                ds = latest;
+            }
+            Err(e) => {
+                latest.last_error = Some(e.to_string());
+                db::derived_streams::set_without_updating_trigger(org_id, &latest).await?;
+                return Err(e);
+            }
+        }
+    }
+    Ok(ds)
+}
+
+async fn materialize_window(
+    org_id: &str,
+    ds: &DerivedStream,
+    start: i64,
+    end: i64,
+) -> Result<usize, anyhow::Error> {
+    let cfg = get_config();
+    // the owner may have lost the access to the source since it was saved
+    let source = Sql::new(&ds.query)?.source;
+    if ds.owner.is_empty()
+        || !SearchService::can_read_stream(org_id, &ds.owner, ds.stream_type, &source).await
+    {
+        return Err(anyhow::anyhow!(
+            "the owner of the derived stream can not read the stream {source}, save it again"
+        ));
+    }
+    let req = config::meta::search::Request {
+        query: config::meta::search::Query {
+            sql: ds.query.clone(),
+            from: 0,
+            // one more row tells a full window from a truncated one
+            size: cfg.limit.derived_stream_max_rows + 1,
+            start_time: start,
+            end_time: end,
+            sql_mode: "full".to_string(),
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: Some(SearchEventType::DerivedStream),
+    };
+    let trace_id = ider::uuid();
+    let mut hits = SearchService::search(
+        &trace_id,
+        org_id,
+        ds.stream_type,
+        Some(ds.owner.clone()),
+        &req,
+    )
+    .await?
+    .hits;
+    if hits.len() as i64 > cfg.limit.derived_stream_max_rows {
+        return Err(anyhow::anyhow!(
+            "the window [{start}, {end}) has more than {} rows, aggregate the query or raise ZO_DERIVED_STREAM_MAX_ROWS",
+            cfg.limit.derived_stream_max_rows
+        ));
+    }
+    masking::mask_hits(org_id, &ds.owner, ds.stream_type, &source, &mut hits);
+    let records = window_records(hits, &cfg.common.column_timestamp, start, end);
+    if records.is_empty() {
+        return Ok(0);
+    }
+    let num = records.len();
+    let req = cluster_rpc::UsageRequest {
+        stream_name: ds.destination.clone(),
+        data: Some(cluster_rpc::UsageData::from(records)),
+    };
+    ingestion_service::ingest(org_id, req).await?;
+    Ok(num)
+}
+
+/// Tags the rows with their window, the rows without a timestamp are written
+/// at the start of the window.
+fn window_records(hits: Vec<Value>, column_timestamp: &str, start: i64, end: i64) -> Vec<Value> {
+    hits.into_iter()
+        .filter_map(|hit| match hit {
+            Value::Object(mut row) => {
+                if !row.get(column_timestamp).is_some_and(|v| v.is_i64()) {
+                    row.insert(column_timestamp.to_string(), Value::Number(start.into()));
+                }
+                row.insert("_window_start".to_string(), Value::Number(start.into()));
+                row.insert("_window_end".to_string(), Value::Number(end.into()));
+                Some(Value::Object(row))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_records() {
+        let hits = vec![
+            json::json!({"errors": 3}),
+            json::json!({"_timestamp": 15, "errors": 1}),
+            json::json!("not a row"),
+        ];
+        let records = window_records(hits, "_timestamp", 10, 20);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["_timestamp"], 10);
+        assert_eq!(records[1]["_timestamp"], 15);
+        assert_eq!(records[1]["_window_start"], 10);
+        assert_eq!(records[1]["_window_end"], 20);
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! DLP rules redact the sensitive values of the logs records when they are
+//! ingested, before the records are written to the WAL.
+
+use std::io::Error;
+
+use actix_web::HttpResponse;
+use config::{metrics, utils::json};
+
+use crate::{
+    common::{
+        infra::config::STREAM_DLP,
+        meta::{dlp::StreamDlp, http::HttpResponse as MetaHttpResponse},
+    },
+    service::{db, format_stream_name},
+};
+
+const DLP_NOT_FOUND: &str = "DLP rules not found";
+
+pub async fn get_rules(org_id: &str, stream_name: &str) -> Result<HttpResponse, Error> {
+    let stream_name = format_stream_name(stream_name);
+    match STREAM_DLP.get(&format!("{org_id}/{stream_name}")) {
+        Some(dlp) => Ok(HttpResponse::Ok().json(dlp.value())),
+        None => Ok(MetaHttpResponse::not_found(DLP_NOT_FOUND)),
+    }
+}
+
+pub async fn save_rules(
+    org_id: &str,
+    stream_name: &str,
+    mut dlp: StreamDlp,
+) -> Result<HttpResponse, Error> {
+    let stream_name = format_stream_name(stream_name);
+    for rule in dlp.rules.iter_mut() {
+        rule.name = rule.name.trim().to_string();
+        if rule.name.is_empty() {
+            return Ok(MetaHttpResponse::bad_request("DLP rule name is required"));
+        }
+    }
+    if let Err(e) = dlp.compile() {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    if let Err(e) = db::dlp::set(org_id, &stream_name, &dlp).await {
+        return Ok(MetaHttpResponse::internal_error(e));
+    }
+    let resp = HttpResponse::Ok().json(&dlp);
+    STREAM_DLP.insert(format!("{org_id}/{stream_name}"), dlp);
+    Ok(resp)
+}
+
+pub async fn delete_rules(org_id: &str, stream_name: &str) -> Result<HttpResponse, Error> {
+    let stream_name = format_stream_name(stream_name);
+    let key = format!("{org_id}/{stream_name}");
+    if !STREAM_DLP.contains_key(&key) {
+        return Ok(MetaHttpResponse::not_found(DLP_NOT_FOUND));
+    }
+    if let Err(e) = db::dlp::delete(org_id, &stream_name).await {
+        return Ok(MetaHttpResponse::internal_error(e));
+    }
+    STREAM_DLP.remove(&key);
+    Ok(MetaHttpResponse::ok("DLP rules deleted"))
+}
+
+/// Redacts the record with the rules of the stream.
+pub fn redact(org_id: &str, stream_name: &str, record: &mut json::Map<String, json::Value>) {
+    let Some(dlp) = STREAM_DLP.get(&format!("{org_id}/{stream_name}")) else {
+        return;
+    };
+    for (rule, count) in dlp.apply(record) {
+        metrics::DLP_REDACTIONS
+            .with_label_values(&[org_id, stream_name, rule])
+            .inc_by(count);
+    }
+}
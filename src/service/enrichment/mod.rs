@@ -1,10 +1,13 @@
-use std::collections::BTreeMap;
+use std::{cmp::Ordering, collections::BTreeMap, net::IpAddr};
 
 use async_trait::async_trait;
 use config::utils::time::parse_str_to_time;
+use ipnetwork::IpNetwork;
 use vector_enrichment::{Case, IndexHandle, Table};
 use vrl::value::Value;
 
+use crate::common::{infra::config::ENRICHMENT_TABLE_LOOKUPS, meta::enrichment_table::LookupMode};
+
 #[derive(Clone)]
 pub struct StreamTableConfig {}
 
@@ -64,6 +67,88 @@ impl Table for StreamTable {
     }
 }
 
+/// How specific a matched row is, rows with a longer prefix or a narrower range
+/// compare greater.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+struct Specificity {
+    prefix_len: u32,
+    neg_range_width: f64,
+}
+
+fn match_equals(
+    column: &Value,
+    value: &Value,
+    mode: LookupMode,
+    case: Case,
+    specificity: &mut Specificity,
+) -> bool {
+    match mode {
+        LookupMode::Exact => match case {
+            Case::Insensitive => match (column, value) {
+                (Value::Bytes(bytes1), Value::Bytes(bytes2)) => {
+                    match (std::str::from_utf8(bytes1), std::str::from_utf8(bytes2)) {
+                        (Ok(s1), Ok(s2)) => s1.eq_ignore_ascii_case(s2),
+                        (Err(_), Err(_)) => bytes1 == bytes2,
+                        _ => false,
+                    }
+                }
+                _ => false,
+            },
+            Case::Sensitive => column == value,
+        },
+        LookupMode::Cidr => {
+            let (Some(network), Some(ip)) = (
+                column
+                    .as_str()
+                    .and_then(|v| v.trim().parse::<IpNetwork>().ok()),
+                value.as_str().and_then(|v| v.trim().parse::<IpAddr>().ok()),
+            ) else {
+                return false;
+            };
+            if !network.contains(ip) {
+                return false;
+            }
+            specificity.prefix_len += network.prefix() as u32;
+            true
+        }
+        LookupMode::Range => {
+            let (Some((from, to)), Some(v)) = (
+                column.as_str().and_then(|v| parse_range(&v)),
+                as_number(value),
+            ) else {
+                return false;
+            };
+            if v < from || v > to {
+                return false;
+            }
+            specificity.neg_range_width -= to - from;
+            true
+        }
+    }
+}
+
+/// Parses `from-to` (inclusive) or a single number
+fn parse_range(s: &str) -> Option<(f64, f64)> {
+    let s = s.trim();
+    // skip the first char, which may be the sign of the lower bound
+    let (from, to) = match s.char_indices().skip(1).find(|(_, c)| *c == '-') {
+        Some((i, _)) => (&s[..i], &s[i + 1..]),
+        None => (s, s),
+    };
+    let from = from.trim().parse::<f64>().ok()?;
+    let to = to.trim().parse::<f64>().ok()?;
+    (from <= to).then_some((from, to))
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(v) => Some(*v as f64),
+        Value::Float(v) => Some(v.into_inner()),
+        Value::Bytes(_) => value.as_str()?.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
 fn get_data(
     table: &StreamTable,
     condition: &[vector_enrichment::Condition],
@@ -71,55 +156,51 @@ fn get_data(
     case: vector_enrichment::Case,
 ) -> Vec<BTreeMap<String, vrl::value::Value>> {
     let mut resp = vec![];
-    let filtered: Vec<&vrl::value::Value> = table
+    if condition.is_empty() {
+        return resp;
+    }
+    let lookup = ENRICHMENT_TABLE_LOOKUPS
+        .get(&format!("{}/{}", table.org_id, table.stream_name))
+        .map(|v| v.value().clone())
+        .unwrap_or_default();
+
+    // a row matches when all the conditions match, so that composite keys can
+    // be expressed as several conditions
+    let mut matched: Vec<(Specificity, &vrl::value::Value)> = table
         .data
         .iter()
-        .filter(|v| {
-            if let vrl::value::Value::Object(map) = v {
-                for cond in condition {
-                    match cond {
-                        vector_enrichment::Condition::Equals { field, value } => match case {
-                            Case::Insensitive => {
-                                if let Some(Value::Bytes(bytes1)) = map.get(field.to_owned()) {
-                                    if let Value::Bytes(bytes2) = value {
-                                        match (
-                                            std::str::from_utf8(bytes1),
-                                            std::str::from_utf8(bytes2),
-                                        ) {
-                                            (Ok(s1), Ok(s2)) => {
-                                                return s1.eq_ignore_ascii_case(s2);
-                                            }
-                                            (Err(_), Err(_)) => return bytes1 == bytes2,
-                                            _ => return false,
-                                        }
-                                    }
-                                }
-                            }
-                            Case::Sensitive => {
-                                if let Some(v) = map.get(field.to_owned()) {
-                                    if v.clone() == value.clone() {
-                                        return true;
-                                    }
-                                }
-                            }
-                        },
-                        vector_enrichment::Condition::BetweenDates { field, from, to } => {
-                            if let Some(v) = map.get(field.to_owned()) {
-                                if let Some(v) = v.as_str() {
-                                    if let Ok(v) = parse_str_to_time(&v) {
-                                        if v >= *from && v <= *to {
-                                            return true;
-                                        }
-                                    }
-                                }
-                            }
+        .filter_map(|v| {
+            let vrl::value::Value::Object(map) = v else {
+                return None;
+            };
+            let mut specificity = Specificity::default();
+            for cond in condition {
+                let matched = match cond {
+                    vector_enrichment::Condition::Equals { field, value } => {
+                        let mode = lookup.columns.get(*field).copied().unwrap_or_default();
+                        match map.get(*field) {
+                            Some(v) => match_equals(v, value, mode, case, &mut specificity),
+                            None => false,
                         }
                     }
+                    vector_enrichment::Condition::BetweenDates { field, from, to } => map
+                        .get(*field)
+                        .and_then(|v| v.as_str())
+                        .and_then(|v| parse_str_to_time(&v).ok())
+                        .map(|v| v >= *from && v <= *to)
+                        .unwrap_or_default(),
+                };
+                if !matched {
+                    return None;
                 }
             }
-            false
+            Some((specificity, v))
         })
         .collect();
+    // the most specific rows first, so that `get_enrichment_table_record` returns
+    // the longest prefix or the narrowest range
+    matched.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    let filtered = matched.into_iter().map(|(_, v)| v);
 
     match select {
         Some(val) => {
@@ -150,3 +231,101 @@ fn get_data(
 
     resp
 }
+
+#[cfg(test)]
+mod tests {
+    use vector_enrichment::Condition;
+
+    use super::*;
+    use crate::common::meta::enrichment_table::EnrichmentTableLookup;
+
+    fn row(fields: &[(&str, &str)]) -> Value {
+        Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| (k.to_string().into(), Value::from(*v)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("1024-2048"), Some((1024.0, 2048.0)));
+        assert_eq!(parse_range("-10 - -5"), Some((-10.0, -5.0)));
+        assert_eq!(parse_range("443"), Some((443.0, 443.0)));
+        assert_eq!(parse_range("2048-1024"), None);
+        assert_eq!(parse_range("abc"), None);
+    }
+
+    #[test]
+    fn test_get_data_composite_and_range() {
+        let table = StreamTable {
+            org_id: "test_enrichment_org".to_string(),
+            stream_name: "networks".to_string(),
+            data: vec![
+                row(&[
+                    ("network", "10.0.0.0/8"),
+                    ("ports", "0-65535"),
+                    ("name", "corp"),
+                ]),
+                row(&[
+                    ("network", "10.1.0.0/16"),
+                    ("ports", "0-65535"),
+                    ("name", "dc"),
+                ]),
+                row(&[
+                    ("network", "10.1.2.0/24"),
+                    ("ports", "80-443"),
+                    ("name", "web"),
+                ]),
+            ],
+        };
+        ENRICHMENT_TABLE_LOOKUPS.insert(
+            "test_enrichment_org/networks".to_string(),
+            EnrichmentTableLookup {
+                columns: [
+                    ("network".to_string(), LookupMode::Cidr),
+                    ("ports".to_string(), LookupMode::Range),
+                ]
+                .into_iter()
+                .collect(),
+            },
+        );
+
+        let names = |ip: &str, port: i64| {
+            let conditions = [
+                Condition::Equals {
+                    field: "network",
+                    value: Value::from(ip),
+                },
+                Condition::Equals {
+                    field: "ports",
+                    value: Value::Integer(port),
+                },
+            ];
+            get_data(&table, &conditions, None, Case::Sensitive)
+                .into_iter()
+                .map(|r| r.get("name").unwrap().as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("10.1.2.3", 443), vec!["web", "dc", "corp"]);
+        assert_eq!(names("10.1.2.3", 8080), vec!["dc", "corp"]);
+        assert_eq!(names("10.2.0.1", 22), vec!["corp"]);
+        assert!(names("192.168.0.1", 22).is_empty());
+
+        // columns without a lookup mode are matched exactly
+        let conditions = [
+            Condition::Equals {
+                field: "network",
+                value: Value::from("10.1.2.3"),
+            },
+            Condition::Equals {
+                field: "name",
+                value: Value::from("dc"),
+            },
+        ];
+        let rows = get_data(&table, &conditions, None, Case::Sensitive);
+        assert_eq!(rows.len(), 1);
+        ENRICHMENT_TABLE_LOOKUPS.remove("test_enrichment_org/networks");
+    }
+}
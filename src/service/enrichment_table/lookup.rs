@@ -0,0 +1,64 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{http::StatusCode, HttpResponse};
+
+use crate::{
+    common::meta::{
+        enrichment_table::EnrichmentTableLookup, http::HttpResponse as MetaHttpResponse,
+    },
+    service::{db, format_stream_name},
+};
+
+pub async fn save_lookup(
+    org_id: &str,
+    table_name: &str,
+    lookup: EnrichmentTableLookup,
+) -> Result<HttpResponse, Error> {
+    let table_name = format_stream_name(table_name.trim());
+    if lookup.columns.keys().any(|k| k.trim().is_empty()) {
+        return Ok(MetaHttpResponse::bad_request(
+            "enrichment table lookup column name is required",
+        ));
+    }
+    match db::enrichment_table::set_lookup(org_id, &table_name, &lookup).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "Enrichment table lookup saved".to_string(),
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn get_lookup(org_id: &str, table_name: &str) -> Result<HttpResponse, Error> {
+    let table_name = format_stream_name(table_name.trim());
+    let lookup = db::enrichment_table::get_lookup(org_id, &table_name)
+        .await
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok().json(lookup))
+}
+
+pub async fn delete_lookup(org_id: &str, table_name: &str) -> Result<HttpResponse, Error> {
+    let table_name = format_stream_name(table_name.trim());
+    match db::enrichment_table::delete_lookup(org_id, &table_name).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "Enrichment table lookup deleted".to_string(),
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
@@ -51,6 +51,8 @@ use crate::{
 };
 
 pub mod geoip;
+pub mod lookup;
+pub mod source;
 
 pub async fn save_enrichment_data(
     org_id: &str,
@@ -58,6 +60,34 @@ pub async fn save_enrichment_data(
     mut payload: Multipart,
     thread_id: usize,
     append_data: bool,
+) -> Result<HttpResponse, Error> {
+    let mut files = Vec::new();
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let content_disposition = field.content_disposition();
+        let filename = content_disposition.get_filename();
+        let mut data = bytes::Bytes::new();
+
+        if filename.is_some() {
+            while let Some(chunk) = field.next().await {
+                let chunked_data = chunk.unwrap();
+                // Reconstruct entire CSV data bytes here to prevent fragmentation of values.
+                data = Bytes::from([data.as_ref(), chunked_data.as_ref()].concat());
+            }
+            files.push(data);
+        }
+    }
+    save_enrichment_csv(org_id, table_name, &files, thread_id, append_data).await
+}
+
+/// Ingests the given CSV files into the enrichment table. Unless `append_data`
+/// is set the existing table is replaced, but only once the new content has
+/// been parsed successfully.
+pub async fn save_enrichment_csv(
+    org_id: &str,
+    table_name: &str,
+    files: &[Bytes],
+    thread_id: usize,
+    append_data: bool,
 ) -> Result<HttpResponse, Error> {
     let start = std::time::Instant::now();
     let mut hour_key = String::new();
@@ -99,12 +129,7 @@ pub async fn save_enrichment_data(
     )
     .await;
 
-    if stream_schema.has_fields && !append_data {
-        delete_enrichment_table(org_id, stream_name, StreamType::EnrichmentTables).await;
-    }
-
-    let mut records = vec![];
-    let mut records_size = 0;
+    let append_data = append_data && stream_schema.has_fields;
     let timestamp = if !append_data {
         Utc::now().timestamp_micros()
     } else {
@@ -117,80 +142,12 @@ pub async fn save_enrichment_data(
             .parse::<i64>()
             .unwrap()
     };
-    while let Ok(Some(mut field)) = payload.try_next().await {
-        let content_disposition = field.content_disposition();
-        let filename = content_disposition.get_filename();
-        let mut data = bytes::Bytes::new();
 
-        if filename.is_some() {
-            while let Some(chunk) = field.next().await {
-                let chunked_data = chunk.unwrap();
-                // Reconstruct entire CSV data bytes here to prevent fragmentation of values.
-                data = Bytes::from([data.as_ref(), chunked_data.as_ref()].concat());
-            }
-            let mut rdr = csv::Reader::from_reader(data.as_ref());
-            let headers: csv::StringRecord = rdr
-                .headers()?
-                .iter()
-                .map(|x| {
-                    let mut x = x.trim().to_string();
-                    format_key(&mut x);
-                    x
-                })
-                .collect::<Vec<_>>()
-                .into();
-
-            for result in rdr.records() {
-                // The iterator yields Result<StringRecord, Error>, so we check the
-                // error here.
-                let record = result?;
-                // Transform the record to a JSON value
-                let mut json_record = json::Map::new();
-
-                for (header, field) in headers.iter().zip(record.iter()) {
-                    json_record.insert(header.into(), json::Value::String(field.into()));
-                }
-                json_record.insert(
-                    get_config().common.column_timestamp.clone(),
-                    json::Value::Number(timestamp.into()),
-                );
-
-                // check for schema evolution
-                if !schema_evolved
-                    && check_for_schema(
-                        org_id,
-                        stream_name,
-                        StreamType::EnrichmentTables,
-                        &mut stream_schema_map,
-                        vec![&json_record],
-                        timestamp,
-                    )
-                    .await
-                    .is_ok()
-                {
-                    schema_evolved = true;
-                }
-
-                if records.is_empty() {
-                    let schema = stream_schema_map.get(stream_name).unwrap();
-                    let schema_key = schema.hash_key();
-                    hour_key = super::ingestion::get_wal_time_key(
-                        timestamp,
-                        &vec![],
-                        PartitionTimeLevel::Unset,
-                        &json_record,
-                        Some(schema_key),
-                    );
-                }
-                let record = json::Value::Object(json_record);
-                let record_size = json::estimate_json_bytes(&record);
-                records.push(Arc::new(record));
-                records_size += record_size;
-            }
-        }
+    let mut json_records = Vec::new();
+    for data in files {
+        json_records.extend(parse_csv(data, timestamp)?);
     }
-
-    if records.is_empty() {
+    if json_records.is_empty() {
         return Ok(
             HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
                 http::StatusCode::BAD_REQUEST.into(),
@@ -199,6 +156,46 @@ pub async fn save_enrichment_data(
         );
     }
 
+    if stream_schema.has_fields && !append_data {
+        delete_enrichment_table(org_id, stream_name, StreamType::EnrichmentTables).await;
+    }
+
+    let mut records = vec![];
+    let mut records_size = 0;
+    for json_record in json_records {
+        // check for schema evolution
+        if !schema_evolved
+            && check_for_schema(
+                org_id,
+                stream_name,
+                StreamType::EnrichmentTables,
+                &mut stream_schema_map,
+                vec![&json_record],
+                timestamp,
+            )
+            .await
+            .is_ok()
+        {
+            schema_evolved = true;
+        }
+
+        if records.is_empty() {
+            let schema = stream_schema_map.get(stream_name).unwrap();
+            let schema_key = schema.hash_key();
+            hour_key = super::ingestion::get_wal_time_key(
+                timestamp,
+                &vec![],
+                PartitionTimeLevel::Unset,
+                &json_record,
+                Some(schema_key),
+            );
+        }
+        let record = json::Value::Object(json_record);
+        let record_size = json::estimate_json_bytes(&record);
+        records.push(Arc::new(record));
+        records_size += record_size;
+    }
+
     let schema = stream_schema_map
         .get(stream_name)
         .unwrap()
@@ -219,7 +216,17 @@ pub async fn save_enrichment_data(
     // write data to wal
     let writer =
         ingester::get_writer(thread_id, org_id, &StreamType::EnrichmentTables.to_string()).await;
-    let mut req_stats = write_file(&writer, stream_name, buf).await;
+    let mut req_stats = match write_file(&writer, stream_name, buf).await {
+        Ok(v) => v,
+        Err(e) => {
+            return Ok(
+                HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                    http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
@@ -242,6 +249,39 @@ pub async fn save_enrichment_data(
     )))
 }
 
+fn parse_csv(data: &[u8], timestamp: i64) -> Result<Vec<json::Map<String, json::Value>>, Error> {
+    let mut rdr = csv::Reader::from_reader(data);
+    let headers: csv::StringRecord = rdr
+        .headers()?
+        .iter()
+        .map(|x| {
+            let mut x = x.trim().to_string();
+            format_key(&mut x);
+            x
+        })
+        .collect::<Vec<_>>()
+        .into();
+
+    let mut json_records = Vec::new();
+    for result in rdr.records() {
+        // The iterator yields Result<StringRecord, Error>, so we check the
+        // error here.
+        let record = result?;
+        // Transform the record to a JSON value
+        let mut json_record = json::Map::new();
+
+        for (header, field) in headers.iter().zip(record.iter()) {
+            json_record.insert(header.into(), json::Value::String(field.into()));
+        }
+        json_record.insert(
+            get_config().common.column_timestamp.clone(),
+            json::Value::Number(timestamp.into()),
+        );
+        json_records.push(json_record);
+    }
+    Ok(json_records)
+}
+
 async fn delete_enrichment_table(org_id: &str, stream_name: &str, stream_type: StreamType) {
     log::info!("deleting enrichment table  {stream_name}");
     // delete stream schema
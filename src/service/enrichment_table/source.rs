@@ -0,0 +1,218 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Enrichment tables backed by a remote CSV, an http(s) url or an object in
+//! the configured bucket, which are reloaded on a schedule.
+
+use std::io::Error;
+
+use actix_web::{
+    http::{self, StatusCode},
+    HttpResponse,
+};
+use bytes::Bytes;
+use chrono::Utc;
+use config::{cluster::LOCAL_NODE_UUID, get_config, SIZE_IN_MB};
+use infra::dist_lock;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+
+use crate::{
+    common::meta::{
+        enrichment_table::EnrichmentTableSource, http::HttpResponse as MetaHttpResponse,
+    },
+    service::{db, format_stream_name},
+};
+
+/// Minimum refresh interval of a source, in seconds
+const MIN_INTERVAL: i64 = 60;
+
+pub async fn save_source(
+    org_id: &str,
+    table_name: &str,
+    mut source: EnrichmentTableSource,
+) -> Result<HttpResponse, Error> {
+    let table_name = format_stream_name(table_name.trim());
+    source.url = source.url.trim().to_string();
+    if source.url.is_empty() {
+        return Ok(MetaHttpResponse::bad_request(
+            "enrichment table source url is required",
+        ));
+    }
+    if source.interval < MIN_INTERVAL {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "enrichment table refresh interval should be at least {MIN_INTERVAL} seconds"
+        )));
+    }
+    // reset the state so that the new source is loaded on the next run
+    source.etag = None;
+    source.hash = String::new();
+    source.last_checked_at = 0;
+    source.last_refreshed_at = 0;
+    source.last_error = None;
+
+    match db::enrichment_table::set_source(org_id, &table_name, &source).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "Enrichment table source saved".to_string(),
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn get_source(org_id: &str, table_name: &str) -> Result<HttpResponse, Error> {
+    let table_name = format_stream_name(table_name.trim());
+    match db::enrichment_table::get_source(org_id, &table_name).await {
+        Ok(source) => Ok(HttpResponse::Ok().json(source)),
+        Err(_) => Ok(MetaHttpResponse::not_found(
+            "Enrichment table source not found",
+        )),
+    }
+}
+
+pub async fn delete_source(org_id: &str, table_name: &str) -> Result<HttpResponse, Error> {
+    let table_name = format_stream_name(table_name.trim());
+    if db::enrichment_table::get_source(org_id, &table_name)
+        .await
+        .is_err()
+    {
+        return Ok(MetaHttpResponse::not_found(
+            "Enrichment table source not found",
+        ));
+    }
+    match db::enrichment_table::delete_source(org_id, &table_name).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "Enrichment table source deleted".to_string(),
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Checks every source that is due and reloads the ones whose content changed.
+pub async fn run_refresh() -> Result<(), anyhow::Error> {
+    let now = Utc::now().timestamp_micros();
+    for (org_id, table_name, source) in db::enrichment_table::list_sources().await? {
+        if !source.is_due(now) {
+            continue;
+        }
+
+        // claim the source, so that it is only checked by one ingester
+        let lock_key = format!("/enrichment_table_source/{org_id}/{table_name}");
+        let locker = dist_lock::lock(&lock_key, 0).await?;
+        let ret = match db::enrichment_table::get_source(&org_id, &table_name).await {
+            Ok(mut source) if source.is_due(now) => {
+                source.last_checked_at = now;
+                db::enrichment_table::set_source(&org_id, &table_name, &source)
+                    .await
+                    .map(|_| Some(source))
+            }
+            _ => Ok(None),
+        };
+        dist_lock::unlock(&locker).await?;
+        drop(locker);
+        let Some(mut source) = ret? else {
+            continue;
+        };
+
+        match refresh(&org_id, &table_name, &mut source).await {
+            Ok(true) => {
+                log::info!(
+                    "[ENRICHMENT_TABLE] {org_id}/{table_name} reloaded from {} by {}",
+                    source.url,
+                    LOCAL_NODE_UUID.as_str()
+                );
+                source.last_refreshed_at = Utc::now().timestamp_micros();
+                source.last_error = None;
+            }
+            Ok(false) => {
+                source.last_error = None;
+            }
+            Err(e) => {
+                log::error!(
+                    "[ENRICHMENT_TABLE] {org_id}/{table_name} refresh from {} error: {e}",
+                    source.url
+                );
+                source.last_error = Some(e.to_string());
+            }
+        }
+        // the source was removed or replaced while we were loading it
+        match db::enrichment_table::get_source(&org_id, &table_name).await {
+            Ok(current) if current.url == source.url => {}
+            _ => continue,
+        }
+        if let Err(e) = db::enrichment_table::set_source(&org_id, &table_name, &source).await {
+            log::error!("[ENRICHMENT_TABLE] {org_id}/{table_name} save source error: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Fetches the source and replaces the table when the content changed,
+/// returns whether the table was replaced.
+async fn refresh(
+    org_id: &str,
+    table_name: &str,
+    source: &mut EnrichmentTableSource,
+) -> Result<bool, anyhow::Error> {
+    let Some((data, etag)) = fetch(source).await? else {
+        return Ok(false);
+    };
+    let limit = get_config().limit.enrichment_table_limit;
+    if data.len() as f64 / SIZE_IN_MB > limit as f64 {
+        return Err(anyhow::anyhow!("exceeds allowed limit of {limit} mb"));
+    }
+    let hash = blake3::hash(&data).to_hex().to_string();
+    if hash == source.hash {
+        source.etag = etag;
+        return Ok(false);
+    }
+
+    let resp = super::save_enrichment_csv(org_id, table_name, &[data], 0, false).await?;
+    if resp.status() != http::StatusCode::OK {
+        return Err(anyhow::anyhow!(
+            "failed to load the table, status: {}",
+            resp.status()
+        ));
+    }
+    source.etag = etag;
+    source.hash = hash;
+    Ok(true)
+}
+
+/// Returns `None` when the http source answered that it was not modified.
+async fn fetch(
+    source: &EnrichmentTableSource,
+) -> Result<Option<(Bytes, Option<String>)>, anyhow::Error> {
+    if !source.is_http() {
+        let data = infra::storage::get(&source.url).await?;
+        return Ok(Some((data, None)));
+    }
+
+    let mut req = reqwest::Client::new().get(&source.url);
+    if let Some(etag) = source.etag.as_ref() {
+        req = req.header(IF_NONE_MATCH, etag);
+    }
+    let resp = req.send().await?;
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let resp = resp.error_for_status()?;
+    let etag = resp
+        .headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    Ok(Some((resp.bytes().await?, etag)))
+}
@@ -19,14 +19,17 @@ use config::{
     cluster::LOCAL_NODE_UUID,
     get_config, ider,
     meta::{
-        cluster::Node,
+        cluster::{Node, GRPC_MIN_VERSION, GRPC_VERSION_HEADER},
         search::ScanStats,
         stream::{FileKey, FileMeta, PartitionTimeLevel, StreamType},
     },
-    utils::{file::get_file_meta as util_get_file_meta, json},
+    utils::{file::get_file_meta as util_get_file_meta, json, parquet},
 };
-use futures::future::try_join_all;
+use futures::{future::try_join_all, StreamExt};
+use hashbrown::HashMap;
 use infra::{
+    cache::negative,
+    dist_lock,
     errors::{Error, ErrorCodes},
     file_list, storage,
 };
@@ -34,16 +37,51 @@ use proto::cluster_rpc;
 use tonic::{
     codec::CompressionEncoding,
     metadata::{MetadataKey, MetadataValue},
-    transport::Channel,
     Request,
 };
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
-    common::infra::cluster,
-    service::{db, search::MetadataMap},
+    common::infra::{cluster, tls},
+    service::{compact, db, search::MetadataMap},
 };
 
+// parquet files whose footers are read before writing them to the file list
+const REBUILD_BATCH_SIZE: usize = 1000;
+const RECOVERY_LOCK_KEY: &str = "/file_list/recovery";
+
+/// Queries the file list for a search. An empty result is remembered for
+/// `ZO_QUERY_NEGATIVE_CACHE_TTL` seconds, the compactor keeps using `query`
+/// so it never skips files because of a cached empty partition.
+pub async fn query_for_search(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    time_level: PartitionTimeLevel,
+    time_min: i64,
+    time_max: i64,
+    is_local: bool,
+) -> Result<Vec<FileKey>, anyhow::Error> {
+    let empty_key = negative::file_list_key(org_id, stream_type, stream_name, time_min, time_max);
+    if negative::is_cached(&empty_key) {
+        return Ok(Vec::new());
+    }
+    let files = query(
+        org_id,
+        stream_name,
+        stream_type,
+        time_level,
+        time_min,
+        time_max,
+        is_local,
+    )
+    .await?;
+    if files.is_empty() {
+        negative::set(empty_key);
+    }
+    Ok(files)
+}
+
 pub async fn query(
     org_id: &str,
     stream_name: &str,
@@ -101,7 +139,11 @@ pub async fn query(
             let token: MetadataValue<_> = cluster::get_internal_grpc_token()
                 .parse()
                 .map_err(|_| Error::Message("invalid token".to_string()))?;
-            let channel = Channel::from_shared(node.grpc_addr.clone())
+            let grpc_version: MetadataValue<_> = node
+                .negotiate_grpc_version()
+                .unwrap_or(GRPC_MIN_VERSION)
+                .into();
+            let channel = tls::grpc_endpoint(node.grpc_addr.clone())
                 .unwrap()
                 .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
                 .connect()
@@ -120,6 +162,8 @@ pub async fn query(
                 channel,
                 move |mut req: Request<()>| {
                     req.metadata_mut().insert("authorization", token.clone());
+                    req.metadata_mut()
+                        .insert(GRPC_VERSION_HEADER, grpc_version.clone());
                     req.metadata_mut()
                         .insert(org_header_key.clone(), org_id.clone());
                     Ok(req)
@@ -213,7 +257,11 @@ pub async fn query(
     let token: MetadataValue<_> = cluster::get_internal_grpc_token()
         .parse()
         .map_err(|_| Error::Message("invalid token".to_string()))?;
-    let channel = Channel::from_shared(node.grpc_addr.clone())
+    let grpc_version: MetadataValue<_> = node
+        .negotiate_grpc_version()
+        .unwrap_or(GRPC_MIN_VERSION)
+        .into();
+    let channel = tls::grpc_endpoint(node.grpc_addr.clone())
         .unwrap()
         .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
         .connect()
@@ -233,6 +281,8 @@ pub async fn query(
         channel,
         move |mut req: Request<()>| {
             req.metadata_mut().insert("authorization", token.clone());
+            req.metadata_mut()
+                .insert(GRPC_VERSION_HEADER, grpc_version.clone());
             req.metadata_mut()
                 .insert(org_header_key.clone(), org_id.clone());
             Ok(req)
@@ -328,6 +378,120 @@ pub fn calculate_local_files_size(files: &[String]) -> Result<u64, anyhow::Error
     Ok(size)
 }
 
+/// Adds the parquet files of the object store under the prefix, e.g.
+/// `files/default/logs/app/`, that are missing from the file list, reading
+/// only their footers. Returns the files added, or that would be added with
+/// `dry_run`.
+pub async fn rebuild_from_storage(
+    prefix: &str,
+    dry_run: bool,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut missing = Vec::new();
+    for (file, size) in storage::list_with_size(prefix).await? {
+        if file.ends_with(".parquet") && !file_list::contains(&file).await? {
+            missing.push((file, size));
+        }
+    }
+    if dry_run {
+        return Ok(missing.into_iter().map(|(file, _)| file).collect());
+    }
+
+    let cfg = get_config();
+    let mut added = Vec::with_capacity(missing.len());
+    for chunk in missing.chunks(REBUILD_BATCH_SIZE) {
+        let metas = futures::stream::iter(chunk.iter().cloned())
+            .map(|(file, size)| async move {
+                let meta = read_footer_meta(&file, size).await;
+                (file, meta)
+            })
+            .buffer_unordered(cfg.limit.cpu_num)
+            .collect::<Vec<_>>()
+            .await;
+        let mut files = Vec::with_capacity(metas.len());
+        for (file, meta) in metas {
+            match meta {
+                Ok(meta) => files.push(FileKey::new(&file, meta, false)),
+                Err(e) => log::error!("[FILE_LIST] rebuild read footer of {file} error: {e}"),
+            }
+        }
+        added.extend(add_files(files).await?);
+        log::info!(
+            "[FILE_LIST] rebuild {prefix} added {} of {} files",
+            added.len(),
+            missing.len()
+        );
+    }
+    Ok(added)
+}
+
+/// Adds data files of the object store to the file list, whether it lives in
+/// the meta store or in file list objects. Returns the keys of the files
+/// added, the keys which aren't data files are left out.
+pub(crate) async fn add_files(files: Vec<FileKey>) -> Result<Vec<String>, anyhow::Error> {
+    // the files of an org and a day go into one file list entry
+    let mut groups: HashMap<(String, String), Vec<FileKey>> = HashMap::new();
+    for file in files {
+        let columns = file.key.split('/').collect::<Vec<_>>();
+        if columns.len() < 9 || columns[0] != "files" {
+            continue;
+        }
+        let key = (columns[1].to_string(), columns[4..8].join("/"));
+        groups.entry(key).or_default().push(file);
+    }
+    let mut added = Vec::new();
+    for ((org_id, _), files) in groups {
+        compact::merge::write_file_list(&org_id, &files).await?;
+        added.extend(files.into_iter().map(|f| f.key));
+    }
+    Ok(added)
+}
+
+/// Rebuilds the whole file list from the bucket after the loss of the meta
+/// store, then the stream stats from it. One node rebuilds it at a time, the
+/// others find it filled.
+pub async fn recover() -> Result<usize, anyhow::Error> {
+    let locker = dist_lock::lock(RECOVERY_LOCK_KEY, 0).await?;
+    if !file_list::is_empty().await {
+        dist_lock::unlock(&locker).await?;
+        return Ok(0);
+    }
+    let start = std::time::Instant::now();
+    log::info!("[FILE_LIST] recover from the bucket start");
+    let ret = rebuild_from_storage("files/", false).await;
+    dist_lock::unlock(&locker).await?;
+    let files = ret?;
+
+    db::compact::stats::set_offset(0, None).await?;
+    file_list::reset_stream_stats().await?;
+    compact::stats::update_stats_from_file_list().await?;
+    log::info!(
+        "[FILE_LIST] recover from the bucket {} files done, took: {}s",
+        files.len(),
+        start.elapsed().as_secs()
+    );
+    Ok(files.len())
+}
+
+// the meta in the footer of a parquet file of the object store, the files
+// written without time range get the one of their name
+async fn read_footer_meta(file: &str, size: usize) -> Result<FileMeta, anyhow::Error> {
+    let read = parquet::FOOTER_READ_SIZE.min(size);
+    let mut tail = storage::get_range(file, size - read..size).await?;
+    let len = parquet::footer_len(&tail)?;
+    if len > tail.len() {
+        if len > size {
+            return Err(anyhow::anyhow!("invalid parquet footer"));
+        }
+        tail = storage::get_range(file, size - len..size).await?;
+    }
+    let mut meta = parquet::read_metadata_from_footer(&tail)?;
+    meta.compressed_size = size as i64;
+    if meta.min_ts == 0 && meta.max_ts == 0 {
+        (meta.min_ts, meta.max_ts) = parquet::parse_time_range_from_filename(file);
+    }
+    Ok(meta)
+}
+
 // Delete one parquet file and update the file list
 pub async fn delete_parquet_file(key: &str, file_list_only: bool) -> Result<(), anyhow::Error> {
     if get_config().common.meta_store_external {
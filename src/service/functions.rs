@@ -13,13 +13,19 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Error;
+use std::{collections::HashMap, io::Error};
 
 use actix_web::{
     http::{self, StatusCode},
     HttpResponse,
 };
-use config::meta::stream::StreamType;
+use chrono::Utc;
+use config::{
+    get_config,
+    meta::stream::StreamType,
+    utils::{flatten, json},
+};
+use vector_enrichment::TableRegistry;
 
 use crate::{
     common::{
@@ -27,15 +33,24 @@ use crate::{
         meta::{
             authz::Authz,
             functions::{
-                FunctionList, StreamFunctionsList, StreamOrder, StreamTransform, Transform,
+                FunctionList, FunctionPreviewRequest, FunctionPreviewResponse,
+                FunctionPreviewResult, FunctionStats, StreamFunctionsList, StreamOrder,
+                StreamTransform, Transform, VRLResultResolver,
             },
             http::HttpResponse as MetaHttpResponse,
         },
         utils::auth::{remove_ownership, set_ownership},
     },
-    service::{db, ingestion::compile_vrl_function},
+    service::{
+        db, format_stream_name,
+        ingestion::{compile_vrl_function, init_functions_runtime, try_apply_vrl_fn},
+        masking, search as SearchService,
+    },
 };
 
+const FN_PREVIEW_SIZE: i64 = 10;
+const FN_PREVIEW_MAX_SIZE: i64 = 100;
+const FN_PREVIEW_LOOKBACK: i64 = 3600;
 const FN_SUCCESS: &str = "Function saved successfully";
 const FN_NOT_FOUND: &str = "Function not found";
 const FN_ADDED: &str = "Function applied to stream";
@@ -193,6 +208,9 @@ pub async fn delete_function(org_id: String, fn_name: String) -> Result<HttpResp
     match result {
         Ok(_) => {
             remove_ownership(&org_id, "functions", Authz::new(&fn_name)).await;
+            if let Err(e) = db::functions::delete_stats(&org_id, &fn_name).await {
+                log::error!("Error deleting function stats: {}", e);
+            }
 
             Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
                 http::StatusCode::OK.into(),
@@ -206,6 +224,138 @@ pub async fn delete_function(org_id: String, fn_name: String) -> Result<HttpResp
     }
 }
 
+/// Returns the execution stats of the function, summed over the nodes, since
+/// they last started.
+pub async fn get_function_stats(org_id: &str, fn_name: &str) -> Result<HttpResponse, Error> {
+    if check_existing_fn(org_id, fn_name).await.is_none() {
+        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+            StatusCode::NOT_FOUND.into(),
+            FN_NOT_FOUND.to_string(),
+        )));
+    }
+    match db::functions::list_stats(org_id, fn_name).await {
+        Ok(list) => {
+            let mut stats = FunctionStats::default();
+            for item in list.iter() {
+                stats.merge(item);
+            }
+            Ok(HttpResponse::Ok().json(stats))
+        }
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Applies the function to the latest records of the stream without ingesting
+/// anything, so that it can be checked before being attached to the stream.
+/// The records are the ones the user can search.
+#[tracing::instrument(skip(req))]
+pub async fn preview_function(
+    org_id: &str,
+    fn_name: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    user_id: &str,
+    req: FunctionPreviewRequest,
+) -> Result<HttpResponse, Error> {
+    // the name is put in the query
+    if stream_name != format_stream_name(stream_name) {
+        return Ok(MetaHttpResponse::bad_request("invalid stream name"));
+    }
+    if !SearchService::can_read_stream(org_id, user_id, stream_type, stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+    let mut function = match req.function {
+        Some(function) => function,
+        None => match check_existing_fn(org_id, fn_name).await {
+            Some(func) => func.function,
+            None => {
+                return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+                    StatusCode::NOT_FOUND.into(),
+                    FN_NOT_FOUND.to_string(),
+                )));
+            }
+        },
+    };
+    if !function.ends_with('.') {
+        function = format!("{} \n .", function);
+    }
+    let vrl_config = match compile_vrl_function(&function, org_id) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let registry = vrl_config.config.get_custom::<TableRegistry>().unwrap();
+    registry.finish_load();
+    let resolver = VRLResultResolver {
+        program: vrl_config.program,
+        fields: vrl_config.fields,
+    };
+
+    let cfg = get_config();
+    let size = req
+        .size
+        .unwrap_or(FN_PREVIEW_SIZE)
+        .clamp(1, FN_PREVIEW_MAX_SIZE);
+    let lookback = req.lookback.unwrap_or(FN_PREVIEW_LOOKBACK).max(1);
+    let end_time = Utc::now().timestamp_micros();
+    let query = config::meta::search::Query {
+        sql: format!(
+            "SELECT * FROM \"{stream_name}\" ORDER BY {} DESC",
+            cfg.common.column_timestamp
+        ),
+        from: 0,
+        size,
+        start_time: end_time - lookback * 1_000_000,
+        end_time,
+        ..Default::default()
+    };
+    let search_req = config::meta::search::Request {
+        query,
+        aggs: HashMap::new(),
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+    };
+    let trace_id = config::ider::uuid();
+    let mut hits = match SearchService::search(
+        &trace_id,
+        org_id,
+        stream_type,
+        Some(user_id.to_string()),
+        &search_req,
+    )
+    .await
+    {
+        Ok(res) => res.hits,
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    masking::mask_hits(org_id, user_id, stream_type, stream_name, &mut hits);
+
+    let mut runtime = init_functions_runtime();
+    let results = hits
+        .into_iter()
+        .map(|before| {
+            let (after, error) = match try_apply_vrl_fn(&mut runtime, &resolver, &before) {
+                Ok(after) if after.is_null() => (after, None),
+                Ok(after) => {
+                    match flatten::flatten_with_level(after, cfg.limit.ingest_flatten_level) {
+                        Ok(after) => (after, None),
+                        Err(e) => (json::Value::Null, Some(e.to_string())),
+                    }
+                }
+                Err(e) => (before.clone(), Some(e)),
+            };
+            FunctionPreviewResult {
+                before,
+                after,
+                error,
+            }
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(FunctionPreviewResponse { results }))
+}
+
 pub async fn list_stream_functions(
     org_id: &str,
     stream_type: StreamType,
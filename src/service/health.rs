@@ -0,0 +1,91 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Readiness of the node: the dependencies it needs to serve requests.
+
+use std::time::Duration;
+
+use config::{
+    cluster::{is_router, LOCAL_NODE_ROLE, LOCAL_NODE_UUID},
+    meta::cluster::NodeStatus,
+};
+use infra::{
+    cache::file_data,
+    errors::{DbError, Error},
+};
+
+use crate::common::infra::cluster;
+
+/// A probe must answer before kubernetes gives up on it.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const META_STORE: &str = "meta_store";
+pub const OBJECT_STORE: &str = "object_store";
+pub const DISK_CACHE: &str = "disk_cache";
+pub const CLUSTER: &str = "cluster";
+
+/// Runs the checks of the dependencies concurrently, the routers don't read
+/// the object store.
+pub async fn readiness() -> Vec<(&'static str, Result<(), String>)> {
+    let (meta_store, object_store, cluster) = tokio::join!(
+        with_timeout(check_meta_store()),
+        with_timeout(check_object_store()),
+        with_timeout(check_cluster()),
+    );
+    let mut checks = vec![(META_STORE, meta_store)];
+    if !is_router(&LOCAL_NODE_ROLE) {
+        checks.push((OBJECT_STORE, object_store));
+    }
+    checks.push((DISK_CACHE, check_disk_cache()));
+    checks.push((CLUSTER, cluster));
+    checks
+}
+
+pub(crate) async fn with_timeout(
+    check: impl std::future::Future<Output = Result<(), String>>,
+) -> Result<(), String> {
+    match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(res) => res,
+        Err(_) => Err(format!("no answer in {}s", CHECK_TIMEOUT.as_secs())),
+    }
+}
+
+pub(crate) async fn check_meta_store() -> Result<(), String> {
+    // a missing key is an answer of the store
+    match infra::db::get_db().await.get("/meta/kv/version").await {
+        Ok(_) | Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub(crate) async fn check_object_store() -> Result<(), String> {
+    infra::storage::check().await.map_err(|e| e.to_string())
+}
+
+fn check_disk_cache() -> Result<(), String> {
+    if file_data::disk::is_loaded() {
+        Ok(())
+    } else {
+        Err("loading the files of the cache dir".to_string())
+    }
+}
+
+async fn check_cluster() -> Result<(), String> {
+    match cluster::get_node_by_uuid(&LOCAL_NODE_UUID).await {
+        Some(node) if node.status == NodeStatus::Online => Ok(()),
+        Some(node) => Err(format!("node is {:?}", node.status)),
+        None => Err("node is not registered".to_string()),
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Suggests the partition keys and the bloom filter fields of a stream from
+//! the filters of its searches, read back from the usage stream.
+
+use std::io::Error;
+
+use actix_web::{http::StatusCode, HttpResponse};
+use config::{
+    get_config,
+    meta::{
+        sql::Sql as MetaSql,
+        stream::{StreamPartition, StreamSettings, StreamType},
+        usage::{UsageEvent, USAGE_STREAM},
+    },
+    utils::json,
+    SQL_FULL_TEXT_SEARCH_FIELDS,
+};
+use hashbrown::{HashMap, HashSet};
+use infra::schema::unwrap_stream_settings;
+
+use crate::{
+    common::meta::{
+        http::HttpResponse as MetaHttpResponse,
+        stream::{FieldFilterUsage, IndexAdvice},
+    },
+    service::{search as SearchService, stream},
+};
+
+/// Maximum number of searches read for one report
+const MAX_QUERIES: i64 = 10000;
+/// Share of the searches a field has to be filtered on in to be suggested
+const MIN_FILTER_RATIO: f64 = 0.1;
+/// A field filtered by at most this many values is suggested as a partition
+/// key, a field with more values for a bloom filter
+const PARTITION_MAX_VALUES: usize = 32;
+/// A field is suggested for a bloom filter when its searches return at most
+/// this many records on average
+const BLOOM_FILTER_MAX_AVG_HITS: f64 = 1000.0;
+
+/// A search of the stream, read back from the usage stream
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+struct SearchEvent {
+    request_body: String,
+    #[serde(default)]
+    num_records: i64,
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    days: i64,
+) -> Result<HttpResponse, Error> {
+    match advise(org_id, stream_type, stream_name, days).await {
+        Ok(advice) => Ok(HttpResponse::Ok().json(advice)),
+        Err(e) => Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            StatusCode::BAD_REQUEST.into(),
+            e.to_string(),
+        ))),
+    }
+}
+
+/// Adds the suggested partition keys and bloom filter fields to the settings
+/// of the stream, they apply to the data ingested from now on.
+pub async fn apply(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    days: i64,
+) -> Result<HttpResponse, Error> {
+    let advice = match advise(org_id, stream_type, stream_name, days).await {
+        Ok(advice) => advice,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                StatusCode::BAD_REQUEST.into(),
+                e.to_string(),
+            )));
+        }
+    };
+    let schema = infra::schema::get(org_id, stream_name, stream_type)
+        .await
+        .unwrap();
+    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    for usage in advice.partition_keys {
+        settings
+            .partition_keys
+            .push(StreamPartition::new(&usage.field));
+    }
+    for usage in advice.bloom_filter_fields {
+        settings.bloom_filter_fields.push(usage.field);
+    }
+    stream::save_stream_settings(org_id, stream_name, stream_type, settings).await
+}
+
+async fn advise(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    days: i64,
+) -> Result<IndexAdvice, anyhow::Error> {
+    let cfg = get_config();
+    if !cfg.common.usage_enabled {
+        return Err(anyhow::anyhow!(
+            "the index advice needs the usage reporting, ZO_USAGE_REPORTING_ENABLED"
+        ));
+    }
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(anyhow::anyhow!("stream [{stream_name}] not found"));
+    }
+    let settings = unwrap_stream_settings(&schema).unwrap_or_default();
+
+    // the searches are reported as logs searches whatever the stream type is
+    let end_time = chrono::Utc::now().timestamp_micros();
+    let start_time = end_time - days.max(1) * 86_400_000_000;
+    let sql = format!(
+        "SELECT request_body, num_records FROM \"{USAGE_STREAM}\" WHERE event = '{}' AND org_id = '{}' AND stream_name = '{}' ORDER BY _timestamp DESC",
+        UsageEvent::Search,
+        escape(org_id),
+        escape(stream_name)
+    );
+    let req = config::meta::search::Request {
+        query: config::meta::search::Query {
+            sql,
+            sql_mode: "full".to_owned(),
+            from: 0,
+            size: MAX_QUERIES,
+            start_time,
+            end_time,
+            ..Default::default()
+        },
+        aggs: std::collections::HashMap::new(),
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+    };
+    let hits =
+        match SearchService::search_as_system("", &cfg.common.usage_org, StreamType::Logs, &req)
+            .await
+        {
+            Ok(res) => res.hits,
+            Err(infra::errors::Error::ErrorCode(
+                infra::errors::ErrorCodes::SearchStreamNotFound(_),
+            )) => vec![],
+            Err(e) => return Err(e.into()),
+        };
+    let events = hits
+        .into_iter()
+        .filter_map(|hit| json::from_value::<SearchEvent>(hit).ok())
+        .collect::<Vec<_>>();
+
+    let (queries, usages) = filter_usage(stream_name, &settings, &events);
+    let (partition_keys, bloom_filter_fields) = suggest(&settings, queries, usages);
+    Ok(IndexAdvice {
+        stream_type,
+        stream_name: stream_name.to_string(),
+        queries,
+        partition_keys,
+        bloom_filter_fields,
+    })
+}
+
+/// Counts, for each field, the searches of the stream filtering on it by
+/// value. Returns the number of searches of the stream and the usage of the
+/// fields.
+fn filter_usage(
+    stream_name: &str,
+    settings: &StreamSettings,
+    events: &[SearchEvent],
+) -> (usize, Vec<FieldFilterUsage>) {
+    let column_timestamp = &get_config().common.column_timestamp;
+    let mut queries = 0;
+    // field -> (searches, values, hits)
+    let mut fields: HashMap<String, (usize, HashSet<String>, i64)> = HashMap::new();
+    for event in events {
+        let Ok(sql) = MetaSql::new(&event.request_body) else {
+            continue;
+        };
+        if sql.source != stream_name {
+            continue;
+        }
+        queries += 1;
+        let mut seen = HashSet::new();
+        for (field, value, _) in sql.quick_text.iter() {
+            // the quick text filters also hold the LIKE filters
+            if value.contains('%') {
+                continue;
+            }
+            let field = settings.resolve_alias(field);
+            if field == column_timestamp || SQL_FULL_TEXT_SEARCH_FIELDS.contains(&field.to_string())
+            {
+                continue;
+            }
+            let entry = fields.entry(field.to_string()).or_default();
+            entry.1.insert(value.to_string());
+            if seen.insert(field) {
+                entry.0 += 1;
+                entry.2 += event.num_records;
+            }
+        }
+    }
+    let mut usages = fields
+        .into_iter()
+        .map(|(field, (searches, values, hits))| FieldFilterUsage {
+            field,
+            queries: searches,
+            distinct_values: values.len(),
+            avg_hits: hits as f64 / searches as f64,
+        })
+        .collect::<Vec<_>>();
+    usages.sort_by(|a, b| b.queries.cmp(&a.queries).then(a.field.cmp(&b.field)));
+    (queries, usages)
+}
+
+/// Splits the fields filtered on often enough into partition keys and bloom
+/// filter fields, leaving out the fields already partitioned or bloom
+/// filtered.
+fn suggest(
+    settings: &StreamSettings,
+    queries: usize,
+    usages: Vec<FieldFilterUsage>,
+) -> (Vec<FieldFilterUsage>, Vec<FieldFilterUsage>) {
+    let mut partition_keys = vec![];
+    let mut bloom_filter_fields = vec![];
+    for usage in usages {
+        if (usage.queries as f64) < queries as f64 * MIN_FILTER_RATIO
+            || settings
+                .partition_keys
+                .iter()
+                .any(|k| !k.disabled && k.field == usage.field)
+            || settings.bloom_filter_fields.contains(&usage.field)
+        {
+            continue;
+        }
+        if usage.distinct_values <= PARTITION_MAX_VALUES {
+            partition_keys.push(usage);
+        } else if usage.avg_hits <= BLOOM_FILTER_MAX_AVG_HITS {
+            bloom_filter_fields.push(usage);
+        }
+    }
+    (partition_keys, bloom_filter_fields)
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(sql: &str, num_records: i64) -> SearchEvent {
+        SearchEvent {
+            request_body: sql.to_string(),
+            num_records,
+        }
+    }
+
+    #[test]
+    fn test_filter_usage() {
+        let mut settings = StreamSettings::default();
+        settings
+            .field_aliases
+            .insert("ns".to_string(), "kubernetes_namespace".to_string());
+        let events = vec![
+            event(
+                "SELECT * FROM \"default\" WHERE ns = 'a' AND code = 500",
+                10,
+            ),
+            event(
+                "SELECT * FROM \"default\" WHERE kubernetes_namespace = 'b'",
+                30,
+            ),
+            event("SELECT * FROM \"default\" WHERE host LIKE '%web%'", 5),
+            event("SELECT * FROM \"other\" WHERE ns = 'c'", 5),
+            event("not a query", 5),
+        ];
+        let (queries, usages) = filter_usage("default", &settings, &events);
+        assert_eq!(queries, 3);
+        assert_eq!(usages.len(), 2);
+        assert_eq!(usages[0].field, "kubernetes_namespace");
+        assert_eq!(usages[0].queries, 2);
+        assert_eq!(usages[0].distinct_values, 2);
+        assert_eq!(usages[0].avg_hits, 20.0);
+        assert_eq!(usages[1].field, "code");
+        assert_eq!(usages[1].queries, 1);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let usage = |field: &str, queries, distinct_values, avg_hits| FieldFilterUsage {
+            field: field.to_string(),
+            queries,
+            distinct_values,
+            avg_hits,
+        };
+        let mut settings = StreamSettings::default();
+        settings.bloom_filter_fields.push("trace_id".to_string());
+        let usages = vec![
+            usage("namespace", 50, 5, 2000.0),
+            usage("request_id", 40, 4000, 3.0),
+            usage("trace_id", 40, 4000, 1.0),
+            usage("path", 30, 2000, 50000.0),
+            usage("level", 2, 3, 100.0),
+        ];
+        let (partition_keys, bloom_filter_fields) = suggest(&settings, 100, usages);
+        assert_eq!(partition_keys.len(), 1);
+        assert_eq!(partition_keys[0].field, "namespace");
+        assert_eq!(bloom_filter_fields.len(), 1);
+        assert_eq!(bloom_filter_fields[0].field, "request_id");
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Degrades the ingestion while the object store or the meta store fails. The
+//! ingester keeps answering from its wal instead of hanging on the stores, the
+//! requests get a 202 with the `degraded` flag and the wal isn't uploaded.
+//! Once both stores answer again the wal is uploaded as usual.
+
+use std::time::Duration;
+
+use config::{cluster, get_config, metrics};
+use tokio::time;
+
+use crate::service::health;
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    if !cfg.limit.ingest_breaker_enabled {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(Duration::from_secs(cfg.limit.ingest_breaker_check_interval));
+    interval.tick().await; // trigger the first run
+    let mut failures = 0;
+    loop {
+        if cluster::is_offline() {
+            break;
+        }
+        interval.tick().await;
+        let (meta_store, object_store) = tokio::join!(
+            health::with_timeout(health::check_meta_store()),
+            health::with_timeout(health::check_object_store()),
+        );
+        let failed = [
+            (health::META_STORE, meta_store),
+            (health::OBJECT_STORE, object_store),
+        ]
+        .into_iter()
+        .filter_map(|(name, ret)| ret.err().map(|e| format!("{name}: {e}")))
+        .collect::<Vec<_>>();
+        let degraded = check(
+            &mut failures,
+            failed.is_empty(),
+            cfg.limit.ingest_breaker_failures,
+        );
+        if degraded == cluster::is_degraded() {
+            continue;
+        }
+        if degraded {
+            log::warn!(
+                "[INGESTER:BREAKER] {failures} failed checks, keeping the ingested data in the wal: {}",
+                failed.join(", ")
+            );
+        } else {
+            log::info!("[INGESTER:BREAKER] the stores answer again, uploading the wal");
+        }
+        cluster::set_degraded(degraded);
+        metrics::INGEST_DEGRADED
+            .with_label_values(&[])
+            .set(degraded as i64);
+    }
+    log::info!("[INGESTER:BREAKER] ingestion breaker is stopped");
+    Ok(())
+}
+
+/// Counts the failed checks in a row, returns whether the ingestion is
+/// degraded.
+fn check(failures: &mut u32, ok: bool, threshold: u32) -> bool {
+    if ok {
+        *failures = 0;
+        return false;
+    }
+    *failures = failures.saturating_add(1);
+    *failures >= threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let mut failures = 0;
+        assert!(!check(&mut failures, false, 3));
+        assert!(!check(&mut failures, false, 3));
+        assert!(check(&mut failures, false, 3));
+        assert!(check(&mut failures, false, 3));
+        assert!(!check(&mut failures, true, 3));
+        assert_eq!(failures, 0);
+        assert!(!check(&mut failures, false, 3));
+    }
+}
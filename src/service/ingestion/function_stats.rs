@@ -0,0 +1,193 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Execution stats of the ingestion functions, and the error streams which
+//! receive the records a function failed on.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use config::{
+    cluster::LOCAL_NODE_UUID,
+    get_config, metrics,
+    utils::json::{self, Value},
+    RwHashMap,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use proto::cluster_rpc;
+use vrl::compiler::runtime::Runtime;
+
+use super::try_apply_vrl_fn;
+use crate::{
+    common::meta::functions::{FunctionStats, VRLResultResolver},
+    service::{db, format_stream_name, usage::ingestion_service},
+};
+
+const ERROR_STREAM_PREFIX: &str = "function_errors_";
+
+struct NodeStats {
+    stats: FunctionStats,
+    dirty: bool,
+}
+
+/// stats of this node since it started, keyed by `org_id/function`
+static STATS: Lazy<RwHashMap<String, NodeStats>> = Lazy::new(Default::default);
+
+/// error records waiting to be ingested, keyed by `(org_id, error stream)`
+static ERROR_RECORDS: Lazy<Mutex<HashMap<(String, String), Vec<Value>>>> =
+    Lazy::new(Default::default);
+
+/// Runs the function on the row and records its stats, the original row is
+/// returned and sent to the error stream of the function when it fails.
+pub fn apply_function(
+    runtime: &mut Runtime,
+    vrl_runtime: &VRLResultResolver,
+    row: &Value,
+    org_id: &str,
+    stream_name: &str,
+    function: &str,
+) -> Value {
+    let start = std::time::Instant::now();
+    let ret = try_apply_vrl_fn(runtime, vrl_runtime, row);
+    let took = start.elapsed().as_secs_f64();
+
+    metrics::FUNCTION_INVOCATIONS
+        .with_label_values(&[org_id, function])
+        .inc();
+    metrics::FUNCTION_TIME
+        .with_label_values(&[org_id, function])
+        .observe(took);
+    let mut entry = STATS
+        .entry(format!("{org_id}/{function}"))
+        .or_insert_with(|| NodeStats {
+            stats: FunctionStats::default(),
+            dirty: true,
+        });
+    entry.stats.invocations += 1;
+    entry.stats.total_time += took;
+    entry.dirty = true;
+
+    match ret {
+        Ok(val) => val,
+        Err(err) => {
+            log::error!(
+                "{}/{} function {} failed {}. Returning original row.",
+                org_id,
+                stream_name,
+                function,
+                err,
+            );
+            metrics::FUNCTION_ERRORS
+                .with_label_values(&[org_id, function])
+                .inc();
+            entry.stats.errors += 1;
+            entry.stats.last_error = Some(err.clone());
+            entry.stats.last_error_at = Utc::now().timestamp_micros();
+            drop(entry);
+            push_error_record(org_id, stream_name, function, row, &err);
+            row.clone()
+        }
+    }
+}
+
+pub fn error_stream_name(function: &str) -> String {
+    format_stream_name(&format!("{ERROR_STREAM_PREFIX}{function}"))
+}
+
+fn push_error_record(org_id: &str, stream_name: &str, function: &str, row: &Value, err: &str) {
+    let cfg = get_config();
+    // don't feed the errors of the error streams back to them
+    if !cfg.limit.function_error_stream_enabled || stream_name.starts_with(ERROR_STREAM_PREFIX) {
+        return;
+    }
+    let mut record = match row {
+        Value::Object(map) => map.clone(),
+        _ => json::Map::new(),
+    };
+    record.insert(
+        cfg.common.column_timestamp.clone(),
+        Value::Number(Utc::now().timestamp_micros().into()),
+    );
+    record.insert("_function".to_string(), Value::String(function.to_string()));
+    record.insert(
+        "_stream".to_string(),
+        Value::String(stream_name.to_string()),
+    );
+    record.insert("_error".to_string(), Value::String(err.to_string()));
+
+    let mut w = ERROR_RECORDS.lock();
+    let records = w
+        .entry((org_id.to_string(), error_stream_name(function)))
+        .or_default();
+    if records.len() < cfg.limit.function_error_stream_max_records {
+        records.push(Value::Object(record));
+    }
+}
+
+pub async fn run() {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+        get_config().limit.function_stats_flush_interval,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        flush().await;
+    }
+}
+
+pub async fn flush() {
+    let error_records = std::mem::take(&mut *ERROR_RECORDS.lock());
+    for ((org_id, stream_name), records) in error_records {
+        let req = cluster_rpc::UsageRequest {
+            stream_name: stream_name.clone(),
+            data: Some(cluster_rpc::UsageData::from(records)),
+        };
+        if let Err(e) = ingestion_service::ingest(&org_id, req).await {
+            log::error!("[FUNCTION] ingest error records to {org_id}/{stream_name} error: {e}");
+        }
+    }
+
+    let mut dirty = Vec::new();
+    for mut entry in STATS.iter_mut() {
+        if entry.dirty {
+            entry.dirty = false;
+            dirty.push((entry.key().clone(), entry.stats.clone()));
+        }
+    }
+    for (key, stats) in dirty {
+        let Some((org_id, function)) = key.split_once('/') else {
+            continue;
+        };
+        if let Err(e) =
+            db::functions::set_stats(org_id, function, LOCAL_NODE_UUID.as_str(), &stats).await
+        {
+            log::error!("[FUNCTION] save stats of {key} error: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_stream_name() {
+        assert_eq!(
+            error_stream_name("parse-logs"),
+            "function_errors_parse_logs"
+        );
+    }
+}
@@ -51,7 +51,11 @@ use crate::{
     service::{db, format_partition_key},
 };
 
+pub mod breaker;
+pub mod function_stats;
 pub mod grpc;
+pub mod pipeline;
+pub mod replication;
 
 pub type TriggerAlertData = Vec<(Alert, Vec<Map<String, Value>>)>;
 
@@ -94,6 +98,26 @@ pub fn apply_vrl_fn(
     org_id: &str,
     stream_name: &str,
 ) -> Value {
+    match try_apply_vrl_fn(runtime, vrl_runtime, row) {
+        Ok(val) => val,
+        Err(err) => {
+            log::error!(
+                "{}/{} vrl failed {}. Returning original row.",
+                org_id,
+                stream_name,
+                err,
+            );
+            row.clone()
+        }
+    }
+}
+
+/// Runs the function on the row, returns the runtime error when it fails
+pub fn try_apply_vrl_fn(
+    runtime: &mut Runtime,
+    vrl_runtime: &VRLResultResolver,
+    row: &Value,
+) -> Result<Value, String> {
     let mut metadata = vrl::value::Value::from(BTreeMap::new());
     let mut target = TargetValueRef {
         value: &mut vrl::value::Value::from(row),
@@ -107,27 +131,10 @@ pub fn apply_vrl_fn(
         }
     };
     match result {
-        Ok(res) => match res.try_into() {
-            Ok(val) => val,
-            Err(err) => {
-                log::error!(
-                    "{}/{} vrl failed at processing result {:?}. Returning original row.",
-                    org_id,
-                    stream_name,
-                    err,
-                );
-                row.clone()
-            }
-        },
-        Err(err) => {
-            log::error!(
-                "{}/{} vrl runtime failed at getting result {:?}. Returning original row.",
-                org_id,
-                stream_name,
-                err,
-            );
-            row.clone()
-        }
+        Ok(res) => res
+            .try_into()
+            .map_err(|err| format!("at processing result {err:?}")),
+        Err(err) => Err(format!("at getting result {err}")),
     }
 }
 
@@ -365,7 +372,14 @@ pub fn apply_stream_functions(
         let func_key = format!("{stream_name}/{}", trans.transform.name);
         if stream_vrl_map.contains_key(&func_key) && !value.is_null() {
             let vrl_runtime = stream_vrl_map.get(&func_key).unwrap();
-            value = apply_vrl_fn(runtime, vrl_runtime, &value, org_id, stream_name);
+            value = function_stats::apply_function(
+                runtime,
+                vrl_runtime,
+                &value,
+                org_id,
+                stream_name,
+                &trans.transform.name,
+            );
         }
     }
     flatten::flatten_with_level(value, get_config().limit.ingest_flatten_level)
@@ -375,47 +389,68 @@ pub fn init_functions_runtime() -> Runtime {
     crate::common::utils::functions::init_vrl_runtime()
 }
 
+#[tracing::instrument(name = "service:ingestion:write_file", skip_all, fields(stream_name = stream_name))]
 pub async fn write_file(
     writer: &Arc<ingester::Writer>,
     stream_name: &str,
     buf: HashMap<String, SchemaRecords>,
-) -> RequestStats {
+) -> std::result::Result<RequestStats, replication::ReplicationError> {
+    let replicate = get_config().limit.ingest_replication_enabled;
     let mut req_stats = RequestStats::default();
+    let mut replica_entries = Vec::new();
     for (hour_key, entry) in buf {
         if entry.records.is_empty() {
             continue;
         }
         let entry_records = entry.records.len();
-        if let Err(e) = writer
-            .write(
-                entry.schema,
-                ingester::Entry {
-                    stream: Arc::from(stream_name),
-                    schema_key: Arc::from(entry.schema_key.as_str()),
-                    partition_key: Arc::from(hour_key.as_str()),
-                    data: entry.records,
-                    data_size: entry.records_size,
-                },
-                false,
-            )
-            .await
-        {
+        let mut wal_entry = ingester::Entry {
+            stream: Arc::from(stream_name),
+            schema_key: Arc::from(entry.schema_key.as_str()),
+            partition_key: Arc::from(hour_key.as_str()),
+            data: entry.records,
+            data_size: entry.records_size,
+        };
+        if replicate {
+            match wal_entry.into_bytes() {
+                Ok(v) => replica_entries.push(v),
+                Err(e) => log::error!("ingestion replicate entry error: {}", e),
+            }
+        }
+        if let Err(e) = writer.write(entry.schema, wal_entry, false).await {
             log::error!("ingestion write file error: {}", e);
         }
 
         req_stats.size += entry.records_size as f64 / SIZE_IN_MB;
         req_stats.records += entry_records as i64;
     }
-    req_stats
+    // the client is acked only once both the local wal and the peer hold the entries
+    if !replica_entries.is_empty() {
+        if let Err(e) = writer.sync().await {
+            log::error!("ingestion sync wal error: {}", e);
+        }
+        if let Err(e) =
+            replication::replicate(writer.org_id(), writer.stream_type(), replica_entries).await
+        {
+            log::error!("ingestion replicate error: {}", e);
+            return Err(replication::ReplicationError(e.to_string()));
+        }
+    }
+    Ok(req_stats)
+}
+
+/// Returns true when the organization is blocked or has used up its storage
+/// quota.
+pub fn is_org_quota_exceeded(org_id: &str) -> bool {
+    (!db::file_list::BLOCKED_ORGS.is_empty()
+        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string()))
+        || crate::service::quota::is_storage_exceeded(org_id)
 }
 
 pub fn check_ingestion_allowed(org_id: &str, stream_name: Option<&str>) -> Result<()> {
     if !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
         return Err(anyhow!("not an ingester"));
     }
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if is_org_quota_exceeded(org_id) {
         return Err(anyhow!("Quota exceeded for this organization [{}]", org_id));
     }
 
@@ -429,6 +464,59 @@ pub fn check_ingestion_allowed(org_id: &str, stream_name: Option<&str>) -> Resul
     Ok(())
 }
 
+/// Exports the lag of the record timestamp behind the ingest time, and
+/// applies `ZO_INGEST_SKEW_POLICY` to the records outside of the skew window.
+/// Returns the timestamp to keep, an error when the record is rejected.
+pub fn check_timestamp_skew(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    timestamp: i64,
+) -> Result<i64> {
+    let cfg = get_config();
+    let now = Utc::now().timestamp_micros();
+    let stream_type = stream_type.to_string();
+    config::metrics::INGEST_EVENT_LAG
+        .with_label_values(&[org_id, stream_name, &stream_type])
+        .observe((now - timestamp) as f64 / 1_000_000.0);
+    let Some(skew) = timestamp_skew(
+        timestamp,
+        now,
+        cfg.limit.ingest_skew_future_secs,
+        cfg.limit.ingest_skew_past_hours,
+    ) else {
+        return Ok(timestamp);
+    };
+    let policy = cfg.limit.ingest_skew_policy.as_str();
+    config::metrics::INGEST_SKEWED_RECORDS
+        .with_label_values(&[org_id, stream_name, &stream_type, skew, policy])
+        .inc();
+    match policy {
+        "clamp" => Ok(now),
+        "reject" => Err(anyhow!(
+            "Record timestamp {timestamp} is too far in the {skew} of the ingest time"
+        )),
+        _ => Ok(timestamp),
+    }
+}
+
+/// `future` or `past` when the timestamp is outside of the skew window of
+/// `now`, a limit of 0 disables its side.
+fn timestamp_skew(
+    timestamp: i64,
+    now: i64,
+    future_secs: i64,
+    past_hours: i64,
+) -> Option<&'static str> {
+    if future_secs > 0 && timestamp > now + future_secs * 1_000_000 {
+        Some("future")
+    } else if past_hours > 0 && timestamp < now - past_hours * 3_600_000_000 {
+        Some("past")
+    } else {
+        None
+    }
+}
+
 pub fn get_val_for_attr(attr_val: &Value) -> Value {
     let local_val = attr_val.as_object().unwrap();
     if let Some((key, value)) = local_val.into_iter().next() {
@@ -518,10 +606,10 @@ pub async fn get_stream_routing(
         "{}/{}/{}",
         &stream_params.org_id, stream_params.stream_type, &stream_params.stream_name,
     )) {
-        let res: Vec<Routing> = pipeline
-            .routing
-            .as_ref()
-            .unwrap()
+        let Some(routing) = pipeline.routing.as_ref() else {
+            return;
+        };
+        let res: Vec<Routing> = routing
             .iter()
             .map(|(k, v)| Routing {
                 destination: k.to_string(),
@@ -561,6 +649,25 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_timestamp_skew() {
+        let now = 1_700_000_000_000_000;
+        assert_eq!(timestamp_skew(now, now, 600, 24), None);
+        assert_eq!(timestamp_skew(now + 599_000_000, now, 600, 24), None);
+        assert_eq!(
+            timestamp_skew(now + 601_000_000, now, 600, 24),
+            Some("future")
+        );
+        assert_eq!(timestamp_skew(now - 23 * 3_600_000_000, now, 600, 24), None);
+        assert_eq!(
+            timestamp_skew(now - 25 * 3_600_000_000, now, 600, 24),
+            Some("past")
+        );
+        // disabled sides
+        assert_eq!(timestamp_skew(now + 3_600_000_000, now, 0, 0), None);
+        assert_eq!(timestamp_skew(0, now, 0, 0), None);
+    }
+
     #[test]
     fn test_format_partition_key() {
         assert_eq!(format_partition_key("default/olympics"), "defaultolympics");
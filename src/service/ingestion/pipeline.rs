@@ -0,0 +1,308 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{
+    get_config,
+    meta::stream::{RoutingCondition, StreamType},
+    utils::{flatten, json::Value},
+};
+use vector_enrichment::TableRegistry;
+use vrl::compiler::runtime::Runtime;
+
+use super::{apply_vrl_fn, compile_vrl_function, function_stats};
+use crate::common::{
+    infra::config::{QUERY_FUNCTIONS, STREAM_PIPELINES},
+    meta::{functions::VRLResultResolver, pipelines::PipeLine},
+};
+
+/// The steps of a pipeline with their functions compiled.
+pub struct StreamPipeline {
+    stream_name: String,
+    steps: Vec<CompiledStep>,
+    dry_run: bool,
+}
+
+/// A record leaving the pipeline.
+#[derive(Debug)]
+pub struct PipelineOutput {
+    /// set when the record goes to another organization
+    pub org_id: Option<String>,
+    pub stream_name: String,
+    pub value: Value,
+}
+
+struct CompiledStep {
+    conditions: Vec<RoutingCondition>,
+    function: Option<(String, VRLResultResolver)>,
+    destination: Option<String>,
+    destination_org: Option<String>,
+    clone_record: bool,
+}
+
+impl StreamPipeline {
+    /// Fails when the function of a step is missing or does not compile.
+    pub fn new(org_id: &str, pipeline: &PipeLine) -> Result<Self, anyhow::Error> {
+        let mut steps = Vec::with_capacity(pipeline.steps.len());
+        for step in pipeline.steps.iter() {
+            let function = match &step.function {
+                None => None,
+                Some(name) => {
+                    let Some(func) = QUERY_FUNCTIONS.get(&format!("{org_id}/{name}")) else {
+                        return Err(anyhow::anyhow!(
+                            "Function {} of step {} not found",
+                            name,
+                            step.name
+                        ));
+                    };
+                    let config = compile_vrl_function(&func.function, org_id)?;
+                    let registry = config.config.get_custom::<TableRegistry>().unwrap();
+                    registry.finish_load();
+                    Some((
+                        name.clone(),
+                        VRLResultResolver {
+                            program: config.program,
+                            fields: config.fields,
+                        },
+                    ))
+                }
+            };
+            let destination_org = step
+                .destination_org
+                .as_ref()
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty() && *v != org_id);
+            if destination_org.is_some() {
+                if !get_config().common.pipeline_cross_org_routing_enabled {
+                    return Err(anyhow::anyhow!(
+                        "Step {} routes to another organization, which is disabled",
+                        step.name
+                    ));
+                }
+                if step.destination.is_none() {
+                    return Err(anyhow::anyhow!(
+                        "Step {} routes to another organization without a destination stream",
+                        step.name
+                    ));
+                }
+            }
+            steps.push(CompiledStep {
+                conditions: step.conditions.clone(),
+                function,
+                destination: step.destination.clone(),
+                destination_org: destination_org.map(|v| v.to_string()),
+                clone_record: step.clone_record,
+            });
+        }
+        Ok(Self {
+            stream_name: pipeline.stream_name.clone(),
+            steps,
+            dry_run: false,
+        })
+    }
+
+    /// Makes the functions run without recording stats nor error records.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// The destination streams in the organization of the pipeline
+    pub fn destinations(&self) -> impl Iterator<Item = &str> {
+        self.steps
+            .iter()
+            .filter(|s| s.destination_org.is_none())
+            .filter_map(|s| s.destination.as_deref())
+    }
+
+    /// Runs the flattened record through the steps, returns where the record
+    /// and its copies go to, nothing when a function dropped the record.
+    pub async fn execute(
+        &self,
+        runtime: &mut Runtime,
+        org_id: &str,
+        mut value: Value,
+    ) -> Vec<PipelineOutput> {
+        let mut outputs = Vec::new();
+        for step in self.steps.iter() {
+            if !step.matches(&value).await {
+                continue;
+            }
+            if let Some((name, function)) = &step.function {
+                value = if self.dry_run {
+                    apply_vrl_fn(runtime, function, &value, org_id, &self.stream_name)
+                } else {
+                    function_stats::apply_function(
+                        runtime,
+                        function,
+                        &value,
+                        org_id,
+                        &self.stream_name,
+                        name,
+                    )
+                };
+                value = match flatten::flatten_with_level(
+                    value,
+                    get_config().limit.ingest_flatten_level,
+                ) {
+                    Ok(v) if v.is_object() => v,
+                    _ => return outputs,
+                };
+            }
+            if let Some(destination) = &step.destination {
+                let record = if step.clone_record {
+                    value.clone()
+                } else {
+                    std::mem::take(&mut value)
+                };
+                outputs.push(PipelineOutput {
+                    org_id: step.destination_org.clone(),
+                    stream_name: destination.clone(),
+                    value: record,
+                });
+                if !step.clone_record {
+                    return outputs;
+                }
+            }
+        }
+        outputs.push(PipelineOutput {
+            org_id: None,
+            stream_name: self.stream_name.clone(),
+            value,
+        });
+        outputs
+    }
+}
+
+impl CompiledStep {
+    async fn matches(&self, value: &Value) -> bool {
+        let Some(row) = value.as_object() else {
+            return false;
+        };
+        for condition in self.conditions.iter() {
+            if !condition.evaluate(row).await {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Compiles the pipeline of the stream, only the pipelines with steps.
+pub fn get_stream_pipeline(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Option<StreamPipeline> {
+    let pipeline = STREAM_PIPELINES.get(&format!("{org_id}/{stream_type}/{stream_name}"))?;
+    if pipeline.steps.is_empty() {
+        return None;
+    }
+    match StreamPipeline::new(org_id, &pipeline) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            log::error!(
+                "{}/{} pipeline {} compile failed: {}",
+                org_id,
+                stream_name,
+                pipeline.name,
+                e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+    use crate::common::meta::pipelines::PipelineStep;
+
+    fn step(column: &str, destination: &str, clone_record: bool) -> PipelineStep {
+        json::from_value(json::json!({
+            "name": destination,
+            "conditions": [{"column": column, "operator": "=", "value": "error"}],
+            "destination": destination,
+            "clone": clone_record,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_execute_steps() {
+        let pipeline = PipeLine {
+            name: "p".to_string(),
+            description: "".to_string(),
+            stream_name: "default".to_string(),
+            stream_type: StreamType::Logs,
+            routing: None,
+            steps: vec![step("level", "errors", true), step("kind", "audit", false)],
+            version: 1,
+            meta: None,
+        };
+        let pipeline = StreamPipeline::new("org", &pipeline).unwrap();
+        let mut runtime = crate::service::ingestion::init_functions_runtime();
+
+        let outputs = pipeline
+            .execute(&mut runtime, "org", json::json!({"level": "error"}))
+            .await;
+        let streams = outputs
+            .iter()
+            .map(|o| o.stream_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(streams, vec!["errors", "default"]);
+
+        let outputs = pipeline
+            .execute(
+                &mut runtime,
+                "org",
+                json::json!({"level": "error", "kind": "error"}),
+            )
+            .await;
+        let streams = outputs
+            .iter()
+            .map(|o| o.stream_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(streams, vec!["errors", "audit"]);
+
+        let outputs = pipeline
+            .execute(&mut runtime, "org", json::json!({"level": "info"}))
+            .await;
+        assert_eq!(outputs.len(), 1);
+    }
+
+    #[test]
+    fn test_cross_org_destination_disabled() {
+        let mut cross_org = step("level", "errors", false);
+        cross_org.destination_org = Some("payments".to_string());
+        let mut pipeline = PipeLine {
+            name: "p".to_string(),
+            description: "".to_string(),
+            stream_name: "default".to_string(),
+            stream_type: StreamType::Logs,
+            routing: None,
+            steps: vec![cross_org],
+            version: 1,
+            meta: None,
+        };
+        assert!(StreamPipeline::new("org", &pipeline).is_err());
+
+        // the organization of the pipeline is not another organization
+        pipeline.steps[0].destination_org = Some("org".to_string());
+        let pipeline = StreamPipeline::new("org", &pipeline).unwrap();
+        assert_eq!(pipeline.destinations().collect::<Vec<_>>(), vec!["errors"]);
+    }
+}
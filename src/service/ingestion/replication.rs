@@ -0,0 +1,578 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Synchronous copy of the ingested wal entries to a peer ingester.
+//!
+//! Every batch written to the local wal is also sent to the ring successor of
+//! the local node, and the client is acked only after the peer stored it. When
+//! the peer can't be reached the batch is kept as a hint on the local disk and
+//! handed off later. A peer only keeps the replicas until the source pushed its
+//! wal to the object storage, and replays them into its own wal when the source
+//! leaves the cluster without releasing them.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use config::{
+    cluster::{is_ingester, LOCAL_NODE_ROLE, LOCAL_NODE_UUID},
+    get_config,
+    meta::cluster::{Node, GRPC_MIN_VERSION, GRPC_VERSION_HEADER},
+    utils::{file::scan_files, schema::infer_json_schema_from_values, schema_ext::SchemaExt},
+};
+use hashbrown::{HashMap, HashSet};
+use once_cell::sync::Lazy;
+use proto::cluster_rpc::{self, replication_client::ReplicationClient};
+use tokio::sync::{Mutex, RwLock};
+use tonic::{
+    codec::CompressionEncoding,
+    metadata::MetadataValue,
+    service::{interceptor::InterceptedService, Interceptor},
+    transport::Channel,
+    Request,
+};
+
+use crate::common::infra::{cluster, tls};
+
+/// The entries could neither be copied to the peer nor kept as a hint, the
+/// request they came with mustn't be acked.
+#[derive(Debug, thiserror::Error)]
+#[error("Replicate to the peer ingester failed: {0}, retry later")]
+pub struct ReplicationError(pub String);
+
+const REPLICAS_DIR: &str = "replicas";
+const HINTS_DIR: &str = "hints";
+
+static REPLICAS: Lazy<Mutex<HashMap<SegmentKey, Segment>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static HINTS: Lazy<Mutex<HashMap<SegmentKey, Segment>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static CHANNELS: Lazy<RwLock<HashMap<String, Channel>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+// sources missing from the cluster, with the time they were first missed
+static LEFT_SOURCES: Lazy<Mutex<HashMap<String, i64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_SEGMENT_ID: Lazy<AtomicU64> =
+    Lazy::new(|| AtomicU64::new(Utc::now().timestamp_micros() as u64));
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+struct SegmentKey {
+    dir: PathBuf,
+    org_id: String,
+    stream_type: String,
+}
+
+struct Segment {
+    writer: wal::Writer,
+    created_at: i64,
+}
+
+/// Copies the entries the local node just wrote into its wal to the peer
+/// ingester, falling back to a hint on the local disk.
+pub async fn replicate(org_id: &str, stream_type: &str, entries: Vec<Vec<u8>>) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let req = cluster_rpc::ReplicateRequest {
+        source: LOCAL_NODE_UUID.clone(),
+        org_id: org_id.to_string(),
+        stream_type: stream_type.to_string(),
+        entries,
+    };
+    match get_peer().await {
+        Some(peer) => match send(&peer, req.clone()).await {
+            Ok(()) => return Ok(()),
+            Err(e) => log::warn!(
+                "[REPLICATION] replicate to node[{}] failed, storing hint: {}",
+                peer.grpc_addr,
+                e
+            ),
+        },
+        None => {
+            // nobody to copy to in a single ingester cluster
+            if !has_other_ingesters().await {
+                return Ok(());
+            }
+        }
+    }
+    let key = SegmentKey {
+        dir: segment_root(HINTS_DIR),
+        org_id: req.org_id,
+        stream_type: req.stream_type,
+    };
+    append(&HINTS, key, &req.entries).await
+}
+
+/// Stores the entries an ingester replicated to the local node.
+pub async fn receive(req: cluster_rpc::ReplicateRequest) -> Result<()> {
+    if req.entries.is_empty() {
+        return Ok(());
+    }
+    if !is_ingester(&LOCAL_NODE_ROLE) {
+        return Err(anyhow!("not an ingester"));
+    }
+    check_name(&req.source)?;
+    check_name(&req.org_id)?;
+    check_name(&req.stream_type)?;
+    let key = SegmentKey {
+        dir: segment_root(REPLICAS_DIR).join(&req.source),
+        org_id: req.org_id,
+        stream_type: req.stream_type,
+    };
+    append(&REPLICAS, key, &req.entries).await
+}
+
+/// Drops the replicas of a source that pushed all its wal to the object
+/// storage.
+pub async fn release_replicas(source: &str) -> Result<()> {
+    check_name(source)?;
+    let dir = segment_root(REPLICAS_DIR).join(source);
+    REPLICAS.lock().await.retain(|k, _| k.dir != dir);
+    LEFT_SOURCES.lock().await.remove(source);
+    if dir.exists() {
+        tokio::fs::remove_dir_all(&dir).await?;
+    }
+    log::info!("[REPLICATION] released replicas of node[{}]", source);
+    Ok(())
+}
+
+/// Tells the other ingesters the local wal is in the object storage, called
+/// once the node drained.
+pub async fn release() {
+    if !get_config().limit.ingest_replication_enabled {
+        return;
+    }
+    // the pending hints are in the object storage as well
+    HINTS.lock().await.clear();
+    let hints_dir = segment_root(HINTS_DIR);
+    if hints_dir.exists() {
+        if let Err(e) = tokio::fs::remove_dir_all(&hints_dir).await {
+            log::error!("[REPLICATION] delete hints failed: {}", e);
+        }
+    }
+    // the peer changes with the cluster, any ingester may hold a replica
+    let nodes = cluster::get_cached_nodes(|node| is_ingester(&node.role))
+        .await
+        .unwrap_or_default();
+    for node in nodes {
+        if node.uuid.eq(LOCAL_NODE_UUID.as_str()) {
+            continue;
+        }
+        let req = cluster_rpc::ReleaseRequest {
+            source: LOCAL_NODE_UUID.clone(),
+        };
+        let ret = match get_client(&node).await {
+            Ok(mut client) => client.release(req).await.map_err(|e| anyhow!(e)),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = ret {
+            log::error!(
+                "[REPLICATION] release replicas on node[{}] failed: {}",
+                node.grpc_addr,
+                e
+            );
+        }
+    }
+}
+
+/// Hands off the hints to the current peer.
+pub async fn deliver_hints() -> Result<()> {
+    let cfg = get_config();
+    let root = segment_root(HINTS_DIR);
+    let files = list_segments(&root)?;
+    if files.is_empty() {
+        return Ok(());
+    }
+    close_segments(&HINTS, &files).await;
+    let Some(peer) = get_peer().await else {
+        return Ok(());
+    };
+    let max_size = cfg.grpc.max_message_size * 1024 * 1024 / 2;
+    for file in files {
+        // a hint the local node already pushed to the object storage is useless
+        if is_expired(&file, cfg.limit.ingest_replica_retention)? {
+            std::fs::remove_file(&file)?;
+            continue;
+        }
+        let Some((org_id, stream_type)) = parse_segment_path(&root, &file) else {
+            continue;
+        };
+        let entries = read_segment(&file)?;
+        for batch in split_entries(entries, max_size) {
+            let req = cluster_rpc::ReplicateRequest {
+                source: LOCAL_NODE_UUID.clone(),
+                org_id: org_id.clone(),
+                stream_type: stream_type.clone(),
+                entries: batch,
+            };
+            // keep the hint for the next round
+            if let Err(e) = send(&peer, req).await {
+                log::warn!(
+                    "[REPLICATION] hand off hints to node[{}] failed: {}",
+                    peer.grpc_addr,
+                    e
+                );
+                return Ok(());
+            }
+        }
+        std::fs::remove_file(&file)?;
+        log::info!(
+            "[REPLICATION] handed off hint {} to node[{}]",
+            file.display(),
+            peer.grpc_addr
+        );
+    }
+    Ok(())
+}
+
+/// Prunes the replicas the sources already pushed, and promotes the replicas
+/// of the sources that left the cluster.
+pub async fn check_replicas() -> Result<()> {
+    let cfg = get_config();
+    let root = segment_root(REPLICAS_DIR);
+    if !root.exists() {
+        return Ok(());
+    }
+    let live = cluster::get_cached_nodes(|_| true)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|node| node.uuid)
+        .collect::<HashSet<_>>();
+    let now = Utc::now().timestamp();
+    for dir in std::fs::read_dir(&root)? {
+        let dir = dir?.path();
+        let Some(source) = dir.file_name().and_then(|v| v.to_str()).map(String::from) else {
+            continue;
+        };
+        if live.contains(&source) {
+            LEFT_SOURCES.lock().await.remove(&source);
+            let files = list_segments(&dir)?
+                .into_iter()
+                .filter(|f| is_expired(f, cfg.limit.ingest_replica_retention).unwrap_or_default())
+                .collect::<Vec<_>>();
+            close_segments(&REPLICAS, &files).await;
+            for file in files {
+                std::fs::remove_file(&file)?;
+            }
+            continue;
+        }
+        // give the node list some time to settle before taking over the wal
+        let since = *LEFT_SOURCES
+            .lock()
+            .await
+            .entry(source.clone())
+            .or_insert(now);
+        if now - since < cfg.limit.node_heartbeat_ttl {
+            continue;
+        }
+        promote(&source, &dir).await?;
+        LEFT_SOURCES.lock().await.remove(&source);
+    }
+    Ok(())
+}
+
+// the source left without releasing, its own wal may be lost with its disk,
+// so the replicas are written into the local wal and pushed by the local node
+async fn promote(source: &str, dir: &Path) -> Result<()> {
+    REPLICAS.lock().await.retain(|k, _| k.dir != dir);
+    let files = list_segments(dir)?;
+    log::warn!(
+        "[REPLICATION] node[{}] left the cluster, promoting {} replica files",
+        source,
+        files.len()
+    );
+    for file in files {
+        let Some((org_id, stream_type)) = parse_segment_path(dir, &file) else {
+            continue;
+        };
+        let writer = ingester::get_writer(0, &org_id, &stream_type).await;
+        for entry_bytes in read_segment(&file)? {
+            let mut entry = match ingester::Entry::from_bytes(&entry_bytes) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("[REPLICATION] unable to read entry: {}, skip the entry", e);
+                    continue;
+                }
+            };
+            let schema =
+                infer_json_schema_from_values(entry.data.iter().cloned(), stream_type.as_str())?;
+            entry.schema_key = schema.hash_key().into();
+            writer.write(Arc::new(schema), entry, false).await?;
+        }
+        std::fs::remove_file(&file)?;
+    }
+    tokio::fs::remove_dir_all(dir).await?;
+    Ok(())
+}
+
+async fn append(
+    segments: &Mutex<HashMap<SegmentKey, Segment>>,
+    key: SegmentKey,
+    entries: &[Vec<u8>],
+) -> Result<()> {
+    let cfg = get_config();
+    let now = Utc::now().timestamp_micros();
+    let max_age = Duration::from_secs(cfg.limit.max_file_retention_time).as_micros() as i64;
+    let mut segments = segments.lock().await;
+    let rotate = segments.get(&key).map_or(true, |s| {
+        s.writer.size().0 > cfg.limit.max_file_size_on_disk || s.created_at + max_age <= now
+    });
+    if rotate {
+        let writer = wal::Writer::new(
+            key.dir.clone(),
+            &key.org_id,
+            &key.stream_type,
+            NEXT_SEGMENT_ID.fetch_add(1, Ordering::SeqCst),
+            0,
+        )?;
+        if let Some(old) = segments.insert(
+            key.clone(),
+            Segment {
+                writer,
+                created_at: now,
+            },
+        ) {
+            old.writer.sync()?;
+        }
+    }
+    let segment = segments.get_mut(&key).unwrap();
+    for entry in entries {
+        segment.writer.write(entry, false)?;
+    }
+    segment.writer.sync()?;
+    Ok(())
+}
+
+// closes the open segments writing into the given files
+async fn close_segments(segments: &Mutex<HashMap<SegmentKey, Segment>>, files: &[PathBuf]) {
+    let files = files.iter().collect::<HashSet<_>>();
+    segments.lock().await.retain(|_, s| {
+        if !files.contains(s.writer.path()) {
+            return true;
+        }
+        if let Err(e) = s.writer.sync() {
+            log::error!(
+                "[REPLICATION] sync {} failed: {}",
+                s.writer.path().display(),
+                e
+            );
+        }
+        false
+    });
+}
+
+fn read_segment(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let mut reader = wal::Reader::from_path(path)?;
+    let mut entries = Vec::new();
+    loop {
+        match reader.read_entry() {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => break,
+            Err(e) => {
+                log::error!(
+                    "[REPLICATION] unable to read {}: {}, skip the rest of the file",
+                    path.display(),
+                    e
+                );
+                break;
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn list_segments(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    Ok(scan_files(dir, "wal", None)?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect())
+}
+
+// segments are stored as {dir}/{org_id}/{stream_type}/{id}.wal
+fn parse_segment_path(dir: &Path, file: &Path) -> Option<(String, String)> {
+    let columns = file
+        .strip_prefix(dir)
+        .ok()?
+        .iter()
+        .map(|v| v.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    if columns.len() != 3 {
+        return None;
+    }
+    Some((columns[0].clone(), columns[1].clone()))
+}
+
+fn is_expired(file: &Path, retention: u64) -> Result<bool> {
+    let modified = std::fs::metadata(file)?.modified()?;
+    Ok(SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or_default()
+        .as_secs()
+        > retention)
+}
+
+fn split_entries(entries: Vec<Vec<u8>>, max_size: usize) -> Vec<Vec<Vec<u8>>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_size = 0;
+    for entry in entries {
+        if !batch.is_empty() && batch_size + entry.len() > max_size {
+            batches.push(std::mem::take(&mut batch));
+            batch_size = 0;
+        }
+        batch_size += entry.len();
+        batch.push(entry);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains('/') || name.contains('\\') || name.contains("..") {
+        return Err(anyhow!("invalid name: {}", name));
+    }
+    Ok(())
+}
+
+fn segment_root(kind: &str) -> PathBuf {
+    PathBuf::from(&get_config().common.data_wal_dir).join(kind)
+}
+
+async fn get_peer() -> Option<Node> {
+    let nodes = cluster::get_cached_online_ingester_nodes().await?;
+    let mut uuids = nodes.iter().map(|n| n.uuid.as_str()).collect::<Vec<_>>();
+    uuids.sort();
+    let peer = ring_successor(&uuids, &LOCAL_NODE_UUID)?;
+    nodes.iter().find(|n| n.uuid == peer).cloned()
+}
+
+async fn has_other_ingesters() -> bool {
+    cluster::get_cached_nodes(|node| is_ingester(&node.role))
+        .await
+        .unwrap_or_default()
+        .iter()
+        .any(|node| node.uuid.ne(LOCAL_NODE_UUID.as_str()))
+}
+
+// the next node after the local one on the ring of the sorted uuids
+fn ring_successor<'a>(uuids: &[&'a str], local: &str) -> Option<&'a str> {
+    uuids
+        .iter()
+        .find(|uuid| **uuid > local)
+        .or_else(|| uuids.first())
+        .copied()
+        .filter(|uuid| *uuid != local)
+}
+
+async fn get_client(
+    node: &Node,
+) -> Result<ReplicationClient<InterceptedService<Channel, impl Interceptor>>> {
+    let cfg = get_config();
+    let token: MetadataValue<_> = cluster::get_internal_grpc_token()
+        .parse()
+        .map_err(|_| anyhow!("invalid token"))?;
+    let grpc_version: MetadataValue<_> = node
+        .negotiate_grpc_version()
+        .unwrap_or(GRPC_MIN_VERSION)
+        .into();
+    let cached = CHANNELS.read().await.get(&node.grpc_addr).cloned();
+    let channel = match cached {
+        Some(v) => v,
+        None => {
+            let channel = tls::grpc_endpoint(node.grpc_addr.clone())?
+                .connect_timeout(Duration::from_secs(cfg.grpc.connect_timeout))
+                .timeout(Duration::from_secs(cfg.limit.ingest_replication_timeout))
+                .connect()
+                .await?;
+            CHANNELS
+                .write()
+                .await
+                .insert(node.grpc_addr.clone(), channel.clone());
+            channel
+        }
+    };
+    let client = ReplicationClient::with_interceptor(channel, move |mut req: Request<()>| {
+        req.metadata_mut().insert("authorization", token.clone());
+        req.metadata_mut()
+            .insert(GRPC_VERSION_HEADER, grpc_version.clone());
+        Ok(req)
+    });
+    Ok(client
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
+        .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024))
+}
+
+async fn send(node: &Node, req: cluster_rpc::ReplicateRequest) -> Result<()> {
+    let mut client = get_client(node).await?;
+    if let Err(e) = client.replicate(req).await {
+        // reconnect on the next request
+        CHANNELS.write().await.remove(&node.grpc_addr);
+        return Err(anyhow!(e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_successor() {
+        let uuids = vec!["a", "c", "e"];
+        assert_eq!(ring_successor(&uuids, "a"), Some("c"));
+        assert_eq!(ring_successor(&uuids, "c"), Some("e"));
+        assert_eq!(ring_successor(&uuids, "e"), Some("a"));
+        // the local node isn't online
+        assert_eq!(ring_successor(&uuids, "d"), Some("e"));
+        assert_eq!(ring_successor(&["a"], "a"), None);
+        assert_eq!(ring_successor(&[], "a"), None);
+    }
+
+    #[test]
+    fn test_split_entries() {
+        let entries = vec![vec![0; 4], vec![0; 4], vec![0; 10], vec![0; 1]];
+        let batches = split_entries(entries, 8);
+        assert_eq!(
+            batches.iter().map(|b| b.len()).collect::<Vec<_>>(),
+            vec![2, 1, 1]
+        );
+        assert!(split_entries(vec![], 8).is_empty());
+    }
+
+    #[test]
+    fn test_parse_segment_path() {
+        let dir = Path::new("/data/wal/hints");
+        assert_eq!(
+            parse_segment_path(dir, Path::new("/data/wal/hints/default/logs/1.wal")),
+            Some(("default".to_string(), "logs".to_string()))
+        );
+        assert_eq!(
+            parse_segment_path(dir, Path::new("/data/wal/hints/1.wal")),
+            None
+        );
+    }
+}
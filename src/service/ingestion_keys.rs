@@ -0,0 +1,248 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{io::Error, net::IpAddr};
+
+use actix_web::HttpResponse;
+use config::{get_config, ider, utils::rand::generate_random_string};
+
+use crate::{
+    common::{
+        infra::config::INGESTION_KEYS,
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            organization::{
+                parse_ingestion_key, IngestionKey, IngestionKeyRequest, IngestionKeyResponse,
+                INGESTION_KEY_PREFIX,
+            },
+            rbac::{Permission, Resource},
+            service_account::is_valid_ip_rule,
+        },
+    },
+    service::{db, rbac},
+};
+
+const KEY_NOT_FOUND: &str = "Ingestion key not found";
+// microseconds, the last used time is written back at most once a minute
+const LAST_USED_INTERVAL: i64 = 60_000_000;
+
+/// Checks an ingestion key of the user in the org, the last used time of the
+/// key is updated in the background.
+pub fn validate_key(org_id: &str, user_email: &str, key: &str) -> bool {
+    let Some((id, secret)) = parse_ingestion_key(key) else {
+        return false;
+    };
+    let Some(mut ingestion_key) = INGESTION_KEYS.get_mut(&format!("{org_id}/{id}")) else {
+        return false;
+    };
+    if !ingestion_key.is_active()
+        || !ingestion_key.user.eq(user_email)
+        || ingestion_key.hash != sha256::digest(secret)
+    {
+        return false;
+    }
+    let now = chrono::Utc::now().timestamp_micros();
+    if now - ingestion_key.last_used_at >= LAST_USED_INTERVAL {
+        ingestion_key.last_used_at = now;
+        let org_id = org_id.to_string();
+        let id = id.to_string();
+        tokio::task::spawn(async move {
+            if let Err(e) = set_last_used(&org_id, &id, now).await {
+                log::error!("Error updating last used time of ingestion key {id}: {e}");
+            }
+        });
+    }
+    true
+}
+
+/// Checks the client ip against the allowlist of an ingestion key, passwords
+/// that aren't ingestion keys are not restricted.
+pub fn is_ip_allowed(org_id: &str, key: &str, ip: Option<IpAddr>) -> bool {
+    let Some((id, _)) = parse_ingestion_key(key) else {
+        return true;
+    };
+    INGESTION_KEYS
+        .get(&format!("{org_id}/{id}"))
+        .map_or(true, |v| v.is_ip_allowed(ip))
+}
+
+// reads the key back so that a concurrent revocation isn't overwritten
+async fn set_last_used(org_id: &str, id: &str, last_used_at: i64) -> Result<(), anyhow::Error> {
+    let mut ingestion_key = db::ingestion_keys::get(org_id, id).await?;
+    if !ingestion_key.is_active() || ingestion_key.last_used_at >= last_used_at {
+        return Ok(());
+    }
+    ingestion_key.last_used_at = last_used_at;
+    db::ingestion_keys::set(org_id, &ingestion_key).await
+}
+
+// users that can manage users of the org can see and revoke all keys
+fn can_manage_all(org_id: &str, user_id: &str) -> bool {
+    rbac::is_allowed(org_id, user_id, Resource::User, "*", Permission::Write)
+}
+
+fn get_keys(org_id: &str) -> Vec<IngestionKey> {
+    let prefix = format!("{org_id}/");
+    INGESTION_KEYS
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix))
+        .map(|v| v.value().clone())
+        .collect()
+}
+
+/// Lists the ingestion keys of the user, newest first, revoked keys are kept
+/// for reference.
+pub async fn list_keys(org_id: &str, user_id: &str) -> Result<HttpResponse, Error> {
+    let all = can_manage_all(org_id, user_id);
+    let mut keys = get_keys(org_id)
+        .into_iter()
+        .filter(|k| all || k.user.eq(user_id))
+        .map(|k| k.redacted())
+        .collect::<Vec<_>>();
+    keys.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(HttpResponse::Ok().json(keys))
+}
+
+pub async fn create_key(
+    org_id: &str,
+    user_id: &str,
+    req: IngestionKeyRequest,
+) -> Result<HttpResponse, Error> {
+    let max = get_config().auth.ingestion_keys_max_per_user;
+    let active = get_keys(org_id)
+        .iter()
+        .filter(|k| k.is_active() && k.user.eq(user_id))
+        .count();
+    if active >= max {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "A user can have at most {max} active ingestion keys, revoke an old key first"
+        )));
+    }
+    if let Some(rule) = req.ip_allowlist.iter().find(|v| !is_valid_ip_rule(v)) {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "Invalid ip allowlist entry: {rule}"
+        )));
+    }
+    let secret = generate_random_string(32);
+    let ingestion_key = IngestionKey {
+        id: ider::generate(),
+        user: user_id.to_string(),
+        description: req.description,
+        hash: sha256::digest(secret.as_str()),
+        created_at: chrono::Utc::now().timestamp_micros(),
+        revoked_at: None,
+        last_used_at: 0,
+        ip_allowlist: req.ip_allowlist,
+    };
+    if let Err(e) = db::ingestion_keys::set(org_id, &ingestion_key).await {
+        return Ok(MetaHttpResponse::internal_error(e));
+    }
+    // make the key usable on this node right away
+    INGESTION_KEYS.insert(
+        format!("{org_id}/{}", ingestion_key.id),
+        ingestion_key.clone(),
+    );
+    Ok(HttpResponse::Ok().json(IngestionKeyResponse {
+        key: format!("{INGESTION_KEY_PREFIX}{}_{secret}", ingestion_key.id),
+        info: ingestion_key.redacted(),
+    }))
+}
+
+/// Revokes a key, the other keys of the user stay valid.
+pub async fn revoke_key(org_id: &str, user_id: &str, id: &str) -> Result<HttpResponse, Error> {
+    let Ok(mut ingestion_key) = db::ingestion_keys::get(org_id, id).await else {
+        return Ok(MetaHttpResponse::not_found(KEY_NOT_FOUND));
+    };
+    if !ingestion_key.user.eq(user_id) && !can_manage_all(org_id, user_id) {
+        return Ok(MetaHttpResponse::not_found(KEY_NOT_FOUND));
+    }
+    if !ingestion_key.is_active() {
+        return Ok(MetaHttpResponse::bad_request(
+            "Ingestion key is already revoked",
+        ));
+    }
+    ingestion_key.revoked_at = Some(chrono::Utc::now().timestamp_micros());
+    if let Err(e) = db::ingestion_keys::set(org_id, &ingestion_key).await {
+        return Ok(MetaHttpResponse::internal_error(e));
+    }
+    INGESTION_KEYS.insert(format!("{org_id}/{id}"), ingestion_key);
+    Ok(MetaHttpResponse::ok("Ingestion key revoked"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        let org_id = "test_ingestion_keys_org";
+        let secret = "secret";
+        INGESTION_KEYS.insert(
+            format!("{org_id}/k1"),
+            IngestionKey {
+                id: "k1".to_string(),
+                user: "user@example.com".to_string(),
+                hash: sha256::digest(secret),
+                created_at: 1,
+                last_used_at: chrono::Utc::now().timestamp_micros(),
+                ..Default::default()
+            },
+        );
+        let key = format!("{INGESTION_KEY_PREFIX}k1_{secret}");
+        assert!(validate_key(org_id, "user@example.com", &key));
+        assert!(!validate_key(org_id, "other@example.com", &key));
+        assert!(!validate_key("other_org", "user@example.com", &key));
+        assert!(!validate_key(
+            org_id,
+            "user@example.com",
+            &format!("{INGESTION_KEY_PREFIX}k1_wrong")
+        ));
+        assert!(!validate_key(org_id, "user@example.com", secret));
+
+        INGESTION_KEYS
+            .get_mut(&format!("{org_id}/k1"))
+            .unwrap()
+            .revoked_at = Some(2);
+        assert!(!validate_key(org_id, "user@example.com", &key));
+    }
+
+    #[test]
+    fn test_is_ip_allowed() {
+        let org_id = "test_ingestion_keys_ip_org";
+        INGESTION_KEYS.insert(
+            format!("{org_id}/k1"),
+            IngestionKey {
+                id: "k1".to_string(),
+                ip_allowlist: vec!["10.0.0.0/8".to_string()],
+                ..Default::default()
+            },
+        );
+        let key = format!("{INGESTION_KEY_PREFIX}k1_secret");
+        assert!(is_ip_allowed(org_id, &key, "10.1.2.3".parse().ok()));
+        assert!(!is_ip_allowed(org_id, &key, "192.168.1.1".parse().ok()));
+        assert!(!is_ip_allowed(org_id, &key, None));
+        assert!(is_ip_allowed(org_id, "password", None));
+    }
+
+    #[test]
+    fn test_parse_ingestion_key() {
+        assert_eq!(
+            parse_ingestion_key("o2ik_abc_secret"),
+            Some(("abc", "secret"))
+        );
+        assert_eq!(parse_ingestion_key("o2ik_abc_"), None);
+        assert_eq!(parse_ingestion_key("o2sa_abc_secret"), None);
+    }
+}
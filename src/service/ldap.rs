@@ -0,0 +1,326 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! LDAP / Active Directory login. The user is searched with the service
+//! account, its password is verified with a bind as the user, and its groups
+//! are mapped to orgs and roles like the OIDC groups.
+
+use std::{collections::HashMap, time::Duration};
+
+use config::{get_config, ider};
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+
+use crate::{
+    common::{
+        meta::{
+            rbac::ROLE_ADMIN,
+            user::{
+                DBUser, TokenValidationResponse, TokenValidationResponseBuilder, UserOrg, UserRole,
+            },
+        },
+        utils::auth::{get_hash, is_root_user},
+    },
+    service::{db, oidc, rbac, users},
+};
+
+/// The directory entry of an authenticated user.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LdapUser {
+    pub dn: String,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub groups: Vec<String>,
+}
+
+/// LDAP is only consulted for users that aren't local, the root user always
+/// logs in with its configured password.
+/// `is_external` is `None` for users that don't exist locally yet.
+pub fn is_ldap_user(user_id: &str, is_external: Option<bool>) -> bool {
+    get_config().ldap.enabled && !is_root_user(user_id) && is_external.unwrap_or(true)
+}
+
+/// Authenticates the user against the directory, creates or updates the
+/// local user and syncs its orgs and roles from the group mapping.
+pub async fn login(
+    username: &str,
+    password: &str,
+) -> Result<TokenValidationResponse, anyhow::Error> {
+    let ldap_user = authenticate(username, password).await?;
+    let cfg = get_config();
+    let orgs = oidc::map_groups(
+        &ldap_user.groups,
+        &oidc::parse_group_mapping(&cfg.ldap.group_mapping),
+        &cfg.ldap.default_org,
+        &cfg.ldap.default_role,
+    );
+    if orgs.is_empty() {
+        return Err(anyhow::anyhow!(
+            "user {} does not match any group mapping",
+            ldap_user.email
+        ));
+    }
+    let db_user = sync_user(&ldap_user, password, &orgs).await?;
+    log::info!("User {} logged in through ldap", db_user.email);
+    Ok(TokenValidationResponseBuilder::from_db_user(&db_user).build())
+}
+
+/// The local password hash of an LDAP user is trusted for `ZO_LDAP_CACHE_TTL`
+/// after the last successful login, later requests go to the directory again.
+pub fn is_cache_valid(verified_at: i64) -> bool {
+    let ttl = get_config().ldap.cache_ttl;
+    chrono::Utc::now().timestamp_micros() - verified_at < ttl * 1_000_000
+}
+
+async fn connect() -> Result<Ldap, anyhow::Error> {
+    let cfg = get_config();
+    let timeout = Duration::from_secs(cfg.ldap.timeout);
+    let settings = LdapConnSettings::new()
+        .set_conn_timeout(timeout)
+        .set_starttls(cfg.ldap.start_tls)
+        .set_no_tls_verify(cfg.ldap.tls_insecure_skip_verify);
+    let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &cfg.ldap.url).await?;
+    ldap3::drive!(conn);
+    ldap.with_timeout(timeout);
+    Ok(ldap)
+}
+
+async fn authenticate(username: &str, password: &str) -> Result<LdapUser, anyhow::Error> {
+    // an empty password is an anonymous bind, which most servers accept
+    if username.is_empty() || password.is_empty() {
+        return Err(anyhow::anyhow!("username and password are required"));
+    }
+    let cfg = get_config();
+    let mut ldap = connect().await?;
+    ldap.simple_bind(&cfg.ldap.bind_dn, &cfg.ldap.bind_password)
+        .await?
+        .success()?;
+
+    let filter = render_filter(&cfg.ldap.user_filter, username, "");
+    let attrs = vec![
+        cfg.ldap.email_attribute.as_str(),
+        cfg.ldap.first_name_attribute.as_str(),
+        cfg.ldap.last_name_attribute.as_str(),
+        cfg.ldap.user_group_attribute.as_str(),
+    ];
+    let (entries, _) = ldap
+        .search(&cfg.ldap.user_base_dn, Scope::Subtree, &filter, attrs)
+        .await?
+        .success()?;
+    if entries.len() != 1 {
+        let _ = ldap.unbind().await;
+        return Err(anyhow::anyhow!(
+            "ldap user search returned {} entries for {username}",
+            entries.len()
+        ));
+    }
+    let entry = SearchEntry::construct(entries.into_iter().next().unwrap());
+
+    let groups = if cfg.ldap.group_base_dn.is_empty() {
+        get_attr_values(&entry.attrs, &cfg.ldap.user_group_attribute)
+            .iter()
+            .map(|dn| get_group_name(dn))
+            .collect::<Vec<_>>()
+    } else {
+        let filter = render_filter(&cfg.ldap.group_filter, username, &entry.dn);
+        let (entries, _) = ldap
+            .search(
+                &cfg.ldap.group_base_dn,
+                Scope::Subtree,
+                &filter,
+                vec![cfg.ldap.group_name_attribute.as_str()],
+            )
+            .await?
+            .success()?;
+        entries
+            .into_iter()
+            .filter_map(|entry| {
+                let entry = SearchEntry::construct(entry);
+                get_attr_values(&entry.attrs, &cfg.ldap.group_name_attribute)
+                    .first()
+                    .cloned()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // verify the password, the connection is bound as the user afterwards
+    let res = ldap.simple_bind(&entry.dn, password).await;
+    let _ = ldap.unbind().await;
+    if res?.success().is_err() {
+        return Err(anyhow::anyhow!("invalid credentials for {username}"));
+    }
+
+    let email = match get_attr_values(&entry.attrs, &cfg.ldap.email_attribute).first() {
+        Some(email) => email.to_lowercase(),
+        None if username.contains('@') => username.to_lowercase(),
+        None => {
+            return Err(anyhow::anyhow!(
+                "ldap user {} has no {} attribute",
+                entry.dn,
+                cfg.ldap.email_attribute
+            ));
+        }
+    };
+    let get_first = |name: &str| {
+        get_attr_values(&entry.attrs, name)
+            .first()
+            .cloned()
+            .unwrap_or_default()
+    };
+    Ok(LdapUser {
+        first_name: get_first(&cfg.ldap.first_name_attribute),
+        last_name: get_first(&cfg.ldap.last_name_attribute),
+        dn: entry.dn.clone(),
+        email,
+        groups,
+    })
+}
+
+// creates the user or refreshes its password hash, names and memberships
+async fn sync_user(
+    ldap_user: &LdapUser,
+    password: &str,
+    orgs: &[(String, String)],
+) -> Result<DBUser, anyhow::Error> {
+    let existing = db::user::get_db_user(&ldap_user.email).await.ok();
+    if existing.as_ref().is_some_and(|user| !user.is_external) {
+        return Err(anyhow::anyhow!(
+            "a local user {} already exists",
+            ldap_user.email
+        ));
+    }
+    let mut db_user = existing.unwrap_or_else(|| DBUser {
+        email: ldap_user.email.clone(),
+        first_name: String::new(),
+        last_name: String::new(),
+        password: String::new(),
+        salt: ider::uuid(),
+        organizations: vec![],
+        is_external: true,
+        password_ext: Some(String::new()),
+        password_updated_at: 0,
+        force_password_reset: false,
+    });
+    db_user.first_name = ldap_user.first_name.clone();
+    db_user.last_name = ldap_user.last_name.clone();
+    db_user.password = get_hash(password, &db_user.salt);
+    db_user.password_ext = Some(get_hash(password, &get_config().auth.ext_auth_salt));
+    // the time of the last successful ldap bind
+    db_user.password_updated_at = chrono::Utc::now().timestamp_micros();
+
+    let removed = db_user
+        .organizations
+        .iter()
+        .filter(|org| !orgs.iter().any(|(name, _)| name.eq(&org.name)))
+        .map(|org| org.name.clone())
+        .collect::<Vec<_>>();
+    // keep the ingestion tokens of the orgs the user stays in
+    let existing_orgs = std::mem::take(&mut db_user.organizations);
+    db_user.organizations = orgs
+        .iter()
+        .map(|(name, role)| {
+            let existing = existing_orgs.iter().find(|org| org.name.eq(name));
+            UserOrg {
+                name: name.to_owned(),
+                role: if role.eq(ROLE_ADMIN) {
+                    UserRole::Admin
+                } else {
+                    UserRole::Member
+                },
+                token: existing.map(|org| org.token.clone()).unwrap_or_default(),
+                rum_token: existing.and_then(|org| org.rum_token.clone()),
+            }
+        })
+        .collect();
+    users::update_db_user(db_user.clone()).await?;
+
+    let email = ldap_user.email.as_str();
+    for org in removed {
+        if let Err(e) = rbac::remove_user_from_roles(&org, email).await {
+            log::error!("Error removing {email} from the roles of org {org}: {e}");
+        }
+    }
+    for (org, role) in orgs {
+        let is_bound = rbac::get_role(org, role).is_some_and(|r| r.users.contains(email));
+        if is_bound {
+            continue;
+        }
+        if let Err(e) = rbac::remove_user_from_roles(org, email).await {
+            log::error!("Error removing {email} from the roles of org {org}: {e}");
+        }
+        if let Err(e) = rbac::add_user_to_role(org, role, email).await {
+            log::error!("Error assigning role {role} in org {org} to {email}: {e}");
+        }
+    }
+    Ok(db_user)
+}
+
+fn render_filter(template: &str, username: &str, dn: &str) -> String {
+    template
+        .replace("{username}", &ldap_escape(username))
+        .replace("{dn}", &ldap_escape(dn))
+}
+
+// attribute names are case insensitive
+fn get_attr_values(attrs: &HashMap<String, Vec<String>>, name: &str) -> Vec<String> {
+    attrs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.clone())
+        .unwrap_or_default()
+}
+
+/// Returns the value of the first RDN of a group DN, e.g. `admins` for
+/// `cn=admins,ou=groups,dc=example,dc=com`.
+pub fn get_group_name(dn: &str) -> String {
+    let rdn = dn.split(',').next().unwrap_or_default();
+    match rdn.split_once('=') {
+        Some((_, name)) => name.trim().to_string(),
+        None => rdn.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_group_name() {
+        assert_eq!(
+            get_group_name("CN=Admins,OU=Groups,DC=example,DC=com"),
+            "Admins"
+        );
+        assert_eq!(get_group_name("cn=sre"), "sre");
+        assert_eq!(get_group_name("developers"), "developers");
+    }
+
+    #[test]
+    fn test_render_filter() {
+        assert_eq!(
+            render_filter("(|(uid={username})(member={dn}))", "a*b", "cn=x,dc=y"),
+            "(|(uid=a\\2ab)(member=cn=x,dc=y))"
+        );
+    }
+
+    #[test]
+    fn test_get_attr_values() {
+        let attrs = HashMap::from([("memberOf".to_string(), vec!["cn=a".to_string()])]);
+        assert_eq!(
+            get_attr_values(&attrs, "memberof"),
+            vec!["cn=a".to_string()]
+        );
+        assert!(get_attr_values(&attrs, "mail").is_empty());
+    }
+}
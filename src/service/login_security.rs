@@ -0,0 +1,276 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::net::IpAddr;
+
+use config::{get_config, RwHashMap};
+use once_cell::sync::Lazy;
+
+use crate::{
+    common::{
+        meta::audit::{AuditAction, AuditEntry, AuditResource},
+        utils::auth::is_root_user,
+    },
+    service::{audit, db},
+};
+
+const SPECIAL_CHARS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Attempts {
+    /// start of the attempt window, in seconds
+    window_start: i64,
+    /// failed logins in the window
+    count: u32,
+    /// the login is rejected until then, in seconds
+    locked_until: i64,
+}
+
+// key: `user:{email}` or `ip:{ip}`, failed logins are counted per node
+static ATTEMPTS: Lazy<RwHashMap<String, Attempts>> = Lazy::new(Default::default);
+
+fn get_keys(user_id: &str, ip: Option<IpAddr>) -> Vec<(String, u32)> {
+    let cfg = get_config();
+    let mut keys = vec![(
+        format!("user:{}", user_id.to_lowercase()),
+        cfg.auth.login_max_attempts_per_user,
+    )];
+    if let Some(ip) = ip {
+        keys.push((format!("ip:{ip}"), cfg.auth.login_max_attempts_per_ip));
+    }
+    keys.retain(|(_, max)| *max > 0);
+    keys
+}
+
+/// Returns the seconds the client has to wait before the next login when
+/// the user or the client ip is locked out.
+pub fn check(user_id: &str, ip: Option<IpAddr>) -> Option<i64> {
+    let now = chrono::Utc::now().timestamp();
+    get_keys(user_id, ip)
+        .iter()
+        .filter_map(|(key, _)| ATTEMPTS.get(key).map(|v| v.locked_until - now))
+        .filter(|retry_after| *retry_after > 0)
+        .max()
+}
+
+/// Counts a failed login against the user and the client ip, returns true
+/// when it locks one of them out.
+pub async fn record_failure(user_id: &str, ip: Option<IpAddr>, path: &str) -> bool {
+    let cfg = get_config();
+    let now = chrono::Utc::now().timestamp();
+    let mut locked = false;
+    for (key, max) in get_keys(user_id, ip) {
+        let mut attempts = ATTEMPTS.entry(key).or_default();
+        locked |= count_failure(
+            &mut attempts,
+            now,
+            max,
+            cfg.auth.login_attempt_window,
+            cfg.auth.login_lockout_duration,
+        );
+    }
+    if locked {
+        log::warn!("Login locked for user: {user_id}, ip: {ip:?}");
+        audit_event(user_id, path, AuditAction::LoginLocked, 429).await;
+    } else {
+        audit_event(user_id, path, AuditAction::LoginFailed, 401).await;
+    }
+    locked
+}
+
+/// Clears the failed logins of the user, the counter of the client ip is
+/// kept so that one valid account can't be used to reset it.
+pub fn record_success(user_id: &str) {
+    ATTEMPTS.remove(&format!("user:{}", user_id.to_lowercase()));
+}
+
+fn count_failure(attempts: &mut Attempts, now: i64, max: u32, window: i64, lockout: i64) -> bool {
+    if now - attempts.window_start >= window {
+        attempts.window_start = now;
+        attempts.count = 0;
+    }
+    attempts.count += 1;
+    if attempts.count < max {
+        return false;
+    }
+    attempts.locked_until = now + lockout;
+    attempts.window_start = now;
+    attempts.count = 0;
+    true
+}
+
+/// Checks a new password against the `ZO_PASSWORD_*` complexity settings.
+pub fn check_password_policy(password: &str) -> Result<(), String> {
+    let cfg = get_config();
+    let mut missing = vec![];
+    if password.chars().count() < cfg.auth.password_min_length {
+        missing.push(format!(
+            "at least {} characters",
+            cfg.auth.password_min_length
+        ));
+    }
+    let rules: [(bool, fn(char) -> bool, &str); 4] = [
+        (
+            cfg.auth.password_require_uppercase,
+            |c| c.is_uppercase(),
+            "an uppercase letter",
+        ),
+        (
+            cfg.auth.password_require_lowercase,
+            |c| c.is_lowercase(),
+            "a lowercase letter",
+        ),
+        (
+            cfg.auth.password_require_digit,
+            |c| c.is_ascii_digit(),
+            "a digit",
+        ),
+        (
+            cfg.auth.password_require_special,
+            |c| SPECIAL_CHARS.contains(c),
+            "a special character",
+        ),
+    ];
+    for (required, is_match, name) in rules {
+        if required && !password.chars().any(is_match) {
+            missing.push(name.to_string());
+        }
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Password must contain {}", missing.join(", ")))
+    }
+}
+
+/// Local users have to change the password when an admin asked for it or
+/// when it is older than `ZO_PASSWORD_EXPIRY_DAYS`, the root user and
+/// external users are exempt.
+pub fn is_reset_required(
+    email: &str,
+    is_external: bool,
+    force_password_reset: bool,
+    password_updated_at: i64,
+) -> bool {
+    if is_external || is_root_user(email) {
+        return false;
+    }
+    force_password_reset
+        || is_expired(
+            password_updated_at,
+            chrono::Utc::now().timestamp_micros(),
+            get_config().auth.password_expiry_days,
+        )
+}
+
+fn is_expired(password_updated_at: i64, now: i64, expiry_days: i64) -> bool {
+    // users created before the policy have no timestamp, they aren't expired
+    if expiry_days <= 0 || password_updated_at <= 0 {
+        return false;
+    }
+    now - password_updated_at
+        > chrono::Duration::days(expiry_days)
+            .num_microseconds()
+            .unwrap()
+}
+
+/// Writes a login event to the audit stream of every organization of the
+/// user, events of unknown users go to the meta organization.
+pub async fn audit_event(user_id: &str, path: &str, action: AuditAction, response_code: u16) {
+    let cfg = get_config();
+    if !cfg.common.audit_enabled {
+        return;
+    }
+    let mut orgs = db::user::get_db_user(user_id)
+        .await
+        .map(|user| {
+            user.organizations
+                .into_iter()
+                .map(|org| org.name)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if orgs.is_empty() {
+        orgs.push(cfg.common.usage_org.clone());
+    }
+    let timestamp = chrono::Utc::now().timestamp_micros();
+    for org_id in orgs {
+        audit::audit(AuditEntry {
+            _timestamp: timestamp,
+            org_id,
+            user_email: user_id.to_string(),
+            method: "POST".to_string(),
+            path: path.to_string(),
+            resource: AuditResource::User.to_string(),
+            resource_id: user_id.to_string(),
+            action: action.to_string(),
+            response_code,
+            ..Default::default()
+        })
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_failure() {
+        let mut attempts = Attempts::default();
+        assert!(!count_failure(&mut attempts, 100, 3, 60, 900));
+        assert!(!count_failure(&mut attempts, 110, 3, 60, 900));
+        assert!(count_failure(&mut attempts, 120, 3, 60, 900));
+        assert_eq!(attempts.locked_until, 1020);
+        assert_eq!(attempts.count, 0);
+
+        // failures outside the window start a new one
+        let mut attempts = Attempts::default();
+        assert!(!count_failure(&mut attempts, 100, 2, 60, 900));
+        assert!(!count_failure(&mut attempts, 200, 2, 60, 900));
+        assert_eq!(attempts.locked_until, 0);
+    }
+
+    #[test]
+    fn test_check_lockout() {
+        let user_id = "lockout@example.com";
+        assert_eq!(check(user_id, None), None);
+        ATTEMPTS.insert(
+            format!("user:{user_id}"),
+            Attempts {
+                locked_until: chrono::Utc::now().timestamp() + 60,
+                ..Default::default()
+            },
+        );
+        assert!(check(user_id, None).unwrap() > 0);
+        record_success(user_id);
+        assert_eq!(check(user_id, None), None);
+    }
+
+    #[test]
+    fn test_check_password_policy() {
+        assert!(check_password_policy("Complexpass#123").is_ok());
+        assert!(check_password_policy("short").is_err());
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let day = 86_400_000_000;
+        assert!(!is_expired(0, 100 * day, 30));
+        assert!(!is_expired(day, 100 * day, 0));
+        assert!(!is_expired(day, 20 * day, 30));
+        assert!(is_expired(day, 40 * day, 30));
+    }
+}
@@ -0,0 +1,370 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Built-in parsers of the AWS log formats, the `log_format` of the stream
+//! settings picks one. CloudTrail events come as json, alone or in the
+//! `Records` of a delivered file, the lines of VPC Flow Logs and ALB access
+//! logs in the `message` field of the raw and kinesis firehose records. Both
+//! are mapped into typed fields without a function per stream.
+
+use anyhow::Result;
+use config::{get_config, meta::stream::LogFormat, utils::json};
+
+use super::raw::MESSAGE_FIELD;
+
+/// Fields of the VPC Flow Logs default format, version 2.
+const VPC_FLOW_FIELDS: [&str; 14] = [
+    "version",
+    "account_id",
+    "interface_id",
+    "srcaddr",
+    "dstaddr",
+    "srcport",
+    "dstport",
+    "protocol",
+    "packets",
+    "bytes",
+    "start",
+    "end",
+    "action",
+    "log_status",
+];
+
+const VPC_FLOW_NUMBERS: [&str; 8] = [
+    "version", "srcport", "dstport", "protocol", "packets", "bytes", "start", "end",
+];
+
+/// Fields of the ALB access logs, the older logs leave out the last ones.
+const ALB_FIELDS: [&str; 29] = [
+    "type",
+    "time",
+    "elb",
+    "client:port",
+    "target:port",
+    "request_processing_time",
+    "target_processing_time",
+    "response_processing_time",
+    "elb_status_code",
+    "target_status_code",
+    "received_bytes",
+    "sent_bytes",
+    "request",
+    "user_agent",
+    "ssl_cipher",
+    "ssl_protocol",
+    "target_group_arn",
+    "trace_id",
+    "domain_name",
+    "chosen_cert_arn",
+    "matched_rule_priority",
+    "request_creation_time",
+    "actions_executed",
+    "redirect_url",
+    "error_reason",
+    "target:port_list",
+    "target_status_code_list",
+    "classification",
+    "classification_reason",
+];
+
+const ALB_NUMBERS: [&str; 8] = [
+    "request_processing_time",
+    "target_processing_time",
+    "response_processing_time",
+    "elb_status_code",
+    "target_status_code",
+    "received_bytes",
+    "sent_bytes",
+    "matched_rule_priority",
+];
+
+/// The fields of an ALB line up to the request.
+const ALB_MIN_FIELDS: usize = 13;
+
+/// Parses a record into the records of the format, a CloudTrail file holds
+/// many events and a VPC Flow Logs header line none.
+pub fn parse(format: LogFormat, record: json::Value) -> Result<Vec<json::Value>> {
+    let json::Value::Object(mut record) = record else {
+        return Err(anyhow::anyhow!("{format:?} record is not an object"));
+    };
+    if format == LogFormat::CloudTrail {
+        return parse_cloudtrail(record);
+    }
+
+    let Some(json::Value::String(line)) = record.remove(MESSAGE_FIELD) else {
+        return Err(anyhow::anyhow!(
+            "{format:?} record has no {MESSAGE_FIELD} line"
+        ));
+    };
+    let fields = match format {
+        LogFormat::VpcFlow => parse_vpc_flow(&line)?,
+        LogFormat::Alb => parse_alb(&line)?,
+        _ => return Err(anyhow::anyhow!("{format:?} is not an AWS log format")),
+    };
+    Ok(match fields {
+        Some(fields) => {
+            record.extend(fields);
+            vec![json::Value::Object(record)]
+        }
+        None => vec![],
+    })
+}
+
+fn parse_cloudtrail(mut record: json::Map<String, json::Value>) -> Result<Vec<json::Value>> {
+    // the record is the event, or holds it in the message when it came
+    // through cloudwatch logs
+    let mut event = match record.remove(MESSAGE_FIELD) {
+        Some(json::Value::Object(event)) => event,
+        Some(json::Value::String(v)) if v.starts_with('{') => json::from_str(&v)?,
+        Some(v) => {
+            record.insert(MESSAGE_FIELD.to_string(), v);
+            std::mem::take(&mut record)
+        }
+        None => std::mem::take(&mut record),
+    };
+    let events = match event.remove("Records") {
+        Some(json::Value::Array(events)) => events,
+        Some(v) => {
+            event.insert("Records".to_string(), v);
+            vec![json::Value::Object(event)]
+        }
+        None => vec![json::Value::Object(event)],
+    };
+
+    let column = &get_config().common.column_timestamp;
+    events
+        .into_iter()
+        .map(|event| {
+            let json::Value::Object(event) = event else {
+                return Err(anyhow::anyhow!("CloudTrail event is not an object"));
+            };
+            let mut fields = record.clone();
+            fields.extend(event);
+            if let Some(time) = fields.get("eventTime").and_then(|v| v.as_str()) {
+                let time = parse_time(time)?;
+                fields.insert(column.clone(), time.into());
+            }
+            Ok(json::Value::Object(fields))
+        })
+        .collect()
+}
+
+fn parse_vpc_flow(line: &str) -> Result<Option<json::Map<String, json::Value>>> {
+    let values = line.split_whitespace().collect::<Vec<_>>();
+    // the files delivered to s3 start with a header line
+    if values.first() == Some(&"version") {
+        return Ok(None);
+    }
+    if values.len() < VPC_FLOW_FIELDS.len() {
+        return Err(anyhow::anyhow!(
+            "VPC Flow Logs line has {} fields, expected {}",
+            values.len(),
+            VPC_FLOW_FIELDS.len()
+        ));
+    }
+
+    let mut fields = json::Map::new();
+    for (name, value) in VPC_FLOW_FIELDS.iter().zip(values) {
+        if let Some(value) = typed_value(value, VPC_FLOW_NUMBERS.contains(name)) {
+            fields.insert(name.to_string(), value);
+        }
+    }
+    if let Some(start) = fields.get("start").and_then(|v| v.as_i64()) {
+        fields.insert(
+            get_config().common.column_timestamp.clone(),
+            (start * 1_000_000).into(),
+        );
+    }
+    Ok(Some(fields))
+}
+
+fn parse_alb(line: &str) -> Result<Option<json::Map<String, json::Value>>> {
+    let values = split_quoted(line);
+    if values.len() < ALB_MIN_FIELDS {
+        return Err(anyhow::anyhow!(
+            "ALB access log line has {} fields, expected at least {ALB_MIN_FIELDS}",
+            values.len()
+        ));
+    }
+
+    let mut fields = json::Map::new();
+    for (name, value) in ALB_FIELDS.iter().zip(values) {
+        match *name {
+            "client:port" | "target:port" => {
+                let prefix = name.trim_end_matches(":port");
+                match value.rsplit_once(':') {
+                    Some((ip, port)) => {
+                        fields.insert(format!("{prefix}_ip"), ip.into());
+                        if let Some(port) = typed_value(port, true) {
+                            fields.insert(format!("{prefix}_port"), port);
+                        }
+                    }
+                    None => {
+                        if let Some(value) = typed_value(value, false) {
+                            fields.insert(format!("{prefix}_ip"), value);
+                        }
+                    }
+                }
+            }
+            "request" => {
+                fields.insert(name.to_string(), value.into());
+                let mut parts = value.splitn(3, ' ');
+                for part in ["request_verb", "request_url", "request_proto"] {
+                    if let Some(value) = parts.next().and_then(|v| typed_value(v, false)) {
+                        fields.insert(part.to_string(), value);
+                    }
+                }
+            }
+            _ => {
+                if let Some(value) = typed_value(value, ALB_NUMBERS.contains(name)) {
+                    fields.insert(name.replace(':', "_"), value);
+                }
+            }
+        }
+    }
+    if let Some(time) = fields.get("time").and_then(|v| v.as_str()) {
+        let time = parse_time(time)?;
+        fields.insert(get_config().common.column_timestamp.clone(), time.into());
+    }
+    Ok(Some(fields))
+}
+
+/// Splits a line on the spaces, the double quoted values keep theirs.
+fn split_quoted(line: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        let (value, next) = match rest.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => match rest.find(' ') {
+                Some(end) => (&rest[..end], &rest[end..]),
+                None => (rest, ""),
+            },
+        };
+        values.push(value);
+        rest = next.trim_start();
+    }
+    values
+}
+
+/// The value of a field, `-` stands for no value.
+fn typed_value(value: &str, number: bool) -> Option<json::Value> {
+    if value == "-" || value.is_empty() {
+        return None;
+    }
+    if number {
+        if let Ok(v) = value.parse::<i64>() {
+            return Some(v.into());
+        }
+        if let Some(v) = value.parse::<f64>().ok().and_then(json::Number::from_f64) {
+            return Some(json::Value::Number(v));
+        }
+    }
+    Some(value.into())
+}
+
+fn parse_time(time: &str) -> Result<i64> {
+    Ok(chrono::DateTime::parse_from_rfc3339(time)?.timestamp_micros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cloudtrail() {
+        let file = json::json!({"Records": [
+            {
+                "eventTime": "2024-05-01T10:00:00Z",
+                "eventName": "GetObject",
+                "userIdentity": {"type": "IAMUser"},
+            },
+            {"eventTime": "2024-05-01T10:00:01Z", "eventName": "PutObject"},
+        ]});
+        let events = parse(LogFormat::CloudTrail, file).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["eventName"], "GetObject");
+        assert_eq!(events[0]["userIdentity"]["type"], "IAMUser");
+        assert_eq!(events[1]["_timestamp"], 1714557601000000i64);
+
+        // an event of cloudwatch logs keeps the fields of the record
+        let record = json::json!({
+            "logGroup": "trail",
+            "message": {"eventTime": "2024-05-01T10:00:00Z", "eventName": "GetObject"},
+        });
+        let events = parse(LogFormat::CloudTrail, record).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["logGroup"], "trail");
+        assert_eq!(events[0]["eventName"], "GetObject");
+    }
+
+    #[test]
+    fn test_parse_vpc_flow() {
+        let header = "version account-id interface-id srcaddr dstaddr srcport dstport protocol packets bytes start end action log-status";
+        let records = parse(LogFormat::VpcFlow, json::json!({"message": header})).unwrap();
+        assert!(records.is_empty());
+
+        let line = "2 123456789010 eni-1235b8ca123456789 172.31.16.139 172.31.16.21 20641 22 6 20 4249 1418530010 1418530070 ACCEPT OK";
+        let records = parse(LogFormat::VpcFlow, json::json!({"message": line})).unwrap();
+        let record = &records[0];
+        assert_eq!(record["account_id"], "123456789010");
+        assert_eq!(record["srcaddr"], "172.31.16.139");
+        assert_eq!(record["dstport"], 22);
+        assert_eq!(record["bytes"], 4249);
+        assert_eq!(record["action"], "ACCEPT");
+        assert_eq!(record["_timestamp"], 1418530010000000i64);
+        assert!(record.get("message").is_none());
+
+        let nodata =
+            "2 123456789010 eni-1235b8ca123456789 - - - - - - - 1431280876 1431280934 - NODATA";
+        let records = parse(LogFormat::VpcFlow, json::json!({"message": nodata})).unwrap();
+        assert!(records[0].get("srcaddr").is_none());
+        assert_eq!(records[0]["log_status"], "NODATA");
+
+        assert!(parse(LogFormat::VpcFlow, json::json!({"message": "2 123"})).is_err());
+    }
+
+    #[test]
+    fn test_parse_alb() {
+        let line = r#"http 2018-07-02T22:23:00.186641Z app/my-loadbalancer/50dc6c495c0c9188 192.168.131.39:2817 10.0.0.1:80 0.000 0.001 0.000 200 200 34 366 "GET http://www.example.com:80/ HTTP/1.1" "curl/7.46.0" - - arn:aws:elasticloadbalancing:us-east-2:123456789012:targetgroup/my-targets/73e2d6bc24d8a067 "Root=1-58337262-36d228ad5d99923122bbe354" "-" "-" 0 2018-07-02T22:22:48.364000Z "forward" "-" "-" "10.0.0.1:80" "200" "-" "-""#;
+        let records = parse(LogFormat::Alb, json::json!({"message": line})).unwrap();
+        let record = &records[0];
+        assert_eq!(record["type"], "http");
+        assert_eq!(record["client_ip"], "192.168.131.39");
+        assert_eq!(record["client_port"], 2817);
+        assert_eq!(record["target_ip"], "10.0.0.1");
+        assert_eq!(record["target_processing_time"], 0.001);
+        assert_eq!(record["elb_status_code"], 200);
+        assert_eq!(record["sent_bytes"], 366);
+        assert_eq!(record["request_verb"], "GET");
+        assert_eq!(record["request_url"], "http://www.example.com:80/");
+        assert_eq!(record["user_agent"], "curl/7.46.0");
+        assert_eq!(record["actions_executed"], "forward");
+        assert_eq!(record["target_port_list"], "10.0.0.1:80");
+        assert!(record.get("ssl_cipher").is_none());
+        assert_eq!(record["_timestamp"], 1530570180186641i64);
+    }
+
+    #[test]
+    fn test_split_quoted() {
+        assert_eq!(
+            split_quoted(r#"a "b c" - "" d"#),
+            vec!["a", "b c", "-", "", "d"]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Backfill jobs ingest large batches of historical records in the
+//! background. The jobs of a node run one at a time by default, in batches
+//! paced at `ZO_BACKFILL_RECORDS_PER_SEC`, and wait while the memtable is
+//! full, so that a migration doesn't starve the live ingestion.
+
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use config::{get_config, ider, utils::json};
+use once_cell::sync::Lazy;
+use tokio::sync::Semaphore;
+
+use crate::{
+    common::meta::ingestion::{BackfillJob, BackfillStatus, IngestionRequest},
+    service::{db, ingestion::check_ingestion_allowed},
+};
+
+static RUNNING: Lazy<Semaphore> =
+    Lazy::new(|| Semaphore::new(get_config().limit.backfill_concurrency.max(1)));
+
+/// Creates a job ingesting the records, a json array or ndjson, into the
+/// stream.
+pub async fn submit(
+    org_id: &str,
+    stream_name: &str,
+    body: &[u8],
+    user_email: &str,
+) -> Result<BackfillJob, anyhow::Error> {
+    check_ingestion_allowed(org_id, Some(stream_name))?;
+    let records = parse_records(body)?;
+    if records.is_empty() {
+        return Err(anyhow::anyhow!("no records to backfill"));
+    }
+    let now = Utc::now().timestamp_micros();
+    let job = BackfillJob {
+        id: ider::uuid(),
+        org_id: org_id.to_string(),
+        stream_name: stream_name.to_string(),
+        node: get_config().common.instance_name.clone(),
+        total: records.len(),
+        created_at: now,
+        updated_at: now,
+        ..Default::default()
+    };
+    db::backfill::set(&job).await?;
+    tokio::task::spawn(run(job.clone(), records, user_email.to_string()));
+    Ok(job)
+}
+
+pub async fn get(org_id: &str, id: &str) -> Result<Option<BackfillJob>, anyhow::Error> {
+    db::backfill::get(org_id, id).await
+}
+
+/// The jobs of the organization, the latest first.
+pub async fn list(org_id: &str) -> Result<Vec<BackfillJob>, anyhow::Error> {
+    let mut jobs = db::backfill::list(org_id).await?;
+    jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(jobs)
+}
+
+/// Fails the jobs this node was running when it stopped, their records were
+/// only kept in memory.
+pub async fn fail_interrupted() -> Result<(), anyhow::Error> {
+    let node = &get_config().common.instance_name;
+    for mut job in db::backfill::list_all().await? {
+        if job.node.ne(node)
+            || !matches!(
+                job.status,
+                BackfillStatus::Pending | BackfillStatus::Running
+            )
+        {
+            continue;
+        }
+        log::warn!(
+            "[BACKFILL] job {} of {}/{} was interrupted by a restart, processed: {}/{}",
+            job.id,
+            job.org_id,
+            job.stream_name,
+            job.processed,
+            job.total
+        );
+        job.status = BackfillStatus::Failed;
+        job.error = Some("the job was interrupted by a restart of the node".to_string());
+        save(&mut job).await;
+    }
+    Ok(())
+}
+
+async fn run(mut job: BackfillJob, records: Vec<json::Value>, user_email: String) {
+    let _permit = RUNNING.acquire().await.unwrap();
+    job.status = BackfillStatus::Running;
+    save(&mut job).await;
+
+    let cfg = get_config();
+    for batch in records.chunks(cfg.limit.backfill_batch_records.max(1)) {
+        let start = Instant::now();
+        let batch = batch.to_vec();
+        loop {
+            match super::ingest::ingest(
+                &job.org_id,
+                &job.stream_name,
+                IngestionRequest::Backfill(&batch),
+                0,
+                &user_email,
+            )
+            .await
+            {
+                // the memtable is full, the live ingestion goes first
+                Ok(resp) if resp.code == 503 => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                Ok(resp) => {
+                    for stream in resp.status {
+                        job.successful += stream.status.successful as usize;
+                        job.failed += stream.status.failed as usize;
+                        if !stream.status.error.is_empty() {
+                            job.error = Some(stream.status.error);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::error!(
+                        "[BACKFILL] job {} of {}/{} failed a batch: {e}",
+                        job.id,
+                        job.org_id,
+                        job.stream_name
+                    );
+                    job.failed += batch.len();
+                    job.error = Some(e.to_string());
+                }
+            }
+            break;
+        }
+        job.processed += batch.len();
+        save(&mut job).await;
+        let pace = pace(batch.len(), cfg.limit.backfill_records_per_sec);
+        if let Some(wait) = pace.checked_sub(start.elapsed()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    job.status = if job.successful == 0 {
+        BackfillStatus::Failed
+    } else {
+        BackfillStatus::Completed
+    };
+    save(&mut job).await;
+    log::info!(
+        "[BACKFILL] job {} of {}/{} done, successful: {}, failed: {}",
+        job.id,
+        job.org_id,
+        job.stream_name,
+        job.successful,
+        job.failed
+    );
+}
+
+async fn save(job: &mut BackfillJob) {
+    job.updated_at = Utc::now().timestamp_micros();
+    if let Err(e) = db::backfill::set(job).await {
+        log::error!("[BACKFILL] save job {} error: {e}", job.id);
+    }
+}
+
+/// The time a batch of records takes at the rate, 0 is unlimited.
+fn pace(records: usize, per_sec: usize) -> Duration {
+    if per_sec == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(records as f64 / per_sec as f64)
+}
+
+/// A json array, a json object or ndjson.
+fn parse_records(body: &[u8]) -> Result<Vec<json::Value>, anyhow::Error> {
+    if let Ok(value) = json::from_slice::<json::Value>(body) {
+        return Ok(match value {
+            json::Value::Array(records) => records,
+            value => vec![value],
+        });
+    }
+    body.split(|b| *b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|line| json::from_slice(line).map_err(|e| anyhow::anyhow!("invalid record: {e}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let records = parse_records(br#"[{"a": 1}, {"a": 2}]"#).unwrap();
+        assert_eq!(records.len(), 2);
+        let records = parse_records(br#"{"a": 1}"#).unwrap();
+        assert_eq!(records, vec![json::json!({"a": 1})]);
+        let records = parse_records(b"{\"a\": 1}\n\n{\"a\": 2}\r\n").unwrap();
+        assert_eq!(records, vec![json::json!({"a": 1}), json::json!({"a": 2})]);
+        assert!(parse_records(b"{\"a\": 1}\nnot json").is_err());
+    }
+
+    #[test]
+    fn test_pace() {
+        assert_eq!(pace(1000, 0), Duration::ZERO);
+        assert_eq!(pace(1000, 5000), Duration::from_millis(200));
+        assert_eq!(pace(5000, 1000), Duration::from_secs(5));
+    }
+}
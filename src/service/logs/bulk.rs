@@ -47,7 +47,11 @@ use crate::{
     },
     service::{
         db, format_stream_name,
-        ingestion::{evaluate_trigger, write_file, TriggerAlertData},
+        ingestion::{
+            evaluate_trigger,
+            pipeline::{get_stream_pipeline, StreamPipeline},
+            write_file, TriggerAlertData,
+        },
         metadata::{distinct_values::DvItem, write, MetadataItem, MetadataType},
         schema::{get_upto_discard_error, stream_schema_exists},
         usage::report_request_usage_stats,
@@ -58,6 +62,7 @@ pub const TRANSFORM_FAILED: &str = "document_failed_transform";
 pub const TS_PARSE_FAILED: &str = "timestamp_parsing_failed";
 pub const SCHEMA_CONFORMANCE_FAILED: &str = "schema_conformance_failed";
 
+#[tracing::instrument(name = "service:logs:bulk:ingest", skip_all, fields(org_id = org_id))]
 pub async fn ingest(
     org_id: &str,
     body: web::Bytes,
@@ -69,9 +74,7 @@ pub async fn ingest(
         return Err(anyhow::anyhow!("not an ingester"));
     }
 
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if crate::service::ingestion::is_org_quota_exceeded(org_id) {
         return Err(anyhow::anyhow!(
             "Quota exceeded for this organization [{}]",
             org_id
@@ -110,8 +113,12 @@ pub async fn ingest(
     let mut stream_trigger_map: HashMap<String, Option<TriggerAlertData>> = HashMap::new();
 
     let mut blocked_stream_warnings: HashMap<String, bool> = HashMap::new();
+    #[cfg(not(feature = "enterprise"))]
+    let mut denied_stream_warnings: HashMap<String, bool> = HashMap::new();
 
     let mut stream_routing_map: HashMap<String, Vec<Routing>> = HashMap::new();
+    let mut stream_pipeline_map: HashMap<String, Option<StreamPipeline>> = HashMap::new();
+    let mut routed_records: HashMap<(String, String), Vec<json::Value>> = HashMap::new();
 
     let mut user_defined_schema_map: HashMap<String, HashSet<String>> = HashMap::new();
 
@@ -148,6 +155,22 @@ pub async fn ingest(
                 continue; // skip
             }
 
+            // skip streams the user is not allowed to write
+            #[cfg(not(feature = "enterprise"))]
+            if !crate::service::rbac::is_allowed(
+                org_id,
+                user_email,
+                crate::common::meta::rbac::Resource::Stream,
+                &stream_name,
+                crate::common::meta::rbac::Permission::Write,
+            ) {
+                denied_stream_warnings.entry(stream_name.clone()).or_insert_with(|| {
+                    log::warn!("user [{user_email}] is not allowed to ingest into stream [{stream_name}]");
+                    true
+                });
+                continue; // skip
+            }
+
             // Start get routing keys
             crate::service::ingestion::get_stream_routing(
                 StreamParams {
@@ -174,6 +197,18 @@ pub async fn ingest(
                     });
                 }
             }
+            let pipeline = stream_pipeline_map
+                .entry(stream_name.clone())
+                .or_insert_with(|| get_stream_pipeline(org_id, StreamType::Logs, &stream_name));
+            if let Some(pipeline) = pipeline {
+                for destination in pipeline.destinations() {
+                    streams.push(StreamParams {
+                        org_id: org_id.to_owned().into(),
+                        stream_type: StreamType::Logs,
+                        stream_name: destination.to_string().into(),
+                    });
+                }
+            }
             // End get stream keys
 
             crate::service::ingestion::get_user_defined_schema(
@@ -228,171 +263,142 @@ pub async fn ingest(
             next_line_is_data = false;
 
             // JSON Flattening
-            let mut value = flatten::flatten_with_level(value, cfg.limit.ingest_flatten_level)?;
-
-            if let Some(routing) = stream_routing_map.get(&stream_name) {
-                if !routing.is_empty() {
-                    for route in routing {
-                        let mut is_routed = true;
-                        let val = &route.routing;
-                        for q_condition in val.iter() {
-                            is_routed =
-                                is_routed && q_condition.evaluate(value.as_object().unwrap()).await;
-                        }
-                        if is_routed && !val.is_empty() {
-                            stream_name = route.destination.clone();
-                            if !stream_data_map.contains_key(&stream_name) {
-                                stream_data_map.insert(
-                                    stream_name.clone(),
-                                    BulkStreamData {
-                                        data: HashMap::new(),
-                                    },
-                                );
-                            }
-                            break;
-                        }
-                    }
-                }
-            }
-
-            let stream_data = stream_data_map.get_mut(&stream_name).unwrap();
-            let buf = &mut stream_data.data;
-
-            let key = format!("{org_id}/{}/{stream_name}", StreamType::Logs);
+            let value = flatten::flatten_with_level(value, cfg.limit.ingest_flatten_level)?;
 
-            // Start row based transform
-            if let Some(transforms) = stream_functions_map.get(&key) {
-                if !transforms.is_empty() {
-                    let mut ret_value = value.clone();
-                    ret_value = crate::service::ingestion::apply_stream_functions(
-                        transforms,
-                        ret_value,
-                        &stream_vrl_map,
-                        org_id,
-                        &stream_name,
-                        &mut runtime,
-                    )?;
-
-                    if ret_value.is_null() || !ret_value.is_object() {
-                        bulk_res.errors = true;
-                        add_record_status(
-                            stream_name.clone(),
-                            doc_id.clone(),
-                            action.clone(),
-                            Some(value),
-                            &mut bulk_res,
-                            Some(TRANSFORM_FAILED.to_owned()),
-                            Some(TRANSFORM_FAILED.to_owned()),
-                        );
-                        continue;
-                    } else {
-                        value = ret_value;
-                    }
-                }
-            }
-            // End row based transform
-
-            // get json object
-            let mut local_val = match value.take() {
-                json::Value::Object(v) => v,
-                _ => unreachable!(),
-            };
-
-            if let Some(fields) = user_defined_schema_map.get(&stream_name) {
-                local_val = crate::service::logs::refactor_map(local_val, fields);
-            }
-
-            // set _id
-            if !doc_id.is_empty() {
-                local_val.insert("_id".to_string(), json::Value::String(doc_id.clone()));
-            }
-
-            // handle timestamp
-            let timestamp = match local_val.get(&cfg.common.column_timestamp) {
-                Some(v) => match parse_timestamp_micro_from_value(v) {
-                    Ok(t) => t,
-                    Err(_e) => {
-                        bulk_res.errors = true;
-                        add_record_status(
-                            stream_name.clone(),
-                            doc_id.clone(),
-                            action.clone(),
-                            Some(value),
-                            &mut bulk_res,
-                            Some(TS_PARSE_FAILED.to_string()),
-                            Some(TS_PARSE_FAILED.to_string()),
-                        );
-                        continue;
-                    }
-                },
-                None => Utc::now().timestamp_micros(),
+            // the pipeline steps may route the record or copies of it to other streams
+            let outputs = match stream_pipeline_map.get(&stream_name) {
+                Some(Some(pipeline)) => pipeline.execute(&mut runtime, org_id, value).await,
+                _ => vec![(stream_name.clone(), value)],
             };
-            // check ingestion time
-            if timestamp < min_ts {
+            if outputs.is_empty() {
                 bulk_res.errors = true;
-                let failure_reason = Some(get_upto_discard_error().to_string());
                 add_record_status(
                     stream_name.clone(),
                     doc_id.clone(),
                     action.clone(),
-                    Some(value),
+                    None,
                     &mut bulk_res,
-                    Some(TS_PARSE_FAILED.to_string()),
-                    failure_reason,
+                    Some(TRANSFORM_FAILED.to_owned()),
+                    Some(TRANSFORM_FAILED.to_owned()),
                 );
-                continue;
             }
-            local_val.insert(
-                cfg.common.column_timestamp.clone(),
-                json::Value::Number(timestamp.into()),
-            );
-            let (partition_keys, partition_time_level) =
-                match stream_partition_keys_map.get(&stream_name) {
-                    Some((_, partition_det)) => (
-                        partition_det.partition_keys.clone(),
-                        partition_det.partition_time_level,
-                    ),
-                    None => (vec![], None),
-                };
+            for output in outputs {
+                if let Some(dest_org_id) = output.org_id {
+                    routed_records
+                        .entry((dest_org_id, output.stream_name))
+                        .or_default()
+                        .push(output.value);
+                    continue;
+                }
+                let mut stream_name = output.stream_name;
+                let mut value = output.value;
+                stream_data_map
+                    .entry(stream_name.clone())
+                    .or_insert_with(|| BulkStreamData {
+                        data: HashMap::new(),
+                    });
 
-            // only for bulk insert
-            let mut status = RecordStatus::default();
-            let need_trigger = !stream_trigger_map.contains_key(&stream_name);
-
-            let mut to_add_distinct_values = vec![];
-            // get distinct_value items
-            for field in DISTINCT_FIELDS.iter() {
-                if let Some(val) = local_val.get(field) {
-                    if !val.is_null() {
-                        to_add_distinct_values.push(MetadataItem::DistinctValues(DvItem {
-                            stream_type: StreamType::Logs,
-                            stream_name: stream_name.clone(),
-                            field_name: field.to_string(),
-                            field_value: val.as_str().unwrap().to_string(),
-                            filter_name: "".to_string(),
-                            filter_value: "".to_string(),
-                        }));
+                if let Some(routing) = stream_routing_map.get(&stream_name) {
+                    if !routing.is_empty() {
+                        for route in routing {
+                            let mut is_routed = true;
+                            let val = &route.routing;
+                            for q_condition in val.iter() {
+                                is_routed = is_routed
+                                    && q_condition.evaluate(value.as_object().unwrap()).await;
+                            }
+                            if is_routed && !val.is_empty() {
+                                stream_name = route.destination.clone();
+                                if !stream_data_map.contains_key(&stream_name) {
+                                    stream_data_map.insert(
+                                        stream_name.clone(),
+                                        BulkStreamData {
+                                            data: HashMap::new(),
+                                        },
+                                    );
+                                }
+                                break;
+                            }
+                        }
                     }
                 }
-            }
 
-            // this is for schema inference at stream level , which avoids locks in case schema
-            // changes are frequent within request
-            if cfg.common.infer_schema_per_request {
-                if let Err(e) = add_record(
-                    &StreamMeta {
-                        org_id: org_id.to_string(),
-                        stream_name: stream_name.clone(),
-                        partition_keys: &partition_keys,
-                        partition_time_level: &partition_time_level,
-                        stream_alerts_map: &stream_alerts_map,
+                let stream_data = stream_data_map.get_mut(&stream_name).unwrap();
+                let buf = &mut stream_data.data;
+
+                let key = format!("{org_id}/{}/{stream_name}", StreamType::Logs);
+
+                // Start row based transform
+                if let Some(transforms) = stream_functions_map.get(&key) {
+                    if !transforms.is_empty() {
+                        let mut ret_value = value.clone();
+                        ret_value = crate::service::ingestion::apply_stream_functions(
+                            transforms,
+                            ret_value,
+                            &stream_vrl_map,
+                            org_id,
+                            &stream_name,
+                            &mut runtime,
+                        )?;
+
+                        if ret_value.is_null() || !ret_value.is_object() {
+                            bulk_res.errors = true;
+                            add_record_status(
+                                stream_name.clone(),
+                                doc_id.clone(),
+                                action.clone(),
+                                Some(value),
+                                &mut bulk_res,
+                                Some(TRANSFORM_FAILED.to_owned()),
+                                Some(TRANSFORM_FAILED.to_owned()),
+                            );
+                            continue;
+                        } else {
+                            value = ret_value;
+                        }
+                    }
+                }
+                // End row based transform
+
+                // get json object
+                let mut local_val = match value.take() {
+                    json::Value::Object(v) => v,
+                    _ => unreachable!(),
+                };
+
+                if let Some(fields) = user_defined_schema_map.get(&stream_name) {
+                    local_val = crate::service::logs::refactor_map(local_val, fields);
+                }
+
+                // set _id
+                if !doc_id.is_empty() {
+                    local_val.insert("_id".to_string(), json::Value::String(doc_id.clone()));
+                }
+
+                // handle timestamp
+                let timestamp = match local_val.get(&cfg.common.column_timestamp) {
+                    Some(v) => match parse_timestamp_micro_from_value(v) {
+                        Ok(t) => t,
+                        Err(_e) => {
+                            bulk_res.errors = true;
+                            add_record_status(
+                                stream_name.clone(),
+                                doc_id.clone(),
+                                action.clone(),
+                                Some(value),
+                                &mut bulk_res,
+                                Some(TS_PARSE_FAILED.to_string()),
+                                Some(TS_PARSE_FAILED.to_string()),
+                            );
+                            continue;
+                        }
                     },
-                    buf,
-                    local_val,
-                )
-                .await
-                {
+                    None => Utc::now().timestamp_micros(),
+                };
+                // check ingestion time
+                if timestamp < min_ts {
                     bulk_res.errors = true;
+                    let failure_reason = Some(get_upto_discard_error().to_string());
                     add_record_status(
                         stream_name.clone(),
                         doc_id.clone(),
@@ -400,29 +406,61 @@ pub async fn ingest(
                         Some(value),
                         &mut bulk_res,
                         Some(TS_PARSE_FAILED.to_string()),
-                        Some(e.to_string()),
+                        failure_reason,
                     );
                     continue;
                 }
-            } else {
-                let local_trigger = match super::add_valid_record(
-                    &StreamMeta {
-                        org_id: org_id.to_string(),
-                        stream_name: stream_name.clone(),
-                        partition_keys: &partition_keys,
-                        partition_time_level: &partition_time_level,
-                        stream_alerts_map: &stream_alerts_map,
-                    },
-                    &mut stream_schema_map,
-                    &mut status,
-                    buf,
-                    local_val,
-                    need_trigger,
-                )
-                .await
-                {
-                    Ok(v) => v,
-                    Err(e) => {
+                local_val.insert(
+                    cfg.common.column_timestamp.clone(),
+                    json::Value::Number(timestamp.into()),
+                );
+                let (partition_keys, partition_time_level) =
+                    match stream_partition_keys_map.get(&stream_name) {
+                        Some((_, partition_det)) => (
+                            partition_det.partition_keys.clone(),
+                            partition_det.partition_time_level,
+                        ),
+                        None => (vec![], None),
+                    };
+
+                // only for bulk insert
+                let mut status = RecordStatus::default();
+                let need_trigger = !stream_trigger_map.contains_key(&stream_name);
+
+                let mut to_add_distinct_values = vec![];
+                // get distinct_value items
+                for field in DISTINCT_FIELDS.iter() {
+                    if let Some(val) = local_val.get(field) {
+                        if !val.is_null() {
+                            to_add_distinct_values.push(MetadataItem::DistinctValues(DvItem {
+                                stream_type: StreamType::Logs,
+                                stream_name: stream_name.clone(),
+                                field_name: field.to_string(),
+                                field_value: val.as_str().unwrap().to_string(),
+                                filter_name: "".to_string(),
+                                filter_value: "".to_string(),
+                            }));
+                        }
+                    }
+                }
+
+                // this is for schema inference at stream level , which avoids locks in case schema
+                // changes are frequent within request
+                if cfg.common.infer_schema_per_request {
+                    if let Err(e) = add_record(
+                        &StreamMeta {
+                            org_id: org_id.to_string(),
+                            stream_name: stream_name.clone(),
+                            partition_keys: &partition_keys,
+                            partition_time_level: &partition_time_level,
+                            stream_alerts_map: &stream_alerts_map,
+                            backfill: false,
+                        },
+                        buf,
+                        local_val,
+                    )
+                    .await
+                    {
                         bulk_res.errors = true;
                         add_record_status(
                             stream_name.clone(),
@@ -435,35 +473,68 @@ pub async fn ingest(
                         );
                         continue;
                     }
-                };
-                if local_trigger.is_some() {
-                    stream_trigger_map.insert(stream_name.clone(), local_trigger);
-                }
+                } else {
+                    let local_trigger = match super::add_valid_record(
+                        &StreamMeta {
+                            org_id: org_id.to_string(),
+                            stream_name: stream_name.clone(),
+                            partition_keys: &partition_keys,
+                            partition_time_level: &partition_time_level,
+                            stream_alerts_map: &stream_alerts_map,
+                            backfill: false,
+                        },
+                        &mut stream_schema_map,
+                        &mut status,
+                        buf,
+                        local_val,
+                        need_trigger,
+                    )
+                    .await
+                    {
+                        Ok(v) => v,
+                        Err(e) => {
+                            bulk_res.errors = true;
+                            add_record_status(
+                                stream_name.clone(),
+                                doc_id.clone(),
+                                action.clone(),
+                                Some(value),
+                                &mut bulk_res,
+                                Some(TS_PARSE_FAILED.to_string()),
+                                Some(e.to_string()),
+                            );
+                            continue;
+                        }
+                    };
+                    if local_trigger.is_some() {
+                        stream_trigger_map.insert(stream_name.clone(), local_trigger);
+                    }
 
-                // get distinct_value item
-                distinct_values.extend(to_add_distinct_values);
+                    // get distinct_value item
+                    distinct_values.extend(to_add_distinct_values);
 
-                if status.failed > 0 {
-                    bulk_res.errors = true;
-                    add_record_status(
-                        stream_name.clone(),
-                        doc_id.clone(),
-                        action.clone(),
-                        Some(value),
-                        &mut bulk_res,
-                        Some(SCHEMA_CONFORMANCE_FAILED.to_string()),
-                        Some(status.error),
-                    );
-                } else {
-                    add_record_status(
-                        stream_name.clone(),
-                        doc_id.clone(),
-                        action.clone(),
-                        None,
-                        &mut bulk_res,
-                        None,
-                        None,
-                    );
+                    if status.failed > 0 {
+                        bulk_res.errors = true;
+                        add_record_status(
+                            stream_name.clone(),
+                            doc_id.clone(),
+                            action.clone(),
+                            Some(value),
+                            &mut bulk_res,
+                            Some(SCHEMA_CONFORMANCE_FAILED.to_string()),
+                            Some(status.error),
+                        );
+                    } else {
+                        add_record_status(
+                            stream_name.clone(),
+                            doc_id.clone(),
+                            action.clone(),
+                            None,
+                            &mut bulk_res,
+                            None,
+                            None,
+                        );
+                    }
                 }
             }
         }
@@ -501,7 +572,7 @@ pub async fn ingest(
         };
 
         // write to file
-        let mut req_stats = write_file(&writer, &stream_name, stream_data.data).await;
+        let mut req_stats = write_file(&writer, &stream_name, stream_data.data).await?;
         req_stats.response_time += time;
         req_stats.user_email = Some(user_email.to_string());
         // metric + data usage
@@ -519,6 +590,7 @@ pub async fn ingest(
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
+    super::ingest_routed_records(routed_records, thread_id).await;
 
     // only one trigger per request, as it updates etcd
     for (_, entry) in stream_trigger_map {
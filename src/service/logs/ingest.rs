@@ -44,7 +44,10 @@ use crate::{
     },
     service::{
         get_formatted_stream_name,
-        ingestion::{check_ingestion_allowed, evaluate_trigger, write_file, TriggerAlertData},
+        ingestion::{
+            check_ingestion_allowed, evaluate_trigger, pipeline::get_stream_pipeline, write_file,
+            TriggerAlertData,
+        },
         logs::StreamMeta,
         metadata::{distinct_values::DvItem, write, MetadataItem, MetadataType},
         schema::get_upto_discard_error,
@@ -52,6 +55,7 @@ use crate::{
     },
 };
 
+#[tracing::instrument(name = "service:logs:ingest", skip_all, fields(org_id = org_id, stream_name = in_stream_name))]
 pub async fn ingest(
     org_id: &str,
     in_stream_name: &str,
@@ -72,12 +76,18 @@ pub async fn ingest(
             code: http::StatusCode::SERVICE_UNAVAILABLE.into(),
             status: vec![],
             error: Some(e.to_string()),
+            degraded: false,
         });
     }
 
     let cfg = get_config();
-    let min_ts = (Utc::now() - Duration::try_hours(cfg.limit.ingest_allowed_upto).unwrap())
-        .timestamp_micros();
+    let backfill = matches!(in_req, IngestionRequest::Backfill(_));
+    let min_ts = if backfill {
+        i64::MIN
+    } else {
+        (Utc::now() - Duration::try_hours(cfg.limit.ingest_allowed_upto).unwrap())
+            .timestamp_micros()
+    };
 
     // Start Register Transforms for stream
     let mut runtime = crate::service::ingestion::init_functions_runtime();
@@ -94,10 +104,15 @@ pub async fn ingest(
         stream_type: StreamType::Logs,
     };
 
-    // Start get stream alerts
+    // Start get stream alerts, the historical records don't trigger them
     let mut stream_alerts_map: HashMap<String, Vec<Alert>> = HashMap::new();
-    crate::service::ingestion::get_stream_alerts(&[stream_param.clone()], &mut stream_alerts_map)
+    if !backfill {
+        crate::service::ingestion::get_stream_alerts(
+            &[stream_param.clone()],
+            &mut stream_alerts_map,
+        )
         .await;
+    }
     // End get stream alerts
 
     // Start get user defined schema
@@ -123,7 +138,10 @@ pub async fn ingest(
     let partition_time_level = partition_det.partition_time_level;
 
     let mut write_buf: HashMap<String, SchemaRecords> = HashMap::new();
+    let pipeline = get_stream_pipeline(org_id, StreamType::Logs, stream_name);
+    let mut routed_records: HashMap<(String, String), Vec<json::Value>> = HashMap::new();
 
+    let settings = infra::schema::get_settings(org_id, stream_name, StreamType::Logs).await;
     let json_req: Vec<json::Value>; // to hold json request because of borrow checker
     let (ep, data) = match in_req {
         IngestionRequest::JSON(req) => {
@@ -135,10 +153,49 @@ pub async fn ingest(
         }
         IngestionRequest::GCP(req) => ("/api/org/ingest/logs/_gcs", IngestionData::GCP(req)),
         IngestionRequest::Multi(req) => ("/api/org/ingest/logs/_multi", IngestionData::Multi(req)),
+        IngestionRequest::RAW(req) => {
+            let line_pattern = settings
+                .as_ref()
+                .and_then(|settings| settings.line_pattern.as_ref())
+                .map(|pattern| super::raw::LinePattern::new(pattern))
+                .transpose()?;
+            json_req = super::raw::parse_lines(req, line_pattern.as_ref())?;
+            ("/api/org/ingest/logs/_raw", IngestionData::JSON(&json_req))
+        }
         IngestionRequest::KinesisFH(req) => (
             "/api/org/ingest/logs/_kinesis",
             IngestionData::KinesisFH(req),
         ),
+        IngestionRequest::Backfill(req) => {
+            ("/api/org/ingest/logs/_backfill", IngestionData::JSON(req))
+        }
+    };
+
+    // the records of the log format of the stream
+    let format_req: Vec<json::Value>;
+    let data = match settings.as_ref().and_then(|settings| settings.log_format) {
+        None => data,
+        Some(format) => {
+            let mut records = Vec::new();
+            for ret in data.iter() {
+                let item = match ret {
+                    Ok(item) => item,
+                    Err(e) => {
+                        log::error!("IngestionError: {:?}", e);
+                        return Err(anyhow::anyhow!("Failed processing: {:?}", e));
+                    }
+                };
+                match super::parse_log_format(format, item) {
+                    Ok(v) => records.extend(v),
+                    Err(e) => {
+                        stream_status.status.failed += 1;
+                        stream_status.status.error = e.to_string();
+                    }
+                }
+            }
+            format_req = records;
+            IngestionData::JSON(&format_req)
+        }
     };
 
     for ret in data.iter() {
@@ -150,6 +207,46 @@ pub async fn ingest(
             }
         };
 
+        // the pipeline steps may route the record or copies of it to other streams
+        let item = match &pipeline {
+            None => item,
+            Some(pipeline) => {
+                let value = match flatten::flatten_with_level(item, cfg.limit.ingest_flatten_level)
+                {
+                    Ok(v) => v,
+                    Err(e) => {
+                        stream_status.status.failed += 1;
+                        stream_status.status.error = e.to_string();
+                        continue;
+                    }
+                };
+                let outputs = pipeline.execute(&mut runtime, org_id, value).await;
+                if outputs.is_empty() {
+                    stream_status.status.failed += 1;
+                    stream_status.status.error = "apply functions failure".to_string();
+                    continue;
+                }
+                let mut kept = None;
+                for output in outputs {
+                    if output.org_id.is_none() && output.stream_name.eq(stream_name) {
+                        kept = Some(output.value);
+                    } else {
+                        routed_records
+                            .entry((
+                                output.org_id.unwrap_or_else(|| org_id.to_string()),
+                                output.stream_name,
+                            ))
+                            .or_default()
+                            .push(output.value);
+                    }
+                }
+                match kept {
+                    Some(v) => v,
+                    None => continue, // moved to another stream
+                }
+            }
+        };
+
         let mut res = match apply_functions(
             item,
             &local_trans,
@@ -205,6 +302,7 @@ pub async fn ingest(
                 partition_keys: &partition_keys,
                 partition_time_level: &partition_time_level,
                 stream_alerts_map: &stream_alerts_map,
+                backfill,
             },
             &mut stream_schema_map,
             &mut stream_status.status,
@@ -231,10 +329,14 @@ pub async fn ingest(
 
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
-    let mut req_stats = write_file(&writer, stream_name, write_buf).await;
+    let mut req_stats = match write_file(&writer, stream_name, write_buf).await {
+        Ok(v) => v,
+        Err(e) => return Ok(IngestionResponse::unavailable(e)),
+    };
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
+    super::ingest_routed_records(routed_records, thread_id).await;
 
     // send distinct_values
     if !distinct_values.is_empty() {
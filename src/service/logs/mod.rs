@@ -23,7 +23,7 @@ use anyhow::Result;
 use arrow_schema::{DataType, Field, Schema};
 use config::{
     get_config,
-    meta::stream::{PartitionTimeLevel, StreamPartition, StreamType},
+    meta::stream::{LogFormat, PartitionTimeLevel, StreamPartition, StreamType},
     utils::{
         json::{estimate_json_bytes, get_string_value, pickup_string_value, Map, Number, Value},
         schema_ext::SchemaExt,
@@ -31,21 +31,37 @@ use config::{
 };
 use infra::schema::{unwrap_partition_time_level, SchemaCache};
 
-use super::ingestion::TriggerAlertData;
+use super::ingestion::{check_timestamp_skew, TriggerAlertData};
 use crate::{
     common::meta::{alerts::Alert, ingestion::RecordStatus, stream::SchemaRecords},
-    service::{ingestion::get_wal_time_key, schema::check_for_schema},
+    service::{
+        dlp,
+        ingestion::get_wal_time_key,
+        schema::{check_fields_limit, check_for_schema},
+    },
 };
 
+pub mod aws;
+pub mod backfill;
 pub mod bulk;
 pub mod ingest;
 pub mod multi;
 pub mod otlp_grpc;
 pub mod otlp_http;
+pub mod raw;
 pub mod syslog;
+pub mod windows_event;
 
 static BULK_OPERATORS: [&str; 3] = ["create", "index", "update"];
 
+/// Parses a record with the built-in parser of the log format of its stream.
+pub fn parse_log_format(format: LogFormat, record: Value) -> Result<Vec<Value>> {
+    match format {
+        LogFormat::WindowsEvent => windows_event::parse(record),
+        _ => aws::parse(format, record),
+    }
+}
+
 fn parse_bulk_index(v: &Value) -> Option<(String, String, String)> {
     let local_val = v.as_object().unwrap();
     for action in BULK_OPERATORS {
@@ -286,12 +302,53 @@ async fn add_valid_record(
     need_trigger: bool,
 ) -> Result<Option<TriggerAlertData>> {
     let cfg = get_config();
+    dlp::redact(
+        &stream_meta.org_id,
+        &stream_meta.stream_name,
+        &mut record_val,
+    );
+    if let Err(e) = check_fields_limit(
+        &stream_meta.org_id,
+        &stream_meta.stream_name,
+        StreamType::Logs,
+        &mut record_val,
+    )
+    .await
+    {
+        status.failed += 1;
+        status.error = e.to_string();
+        return Ok(None);
+    }
     let mut trigger: TriggerAlertData = Vec::new();
-    let timestamp: i64 = record_val
+    let record_ts: i64 = record_val
         .get(&cfg.common.column_timestamp)
         .unwrap()
         .as_i64()
         .unwrap();
+    let checked = if stream_meta.backfill {
+        Ok(record_ts)
+    } else {
+        check_timestamp_skew(
+            &stream_meta.org_id,
+            &stream_meta.stream_name,
+            StreamType::Logs,
+            record_ts,
+        )
+    };
+    let timestamp = match checked {
+        Ok(ts) => ts,
+        Err(e) => {
+            status.failed += 1;
+            status.error = e.to_string();
+            return Ok(None);
+        }
+    };
+    if timestamp != record_ts {
+        record_val.insert(
+            cfg.common.column_timestamp.clone(),
+            Value::Number(timestamp.into()),
+        );
+    }
 
     // check schema
     let schema_evolution = check_for_schema(
@@ -394,6 +451,27 @@ async fn add_valid_record(
     }
 }
 
+/// Writes the records a pipeline routed out of the request, keyed by
+/// `(org_id, stream_name)`. The functions of the destination streams are not
+/// applied to them.
+async fn ingest_routed_records(routed: HashMap<(String, String), Vec<Value>>, thread_id: usize) {
+    for ((org_id, stream_name), records) in routed {
+        let body = match config::utils::json::to_vec(&records) {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!(
+                    "[PIPELINE] encode records routed to {org_id}/{stream_name} error: {e}"
+                );
+                continue;
+            }
+        };
+        if let Err(e) = otlp_grpc::usage_ingest(&org_id, &stream_name, body.into(), thread_id).await
+        {
+            log::error!("[PIPELINE] ingest records routed to {org_id}/{stream_name} error: {e}");
+        }
+    }
+}
+
 fn set_parsing_error(parse_error: &mut String, field: &Field) {
     parse_error.push_str(&format!(
         "Failed to cast {} to type {} ",
@@ -405,9 +483,21 @@ fn set_parsing_error(parse_error: &mut String, field: &Field) {
 async fn add_record(
     stream_meta: &StreamMeta<'_>,
     write_buf: &mut HashMap<String, SchemaRecords>,
-    record_val: Map<String, Value>,
+    mut record_val: Map<String, Value>,
 ) -> Result<()> {
     let cfg = get_config();
+    dlp::redact(
+        &stream_meta.org_id,
+        &stream_meta.stream_name,
+        &mut record_val,
+    );
+    check_fields_limit(
+        &stream_meta.org_id,
+        &stream_meta.stream_name,
+        StreamType::Logs,
+        &mut record_val,
+    )
+    .await?;
     let timestamp: i64 = record_val
         .get(&cfg.common.column_timestamp)
         .unwrap()
@@ -443,6 +533,8 @@ struct StreamMeta<'a> {
     partition_keys: &'a Vec<StreamPartition>,
     partition_time_level: &'a Option<PartitionTimeLevel>,
     stream_alerts_map: &'a HashMap<String, Vec<Alert>>,
+    /// historical records, the timestamp skew policy doesn't apply
+    backfill: bool,
 }
 
 pub fn refactor_map(
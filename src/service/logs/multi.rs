@@ -206,6 +206,7 @@ async fn ingest_inner(
                 partition_keys: &partition_keys,
                 partition_time_level: &partition_time_level,
                 stream_alerts_map: &stream_alerts_map,
+                backfill: false,
             },
             &mut stream_schema_map,
             &mut stream_status.status,
@@ -232,7 +233,10 @@ async fn ingest_inner(
 
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
-    let mut req_stats = write_file(&writer, stream_name, buf).await;
+    let mut req_stats = match write_file(&writer, stream_name, buf).await {
+        Ok(v) => v,
+        Err(e) => return Ok(IngestionResponse::unavailable(e)),
+    };
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
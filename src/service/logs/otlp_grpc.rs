@@ -72,9 +72,7 @@ pub async fn usage_ingest(
         return Err(anyhow::anyhow!("not an ingester"));
     }
 
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if crate::service::ingestion::is_org_quota_exceeded(org_id) {
         return Err(anyhow::anyhow!(
             "Quota exceeded for this organization [{}]",
             org_id
@@ -178,6 +176,7 @@ pub async fn usage_ingest(
                 partition_keys: &partition_keys,
                 partition_time_level: &partition_time_level,
                 stream_alerts_map: &stream_alerts_map,
+                backfill: false,
             },
             &mut stream_schema_map,
             &mut stream_status.status,
@@ -204,7 +203,7 @@ pub async fn usage_ingest(
 
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
-    let _req_stats = write_file(&writer, stream_name, buf).await;
+    write_file(&writer, stream_name, buf).await?;
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
@@ -245,6 +244,7 @@ pub async fn usage_ingest(
     ))
 }
 
+#[tracing::instrument(name = "service:logs:otlp:ingest", skip_all, fields(org_id = org_id))]
 pub async fn handle_grpc_request(
     org_id: &str,
     thread_id: usize,
@@ -262,9 +262,7 @@ pub async fn handle_grpc_request(
         );
     }
 
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if crate::service::ingestion::is_org_quota_exceeded(org_id) {
         return Ok(HttpResponse::Forbidden().json(MetaHttpResponse::error(
             http::StatusCode::FORBIDDEN.into(),
             format!("Quota exceeded for this organization [{}]", org_id),
@@ -478,6 +476,7 @@ pub async fn handle_grpc_request(
                         partition_keys: &partition_keys,
                         partition_time_level: &partition_time_level,
                         stream_alerts_map: &stream_alerts_map,
+                        backfill: false,
                     },
                     &mut stream_schema_map,
                     &mut stream_status.status,
@@ -504,7 +503,17 @@ pub async fn handle_grpc_request(
 
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
-    let mut req_stats = write_file(&writer, stream_name, data_buf).await;
+    let mut req_stats = match write_file(&writer, stream_name, data_buf).await {
+        Ok(v) => v,
+        Err(e) => {
+            return Ok(
+                HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                    http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
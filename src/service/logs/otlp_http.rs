@@ -42,7 +42,7 @@ use crate::{
     },
     handler::http::request::CONTENT_TYPE_JSON,
     service::{
-        db, get_formatted_stream_name,
+        get_formatted_stream_name,
         ingestion::{evaluate_trigger, get_val_for_attr, write_file, TriggerAlertData},
         metadata::{distinct_values::DvItem, write, MetadataItem, MetadataType},
         schema::{get_upto_discard_error, stream_schema_exists},
@@ -86,6 +86,7 @@ pub async fn logs_proto_handler(
 
 // example at: https://opentelemetry.io/docs/specs/otel/protocol/file-exporter/#examples
 // otel collector handling json request for logs https://github.com/open-telemetry/opentelemetry-collector/blob/main/pdata/plog/json.go
+#[tracing::instrument(name = "service:logs:otlp_json:ingest", skip_all, fields(org_id = org_id))]
 pub async fn logs_json_handler(
     org_id: &str,
     thread_id: usize,
@@ -102,9 +103,7 @@ pub async fn logs_json_handler(
         );
     }
 
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if crate::service::ingestion::is_org_quota_exceeded(org_id) {
         return Ok(HttpResponse::Forbidden().json(MetaHttpResponse::error(
             http::StatusCode::FORBIDDEN.into(),
             format!("Quota exceeded for this organization [{}]", org_id),
@@ -411,6 +410,7 @@ pub async fn logs_json_handler(
                         partition_keys: &partition_keys,
                         partition_time_level: &partition_time_level,
                         stream_alerts_map: &stream_alerts_map,
+                        backfill: false,
                     },
                     &mut stream_schema_map,
                     &mut stream_status.status,
@@ -439,7 +439,17 @@ pub async fn logs_json_handler(
 
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
-    let mut req_stats = write_file(&writer, stream_name, buf).await;
+    let mut req_stats = match write_file(&writer, stream_name, buf).await {
+        Ok(v) => v,
+        Err(e) => {
+            return Ok(
+                HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                    http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
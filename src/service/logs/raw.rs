@@ -0,0 +1,300 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Records of the logs ingested as raw text, one per line. The `line_pattern`
+//! of the stream, a regex with named groups where `%{NAME:field}` stands for
+//! the grok pattern NAME, extracts fields from the lines.
+
+use std::io::{BufRead, Read};
+
+use actix_multipart::Multipart;
+use actix_web::web;
+use anyhow::Result;
+use config::utils::json;
+use flate2::read::GzDecoder;
+use futures::{StreamExt, TryStreamExt};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::common::meta::ingestion::{IngestionRequest, IngestionResponse};
+
+/// The field holding the line.
+pub const MESSAGE_FIELD: &str = "message";
+
+static RE_GROK: Lazy<Regex> = Lazy::new(|| Regex::new(r"%\{(\w+)(?::(\w+))?\}").unwrap());
+
+/// The grok patterns known in the line patterns, the fields of INT and NUMBER
+/// are stored as numbers.
+const GROK_PATTERNS: [(&str, &str); 14] = [
+    ("WORD", r"\w+"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("INT", r"[+-]?\d+"),
+    ("NUMBER", r"[+-]?(?:\d+(?:\.\d*)?|\.\d+)"),
+    (
+        "IP",
+        r"(?:\d{1,3}\.){3}\d{1,3}|[0-9A-Fa-f]{0,4}(?::[0-9A-Fa-f]{0,4}){2,7}",
+    ),
+    (
+        "LOGLEVEL",
+        r"(?i:trace|debug|info|notice|warn(?:ing)?|err(?:or)?|crit(?:ical)?|fatal|severe|emerg(?:ency)?)",
+    ),
+    (
+        "TIMESTAMP_ISO8601",
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(?::\d{2}(?:[.,]\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?",
+    ),
+    ("HTTPDATE", r"\d{2}/\w{3}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4}"),
+    (
+        "UUID",
+        r"[0-9A-Fa-f]{8}-(?:[0-9A-Fa-f]{4}-){3}[0-9A-Fa-f]{12}",
+    ),
+    ("QUOTEDSTRING", r#""(?:[^"\\]|\\.)*""#),
+    ("URIPATH", r"/[^\s?#]*"),
+];
+
+pub struct LinePattern {
+    regex: Regex,
+    numeric_fields: Vec<String>,
+}
+
+impl LinePattern {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let mut numeric_fields = Vec::new();
+        let mut expanded = String::with_capacity(pattern.len());
+        let mut last = 0;
+        for cap in RE_GROK.captures_iter(pattern) {
+            let all = cap.get(0).unwrap();
+            let name = &cap[1];
+            let Some((_, re)) = GROK_PATTERNS.iter().find(|(n, _)| *n == name) else {
+                return Err(anyhow::anyhow!("unknown grok pattern: {name}"));
+            };
+            expanded.push_str(&pattern[last..all.start()]);
+            match cap.get(2) {
+                Some(field) => {
+                    if matches!(name, "INT" | "NUMBER") {
+                        numeric_fields.push(field.as_str().to_string());
+                    }
+                    expanded.push_str(&format!("(?P<{}>{re})", field.as_str()));
+                }
+                None => expanded.push_str(&format!("(?:{re})")),
+            }
+            last = all.end();
+        }
+        expanded.push_str(&pattern[last..]);
+        let regex = Regex::new(&expanded)?;
+        if regex.capture_names().flatten().next().is_none() {
+            return Err(anyhow::anyhow!("line pattern has no named group"));
+        }
+        Ok(Self {
+            regex,
+            numeric_fields,
+        })
+    }
+
+    /// Adds the fields captured from the line to the record, nothing when the
+    /// line doesn't match.
+    fn extract(&self, line: &str, record: &mut json::Map<String, json::Value>) {
+        let Some(caps) = self.regex.captures(line) else {
+            return;
+        };
+        for name in self.regex.capture_names().flatten() {
+            let Some(value) = caps.name(name) else {
+                continue;
+            };
+            let value = value.as_str();
+            let value = if self.numeric_fields.iter().any(|f| f == name) {
+                match value.parse::<i64>() {
+                    Ok(v) => json::Value::from(v),
+                    Err(_) => value
+                        .parse::<f64>()
+                        .map(json::Value::from)
+                        .unwrap_or_else(|_| value.into()),
+                }
+            } else {
+                value.into()
+            };
+            record.insert(name.to_string(), value);
+        }
+    }
+}
+
+/// Reads a record from each non empty line of the data.
+pub fn parse_lines(data: &[u8], pattern: Option<&LinePattern>) -> Result<Vec<json::Value>> {
+    let mut records = Vec::new();
+    for line in data.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut record = json::Map::new();
+        record.insert(MESSAGE_FIELD.to_string(), line.into());
+        if let Some(pattern) = pattern {
+            pattern.extract(line, &mut record);
+        }
+        records.push(json::Value::Object(record));
+    }
+    Ok(records)
+}
+
+/// Ingests each file of the upload, gzipped files end in `.gz`. The files
+/// with a json content type or name are read as multiple line json, the others
+/// as raw text.
+pub async fn ingest_upload(
+    org_id: &str,
+    stream_name: &str,
+    mut payload: Multipart,
+    thread_id: usize,
+    user_email: &str,
+) -> Result<IngestionResponse> {
+    let mut resp = IngestionResponse::new(200, vec![]);
+    while let Some(mut field) = payload
+        .try_next()
+        .await
+        .map_err(|e| anyhow::anyhow!("read upload error: {e}"))?
+    {
+        let filename = field
+            .content_disposition()
+            .get_filename()
+            .unwrap_or_default()
+            .to_string();
+        let content_type = field
+            .content_type()
+            .map(|v| v.essence_str().to_string())
+            .unwrap_or_default();
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            data.extend_from_slice(&chunk.map_err(|e| anyhow::anyhow!("read upload error: {e}"))?);
+        }
+        let filename = match filename.strip_suffix(".gz") {
+            Some(name) => {
+                let mut decoded = Vec::new();
+                GzDecoder::new(data.as_slice()).read_to_end(&mut decoded)?;
+                data = decoded;
+                name.to_string()
+            }
+            None => filename,
+        };
+        if data.is_empty() {
+            continue;
+        }
+
+        let data = web::Bytes::from(data);
+        let req = if is_json_file(&filename, &content_type) {
+            IngestionRequest::Multi(&data)
+        } else {
+            IngestionRequest::RAW(&data)
+        };
+        let part = super::ingest::ingest(org_id, stream_name, req, thread_id, user_email).await?;
+        merge_response(&mut resp, part);
+        if resp.code != 200 {
+            break;
+        }
+    }
+    Ok(resp)
+}
+
+fn is_json_file(filename: &str, content_type: &str) -> bool {
+    content_type.ends_with("json")
+        || filename.ends_with(".json")
+        || filename.ends_with(".ndjson")
+        || filename.ends_with(".jsonl")
+}
+
+fn merge_response(resp: &mut IngestionResponse, part: IngestionResponse) {
+    if part.code != 200 {
+        resp.code = part.code;
+        resp.error = part.error;
+    }
+    for status in part.status {
+        match resp.status.iter_mut().find(|s| s.name == status.name) {
+            Some(s) => {
+                s.status.successful += status.status.successful;
+                s.status.failed += status.status.failed;
+                if !status.status.error.is_empty() {
+                    s.status.error = status.status.error;
+                }
+            }
+            None => resp.status.push(status),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::meta::ingestion::StreamStatus;
+
+    #[test]
+    fn test_line_pattern() {
+        assert!(LinePattern::new("%{NOPE:x}").is_err());
+        assert!(LinePattern::new("%{WORD}").is_err());
+        assert!(LinePattern::new("(?P<bad").is_err());
+
+        let pattern = LinePattern::new(
+            r"^%{TIMESTAMP_ISO8601:ts} %{LOGLEVEL:level} %{IP:client} took=%{NUMBER:took}ms (?P<msg>.*)$",
+        )
+        .unwrap();
+        let data =
+            b"2024-03-01T10:00:00Z ERROR 10.0.0.1 took=12.5ms upstream failed\r\n\n not matching\n";
+        let records = parse_lines(data, Some(&pattern)).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0],
+            json::json!({
+                "message": "2024-03-01T10:00:00Z ERROR 10.0.0.1 took=12.5ms upstream failed",
+                "ts": "2024-03-01T10:00:00Z",
+                "level": "ERROR",
+                "client": "10.0.0.1",
+                "took": 12.5,
+                "msg": "upstream failed",
+            })
+        );
+        assert_eq!(records[1], json::json!({"message": " not matching"}));
+    }
+
+    #[test]
+    fn test_merge_response() {
+        let mut stream = StreamStatus::new("app");
+        stream.status.successful = 2;
+        let mut resp = IngestionResponse::new(200, vec![stream.clone()]);
+        stream.status.failed = 1;
+        stream.status.error = "too old".to_string();
+        merge_response(
+            &mut resp,
+            IngestionResponse::new(200, vec![stream, StreamStatus::new("other")]),
+        );
+        assert_eq!(resp.code, 200);
+        assert_eq!(resp.status.len(), 2);
+        assert_eq!(resp.status[0].status.successful, 4);
+        assert_eq!(resp.status[0].status.failed, 1);
+        assert_eq!(resp.status[0].status.error, "too old");
+
+        assert!(is_json_file("app.ndjson", ""));
+        assert!(is_json_file("upload", "application/json"));
+        assert!(!is_json_file("app.log", "text/plain"));
+    }
+
+    #[test]
+    fn test_parse_lines_without_pattern() {
+        let records = parse_lines(b"a\nb\n", None).unwrap();
+        assert_eq!(
+            records,
+            vec![json::json!({"message": "a"}), json::json!({"message": "b"})]
+        );
+    }
+}
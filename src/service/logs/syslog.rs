@@ -204,6 +204,7 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
             partition_keys: &partition_keys,
             partition_time_level: &partition_time_level,
             stream_alerts_map: &stream_alerts_map,
+            backfill: false,
         },
         &mut stream_schema_map,
         &mut stream_status.status,
@@ -229,7 +230,9 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
 
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
-    write_file(&writer, stream_name, buf).await;
+    if let Err(e) = write_file(&writer, stream_name, buf).await {
+        log::error!("syslog ingestion error: {}", e);
+    }
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
@@ -0,0 +1,348 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Windows Event Log records, the xml of the events as winlogbeat or NXLog
+//! ship it in the `message` field. The System elements become typed fields,
+//! the EventData and UserData values the fields of their objects, and the
+//! rendered message of RenderingInfo the `Message` field. The xml is read as
+//! a stream of tags, much cheaper than parsing it in a function.
+
+use anyhow::Result;
+use config::{get_config, utils::json};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use super::raw::MESSAGE_FIELD;
+
+/// The System elements holding numbers, the others hold strings.
+const SYSTEM_NUMBERS: [&str; 6] = [
+    "EventID",
+    "Version",
+    "Level",
+    "Task",
+    "Opcode",
+    "EventRecordID",
+];
+
+/// The fields of the System attributes, by element and attribute.
+const SYSTEM_ATTRIBUTES: [(&str, &str, &str); 10] = [
+    ("Provider", "Name", "Provider"),
+    ("Provider", "Guid", "ProviderGuid"),
+    ("Provider", "EventSourceName", "EventSourceName"),
+    ("EventID", "Qualifiers", "Qualifiers"),
+    ("TimeCreated", "SystemTime", "TimeCreated"),
+    ("Correlation", "ActivityID", "ActivityID"),
+    ("Correlation", "RelatedActivityID", "RelatedActivityID"),
+    ("Execution", "ProcessID", "ProcessID"),
+    ("Execution", "ThreadID", "ThreadID"),
+    ("Security", "UserID", "UserID"),
+];
+
+const ATTRIBUTE_NUMBERS: [&str; 3] = ["Qualifiers", "ProcessID", "ThreadID"];
+
+/// The texts of RenderingInfo kept next to the numbers of System.
+const RENDERED_TEXTS: [&str; 4] = ["Level", "Task", "Opcode", "Channel"];
+
+#[derive(Default)]
+struct EventFields {
+    fields: json::Map<String, json::Value>,
+    event_data: json::Map<String, json::Value>,
+    user_data: json::Map<String, json::Value>,
+    /// name of the EventData value being read
+    data_name: Option<String>,
+    /// unnamed EventData values so far, they are named `param1`, `param2`...
+    params: usize,
+}
+
+/// Parses the xml in the message of a record into a record per event, the
+/// other fields of the record are kept.
+pub fn parse(record: json::Value) -> Result<Vec<json::Value>> {
+    let json::Value::Object(mut record) = record else {
+        return Err(anyhow::anyhow!("Windows event record is not an object"));
+    };
+    let Some(json::Value::String(xml)) = record.remove(MESSAGE_FIELD) else {
+        return Err(anyhow::anyhow!(
+            "Windows event record has no {MESSAGE_FIELD} xml"
+        ));
+    };
+    Ok(parse_events(&xml)?
+        .into_iter()
+        .map(|fields| {
+            let mut event = record.clone();
+            event.extend(fields);
+            json::Value::Object(event)
+        })
+        .collect())
+}
+
+/// The fields of the events of the xml, a single `Event` or the `Events` of
+/// an export.
+fn parse_events(xml: &str) -> Result<Vec<json::Map<String, json::Value>>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut path: Vec<String> = Vec::new();
+    let mut events = Vec::new();
+    let mut event = EventFields::default();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = local_name(&e);
+                event.read_attributes(&path, &name, &e)?;
+                path.push(name);
+            }
+            Event::Empty(e) => {
+                let name = local_name(&e);
+                event.read_attributes(&path, &name, &e)?;
+                event.data_name = None;
+            }
+            Event::Text(e) => event.read_text(&path, &e.unescape()?),
+            Event::CData(e) => event.read_text(&path, &String::from_utf8_lossy(&e.into_inner())),
+            Event::End(_) => {
+                if path.pop().as_deref() == Some("Event") {
+                    events.push(std::mem::take(&mut event).finish());
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if events.is_empty() {
+        return Err(anyhow::anyhow!("no Windows event in the xml"));
+    }
+    Ok(events)
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+impl EventFields {
+    fn read_attributes(&mut self, path: &[String], name: &str, e: &BytesStart) -> Result<()> {
+        let parent = path.last().map(|v| v.as_str()).unwrap_or_default();
+        if parent == "EventData" && name == "Data" {
+            let mut data_name = None;
+            for attr in e.attributes() {
+                let attr = attr?;
+                if attr.key.local_name().as_ref() == b"Name" {
+                    data_name = Some(attr.unescape_value()?.into_owned());
+                }
+            }
+            self.data_name = match data_name {
+                Some(v) => Some(v),
+                None => {
+                    self.params += 1;
+                    Some(format!("param{}", self.params))
+                }
+            };
+            return Ok(());
+        }
+        if parent != "System" {
+            return Ok(());
+        }
+
+        for attr in e.attributes() {
+            let attr = attr?;
+            let key = attr.key.local_name();
+            let Some((_, _, field)) = SYSTEM_ATTRIBUTES.iter().find(|(element, attribute, _)| {
+                *element == name && attribute.as_bytes() == key.as_ref()
+            }) else {
+                continue;
+            };
+            let value = attr.unescape_value()?;
+            self.fields.insert(
+                field.to_string(),
+                typed_value(&value, ATTRIBUTE_NUMBERS.contains(field)),
+            );
+        }
+        Ok(())
+    }
+
+    fn read_text(&mut self, path: &[String], text: &str) {
+        let path = path.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+        match path.as_slice() {
+            [.., "System", name] => {
+                self.fields.insert(
+                    name.to_string(),
+                    typed_value(text, SYSTEM_NUMBERS.contains(name)),
+                );
+            }
+            [.., "EventData", "Data"] => {
+                if let Some(name) = self.data_name.take() {
+                    self.event_data.insert(name, text.into());
+                }
+            }
+            [.., "EventData", name] => {
+                self.event_data.insert(name.to_string(), text.into());
+            }
+            [.., "RenderingInfo", "Message"] => {
+                self.fields.insert("Message".to_string(), text.into());
+            }
+            [.., "RenderingInfo", name] if RENDERED_TEXTS.contains(name) => {
+                self.fields.insert(format!("{name}Text"), text.into());
+            }
+            [.., name] if path.contains(&"UserData") => {
+                self.user_data.insert(name.to_string(), text.into());
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> json::Map<String, json::Value> {
+        let mut fields = self.fields;
+        if !self.event_data.is_empty() {
+            fields.insert("EventData".to_string(), self.event_data.into());
+        }
+        if !self.user_data.is_empty() {
+            fields.insert("UserData".to_string(), self.user_data.into());
+        }
+        if !fields.contains_key("LevelText") {
+            if let Some(level) = fields
+                .get("Level")
+                .and_then(|v| v.as_i64())
+                .and_then(level_name)
+            {
+                fields.insert("LevelText".to_string(), level.into());
+            }
+        }
+        if let Some(time) = fields
+            .get("TimeCreated")
+            .and_then(|v| v.as_str())
+            .and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok())
+        {
+            fields.insert(
+                get_config().common.column_timestamp.clone(),
+                time.timestamp_micros().into(),
+            );
+        }
+        fields
+    }
+}
+
+/// The names of the standard levels, as the event viewer shows them.
+fn level_name(level: i64) -> Option<&'static str> {
+    match level {
+        0 | 4 => Some("Information"),
+        1 => Some("Critical"),
+        2 => Some("Error"),
+        3 => Some("Warning"),
+        5 => Some("Verbose"),
+        _ => None,
+    }
+}
+
+fn typed_value(value: &str, number: bool) -> json::Value {
+    match value.parse::<i64>() {
+        Ok(v) if number => v.into(),
+        _ => value.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOGON: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'>
+  <System>
+    <Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/>
+    <EventID>4624</EventID>
+    <Version>2</Version>
+    <Level>0</Level>
+    <Task>12544</Task>
+    <Opcode>0</Opcode>
+    <Keywords>0x8020000000000000</Keywords>
+    <TimeCreated SystemTime='2024-05-01T10:00:00.1234567Z'/>
+    <EventRecordID>123456</EventRecordID>
+    <Correlation ActivityID='{e7a4e6a8-9b0c-0001-54e8-a4e70c9bda01}'/>
+    <Execution ProcessID='636' ThreadID='700'/>
+    <Channel>Security</Channel>
+    <Computer>DC01.example.com</Computer>
+    <Security/>
+  </System>
+  <EventData>
+    <Data Name='SubjectUserSid'>S-1-5-18</Data>
+    <Data Name='TargetUserName'>alice &amp; bob</Data>
+    <Data Name='LogonType'>3</Data>
+    <Data Name='IpAddress'>-</Data>
+    <Data Name='Empty'/>
+  </EventData>
+  <RenderingInfo Culture='en-US'>
+    <Message>An account was successfully logged on.</Message>
+    <Level>Information</Level>
+    <Task>Logon</Task>
+  </RenderingInfo>
+</Event>"#;
+
+    #[test]
+    fn test_parse() {
+        let record = json::json!({"host": "dc01", "message": LOGON});
+        let events = parse(record).unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event["host"], "dc01");
+        assert!(event.get("message").is_none());
+        assert_eq!(event["Provider"], "Microsoft-Windows-Security-Auditing");
+        assert_eq!(event["EventID"], 4624);
+        assert_eq!(event["Level"], 0);
+        assert_eq!(event["LevelText"], "Information");
+        assert_eq!(event["TaskText"], "Logon");
+        assert_eq!(event["Keywords"], "0x8020000000000000");
+        assert_eq!(event["ProcessID"], 636);
+        assert_eq!(event["Channel"], "Security");
+        assert_eq!(event["Computer"], "DC01.example.com");
+        assert_eq!(event["Message"], "An account was successfully logged on.");
+        assert_eq!(event["_timestamp"], 1714557600123456i64);
+        assert_eq!(
+            event["EventData"],
+            json::json!({
+                "SubjectUserSid": "S-1-5-18",
+                "TargetUserName": "alice & bob",
+                "LogonType": "3",
+                "IpAddress": "-",
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let xml = "<Events>\
+            <Event><System><EventID Qualifiers='16384'>7036</EventID><Level>4</Level></System>\
+            <EventData><Data>Windows Update</Data><Data>running</Data></EventData></Event>\
+            <Event><System><EventID>1102</EventID><Level>2</Level></System>\
+            <UserData><LogFileCleared><SubjectUserName>admin</SubjectUserName></LogFileCleared></UserData></Event>\
+            </Events>";
+        let events = parse_events(xml).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["Qualifiers"], 16384);
+        assert_eq!(events[0]["LevelText"], "Information");
+        assert_eq!(
+            events[0]["EventData"],
+            json::json!({"param1": "Windows Update", "param2": "running"})
+        );
+        assert_eq!(events[1]["EventID"], 1102);
+        assert_eq!(events[1]["LevelText"], "Error");
+        assert_eq!(
+            events[1]["UserData"],
+            json::json!({"SubjectUserName": "admin"})
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse(json::json!({"message": "not xml"})).is_err());
+        assert!(parse(json::json!({"log": "<Event/>"})).is_err());
+    }
+}
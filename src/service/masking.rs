@@ -0,0 +1,150 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::HttpResponse;
+use config::{meta::stream::StreamType, utils::json};
+
+use crate::{
+    common::{
+        infra::config::MASKING_POLICIES,
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            masking::{MaskingPolicy, MaskingPolicyList, MaskingRule},
+        },
+        utils::auth::is_root_user,
+    },
+    service::{db, rbac},
+};
+
+const POLICY_NOT_FOUND: &str = "Masking policy not found";
+
+pub async fn list_policies(org_id: &str) -> Result<HttpResponse, Error> {
+    let prefix = format!("{org_id}/");
+    let mut list = MASKING_POLICIES
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix))
+        .map(|v| v.value().clone())
+        .collect::<Vec<_>>();
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(HttpResponse::Ok().json(MaskingPolicyList { list }))
+}
+
+pub async fn get_policy(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    match MASKING_POLICIES.get(&format!("{org_id}/{name}")) {
+        Some(policy) => Ok(HttpResponse::Ok().json(policy.value())),
+        None => Ok(MetaHttpResponse::not_found(POLICY_NOT_FOUND)),
+    }
+}
+
+pub async fn save_policy(org_id: &str, mut policy: MaskingPolicy) -> Result<HttpResponse, Error> {
+    policy.name = policy.name.trim().to_string();
+    if !rbac::is_valid_role_name(&policy.name) {
+        return Ok(MetaHttpResponse::bad_request(
+            "Masking policy name can only contain alphanumeric characters, '_' and '-'",
+        ));
+    }
+    if policy.stream_name.trim().is_empty() {
+        return Ok(MetaHttpResponse::bad_request("stream_name is required"));
+    }
+    if policy.rules.is_empty() || policy.rules.iter().any(|r| r.field.trim().is_empty()) {
+        return Ok(MetaHttpResponse::bad_request(
+            "Masking policy requires rules with a field",
+        ));
+    }
+    if let Err(e) = db::masking::set(org_id, &policy).await {
+        return Ok(MetaHttpResponse::internal_error(e));
+    }
+    MASKING_POLICIES.insert(format!("{org_id}/{}", policy.name), policy.clone());
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+pub async fn delete_policy(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    let key = format!("{org_id}/{name}");
+    if !MASKING_POLICIES.contains_key(&key) {
+        return Ok(MetaHttpResponse::not_found(POLICY_NOT_FOUND));
+    }
+    if let Err(e) = db::masking::delete(org_id, name).await {
+        return Ok(MetaHttpResponse::internal_error(e));
+    }
+    MASKING_POLICIES.remove(&key);
+    Ok(MetaHttpResponse::ok("Masking policy deleted"))
+}
+
+/// Returns the masking rules that apply to the user for the stream, users
+/// with one of the exempt roles of a policy see the original values.
+pub fn get_rules(
+    org_id: &str,
+    user_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Vec<MaskingRule> {
+    if is_root_user(user_id) {
+        return vec![];
+    }
+    let prefix = format!("{org_id}/");
+    let policies = MASKING_POLICIES
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix) && v.value().is_match(stream_type, stream_name))
+        .map(|v| v.value().clone())
+        .collect::<Vec<_>>();
+    if policies.is_empty() {
+        return vec![];
+    }
+    let roles = rbac::get_user_roles(org_id, user_id)
+        .into_iter()
+        .map(|role| role.name)
+        .collect::<Vec<_>>();
+    policies
+        .into_iter()
+        .filter(|policy| !policy.is_exempt(&roles))
+        .flat_map(|policy| policy.rules)
+        .collect()
+}
+
+/// Masks the hits of a search response.
+pub fn mask_hits(
+    org_id: &str,
+    user_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    hits: &mut [json::Value],
+) {
+    let rules = get_rules(org_id, user_id, stream_type, stream_name);
+    if rules.is_empty() {
+        return;
+    }
+    for hit in hits.iter_mut().filter_map(|hit| hit.as_object_mut()) {
+        rules.iter().for_each(|rule| rule.apply(hit));
+    }
+}
+
+/// Masks the hits of a `_values` response.
+pub fn mask_values(
+    org_id: &str,
+    user_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    hits: &mut [json::Value],
+) {
+    let rules = get_rules(org_id, user_id, stream_type, stream_name);
+    if rules.is_empty() {
+        return;
+    }
+    for hit in hits.iter_mut().filter_map(|hit| hit.as_object_mut()) {
+        rules.iter().for_each(|rule| rule.apply_values(hit));
+    }
+}
@@ -120,7 +120,9 @@ impl Metadata for TraceListIndex {
         }
 
         let writer = ingester::get_writer(0, org_id, &StreamType::Metadata.to_string()).await;
-        _ = ingestion::write_file(&writer, STREAM_NAME, buf).await;
+        if let Err(e) = ingestion::write_file(&writer, STREAM_NAME, buf).await {
+            log::error!("[TraceListIndex] error while writing: {}", e);
+        }
         if let Err(e) = writer.sync().await {
             log::error!("[TraceListIndex] error while syncing writer: {}", e);
         }
@@ -186,6 +188,8 @@ impl TraceListIndex {
                 data_retention: 0,
                 flatten_level: None,
                 defined_schema_fields: None,
+                flush_policy: None,
+                ..Default::default()
             };
 
             stream::save_stream_settings(org_id, STREAM_NAME, StreamType::Metadata, settings)
@@ -53,9 +53,7 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
         return Err(anyhow::anyhow!("not an ingester"));
     }
 
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if crate::service::ingestion::is_org_quota_exceeded(org_id) {
         return Err(anyhow::anyhow!(
             "Quota exceeded for this organization [{}]",
             org_id
@@ -68,6 +66,7 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
             code: http::StatusCode::SERVICE_UNAVAILABLE.into(),
             status: vec![],
             error: Some(e.to_string()),
+            degraded: false,
         });
     }
 
@@ -297,7 +296,10 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
             continue;
         }
 
-        let mut req_stats = write_file(&writer, &stream_name, stream_data).await;
+        let mut req_stats = match write_file(&writer, &stream_name, stream_data).await {
+            Ok(v) => v,
+            Err(e) => return Ok(IngestionResponse::unavailable(e)),
+        };
         req_stats.response_time = time;
         report_request_usage_stats(
             req_stats,
@@ -90,9 +90,7 @@ pub async fn metrics_json_handler(
         );
     }
 
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if crate::service::ingestion::is_org_quota_exceeded(org_id) {
         return Ok(HttpResponse::Forbidden().json(MetaHttpResponse::error(
             http::StatusCode::FORBIDDEN.into(),
             format!("Quota exceeded for this organization [{}]", org_id),
@@ -492,7 +490,17 @@ pub async fn metrics_json_handler(
         }
 
         // write to file
-        let mut req_stats = write_file(&writer, &stream_name, stream_data).await;
+        let mut req_stats = match write_file(&writer, &stream_name, stream_data).await {
+            Ok(v) => v,
+            Err(e) => {
+                return Ok(
+                    HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                        http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                        e.to_string(),
+                    )),
+                );
+            }
+        };
 
         req_stats.response_time += time;
         report_request_usage_stats(
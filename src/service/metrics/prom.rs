@@ -67,9 +67,7 @@ pub async fn remote_write(
         return Err(anyhow::anyhow!("not an ingester"));
     }
 
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if crate::service::ingestion::is_org_quota_exceeded(org_id) {
         return Err(anyhow::anyhow!(
             "Quota exceeded for this organization [{}]",
             org_id
@@ -418,7 +416,7 @@ pub async fn remote_write(
         }
 
         // write to file
-        let mut req_stats = write_file(&writer, &stream_name, stream_data).await;
+        let mut req_stats = write_file(&writer, &stream_name, stream_data).await?;
 
         let fns_length: usize = stream_transform_map.values().map(|v| v.len()).sum();
         req_stats.response_time += time;
@@ -548,6 +546,7 @@ fn get_metadata_object(schema: &Schema) -> Option<MetadataObject> {
 
 pub(crate) async fn get_series(
     org_id: &str,
+    user_id: &str,
     selector: Option<parser::VectorSelector>,
     start: i64,
     end: i64,
@@ -626,7 +625,15 @@ pub(crate) async fn get_series(
         timeout: 0,
         search_type: None,
     };
-    let series = match search_service::search("", org_id, StreamType::Metrics, None, &req).await {
+    let series = match search_service::search(
+        "",
+        org_id,
+        StreamType::Metrics,
+        Some(user_id.to_string()),
+        &req,
+    )
+    .await
+    {
         Err(err) => {
             log::error!("search series error: {err}");
             return Err(err);
@@ -688,6 +695,7 @@ pub(crate) async fn get_labels(
 // XXX-TODO: filter the results in accordance with `selector.matchers`
 pub(crate) async fn get_label_values(
     org_id: &str,
+    user_id: &str,
     label_name: String,
     selector: Option<parser::VectorSelector>,
     start: i64,
@@ -772,7 +780,15 @@ pub(crate) async fn get_label_values(
         timeout: 0,
         search_type: None,
     };
-    let mut label_values = match search_service::search("", org_id, stream_type, None, &req).await {
+    let mut label_values = match search_service::search(
+        "",
+        org_id,
+        stream_type,
+        Some(user_id.to_string()),
+        &req,
+    )
+    .await
+    {
         Ok(resp) => resp
             .hits
             .iter()
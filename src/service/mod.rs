@@ -20,26 +20,57 @@ use regex::Regex;
 
 use crate::common::meta::stream::StreamParams;
 
+pub mod admission;
 pub mod alerts;
+pub mod annotations;
+pub mod audit;
+pub mod backup;
+pub mod bucket_ingest;
+pub mod bundles;
+pub mod caches;
 pub mod compact;
 pub mod dashboards;
 pub mod db;
+pub mod derived_streams;
+pub mod dlp;
 pub mod enrichment;
 pub mod enrichment_table;
 pub mod file_list;
 pub mod functions;
+pub mod health;
+pub mod index_advisor;
 pub mod ingestion;
+pub mod ingestion_keys;
 pub mod kv;
+pub mod ldap;
+pub mod login_security;
 pub mod logs;
+pub mod masking;
 pub mod metadata;
 pub mod metrics;
+pub mod network_policy;
+pub mod node;
+#[cfg(not(feature = "enterprise"))]
+pub mod oidc;
 pub mod organization;
+pub mod patterns;
 pub mod pipelines;
 pub mod promql;
+pub mod provisioning;
+pub mod quota;
+pub mod rbac;
+pub mod runtime_config;
 pub mod schema;
+pub mod schema_changes;
+pub mod scim;
 pub mod search;
+pub mod search_templates;
+pub mod self_monitoring;
+pub mod service_accounts;
 pub mod session;
 pub mod stream;
+pub mod stream_archive;
+pub mod stream_template;
 pub mod syslogs_route;
 pub mod traces;
 pub mod usage;
@@ -0,0 +1,197 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{io::Error, net::IpAddr};
+
+use actix_web::HttpResponse;
+use config::get_config;
+
+use crate::{
+    common::{
+        infra::config::ORGANIZATION_NETWORK_POLICIES,
+        meta::{
+            audit::{AuditAction, AuditEntry, AuditResource},
+            http::HttpResponse as MetaHttpResponse,
+            ingestion::INGESTION_EP,
+            organization::OrgNetworkPolicy,
+            service_account::is_valid_ip_rule,
+        },
+        utils::auth::is_root_user,
+    },
+    service::{audit, db},
+};
+
+/// Ingestion requests are checked against the ingestion allowlist, every
+/// other request of the org against the management allowlist.
+pub fn is_ingestion_request(method: &str, path: &str) -> bool {
+    method.eq("POST")
+        && path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .is_some_and(|v| INGESTION_EP.contains(&v))
+}
+
+/// Checks a request of the user to the org against the network policy of
+/// the org, root users are never restricted so that they can't be locked out.
+pub fn is_request_allowed(
+    org_id: &str,
+    user_id: &str,
+    method: &str,
+    path: &str,
+    ip: Option<IpAddr>,
+) -> bool {
+    is_ip_allowed(org_id, user_id, is_ingestion_request(method, path), ip)
+}
+
+pub fn is_ip_allowed(org_id: &str, user_id: &str, is_ingestion: bool, ip: Option<IpAddr>) -> bool {
+    if is_root_user(user_id) {
+        return true;
+    }
+    match ORGANIZATION_NETWORK_POLICIES.get(org_id) {
+        Some(policy) => policy.is_ip_allowed(is_ingestion, ip),
+        None => true,
+    }
+}
+
+/// Writes a denied request to the audit stream of the org.
+pub async fn audit_denied(
+    org_id: &str,
+    user_id: &str,
+    method: &str,
+    path: &str,
+    ip: Option<IpAddr>,
+) {
+    if !get_config().common.audit_enabled {
+        return;
+    }
+    audit::audit(AuditEntry {
+        _timestamp: chrono::Utc::now().timestamp_micros(),
+        org_id: org_id.to_string(),
+        user_email: user_id.to_string(),
+        method: method.to_string(),
+        path: path.to_string(),
+        resource: AuditResource::Setting.to_string(),
+        resource_id: "network_policy".to_string(),
+        action: AuditAction::AccessDenied.to_string(),
+        response_code: 403,
+        client_ip: ip.map(|v| v.to_string()).unwrap_or_default(),
+        ..Default::default()
+    })
+    .await;
+}
+
+pub async fn get_policy(org_id: &str) -> Result<HttpResponse, Error> {
+    let policy = ORGANIZATION_NETWORK_POLICIES
+        .get(org_id)
+        .map(|v| v.clone())
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+/// Saves the network policy of the org, the management allowlist has to
+/// include the ip of the user setting it.
+pub async fn set_policy(
+    org_id: &str,
+    user_id: &str,
+    ip: Option<IpAddr>,
+    policy: OrgNetworkPolicy,
+) -> Result<HttpResponse, Error> {
+    if let Some(rule) = policy
+        .ingestion_allowlist
+        .iter()
+        .chain(policy.management_allowlist.iter())
+        .find(|v| !is_valid_ip_rule(v))
+    {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "Invalid ip allowlist entry: {rule}"
+        )));
+    }
+    if !is_root_user(user_id) && !policy.is_ip_allowed(false, ip) {
+        return Ok(MetaHttpResponse::bad_request(
+            "The management allowlist should include your own ip",
+        ));
+    }
+    if let Err(e) = db::organization::set_network_policy(org_id, &policy).await {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    ORGANIZATION_NETWORK_POLICIES.insert(org_id.to_string(), policy.clone());
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+pub async fn delete_policy(org_id: &str) -> Result<HttpResponse, Error> {
+    if !ORGANIZATION_NETWORK_POLICIES.contains_key(org_id) {
+        return Ok(MetaHttpResponse::not_found("Org network policy not found"));
+    }
+    if let Err(e) = db::organization::delete_network_policy(org_id).await {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    ORGANIZATION_NETWORK_POLICIES.remove(org_id);
+    Ok(MetaHttpResponse::ok("Org network policy deleted"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ingestion_request() {
+        assert!(is_ingestion_request("POST", "default/logs/_json"));
+        assert!(is_ingestion_request("POST", "default/_bulk"));
+        assert!(is_ingestion_request("POST", "default/v1/traces"));
+        assert!(!is_ingestion_request("GET", "default/logs/_json"));
+        assert!(!is_ingestion_request("POST", "default/_search"));
+        assert!(!is_ingestion_request("PUT", "default/network_policy"));
+    }
+
+    #[test]
+    fn test_is_request_allowed() {
+        let org_id = "test_network_policy_org";
+        ORGANIZATION_NETWORK_POLICIES.insert(
+            org_id.to_string(),
+            OrgNetworkPolicy {
+                ingestion_allowlist: vec!["10.0.0.0/8".to_string()],
+                management_allowlist: vec!["192.168.1.10".to_string()],
+            },
+        );
+        let agent = "10.1.2.3".parse().ok();
+        let office = "192.168.1.10".parse().ok();
+        let user = "user@example.com";
+        assert!(is_request_allowed(
+            org_id,
+            user,
+            "POST",
+            "logs/_json",
+            agent
+        ));
+        assert!(!is_request_allowed(
+            org_id,
+            user,
+            "POST",
+            "logs/_json",
+            office
+        ));
+        assert!(is_request_allowed(org_id, user, "GET", "streams", office));
+        assert!(!is_request_allowed(org_id, user, "GET", "streams", agent));
+        assert!(!is_request_allowed(org_id, user, "GET", "streams", None));
+        assert!(is_request_allowed(
+            "other_org",
+            user,
+            "GET",
+            "streams",
+            agent
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use config::{
+    cluster::{is_ingester, set_draining, LOCAL_NODE_ROLE, LOCAL_NODE_UUID},
+    get_config,
+    meta::cluster::{Node, NodeHeartbeat, NodeInfo, NodeMetrics},
+    metrics,
+};
+use hashbrown::HashMap;
+use once_cell::sync::Lazy;
+use sysinfo::{CpuExt, SystemExt};
+use tokio::sync::Mutex;
+
+use crate::{
+    common::infra::{cluster, config::VERSION},
+    job,
+    service::{db, ingestion},
+};
+
+// a node missing this many heartbeats is reported as stale
+const STALE_HEARTBEATS: i64 = 3;
+
+static STARTED_AT: Lazy<i64> = Lazy::new(|| chrono::Utc::now().timestamp_micros());
+
+// true once the node is drained
+static DRAINED: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+static INGEST_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts an ingestion request in flight, draining waits for them to finish.
+pub struct IngestGuard;
+
+impl IngestGuard {
+    pub fn new() -> Self {
+        INGEST_REQUESTS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Default for IngestGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for IngestGuard {
+    fn drop(&mut self) {
+        INGEST_REQUESTS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Drains an ingester before shutdown: it stops accepting ingestion, flushes
+/// the memtables to parquet and uploads the wal, which publishes the files to
+/// file_list. The node stays in the cluster, the caller takes it offline.
+pub async fn drain() -> Result<(), anyhow::Error> {
+    if !is_ingester(&LOCAL_NODE_ROLE) {
+        return Ok(());
+    }
+    let mut drained = DRAINED.lock().await;
+    if *drained {
+        return Ok(());
+    }
+    let start = Instant::now();
+    let deadline = start + Duration::from_secs(get_config().limit.node_drain_timeout);
+    log::info!("[NODE] start draining");
+    set_draining(true);
+
+    // routers stop sending ingestion to a node that isn't scheduled
+    if let Some(mut node) = cluster::get_node_by_uuid(&LOCAL_NODE_UUID).await {
+        node.scheduled = false;
+        if let Err(e) = cluster::update_local_node(&node).await {
+            log::error!("[NODE] unschedule node failed: {e}");
+        }
+    }
+
+    // the requests accepted before draining still write to the memtables
+    while INGEST_REQUESTS.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    ingester::flush_and_persist().await?;
+    job::files::parquet::flush_all(deadline).await?;
+    // the wal is in the object storage, the peers can drop their copy
+    ingestion::replication::release().await;
+    *drained = true;
+    log::info!("[NODE] drained, took: {} ms", start.elapsed().as_millis());
+    Ok(())
+}
+
+/// Samples the load of the local node between two heartbeats.
+pub struct MetricsCollector {
+    system: sysinfo::System,
+    cache_hits: u64,
+    cache_misses: u64,
+}
+
+impl MetricsCollector {
+    pub fn new() -> Self {
+        Self {
+            system: sysinfo::System::new(),
+            cache_hits: 0,
+            cache_misses: 0,
+        }
+    }
+
+    pub fn collect(&mut self) -> NodeMetrics {
+        let cfg = get_config();
+        // cpu usage is measured since the previous refresh
+        self.system.refresh_cpu();
+
+        let hits = metrics::QUERY_CACHE_HIT_FILES.with_label_values(&[]).get();
+        let misses = metrics::QUERY_CACHE_MISS_FILES.with_label_values(&[]).get();
+        let cache_hit_rate = cache_hit_rate(hits - self.cache_hits, misses - self.cache_misses);
+        self.cache_hits = hits;
+        self.cache_misses = misses;
+
+        NodeMetrics {
+            cpu_num: cfg.limit.cpu_num as u64,
+            cpu_usage: self.system.global_cpu_info().cpu_usage(),
+            memory_total: cfg.limit.mem_total as u64,
+            memory_usage: memory_stats::memory_stats()
+                .map(|v| v.physical_mem as u64)
+                .unwrap_or_default(),
+            wal_backlog_bytes: super::admission::wal_backlog_bytes(),
+            cache_hit_rate,
+            running_queries: metrics::QUERY_RUNNING_NUMS.with_label_values(&[]).get(),
+        }
+    }
+}
+
+impl Default for MetricsCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn cache_hit_rate(hits: u64, misses: u64) -> f64 {
+    if hits + misses == 0 {
+        return 0.0;
+    }
+    hits as f64 / (hits + misses) as f64
+}
+
+/// Publishes the load of the local node to the cluster coordinator.
+pub async fn heartbeat(collector: &mut MetricsCollector) -> Result<(), anyhow::Error> {
+    let heartbeat = NodeHeartbeat {
+        uuid: LOCAL_NODE_UUID.clone(),
+        version: VERSION.to_string(),
+        started_at: *STARTED_AT,
+        updated_at: chrono::Utc::now().timestamp_micros(),
+        metrics: collector.collect(),
+    };
+    db::node::set_heartbeat(&heartbeat).await
+}
+
+/// Lists the members of the cluster with the load of their last heartbeat.
+pub async fn list_nodes() -> Result<Vec<NodeInfo>, anyhow::Error> {
+    let heartbeats = db::node::list_heartbeats()
+        .await?
+        .into_iter()
+        .map(|v| (v.uuid.clone(), v))
+        .collect::<HashMap<_, _>>();
+    let now = chrono::Utc::now().timestamp_micros();
+    let stale_after =
+        STALE_HEARTBEATS * get_config().limit.node_heartbeat_interval as i64 * 1_000_000;
+    let mut nodes = cluster::get_cached_nodes(|_| true)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|node| {
+            let heartbeat = heartbeats.get(&node.uuid);
+            node_info(node, heartbeat, now, stale_after)
+        })
+        .collect::<Vec<_>>();
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(nodes)
+}
+
+fn node_info(
+    node: Node,
+    heartbeat: Option<&NodeHeartbeat>,
+    now: i64,
+    stale_after: i64,
+) -> NodeInfo {
+    let (version, uptime, last_heartbeat, metrics) = match heartbeat {
+        Some(v) => (
+            v.version.clone(),
+            (now - v.started_at) / 1_000_000,
+            v.updated_at,
+            v.metrics.clone(),
+        ),
+        None => (String::new(), 0, 0, NodeMetrics::default()),
+    };
+    NodeInfo {
+        uuid: node.uuid,
+        name: node.name,
+        http_addr: node.http_addr,
+        grpc_addr: node.grpc_addr,
+        role: node.role,
+        zone: node.zone,
+        region: node.region,
+        status: node.status,
+        scheduled: node.scheduled,
+        version,
+        uptime,
+        last_heartbeat,
+        stale: now - last_heartbeat > stale_after,
+        metrics,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hit_rate() {
+        assert_eq!(cache_hit_rate(0, 0), 0.0);
+        assert_eq!(cache_hit_rate(3, 1), 0.75);
+    }
+
+    #[test]
+    fn test_node_info() {
+        let node = Node {
+            uuid: "node1".to_string(),
+            name: "node1".to_string(),
+            ..Default::default()
+        };
+        let heartbeat = NodeHeartbeat {
+            uuid: "node1".to_string(),
+            version: "v1".to_string(),
+            started_at: 0,
+            updated_at: 50_000_000,
+            metrics: NodeMetrics::default(),
+        };
+        let info = node_info(node.clone(), Some(&heartbeat), 60_000_000, 30_000_000);
+        assert_eq!(info.version, "v1");
+        assert_eq!(info.uptime, 60);
+        assert!(!info.stale);
+
+        let info = node_info(node.clone(), Some(&heartbeat), 90_000_000, 30_000_000);
+        assert!(info.stale);
+
+        let info = node_info(node, None, 60_000_000, 30_000_000);
+        assert_eq!(info.last_heartbeat, 0);
+        assert!(info.stale);
+    }
+}
@@ -0,0 +1,450 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! OpenID Connect login using the authorization code flow with PKCE.
+
+use std::collections::HashMap;
+
+use config::{get_config, utils::rand::generate_random_string};
+use infra::errors::JwtError;
+use jsonwebtoken::TokenData;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use crate::{
+    common::{
+        meta::{
+            rbac::ROLE_ADMIN,
+            user::{DBUser, TokenValidationResponse, UserOrg, UserRole},
+        },
+        utils::jwt::verify_decode_token,
+    },
+    handler::http::auth::validator::PKCE_STATE_ORG,
+    service::{db, kv, rbac, users},
+};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    #[serde(default)]
+    pub end_session_endpoint: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub id_token: Option<String>,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+}
+
+/// A group mapping rule, `group` supports `*` as wildcard.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupRule {
+    pub group: String,
+    pub org: String,
+    pub role: String,
+}
+
+static PROVIDER: Lazy<RwLock<Option<ProviderMetadata>>> = Lazy::new(|| RwLock::new(None));
+// (fetched_at in seconds, jwks json)
+static JWKS: Lazy<RwLock<(i64, String)>> = Lazy::new(|| RwLock::new((0, String::new())));
+
+pub async fn get_provider() -> Result<ProviderMetadata, anyhow::Error> {
+    if let Some(provider) = PROVIDER.read().await.as_ref() {
+        return Ok(provider.clone());
+    }
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        get_config().oidc.issuer_url
+    );
+    let provider: ProviderMetadata = reqwest::get(&url).await?.error_for_status()?.json().await?;
+    *PROVIDER.write().await = Some(provider.clone());
+    Ok(provider)
+}
+
+/// Returns the JWKS of the provider, the keys are fetched again once the cache
+/// is expired or when `force` is set, e.g. the provider rotated its keys.
+pub async fn get_jwks(force: bool) -> Result<String, anyhow::Error> {
+    let now = chrono::Utc::now().timestamp();
+    {
+        let r = JWKS.read().await;
+        if !force && !r.1.is_empty() && now - r.0 < get_config().oidc.jwks_cache_ttl {
+            return Ok(r.1.clone());
+        }
+    }
+    let provider = get_provider().await?;
+    let jwks = reqwest::get(&provider.jwks_uri)
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    *JWKS.write().await = (now, jwks.clone());
+    Ok(jwks)
+}
+
+/// Verifies the signature, expiry and audience of a token issued by the
+/// provider.
+pub async fn verify_token(
+    token: &str,
+) -> Result<
+    (
+        TokenValidationResponse,
+        Option<TokenData<HashMap<String, Value>>>,
+    ),
+    anyhow::Error,
+> {
+    let client_id = &get_config().oidc.client_id;
+    let issuer = get_provider().await?.issuer;
+    let keys = get_jwks(false).await?;
+    match verify_decode_token(token, &keys, client_id, Some(&issuer), true).await {
+        Err(e) if matches!(e.downcast_ref::<JwtError>(), Some(JwtError::KeyNotExists())) => {
+            // the provider may have rotated its keys
+            let keys = get_jwks(true).await?;
+            verify_decode_token(token, &keys, client_id, Some(&issuer), true).await
+        }
+        res => res,
+    }
+}
+
+/// Builds the authorization url, the PKCE verifier is kept in the
+/// coordinator under the returned state until the provider redirects back.
+pub async fn get_login_url() -> Result<String, anyhow::Error> {
+    let cfg = get_config();
+    let provider = get_provider().await?;
+    let state = generate_random_string(32);
+    let verifier = generate_random_string(64);
+    kv::set(PKCE_STATE_ORG, &state, verifier.clone().into()).await?;
+
+    let mut url = url::Url::parse(&provider.authorization_endpoint)?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &cfg.oidc.client_id)
+        .append_pair("redirect_uri", &cfg.oidc.redirect_url)
+        .append_pair("scope", &cfg.oidc.scopes)
+        .append_pair("state", &state)
+        .append_pair("code_challenge", &pkce_challenge(&verifier))
+        .append_pair("code_challenge_method", "S256");
+    Ok(url.to_string())
+}
+
+/// Exchanges the authorization code, `state` must be the one issued by
+/// [`get_login_url`] and can only be used once.
+pub async fn exchange_code(code: &str, state: &str) -> Result<TokenResponse, anyhow::Error> {
+    let verifier = match kv::get(PKCE_STATE_ORG, state).await {
+        Ok(v) => String::from_utf8(v.to_vec())?,
+        Err(_) => return Err(anyhow::anyhow!("invalid state in request")),
+    };
+    let _ = kv::delete(PKCE_STATE_ORG, state).await;
+
+    let cfg = get_config();
+    request_token(&[
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", cfg.oidc.redirect_url.as_str()),
+        ("code_verifier", verifier.as_str()),
+    ])
+    .await
+}
+
+pub async fn refresh_token(refresh_token: &str) -> Result<TokenResponse, anyhow::Error> {
+    request_token(&[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+    ])
+    .await
+}
+
+async fn request_token(params: &[(&str, &str)]) -> Result<TokenResponse, anyhow::Error> {
+    let cfg = get_config();
+    let provider = get_provider().await?;
+    let mut form = params.to_vec();
+    form.push(("client_id", cfg.oidc.client_id.as_str()));
+    if !cfg.oidc.client_secret.is_empty() {
+        form.push(("client_secret", cfg.oidc.client_secret.as_str()));
+    }
+    let resp = reqwest::Client::new()
+        .post(&provider.token_endpoint)
+        .form(&form)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        return Err(anyhow::anyhow!("token request failed: {status} {body}"));
+    }
+    Ok(resp.json().await?)
+}
+
+fn pkce_challenge(verifier: &str) -> String {
+    use base64::Engine;
+
+    let digest = hex::decode(sha256::digest(verifier)).unwrap();
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest)
+}
+
+/// Parses rules like `sre=default:admin,dev*=default:editor`.
+pub fn parse_group_mapping(rules: &str) -> Vec<GroupRule> {
+    rules
+        .split(',')
+        .filter_map(|rule| {
+            let (group, target) = rule.trim().split_once('=')?;
+            let (org, role) = target.split_once(':')?;
+            if group.trim().is_empty() || org.trim().is_empty() || role.trim().is_empty() {
+                log::warn!("invalid group mapping rule: {rule}");
+                return None;
+            }
+            Some(GroupRule {
+                group: group.trim().to_string(),
+                org: org.trim().to_string(),
+                role: role.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Maps the groups of a user to the `(org, role)` pairs, the first matching
+/// rule wins for a given org.
+pub fn map_groups(
+    groups: &[String],
+    rules: &[GroupRule],
+    default_org: &str,
+    default_role: &str,
+) -> Vec<(String, String)> {
+    let mut orgs: Vec<(String, String)> = vec![];
+    for rule in rules {
+        if orgs.iter().any(|(org, _)| org.eq(&rule.org)) {
+            continue;
+        }
+        if groups
+            .iter()
+            .any(|g| crate::common::meta::rbac::wildcard_match(&rule.group, g))
+        {
+            orgs.push((rule.org.clone(), rule.role.clone()));
+        }
+    }
+    if orgs.is_empty() && !default_org.is_empty() {
+        orgs.push((default_org.to_string(), default_role.to_string()));
+    }
+    orgs
+}
+
+/// Creates the user of a verified token on first login and assigns it to the
+/// orgs and roles resolved from its group claim.
+pub async fn process_token(
+    res: &(
+        TokenValidationResponse,
+        Option<TokenData<HashMap<String, Value>>>,
+    ),
+) -> Result<(), anyhow::Error> {
+    let cfg = get_config();
+    let user_email = &res.0.user_email;
+    if user_email.is_empty() {
+        return Err(anyhow::anyhow!("email claim is missing in token"));
+    }
+    let existing = db::user::get_user_by_email(user_email).await;
+    if check_existing_user(user_email, existing.as_ref(), is_email_verified(res))? {
+        return Ok(());
+    }
+
+    let groups = res
+        .1
+        .as_ref()
+        .and_then(|token| token.claims.get(&cfg.oidc.group_claim))
+        .and_then(|v| v.as_array())
+        .map(|v| {
+            v.iter()
+                .filter_map(|g| g.as_str().map(|g| g.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let orgs = map_groups(
+        &groups,
+        &parse_group_mapping(&cfg.oidc.group_mapping),
+        &cfg.oidc.default_org,
+        &cfg.oidc.default_role,
+    );
+    if orgs.is_empty() {
+        return Err(anyhow::anyhow!(
+            "user {user_email} does not match any group mapping"
+        ));
+    }
+
+    let db_user = DBUser {
+        email: user_email.to_owned(),
+        first_name: res.0.given_name.to_owned(),
+        last_name: res.0.family_name.to_owned(),
+        password: "".to_owned(),
+        salt: "".to_owned(),
+        organizations: orgs
+            .iter()
+            .map(|(org, role)| UserOrg {
+                name: org.to_owned(),
+                role: if role.eq(ROLE_ADMIN) {
+                    UserRole::Admin
+                } else {
+                    UserRole::Member
+                },
+                ..UserOrg::default()
+            })
+            .collect(),
+        is_external: true,
+        password_ext: Some("".to_owned()),
+        password_updated_at: 0,
+        force_password_reset: false,
+    };
+    users::update_db_user(db_user).await?;
+    for (org, role) in orgs.iter() {
+        if let Err(e) = rbac::add_user_to_role(org, role, user_email).await {
+            log::error!("Error assigning role {role} in org {org} to {user_email}: {e}");
+        }
+    }
+    log::info!("User {user_email} created from oidc login");
+    Ok(())
+}
+
+/// Whether the identity provider verified the email of the token.
+pub fn is_email_verified(
+    res: &(
+        TokenValidationResponse,
+        Option<TokenData<HashMap<String, Value>>>,
+    ),
+) -> bool {
+    res.1
+        .as_ref()
+        .and_then(|token| token.claims.get("email_verified"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Whether the token is linked to an existing user. Only the external users
+/// are linked, and only when the identity provider verified the email, so an
+/// account of the provider can't take over a local user or the root user.
+pub fn check_existing_user(
+    user_email: &str,
+    existing: Option<&DBUser>,
+    email_verified: bool,
+) -> Result<bool, anyhow::Error> {
+    let Some(existing) = existing else {
+        return Ok(false);
+    };
+    if !existing.is_external {
+        return Err(anyhow::anyhow!("a local user {user_email} already exists"));
+    }
+    if !email_verified {
+        return Err(anyhow::anyhow!(
+            "the email {user_email} is not verified by the identity provider"
+        ));
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_group_mapping() {
+        let rules = parse_group_mapping("sre=default:admin, dev*=default:editor,invalid,=a:b");
+        assert_eq!(
+            rules,
+            vec![
+                GroupRule {
+                    group: "sre".to_string(),
+                    org: "default".to_string(),
+                    role: "admin".to_string(),
+                },
+                GroupRule {
+                    group: "dev*".to_string(),
+                    org: "default".to_string(),
+                    role: "editor".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_map_groups() {
+        let rules = parse_group_mapping("sre=default:admin,dev*=default:editor,dev*=team:viewer");
+        let groups = vec!["dev-backend".to_string()];
+        assert_eq!(
+            map_groups(&groups, &rules, "", "viewer"),
+            vec![
+                ("default".to_string(), "editor".to_string()),
+                ("team".to_string(), "viewer".to_string()),
+            ]
+        );
+        let groups = vec!["sre".to_string(), "dev".to_string()];
+        assert_eq!(
+            map_groups(&groups, &rules, "", "viewer"),
+            vec![
+                ("default".to_string(), "admin".to_string()),
+                ("team".to_string(), "viewer".to_string()),
+            ]
+        );
+        assert!(map_groups(&[], &rules, "", "viewer").is_empty());
+        assert_eq!(
+            map_groups(&[], &rules, "default", "viewer"),
+            vec![("default".to_string(), "viewer".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        // from RFC 7636 appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gXFI8TfEY"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGI1ljkZvM"
+        );
+    }
+
+    fn db_user(is_external: bool) -> DBUser {
+        DBUser {
+            email: "john@example.com".to_string(),
+            first_name: "".to_string(),
+            last_name: "".to_string(),
+            password: "".to_string(),
+            salt: "".to_string(),
+            organizations: vec![],
+            is_external,
+            password_ext: None,
+            password_updated_at: 0,
+            force_password_reset: false,
+        }
+    }
+
+    #[test]
+    fn test_check_existing_user_local() {
+        let email = "john@example.com";
+        assert!(check_existing_user(email, Some(&db_user(false)), true).is_err());
+        assert!(check_existing_user(email, Some(&db_user(false)), false).is_err());
+    }
+
+    #[test]
+    fn test_check_existing_user_email_verified() {
+        let email = "john@example.com";
+        assert!(check_existing_user(email, Some(&db_user(true)), false).is_err());
+        assert!(check_existing_user(email, Some(&db_user(true)), true).unwrap());
+        assert!(!check_existing_user(email, None, false).unwrap());
+    }
+}
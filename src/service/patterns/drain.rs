@@ -0,0 +1,193 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Drain style log template mining. The lines are split into tokens, the
+//! tokens holding digits are masked as variables, and every line joins the
+//! most similar template with the same length and leading tokens, the tokens
+//! differing between a template and its lines become wildcards.
+
+use hashbrown::HashMap;
+
+pub const WILDCARD: &str = "<*>";
+
+/// number of leading tokens used to pick the candidate templates of a line
+const PREFIX_DEPTH: usize = 2;
+
+#[derive(Clone, Debug)]
+pub struct Cluster {
+    pub tokens: Vec<String>,
+    pub count: u64,
+    pub exemplar: String,
+}
+
+impl Cluster {
+    pub fn template(&self) -> String {
+        self.tokens.join(" ")
+    }
+
+    /// share of the tokens of the template equal to the tokens of the line
+    fn similarity(&self, tokens: &[String]) -> f64 {
+        let same = self
+            .tokens
+            .iter()
+            .zip(tokens)
+            .filter(|(a, b)| a == b)
+            .count();
+        same as f64 / self.tokens.len() as f64
+    }
+
+    fn merge(&mut self, tokens: &[String]) {
+        for (t, token) in self.tokens.iter_mut().zip(tokens) {
+            if t != token {
+                *t = WILDCARD.to_string();
+            }
+        }
+        self.count += 1;
+    }
+}
+
+pub struct Drain {
+    similarity: f64,
+    max_clusters: usize,
+    clusters: Vec<Cluster>,
+    leaves: HashMap<(usize, String), Vec<usize>>,
+}
+
+impl Drain {
+    /// `similarity` is the share of the tokens, from 0 to 1, a line must have
+    /// in common with a template to join it.
+    pub fn new(similarity: f64, max_clusters: usize) -> Self {
+        Self {
+            similarity,
+            max_clusters,
+            clusters: Vec::new(),
+            leaves: HashMap::new(),
+        }
+    }
+
+    /// Adds the line to its template, returns the index of the template or
+    /// None when the line is empty or the templates are full.
+    pub fn add(&mut self, line: &str) -> Option<usize> {
+        let tokens = tokenize(line);
+        if tokens.is_empty() {
+            return None;
+        }
+        let key = (
+            tokens.len(),
+            tokens[..tokens.len().min(PREFIX_DEPTH)].join(" "),
+        );
+        let leaf = self.leaves.entry(key).or_default();
+
+        let mut best: Option<(usize, f64)> = None;
+        for &id in leaf.iter() {
+            let sim = self.clusters[id].similarity(&tokens);
+            if sim >= self.similarity && best.map_or(true, |(_, s)| sim > s) {
+                best = Some((id, sim));
+            }
+        }
+        if let Some((id, _)) = best {
+            self.clusters[id].merge(&tokens);
+            return Some(id);
+        }
+
+        if self.clusters.len() >= self.max_clusters {
+            return None;
+        }
+        let id = self.clusters.len();
+        self.clusters.push(Cluster {
+            tokens,
+            count: 1,
+            exemplar: line.to_string(),
+        });
+        leaf.push(id);
+        Some(id)
+    }
+
+    pub fn clusters(&self) -> &[Cluster] {
+        &self.clusters
+    }
+
+    pub fn into_clusters(self) -> Vec<Cluster> {
+        self.clusters
+    }
+}
+
+/// Splits the line on whitespace and masks the variable tokens.
+pub fn tokenize(line: &str) -> Vec<String> {
+    line.split_whitespace().map(mask_token).collect()
+}
+
+/// The template of a single line, without mining it against other lines.
+pub fn template(line: &str) -> String {
+    tokenize(line).join(" ")
+}
+
+/// Masks the tokens holding digits, such as numbers, ids, ips and dates, the
+/// key of a `key=value` token is kept.
+fn mask_token(token: &str) -> String {
+    if let Some((key, value)) = token.split_once('=') {
+        if !key.is_empty() && !key.bytes().any(|c| c.is_ascii_digit()) {
+            return format!("{key}={}", mask_token(value));
+        }
+    }
+    if token.bytes().any(|c| c.is_ascii_digit()) {
+        WILDCARD.to_string()
+    } else {
+        token.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        assert_eq!(
+            template("user=42 connected from 10.0.0.1 in  12ms"),
+            "user=<*> connected from <*> in <*>"
+        );
+        assert_eq!(template("GET /api/health"), "GET /api/health");
+        assert_eq!(template("   "), "");
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut drain = Drain::new(0.5, 10);
+        let a = drain.add("Connection closed by alice port 22").unwrap();
+        let b = drain.add("Connection closed by bob port 2222").unwrap();
+        let c = drain.add("Disk /dev/sda is full").unwrap();
+        let d = drain.add("Connection closed").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, d);
+        assert_eq!(drain.add(""), None);
+
+        let clusters = drain.clusters();
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(clusters[a].template(), "Connection closed by <*> port <*>");
+        assert_eq!(clusters[a].count, 2);
+        assert_eq!(clusters[a].exemplar, "Connection closed by alice port 22");
+    }
+
+    #[test]
+    fn test_drain_max_clusters() {
+        let mut drain = Drain::new(0.5, 1);
+        assert_eq!(drain.add("first template"), Some(0));
+        assert_eq!(drain.add("other line here"), None);
+        assert_eq!(drain.add("first template"), Some(0));
+        assert_eq!(drain.clusters()[0].count, 2);
+    }
+}
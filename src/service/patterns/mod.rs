@@ -0,0 +1,174 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Mines the log templates of the logs streams, the templates of every run are
+//! ingested into the `<stream>_patterns` stream of the stream with their
+//! count and an exemplar line.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use config::{
+    get_config,
+    meta::stream::StreamType,
+    utils::json::{self, Value},
+};
+use proto::cluster_rpc;
+
+use crate::service::{db, search as SearchService, usage::ingestion_service};
+
+pub mod drain;
+
+pub const PATTERNS_STREAM_SUFFIX: &str = "_patterns";
+
+pub fn patterns_stream_name(stream_name: &str) -> String {
+    format!("{stream_name}{PATTERNS_STREAM_SUFFIX}")
+}
+
+/// Mines the records of all the logs streams between `start_time` and
+/// `end_time`.
+pub async fn mine(start_time: i64, end_time: i64) {
+    for org_id in db::schema::list_organizations_from_cache().await {
+        let streams = db::schema::list_streams_from_cache(&org_id, StreamType::Logs).await;
+        for stream_name in streams {
+            if stream_name.ends_with(PATTERNS_STREAM_SUFFIX) {
+                continue;
+            }
+            if let Err(e) = mine_stream(&org_id, &stream_name, start_time, end_time).await {
+                log::error!("[PATTERNS] mine {org_id}/{stream_name} error: {e}");
+            }
+        }
+    }
+}
+
+pub async fn mine_stream(
+    org_id: &str,
+    stream_name: &str,
+    start_time: i64,
+    end_time: i64,
+) -> Result<usize, anyhow::Error> {
+    let cfg = get_config();
+    let query = config::meta::search::Query {
+        sql: format!(
+            "SELECT * FROM \"{stream_name}\" ORDER BY {} DESC",
+            cfg.common.column_timestamp
+        ),
+        from: 0,
+        size: cfg.patterns.sample_size,
+        start_time,
+        end_time,
+        ..Default::default()
+    };
+    let req = config::meta::search::Request {
+        query,
+        aggs: HashMap::new(),
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+    };
+    let trace_id = config::ider::uuid();
+    let hits = SearchService::search_as_system(&trace_id, org_id, StreamType::Logs, &req)
+        .await?
+        .hits;
+
+    let fields = cfg
+        .patterns
+        .fields
+        .split(',')
+        .map(|f| f.trim())
+        .filter(|f| !f.is_empty())
+        .collect::<Vec<_>>();
+    let mut drain = drain::Drain::new(
+        cfg.patterns.similarity as f64 / 100.0,
+        cfg.patterns.max_clusters,
+    );
+    for hit in hits.iter() {
+        if let Some(line) = log_line(hit, &fields) {
+            drain.add(line);
+        }
+    }
+
+    let clusters = drain.into_clusters();
+    if clusters.is_empty() {
+        return Ok(0);
+    }
+    let records = clusters
+        .iter()
+        .map(|c| {
+            let template = c.template();
+            let mut record = json::Map::new();
+            record.insert(
+                cfg.common.column_timestamp.clone(),
+                Value::Number(end_time.into()),
+            );
+            record.insert(
+                "pattern_id".to_string(),
+                Value::String(pattern_id(&template)),
+            );
+            record.insert("template".to_string(), Value::String(template));
+            record.insert("count".to_string(), Value::Number(c.count.into()));
+            record.insert("exemplar".to_string(), Value::String(c.exemplar.clone()));
+            record.insert("stream".to_string(), Value::String(stream_name.to_string()));
+            Value::Object(record)
+        })
+        .collect::<Vec<_>>();
+    let num = records.len();
+    let req = cluster_rpc::UsageRequest {
+        stream_name: patterns_stream_name(stream_name),
+        data: Some(cluster_rpc::UsageData::from(records)),
+    };
+    ingestion_service::ingest(org_id, req).await?;
+    log::info!(
+        "[PATTERNS] mined {num} templates from {} records of {org_id}/{stream_name}",
+        hits.len()
+    );
+    Ok(num)
+}
+
+/// The first of the fields present in the record as a string.
+fn log_line<'a>(record: &'a Value, fields: &[&str]) -> Option<&'a str> {
+    fields
+        .iter()
+        .find_map(|f| record.get(*f).and_then(|v| v.as_str()))
+}
+
+/// A stable id of the template, for following it across the runs.
+fn pattern_id(template: &str) -> String {
+    blake3::hash(template.as_bytes()).to_hex()[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_line() {
+        let record = json::json!({"message": "hello", "log": 1, "body": "world"});
+        assert_eq!(
+            log_line(&record, &["log", "message", "body"]),
+            Some("hello")
+        );
+        assert_eq!(log_line(&record, &["level"]), None);
+    }
+
+    #[test]
+    fn test_pattern_id() {
+        assert_eq!(pattern_id("a <*>"), pattern_id("a <*>"));
+        assert_ne!(pattern_id("a <*>"), pattern_id("b <*>"));
+        assert_eq!(pattern_id("a <*>").len(), 16);
+    }
+}
@@ -19,19 +19,30 @@ use actix_web::{
     http::{self, StatusCode},
     HttpResponse,
 };
-use config::meta::stream::StreamType;
+use config::{
+    get_config,
+    meta::stream::StreamType,
+    utils::{flatten, json},
+};
 
-use super::db;
+use super::{db, ingestion::pipeline::StreamPipeline};
 use crate::common::{
     infra::config::STREAM_FUNCTIONS,
     meta::{
         http::HttpResponse as MetaHttpResponse,
-        pipelines::{PipeLine, PipeLineList},
+        pipelines::{
+            PipeLine, PipeLineList, PipelineTestOutput, PipelineTestRequest, PipelineTestResponse,
+            PipelineVersionList,
+        },
     },
 };
 
 #[tracing::instrument(skip(pipeline))]
-pub async fn save_pipeline(org_id: String, pipeline: PipeLine) -> Result<HttpResponse, Error> {
+pub async fn save_pipeline(org_id: String, mut pipeline: PipeLine) -> Result<HttpResponse, Error> {
+    if let Err(e) = StreamPipeline::new(&org_id, &pipeline) {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    pipeline.version = 1;
     if let Some(_existing_pipeline) = check_existing_pipeline(
         &org_id,
         pipeline.stream_type,
@@ -63,8 +74,11 @@ pub async fn save_pipeline(org_id: String, pipeline: PipeLine) -> Result<HttpRes
 pub async fn update_pipeline(
     org_id: &str,
     pipeline_name: &str,
-    pipeline: PipeLine,
+    mut pipeline: PipeLine,
 ) -> Result<HttpResponse, Error> {
+    if let Err(e) = StreamPipeline::new(org_id, &pipeline) {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
     let existing_pipeline = match check_existing_pipeline(
         org_id,
         pipeline.stream_type,
@@ -81,10 +95,21 @@ pub async fn update_pipeline(
             )));
         }
     };
+    pipeline.version = existing_pipeline.version;
     if pipeline.eq(&existing_pipeline) {
         return Ok(HttpResponse::Ok().json(pipeline));
     }
 
+    // keep the replaced version
+    if let Err(error) = db::pipelines::set_version(org_id, &existing_pipeline).await {
+        return Ok(
+            HttpResponse::InternalServerError().json(MetaHttpResponse::message(
+                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
+                error.to_string(),
+            )),
+        );
+    }
+    pipeline.version = existing_pipeline.version + 1;
     if let Err(error) = db::pipelines::set(org_id, &pipeline.name, &pipeline).await {
         return Ok(
             HttpResponse::InternalServerError().json(MetaHttpResponse::message(
@@ -153,6 +178,83 @@ pub async fn delete_pipeline(
     }
 }
 
+#[tracing::instrument]
+pub async fn list_pipeline_versions(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    pipeline_name: &str,
+) -> Result<HttpResponse, Error> {
+    match db::pipelines::list_versions(org_id, stream_type, stream_name, pipeline_name).await {
+        Ok(list) => Ok(HttpResponse::Ok().json(PipelineVersionList { list })),
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(MetaHttpResponse::message(
+                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
+                e.to_string(),
+            )),
+        ),
+    }
+}
+
+/// Applies the steps of the pipeline to the records without ingesting them,
+/// the functions of the destination streams are not applied.
+#[tracing::instrument(skip(req))]
+pub async fn test_pipeline(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    pipeline_name: &str,
+    req: PipelineTestRequest,
+) -> Result<HttpResponse, Error> {
+    let pipeline = match req.pipeline {
+        Some(mut pipeline) => {
+            pipeline.stream_name = stream_name.to_string();
+            pipeline.stream_type = stream_type;
+            pipeline
+        }
+        None => {
+            match check_existing_pipeline(org_id, stream_type, stream_name, pipeline_name).await {
+                Some(pipeline) => pipeline,
+                None => {
+                    return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+                        StatusCode::NOT_FOUND.into(),
+                        "Pipeline not found".to_string(),
+                    )));
+                }
+            }
+        }
+    };
+    let pipeline = match StreamPipeline::new(org_id, &pipeline) {
+        Ok(v) => v.dry_run(),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+
+    let cfg = get_config();
+    let mut runtime = super::ingestion::init_functions_runtime();
+    let mut results = Vec::with_capacity(req.records.len());
+    for record in req.records {
+        let record = match flatten::flatten_with_level(record, cfg.limit.ingest_flatten_level) {
+            Ok(v @ json::Value::Object(_)) => v,
+            _ => {
+                results.push(vec![]);
+                continue;
+            }
+        };
+        let outputs = pipeline.execute(&mut runtime, org_id, record).await;
+        results.push(
+            outputs
+                .into_iter()
+                .map(|output| PipelineTestOutput {
+                    org_id: output.org_id,
+                    stream_name: output.stream_name,
+                    record: output.value,
+                })
+                .collect(),
+        );
+    }
+    Ok(HttpResponse::Ok().json(PipelineTestResponse { results }))
+}
+
 async fn check_existing_pipeline(
     org_id: &str,
     stream_type: StreamType,
@@ -176,7 +176,7 @@ async fn get_file_list(
     filters: &[(&str, Vec<String>)],
 ) -> Result<Vec<FileKey>> {
     let (time_min, time_max) = time_range;
-    let results = match file_list::query(
+    let results = match file_list::query_for_search(
         org_id,
         stream_name,
         StreamType::Metrics,
@@ -19,6 +19,7 @@ use arrow::{ipc::reader::StreamReader, record_batch::RecordBatch};
 use config::{
     get_config,
     meta::{
+        cluster::{GRPC_MIN_VERSION, GRPC_VERSION_HEADER},
         search::{ScanStats, SearchType, Session as SearchSession, StorageType},
         stream::StreamType,
     },
@@ -37,14 +38,16 @@ use proto::cluster_rpc;
 use tonic::{
     codec::CompressionEncoding,
     metadata::{MetadataKey, MetadataValue},
-    transport::Channel,
     Request,
 };
 use tracing::{info_span, Instrument};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
-    common::infra::cluster::{get_cached_online_ingester_nodes, get_internal_grpc_token},
+    common::infra::{
+        cluster::{get_cached_online_ingester_nodes, get_internal_grpc_token},
+        tls,
+    },
     service::search::{
         datafusion::exec::{prepare_datafusion_context, register_table},
         MetadataMap,
@@ -255,7 +258,11 @@ async fn get_file_list(
                 let token: MetadataValue<_> = get_internal_grpc_token()
                     .parse()
                     .map_err(|_| DataFusionError::Execution("invalid token".to_string()))?;
-                let channel = Channel::from_shared(node_addr)
+                let grpc_version: MetadataValue<_> = node
+                    .negotiate_grpc_version()
+                    .unwrap_or(GRPC_MIN_VERSION)
+                    .into();
+                let channel = tls::grpc_endpoint(node_addr)
                     .unwrap()
                     .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
                     .connect()
@@ -267,6 +274,8 @@ async fn get_file_list(
                     channel,
                     move |mut req: Request<()>| {
                         req.metadata_mut().insert("authorization", token.clone());
+                        req.metadata_mut()
+                            .insert(GRPC_VERSION_HEADER, grpc_version.clone());
                         req.metadata_mut()
                             .insert(org_header_key.clone(), org_id.clone());
                         Ok(req)
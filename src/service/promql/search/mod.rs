@@ -21,6 +21,7 @@ use std::{
 use config::{
     ider,
     meta::{
+        cluster::{GRPC_MIN_VERSION, GRPC_VERSION_HEADER},
         search::ScanStats,
         stream::StreamType,
         usage::{RequestStats, UsageType},
@@ -33,14 +34,13 @@ use proto::cluster_rpc;
 use tonic::{
     codec::CompressionEncoding,
     metadata::{MetadataKey, MetadataValue},
-    transport::Channel,
     Request,
 };
 use tracing::{info_span, Instrument};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
-    common::infra::cluster,
+    common::infra::{cluster, tls},
     service::{
         promql::{micros, value::*, MetricsQueryRequest, DEFAULT_LOOKBACK},
         search::{server_internal_error, MetadataMap},
@@ -174,7 +174,11 @@ async fn search_in_cluster(
                 let token: MetadataValue<_> = cluster::get_internal_grpc_token()
                     .parse()
                     .map_err(|_| Error::Message("invalid token".to_string()))?;
-                let channel = Channel::from_shared(node_addr)
+                let grpc_version: MetadataValue<_> = node
+                    .negotiate_grpc_version()
+                    .unwrap_or(GRPC_MIN_VERSION)
+                    .into();
+                let channel = tls::grpc_endpoint(node_addr)
                     .unwrap()
                     .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
                     .connect()
@@ -192,6 +196,8 @@ async fn search_in_cluster(
                         channel,
                         move |mut req: Request<()>| {
                         req.metadata_mut().insert("authorization", token.clone());
+                        req.metadata_mut()
+                            .insert(GRPC_VERSION_HEADER, grpc_version.clone());
                         req.metadata_mut()
                             .insert(org_header_key.clone(), org_id.clone());
                         Ok(req)
@@ -0,0 +1,362 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Provisioning as code: the dashboards, alerts, functions and streams are
+//! reconciled with their definitions, YAML or JSON files read from a local
+//! directory or an object store prefix, one subdirectory per organization.
+//!
+//! Every reconciliation reports the drift of the defined resources, and
+//! unless it is a dry run, applies the definitions of the drifted ones. The
+//! resources without a definition are left untouched.
+
+use std::collections::HashMap;
+
+use config::{
+    get_config,
+    utils::{file::scan_files, json},
+};
+
+use crate::{
+    common::meta::{
+        bundles::{Bundle, ConflictStrategy, ImportAction, ImportResult, BUNDLE_VERSION},
+        dashboards::{v1, v2, v3, versions, DashboardVersion},
+        provisioning::{self, DriftItem, DriftState, ProvisioningReport, Resource},
+    },
+    service::{bundles, db, stream},
+};
+
+const EXTENSIONS: [&str; 3] = ["yaml", "yml", "json"];
+
+/// Reconciles every organization of the source.
+pub async fn run() {
+    let cfg = get_config();
+    let sources = match load(None).await {
+        Ok(sources) => sources,
+        Err(e) => {
+            log::error!("[PROVISIONING] failed to read the definitions: {e}");
+            return;
+        }
+    };
+    for (org_id, (resources, errors)) in sources {
+        let report = reconcile(&org_id, resources, errors, cfg.provisioning.dry_run).await;
+        let drifted = report
+            .drift
+            .iter()
+            .filter(|d| d.state != DriftState::InSync)
+            .count();
+        log::info!(
+            "[PROVISIONING] org {org_id}: {} resources, {drifted} drifted, {} errors",
+            report.drift.len(),
+            report.errors.len()
+        );
+    }
+}
+
+/// Reconciles the organization now, for the API.
+pub async fn sync(org_id: &str, dry_run: bool) -> Result<ProvisioningReport, anyhow::Error> {
+    let (resources, errors) = load(Some(org_id)).await?.remove(org_id).unwrap_or_default();
+    Ok(reconcile(org_id, resources, errors, dry_run).await)
+}
+
+/// Reads the definitions of the source, grouped by organization with the
+/// errors of the files that couldn't be read.
+async fn load(
+    org_filter: Option<&str>,
+) -> Result<HashMap<String, (Vec<Resource>, Vec<String>)>, anyhow::Error> {
+    let mut sources: HashMap<String, (Vec<Resource>, Vec<String>)> = HashMap::new();
+    for (path, data) in read_source(org_filter).await? {
+        let Some(org_id) = provisioning::org_of(&path) else {
+            log::warn!("[PROVISIONING] {path} is not in an organization directory");
+            continue;
+        };
+        if org_filter.is_some_and(|org| org != org_id) {
+            continue;
+        }
+        let entry = sources.entry(org_id.to_string()).or_default();
+        match provisioning::parse(&path, &data) {
+            Ok(resources) => entry.0.extend(resources),
+            Err(e) => entry.1.push(format!("{path}: {e}")),
+        }
+    }
+    Ok(sources)
+}
+
+// the files of the source, with their paths relative to it
+async fn read_source(org_filter: Option<&str>) -> Result<Vec<(String, Vec<u8>)>, anyhow::Error> {
+    let cfg = get_config();
+    let mut files = vec![];
+    if !cfg.provisioning.dir.is_empty() {
+        let root = match org_filter {
+            Some(org_id) => format!("{}{org_id}/", cfg.provisioning.dir),
+            None => cfg.provisioning.dir.clone(),
+        };
+        if !std::path::Path::new(&root).exists() {
+            return Ok(files);
+        }
+        for ext in EXTENSIONS {
+            for path in scan_files(&root, ext, None)? {
+                let data = tokio::fs::read(&path).await?;
+                let path = path.strip_prefix(&cfg.provisioning.dir).unwrap_or(&path);
+                files.push((path.to_string(), data));
+            }
+        }
+    } else if !cfg.provisioning.prefix.is_empty() {
+        let prefix = cfg.provisioning.prefix.trim_end_matches('/');
+        let root = match org_filter {
+            Some(org_id) => format!("{prefix}/{org_id}/"),
+            None => format!("{prefix}/"),
+        };
+        for key in infra::storage::list(&root).await? {
+            if !EXTENSIONS
+                .iter()
+                .any(|ext| key.ends_with(&format!(".{ext}")))
+            {
+                continue;
+            }
+            let data = infra::storage::get(&key).await?;
+            let path = key.strip_prefix(prefix).unwrap_or(&key);
+            files.push((path.to_string(), data.to_vec()));
+        }
+    } else {
+        return Err(anyhow::anyhow!(
+            "neither ZO_PROVISIONING_DIR nor ZO_PROVISIONING_PREFIX is set"
+        ));
+    }
+    Ok(files)
+}
+
+/// Reports the drift of the resources, and applies the definitions of the
+/// drifted ones unless it is a dry run. The report is stored as the last one
+/// of the organization.
+async fn reconcile(
+    org_id: &str,
+    resources: Vec<Resource>,
+    errors: Vec<String>,
+    dry_run: bool,
+) -> ProvisioningReport {
+    let mut report = ProvisioningReport {
+        org_id: org_id.to_string(),
+        started_at: chrono::Utc::now().timestamp_micros(),
+        dry_run,
+        errors,
+        ..Default::default()
+    };
+    let mut bundle = Bundle {
+        version: BUNDLE_VERSION,
+        org_id: org_id.to_string(),
+        ..Default::default()
+    };
+    let mut streams = vec![];
+    for resource in resources {
+        let (resource, item) = match drift(org_id, resource).await {
+            Ok(drift) => drift,
+            Err(e) => {
+                report.errors.push(e.to_string());
+                continue;
+            }
+        };
+        let drifted = item.state != DriftState::InSync;
+        report.drift.push(item);
+        if !drifted {
+            continue;
+        }
+        match resource {
+            Resource::Folder(folder) => bundle.folders.push(folder),
+            Resource::Dashboard(dashboard) => bundle.dashboards.push(dashboard),
+            Resource::Template(template) => bundle.templates.push(template),
+            Resource::Destination(destination) => bundle.destinations.push(destination),
+            Resource::Alert(alert) => bundle.alerts.push(alert),
+            Resource::Function(function) => bundle.functions.push(function),
+            Resource::Stream(s) => streams.push(s),
+        }
+    }
+
+    if !dry_run {
+        report.results = bundles::import(org_id, bundle, ConflictStrategy::Overwrite)
+            .await
+            .results;
+        for s in streams {
+            let resp =
+                stream::save_stream_settings(org_id, &s.name, s.stream_type, s.settings).await;
+            let error = match resp {
+                Ok(resp) if resp.status().is_success() => None,
+                Ok(resp) => Some(format!("failed to save the settings: {}", resp.status())),
+                Err(e) => Some(e.to_string()),
+            };
+            report.results.push(ImportResult {
+                kind: "stream".to_string(),
+                name: s.name,
+                action: if error.is_some() {
+                    ImportAction::Failed
+                } else {
+                    ImportAction::Overwritten
+                },
+                new_name: None,
+                error,
+            });
+        }
+    }
+
+    if let Err(e) = db::provisioning::put_report(org_id, &report).await {
+        log::error!("[PROVISIONING] failed to store the report of {org_id}: {e}");
+    }
+    report
+}
+
+/// Compares the resource with its definition, the definition is returned
+/// normalized the way it is compared.
+async fn drift(org_id: &str, resource: Resource) -> Result<(Resource, DriftItem), anyhow::Error> {
+    let kind = resource.kind();
+    let (resource, name, current, desired) = match resource {
+        Resource::Folder(folder) => {
+            let current = db::dashboards::folders::get(org_id, &folder.folder_id)
+                .await
+                .ok()
+                .map(|f| json::to_value(f).unwrap());
+            let desired = json::to_value(&folder)?;
+            (
+                Resource::Folder(folder.clone()),
+                folder.folder_id,
+                current,
+                desired,
+            )
+        }
+        Resource::Dashboard(mut dashboard) => {
+            let id = dashboard
+                .dashboard
+                .get("dashboardId")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            if id.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "dashboard {:?} needs a dashboardId to be reconciled",
+                    dashboard.dashboard.get("title")
+                ));
+            }
+            let existing = db::dashboards::list_all(org_id)
+                .await?
+                .into_iter()
+                .find(|d| d.dashboard_id() == id);
+            let current = existing.as_ref().map(|d| {
+                let mut value = versions::to_value(d);
+                value["folder_id"] = json::Value::from(d.folder_id.clone().unwrap_or_default());
+                value
+            });
+            // keep the creation time of the dashboard when it isn't defined
+            if let (Some(current), Some(obj)) = (&current, dashboard.dashboard.as_object_mut()) {
+                if !obj.contains_key("created") {
+                    if let Some(created) = current.get("created") {
+                        obj.insert("created".to_string(), created.clone());
+                    }
+                }
+            }
+            dashboard.dashboard = normalize_dashboard(&dashboard.dashboard)?;
+            let mut desired = dashboard.dashboard.clone();
+            desired["folder_id"] = json::Value::from(dashboard.folder_id.clone());
+            (Resource::Dashboard(dashboard), id, current, desired)
+        }
+        Resource::Template(template) => {
+            let current = db::alerts::templates::get(org_id, &template.name)
+                .await
+                .ok()
+                .map(|t| json::to_value(t).unwrap());
+            let desired = json::to_value(&template)?;
+            (
+                Resource::Template(template.clone()),
+                template.name,
+                current,
+                desired,
+            )
+        }
+        Resource::Destination(destination) => {
+            let current = db::alerts::destinations::get(org_id, &destination.name)
+                .await
+                .ok()
+                .map(|d| json::to_value(d).unwrap());
+            let desired = json::to_value(&destination)?;
+            let name = destination.name.clone();
+            (Resource::Destination(destination), name, current, desired)
+        }
+        Resource::Alert(mut alert) => {
+            alert.org_id = org_id.to_string();
+            let current =
+                db::alerts::get(org_id, alert.stream_type, &alert.stream_name, &alert.name)
+                    .await?
+                    .map(|a| json::to_value(a).unwrap());
+            let desired = json::to_value(&alert)?;
+            let name = format!("{}/{}/{}", alert.stream_type, alert.stream_name, alert.name);
+            (Resource::Alert(alert), name, current, desired)
+        }
+        Resource::Function(mut function) => {
+            // the streams using the function are set on the streams
+            function.streams = None;
+            let current = db::functions::get(org_id, &function.name)
+                .await
+                .ok()
+                .map(|mut f| {
+                    f.streams = None;
+                    json::to_value(f).unwrap()
+                });
+            let desired = json::to_value(&function)?;
+            let name = function.name.clone();
+            (Resource::Function(function), name, current, desired)
+        }
+        Resource::Stream(s) => {
+            let current = infra::schema::get_settings(org_id, &s.name, s.stream_type)
+                .await
+                .map(|settings| json::to_value(settings).unwrap());
+            let desired = json::to_value(&s.settings)?;
+            let name = format!("{}/{}", s.stream_type, s.name);
+            (Resource::Stream(s), name, current, desired)
+        }
+    };
+    let item = match current {
+        None => DriftItem {
+            kind: kind.to_string(),
+            name,
+            state: DriftState::Missing,
+            changes: vec![],
+        },
+        Some(current) => {
+            let changes = versions::diff(&current, &desired);
+            DriftItem {
+                kind: kind.to_string(),
+                name,
+                state: if changes.is_empty() {
+                    DriftState::InSync
+                } else {
+                    DriftState::Changed
+                },
+                changes,
+            }
+        }
+    };
+    Ok((resource, item))
+}
+
+// the dashboard as it is stored, the title is trimmed on save
+fn normalize_dashboard(value: &json::Value) -> Result<json::Value, anyhow::Error> {
+    let version = json::from_value::<DashboardVersion>(value.clone())?.version;
+    let mut value = match version {
+        1 => json::to_value(json::from_value::<v1::Dashboard>(value.clone())?)?,
+        2 => json::to_value(json::from_value::<v2::Dashboard>(value.clone())?)?,
+        _ => json::to_value(json::from_value::<v3::Dashboard>(value.clone())?)?,
+    };
+    if let Some(title) = value.get("title").and_then(|t| t.as_str()) {
+        value["title"] = json::Value::from(title.trim());
+    }
+    Ok(value)
+}
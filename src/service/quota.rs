@@ -0,0 +1,320 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::HttpResponse;
+use config::{get_config, meta::stream::StreamType, metrics, RwHashMap};
+use infra::schema::STREAM_SCHEMAS_LATEST;
+use once_cell::sync::Lazy;
+
+use crate::{
+    common::{
+        infra::{
+            cluster,
+            config::{ORGANIZATION_QUOTAS, STREAM_ALERTS},
+        },
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            organization::{OrgQuota, OrgQuotaResponse, OrgQuotaUsage},
+        },
+    },
+    service::db,
+};
+
+const RESOURCES: [&str; 4] = ["streams", "storage_bytes", "queries_per_minute", "alerts"];
+
+// key: org_id, value: (minute, number of queries in that minute)
+static QUERY_COUNTERS: Lazy<RwHashMap<String, (i64, i64)>> = Lazy::new(Default::default);
+// key: org_id, refreshed every `ZO_ORG_QUOTA_CHECK_INTERVAL`
+static ORG_USAGE: Lazy<RwHashMap<String, OrgQuotaUsage>> = Lazy::new(Default::default);
+
+fn get_limit(org_id: &str, f: impl Fn(&OrgQuota) -> Option<i64>) -> Option<i64> {
+    ORGANIZATION_QUOTAS.get(org_id).and_then(|quota| f(&quota))
+}
+
+/// Storage is checked against the usage of the last refresh so that
+/// ingestion doesn't have to walk the stream stats on every request.
+pub fn is_storage_exceeded(org_id: &str) -> bool {
+    let Some(max) = get_limit(org_id, |q| q.max_storage_bytes) else {
+        return false;
+    };
+    ORG_USAGE
+        .get(org_id)
+        .map(|usage| usage.storage_bytes >= max)
+        .unwrap_or_default()
+}
+
+pub async fn check_stream_quota(org_id: &str) -> Result<(), anyhow::Error> {
+    let Some(max) = get_limit(org_id, |q| q.max_streams) else {
+        return Ok(());
+    };
+    if count_streams(org_id).await >= max {
+        return Err(anyhow::anyhow!(
+            "Quota exceeded for this organization [{org_id}], max streams: {max}"
+        ));
+    }
+    Ok(())
+}
+
+pub async fn check_alert_quota(org_id: &str) -> Result<(), anyhow::Error> {
+    let Some(max) = get_limit(org_id, |q| q.max_alerts) else {
+        return Ok(());
+    };
+    if count_alerts(org_id).await >= max {
+        return Err(anyhow::anyhow!(
+            "Quota exceeded for this organization [{org_id}], max alerts: {max}"
+        ));
+    }
+    Ok(())
+}
+
+/// Counts the query against the per minute quota of the organization. The
+/// counter is kept per querier node, so each node only admits its share of
+/// the limit split across the online queriers.
+pub async fn check_query_quota(org_id: &str) -> Result<(), anyhow::Error> {
+    let Some(max) = get_limit(org_id, |q| q.max_queries_per_minute) else {
+        return Ok(());
+    };
+    let queriers = cluster::get_cached_online_querier_nodes()
+        .await
+        .map(|nodes| nodes.len())
+        .unwrap_or_default();
+    let share = node_share(max, queriers);
+    let minute = chrono::Utc::now().timestamp() / 60;
+    let mut counter = QUERY_COUNTERS
+        .entry(org_id.to_string())
+        .or_insert((minute, 0));
+    if !take_query_slot(&mut counter, minute, share) {
+        return Err(anyhow::anyhow!(
+            "Quota exceeded for this organization [{org_id}], max queries per minute: {max}"
+        ));
+    }
+    Ok(())
+}
+
+fn node_share(max: i64, nodes: usize) -> i64 {
+    let nodes = nodes.max(1) as i64;
+    (max + nodes - 1) / nodes
+}
+
+fn take_query_slot(counter: &mut (i64, i64), minute: i64, max: i64) -> bool {
+    if counter.0 != minute {
+        *counter = (minute, 0);
+    }
+    if counter.1 >= max {
+        return false;
+    }
+    counter.1 += 1;
+    true
+}
+
+async fn count_streams(org_id: &str) -> i64 {
+    let prefix = format!("{org_id}/");
+    STREAM_SCHEMAS_LATEST
+        .read()
+        .await
+        .keys()
+        .filter_map(|key| key.strip_prefix(&prefix))
+        .filter(|key| {
+            let stream_type = StreamType::from(key.split('/').next().unwrap_or_default());
+            !matches!(stream_type, StreamType::Index | StreamType::Metadata)
+        })
+        .count() as i64
+}
+
+async fn count_alerts(org_id: &str) -> i64 {
+    let prefix = format!("{org_id}/");
+    STREAM_ALERTS
+        .read()
+        .await
+        .iter()
+        .filter(|(key, _)| key.starts_with(&prefix))
+        .map(|(_, alerts)| alerts.len() as i64)
+        .sum()
+}
+
+fn get_storage_bytes(org_id: &str) -> i64 {
+    let prefix = format!("{org_id}/");
+    infra::cache::stats::get_stats()
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix))
+        .map(|v| v.value().compressed_size)
+        .sum::<f64>() as i64
+}
+
+fn get_queries_per_minute(org_id: &str) -> i64 {
+    let minute = chrono::Utc::now().timestamp() / 60;
+    QUERY_COUNTERS
+        .get(org_id)
+        .filter(|counter| counter.0 == minute)
+        .map(|counter| counter.1)
+        .unwrap_or_default()
+}
+
+async fn get_usage(org_id: &str) -> OrgQuotaUsage {
+    OrgQuotaUsage {
+        streams: count_streams(org_id).await,
+        storage_bytes: get_storage_bytes(org_id),
+        queries_per_minute: get_queries_per_minute(org_id),
+        alerts: count_alerts(org_id).await,
+    }
+}
+
+fn report_metrics(org_id: &str, quota: &OrgQuota, usage: &OrgQuotaUsage) {
+    let limits = [
+        quota.max_streams,
+        quota.max_storage_bytes,
+        quota.max_queries_per_minute,
+        quota.max_alerts,
+    ];
+    let usages = [
+        usage.streams,
+        usage.storage_bytes,
+        usage.queries_per_minute,
+        usage.alerts,
+    ];
+    for ((resource, limit), usage) in RESOURCES.into_iter().zip(limits).zip(usages) {
+        match limit {
+            Some(limit) => metrics::ORG_QUOTA_LIMIT
+                .with_label_values(&[org_id, resource])
+                .set(limit),
+            None => {
+                _ = metrics::ORG_QUOTA_LIMIT.remove_label_values(&[org_id, resource]);
+            }
+        }
+        metrics::ORG_QUOTA_USAGE
+            .with_label_values(&[org_id, resource])
+            .set(usage);
+    }
+}
+
+fn remove_metrics(org_id: &str) {
+    for resource in RESOURCES {
+        _ = metrics::ORG_QUOTA_LIMIT.remove_label_values(&[org_id, resource]);
+        _ = metrics::ORG_QUOTA_USAGE.remove_label_values(&[org_id, resource]);
+    }
+}
+
+pub async fn run() {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+        get_config().limit.org_quota_check_interval,
+    ));
+    loop {
+        interval.tick().await;
+        let quotas = ORGANIZATION_QUOTAS
+            .iter()
+            .map(|v| (v.key().clone(), v.value().clone()))
+            .collect::<Vec<_>>();
+        ORG_USAGE.retain(|org_id, _| ORGANIZATION_QUOTAS.contains_key(org_id));
+        for (org_id, quota) in quotas {
+            let usage = get_usage(&org_id).await;
+            report_metrics(&org_id, &quota, &usage);
+            ORG_USAGE.insert(org_id, usage);
+        }
+    }
+}
+
+pub async fn get_quota(org_id: &str) -> Result<HttpResponse, Error> {
+    let quota = ORGANIZATION_QUOTAS
+        .get(org_id)
+        .map(|v| v.clone())
+        .unwrap_or_default();
+    let usage = get_usage(org_id).await;
+    Ok(HttpResponse::Ok().json(OrgQuotaResponse { quota, usage }))
+}
+
+pub async fn set_quota(org_id: &str, quota: OrgQuota) -> Result<HttpResponse, Error> {
+    let limits = [
+        quota.max_streams,
+        quota.max_storage_bytes,
+        quota.max_queries_per_minute,
+        quota.max_alerts,
+    ];
+    if limits.iter().flatten().any(|v| *v < 0) {
+        return Ok(MetaHttpResponse::bad_request(
+            "quota limits should not be negative",
+        ));
+    }
+    if let Err(e) = db::organization::set_quota(org_id, &quota).await {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    ORGANIZATION_QUOTAS.insert(org_id.to_string(), quota.clone());
+    let usage = get_usage(org_id).await;
+    report_metrics(org_id, &quota, &usage);
+    ORG_USAGE.insert(org_id.to_string(), usage);
+    Ok(HttpResponse::Ok().json(quota))
+}
+
+pub async fn delete_quota(org_id: &str) -> Result<HttpResponse, Error> {
+    if !ORGANIZATION_QUOTAS.contains_key(org_id) {
+        return Ok(MetaHttpResponse::not_found("Org quota not found"));
+    }
+    if let Err(e) = db::organization::delete_quota(org_id).await {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    ORGANIZATION_QUOTAS.remove(org_id);
+    ORG_USAGE.remove(org_id);
+    remove_metrics(org_id);
+    Ok(MetaHttpResponse::ok("Org quota deleted"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_query_slot() {
+        let mut counter = (10, 0);
+        assert!(take_query_slot(&mut counter, 10, 2));
+        assert!(take_query_slot(&mut counter, 10, 2));
+        assert!(!take_query_slot(&mut counter, 10, 2));
+        assert_eq!(counter, (10, 2));
+        // a new minute resets the window
+        assert!(take_query_slot(&mut counter, 11, 2));
+        assert_eq!(counter, (11, 1));
+    }
+
+    #[test]
+    fn test_node_share() {
+        assert_eq!(node_share(10, 0), 10);
+        assert_eq!(node_share(10, 1), 10);
+        assert_eq!(node_share(10, 3), 4);
+        assert_eq!(node_share(10, 20), 1);
+        assert_eq!(node_share(0, 3), 0);
+    }
+
+    #[test]
+    fn test_is_storage_exceeded() {
+        let org_id = "test_quota_org";
+        assert!(!is_storage_exceeded(org_id));
+        ORGANIZATION_QUOTAS.insert(
+            org_id.to_string(),
+            OrgQuota {
+                max_storage_bytes: Some(100),
+                ..Default::default()
+            },
+        );
+        assert!(!is_storage_exceeded(org_id));
+        ORG_USAGE.insert(
+            org_id.to_string(),
+            OrgQuotaUsage {
+                storage_bytes: 100,
+                ..Default::default()
+            },
+        );
+        assert!(is_storage_exceeded(org_id));
+    }
+}
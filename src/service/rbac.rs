@@ -0,0 +1,500 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashSet, io::Error};
+
+use actix_web::HttpResponse;
+use config::get_config;
+
+use crate::{
+    common::{
+        infra::config::ROLES,
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            rbac::{get_built_in_role, Permission, Resource, Role, RoleRequest, BUILT_IN_ROLES},
+            service_account::parse_principal,
+        },
+        utils::auth::is_root_user,
+    },
+    service::{db, search::row_filter, service_accounts},
+};
+
+const ROLE_NOT_FOUND: &str = "Role not found";
+const ROLE_ALREADY_EXISTS: &str = "Role already exists";
+const ROLE_BUILT_IN: &str = "Built-in roles can only be updated with users and row filters";
+const ROLE_INVALID_NAME: &str = "Role name can only contain alphanumeric characters, '_' and '-'";
+
+/// Checks if the user can perform `permission` on the object `obj` of type
+/// `resource` in the organization.
+///
+/// Root user is always allowed, users that are not bound to any role get the
+/// role configured with `ZO_RBAC_DEFAULT_ROLE`.
+pub fn is_allowed(
+    org_id: &str,
+    user_id: &str,
+    resource: Resource,
+    obj: &str,
+    permission: Permission,
+) -> bool {
+    if is_root_user(user_id) {
+        return true;
+    }
+    if let Some((name, token_id)) = parse_principal(user_id) {
+        return service_accounts::is_token_allowed(
+            org_id, name, token_id, resource, obj, permission,
+        );
+    }
+    get_user_roles(org_id, user_id)
+        .iter()
+        .any(|role| role.is_allowed(resource, obj, permission))
+}
+
+/// Returns the effective roles of the user in the organization.
+pub fn get_user_roles(org_id: &str, user_id: &str) -> Vec<Role> {
+    let prefix = format!("{org_id}/");
+    let roles = ROLES
+        .iter()
+        .filter(|r| r.key().starts_with(&prefix) && r.value().users.contains(user_id))
+        .filter_map(|r| resolve_role(r.value()))
+        .collect::<Vec<_>>();
+    if !roles.is_empty() {
+        return roles;
+    }
+    let default_role = &get_config().auth.rbac_default_role;
+    match ROLES.get(&format!("{org_id}/{default_role}")) {
+        Some(role) => resolve_role(role.value()).into_iter().collect(),
+        None => get_built_in_role(default_role).into_iter().collect(),
+    }
+}
+
+// the stored built-in roles only carry the user bindings
+fn resolve_role(role: &Role) -> Option<Role> {
+    if !role.built_in {
+        return Some(role.clone());
+    }
+    let mut built_in = get_built_in_role(&role.name)?;
+    built_in.users = role.users.clone();
+    built_in.row_filters = role.row_filters.clone();
+    Some(built_in)
+}
+
+/// Maps a request to the permission it requires, `path` is relative to
+/// `/api/` and starts with the org_id.
+///
+/// Returns `None` for the requests that are not covered by RBAC, like
+/// listing, or the ones checked by their handlers (`_bulk`, `_search`).
+pub fn get_permission_for_path(method: &str, path: &str) -> Option<(Permission, Resource, String)> {
+    let columns = path.trim_end_matches('/').split('/').collect::<Vec<_>>();
+    if columns.len() < 2 {
+        return None;
+    }
+    let is_list = method.eq("GET") && columns.len() == 2;
+    let permission = Permission::from_method(method);
+    let (resource, obj) = match &columns[1..] {
+        ["_bulk"] | ["_search"] | ["_search_partition"] => return None,
+        // the bundles carry the secrets of the destinations
+        ["_export"] | ["_import"] => {
+            return Some((Permission::Write, Resource::Setting, "*".to_string()));
+        }
+        ["streams", stream, rest @ ..] => {
+            let permission = if rest.last() == Some(&"delete_fields") {
+                Permission::Delete
+            } else {
+                permission
+            };
+            return Some((permission, Resource::Stream, stream.to_string()));
+        }
+        ["enrichment_tables", name] => {
+            return Some((Permission::Write, Resource::Stream, name.to_string()));
+        }
+        ["prometheus", "api", "v1", "write"]
+        | ["v1", "logs" | "metrics" | "traces"]
+        | ["traces"]
+        | ["ingest", "metrics", "_json"] => {
+            return Some((Permission::Write, Resource::Stream, "*".to_string()));
+        }
+        ["prometheus", ..] => return Some((Permission::Read, Resource::Stream, "*".to_string())),
+        ["scim", ..] => return Some((permission, Resource::User, "*".to_string())),
+        ["alerts", "templates" | "destinations"] if method.eq("GET") => return None,
+        ["alerts", "templates" | "destinations", rest @ ..] => {
+            (Resource::Alert, rest.first().copied().unwrap_or("*"))
+        }
+        [_, "alerts"] if method.eq("GET") => return None,
+        [_, "alerts", rest @ ..] => (Resource::Alert, rest.first().copied().unwrap_or("*")),
+        ["derived_streams", rest @ ..] => (Resource::Alert, rest.first().copied().unwrap_or("*")),
+        ["dashboards", dashboard_id, "versions" | "snapshots", ..] => {
+            (Resource::Dashboard, *dashboard_id)
+        }
+        ["dashboards" | "folders", rest @ ..] => {
+            (Resource::Dashboard, rest.last().copied().unwrap_or("*"))
+        }
+        ["reports", rest @ ..] => (Resource::Dashboard, rest.first().copied().unwrap_or("*")),
+        ["snapshots" | "annotations", rest @ ..] => {
+            (Resource::Dashboard, rest.first().copied().unwrap_or("*"))
+        }
+        ["functions", rest @ ..] => (Resource::Function, rest.first().copied().unwrap_or("*")),
+        // running a template is a search, the handler checks the stream
+        ["search_templates", _, "_run"] => return None,
+        ["search_templates", rest @ ..] => {
+            (Resource::Dashboard, rest.first().copied().unwrap_or("*"))
+        }
+        [
+            "settings" | "audit" | "quotas" | "masking_policies" | "network_policy"
+            | "provisioning",
+            ..,
+        ] => (Resource::Setting, "*"),
+        ["metering"] => (Resource::Setting, "*"),
+        // the running queries of all the users are for the admins only
+        ["queries", ..] => {
+            return Some((Permission::Write, Resource::Setting, "*".to_string()));
+        }
+        ["users", _, "attributes"] => (Resource::User, "*"),
+        ["roles" | "groups", rest @ ..] => (Resource::Role, rest.first().copied().unwrap_or("*")),
+        ["service_accounts", rest @ ..] => (
+            Resource::ServiceAccount,
+            rest.first().copied().unwrap_or("*"),
+        ),
+        [
+            stream,
+            "_json" | "_multi" | "_raw" | "_kinesis_firehose" | "_sub" | "_backfill",
+        ] => {
+            return Some((Permission::Write, Resource::Stream, stream.to_string()));
+        }
+        [stream, "_around" | "_values"] | [stream, "traces", "latest"] => {
+            return Some((Permission::Read, Resource::Stream, stream.to_string()));
+        }
+        _ => return None,
+    };
+    if is_list
+        && !matches!(
+            resource,
+            Resource::Setting | Resource::Role | Resource::ServiceAccount
+        )
+    {
+        return None;
+    }
+    Some((permission, resource, obj.to_string()))
+}
+
+pub async fn list_roles(org_id: &str) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(get_roles(org_id)))
+}
+
+/// Returns the built-in roles followed by the custom roles of the organization.
+pub fn get_roles(org_id: &str) -> Vec<Role> {
+    let mut roles = BUILT_IN_ROLES
+        .iter()
+        .filter_map(|name| get_role(org_id, name))
+        .collect::<Vec<_>>();
+    let prefix = format!("{org_id}/");
+    let mut custom = ROLES
+        .iter()
+        .filter(|r| r.key().starts_with(&prefix) && !r.value().built_in)
+        .map(|r| r.value().clone())
+        .collect::<Vec<_>>();
+    custom.sort_by(|a, b| a.name.cmp(&b.name));
+    roles.extend(custom);
+    roles
+}
+
+pub fn is_valid_role_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+pub async fn create_role(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    let name = name.trim();
+    if !is_valid_role_name(name) {
+        return Ok(MetaHttpResponse::bad_request(ROLE_INVALID_NAME));
+    }
+    if get_role(org_id, name).is_some() {
+        return Ok(MetaHttpResponse::bad_request(ROLE_ALREADY_EXISTS));
+    }
+    let role = Role {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    match db::rbac::set(org_id, &role).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(role)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn update_role(
+    org_id: &str,
+    name: &str,
+    req: RoleRequest,
+) -> Result<HttpResponse, Error> {
+    let Some(mut role) = get_role(org_id, name) else {
+        return Ok(MetaHttpResponse::not_found(ROLE_NOT_FOUND));
+    };
+    if role.built_in && (!req.add.is_empty() || !req.remove.is_empty()) {
+        return Ok(MetaHttpResponse::bad_request(ROLE_BUILT_IN));
+    }
+    for filter in req.add_row_filters.iter() {
+        if let Err(e) = row_filter::validate(filter) {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "Invalid row filter: {e}"
+            )));
+        }
+    }
+    role.grants.retain(|g| !req.remove.contains(g));
+    for grant in req.add {
+        if !role.grants.contains(&grant) {
+            role.grants.push(grant);
+        }
+    }
+    if let Some(users) = req.remove_users {
+        role.users.retain(|u| !users.contains(u));
+    }
+    if let Some(users) = req.add_users {
+        role.users.extend(users);
+    }
+    role.row_filters
+        .retain(|f| !req.remove_row_filters.contains(f));
+    for filter in req.add_row_filters {
+        if !role.row_filters.contains(&filter) {
+            role.row_filters.push(filter);
+        }
+    }
+
+    match store_role(org_id, &role).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(role)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn delete_role(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    if BUILT_IN_ROLES.contains(&name) {
+        return Ok(MetaHttpResponse::bad_request(ROLE_BUILT_IN));
+    }
+    if get_role(org_id, name).is_none() {
+        return Ok(MetaHttpResponse::not_found(ROLE_NOT_FOUND));
+    }
+    match db::rbac::delete(org_id, name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Role deleted")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn get_role_permissions(
+    org_id: &str,
+    name: &str,
+    resource: &str,
+) -> Result<HttpResponse, Error> {
+    let resource: Resource = match resource.parse() {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let Some(role) = get_role(org_id, name) else {
+        return Ok(MetaHttpResponse::not_found(ROLE_NOT_FOUND));
+    };
+    let grants = role
+        .grants
+        .into_iter()
+        .filter(|g| {
+            resource == Resource::All || g.resource == resource || g.resource == Resource::All
+        })
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(grants))
+}
+
+pub async fn get_users_with_role(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    let Some(role) = get_role(org_id, name) else {
+        return Ok(MetaHttpResponse::not_found(ROLE_NOT_FOUND));
+    };
+    let mut users = role.users.into_iter().collect::<Vec<_>>();
+    users.sort();
+    Ok(HttpResponse::Ok().json(users))
+}
+
+/// Binds the user to a role, used when provisioning users from an identity
+/// provider.
+pub async fn add_user_to_role(
+    org_id: &str,
+    name: &str,
+    user_id: &str,
+) -> Result<(), anyhow::Error> {
+    let Some(mut role) = get_role(org_id, name) else {
+        return Err(anyhow::anyhow!("{ROLE_NOT_FOUND}: {name}"));
+    };
+    if !role.users.insert(user_id.to_string()) {
+        return Ok(());
+    }
+    store_role(org_id, &role).await
+}
+
+/// Replaces the users bound to a role, the role is created without grants
+/// when it doesn't exist. Used to sync group members from an identity
+/// provider.
+pub async fn set_role_users(
+    org_id: &str,
+    name: &str,
+    users: HashSet<String>,
+) -> Result<Role, anyhow::Error> {
+    let mut role = get_role(org_id, name).unwrap_or_else(|| Role {
+        name: name.to_string(),
+        ..Default::default()
+    });
+    role.users = users;
+    store_role(org_id, &role).await?;
+    Ok(role)
+}
+
+/// Unbinds the user from every role of the organization.
+pub async fn remove_user_from_roles(org_id: &str, user_id: &str) -> Result<(), anyhow::Error> {
+    for mut role in get_roles(org_id)
+        .into_iter()
+        .filter(|role| role.users.contains(user_id))
+    {
+        role.users.remove(user_id);
+        store_role(org_id, &role).await?;
+    }
+    Ok(())
+}
+
+// the grants of built-in roles are not stored, only their user bindings
+async fn store_role(org_id: &str, role: &Role) -> Result<(), anyhow::Error> {
+    if role.built_in {
+        let mut stored = role.clone();
+        stored.grants.clear();
+        db::rbac::set(org_id, &stored).await
+    } else {
+        db::rbac::set(org_id, role).await
+    }
+}
+
+pub fn get_role(org_id: &str, name: &str) -> Option<Role> {
+    match ROLES.get(&format!("{org_id}/{name}")) {
+        Some(role) => resolve_role(role.value()),
+        None => get_built_in_role(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_permission_for_path() {
+        assert_eq!(
+            get_permission_for_path("POST", "default/app/_json"),
+            Some((Permission::Write, Resource::Stream, "app".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("GET", "default/app/_values"),
+            Some((Permission::Read, Resource::Stream, "app".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("DELETE", "default/streams/app"),
+            Some((Permission::Delete, Resource::Stream, "app".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("PUT", "default/streams/app/delete_fields"),
+            Some((Permission::Delete, Resource::Stream, "app".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("PUT", "default/streams/app/settings"),
+            Some((Permission::Write, Resource::Stream, "app".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("POST", "default/app/alerts"),
+            Some((Permission::Write, Resource::Alert, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("PUT", "default/app/alerts/high_cpu/enable"),
+            Some((Permission::Write, Resource::Alert, "high_cpu".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("DELETE", "default/dashboards/123"),
+            Some((Permission::Delete, Resource::Dashboard, "123".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("POST", "default/dashboards/123/versions/2/_restore"),
+            Some((Permission::Write, Resource::Dashboard, "123".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("GET", "default/metering"),
+            Some((Permission::Read, Resource::Setting, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("PUT", "default/roles/editor"),
+            Some((Permission::Write, Resource::Role, "editor".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("POST", "default/prometheus/api/v1/write"),
+            Some((Permission::Write, Resource::Stream, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("PUT", "default/service_accounts/agent/tokens/1/rotate"),
+            Some((
+                Permission::Write,
+                Resource::ServiceAccount,
+                "agent".to_string()
+            ))
+        );
+        assert_eq!(
+            get_permission_for_path("PATCH", "default/scim/v2/Users/john@example.com"),
+            Some((Permission::Write, Resource::User, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("POST", "default/provisioning/_sync"),
+            Some((Permission::Write, Resource::Setting, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("PUT", "default/users/john@example.com/attributes"),
+            Some((Permission::Write, Resource::User, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("DELETE", "default/derived_streams/errors_5m"),
+            Some((Permission::Delete, Resource::Alert, "errors_5m".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("GET", "default/queries"),
+            Some((Permission::Write, Resource::Setting, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("DELETE", "default/queries/abc"),
+            Some((Permission::Write, Resource::Setting, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("POST", "default/app/_backfill"),
+            Some((Permission::Write, Resource::Stream, "app".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("PUT", "default/search_templates/top_errors"),
+            Some((
+                Permission::Write,
+                Resource::Dashboard,
+                "top_errors".to_string()
+            ))
+        );
+        assert_eq!(
+            get_permission_for_path("POST", "default/search_templates"),
+            Some((Permission::Write, Resource::Dashboard, "*".to_string()))
+        );
+        assert_eq!(
+            get_permission_for_path("POST", "default/search_templates/top_errors/_run"),
+            None
+        );
+        assert_eq!(get_permission_for_path("GET", "default/dashboards"), None);
+        assert_eq!(get_permission_for_path("POST", "default/_bulk"), None);
+        assert_eq!(get_permission_for_path("POST", "default/_search"), None);
+        assert_eq!(get_permission_for_path("GET", "default/users"), None);
+        assert_eq!(get_permission_for_path("GET", "organizations"), None);
+    }
+}
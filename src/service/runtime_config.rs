@@ -0,0 +1,109 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Settings changed at runtime through the admin config api, they are kept in
+//! the meta store and applied on every node without a restart.
+
+use std::collections::BTreeMap;
+
+use config::{get_config, get_config_overrides, set_config_overrides, RELOADABLE_SETTINGS};
+use infra::cache::file_data;
+
+use crate::{
+    common::meta::runtime_config::{RuntimeConfig, RuntimeConfigRequest, RuntimeSetting},
+    service::db,
+};
+
+pub fn get() -> RuntimeConfig {
+    let overrides = get_config_overrides();
+    let settings = RELOADABLE_SETTINGS
+        .iter()
+        .map(|(name, help)| {
+            let overridden = overrides.get(*name);
+            RuntimeSetting {
+                name: name.to_string(),
+                help: help.to_string(),
+                value: overridden.cloned().or_else(|| std::env::var(name).ok()),
+                overridden: overridden.is_some(),
+            }
+        })
+        .collect();
+    RuntimeConfig { settings }
+}
+
+/// Merges the changes into the current overrides, applies them on this node
+/// and stores them for the other nodes.
+pub async fn update(req: RuntimeConfigRequest) -> Result<RuntimeConfig, anyhow::Error> {
+    let overrides = merge(get_config_overrides(), req.settings);
+    apply(overrides.clone()).await?;
+    db::runtime_config::set(&overrides).await?;
+    Ok(get())
+}
+
+/// Reloads the config with the overrides and resizes the caches to it.
+pub async fn apply(overrides: BTreeMap<String, String>) -> Result<(), anyhow::Error> {
+    let old_cfg = get_config();
+    set_config_overrides(overrides)?;
+    let cfg = get_config();
+    if cfg.memory_cache.max_size != old_cfg.memory_cache.max_size {
+        file_data::memory::resize(cfg.memory_cache.max_size).await?;
+    }
+    if cfg.disk_cache.max_size != old_cfg.disk_cache.max_size {
+        file_data::disk::resize(cfg.disk_cache.max_size).await?;
+    }
+    Ok(())
+}
+
+fn merge(
+    mut overrides: BTreeMap<String, String>,
+    changes: BTreeMap<String, Option<String>>,
+) -> BTreeMap<String, String> {
+    for (name, value) in changes {
+        match value {
+            Some(value) => overrides.insert(name, value),
+            None => overrides.remove(&name),
+        };
+    }
+    overrides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let overrides = BTreeMap::from([
+            ("ZO_QUERY_TIMEOUT".to_string(), "30".to_string()),
+            ("ZO_COMPACT_INTERVAL".to_string(), "120".to_string()),
+        ]);
+        let changes = BTreeMap::from([
+            ("ZO_QUERY_TIMEOUT".to_string(), None),
+            ("ZO_COMPACT_INTERVAL".to_string(), Some("90".to_string())),
+            (
+                "ZO_QUERY_DEFAULT_LIMIT".to_string(),
+                Some("500".to_string()),
+            ),
+        ]);
+        let overrides = merge(overrides, changes);
+        assert_eq!(
+            overrides,
+            BTreeMap::from([
+                ("ZO_COMPACT_INTERVAL".to_string(), "90".to_string()),
+                ("ZO_QUERY_DEFAULT_LIMIT".to_string(), "500".to_string()),
+            ])
+        );
+    }
+}
@@ -18,10 +18,10 @@ use std::{collections::HashMap, sync::Arc};
 use anyhow::Result;
 use config::{
     get_config,
-    meta::stream::StreamType,
+    meta::stream::{FieldsOverflow, StreamType},
     utils::{json, schema::infer_json_schema_from_map, schema_ext::SchemaExt},
 };
-use datafusion::arrow::datatypes::{Field, Schema};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
 use hashbrown::HashSet;
 use infra::schema::{
     get_settings, unwrap_stream_settings, SchemaCache, STREAM_SCHEMAS_LATEST, STREAM_SETTINGS,
@@ -52,6 +52,101 @@ pub(crate) fn get_request_columns_limit_error(
     )
 }
 
+/// The field the new fields of a stream over its max fields are kept in.
+pub const EXTRA_FIELD: &str = "_extra";
+
+/// Enforces the max fields of the stream on a record, the new fields over the
+/// limit are moved into the `_extra` json field or the record is rejected.
+pub async fn check_fields_limit(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    record: &mut Map<String, Value>,
+) -> Result<()> {
+    let cfg = get_config();
+    let settings = get_settings(org_id, stream_name, stream_type)
+        .await
+        .unwrap_or_default();
+    let max_fields = if settings.max_fields > 0 {
+        settings.max_fields
+    } else {
+        cfg.limit.schema_max_fields
+    };
+    if max_fields == 0 {
+        return Ok(());
+    }
+    let overflow = settings
+        .fields_overflow
+        .unwrap_or_else(|| FieldsOverflow::from(cfg.limit.schema_fields_overflow.as_str()));
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    let r = STREAM_SCHEMAS_LATEST.read().await;
+    let empty = HashMap::new();
+    let fields = r.get(&key).map(|v| v.fields_map()).unwrap_or(&empty);
+    let ret = limit_fields(
+        fields,
+        max_fields,
+        overflow,
+        &cfg.common.column_timestamp,
+        record,
+    );
+    drop(r);
+    ret.map_err(|new_fields| {
+        anyhow::anyhow!(
+            "Stream {key} has the max {max_fields} fields, {new_fields} new fields rejected. You can adjust the limit with the max_fields setting of the stream or the environment variable ZO_SCHEMA_MAX_FIELDS=<max_fields>"
+        )
+    })
+}
+
+// returns the number of new fields when the record is rejected
+fn limit_fields(
+    fields: &HashMap<String, usize>,
+    max_fields: usize,
+    overflow: FieldsOverflow,
+    column_timestamp: &str,
+    record: &mut Map<String, Value>,
+) -> std::result::Result<(), usize> {
+    let has_extra = fields.contains_key(EXTRA_FIELD);
+    let new_fields = record
+        .keys()
+        .filter(|k| {
+            !fields.contains_key(k.as_str())
+                && k.as_str() != EXTRA_FIELD
+                && k.as_str() != column_timestamp
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    let new_extra = !has_extra && record.contains_key(EXTRA_FIELD);
+    if fields.len() + new_fields.len() + usize::from(new_extra) <= max_fields {
+        return Ok(());
+    }
+    if overflow == FieldsOverflow::Reject {
+        return Err(new_fields.len() + usize::from(new_extra));
+    }
+
+    // a slot is kept for the _extra field
+    let room = max_fields.saturating_sub(fields.len() + usize::from(!has_extra));
+    let mut extra = match record.remove(EXTRA_FIELD) {
+        Some(v) => match v
+            .as_str()
+            .and_then(|v| json::from_str::<Map<String, Value>>(v).ok())
+        {
+            Some(v) => v,
+            None => Map::from_iter([(EXTRA_FIELD.to_string(), v)]),
+        },
+        None => Map::new(),
+    };
+    for field in new_fields.into_iter().skip(room) {
+        if let Some(v) = record.remove(&field) {
+            extra.insert(field, v);
+        }
+    }
+    record.insert(
+        EXTRA_FIELD.to_string(),
+        Value::String(Value::Object(extra).to_string()),
+    );
+    Ok(())
+}
+
 pub async fn check_for_schema(
     org_id: &str,
     stream_name: &str,
@@ -96,6 +191,35 @@ pub async fn check_for_schema(
 
     let mut need_insert_new_latest = false;
     let is_new = schema.schema().fields().is_empty();
+    if is_new && !matches!(stream_type, StreamType::Index | StreamType::Metadata) {
+        crate::service::quota::check_stream_quota(org_id).await?;
+    }
+    if !is_new && cfg.common.widening_schema_evolution {
+        let strict_schema = unwrap_stream_settings(schema.schema())
+            .map(|settings| settings.strict_schema)
+            .unwrap_or_default();
+        let type_changes = if strict_schema {
+            get_type_changes(schema, &inferred_schema)
+        } else {
+            vec![]
+        };
+        if let Some((field, from_type, to_type)) = type_changes.first() {
+            for (field, from_type, to_type) in type_changes.iter() {
+                crate::service::schema_changes::record(
+                    org_id,
+                    stream_type,
+                    stream_name,
+                    field,
+                    from_type,
+                    to_type,
+                )
+                .await;
+            }
+            return Err(anyhow::anyhow!(
+                "Field [{field}] of stream {org_id}/{stream_type}/{stream_name} is {from_type} and the record has {to_type}. The stream is in strict schema mode, the change waits for approval"
+            ));
+        }
+    }
     if !is_new {
         let (is_schema_changed, field_datatype_delta) =
             get_schema_changes(schema, &inferred_schema);
@@ -211,6 +335,13 @@ async fn handle_diff_schema(
         );
     }
 
+    // fail fast instead of retrying against a failing meta store
+    if config::cluster::is_degraded() {
+        return Err(anyhow::anyhow!(
+            "the schema of the stream [{stream_name}] can't be updated while the meta store or the object store is failing"
+        ));
+    }
+
     let mut retries = 0;
     let mut err: Option<anyhow::Error> = None;
     let mut ret: Option<_> = None;
@@ -332,6 +463,30 @@ pub fn generate_schema_for_defined_schema_fields(
     ))
 }
 
+// the fields the record would widen to another type
+fn get_type_changes(
+    schema: &SchemaCache,
+    inferred_schema: &Schema,
+) -> Vec<(String, DataType, DataType)> {
+    inferred_schema
+        .fields()
+        .iter()
+        .filter_map(|item| {
+            let idx = schema.fields_map().get(item.name())?;
+            let existing = schema.schema().field(*idx).data_type();
+            (existing != item.data_type()
+                && infra::schema::is_widening_conversion(existing, item.data_type()))
+            .then(|| {
+                (
+                    item.name().to_string(),
+                    existing.clone(),
+                    item.data_type().clone(),
+                )
+            })
+        })
+        .collect()
+}
+
 fn get_schema_changes(schema: &SchemaCache, inferred_schema: &Schema) -> (bool, Vec<Field>) {
     let mut is_schema_changed = false;
     let mut field_datatype_delta: Vec<Field> = vec![];
@@ -419,10 +574,57 @@ pub async fn stream_schema_exists(
 mod tests {
     use std::str::FromStr;
 
-    use datafusion::arrow::datatypes::DataType;
-
     use super::*;
 
+    #[test]
+    fn test_get_type_changes() {
+        let schema = SchemaCache::new(Schema::new(vec![
+            Field::new("code", DataType::Int64, true),
+            Field::new("msg", DataType::Utf8, true),
+        ]));
+        let inferred = Schema::new(vec![
+            Field::new("code", DataType::Utf8, true),
+            Field::new("msg", DataType::Int64, true),
+            Field::new("new", DataType::Utf8, true),
+        ]);
+        // a number in the string field is cast, only the number field widens
+        assert_eq!(
+            get_type_changes(&schema, &inferred),
+            vec![("code".to_string(), DataType::Int64, DataType::Utf8)]
+        );
+    }
+
+    #[test]
+    fn test_limit_fields() {
+        let fields = HashMap::from([("_timestamp".to_string(), 0), ("a".to_string(), 1)]);
+        let record = json::json!({"_timestamp": 1, "a": 1, "b": 2, "c": 3, "d": 4});
+        let record = record.as_object().unwrap();
+
+        let mut rec = record.clone();
+        assert!(limit_fields(&fields, 10, FieldsOverflow::Extra, "_timestamp", &mut rec).is_ok());
+        assert_eq!(&rec, record);
+
+        assert_eq!(
+            limit_fields(
+                &fields,
+                3,
+                FieldsOverflow::Reject,
+                "_timestamp",
+                &mut rec.clone()
+            ),
+            Err(3)
+        );
+
+        // one new field and the _extra field fit
+        limit_fields(&fields, 4, FieldsOverflow::Extra, "_timestamp", &mut rec).unwrap();
+        assert_eq!(rec.len(), 4);
+        assert_eq!(rec.get("b"), Some(&json::json!(2)));
+        let extra: Map<String, Value> =
+            json::from_str(rec.get(EXTRA_FIELD).unwrap().as_str().unwrap()).unwrap();
+        assert_eq!(extra.len(), 2);
+        assert_eq!(extra.get("d"), Some(&json::json!(4)));
+    }
+
     #[tokio::test]
     async fn test_check_for_schema() {
         let stream_name = "Sample";
@@ -0,0 +1,179 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The type changes of the fields rejected by the streams in strict schema
+//! mode, kept until they are approved or rejected.
+
+use std::io::Error;
+
+use actix_web::{http::StatusCode, HttpResponse};
+use config::{meta::stream::StreamType, RwHashMap};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use once_cell::sync::Lazy;
+
+use crate::{
+    common::meta::{http::HttpResponse as MetaHttpResponse, stream::PendingSchemaChange},
+    service::db,
+};
+
+// a node saves a pending change at most once a minute
+const SAVE_INTERVAL: i64 = 60_000_000;
+
+static LAST_SAVED: Lazy<RwHashMap<String, i64>> = Lazy::new(Default::default);
+
+/// Records a type change of a field rejected at ingest.
+pub async fn record(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    field: &str,
+    from_type: &DataType,
+    to_type: &DataType,
+) {
+    let key = format!("{org_id}/{stream_type}/{stream_name}/{field}");
+    let now = chrono::Utc::now().timestamp_micros();
+    if LAST_SAVED
+        .get(&key)
+        .is_some_and(|v| now - *v < SAVE_INTERVAL)
+    {
+        return;
+    }
+    LAST_SAVED.insert(key, now);
+
+    let (from_type, to_type) = (from_type.to_string(), to_type.to_string());
+    let first_seen = match db::schema_changes::get(org_id, stream_type, stream_name, field).await {
+        Ok(change) if change.from_type == from_type && change.to_type == to_type => {
+            change.first_seen
+        }
+        _ => now,
+    };
+    let change = PendingSchemaChange {
+        stream_type,
+        stream_name: stream_name.to_string(),
+        field: field.to_string(),
+        from_type,
+        to_type,
+        first_seen,
+        last_seen: now,
+    };
+    if let Err(e) = db::schema_changes::set(org_id, &change).await {
+        log::error!(
+            "[SCHEMA] save pending change of {org_id}/{stream_type}/{stream_name}/{field} error: {e}"
+        );
+    }
+}
+
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<HttpResponse, Error> {
+    match db::schema_changes::list(org_id, stream_type, stream_name).await {
+        Ok(changes) => Ok(HttpResponse::Ok().json(changes)),
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR.into(),
+                e.to_string(),
+            )),
+        ),
+    }
+}
+
+/// Applies a pending change to the schema of the stream, the field is widened
+/// to the new type like a stream out of strict schema mode does.
+pub async fn approve(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    field: &str,
+) -> Result<HttpResponse, Error> {
+    let Ok(change) = db::schema_changes::get(org_id, stream_type, stream_name, field).await else {
+        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+            StatusCode::NOT_FOUND.into(),
+            format!("no pending schema change of field [{field}]"),
+        )));
+    };
+    let Some(data_type) = parse_data_type(&change.to_type) else {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            StatusCode::BAD_REQUEST.into(),
+            format!("type {} can't be applied", change.to_type),
+        )));
+    };
+    let schema = Schema::new(vec![Field::new(field, data_type, true)]);
+    if let Err(e) = db::schema::merge(
+        org_id,
+        stream_name,
+        stream_type,
+        &schema,
+        Some(chrono::Utc::now().timestamp_micros()),
+    )
+    .await
+    {
+        return Ok(
+            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR.into(),
+                e.to_string(),
+            )),
+        );
+    }
+    reject(org_id, stream_type, stream_name, field).await
+}
+
+/// Drops a pending change, the records with the new type keep being rejected.
+pub async fn reject(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    field: &str,
+) -> Result<HttpResponse, Error> {
+    LAST_SAVED.remove(&format!("{org_id}/{stream_type}/{stream_name}/{field}"));
+    match db::schema_changes::delete(org_id, stream_type, stream_name, field).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "".to_string(),
+        ))),
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR.into(),
+                e.to_string(),
+            )),
+        ),
+    }
+}
+
+// the types the schema inference of the ingestion gives
+fn parse_data_type(s: &str) -> Option<DataType> {
+    match s {
+        "Utf8" => Some(DataType::Utf8),
+        "Int64" => Some(DataType::Int64),
+        "UInt64" => Some(DataType::UInt64),
+        "Float64" => Some(DataType::Float64),
+        "Boolean" => Some(DataType::Boolean),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_data_type() {
+        for data_type in [DataType::Utf8, DataType::Int64, DataType::Float64] {
+            assert_eq!(parse_data_type(&data_type.to_string()), Some(data_type));
+        }
+        assert_eq!(parse_data_type("Date32"), None);
+    }
+}
@@ -0,0 +1,593 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashSet, io::Error};
+
+use actix_web::{http::StatusCode, HttpResponse};
+use config::get_config;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    common::{
+        infra::config::USERS,
+        meta::{
+            rbac::{Role, BUILT_IN_ROLES},
+            scim::{
+                map_group, parse_filter, parse_group_mapping, parse_member_path, value_as_bool,
+                ScimEmail, ScimError, ScimGroup, ScimListQuery, ScimListResponse, ScimMember,
+                ScimMeta, ScimName, ScimPatchOperation, ScimPatchRequest, ScimUser, CONTENT_TYPE,
+                SCHEMA_GROUP, SCHEMA_USER,
+            },
+            user::{DBUser, User, UserOrg, UserRole},
+        },
+    },
+    service::{db, rbac, users},
+};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const USER_NOT_FOUND: &str = "User not found";
+const GROUP_NOT_FOUND: &str = "Group not found";
+
+pub async fn list_users(org_id: &str, query: ScimListQuery) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    let filter = match query.filter.as_deref().map(parse_filter) {
+        Some(Some((attr, value))) => match attr.as_str() {
+            "userName" | "id" | "emails.value" | "emails" => Some(value.to_lowercase()),
+            _ => {
+                return Ok(error(
+                    StatusCode::BAD_REQUEST,
+                    "Unsupported filter attribute",
+                ));
+            }
+        },
+        Some(None) => return Ok(error(StatusCode::BAD_REQUEST, "Unsupported filter")),
+        None => None,
+    };
+
+    let prefix = format!("{org_id}/");
+    let mut list = USERS
+        .iter()
+        .filter(|user| user.key().starts_with(&prefix))
+        .filter(|user| filter.is_none() || filter.as_deref() == Some(user.value().email.as_str()))
+        .map(|user| user.value().clone())
+        .collect::<Vec<_>>();
+    list.sort_by(|a, b| a.email.cmp(&b.email));
+
+    let roles = rbac::get_roles(org_id);
+    let resp = paginate(&query, list, |user| {
+        serde_json::to_value(to_scim_user(org_id, &user, &roles)).unwrap()
+    });
+    Ok(response(StatusCode::OK, &resp))
+}
+
+pub async fn get_user(org_id: &str, id: &str) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    match USERS.get(&format!("{org_id}/{id}")) {
+        Some(user) => Ok(response(
+            StatusCode::OK,
+            &to_scim_user(org_id, user.value(), &rbac::get_roles(org_id)),
+        )),
+        None => Ok(error(StatusCode::NOT_FOUND, USER_NOT_FOUND)),
+    }
+}
+
+/// Provisions the user into the organization, the user is created as an
+/// external user when it doesn't exist yet.
+pub async fn create_user(org_id: &str, user: ScimUser) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    let email = user.email();
+    if email.is_empty() || !email.contains('@') {
+        return Ok(error(StatusCode::BAD_REQUEST, "userName must be an email"));
+    }
+    let name = user.name.clone().unwrap_or_default();
+    let user_org = UserOrg {
+        name: org_id.to_string(),
+        role: UserRole::Member,
+        ..UserOrg::default()
+    };
+    let db_user = match db::user::get_user_by_email(&email).await {
+        Some(mut db_user) => {
+            if db_user.organizations.iter().any(|org| org.name.eq(org_id)) {
+                let mut err = ScimError::new(StatusCode::CONFLICT.as_u16(), "User already exists");
+                err.scim_type = Some("uniqueness".to_string());
+                return Ok(response(StatusCode::CONFLICT, &err));
+            }
+            db_user.organizations.push(user_org);
+            db_user
+        }
+        None => DBUser {
+            email: email.clone(),
+            first_name: name.given_name,
+            last_name: name.family_name,
+            password: "".to_owned(),
+            salt: "".to_owned(),
+            organizations: vec![user_org],
+            is_external: true,
+            password_ext: Some("".to_owned()),
+            password_updated_at: 0,
+            force_password_reset: false,
+        },
+    };
+    if let Err(e) = users::update_db_user(db_user).await {
+        return Ok(error(StatusCode::INTERNAL_SERVER_ERROR, e));
+    }
+    log::info!("User {email} provisioned into org {org_id} through scim");
+    get_user_response(org_id, &email, StatusCode::CREATED)
+}
+
+/// Replaces the attributes of the user, setting `active` to false removes the
+/// user from the organization.
+pub async fn update_user(org_id: &str, id: &str, user: ScimUser) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    if !USERS.contains_key(&format!("{org_id}/{id}")) {
+        return Ok(error(StatusCode::NOT_FOUND, USER_NOT_FOUND));
+    }
+    if !user.active {
+        return deprovision_response(org_id, id).await;
+    }
+    let name = user.name.unwrap_or_default();
+    update_user_name(org_id, id, Some(name.given_name), Some(name.family_name)).await
+}
+
+/// Applies patch operations to the user, both the Okta form
+/// `{"op": "replace", "value": {"active": false}}` and the Azure AD form
+/// `{"op": "Replace", "path": "active", "value": "False"}` are supported.
+pub async fn patch_user(
+    org_id: &str,
+    id: &str,
+    req: ScimPatchRequest,
+) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    if !USERS.contains_key(&format!("{org_id}/{id}")) {
+        return Ok(error(StatusCode::NOT_FOUND, USER_NOT_FOUND));
+    }
+
+    let mut attrs = Vec::new();
+    for op in req.operations.iter() {
+        let Some(value) = op.value.as_ref() else {
+            continue;
+        };
+        match (op.path.as_deref(), value) {
+            (Some(path), value) => attrs.push((path.to_string(), value.clone())),
+            (None, Value::Object(obj)) => {
+                for (key, value) in obj.iter() {
+                    match value {
+                        Value::Object(inner) if key.eq("name") => attrs
+                            .extend(inner.iter().map(|(k, v)| (format!("name.{k}"), v.clone()))),
+                        _ => attrs.push((key.to_string(), value.clone())),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (mut given_name, mut family_name) = (None, None);
+    for (attr, value) in attrs.iter() {
+        match attr.as_str() {
+            "active" => {
+                if value_as_bool(value) == Some(false) {
+                    return deprovision_response(org_id, id).await;
+                }
+            }
+            "name.givenName" => given_name = value.as_str().map(|v| v.to_string()),
+            "name.familyName" => family_name = value.as_str().map(|v| v.to_string()),
+            _ => {}
+        }
+    }
+    update_user_name(org_id, id, given_name, family_name).await
+}
+
+pub async fn delete_user(org_id: &str, id: &str) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    if !USERS.contains_key(&format!("{org_id}/{id}")) {
+        return Ok(error(StatusCode::NOT_FOUND, USER_NOT_FOUND));
+    }
+    match deprovision(org_id, id).await {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Ok(error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+pub async fn list_groups(org_id: &str, query: ScimListQuery) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    let filter = match query.filter.as_deref().map(parse_filter) {
+        Some(Some((attr, value))) => match attr.as_str() {
+            "displayName" => Some(map_group(&value, &group_rules())),
+            "id" => Some(value),
+            _ => {
+                return Ok(error(
+                    StatusCode::BAD_REQUEST,
+                    "Unsupported filter attribute",
+                ));
+            }
+        },
+        Some(None) => return Ok(error(StatusCode::BAD_REQUEST, "Unsupported filter")),
+        None => None,
+    };
+
+    let list = rbac::get_roles(org_id)
+        .into_iter()
+        .filter(|role| filter.is_none() || filter.as_deref() == Some(role.name.as_str()))
+        .collect::<Vec<_>>();
+    let resp = paginate(&query, list, |role| {
+        serde_json::to_value(to_scim_group(org_id, &role)).unwrap()
+    });
+    Ok(response(StatusCode::OK, &resp))
+}
+
+pub async fn get_group(org_id: &str, id: &str) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    match rbac::get_role(org_id, id) {
+        Some(role) => Ok(response(StatusCode::OK, &to_scim_group(org_id, &role))),
+        None => Ok(error(StatusCode::NOT_FOUND, GROUP_NOT_FOUND)),
+    }
+}
+
+/// Maps the group to a role through `ZO_SCIM_GROUP_MAPPING` and binds its
+/// members to the role, the role is created without grants when it doesn't
+/// exist.
+pub async fn create_group(org_id: &str, group: ScimGroup) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    let name = map_group(&group.display_name, &group_rules());
+    if !rbac::is_valid_role_name(&name) {
+        return Ok(error(StatusCode::BAD_REQUEST, "Invalid group name"));
+    }
+    set_members(org_id, &name, members(&group.members), StatusCode::CREATED).await
+}
+
+pub async fn update_group(org_id: &str, id: &str, group: ScimGroup) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    if rbac::get_role(org_id, id).is_none() {
+        return Ok(error(StatusCode::NOT_FOUND, GROUP_NOT_FOUND));
+    }
+    set_members(org_id, id, members(&group.members), StatusCode::OK).await
+}
+
+pub async fn patch_group(
+    org_id: &str,
+    id: &str,
+    req: ScimPatchRequest,
+) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    let Some(role) = rbac::get_role(org_id, id) else {
+        return Ok(error(StatusCode::NOT_FOUND, GROUP_NOT_FOUND));
+    };
+    let mut users = role.users;
+    for op in req.operations.iter() {
+        if let Err(e) = apply_member_operation(&mut users, op) {
+            return Ok(error(StatusCode::BAD_REQUEST, e));
+        }
+    }
+    set_members(org_id, id, users, StatusCode::OK).await
+}
+
+/// Deletes the role the group is mapped to, built-in roles are kept and only
+/// lose their members.
+pub async fn delete_group(org_id: &str, id: &str) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_enabled() {
+        return Ok(resp);
+    }
+    if rbac::get_role(org_id, id).is_none() {
+        return Ok(error(StatusCode::NOT_FOUND, GROUP_NOT_FOUND));
+    }
+    let ret = if BUILT_IN_ROLES.contains(&id) {
+        rbac::set_role_users(org_id, id, HashSet::new())
+            .await
+            .map(|_| ())
+    } else {
+        db::rbac::delete(org_id, id).await
+    };
+    match ret {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Ok(error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+fn apply_member_operation(
+    users: &mut HashSet<String>,
+    op: &ScimPatchOperation,
+) -> Result<(), String> {
+    let op_name = op.op.to_lowercase();
+    let path = op.path.as_deref().map(|p| p.trim());
+    // `{"op": "replace", "value": {"members": [...]}}` carries the path in the value
+    let value = match (path, op.value.as_ref()) {
+        (None, Some(Value::Object(obj))) => match obj.get("members") {
+            Some(v) => Some(v),
+            // renaming a group doesn't change the role it is mapped to
+            None => return Ok(()),
+        },
+        (_, v) => v,
+    };
+    if let Some(member) = path.and_then(parse_member_path) {
+        if op_name.eq("remove") {
+            users.remove(&member.to_lowercase());
+            return Ok(());
+        }
+        return Err(format!("Unsupported operation {} on {}", op.op, member));
+    }
+    if !matches!(path, None | Some("members")) {
+        return Err(format!("Unsupported path {}", path.unwrap_or_default()));
+    }
+
+    let list = value
+        .and_then(|v| v.as_array())
+        .map(|v| {
+            v.iter()
+                .filter_map(|m| m.get("value").and_then(|v| v.as_str()))
+                .map(|m| m.to_lowercase())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    match op_name.as_str() {
+        "add" => users.extend(list),
+        "replace" => *users = list.into_iter().collect(),
+        "remove" if value.is_none() => users.clear(),
+        "remove" => list.iter().for_each(|m| {
+            users.remove(m);
+        }),
+        _ => return Err(format!("Unsupported operation {}", op.op)),
+    }
+    Ok(())
+}
+
+async fn set_members(
+    org_id: &str,
+    name: &str,
+    users: HashSet<String>,
+    status: StatusCode,
+) -> Result<HttpResponse, Error> {
+    match rbac::set_role_users(org_id, name, users).await {
+        Ok(role) => Ok(response(status, &to_scim_group(org_id, &role))),
+        Err(e) => Ok(error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn update_user_name(
+    org_id: &str,
+    id: &str,
+    given_name: Option<String>,
+    family_name: Option<String>,
+) -> Result<HttpResponse, Error> {
+    if given_name.is_some() || family_name.is_some() {
+        let mut db_user = match db::user::get_db_user(id).await {
+            Ok(user) => user,
+            Err(_) => return Ok(error(StatusCode::NOT_FOUND, USER_NOT_FOUND)),
+        };
+        if let Some(given_name) = given_name {
+            db_user.first_name = given_name;
+        }
+        if let Some(family_name) = family_name {
+            db_user.last_name = family_name;
+        }
+        if let Err(e) = users::update_db_user(db_user).await {
+            return Ok(error(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    }
+    get_user_response(org_id, id, StatusCode::OK)
+}
+
+async fn deprovision_response(org_id: &str, id: &str) -> Result<HttpResponse, Error> {
+    let user = USERS
+        .get(&format!("{org_id}/{id}"))
+        .map(|user| user.value().clone());
+    if let Err(e) = deprovision(org_id, id).await {
+        return Ok(error(StatusCode::INTERNAL_SERVER_ERROR, e));
+    }
+    match user {
+        Some(user) => {
+            let mut user = to_scim_user(org_id, &user, &[]);
+            user.active = false;
+            Ok(response(StatusCode::OK, &user))
+        }
+        None => Ok(error(StatusCode::NOT_FOUND, USER_NOT_FOUND)),
+    }
+}
+
+/// Removes the user from the organization and from its roles, the user is
+/// deleted when it doesn't belong to any other organization.
+async fn deprovision(org_id: &str, id: &str) -> Result<(), anyhow::Error> {
+    let mut db_user = db::user::get_db_user(id).await?;
+    db_user.organizations.retain(|org| !org.name.eq(org_id));
+    if db_user.organizations.is_empty() {
+        db::user::delete(id).await?;
+    } else {
+        db::user::set(&db_user).await?;
+    }
+    USERS.remove(&format!("{org_id}/{id}"));
+    rbac::remove_user_from_roles(org_id, id).await?;
+    log::info!("User {id} deprovisioned from org {org_id} through scim");
+    Ok(())
+}
+
+fn get_user_response(org_id: &str, id: &str, status: StatusCode) -> Result<HttpResponse, Error> {
+    match USERS.get(&format!("{org_id}/{id}")) {
+        Some(user) => Ok(response(
+            status,
+            &to_scim_user(org_id, user.value(), &rbac::get_roles(org_id)),
+        )),
+        None => Ok(error(StatusCode::NOT_FOUND, USER_NOT_FOUND)),
+    }
+}
+
+fn to_scim_user(org_id: &str, user: &User, roles: &[Role]) -> ScimUser {
+    ScimUser {
+        schemas: vec![SCHEMA_USER.to_string()],
+        id: user.email.clone(),
+        external_id: None,
+        user_name: user.email.clone(),
+        name: Some(ScimName {
+            given_name: user.first_name.clone(),
+            family_name: user.last_name.clone(),
+        }),
+        emails: vec![ScimEmail {
+            value: user.email.clone(),
+            primary: true,
+        }],
+        active: true,
+        groups: roles
+            .iter()
+            .filter(|role| role.users.contains(&user.email))
+            .map(|role| ScimMember {
+                value: role.name.clone(),
+                display: Some(role.name.clone()),
+            })
+            .collect(),
+        meta: Some(ScimMeta {
+            resource_type: "User".to_string(),
+            location: format!("/api/{org_id}/scim/v2/Users/{}", user.email),
+        }),
+    }
+}
+
+fn to_scim_group(org_id: &str, role: &Role) -> ScimGroup {
+    let mut members = role
+        .users
+        .iter()
+        .map(|user| ScimMember {
+            value: user.clone(),
+            display: Some(user.clone()),
+        })
+        .collect::<Vec<_>>();
+    members.sort_by(|a, b| a.value.cmp(&b.value));
+    ScimGroup {
+        schemas: vec![SCHEMA_GROUP.to_string()],
+        id: role.name.clone(),
+        display_name: role.name.clone(),
+        members,
+        meta: Some(ScimMeta {
+            resource_type: "Group".to_string(),
+            location: format!("/api/{org_id}/scim/v2/Groups/{}", role.name),
+        }),
+    }
+}
+
+fn members(members: &[ScimMember]) -> HashSet<String> {
+    members
+        .iter()
+        .map(|member| member.value.trim().to_lowercase())
+        .filter(|member| !member.is_empty())
+        .collect()
+}
+
+// start index of scim list queries is 1-based
+fn paginate<T>(query: &ScimListQuery, list: Vec<T>, f: impl Fn(T) -> Value) -> ScimListResponse {
+    let start_index = query.start_index.unwrap_or(1).max(1);
+    let count = query.count.unwrap_or(DEFAULT_PAGE_SIZE);
+    let total = list.len();
+    let page = list
+        .into_iter()
+        .skip(start_index - 1)
+        .take(count)
+        .map(f)
+        .collect();
+    ScimListResponse::new(total, start_index, page)
+}
+
+fn group_rules() -> Vec<(String, String)> {
+    parse_group_mapping(&get_config().scim.group_mapping)
+}
+
+fn check_enabled() -> Option<HttpResponse> {
+    if get_config().scim.enabled {
+        None
+    } else {
+        Some(error(StatusCode::NOT_FOUND, "SCIM is not enabled"))
+    }
+}
+
+fn response(status: StatusCode, body: &impl Serialize) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type(CONTENT_TYPE)
+        .json(body)
+}
+
+fn error(status: StatusCode, detail: impl ToString) -> HttpResponse {
+    response(status, &ScimError::new(status.as_u16(), detail))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn op(value: Value) -> ScimPatchOperation {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_apply_member_operation() {
+        let mut users = HashSet::from(["a@example.com".to_string()]);
+        apply_member_operation(
+            &mut users,
+            &op(json!({"op": "add", "path": "members", "value": [{"value": "B@example.com"}]})),
+        )
+        .unwrap();
+        assert!(users.contains("b@example.com"));
+
+        apply_member_operation(
+            &mut users,
+            &op(json!({"op": "Remove", "path": "members[value eq \"a@example.com\"]"})),
+        )
+        .unwrap();
+        assert!(!users.contains("a@example.com"));
+
+        apply_member_operation(
+            &mut users,
+            &op(json!({"op": "replace", "value": {"members": [{"value": "c@example.com"}]}})),
+        )
+        .unwrap();
+        assert_eq!(users, HashSet::from(["c@example.com".to_string()]));
+
+        apply_member_operation(
+            &mut users,
+            &op(json!({"op": "replace", "value": {"displayName": "renamed"}})),
+        )
+        .unwrap();
+        assert_eq!(users.len(), 1);
+
+        assert!(
+            apply_member_operation(
+                &mut users,
+                &op(json!({"op": "add", "path": "displayName", "value": "x"})),
+            )
+            .is_err()
+        );
+    }
+}
@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{cmp::min, io::Cursor, sync::Arc};
+use std::{cmp::min, future::Future, io::Cursor, sync::Arc, time::Duration};
 
 use ::datafusion::arrow::{datatypes::Schema, ipc, record_batch::RecordBatch};
 use async_recursion::async_recursion;
@@ -21,7 +21,8 @@ use config::{
     cluster::{is_ingester, is_querier},
     get_config,
     meta::{
-        cluster::{Node, Role},
+        cluster::{Node, NodeStatus, ZoneLocality, GRPC_MIN_VERSION, GRPC_VERSION_HEADER},
+        logger::{get_request_id, REQUEST_ID_HEADER},
         search::{self, ScanStats},
         stream::{
             FileKey, PartitionTimeLevel, QueryPartitionStrategy, StreamPartition, StreamType,
@@ -41,19 +42,24 @@ use proto::cluster_rpc;
 use tonic::{
     codec::CompressionEncoding,
     metadata::{MetadataKey, MetadataValue},
-    transport::Channel,
     Request,
 };
 use tracing::{info_span, Instrument};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::{common::infra::cluster as infra_cluster, service::file_list};
+use crate::{
+    common::infra::{cluster as infra_cluster, tls},
+    service::file_list,
+};
 
 pub mod grpc;
 pub mod http;
 #[cfg(feature = "enterprise")]
 pub mod super_cluster;
 
+// how often a node is checked to still be online while it searches
+const NODE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 #[async_recursion]
 #[tracing::instrument(
     name = "service:search:cluster:run",
@@ -81,7 +87,17 @@ pub async fn search(
     nodes.sort_by(|a, b| a.grpc_addr.cmp(&b.grpc_addr));
     nodes.dedup_by(|a, b| a.grpc_addr == b.grpc_addr);
     nodes.sort_by_key(|x| x.id);
-    let nodes = nodes;
+    let region = if cfg.s3.region_name.is_empty() {
+        &cfg.common.node_region
+    } else {
+        &cfg.s3.region_name
+    };
+    let nodes = filter_nodes_by_zone(
+        nodes,
+        &cfg.common.node_zone,
+        region,
+        ZoneLocality::from(cfg.common.query_zone_locality.as_str()),
+    )?;
 
     let querier_num = nodes.iter().filter(|node| is_querier(&node.role)).count();
     if querier_num == 0 {
@@ -337,7 +353,7 @@ pub async fn search(
             1
         }
         QueryPartitionStrategy::FileHash => {
-            let files = partition_file_by_hash(&file_list, &nodes).await;
+            let files = partition_file_by_hash(&file_list, &nodes);
             file_num = files.len();
             partition_files = files;
             1
@@ -417,16 +433,6 @@ pub async fn search(
             }
         }
 
-        let req_files = req.file_list.len();
-        let node_addr = node.grpc_addr.clone();
-        let grpc_span = info_span!(
-            "service:search:cluster:grpc_search",
-            trace_id,
-            org_id = req.org_id,
-            node_id = node.id,
-            node_addr = node_addr.as_str(),
-        );
-
         #[cfg(feature = "enterprise")]
         let (abort_sender, abort_receiver) = tokio::sync::oneshot::channel();
         #[cfg(feature = "enterprise")]
@@ -449,109 +455,41 @@ pub async fn search(
             ))));
         }
 
-        let task = tokio::task::spawn(
-            async move {
-                let cfg = config::get_config();
-                let org_id: MetadataValue<_> = req
-                    .org_id
-                    .parse()
-                    .map_err(|_| Error::Message("invalid org_id".to_string()))?;
-                let mut request = tonic::Request::new(req);
-                // request.set_timeout(Duration::from_secs(cfg.grpc.timeout));
-
-                opentelemetry::global::get_text_map_propagator(|propagator| {
-                    propagator.inject_context(
-                        &tracing::Span::current().context(),
-                        &mut super::MetadataMap(request.metadata_mut()),
-                    )
-                });
-
-                log::info!("[trace_id {trace_id}] search->grpc: request node: {}, is_querier: {}, files: {req_files}", &node_addr, is_querier);
-
-                let org_header_key: MetadataKey<_> = cfg
-                .grpc
-                .org_header_key
-                .parse()
-                .map_err(|_| Error::Message("invalid org_header_key".to_string()))?;
-                let token: MetadataValue<_> = infra_cluster::get_internal_grpc_token()
-                    .parse()
-                    .map_err(|_| Error::Message("invalid token".to_string()))?;
-                let channel = Channel::from_shared(node_addr)
-                    .unwrap()
-                    .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
-                    .connect()
-                    .await
-                    .map_err(|err| {
-                        log::error!("[trace_id {trace_id}] search->grpc: node: {}, connect err: {:?}", &node.grpc_addr, err);
-                        super::server_internal_error("connect search node error")
-                    })?;
-                let mut client = cluster_rpc::search_client::SearchClient::with_interceptor(
-                    channel,
-                    move |mut req: Request<()>| {
-                        req.metadata_mut().insert("authorization", token.clone());
-                        req.metadata_mut()
-                            .insert(org_header_key.clone(), org_id.clone());
-                        Ok(req)
-                    },
-                );
-                client = client
-                    .send_compressed(CompressionEncoding::Gzip)
-                    .accept_compressed(CompressionEncoding::Gzip)
-                    .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
-                    .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024);
-                let response;
-                tokio::select! {
-                    result = client.search(request) => {
-                        match result {
-                            Ok(res) => response = res.into_inner(),
-                            Err(err) => {
-                                log::error!("[trace_id {trace_id}] search->grpc: node: {}, search err: {:?}", &node.grpc_addr, err);
-                                if err.code() == tonic::Code::Internal {
-                                    let err = ErrorCodes::from_json(err.message())?;
-                                    return Err(Error::ErrorCode(err));
-                                }
-                                return Err(super::server_internal_error("search node error"));
-                            }
-                        }
-                    }
-                    _ = async {
-                        #[cfg(feature = "enterprise")]
-                        let _ = abort_receiver.await;
-                        #[cfg(not(feature = "enterprise"))]
-                        futures::future::pending::<()>().await;
-                    } => {
-                        log::info!("[trace_id {trace_id}] search->grpc: cancel search in node: {:?}", &node.grpc_addr);
-                        return Err(Error::ErrorCode(ErrorCodes::SearchCancelQuery(format!("[trace_id {trace_id}] search->grpc: search canceled"))));
-                    }
-                }
-
-                log::info!(
-                    "[trace_id {trace_id}] search->grpc: response node: {}, is_querier: {}, total: {}, took: {} ms, files: {}, scan_size: {}",
-                    &node.grpc_addr,
-                    is_querier,
-                    response.total,
-                    response.took,
-                    response.scan_stats.as_ref().unwrap().files,
-                    response.scan_stats.as_ref().unwrap().original_size,
-                );
-                Ok((node.clone(),response))
-            }
-            .instrument(grpc_span),
-        );
-        tasks.push(task);
+        #[cfg(feature = "enterprise")]
+        let abort = async move {
+            let _ = abort_receiver.await;
+        };
+        #[cfg(not(feature = "enterprise"))]
+        let abort = super::tracker::cancelled(&trace_id);
+        let task = spawn_node_search(trace_id, node.clone(), req.clone(), abort);
+        tasks.push((task, node, req));
     }
 
     let mut results = Vec::new();
     let mut succeed = 0;
     let mut last_error = None;
-    for task in tasks {
-        match task.await {
-            Ok(res) => match res {
-                Ok(res) => {
+    let mut failed_nodes = HashSet::new();
+    let mut retries = 0;
+    while !tasks.is_empty() {
+        let mut failed_reqs = Vec::new();
+        for (task, node, node_req) in tasks {
+            match task.await {
+                Ok(Ok(res)) => {
                     succeed += 1;
                     results.push(res);
                 }
-                Err(err) => {
+                Ok(Err(NodeSearchError::NodeFailure(err)))
+                    if node_req.stype == cluster_rpc::SearchType::Cluster as i32
+                        && !node_req.file_list.is_empty() =>
+                {
+                    log::error!(
+                        "[trace_id {trace_id}] search->grpc: node {} failed: {err}",
+                        &node.grpc_addr
+                    );
+                    failed_nodes.insert(node.uuid.clone());
+                    failed_reqs.push((node, node_req, err));
+                }
+                Ok(Err(NodeSearchError::NodeFailure(err) | NodeSearchError::Query(err))) => {
                     results.push((
                         Node::default(),
                         cluster_rpc::SearchResponse {
@@ -562,23 +500,88 @@ pub async fn search(
                     log::error!("[trace_id {trace_id}] search->grpc: node search error: {err}");
                     last_error = Some(err);
                 }
-            },
-            Err(e) => {
-                // search done, release lock
-                #[cfg(not(feature = "enterprise"))]
-                dist_lock::unlock(&locker).await?;
-                #[cfg(feature = "enterprise")]
-                work_group
-                    .as_ref()
-                    .unwrap()
-                    .done(trace_id)
-                    .await
-                    .map_err(|e| Error::Message(e.to_string()))?;
-                return Err(Error::ErrorCode(ErrorCodes::ServerInternalError(
-                    e.to_string(),
-                )));
+                Err(e) => {
+                    // search done, release lock
+                    #[cfg(not(feature = "enterprise"))]
+                    dist_lock::unlock(&locker).await?;
+                    #[cfg(feature = "enterprise")]
+                    work_group
+                        .as_ref()
+                        .unwrap()
+                        .done(trace_id)
+                        .await
+                        .map_err(|e| Error::Message(e.to_string()))?;
+                    return Err(Error::ErrorCode(ErrorCodes::ServerInternalError(
+                        e.to_string(),
+                    )));
+                }
             }
         }
+
+        // reassign the files of the failed nodes to the queriers that are still alive
+        tasks = Vec::new();
+        for (node, mut node_req, err) in failed_reqs {
+            let new_node = if retries < cfg.limit.query_node_retries {
+                get_retry_node(&failed_nodes, retries).await
+            } else {
+                None
+            };
+            let Some(new_node) = new_node else {
+                results.push((
+                    Node::default(),
+                    cluster_rpc::SearchResponse {
+                        is_partial: true,
+                        ..Default::default()
+                    },
+                ));
+                last_error = Some(err);
+                continue;
+            };
+            retries += 1;
+            // the wal of a failed ingester can't be searched anywhere else
+            if is_ingester(&node.role) {
+                results.push((
+                    Node::default(),
+                    cluster_rpc::SearchResponse {
+                        is_partial: true,
+                        ..Default::default()
+                    },
+                ));
+            }
+            log::warn!(
+                "[trace_id {trace_id}] search->grpc: reassign {} files of node {} to node {}",
+                node_req.file_list.len(),
+                &node.grpc_addr,
+                &new_node.grpc_addr
+            );
+            #[cfg(feature = "enterprise")]
+            let abort = {
+                let (abort_sender, abort_receiver) = tokio::sync::oneshot::channel();
+                if super::SEARCH_SERVER
+                    .insert_sender(trace_id, abort_sender)
+                    .await
+                    .is_err()
+                {
+                    log::info!("[trace_id {trace_id}] search->grpc: search canceled before retry");
+                    last_error = Some(Error::ErrorCode(ErrorCodes::SearchCancelQuery(format!(
+                        "[trace_id {trace_id}] search->grpc: search canceled before retry"
+                    ))));
+                    continue;
+                }
+                async move {
+                    let _ = abort_receiver.await;
+                }
+            };
+            #[cfg(not(feature = "enterprise"))]
+            let abort = super::tracker::cancelled(trace_id);
+            let task = spawn_node_search(
+                trace_id.to_string(),
+                new_node.clone(),
+                node_req.clone(),
+                abort,
+            );
+            tasks.push((task, new_node, node_req));
+        }
     }
     if succeed == 0 || results.iter().map(|(_, v)| v.total).sum::<i64>() == 0 {
         if let Some(err) = last_error {
@@ -619,6 +622,196 @@ pub async fn search(
     Ok((merge_batches, scan_stats, took_wait, is_partial))
 }
 
+/// Why the partial search on a node failed, only node failures are retried on
+/// another querier.
+enum NodeSearchError {
+    /// the node went away: connection failure, broken stream or expired lease
+    NodeFailure(Error),
+    /// the node rejected the search, retrying it elsewhere fails the same way
+    Query(Error),
+}
+
+impl From<Error> for NodeSearchError {
+    fn from(err: Error) -> Self {
+        NodeSearchError::Query(err)
+    }
+}
+
+type NodeSearchTask = tokio::task::JoinHandle<
+    std::result::Result<(Node, cluster_rpc::SearchResponse), NodeSearchError>,
+>;
+
+fn spawn_node_search(
+    trace_id: String,
+    node: Node,
+    req: cluster_rpc::SearchRequest,
+    abort: impl Future<Output = ()> + Send + 'static,
+) -> NodeSearchTask {
+    let is_querier = is_querier(&node.role);
+    let req_files = req.file_list.len();
+    let node_addr = node.grpc_addr.clone();
+    let grpc_span = info_span!(
+        "service:search:cluster:grpc_search",
+        trace_id,
+        org_id = req.org_id,
+        node_id = node.id,
+        node_addr = node_addr.as_str(),
+    );
+    let request_id = get_request_id().and_then(|id| id.parse::<MetadataValue<_>>().ok());
+    tokio::task::spawn(
+        async move {
+            let cfg = config::get_config();
+            let org_id: MetadataValue<_> = req
+                .org_id
+                .parse()
+                .map_err(|_| Error::Message("invalid org_id".to_string()))?;
+            let mut request = tonic::Request::new(req);
+            // request.set_timeout(Duration::from_secs(cfg.grpc.timeout));
+
+            opentelemetry::global::get_text_map_propagator(|propagator| {
+                propagator.inject_context(
+                    &tracing::Span::current().context(),
+                    &mut super::MetadataMap(request.metadata_mut()),
+                )
+            });
+
+            log::info!(
+                "[trace_id {trace_id}] search->grpc: request node: {}, is_querier: {}, files: {req_files}",
+                &node_addr,
+                is_querier
+            );
+
+            let org_header_key: MetadataKey<_> = cfg
+                .grpc
+                .org_header_key
+                .parse()
+                .map_err(|_| Error::Message("invalid org_header_key".to_string()))?;
+            let token: MetadataValue<_> = infra_cluster::get_internal_grpc_token()
+                .parse()
+                .map_err(|_| Error::Message("invalid token".to_string()))?;
+            let grpc_version: MetadataValue<_> = node
+                .negotiate_grpc_version()
+                .unwrap_or(GRPC_MIN_VERSION)
+                .into();
+            let channel = tls::grpc_endpoint(node_addr)
+                .unwrap()
+                .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
+                .connect()
+                .await
+                .map_err(|err| {
+                    log::error!(
+                        "[trace_id {trace_id}] search->grpc: node: {}, connect err: {:?}",
+                        &node.grpc_addr,
+                        err
+                    );
+                    NodeSearchError::NodeFailure(super::server_internal_error(
+                        "connect search node error",
+                    ))
+                })?;
+            let mut client = cluster_rpc::search_client::SearchClient::with_interceptor(
+                channel,
+                move |mut req: Request<()>| {
+                    req.metadata_mut().insert("authorization", token.clone());
+                    req.metadata_mut()
+                        .insert(GRPC_VERSION_HEADER, grpc_version.clone());
+                    req.metadata_mut()
+                        .insert(org_header_key.clone(), org_id.clone());
+                    if let Some(request_id) = &request_id {
+                        req.metadata_mut()
+                            .insert(REQUEST_ID_HEADER, request_id.clone());
+                    }
+                    Ok(req)
+                },
+            );
+            client = client
+                .send_compressed(CompressionEncoding::Gzip)
+                .accept_compressed(CompressionEncoding::Gzip)
+                .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
+                .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024);
+            let response;
+            tokio::select! {
+                result = client.search(request) => {
+                    match result {
+                        Ok(res) => response = res.into_inner(),
+                        Err(err) => {
+                            log::error!("[trace_id {trace_id}] search->grpc: node: {}, search err: {:?}", &node.grpc_addr, err);
+                            if err.code() == tonic::Code::Internal {
+                                let err = ErrorCodes::from_json(err.message())?;
+                                return Err(Error::ErrorCode(err).into());
+                            }
+                            let node_failed = is_node_failure(err.code());
+                            let err = super::server_internal_error("search node error");
+                            return Err(if node_failed { NodeSearchError::NodeFailure(err) } else { err.into() });
+                        }
+                    }
+                }
+                _ = wait_node_offline(&node.uuid) => {
+                    log::error!("[trace_id {trace_id}] search->grpc: node: {}, lease expired during the search", &node.grpc_addr);
+                    return Err(NodeSearchError::NodeFailure(super::server_internal_error("search node left the cluster")));
+                }
+                _ = abort => {
+                    log::info!("[trace_id {trace_id}] search->grpc: cancel search in node: {:?}", &node.grpc_addr);
+                    return Err(Error::ErrorCode(ErrorCodes::SearchCancelQuery(format!("[trace_id {trace_id}] search->grpc: search canceled"))).into());
+                }
+            }
+
+            log::info!(
+                "[trace_id {trace_id}] search->grpc: response node: {}, is_querier: {}, total: {}, took: {} ms, files: {}, scan_size: {}",
+                &node.grpc_addr,
+                is_querier,
+                response.total,
+                response.took,
+                response.scan_stats.as_ref().unwrap().files,
+                response.scan_stats.as_ref().unwrap().original_size,
+            );
+            Ok((node.clone(), response))
+        }
+        .instrument(grpc_span),
+    )
+}
+
+// status codes of a broken connection or stream, the node itself is gone
+fn is_node_failure(code: tonic::Code) -> bool {
+    matches!(
+        code,
+        tonic::Code::Unavailable
+            | tonic::Code::Unknown
+            | tonic::Code::Cancelled
+            | tonic::Code::Aborted
+            | tonic::Code::DeadlineExceeded
+    )
+}
+
+/// Resolves once the node isn't online in the cluster anymore, e.g. when its
+/// lease expired without the grpc stream breaking.
+async fn wait_node_offline(uuid: &str) {
+    let mut interval = tokio::time::interval(NODE_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        match infra_cluster::get_node_by_uuid(uuid).await {
+            Some(node) if node.status == NodeStatus::Online => {}
+            _ => return,
+        }
+    }
+}
+
+/// Picks an online querier that didn't fail during the search, nodes that
+/// also ingest are skipped because they already searched their own wal.
+async fn get_retry_node(failed_nodes: &HashSet<String>, retry: usize) -> Option<Node> {
+    let mut nodes = infra_cluster::get_cached_online_querier_nodes()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|node| !is_ingester(&node.role) && !failed_nodes.contains(&node.uuid))
+        .collect::<Vec<_>>();
+    if nodes.is_empty() {
+        return None;
+    }
+    nodes.sort_by_key(|x| x.id);
+    let idx = retry % nodes.len();
+    Some(nodes.swap_remove(idx))
+}
+
 async fn merge_grpc_result(
     trace_id: &str,
     sql: Arc<super::sql::Sql>,
@@ -723,7 +916,7 @@ async fn merge_grpc_result(
                 #[cfg(feature = "enterprise")]
                 let _ = abort_receiver.await;
                 #[cfg(not(feature = "enterprise"))]
-                futures::future::pending::<()>().await;
+                super::tracker::cancelled(trace_id).await;
             } => {
                 log::info!("[trace_id {trace_id}] search->cluster: final merge task is cancel");
                 return Err(Error::ErrorCode(ErrorCodes::SearchCancelQuery(format!("[trace_id {trace_id}] search->cluster: final merge task is cancel"))));
@@ -750,7 +943,7 @@ pub(crate) async fn get_file_list(
             .len()
             <= 1;
     let (time_min, time_max) = sql.meta.time_range.unwrap();
-    let file_list = match file_list::query(
+    let file_list = match file_list::query_for_search(
         &sql.org_id,
         &sql.stream_name,
         stream_type,
@@ -804,7 +997,7 @@ pub(crate) fn partition_file_by_bytes(
     partitions
 }
 
-pub(crate) async fn partition_file_by_hash<'a>(
+pub(crate) fn partition_file_by_hash<'a>(
     file_keys: &'a [FileKey],
     nodes: &'a [Node],
 ) -> Vec<Vec<&'a FileKey>> {
@@ -819,15 +1012,47 @@ pub(crate) async fn partition_file_by_hash<'a>(
     }
     let mut partitions: Vec<Vec<&FileKey>> = vec![Vec::new(); idx];
     for fk in file_keys {
-        let node_uuid = infra_cluster::get_node_from_consistent_hash(&fk.key, &Role::Querier)
-            .await
-            .expect("there is no querier node in consistent hash ring");
-        let idx = node_idx.get(&node_uuid).unwrap_or(&0);
+        let node = infra_cluster::get_querier_from_rendezvous_hash(&fk.key, nodes)
+            .expect("there is no querier node to search the files");
+        let idx = node_idx.get(&node.uuid).unwrap_or(&0);
         partitions[*idx].push(fk);
     }
     partitions
 }
 
+/// Keeps the queriers in the zone of this node and in the region of the object
+/// store so that files aren't downloaded across zones, ingesters are always
+/// kept because only they can search their wal.
+pub(crate) fn filter_nodes_by_zone(
+    nodes: Vec<Node>,
+    zone: &str,
+    region: &str,
+    locality: ZoneLocality,
+) -> Result<Vec<Node>> {
+    if locality == ZoneLocality::Disabled || (zone.is_empty() && region.is_empty()) {
+        return Ok(nodes);
+    }
+    let is_local = |node: &Node| {
+        (zone.is_empty() || node.zone.eq(zone))
+            && (region.is_empty() || node.region.is_empty() || node.region.eq(region))
+    };
+    if nodes
+        .iter()
+        .any(|node| is_querier(&node.role) && is_local(node))
+    {
+        return Ok(nodes
+            .into_iter()
+            .filter(|node| !is_querier(&node.role) || is_ingester(&node.role) || is_local(node))
+            .collect());
+    }
+    match locality {
+        ZoneLocality::Require => Err(Error::Message(format!(
+            "no querier node online in zone {zone}"
+        ))),
+        _ => Ok(nodes),
+    }
+}
+
 fn handle_table_response(
     schema: Arc<Schema>,
     sources: Vec<json::Value>,
@@ -894,8 +1119,45 @@ fn handle_metrics_response(sources: Vec<json::Value>) -> Vec<json::Value> {
 
 #[cfg(test)]
 mod tests {
+    use config::meta::cluster::Role;
+
     use super::*;
 
+    #[test]
+    fn test_is_node_failure() {
+        assert!(is_node_failure(tonic::Code::Unavailable));
+        assert!(is_node_failure(tonic::Code::Cancelled));
+        assert!(!is_node_failure(tonic::Code::Internal));
+        assert!(!is_node_failure(tonic::Code::InvalidArgument));
+        assert!(!is_node_failure(tonic::Code::PermissionDenied));
+    }
+
+    #[test]
+    fn test_filter_nodes_by_zone() {
+        let node = |uuid: &str, role: Role, zone: &str| Node {
+            uuid: uuid.to_string(),
+            role: vec![role],
+            zone: zone.to_string(),
+            ..Default::default()
+        };
+        let nodes = vec![
+            node("q1", Role::Querier, "a"),
+            node("q2", Role::Querier, "b"),
+            node("i1", Role::Ingester, "b"),
+        ];
+        let uuids = |nodes: Vec<Node>| nodes.into_iter().map(|n| n.uuid).collect::<Vec<_>>();
+
+        let ret = filter_nodes_by_zone(nodes.clone(), "a", "", ZoneLocality::Prefer).unwrap();
+        assert_eq!(uuids(ret), vec!["q1", "i1"]);
+        let ret = filter_nodes_by_zone(nodes.clone(), "a", "", ZoneLocality::Disabled).unwrap();
+        assert_eq!(ret.len(), 3);
+        let ret = filter_nodes_by_zone(nodes.clone(), "c", "", ZoneLocality::Prefer).unwrap();
+        assert_eq!(ret.len(), 3);
+        assert!(filter_nodes_by_zone(nodes.clone(), "c", "", ZoneLocality::Require).is_err());
+        let ret = filter_nodes_by_zone(nodes, "", "", ZoneLocality::Require).unwrap();
+        assert_eq!(ret.len(), 3);
+    }
+
     #[test]
     fn test_partition_file_by_bytes() {
         use config::meta::stream::FileMeta;
@@ -49,6 +49,7 @@ use datafusion::{
         runtime_env::{RuntimeConfig, RuntimeEnv},
     },
     logical_expr::expr::Alias,
+    physical_plan::displayable,
     prelude::{cast, col, lit, Expr, SessionContext},
     scalar::ScalarValue,
 };
@@ -63,7 +64,7 @@ use regex::Regex;
 use super::{storage::file_list, transform_udf::get_all_transform};
 use crate::{
     common::meta::functions::VRLResultResolver,
-    service::search::{datafusion::rewrite, sql::Sql, RE_SELECT_WILDCARD},
+    service::search::{datafusion::rewrite, sql::Sql, tracker, RE_SELECT_WILDCARD},
 };
 
 const DATAFUSION_MIN_MEM: usize = 1024 * 1024 * 256; // 256MB
@@ -129,6 +130,9 @@ pub async fn sql(
     // register UDF
     register_udf(&mut ctx, &sql.org_id).await;
 
+    // report the memory of the query
+    tracker::add_memory_pool(&session.id, ctx.runtime_env().memory_pool.clone());
+
     let mut result: HashMap<String, Vec<RecordBatch>> = HashMap::new();
 
     // query sql
@@ -217,6 +221,33 @@ pub async fn sql(
     Ok(result)
 }
 
+/// Plans the query against an empty table of the stream schema, returns the
+/// optimized logical plan and the physical plan without reading any data.
+pub async fn explain(
+    session: &SearchSession,
+    schema: Arc<Schema>,
+    sql: &Sql,
+) -> Result<(String, String)> {
+    let mut ctx =
+        prepare_datafusion_context(session.work_group.clone(), &session.search_type, false).await?;
+    let mem_table = Arc::new(MemTable::try_new(schema, vec![vec![]])?);
+    ctx.register_table("tbl", mem_table)?;
+    register_udf(&mut ctx, &sql.org_id).await;
+
+    let query = if !sql.query_context.is_empty() {
+        sql.query_context.replace(&sql.stream_name, "tbl")
+    } else {
+        sql.origin_sql.clone()
+    };
+    let df = ctx.sql(&query).await?;
+    let physical_plan = df.clone().create_physical_plan().await?;
+    let logical_plan = df.into_optimized_plan()?;
+    Ok((
+        logical_plan.display_indent().to_string(),
+        displayable(physical_plan.as_ref()).indent(true).to_string(),
+    ))
+}
+
 async fn exec_query(
     ctx: &SessionContext,
     session: &SearchSession,
@@ -1132,6 +1163,12 @@ pub async fn create_runtime_env(_work_group: Option<String>) -> Result<RuntimeEn
                 log::debug!("[datafusion:{}] memory pool size: {}", wg, memory_size);
             }
         }
+        // a search can't take more than the budget the admission reserved for it
+        let memory_size = if cfg.limit.query_memory_budget > 0 {
+            std::cmp::min(memory_size, cfg.limit.query_memory_budget)
+        } else {
+            memory_size
+        };
         let memory_size = std::cmp::max(DATAFUSION_MIN_MEM, memory_size);
         let mem_pool = super::MemoryPoolType::from_str(&cfg.memory_cache.datafusion_memory_pool)
             .map_err(|e| {
@@ -1179,6 +1216,11 @@ async fn register_udf(ctx: &mut SessionContext, _org_id: &str) {
     ctx.register_udf(super::time_range_udf::TIME_RANGE_UDF.clone());
     ctx.register_udf(super::date_format_udf::DATE_FORMAT_UDF.clone());
     ctx.register_udf(super::string_to_array_v2_udf::STRING_TO_ARRAY_V2_UDF.clone());
+    ctx.register_udf(super::log_template_udf::LOG_TEMPLATE_UDF.clone());
+    ctx.register_udf(super::field_type_udf::IP_IN_CIDR_UDF.clone());
+    ctx.register_udf(super::field_type_udf::IP_SORT_KEY_UDF.clone());
+    ctx.register_udf(super::field_type_udf::DURATION_TO_MICROS_UDF.clone());
+    ctx.register_udf(super::field_type_udf::UUID_NORMALIZE_UDF.clone());
 
     {
         let udf_list = get_all_transform(_org_id).await;
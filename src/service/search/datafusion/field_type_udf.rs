@@ -0,0 +1,301 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The functions of the logical field types, see
+//! [`config::meta::stream::FieldType`].
+
+use std::{net::IpAddr, str::FromStr, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, BooleanArray, Int64Array, StringArray},
+        datatypes::DataType,
+    },
+    common::cast::as_string_array,
+    error::DataFusionError,
+    logical_expr::{ScalarUDF, Volatility},
+    prelude::create_udf,
+    scalar::ScalarValue,
+    sql::sqlparser::parser::ParserError,
+};
+use datafusion_expr::ColumnarValue;
+use ipnetwork::IpNetwork;
+use once_cell::sync::Lazy;
+
+/// Implementation of ip_in_cidr, returns true when the address is in the
+/// network, e.g. `ip_in_cidr(client_ip, '10.0.0.0/8')`.
+pub(crate) static IP_IN_CIDR_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        "ip_in_cidr",
+        // expects the address and the network
+        vec![DataType::Utf8, DataType::Utf8],
+        Arc::new(DataType::Boolean),
+        Volatility::Immutable,
+        Arc::new(ip_in_cidr_expr_impl),
+    )
+});
+
+/// Implementation of ip_sort_key, returns a key that sorts the addresses by
+/// their numeric value, the ipv4 addresses as ipv4 mapped ipv6 ones.
+pub(crate) static IP_SORT_KEY_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        "ip_sort_key",
+        vec![DataType::Utf8],
+        Arc::new(DataType::Utf8),
+        Volatility::Immutable,
+        Arc::new(ip_sort_key_expr_impl),
+    )
+});
+
+/// Implementation of duration_to_micros, parses durations like `1h30m`,
+/// `250ms` or `1.5s` into microseconds, a number without unit is already
+/// microseconds.
+pub(crate) static DURATION_TO_MICROS_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        "duration_to_micros",
+        vec![DataType::Utf8],
+        Arc::new(DataType::Int64),
+        Volatility::Immutable,
+        Arc::new(duration_to_micros_expr_impl),
+    )
+});
+
+/// Implementation of uuid_normalize, returns the lowercase hyphenated form of
+/// the uuid so the values compare and sort the same whatever their writing.
+pub(crate) static UUID_NORMALIZE_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        "uuid_normalize",
+        vec![DataType::Utf8],
+        Arc::new(DataType::Utf8),
+        Volatility::Immutable,
+        Arc::new(uuid_normalize_expr_impl),
+    )
+});
+
+fn params_error(msg: &str) -> DataFusionError {
+    DataFusionError::SQL(ParserError::ParserError(msg.to_string()), None)
+}
+
+fn ip_in_cidr_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 2 {
+        return Err(params_error(
+            "UDF params should be: ip_in_cidr(field, 'cidr')",
+        ));
+    }
+    let network = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(cidr))) => IpNetwork::from_str(cidr)
+            .map_err(|e| params_error(&format!("invalid cidr {cidr}: {e}")))?,
+        _ => {
+            return Err(params_error(
+                "the network of ip_in_cidr should be a string like '10.0.0.0/8'",
+            ));
+        }
+    };
+    let args = ColumnarValue::values_to_arrays(&args[..1])?;
+    let values = as_string_array(&args[0]).expect("cast failed");
+    let array = values
+        .iter()
+        .map(|value| {
+            value.map(|value| IpAddr::from_str(value.trim()).is_ok_and(|ip| network.contains(ip)))
+        })
+        .collect::<BooleanArray>();
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
+
+fn ip_sort_key_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 1 {
+        return Err(params_error("UDF params should be: ip_sort_key(field)"));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let values = as_string_array(&args[0]).expect("cast failed");
+    let array = values
+        .iter()
+        .map(|value| value.and_then(ip_sort_key))
+        .collect::<StringArray>();
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
+
+fn duration_to_micros_expr_impl(
+    args: &[ColumnarValue],
+) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 1 {
+        return Err(params_error(
+            "UDF params should be: duration_to_micros(field)",
+        ));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let values = as_string_array(&args[0]).expect("cast failed");
+    let array = values
+        .iter()
+        .map(|value| value.and_then(duration_to_micros))
+        .collect::<Int64Array>();
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
+
+fn uuid_normalize_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 1 {
+        return Err(params_error("UDF params should be: uuid_normalize(field)"));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let values = as_string_array(&args[0]).expect("cast failed");
+    let array = values
+        .iter()
+        .map(|value| value.and_then(uuid_normalize))
+        .collect::<StringArray>();
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
+
+fn ip_sort_key(value: &str) -> Option<String> {
+    let ip = match IpAddr::from_str(value.trim()).ok()? {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    };
+    Some(format!("{:032x}", u128::from(ip)))
+}
+
+fn duration_to_micros(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if value.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return value.parse::<f64>().ok().map(|v| v as i64);
+    }
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let num_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let num = rest[..num_end].parse::<f64>().ok()?;
+        rest = &rest[num_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let micros = match rest[..unit_end].trim() {
+            "ns" => 0.001,
+            "us" | "µs" => 1.0,
+            "ms" => 1_000.0,
+            "s" => 1_000_000.0,
+            "m" => 60_000_000.0,
+            "h" => 3_600_000_000.0,
+            "d" => 86_400_000_000.0,
+            _ => return None,
+        };
+        total += num * micros;
+        rest = rest[unit_end..].trim_start();
+    }
+    Some(total as i64)
+}
+
+fn uuid_normalize(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = value.strip_prefix("urn:uuid:").unwrap_or(value);
+    let value = value.trim_start_matches('{').trim_end_matches('}');
+    let hex = value.replace('-', "").to_lowercase();
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        assert_batches_eq,
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_ip_sort_key() {
+        let mut ips = vec!["10.0.0.10", "10.0.0.9", "::1", "192.168.1.1"];
+        ips.sort_by_key(|ip| ip_sort_key(ip));
+        assert_eq!(ips, vec!["::1", "10.0.0.9", "10.0.0.10", "192.168.1.1"]);
+        assert_eq!(ip_sort_key("not an ip"), None);
+    }
+
+    #[test]
+    fn test_duration_to_micros() {
+        assert_eq!(duration_to_micros("250ms"), Some(250_000));
+        assert_eq!(duration_to_micros("1.5s"), Some(1_500_000));
+        assert_eq!(duration_to_micros("1h30m"), Some(5_400_000_000));
+        assert_eq!(duration_to_micros("1500ns"), Some(1));
+        assert_eq!(duration_to_micros("42"), Some(42));
+        assert_eq!(duration_to_micros("3 weeks"), None);
+    }
+
+    #[test]
+    fn test_uuid_normalize() {
+        assert_eq!(
+            uuid_normalize("{6BA7B810-9DAD-11D1-80B4-00C04FD430C8}").as_deref(),
+            Some("6ba7b810-9dad-11d1-80b4-00c04fd430c8")
+        );
+        assert_eq!(
+            uuid_normalize("6ba7b8109dad11d180b400c04fd430c8").as_deref(),
+            Some("6ba7b810-9dad-11d1-80b4-00c04fd430c8")
+        );
+        assert_eq!(uuid_normalize("6ba7b810"), None);
+    }
+
+    #[tokio::test]
+    async fn test_ip_in_cidr_udf() {
+        let schema = Arc::new(Schema::new(vec![Field::new("ip", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec![
+                Some("10.1.2.3"),
+                Some("192.168.1.1"),
+                Some("bad"),
+                None,
+            ]))],
+        )
+        .unwrap();
+
+        let ctx = SessionContext::new();
+        ctx.register_udf(IP_IN_CIDR_UDF.clone());
+        let provider = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+
+        let df = ctx
+            .sql("select ip from t where ip_in_cidr(ip, '10.0.0.0/8')")
+            .await
+            .unwrap();
+        let data = df.collect().await.unwrap();
+        assert_batches_eq!(
+            vec![
+                "+----------+",
+                "| ip       |",
+                "+----------+",
+                "| 10.1.2.3 |",
+                "+----------+",
+            ],
+            &data
+        );
+    }
+}
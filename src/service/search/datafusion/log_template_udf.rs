@@ -0,0 +1,117 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, StringArray},
+        datatypes::DataType,
+    },
+    common::cast::as_string_array,
+    error::DataFusionError,
+    logical_expr::{ScalarUDF, Volatility},
+    prelude::create_udf,
+    sql::sqlparser::parser::ParserError,
+};
+use datafusion_expr::ColumnarValue;
+use once_cell::sync::Lazy;
+
+use crate::service::patterns::drain;
+
+/// The name of the log_template UDF given to DataFusion.
+pub const LOG_TEMPLATE_UDF_NAME: &str = "log_template";
+
+/// Implementation of log_template, masks the variable tokens of the field so
+/// the records can be grouped by their pattern.
+pub(crate) static LOG_TEMPLATE_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        LOG_TEMPLATE_UDF_NAME,
+        // expects one string
+        vec![DataType::Utf8],
+        // returns string
+        Arc::new(DataType::Utf8),
+        Volatility::Immutable,
+        Arc::new(log_template_expr_impl),
+    )
+});
+
+/// log_template function for datafusion
+pub fn log_template_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 1 {
+        return Err(DataFusionError::SQL(
+            ParserError::ParserError("UDF params should be: log_template(field)".to_string()),
+            None,
+        ));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let lines = as_string_array(&args[0]).expect("cast failed");
+    let array = lines
+        .iter()
+        .map(|line| line.map(drain::template))
+        .collect::<StringArray>();
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        assert_batches_eq,
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_log_template_udf() {
+        let schema = Arc::new(Schema::new(vec![Field::new("log", DataType::Utf8, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec![
+                "took 12ms",
+                "took 240ms",
+                "disk full",
+            ]))],
+        )
+        .unwrap();
+
+        let ctx = SessionContext::new();
+        ctx.register_udf(LOG_TEMPLATE_UDF.clone());
+        let provider = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+
+        let df = ctx
+            .sql("select log_template(log) as pattern, count(*) as num from t group by pattern order by num desc")
+            .await
+            .unwrap();
+        let data = df.collect().await.unwrap();
+        assert_batches_eq!(
+            vec![
+                "+-----------+-----+",
+                "| pattern   | num |",
+                "+-----------+-----+",
+                "| took <*>  | 2   |",
+                "| disk full | 1   |",
+                "+-----------+-----+",
+            ],
+            &data
+        );
+    }
+}
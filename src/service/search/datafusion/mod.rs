@@ -19,6 +19,8 @@ use crate::common::meta::functions::ZoFunction;
 
 mod date_format_udf;
 pub mod exec;
+mod field_type_udf;
+mod log_template_udf;
 pub mod match_udf;
 pub mod regexp_udf;
 mod rewrite;
@@ -56,7 +58,7 @@ pub const REGEX_MATCH_UDF_NAME: &str = "re_match";
 /// The name of the not_regex_match UDF given to DataFusion.
 pub const REGEX_NOT_MATCH_UDF_NAME: &str = "re_not_match";
 
-pub const DEFAULT_FUNCTIONS: [ZoFunction; 7] = [
+pub const DEFAULT_FUNCTIONS: [ZoFunction; 8] = [
     ZoFunction {
         name: "match_all_raw",
         text: "match_all_raw('v')",
@@ -85,4 +87,8 @@ pub const DEFAULT_FUNCTIONS: [ZoFunction; 7] = [
         name: REGEX_NOT_MATCH_UDF_NAME,
         text: "re_not_match(field, 'pattern')",
     },
+    ZoFunction {
+        name: log_template_udf::LOG_TEMPLATE_UDF_NAME,
+        text: "log_template(field)",
+    },
 ];
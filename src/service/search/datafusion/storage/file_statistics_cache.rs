@@ -79,7 +79,8 @@ impl CacheAccessor<Path, Arc<Statistics>> for FileStatisticsCache {
     /// Get `Statistics` for file location. Returns None if file has changed or not found.
     fn get_with_extra(&self, k: &Path, e: &Self::Extra) -> Option<Arc<Statistics>> {
         let k = self.format_key(k);
-        self.statistics
+        let ret = self
+            .statistics
             .get(&k)
             .map(|s| {
                 let (saved_meta, statistics) = s.value();
@@ -90,7 +91,9 @@ impl CacheAccessor<Path, Arc<Statistics>> for FileStatisticsCache {
                     Some(statistics.clone())
                 }
             })
-            .unwrap_or(None)
+            .unwrap_or(None);
+        infra::cache::record_lookup(infra::cache::FILE_STATISTICS, ret.is_some());
+        ret
     }
 
     /// Save collected file statistics
@@ -108,13 +111,17 @@ impl CacheAccessor<Path, Arc<Statistics>> for FileStatisticsCache {
         let mut w = self.cacher.lock();
         if w.len() >= self.max_entries {
             // release 5% of the cache
+            let mut evicted = 0;
             for _ in 0..(self.max_entries / 20) {
                 if let Some(k) = w.pop_front() {
-                    self.statistics.remove(&k);
+                    if self.statistics.remove(&k).is_some() {
+                        evicted += 1;
+                    }
                 } else {
                     break;
                 }
             }
+            infra::cache::record_evictions(infra::cache::FILE_STATISTICS, evicted);
         }
         w.push_back(k.clone());
         drop(w);
@@ -136,6 +143,7 @@ impl CacheAccessor<Path, Arc<Statistics>> for FileStatisticsCache {
     }
 
     fn clear(&self) {
+        self.cacher.lock().clear();
         self.statistics.clear()
     }
     fn name(&self) -> String {
@@ -189,8 +197,14 @@ mod tests {
         assert!(cache.get_with_extra(&meta2.location, &meta2).is_none());
 
         // different file
-        let mut meta2 = meta;
+        let mut meta2 = meta.clone();
         meta2.location = Path::from("test2");
         assert!(cache.get_with_extra(&meta2.location, &meta2).is_none());
+
+        // flushed
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+        assert!(cache.cacher.lock().is_empty());
+        assert!(cache.get_with_extra(&meta.location, &meta).is_none());
     }
 }
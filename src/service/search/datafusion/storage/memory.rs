@@ -19,7 +19,10 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use config::utils::time::BASE_TIME;
 use futures::{stream::BoxStream, StreamExt};
-use infra::{cache::file_data, storage};
+use infra::{
+    cache::{file_data, negative},
+    storage,
+};
 use object_store::{
     path::Path, GetOptions, GetResult, GetResultPayload, ListResult, MultipartId, ObjectMeta,
     ObjectStore, PutOptions, PutResult, Result,
@@ -52,6 +55,25 @@ impl FS {
         tokio::task::yield_now().await;
         data
     }
+
+    /// Fails fast for the objects recently not found in the object store.
+    fn check_missing(&self, location: &Path) -> Result<()> {
+        if negative::is_cached(&negative::object_key(location.as_ref())) {
+            return Err(object_store::Error::NotFound {
+                path: location.to_string(),
+                source: "object recently not found".into(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Remembers the object as missing when the object store did not find it.
+    fn record_missing<T>(&self, location: &Path, ret: Result<T>) -> Result<T> {
+        if let Err(object_store::Error::NotFound { .. }) = &ret {
+            negative::set(negative::object_key(location.as_ref()));
+        }
+        ret
+    }
 }
 
 impl std::fmt::Display for FS {
@@ -63,6 +85,7 @@ impl std::fmt::Display for FS {
 #[async_trait]
 impl ObjectStore for FS {
     async fn get(&self, location: &Path) -> Result<GetResult> {
+        let trace_id = storage::limiter::trace_id_of_location(location.as_ref());
         let location = &self.format_location(location);
         match self.get_cache(location, None).await {
             Some(data) => {
@@ -87,12 +110,18 @@ impl ObjectStore for FS {
             }
             None => match storage::LOCAL_CACHE.get(location).await {
                 Ok(data) => Ok(data),
-                Err(_) => storage::DEFAULT.get(location).await,
+                Err(_) => {
+                    self.check_missing(location)?;
+                    let _permit = storage::limiter::acquire(trace_id).await;
+                    let ret = storage::DEFAULT.get(location).await;
+                    self.record_missing(location, ret)
+                }
             },
         }
     }
 
     async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        let trace_id = storage::limiter::trace_id_of_location(location.as_ref());
         let location = &self.format_location(location);
         match self.get_cache(location, None).await {
             Some(data) => {
@@ -136,12 +165,18 @@ impl ObjectStore for FS {
                 .await
             {
                 Ok(ret) => Ok(ret),
-                Err(_) => storage::DEFAULT.get_opts(location, options).await,
+                Err(_) => {
+                    self.check_missing(location)?;
+                    let _permit = storage::limiter::acquire(trace_id).await;
+                    let ret = storage::DEFAULT.get_opts(location, options).await;
+                    self.record_missing(location, ret)
+                }
             },
         }
     }
 
     async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        let trace_id = storage::limiter::trace_id_of_location(location.as_ref());
         let location = &self.format_location(location);
         match self.get_cache(location, Some(range.clone())).await {
             Some(data) => {
@@ -158,7 +193,12 @@ impl ObjectStore for FS {
                 .await
             {
                 Ok(data) => Ok(data),
-                Err(_) => storage::DEFAULT.get_range(location, range).await,
+                Err(_) => {
+                    self.check_missing(location)?;
+                    let _permit = storage::limiter::acquire(trace_id).await;
+                    let ret = storage::DEFAULT.get_range(location, range).await;
+                    self.record_missing(location, ret)
+                }
             },
         }
     }
@@ -167,6 +207,7 @@ impl ObjectStore for FS {
         if ranges.is_empty() {
             return Ok(vec![]);
         }
+        let trace_id = storage::limiter::trace_id_of_location(location.as_ref());
         let location = &self.format_location(location);
         match self.get_cache(location, None).await {
             Some(data) => ranges
@@ -183,12 +224,18 @@ impl ObjectStore for FS {
                 .collect(),
             None => match storage::LOCAL_CACHE.get_ranges(location, ranges).await {
                 Ok(data) => Ok(data),
-                Err(_) => storage::DEFAULT.get_ranges(location, ranges).await,
+                Err(_) => {
+                    self.check_missing(location)?;
+                    let _permit = storage::limiter::acquire(trace_id).await;
+                    let ret = storage::DEFAULT.get_ranges(location, ranges).await;
+                    self.record_missing(location, ret)
+                }
             },
         }
     }
 
     async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        let trace_id = storage::limiter::trace_id_of_location(location.as_ref());
         let location = &self.format_location(location);
         match self.get_cache(location, None).await {
             Some(data) => Ok(ObjectMeta {
@@ -200,7 +247,12 @@ impl ObjectStore for FS {
             }),
             None => match storage::LOCAL_CACHE.head(location).await {
                 Ok(data) => Ok(data),
-                Err(_) => storage::DEFAULT.head(location).await,
+                Err(_) => {
+                    self.check_missing(location)?;
+                    let _permit = storage::limiter::acquire(trace_id).await;
+                    let ret = storage::DEFAULT.head(location).await;
+                    self.record_missing(location, ret)
+                }
             },
         }
     }
@@ -0,0 +1,329 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Searches the remote clusters configured by `ZO_FEDERATION_CLUSTERS` along
+//! the local one and merges their results, every row is attributed to its
+//! cluster by the `_cluster` column.
+
+use config::{
+    get_config,
+    meta::{
+        search::{self, RequestEncoding},
+        stream::StreamType,
+    },
+    utils::json,
+};
+use futures::future::join_all;
+use infra::errors::{Error, ErrorCodes};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+pub const CLUSTER_COLUMN: &str = "_cluster";
+
+static RE_ORDER_BY: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\border\s+by\b").unwrap());
+
+/// Searches the local cluster, and the remote clusters as well when the
+/// federation is enabled and the request doesn't limit the search to the
+/// local cluster.
+pub async fn search(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: Option<String>,
+    in_req: &search::Request,
+) -> Result<search::Response, Error> {
+    let remotes = get_remote_clusters(&in_req.clusters);
+    if remotes.is_empty() {
+        return super::search(trace_id, org_id, stream_type, user_id, in_req).await;
+    }
+
+    // every cluster returns the rows up to the requested page, the page is cut
+    // after the merge
+    let mut req = in_req.clone();
+    req.encoding = RequestEncoding::Empty;
+    if req.query.size > 0 {
+        req.query.size += req.query.from;
+    }
+    req.query.from = 0;
+    let mut local_req = req.clone();
+    local_req.regions = vec!["local".to_string()];
+    local_req.clusters = vec!["local".to_string()];
+    // the remote clusters mustn't fan the search out again
+    let mut remote_req = req;
+    remote_req.clusters = vec!["local".to_string()];
+    if let Some(sql) = super::row_filter::apply(
+        org_id,
+        user_id.as_deref(),
+        stream_type,
+        &remote_req.query.sql,
+    )
+    .await?
+    {
+        remote_req.query.sql = sql;
+    }
+
+    let local_name = config::get_cluster_name();
+    let search_local = in_req.clusters.is_empty()
+        || in_req
+            .clusters
+            .iter()
+            .any(|c| c == "local" || c == &local_name);
+    let local = async {
+        if search_local {
+            Some(super::search(trace_id, org_id, stream_type, user_id, &local_req).await)
+        } else {
+            None
+        }
+    };
+    let tasks = remotes
+        .iter()
+        .map(|(name, url)| search_remote(name, url, org_id, stream_type, &remote_req));
+    let (local, remote_results) = tokio::join!(local, join_all(tasks));
+
+    let mut results = Vec::with_capacity(remotes.len() + 1);
+    let mut errors = Vec::new();
+    if let Some(local) = local {
+        match local {
+            Ok(res) => results.push((local_name, res)),
+            Err(e) => errors.push((local_name, e)),
+        }
+    }
+    for ((name, _), res) in remotes.into_iter().zip(remote_results) {
+        match res {
+            Ok(res) => results.push((name, res)),
+            Err(e) => {
+                log::error!("[FEDERATION] search cluster {} failed: {}", name, e);
+                errors.push((name, e));
+            }
+        }
+    }
+    if results.is_empty() {
+        return Err(errors
+            .pop()
+            .map(|(_, e)| e)
+            .unwrap_or_else(|| Error::Message("no cluster to search".to_string())));
+    }
+
+    let cfg = get_config();
+    let sort_by_time = !RE_ORDER_BY.is_match(&in_req.query.sql);
+    let mut res = merge_responses(
+        results,
+        &cfg.common.column_timestamp,
+        sort_by_time,
+        in_req.query.from,
+        in_req.query.size,
+    );
+    if !errors.is_empty() {
+        res.is_partial = true;
+        let failed = errors
+            .iter()
+            .map(|(name, e)| format!("{name}: {e}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        res.function_error = if res.function_error.is_empty() {
+            format!("search clusters failed: {failed}")
+        } else {
+            format!("{}, search clusters failed: {failed}", res.function_error)
+        };
+    }
+    res.trace_id = trace_id.to_string();
+    Ok(res)
+}
+
+async fn search_remote(
+    name: &str,
+    url: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    req: &search::Request,
+) -> Result<search::Response, Error> {
+    let cfg = get_config();
+    let url = format!(
+        "{}/api/{org_id}/_search?type={stream_type}",
+        url.trim_end_matches('/')
+    );
+    let body = json::to_vec(req).map_err(|e| Error::Message(e.to_string()))?;
+    let mut request = reqwest::Client::new()
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .timeout(std::time::Duration::from_secs(cfg.federation.timeout))
+        .body(body);
+    if !cfg.federation.auth.is_empty() {
+        request = request.header("Authorization", &cfg.federation.auth);
+    }
+    let resp = request.send().await.map_err(|e| {
+        Error::ErrorCode(ErrorCodes::ServerInternalError(format!(
+            "cluster {name} unreachable: {e}"
+        )))
+    })?;
+    let status = resp.status();
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|e| Error::Message(e.to_string()))?;
+    if !status.is_success() {
+        return Err(Error::ErrorCode(ErrorCodes::ServerInternalError(format!(
+            "cluster {name} responded {status}: {}",
+            String::from_utf8_lossy(&body)
+        ))));
+    }
+    json::from_slice(&body).map_err(|e| Error::Message(e.to_string()))
+}
+
+/// Returns the remote clusters a search fans out to, empty clusters in the
+/// request means all of them and `local` only the local cluster.
+fn get_remote_clusters(req_clusters: &[String]) -> Vec<(String, String)> {
+    let cfg = get_config();
+    if !cfg.federation.enabled {
+        return vec![];
+    }
+    parse_clusters(&cfg.federation.clusters)
+        .into_iter()
+        .filter(|(name, _)| req_clusters.is_empty() || req_clusters.contains(name))
+        .collect()
+}
+
+// name=url pairs separated by commas
+fn parse_clusters(s: &str) -> Vec<(String, String)> {
+    s.split(',')
+        .filter_map(|v| {
+            let (name, url) = v.split_once('=')?;
+            let (name, url) = (name.trim(), url.trim());
+            if name.is_empty() || url.is_empty() {
+                None
+            } else {
+                Some((name.to_string(), url.to_string()))
+            }
+        })
+        .collect()
+}
+
+fn merge_responses(
+    results: Vec<(String, search::Response)>,
+    ts_column: &str,
+    sort_by_time: bool,
+    from: i64,
+    size: i64,
+) -> search::Response {
+    let mut res = search::Response::new(from, size);
+    for (cluster, mut cluster_res) in results {
+        attribute(&mut cluster_res.hits, &cluster);
+        res.hits.append(&mut cluster_res.hits);
+        for (name, mut values) in cluster_res.aggs {
+            attribute(&mut values, &cluster);
+            res.aggs.entry(name).or_default().append(&mut values);
+        }
+        for column in cluster_res.columns {
+            if !res.columns.contains(&column) {
+                res.columns.push(column);
+            }
+        }
+        res.took = res.took.max(cluster_res.took);
+        res.total += cluster_res.total;
+        res.file_count += cluster_res.file_count;
+        res.scan_size += cluster_res.scan_size;
+        res.scan_records += cluster_res.scan_records;
+        res.is_partial |= cluster_res.is_partial;
+        if res.function_error.is_empty() {
+            res.function_error = cluster_res.function_error;
+        }
+        if res.histogram_interval.is_none() {
+            res.histogram_interval = cluster_res.histogram_interval;
+        }
+        if res.response_type.is_empty() {
+            res.response_type = cluster_res.response_type;
+        }
+    }
+    if !res.columns.is_empty() {
+        res.columns.push(CLUSTER_COLUMN.to_string());
+    }
+    if sort_by_time {
+        res.hits.sort_by(|a, b| {
+            let a = a
+                .get(ts_column)
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default();
+            let b = b
+                .get(ts_column)
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default();
+            b.cmp(&a)
+        });
+    }
+    let from = from.max(0) as usize;
+    res.hits = if size > 0 {
+        res.hits
+            .into_iter()
+            .skip(from)
+            .take(size as usize)
+            .collect()
+    } else {
+        res.hits.into_iter().skip(from).collect()
+    };
+    res
+}
+
+fn attribute(rows: &mut [json::Value], cluster: &str) {
+    for row in rows.iter_mut() {
+        if let Some(row) = row.as_object_mut() {
+            row.insert(
+                CLUSTER_COLUMN.to_string(),
+                json::Value::String(cluster.to_string()),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clusters() {
+        assert_eq!(
+            parse_clusters("eu=https://eu.example.com, us = http://us:5080,invalid,=x"),
+            vec![
+                ("eu".to_string(), "https://eu.example.com".to_string()),
+                ("us".to_string(), "http://us:5080".to_string()),
+            ]
+        );
+        assert!(parse_clusters("").is_empty());
+    }
+
+    #[test]
+    fn test_merge_responses() {
+        let mut local = search::Response::new(0, 2);
+        local.hits = vec![
+            json::json!({"_timestamp": 3, "a": 1}),
+            json::json!({"_timestamp": 1, "a": 2}),
+        ];
+        local.total = 2;
+        let mut remote = search::Response::new(0, 2);
+        remote.hits = vec![json::json!({"_timestamp": 2, "a": 3})];
+        remote.total = 1;
+        let res = merge_responses(
+            vec![("local".to_string(), local), ("eu".to_string(), remote)],
+            "_timestamp",
+            true,
+            0,
+            2,
+        );
+        assert_eq!(res.total, 3);
+        assert_eq!(res.hits.len(), 2);
+        assert_eq!(res.hits[0]["_cluster"], "local");
+        assert_eq!(res.hits[1]["_cluster"], "eu");
+        assert_eq!(res.hits[1]["_timestamp"], 2);
+    }
+}
@@ -233,7 +233,7 @@ pub async fn search(
                 #[cfg(feature = "enterprise")]
                 let _ = abort_receiver.await;
                 #[cfg(not(feature = "enterprise"))]
-                futures::future::pending::<()>().await;
+                super::tracker::cancelled(&trace_id).await;
             } => {
                 log::info!("[trace_id {trace_id}] in node merge task is cancel");
                 return Err(Error::Message(format!("[trace_id {trace_id}] in node merge task is cancel")));
@@ -15,22 +15,32 @@
 
 use std::sync::Arc;
 
+use arrow_schema::Schema;
+use bytes::Bytes;
 use config::{
     get_config, is_local_disk_storage,
     meta::{
         search::{ScanStats, SearchType, StorageType},
         stream::{FileKey, PartitionTimeLevel, StreamPartition, StreamType},
     },
+    metrics,
     utils::schema_ext::SchemaExt,
 };
 use datafusion::{arrow::record_batch::RecordBatch, common::FileType};
 use futures::future::try_join_all;
 use hashbrown::HashMap;
 use infra::{
-    cache::file_data,
+    cache::{file_data, record_batch},
     errors::{Error, ErrorCodes},
     schema::{unwrap_partition_time_level, unwrap_stream_settings},
 };
+use parquet::{
+    arrow::{
+        arrow_reader::{ArrowReaderMetadata, ParquetRecordBatchReaderBuilder},
+        ProjectionMask,
+    },
+    errors::ParquetError,
+};
 use tokio::{sync::Semaphore, time::Duration};
 use tracing::{info_span, Instrument};
 
@@ -38,9 +48,9 @@ use crate::service::{
     db, file_list,
     search::{
         datafusion::exec,
-        grpc::{generate_search_schema, generate_select_start_search_schema},
+        grpc::{generate_search_schema, generate_select_start_search_schema, wal::adapt_batch},
         sql::Sql,
-        RE_SELECT_WILDCARD,
+        tracker, RE_SELECT_WILDCARD,
     },
 };
 
@@ -182,6 +192,12 @@ pub async fn search(
     // load files to local cache
     let (cache_type, deleted_files, (mem_cached_files, disk_cached_files)) =
         cache_parquet_files(trace_id, &files, &scan_stats).await?;
+    if tracker::is_cancelled(trace_id) {
+        log::info!("[trace_id {trace_id}] search->storage: search canceled while loading files");
+        return Err(Error::ErrorCode(ErrorCodes::SearchCancelQuery(format!(
+            "[trace_id {trace_id}] search->storage: search canceled while loading files"
+        ))));
+    }
     if !deleted_files.is_empty() {
         // remove deleted files from files_group
         for (_, g_files) in files_group.iter_mut() {
@@ -247,6 +263,22 @@ pub async fn search(
             generate_search_schema(&sql, &schema, &schema_latest_map)?
         };
 
+        // the row groups of the memory cached files are decoded once through
+        // the batch cache, the fast mode only reads a few files anyway
+        let record_batches = if cache_type == file_data::CacheType::Memory
+            && record_batch::is_enabled()
+            && !(sql.fast_mode && sql.meta.limit > 0)
+        {
+            load_record_batches(trace_id, &files, &schema).await
+        } else {
+            None
+        };
+        let file_type = if record_batches.is_some() {
+            FileType::ARROW
+        } else {
+            FileType::PARQUET
+        };
+
         let datafusion_span = info_span!(
             "service:search:grpc:storage:datafusion",
             trace_id,
@@ -273,7 +305,8 @@ pub async fn search(
             )));
         }
 
-        let task = tokio::task::spawn(
+        let task = tokio::task::spawn(tracker::timed(
+            trace_id,
             async move {
                 tokio::select! {
                     ret = exec::sql(
@@ -282,8 +315,8 @@ pub async fn search(
                         &diff_fields,
                         &sql,
                         &files,
-                        None,
-                        FileType::PARQUET,
+                        record_batches,
+                        file_type,
                     ) => {
                         match ret {
                             Ok(ret) => Ok(ret),
@@ -311,7 +344,7 @@ pub async fn search(
                         #[cfg(feature = "enterprise")]
                         let _ = abort_receiver.await;
                         #[cfg(not(feature = "enterprise"))]
-                        futures::future::pending::<()>().await;
+                        tracker::cancelled(&session.id).await;
                     } => {
                         log::info!("[trace_id {}] search->storage: search canceled", session.id);
                         Err(datafusion::error::DataFusionError::Execution(format!(
@@ -321,7 +354,7 @@ pub async fn search(
                 }
             }
             .instrument(datafusion_span),
-        );
+        ));
 
         tasks.push(task);
     }
@@ -369,7 +402,7 @@ async fn get_file_list(
         &sql.meta.time_range
     );
     let (time_min, time_max) = sql.meta.time_range.unwrap();
-    let file_list = match file_list::query(
+    let file_list = match file_list::query_for_search(
         &sql.org_id,
         &sql.stream_name,
         stream_type,
@@ -402,6 +435,65 @@ async fn get_file_list(
     Ok(files)
 }
 
+// decodes the row groups of the memory cached files with the columns of the
+// table, returns None if a file is not in the memory cache anymore
+async fn load_record_batches(
+    trace_id: &str,
+    files: &[FileKey],
+    schema: &Schema,
+) -> Option<Vec<RecordBatch>> {
+    let mut batches = Vec::new();
+    for file in files.iter() {
+        let data = file_data::memory::get(&file.key, None).await?;
+        match decode_row_groups(&file.key, data, schema) {
+            Ok(v) => batches.extend(v),
+            Err(e) => {
+                log::error!(
+                    "[trace_id {trace_id}] search->storage: decode file {} err: {}",
+                    file.key,
+                    e
+                );
+                return None;
+            }
+        }
+    }
+    Some(batches.iter().map(|b| adapt_batch(schema, b)).collect())
+}
+
+fn decode_row_groups(
+    file: &str,
+    data: Bytes,
+    schema: &Schema,
+) -> Result<Vec<RecordBatch>, ParquetError> {
+    let metadata = ArrowReaderMetadata::load(&data, Default::default())?;
+    let (indices, columns): (Vec<_>, Vec<_>) = metadata
+        .schema()
+        .fields()
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| schema.field_with_name(f.name()).is_ok())
+        .map(|(i, f)| (i, f.name().as_str()))
+        .unzip();
+    let mut batches = Vec::new();
+    for row_group in 0..metadata.metadata().num_row_groups() {
+        let key = record_batch::key(file, row_group, &columns);
+        if let Some(cached) = record_batch::get(&key) {
+            batches.extend(cached);
+            continue;
+        }
+        let mask = ProjectionMask::roots(metadata.parquet_schema(), indices.clone());
+        let reader =
+            ParquetRecordBatchReaderBuilder::new_with_metadata(data.clone(), metadata.clone())
+                .with_row_groups(vec![row_group])
+                .with_projection(mask)
+                .build()?;
+        let decoded = reader.collect::<Result<Vec<_>, _>>()?;
+        record_batch::set(key, decoded.clone());
+        batches.extend(decoded);
+    }
+    Ok(batches)
+}
+
 #[tracing::instrument(
     name = "service:search:grpc:storage:cache_parquet_files",
     skip_all,
@@ -429,81 +521,135 @@ async fn cache_parquet_files(
         return Ok((file_data::CacheType::None, vec![], (0, 0)));
     };
 
-    let mut mem_cached_files = 0;
-    let mut disk_cached_files = 0;
+    let semaphore = Arc::new(Semaphore::new(cfg.limit.query_thread_num));
+    // the search starts once the first files are cached, the others are
+    // prefetched meanwhile and read from the object store until they are
+    let wait_num = match cfg.limit.query_prefetch_files {
+        0 => files.len(),
+        n => n.min(files.len()),
+    };
+    let (files, prefetch_files) = files.split_at(wait_num);
+    let tasks = spawn_cache_tasks(trace_id, files, cache_type, semaphore.clone()).await;
+    if !prefetch_files.is_empty() {
+        let trace_id = trace_id.to_string();
+        let prefetch_files = prefetch_files.to_vec();
+        tokio::task::spawn(async move {
+            let tasks = spawn_cache_tasks(&trace_id, &prefetch_files, cache_type, semaphore).await;
+            let (_, (mem_cached_files, disk_cached_files)) =
+                collect_cache_tasks(&trace_id, tasks).await;
+            log::info!(
+                "[trace_id {trace_id}] search->storage: prefetch files {}, memory cached {}, disk cached {}, download others into {:?} cache done",
+                prefetch_files.len(),
+                mem_cached_files,
+                disk_cached_files,
+                cache_type,
+            );
+        });
+    }
+    let (delete_files, cached_files) = collect_cache_tasks(trace_id, tasks).await;
 
-    let mut tasks = Vec::new();
-    let semaphore = std::sync::Arc::new(Semaphore::new(cfg.limit.query_thread_num));
+    Ok((cache_type, delete_files, cached_files))
+}
+
+async fn spawn_cache_tasks(
+    trace_id: &str,
+    files: &[FileKey],
+    cache_type: file_data::CacheType,
+    semaphore: Arc<Semaphore>,
+) -> Vec<tokio::task::JoinHandle<(Option<String>, bool, bool)>> {
+    let mut tasks = Vec::with_capacity(files.len());
     for file in files.iter() {
         let trace_id = trace_id.to_string();
         let file_name = file.key.clone();
         let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let task: tokio::task::JoinHandle<(Option<String>, bool, bool)> = tokio::task::spawn(
-            async move {
-                let cfg = get_config();
-                let ret = match cache_type {
-                    file_data::CacheType::Memory => {
-                        let mut disk_exists = false;
-                        let mem_exists = file_data::memory::exist(&file_name).await;
-                        if !mem_exists && !cfg.memory_cache.skip_disk_check {
-                            // when skip_disk_check = false, need to check disk cache
-                            disk_exists = file_data::disk::exist(&file_name).await;
-                        }
-                        if !mem_exists && (cfg.memory_cache.skip_disk_check || !disk_exists) {
-                            (
-                                file_data::memory::download(&trace_id, &file_name)
-                                    .await
-                                    .err(),
-                                false,
-                                false,
-                            )
-                        } else {
-                            (None, mem_exists, disk_exists)
-                        }
-                    }
-                    file_data::CacheType::Disk => {
-                        if !file_data::disk::exist(&file_name).await {
-                            (
-                                file_data::disk::download(&trace_id, &file_name).await.err(),
-                                false,
-                                false,
-                            )
-                        } else {
-                            (None, false, true)
-                        }
-                    }
-                    _ => (None, false, false),
-                };
-                let file_name = if let Some(e) = ret.0 {
-                    if e.to_string().to_lowercase().contains("not found")
-                        || e.to_string().to_lowercase().contains("data size is zero")
-                    {
-                        // delete file from file list
-                        log::warn!("found invalid file: {}", file_name);
-                        if let Err(e) = file_list::delete_parquet_file(&file_name, true).await {
-                            log::error!(
-                                "[trace_id {trace_id}] search->storage: delete from file_list err: {}",
-                                e
-                            );
-                        }
-                        Some(file_name)
-                    } else {
-                        log::error!(
-                            "[trace_id {trace_id}] search->storage: download file to cache err: {}",
-                            e
-                        );
-                        None
-                    }
-                } else {
-                    None
+        let task: tokio::task::JoinHandle<(Option<String>, bool, bool)> =
+            tokio::task::spawn(async move {
+                // a cancelled query stops downloading its files
+                let ret = tokio::select! {
+                    ret = cache_parquet_file(&trace_id, file_name, cache_type) => ret,
+                    _ = tracker::cancelled(&trace_id) => (None, false, false),
                 };
                 drop(permit);
-                (file_name, ret.1, ret.2)
-            },
-        );
+                ret
+            });
         tasks.push(task);
     }
+    tasks
+}
+
+// returns the invalid file if the file is not found in the object store, and
+// if the file was in the memory cache or the disk cache already
+async fn cache_parquet_file(
+    trace_id: &str,
+    file_name: String,
+    cache_type: file_data::CacheType,
+) -> (Option<String>, bool, bool) {
+    let cfg = get_config();
+    let ret = match cache_type {
+        file_data::CacheType::Memory => {
+            let mut disk_exists = false;
+            let mem_exists = file_data::memory::exist(&file_name).await;
+            if !mem_exists && !cfg.memory_cache.skip_disk_check {
+                // when skip_disk_check = false, need to check disk cache
+                disk_exists = file_data::disk::exist(&file_name).await;
+            }
+            if !mem_exists && (cfg.memory_cache.skip_disk_check || !disk_exists) {
+                (
+                    file_data::memory::download(trace_id, &file_name)
+                        .await
+                        .err(),
+                    false,
+                    false,
+                )
+            } else {
+                (None, mem_exists, disk_exists)
+            }
+        }
+        file_data::CacheType::Disk => {
+            if !file_data::disk::exist(&file_name).await {
+                (
+                    file_data::disk::download(trace_id, &file_name).await.err(),
+                    false,
+                    false,
+                )
+            } else {
+                (None, false, true)
+            }
+        }
+        _ => (None, false, false),
+    };
+    let file_name = if let Some(e) = ret.0 {
+        if e.to_string().to_lowercase().contains("not found")
+            || e.to_string().to_lowercase().contains("data size is zero")
+        {
+            // delete file from file list
+            log::warn!("found invalid file: {}", file_name);
+            if let Err(e) = file_list::delete_parquet_file(&file_name, true).await {
+                log::error!(
+                    "[trace_id {trace_id}] search->storage: delete from file_list err: {}",
+                    e
+                );
+            }
+            Some(file_name)
+        } else {
+            log::error!(
+                "[trace_id {trace_id}] search->storage: download file to cache err: {}",
+                e
+            );
+            None
+        }
+    } else {
+        None
+    };
+    (file_name, ret.1, ret.2)
+}
 
+async fn collect_cache_tasks(
+    trace_id: &str,
+    tasks: Vec<tokio::task::JoinHandle<(Option<String>, bool, bool)>>,
+) -> (Vec<String>, CachedFiles) {
+    let mut mem_cached_files = 0;
+    let mut disk_cached_files = 0;
     let mut delete_files = Vec::new();
     for task in tasks {
         match task.await {
@@ -513,6 +659,11 @@ async fn cache_parquet_files(
                 } else if disk_exists {
                     disk_cached_files += 1;
                 }
+                if mem_exists || disk_exists {
+                    metrics::QUERY_CACHE_HIT_FILES.with_label_values(&[]).inc();
+                } else {
+                    metrics::QUERY_CACHE_MISS_FILES.with_label_values(&[]).inc();
+                }
                 if let Some(file) = file {
                     delete_files.push(file);
                 }
@@ -526,9 +677,5 @@ async fn cache_parquet_files(
         }
     }
 
-    Ok((
-        cache_type,
-        delete_files,
-        (mem_cached_files, disk_cached_files),
-    ))
+    (delete_files, (mem_cached_files, disk_cached_files))
 }
@@ -49,7 +49,7 @@ use crate::{
             datafusion::exec,
             grpc::{generate_search_schema, generate_select_start_search_schema},
             sql::Sql,
-            RE_SELECT_WILDCARD,
+            tracker, RE_SELECT_WILDCARD,
         },
     },
 };
@@ -304,7 +304,8 @@ pub async fn search_parquet(
             )));
         }
 
-        let task = tokio::task::spawn(
+        let task = tokio::task::spawn(tracker::timed(
+            trace_id,
             async move {
                 tokio::select! {
                     ret = exec::sql(
@@ -326,7 +327,7 @@ pub async fn search_parquet(
                         #[cfg(feature = "enterprise")]
                         let _ = abort_receiver.await;
                         #[cfg(not(feature = "enterprise"))]
-                        futures::future::pending::<()>().await;
+                        tracker::cancelled(&session.id).await;
                     } => {
                         log::info!("[trace_id {}] wal->parquet->search: search canceled", session.id);
                         Err(datafusion::error::DataFusionError::Execution(format!(
@@ -336,7 +337,7 @@ pub async fn search_parquet(
                 }
             }
             .instrument(datafusion_span),
-        );
+        ));
 
         tasks.push(task);
     }
@@ -520,7 +521,8 @@ pub async fn search_memtable(
             )));
         }
 
-        let task = tokio::task::spawn(
+        let task = tokio::task::spawn(tracker::timed(
+            trace_id,
             async move {
                 let files = vec![];
                 tokio::select! {
@@ -543,7 +545,7 @@ pub async fn search_memtable(
                         #[cfg(feature = "enterprise")]
                         let _ = abort_receiver.await;
                         #[cfg(not(feature = "enterprise"))]
-                        futures::future::pending::<()>().await;
+                        tracker::cancelled(&session.id).await;
                     } => {
                         log::info!("[trace_id {}] wal->mem->search: search canceled", session.id);
                         Err(datafusion::error::DataFusionError::Execution(format!(
@@ -553,7 +555,7 @@ pub async fn search_memtable(
                 }
             }
             .instrument(datafusion_span),
-        );
+        ));
 
         tasks.push(task);
     }
@@ -0,0 +1,237 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{meta::stream::StreamType, utils::json, SQL_FULL_TEXT_SEARCH_FIELDS};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// The key of the offsets added to a hit
+pub const HIGHLIGHT_KEY: &str = "_highlight";
+
+static RE_MATCH_ALL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\bmatch_all(_raw)?(_ignore_case)?\(\s*'([^']*)'\s*\)").unwrap());
+static RE_MATCH_FIELD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\b(?:str_)?match(_ignore_case)?\(\s*"?([^,"'\)]+?)"?\s*,\s*'([^']*)'\s*\)"#)
+        .unwrap()
+});
+
+#[derive(Clone, Debug, PartialEq)]
+struct Term {
+    /// None for a match_all term, which applies to the full text fields
+    field: Option<String>,
+    value: String,
+    ignore_case: bool,
+}
+
+/// Adds to each hit the character offsets of the `match_all` and `str_match`
+/// terms of the query, as `{"_highlight": {"field": [[start, end], ..]}}`.
+/// The offsets count unicode chars and the end is exclusive.
+pub async fn highlight_hits(
+    org_id: &str,
+    stream_type: StreamType,
+    sql: &str,
+    hits: &mut [json::Value],
+) {
+    let terms = parse_terms(sql);
+    if terms.is_empty() || hits.is_empty() {
+        return;
+    }
+    let mut fts_fields = Vec::new();
+    if terms.iter().any(|t| t.field.is_none()) {
+        if let Ok(meta) = config::meta::sql::Sql::new(sql) {
+            if let Ok(schema) = infra::schema::get(org_id, &meta.source, stream_type).await {
+                fts_fields =
+                    infra::schema::get_stream_setting_fts_fields(&schema).unwrap_or_default();
+            }
+        }
+        if fts_fields.is_empty() {
+            fts_fields = SQL_FULL_TEXT_SEARCH_FIELDS.to_vec();
+        }
+    }
+    for hit in hits.iter_mut() {
+        let Some(highlight) = highlight_hit(hit, &terms, &fts_fields) else {
+            continue;
+        };
+        if let Some(hit) = hit.as_object_mut() {
+            hit.insert(HIGHLIGHT_KEY.to_string(), highlight);
+        }
+    }
+}
+
+fn parse_terms(sql: &str) -> Vec<Term> {
+    let mut terms = Vec::new();
+    for cap in RE_MATCH_ALL.captures_iter(sql) {
+        // match_all is served by the lowercase index or by ILIKE
+        let ignore_case = cap.get(1).is_none() || cap.get(2).is_some();
+        terms.push(Term {
+            field: None,
+            value: cap[3].to_string(),
+            ignore_case,
+        });
+    }
+    for cap in RE_MATCH_FIELD.captures_iter(sql) {
+        terms.push(Term {
+            field: Some(cap[2].trim().to_string()),
+            value: cap[3].to_string(),
+            ignore_case: cap.get(1).is_some(),
+        });
+    }
+    terms.retain(|t| !t.value.is_empty());
+    terms
+}
+
+fn highlight_hit(hit: &json::Value, terms: &[Term], fts_fields: &[String]) -> Option<json::Value> {
+    let hit = hit.as_object()?;
+    let mut highlight = json::Map::new();
+    for (field, value) in hit.iter() {
+        let Some(text) = value.as_str() else {
+            continue;
+        };
+        let mut offsets = Vec::new();
+        for term in terms {
+            let matched = match &term.field {
+                Some(name) => name.eq_ignore_ascii_case(field),
+                None => fts_fields.iter().any(|f| f.eq_ignore_ascii_case(field)),
+            };
+            if matched {
+                offsets.extend(find_offsets(text, &term.value, term.ignore_case));
+            }
+        }
+        if offsets.is_empty() {
+            continue;
+        }
+        let offsets = merge_offsets(offsets)
+            .into_iter()
+            .map(|[start, end]| json::json!([start, end]))
+            .collect();
+        highlight.insert(field.to_string(), json::Value::Array(offsets));
+    }
+    if highlight.is_empty() {
+        None
+    } else {
+        Some(json::Value::Object(highlight))
+    }
+}
+
+fn find_offsets(text: &str, term: &str, ignore_case: bool) -> Vec<[usize; 2]> {
+    let text = text.chars().collect::<Vec<_>>();
+    let term = term.chars().collect::<Vec<_>>();
+    let eq = |a: &char, b: &char| a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()));
+    let mut offsets = Vec::new();
+    let mut i = 0;
+    while !term.is_empty() && i + term.len() <= text.len() {
+        if text[i..i + term.len()]
+            .iter()
+            .zip(term.iter())
+            .all(|(a, b)| eq(a, b))
+        {
+            offsets.push([i, i + term.len()]);
+            i += term.len();
+        } else {
+            i += 1;
+        }
+    }
+    offsets
+}
+
+/// Sorts the offsets and merges the overlapping ones.
+fn merge_offsets(mut offsets: Vec<[usize; 2]>) -> Vec<[usize; 2]> {
+    offsets.sort_unstable();
+    let mut merged: Vec<[usize; 2]> = Vec::with_capacity(offsets.len());
+    for [start, end] in offsets {
+        match merged.last_mut() {
+            Some(last) if start <= last[1] => last[1] = last[1].max(end),
+            _ => merged.push([start, end]),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_terms() {
+        let sql = "SELECT * FROM app WHERE match_all('Error') AND match_all_raw('Disk') \
+                   AND str_match(host, 'node-1') AND str_match_ignore_case(\"pod\", 'Api')";
+        let terms = parse_terms(sql);
+        assert_eq!(
+            terms,
+            vec![
+                Term {
+                    field: None,
+                    value: "Error".to_string(),
+                    ignore_case: true,
+                },
+                Term {
+                    field: None,
+                    value: "Disk".to_string(),
+                    ignore_case: false,
+                },
+                Term {
+                    field: Some("host".to_string()),
+                    value: "node-1".to_string(),
+                    ignore_case: false,
+                },
+                Term {
+                    field: Some("pod".to_string()),
+                    value: "Api".to_string(),
+                    ignore_case: true,
+                },
+            ]
+        );
+        assert!(parse_terms("SELECT * FROM app WHERE code = 500").is_empty());
+    }
+
+    #[test]
+    fn test_find_offsets() {
+        assert_eq!(
+            find_offsets("error: ERROR again", "error", true),
+            vec![[0, 5], [7, 12]]
+        );
+        assert_eq!(find_offsets("error: ERROR", "error", false), vec![[0, 5]]);
+        assert_eq!(find_offsets("héllo wörld", "wörld", false), vec![[6, 11]]);
+        assert_eq!(find_offsets("aaaa", "aa", false), vec![[0, 2], [2, 4]]);
+        assert!(find_offsets("abc", "", false).is_empty());
+        assert!(find_offsets("ab", "abc", false).is_empty());
+    }
+
+    #[test]
+    fn test_merge_offsets() {
+        assert_eq!(
+            merge_offsets(vec![[5, 8], [0, 3], [2, 4], [8, 9]]),
+            vec![[0, 4], [5, 9]]
+        );
+    }
+
+    #[test]
+    fn test_highlight_hit() {
+        let terms =
+            parse_terms("SELECT * FROM app WHERE match_all('timeout') AND str_match(host, 'b')");
+        let hit = json::json!({
+            "log": "Timeout after 30s, timeout",
+            "host": "node-b",
+            "message": "not a fts field timeout",
+            "code": 504
+        });
+        let highlight = highlight_hit(&hit, &terms, &["log".to_string()]).unwrap();
+        assert_eq!(
+            highlight,
+            json::json!({"log": [[0, 7], [19, 26]], "host": [[5, 6]]})
+        );
+        assert!(highlight_hit(&json::json!({"code": 1}), &terms, &[]).is_none());
+    }
+}
@@ -36,13 +36,13 @@ use regex::Regex;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 #[cfg(feature = "enterprise")]
 use {
+    crate::common::infra::tls,
+    config::meta::cluster::{GRPC_MIN_VERSION, GRPC_VERSION_HEADER},
     hashbrown::HashSet,
     o2_enterprise::enterprise::{common::infra::config::O2_CONFIG, search::TaskStatus},
-    tonic::{codec::CompressionEncoding, metadata::MetadataValue, transport::Channel, Request},
+    tonic::{codec::CompressionEncoding, metadata::MetadataValue, Request},
     tracing::{info_span, Instrument},
 };
-#[cfg(not(feature = "enterprise"))]
-use {std::sync::Arc, tokio::sync::Mutex};
 
 use super::usage::report_request_usage_stats;
 use crate::{
@@ -53,25 +53,103 @@ use crate::{
 
 pub(crate) mod cluster;
 pub(crate) mod datafusion;
+pub(crate) mod federation;
 pub(crate) mod grpc;
+pub(crate) mod highlight;
+#[cfg(not(feature = "enterprise"))]
+pub(crate) mod queue;
+pub(crate) mod row_filter;
 pub(crate) mod sql;
+pub(crate) mod streaming;
+pub(crate) mod tracker;
 
 pub static SEARCH_SERVER: Lazy<Searcher> = Lazy::new(Searcher::new);
 
-#[cfg(not(feature = "enterprise"))]
-pub(crate) static QUEUE_LOCKER: Lazy<Arc<Mutex<bool>>> =
-    Lazy::new(|| Arc::new(Mutex::const_new(false)));
-
 static RE_SELECT_WILDCARD: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(?i)select\s+\*\s+from").unwrap());
 
-#[tracing::instrument(name = "service:search:enter", skip(in_req))]
+/// Checks the permissions of the user on the stream to search.
+pub async fn can_read_stream(
+    org_id: &str,
+    user_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> bool {
+    #[cfg(feature = "enterprise")]
+    {
+        use crate::common::{
+            infra::config::USERS,
+            utils::auth::{is_root_user, is_service_account, AuthExtractor},
+        };
+
+        if !is_root_user(user_id) && !is_service_account(user_id) {
+            let user: crate::common::meta::user::User =
+                USERS.get(&format!("{org_id}/{user_id}")).unwrap().clone();
+
+            if user.is_external
+                && !crate::handler::http::auth::validator::check_permissions(
+                    user_id,
+                    AuthExtractor {
+                        auth: "".to_string(),
+                        method: "GET".to_string(),
+                        o2_type: format!("{}:{}", stream_type, stream_name),
+                        org_id: org_id.to_string(),
+                        bypass_check: false,
+                        parent_id: "".to_string(),
+                    },
+                    Some(user.role),
+                )
+                .await
+            {
+                return false;
+            }
+        }
+        true
+    }
+    #[cfg(not(feature = "enterprise"))]
+    {
+        let _ = stream_type;
+        crate::service::rbac::is_allowed(
+            org_id,
+            user_id,
+            crate::common::meta::rbac::Resource::Stream,
+            stream_name,
+            crate::common::meta::rbac::Permission::Read,
+        )
+    }
+}
+
+/// Searches as the user, the row filters of the roles of the user apply.
 pub async fn search(
     trace_id: &str,
     org_id: &str,
     stream_type: StreamType,
     user_id: Option<String>,
     in_req: &search::Request,
+) -> Result<search::Response, Error> {
+    search_inner(trace_id, org_id, stream_type, user_id, false, in_req).await
+}
+
+/// Searches for the jobs of the system, like the usage reports and the
+/// alerts, without the row filters of a user. The results must not be
+/// returned to a user.
+pub async fn search_as_system(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    in_req: &search::Request,
+) -> Result<search::Response, Error> {
+    search_inner(trace_id, org_id, stream_type, None, true, in_req).await
+}
+
+#[tracing::instrument(name = "service:search:enter", skip(in_req))]
+async fn search_inner(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: Option<String>,
+    system: bool,
+    in_req: &search::Request,
 ) -> Result<search::Response, Error> {
     let start = std::time::Instant::now();
     let cfg = get_config();
@@ -123,9 +201,25 @@ pub async fn search(
     req.org_id = org_id.to_string();
     req.stype = cluster_rpc::SearchType::Cluster as _;
     req.stream_type = stream_type.to_string();
+    // the row filters of the roles of the user
+    if !system {
+        if let Some(sql) =
+            row_filter::apply(org_id, user_id.as_deref(), stream_type, &in_req.query.sql).await?
+        {
+            req.query.as_mut().unwrap().sql = sql;
+        }
+    }
 
     let req_query = req.clone().query.unwrap();
 
+    let _query_guard = tracker::register(
+        &trace_id,
+        org_id,
+        &stream_type.to_string(),
+        user_id.clone(),
+        &req_query.sql,
+    );
+
     let res = {
         #[cfg(feature = "enterprise")]
         if O2_CONFIG.super_cluster.enabled && !local_cluster_search {
@@ -145,7 +239,11 @@ pub async fn search(
 
     // do this because of clippy warning
     match res {
-        Ok(res) => {
+        Ok(mut res) => {
+            if in_req.query.highlight {
+                highlight::highlight_hits(org_id, stream_type, &in_req.query.sql, &mut res.hits)
+                    .await;
+            }
             let time = start.elapsed().as_secs_f64();
             let (report_usage, search_type) = match in_req.search_type {
                 Some(search_type) => match search_type {
@@ -154,6 +252,7 @@ pub async fn search(
                     search::SearchEventType::Reports => (true, in_req.search_type),
                     search::SearchEventType::Alerts => (true, in_req.search_type),
                     search::SearchEventType::RUM => (true, in_req.search_type),
+                    search::SearchEventType::DerivedStream => (true, in_req.search_type),
                     search::SearchEventType::Values => (false, None),
                     search::SearchEventType::Other => (false, None),
                 },
@@ -300,6 +399,106 @@ pub async fn search_partition(
     Ok(resp)
 }
 
+#[tracing::instrument(name = "service:search_explain:enter", skip(req))]
+pub async fn search_explain(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    req: &search::SearchPartitionRequest,
+) -> Result<search::SearchExplainResponse, Error> {
+    let query = cluster_rpc::SearchQuery {
+        start_time: req.start_time,
+        end_time: req.end_time,
+        sql: req.sql.to_string(),
+        sql_mode: req.sql_mode.to_string(),
+        ..Default::default()
+    };
+    let search_req = cluster_rpc::SearchRequest {
+        org_id: org_id.to_string(),
+        stream_type: stream_type.to_string(),
+        query: Some(query),
+        ..Default::default()
+    };
+    let meta = sql::Sql::new(&search_req).await?;
+
+    let stream_settings = unwrap_stream_settings(&meta.schema).unwrap_or_default();
+    let partition_time_level =
+        unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
+    let files = cluster::get_file_list(
+        trace_id,
+        &meta,
+        stream_type,
+        partition_time_level,
+        &stream_settings.partition_keys,
+    )
+    .await;
+
+    let session = search::Session {
+        id: trace_id.to_string(),
+        storage_type: search::StorageType::Memory,
+        search_type: search::SearchType::Normal,
+        work_group: None,
+    };
+    let schema = std::sync::Arc::new(meta.schema.clone());
+    let (logical_plan, physical_plan) = datafusion::exec::explain(&session, schema, &meta)
+        .await
+        .map_err(|e| Error::Message(e.to_string()))?;
+
+    let partitions = group_files_by_partition(&files);
+    let (time_min, time_max) = meta
+        .meta
+        .time_range
+        .unwrap_or((req.start_time, req.end_time));
+    Ok(search::SearchExplainResponse {
+        trace_id: trace_id.to_string(),
+        sql: meta.origin_sql.clone(),
+        time_range: [time_min, time_max],
+        logical_plan,
+        physical_plan,
+        file_num: files.len(),
+        records: partitions.iter().map(|p| p.records).sum(),
+        original_size: partitions.iter().map(|p| p.original_size).sum(),
+        compressed_size: partitions.iter().map(|p| p.compressed_size).sum(),
+        partitions,
+    })
+}
+
+/// Groups the files by their partition directory, in key order.
+fn group_files_by_partition(files: &[FileKey]) -> Vec<search::SearchExplainPartition> {
+    let mut partitions: Vec<search::SearchExplainPartition> = Vec::new();
+    for file in files {
+        let prefix = file
+            .key
+            .rsplit_once('/')
+            .map(|(prefix, _)| prefix)
+            .unwrap_or_default();
+        let file = search::SearchExplainFile {
+            key: file.key.clone(),
+            min_ts: file.meta.min_ts,
+            max_ts: file.meta.max_ts,
+            records: file.meta.records as usize,
+            original_size: file.meta.original_size as usize,
+            compressed_size: file.meta.compressed_size as usize,
+        };
+        let partition = match partitions.last_mut() {
+            Some(p) if p.prefix == prefix => p,
+            _ => {
+                partitions.push(search::SearchExplainPartition {
+                    prefix: prefix.to_string(),
+                    ..Default::default()
+                });
+                partitions.last_mut().unwrap()
+            }
+        };
+        partition.file_num += 1;
+        partition.records += file.records;
+        partition.original_size += file.original_size;
+        partition.compressed_size += file.compressed_size;
+        partition.files.push(file);
+    }
+    partitions
+}
+
 #[cfg(feature = "enterprise")]
 pub async fn query_status() -> Result<search::QueryStatusResponse, Error> {
     // get nodes from cluster
@@ -335,7 +534,11 @@ pub async fn query_status() -> Result<search::QueryStatusResponse, Error> {
                 let token: MetadataValue<_> = infra_cluster::get_internal_grpc_token()
                     .parse()
                     .map_err(|_| Error::Message("invalid token".to_string()))?;
-                let channel = Channel::from_shared(node_addr)
+                let grpc_version: MetadataValue<_> = node
+                    .negotiate_grpc_version()
+                    .unwrap_or(GRPC_MIN_VERSION)
+                    .into();
+                let channel = tls::grpc_endpoint(node_addr)
                     .unwrap()
                     .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
                     .connect()
@@ -352,6 +555,8 @@ pub async fn query_status() -> Result<search::QueryStatusResponse, Error> {
                     channel,
                     move |mut req: Request<()>| {
                         req.metadata_mut().insert("authorization", token.clone());
+                        req.metadata_mut()
+                            .insert(GRPC_VERSION_HEADER, grpc_version.clone());
                         Ok(req)
                     },
                 );
@@ -483,7 +688,11 @@ pub async fn cancel_query(trace_id: &str) -> Result<search::CancelQueryResponse,
                 let token: MetadataValue<_> = infra_cluster::get_internal_grpc_token()
                     .parse()
                     .map_err(|_| Error::Message("invalid token".to_string()))?;
-                let channel = Channel::from_shared(node_addr)
+                let grpc_version: MetadataValue<_> = node
+                    .negotiate_grpc_version()
+                    .unwrap_or(GRPC_MIN_VERSION)
+                    .into();
+                let channel = tls::grpc_endpoint(node_addr)
                     .unwrap()
                     .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
                     .connect()
@@ -500,6 +709,8 @@ pub async fn cancel_query(trace_id: &str) -> Result<search::CancelQueryResponse,
                     channel,
                     move |mut req: Request<()>| {
                         req.metadata_mut().insert("authorization", token.clone());
+                        req.metadata_mut()
+                            .insert(GRPC_VERSION_HEADER, grpc_version.clone());
                         Ok(req)
                     },
                 );
@@ -832,4 +1043,39 @@ mod tests {
             assert_eq!(filter_source_by_partition_key(path, &filter), expected);
         }
     }
+
+    #[test]
+    fn test_group_files_by_partition() {
+        let file = |key: &str, records: i64| {
+            FileKey::new(
+                key,
+                config::meta::stream::FileMeta {
+                    min_ts: 1,
+                    max_ts: 2,
+                    records,
+                    original_size: records * 10,
+                    compressed_size: records,
+                    flattened: false,
+                },
+                false,
+            )
+        };
+        let files = vec![
+            file("files/default/logs/app/2024/05/01/00/host=a/1.parquet", 1),
+            file("files/default/logs/app/2024/05/01/00/host=a/2.parquet", 2),
+            file("files/default/logs/app/2024/05/01/01/host=b/3.parquet", 4),
+        ];
+        let partitions = group_files_by_partition(&files);
+        assert_eq!(partitions.len(), 2);
+        assert_eq!(
+            partitions[0].prefix,
+            "files/default/logs/app/2024/05/01/00/host=a"
+        );
+        assert_eq!(partitions[0].file_num, 2);
+        assert_eq!(partitions[0].records, 3);
+        assert_eq!(partitions[0].original_size, 30);
+        assert_eq!(partitions[1].files[0].key, files[2].key);
+        assert_eq!(partitions[1].compressed_size, 4);
+        assert!(group_files_by_partition(&[]).is_empty());
+    }
 }
@@ -0,0 +1,314 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fair queue of the searches the http api of a node sends to the cluster.
+//!
+//! `ZO_QUERY_QUEUE_CONCURRENCY` searches run at the same time, the others
+//! wait in the queue of their organization. The queues are served by start
+//! time fair queueing: an organization gets a share of the slots by its weight
+//! in `ZO_QUERY_QUEUE_ORG_WEIGHTS`, so the burst of dashboard refreshes of one
+//! organization doesn't delay the searches of the others. The searches of an
+//! organization are served round robin by user. An organization with
+//! `ZO_QUERY_QUEUE_MAX_DEPTH` searches waiting has the next ones rejected.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+use config::{get_config, metrics};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+static QUEUE: Lazy<Mutex<Queue>> = Lazy::new(|| Mutex::new(Queue::default()));
+
+static ORG_WEIGHTS: Lazy<HashMap<String, u32>> =
+    Lazy::new(|| parse_weights(&get_config().limit.query_queue_org_weights));
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueueError {
+    #[error("Search queue of organization {0} is full: {1} searches are waiting, retry later")]
+    Full(String, usize),
+}
+
+/// Holds the slot of a search until it is dropped.
+pub struct QueueTicket {
+    _private: (),
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        let mut queue = QUEUE.lock();
+        queue.running = queue.running.saturating_sub(1);
+        queue.dispatch(get_config().limit.query_queue_concurrency);
+    }
+}
+
+/// Waits for a slot of the search of the user, `None` when the queue is
+/// disabled.
+pub async fn enter(org_id: &str, user_id: &str) -> Result<Option<QueueTicket>, QueueError> {
+    let cfg = get_config();
+    if !cfg.common.feature_query_queue_enabled {
+        return Ok(None);
+    }
+    let start = Instant::now();
+    let (id, rx) = {
+        let mut queue = QUEUE.lock();
+        let weight = ORG_WEIGHTS.get(org_id).copied().unwrap_or(1);
+        let (id, rx) = queue.push(org_id, user_id, weight, cfg.limit.query_queue_max_depth)?;
+        queue.dispatch(cfg.limit.query_queue_concurrency);
+        (id, rx)
+    };
+    let mut waiting = Waiting {
+        org_id,
+        id,
+        done: false,
+    };
+    let granted = rx.await.is_ok();
+    waiting.done = true;
+    metrics::QUERY_QUEUE_TIME
+        .with_label_values(&[org_id])
+        .observe(start.elapsed().as_secs_f64());
+    Ok(granted.then_some(QueueTicket { _private: () }))
+}
+
+// leaves the queue when the search is cancelled while it waits, or gives back
+// the slot it was granted before it could take it
+struct Waiting<'a> {
+    org_id: &'a str,
+    id: u64,
+    done: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut queue = QUEUE.lock();
+        if queue.scheduler.remove(self.org_id, self.id) {
+            queue.waiters.remove(&self.id);
+            metrics::QUERY_QUEUE_DEPTH
+                .with_label_values(&[self.org_id])
+                .dec();
+        } else {
+            queue.running = queue.running.saturating_sub(1);
+            queue.dispatch(get_config().limit.query_queue_concurrency);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    scheduler: Scheduler,
+    running: usize,
+    next_id: u64,
+    waiters: HashMap<u64, oneshot::Sender<()>>,
+}
+
+impl Queue {
+    fn push(
+        &mut self,
+        org_id: &str,
+        user_id: &str,
+        weight: u32,
+        max_depth: usize,
+    ) -> Result<(u64, oneshot::Receiver<()>), QueueError> {
+        let depth = self.scheduler.depth(org_id);
+        if max_depth > 0 && depth >= max_depth {
+            metrics::ADMISSION_REJECTED
+                .with_label_values(&["search_queue"])
+                .inc();
+            return Err(QueueError::Full(org_id.to_string(), depth));
+        }
+        self.next_id += 1;
+        let id = self.next_id;
+        let (tx, rx) = oneshot::channel();
+        self.scheduler.push(org_id, user_id, weight, id);
+        self.waiters.insert(id, tx);
+        metrics::QUERY_QUEUE_DEPTH
+            .with_label_values(&[org_id])
+            .inc();
+        Ok((id, rx))
+    }
+
+    fn dispatch(&mut self, concurrency: usize) {
+        while self.running < concurrency {
+            let Some((org_id, id)) = self.scheduler.pop() else {
+                return;
+            };
+            metrics::QUERY_QUEUE_DEPTH
+                .with_label_values(&[&org_id])
+                .dec();
+            if let Some(tx) = self.waiters.remove(&id) {
+                if tx.send(()).is_ok() {
+                    self.running += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Start time fair queueing of the organizations, each search costs one.
+#[derive(Default)]
+struct Scheduler {
+    // start tag of the last search served
+    vtime: f64,
+    orgs: HashMap<String, OrgQueue>,
+}
+
+struct OrgQueue {
+    weight: u32,
+    // finish tag of the last search served of the organization
+    finish: f64,
+    queued: usize,
+    users: VecDeque<(String, VecDeque<u64>)>,
+}
+
+impl Scheduler {
+    fn depth(&self, org_id: &str) -> usize {
+        self.orgs.get(org_id).map_or(0, |org| org.queued)
+    }
+
+    fn push(&mut self, org_id: &str, user_id: &str, weight: u32, id: u64) {
+        let org = self
+            .orgs
+            .entry(org_id.to_string())
+            .or_insert_with(|| OrgQueue {
+                weight,
+                finish: 0.0,
+                queued: 0,
+                users: VecDeque::new(),
+            });
+        org.weight = weight.max(1);
+        org.queued += 1;
+        match org.users.iter_mut().find(|(user, _)| user == user_id) {
+            Some((_, ids)) => ids.push_back(id),
+            None => org
+                .users
+                .push_back((user_id.to_string(), VecDeque::from([id]))),
+        }
+    }
+
+    fn pop(&mut self) -> Option<(String, u64)> {
+        let vtime = self.vtime;
+        // an idle organization doesn't save up the share it didn't use
+        let (org_id, start) = self
+            .orgs
+            .iter()
+            .filter(|(_, org)| org.queued > 0)
+            .map(|(org_id, org)| (org_id, org.finish.max(vtime)))
+            .min_by(|(a_id, a), (b_id, b)| a.total_cmp(b).then_with(|| a_id.cmp(b_id)))
+            .map(|(org_id, start)| (org_id.clone(), start))?;
+        self.vtime = start;
+        let org = self.orgs.get_mut(&org_id)?;
+        org.finish = start + 1.0 / org.weight as f64;
+        org.queued -= 1;
+        let (user_id, mut ids) = org.users.pop_front()?;
+        let id = ids.pop_front()?;
+        if !ids.is_empty() {
+            org.users.push_back((user_id, ids));
+        }
+        let vtime = self.vtime;
+        self.orgs
+            .retain(|_, org| org.queued > 0 || org.finish > vtime);
+        Some((org_id, id))
+    }
+
+    fn remove(&mut self, org_id: &str, id: u64) -> bool {
+        let Some(org) = self.orgs.get_mut(org_id) else {
+            return false;
+        };
+        let Some(pos) = org.users.iter().position(|(_, ids)| ids.contains(&id)) else {
+            return false;
+        };
+        let ids = &mut org.users[pos].1;
+        ids.retain(|v| *v != id);
+        if ids.is_empty() {
+            org.users.remove(pos);
+        }
+        org.queued -= 1;
+        true
+    }
+}
+
+fn parse_weights(s: &str) -> HashMap<String, u32> {
+    s.split(',')
+        .filter_map(|item| {
+            let (org_id, weight) = item.trim().split_once(':')?;
+            let weight = weight.trim().parse::<u32>().ok().filter(|w| *w > 0)?;
+            Some((org_id.trim().to_string(), weight))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(scheduler: &mut Scheduler) -> Vec<u64> {
+        std::iter::from_fn(|| scheduler.pop().map(|(_, id)| id)).collect()
+    }
+
+    #[test]
+    fn test_scheduler_fair_across_orgs() {
+        let mut scheduler = Scheduler::default();
+        // a burst of org1 queued before a single search of org2
+        for id in 1..=4 {
+            scheduler.push("org1", "u1", 1, id);
+        }
+        scheduler.push("org2", "u2", 1, 10);
+        assert_eq!(drain(&mut scheduler), vec![1, 10, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_scheduler_weights() {
+        let mut scheduler = Scheduler::default();
+        for id in 1..=4 {
+            scheduler.push("org1", "u1", 3, id);
+            scheduler.push("org2", "u2", 1, id + 10);
+        }
+        assert_eq!(drain(&mut scheduler), vec![1, 11, 2, 3, 4, 12, 13, 14]);
+    }
+
+    #[test]
+    fn test_scheduler_round_robin_users() {
+        let mut scheduler = Scheduler::default();
+        scheduler.push("org1", "u1", 1, 1);
+        scheduler.push("org1", "u1", 1, 2);
+        scheduler.push("org1", "u2", 1, 3);
+        assert_eq!(drain(&mut scheduler), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn test_scheduler_remove() {
+        let mut scheduler = Scheduler::default();
+        scheduler.push("org1", "u1", 1, 1);
+        scheduler.push("org1", "u1", 1, 2);
+        assert!(scheduler.remove("org1", 1));
+        assert!(!scheduler.remove("org1", 1));
+        assert_eq!(scheduler.depth("org1"), 1);
+        assert_eq!(drain(&mut scheduler), vec![2]);
+    }
+
+    #[test]
+    fn test_parse_weights() {
+        let weights = parse_weights("org1:4, org2:2,bad,org3:0");
+        assert_eq!(weights.len(), 2);
+        assert_eq!(weights.get("org1"), Some(&4));
+        assert_eq!(weights.get("org2"), Some(&2));
+    }
+}
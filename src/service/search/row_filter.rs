@@ -0,0 +1,477 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Row-level security. The row filters of the roles of a user are AND-ed into
+//! the queries the user runs, on every select reading a matching stream. A
+//! user sees the rows matching any of the filters of their roles on the
+//! stream, the roles without a filter on it don't lift the filters of the
+//! others.
+
+use std::{collections::HashMap, ops::ControlFlow};
+
+use config::meta::stream::StreamType;
+use infra::errors::{Error, Result};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use sqlparser::{
+    ast::{
+        visit_relations, BinaryOperator, Expr, ObjectName, Query, Select, SetExpr, TableFactor,
+        Value as SqlValue, VisitMut, VisitorMut,
+    },
+    dialect::GenericDialect,
+    parser::{Parser, ParserError},
+    tokenizer::Token,
+};
+
+use crate::{
+    common::{
+        infra::config::ROLES,
+        meta::{rbac::RowFilter, service_account::parse_principal},
+        utils::auth::is_root_user,
+    },
+    service::{db, rbac, service_accounts},
+};
+
+static RE_USER_VAR: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*user\.([A-Za-z0-9_]+)\s*\}\}").unwrap());
+
+/// Returns the query with the row filters of the roles of the user, `None`
+/// when no filter applies to the query. A search without a user fails when
+/// the organization has row filters on the stream type, the searches of the
+/// system use `search_as_system` that doesn't apply them.
+pub async fn apply(
+    org_id: &str,
+    user_id: Option<&str>,
+    stream_type: StreamType,
+    sql: &str,
+) -> Result<Option<String>> {
+    let Some(user_id) = user_id else {
+        if has_filters(org_id, stream_type) {
+            return Err(Error::Message(
+                "row filters: the search has no user to apply them for".to_string(),
+            ));
+        }
+        return Ok(None);
+    };
+    if is_root_user(user_id)
+        || parse_principal(user_id)
+            .is_some_and(|(name, _)| service_accounts::bypasses_row_filters(org_id, name))
+    {
+        return Ok(None);
+    }
+    let filters = rbac::get_user_roles(org_id, user_id)
+        .into_iter()
+        .flat_map(|role| role.row_filters)
+        .filter(|filter| filter.stream_type == stream_type)
+        .collect::<Vec<_>>();
+    if filters.is_empty() {
+        return Ok(None);
+    }
+
+    let mut vars = if filters.iter().any(|f| RE_USER_VAR.is_match(&f.filter)) {
+        db::user::get_attributes(org_id, user_id)
+            .await
+            .map_err(|e| Error::Message(e.to_string()))?
+    } else {
+        HashMap::new()
+    };
+    vars.insert("email".to_string(), user_id.to_string());
+    add_filters(sql, &filters, stream_type, &vars)
+        .map_err(|e| Error::Message(format!("row filters: {e}")))
+}
+
+/// Whether a role of the organization has row filters on the stream type.
+fn has_filters(org_id: &str, stream_type: StreamType) -> bool {
+    let prefix = format!("{org_id}/");
+    ROLES.iter().any(|r| {
+        r.key().starts_with(&prefix)
+            && r.value()
+                .row_filters
+                .iter()
+                .any(|f| f.stream_type == stream_type)
+    })
+}
+
+/// Checks a row filter before it is attached to a role.
+pub fn validate(filter: &RowFilter) -> std::result::Result<(), anyhow::Error> {
+    if filter.stream_name.trim().is_empty() {
+        return Err(anyhow::anyhow!("stream_name is required"));
+    }
+    // the promql queries don't go through the sql the filters are added to
+    if filter.stream_type == StreamType::Metrics {
+        return Err(anyhow::anyhow!(
+            "the row filters are not supported on the metrics streams"
+        ));
+    }
+    // the values are quoted as strings, outside of quotes they could change
+    // the condition
+    if strip_quoted(&filter.filter).contains("{{") {
+        return Err(anyhow::anyhow!(
+            "the user variables can only be used inside quoted strings"
+        ));
+    }
+    let vars = RE_USER_VAR
+        .captures_iter(&filter.filter)
+        .map(|caps| (caps[1].to_string(), "x".to_string()))
+        .collect::<HashMap<_, _>>();
+    let condition = parse_condition(&render(&filter.filter, &vars).unwrap_or_default())?;
+    if visit_relations(&condition, |_| ControlFlow::Break(())).is_break() {
+        return Err(anyhow::anyhow!("the row filters can not read streams"));
+    }
+    Ok(())
+}
+
+fn add_filters(
+    sql: &str,
+    filters: &[RowFilter],
+    stream_type: StreamType,
+    vars: &HashMap<String, String>,
+) -> std::result::Result<Option<String>, anyhow::Error> {
+    let mut statements = Parser::parse_sql(&GenericDialect {}, sql)?;
+    if statements.len() != 1 {
+        return Err(anyhow::anyhow!("only one statement can be run"));
+    }
+    let mut tables = Vec::new();
+    let _ = visit_relations(&statements, |name| {
+        tables.push(table_name(name));
+        ControlFlow::<()>::Continue(())
+    });
+    let mut conditions = HashMap::new();
+    for table in tables {
+        if conditions.contains_key(&table) {
+            continue;
+        }
+        if let Some(condition) = condition(filters, stream_type, &table, vars)? {
+            conditions.insert(table, condition);
+        }
+    }
+    if conditions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut visitor = AddFilters {
+        conditions: &conditions,
+        unsupported: false,
+    };
+    let _ = statements.visit(&mut visitor);
+    if visitor.unsupported {
+        return Err(anyhow::anyhow!(
+            "the streams with row filters can not be joined"
+        ));
+    }
+    Ok(Some(statements[0].to_string()))
+}
+
+/// The filters of the stream OR-ed, a filter referring to a missing
+/// attribute of the user matches no row.
+fn condition(
+    filters: &[RowFilter],
+    stream_type: StreamType,
+    table: &str,
+    vars: &HashMap<String, String>,
+) -> std::result::Result<Option<Expr>, ParserError> {
+    let mut conditions = Vec::new();
+    for filter in filters.iter().filter(|f| f.is_match(stream_type, table)) {
+        let condition = match render(&filter.filter, vars) {
+            Some(rendered) => parse_condition(&rendered)?,
+            None => Expr::Value(SqlValue::Boolean(false)),
+        };
+        conditions.push(Expr::Nested(Box::new(condition)));
+    }
+    let len = conditions.len();
+    let condition = conditions.into_iter().reduce(|left, right| Expr::BinaryOp {
+        left: Box::new(left),
+        op: BinaryOperator::Or,
+        right: Box::new(right),
+    });
+    Ok(condition.map(|condition| {
+        if len > 1 {
+            Expr::Nested(Box::new(condition))
+        } else {
+            condition
+        }
+    }))
+}
+
+/// Replaces the user variables by their values, with the single quotes
+/// doubled. Returns `None` when a variable is missing, or holds a backslash
+/// some dialects read as an escape.
+fn render(filter: &str, vars: &HashMap<String, String>) -> Option<String> {
+    let mut missing = false;
+    let rendered = RE_USER_VAR.replace_all(filter, |caps: &Captures| match vars.get(&caps[1]) {
+        Some(value) if !value.contains('\\') => value.replace('\'', "''"),
+        _ => {
+            missing = true;
+            String::new()
+        }
+    });
+    let rendered = rendered.into_owned();
+    (!missing).then_some(rendered)
+}
+
+fn parse_condition(sql: &str) -> std::result::Result<Expr, ParserError> {
+    let dialect = GenericDialect {};
+    let mut parser = Parser::new(&dialect).try_with_sql(sql)?;
+    let expr = parser.parse_expr()?;
+    let next = parser.peek_token();
+    if next.token != Token::EOF {
+        return Err(ParserError::ParserError(format!(
+            "unexpected {} after the condition",
+            next.token
+        )));
+    }
+    Ok(expr)
+}
+
+/// The text of the filter outside of its single quoted strings.
+fn strip_quoted(filter: &str) -> String {
+    let mut in_quotes = false;
+    filter
+        .chars()
+        .filter(|c| {
+            if *c == '\'' {
+                in_quotes = !in_quotes;
+                return false;
+            }
+            !in_quotes
+        })
+        .collect()
+}
+
+fn table_name(name: &ObjectName) -> String {
+    name.0
+        .iter()
+        .map(|ident| ident.value.as_str())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+struct AddFilters<'a> {
+    conditions: &'a HashMap<String, Expr>,
+    unsupported: bool,
+}
+
+impl VisitorMut for AddFilters<'_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<Self::Break> {
+        self.add_to_set_expr(query.body.as_mut());
+        ControlFlow::Continue(())
+    }
+}
+
+impl AddFilters<'_> {
+    fn add_to_set_expr(&mut self, body: &mut SetExpr) {
+        match body {
+            SetExpr::Select(select) => self.add_to_select(select),
+            SetExpr::SetOperation { left, right, .. } => {
+                self.add_to_set_expr(left);
+                self.add_to_set_expr(right);
+            }
+            _ => {}
+        }
+    }
+
+    fn add_to_select(&mut self, select: &mut Select) {
+        let conditions = self.conditions;
+        let filtered = select
+            .from
+            .iter()
+            .flat_map(|table| {
+                std::iter::once(&table.relation).chain(table.joins.iter().map(|j| &j.relation))
+            })
+            .filter_map(|relation| match relation {
+                TableFactor::Table { name, .. } => conditions.get(&table_name(name)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if filtered.is_empty() {
+            return;
+        }
+        if select.from.len() != 1 || !select.from[0].joins.is_empty() {
+            self.unsupported = true;
+            return;
+        }
+        let condition = filtered[0].clone();
+        select.selection = Some(match select.selection.take() {
+            Some(selection) => Expr::BinaryOp {
+                left: Box::new(condition),
+                op: BinaryOperator::And,
+                right: Box::new(Expr::Nested(Box::new(selection))),
+            },
+            None => condition,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(stream_name: &str, filter: &str) -> RowFilter {
+        RowFilter {
+            stream_type: StreamType::Logs,
+            stream_name: stream_name.to_string(),
+            filter: filter.to_string(),
+        }
+    }
+
+    fn vars(items: &[(&str, &str)]) -> HashMap<String, String> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_add_filters() {
+        let filters = vec![filter("shared_*", "tenant_id = '{{user.tenant}}'")];
+        let tenant = vars(&[("tenant", "acme")]);
+        let sql = "SELECT * FROM shared_logs WHERE a = 1 OR b = 2";
+        assert_eq!(
+            add_filters(sql, &filters, StreamType::Logs, &tenant).unwrap(),
+            Some(
+                "SELECT * FROM shared_logs WHERE (tenant_id = 'acme') AND (a = 1 OR b = 2)"
+                    .to_string()
+            )
+        );
+        let sql = "SELECT count(*) FROM \"shared_logs\"";
+        assert_eq!(
+            add_filters(sql, &filters, StreamType::Logs, &tenant).unwrap(),
+            Some("SELECT count(*) FROM \"shared_logs\" WHERE (tenant_id = 'acme')".to_string())
+        );
+        let sql = "SELECT * FROM app_logs";
+        assert_eq!(
+            add_filters(sql, &filters, StreamType::Logs, &tenant).unwrap(),
+            None
+        );
+        assert_eq!(
+            add_filters(sql, &filters, StreamType::Traces, &tenant).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_add_filters_subquery() {
+        let filters = vec![
+            filter("shared", "tenant_id = '{{user.tenant}}'"),
+            filter("shared", "team = 'web'"),
+        ];
+        let sql = "SELECT a FROM (SELECT * FROM shared) WHERE a > 1";
+        assert_eq!(
+            add_filters(sql, &filters, StreamType::Logs, &vars(&[])).unwrap(),
+            Some(
+                "SELECT a FROM (SELECT * FROM shared WHERE (false) OR (team = 'web')) WHERE a > 1"
+                    .to_string()
+            )
+        );
+        let sql = "SELECT * FROM shared, other";
+        assert!(add_filters(sql, &filters, StreamType::Logs, &vars(&[])).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let filter = "tenant_id = '{{ user.tenant }}'";
+        assert_eq!(
+            render(filter, &vars(&[("tenant", "a' OR '1'='1")])).unwrap(),
+            "tenant_id = 'a'' OR ''1''=''1'"
+        );
+        assert_eq!(render(filter, &vars(&[("tenant", "a\\")])), None);
+        assert_eq!(render(filter, &vars(&[])), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&filter("logs", "tenant_id = '{{user.tenant}}'")).is_ok());
+        assert!(validate(&filter("logs", "tenant_id = {{user.tenant}}")).is_err());
+        assert!(validate(&filter("logs", "a = 1 OR")).is_err());
+        assert!(validate(&filter("logs", "a = 1) OR (1 = 1")).is_err());
+        assert!(validate(&filter("logs", "a IN (SELECT a FROM other)")).is_err());
+        assert!(validate(&filter("", "a = 1")).is_err());
+        let mut metrics = filter("cpu", "a = 1");
+        metrics.stream_type = StreamType::Metrics;
+        assert!(validate(&metrics).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apply_without_user() {
+        let sql = "SELECT * FROM logs";
+        assert_eq!(
+            apply("row_filter_none", None, StreamType::Logs, sql)
+                .await
+                .unwrap(),
+            None
+        );
+        ROLES.insert(
+            "row_filter_none/tenants".to_string(),
+            crate::common::meta::rbac::Role {
+                name: "tenants".to_string(),
+                row_filters: vec![filter("logs", "tenant_id = '{{user.tenant}}'")],
+                ..Default::default()
+            },
+        );
+        assert!(
+            apply("row_filter_none", None, StreamType::Logs, sql)
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            apply("row_filter_none", None, StreamType::Traces, sql)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_service_account() {
+        use crate::common::{
+            infra::config::SERVICE_ACCOUNTS,
+            meta::{rbac::Role, service_account::get_principal},
+        };
+
+        let principal = get_principal("agent", "t1");
+        let sql = "SELECT * FROM logs";
+        ROLES.insert(
+            "row_filter_sa/agents".to_string(),
+            Role {
+                name: "agents".to_string(),
+                users: [principal.clone()].into_iter().collect(),
+                row_filters: vec![filter("logs", "a = 1")],
+                ..Default::default()
+            },
+        );
+        assert!(
+            apply("row_filter_sa", Some(&principal), StreamType::Logs, sql)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        SERVICE_ACCOUNTS.insert(
+            "row_filter_sa/agent".to_string(),
+            crate::common::meta::service_account::ServiceAccount {
+                name: "agent".to_string(),
+                bypass_row_filters: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            apply("row_filter_sa", Some(&principal), StreamType::Logs, sql)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -139,6 +139,23 @@ impl Sql {
             }
         };
 
+        // the fields are searched by their aliases too
+        let field_aliases = infra::schema::get_settings(&org_id, &meta.source, stream_type)
+            .await
+            .map(|settings| settings.field_aliases)
+            .unwrap_or_default();
+        if !field_aliases.is_empty() {
+            origin_sql = resolve_field_aliases(&origin_sql, &field_aliases);
+            rewrite_sql = resolve_field_aliases(&rewrite_sql, &field_aliases);
+            meta = match MetaSql::new(&origin_sql) {
+                Ok(meta) => meta,
+                Err(err) => {
+                    log::error!("parse sql error: {}, sql: {}", err, origin_sql);
+                    return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(origin_sql)));
+                }
+            };
+        }
+
         let cfg = get_config();
         // need check some things:
         // 1. no where
@@ -1015,6 +1032,57 @@ fn split_sql_token(text: &str) -> Vec<String> {
     tokens
 }
 
+/// Replaces the field aliases of a stream in the sql by the fields they stand
+/// for, the quoted strings and the function names are left as they are.
+pub(crate) fn resolve_field_aliases(
+    sql: &str,
+    aliases: &hashbrown::HashMap<String, String>,
+) -> String {
+    if aliases.is_empty() {
+        return sql.to_string();
+    }
+    let chars = sql.chars().collect::<Vec<char>>();
+    let mut out = String::with_capacity(sql.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' || c == '"' {
+            let Some(end) = chars[i + 1..].iter().position(|v| *v == c) else {
+                out.extend(&chars[i..]);
+                break;
+            };
+            let end = i + 1 + end;
+            let quoted = chars[i + 1..end].iter().collect::<String>();
+            match aliases.get(&quoted) {
+                Some(field) if c == '"' => out.push_str(&format!("\"{field}\"")),
+                _ => out.extend(&chars[i..=end]),
+            }
+            i = end + 1;
+            continue;
+        }
+        if !(c.is_alphanumeric() || c == '_') {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+            i += 1;
+        }
+        let word = chars[start..i].iter().collect::<String>();
+        let is_function = chars[i..]
+            .iter()
+            .find(|v| !v.is_whitespace())
+            .is_some_and(|v| *v == '(');
+        let is_qualified = start > 0 && chars[start - 1] == '.';
+        match aliases.get(&word) {
+            Some(field) if !is_function && !is_qualified => out.push_str(field),
+            _ => out.push_str(&word),
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1039,6 +1107,7 @@ mod tests {
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            highlight: false,
         };
 
         let req: config::meta::search::Request = config::meta::search::Request {
@@ -1151,6 +1220,7 @@ mod tests {
                 uses_zo_fn: false,
                 query_fn: None,
                 skip_wal: false,
+                highlight: false,
             };
             let req = config::meta::search::Request {
                 query: query.clone(),
@@ -1275,6 +1345,7 @@ mod tests {
                 uses_zo_fn: false,
                 query_fn: None,
                 skip_wal: false,
+                highlight: false,
             };
             let req = config::meta::search::Request {
                 query: query.clone(),
@@ -1310,4 +1381,23 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_resolve_field_aliases() {
+        let aliases = hashbrown::HashMap::from([
+            ("service".to_string(), "svc_name".to_string()),
+            ("count".to_string(), "hits".to_string()),
+        ]);
+        assert_eq!(
+            resolve_field_aliases(
+                "SELECT service, count(*) FROM \"default\" WHERE service = 'service' AND \"count\" > 1",
+                &aliases
+            ),
+            "SELECT svc_name, count(*) FROM \"default\" WHERE svc_name = 'service' AND \"hits\" > 1"
+        );
+        assert_eq!(
+            resolve_field_aliases("SELECT * FROM t WHERE t.service_a = 1", &aliases),
+            "SELECT * FROM t WHERE t.service_a = 1"
+        );
+    }
 }
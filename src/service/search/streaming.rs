@@ -0,0 +1,237 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Search answered one partition of the time range at a time, newest first,
+//! so that the hits of the first partitions are sent while the older ones are
+//! still scanned.
+
+use config::{
+    get_config,
+    meta::{
+        search::{self, StreamingEvent},
+        sql::Sql,
+        stream::StreamType,
+        usage::{RequestStats, UsageType},
+    },
+};
+use infra::errors::Error;
+use tokio::sync::mpsc;
+
+use crate::service::{masking, stream, usage::report_request_usage_stats};
+
+/// The aggregations can't be answered partition by partition, their queries
+/// run over the whole time range.
+const AGGREGATE_FUNCTIONS: [&str; 9] = [
+    "count",
+    "sum",
+    "avg",
+    "min",
+    "max",
+    "approx_distinct",
+    "approx_percentile_cont",
+    "array_agg",
+    "histogram",
+];
+
+/// Runs the search and sends its hits and progress to `tx`, ending with an
+/// `End` event. It stops early when the receiver is dropped.
+pub async fn search(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: &str,
+    req: search::Request,
+    tx: mpsc::Sender<StreamingEvent>,
+) -> Result<(), Error> {
+    let start = std::time::Instant::now();
+    let meta = Sql::new(&req.query.sql).map_err(|e| Error::Message(e.to_string()))?;
+    let stream_name = meta.source.to_string();
+
+    let partitions = if can_partition(&meta, &req) {
+        let partition_req = search::SearchPartitionRequest {
+            sql: req.query.sql.to_string(),
+            sql_mode: req.query.sql_mode.to_string(),
+            start_time: req.query.start_time,
+            end_time: req.query.end_time,
+            encoding: search::RequestEncoding::Empty,
+            regions: req.regions.clone(),
+            clusters: req.clusters.clone(),
+        };
+        super::search_partition(trace_id, org_id, stream_type, &partition_req)
+            .await?
+            .partitions
+    } else {
+        vec![[req.query.start_time, req.query.end_time]]
+    };
+
+    let size = req.query.size;
+    let (mut hits, mut scan_size, mut scan_records) = (0, 0, 0);
+    for (partition, [start_time, end_time]) in partitions.iter().copied().enumerate() {
+        let mut partition_req = req.clone();
+        partition_req.query.start_time = start_time;
+        partition_req.query.end_time = end_time;
+        if size > 0 {
+            partition_req.query.size = size - hits as i64;
+        }
+        let mut res = super::federation::search(
+            trace_id,
+            org_id,
+            stream_type,
+            Some(user_id.to_string()),
+            &partition_req,
+        )
+        .await?;
+        masking::mask_hits(org_id, user_id, stream_type, &stream_name, &mut res.hits);
+        stream::apply_field_settings(org_id, stream_type, &stream_name, &mut res.hits).await;
+        res.set_trace_id(trace_id.to_string());
+        hits += res.hits.len();
+        scan_size += res.scan_size;
+        scan_records += res.scan_records;
+
+        let events = [
+            StreamingEvent::Hits {
+                partition,
+                start_time,
+                end_time,
+                response: res,
+            },
+            StreamingEvent::Progress {
+                trace_id: trace_id.to_string(),
+                partitions: partitions.len(),
+                partitions_done: partition + 1,
+                hits,
+                scan_size,
+                scan_records,
+                took: start.elapsed().as_millis() as usize,
+            },
+        ];
+        let mut closed = false;
+        for event in events {
+            if tx.send(event).await.is_err() {
+                closed = true;
+                break;
+            }
+        }
+        if closed || (size > 0 && hits as i64 >= size) {
+            break;
+        }
+    }
+
+    let req_stats = RequestStats {
+        records: hits as i64,
+        response_time: start.elapsed().as_secs_f64(),
+        size: scan_size as f64,
+        request_body: Some(req.query.sql.to_string()),
+        user_email: Some(user_id.to_string()),
+        min_ts: Some(req.query.start_time),
+        max_ts: Some(req.query.end_time),
+        search_type: req.search_type,
+        trace_id: Some(trace_id.to_string()),
+        ..Default::default()
+    };
+    let num_fn = req.query.query_fn.is_some() as u16;
+    report_request_usage_stats(
+        req_stats,
+        org_id,
+        &stream_name,
+        StreamType::Logs,
+        UsageType::Search,
+        num_fn,
+    )
+    .await;
+
+    let _ = tx
+        .send(StreamingEvent::End {
+            trace_id: trace_id.to_string(),
+            hits,
+            took: start.elapsed().as_millis() as usize,
+        })
+        .await;
+    Ok(())
+}
+
+/// Whether the hits of the partitions, newest first, are the hits of the
+/// whole time range: the query lists records by time, without aggregations
+/// or offset.
+fn can_partition(meta: &Sql, req: &search::Request) -> bool {
+    if req.query.from > 0 || !req.aggs.is_empty() || !meta.group_by.is_empty() {
+        return false;
+    }
+    let column_timestamp = &get_config().common.column_timestamp;
+    if let Some((field, desc)) = meta.order_by.first() {
+        if field != column_timestamp || !desc {
+            return false;
+        }
+    }
+    let sql = req.query.sql.to_lowercase();
+    !AGGREGATE_FUNCTIONS
+        .iter()
+        .any(|f| sql.contains(&format!("{f}(")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(sql: &str) -> search::Request {
+        search::Request {
+            query: search::Query {
+                sql: sql.to_string(),
+                from: 0,
+                size: 100,
+                start_time: 0,
+                end_time: 0,
+                sort_by: None,
+                sql_mode: "full".to_string(),
+                quick_mode: false,
+                query_type: "".to_string(),
+                track_total_hits: false,
+                query_context: None,
+                uses_zo_fn: false,
+                query_fn: None,
+                skip_wal: false,
+                highlight: false,
+            },
+            aggs: Default::default(),
+            encoding: search::RequestEncoding::Empty,
+            regions: vec![],
+            clusters: vec![],
+            timeout: 0,
+            search_type: None,
+        }
+    }
+
+    #[test]
+    fn test_can_partition() {
+        for (sql, expected) in [
+            ("select * from app", true),
+            ("select * from app where level = 'error'", true),
+            ("select * from app order by _timestamp desc", true),
+            ("select * from app order by _timestamp asc", false),
+            ("select * from app order by took desc", false),
+            ("select count(*) from app", false),
+            ("select level, count(*) from app group by level", false),
+        ] {
+            let req = request(sql);
+            let meta = Sql::new(sql).unwrap();
+            assert_eq!(can_partition(&meta, &req), expected, "{sql}");
+        }
+
+        let mut req = request("select * from app");
+        req.query.from = 100;
+        let meta = Sql::new(&req.query.sql).unwrap();
+        assert!(!can_partition(&meta, &req));
+    }
+}
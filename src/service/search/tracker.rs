@@ -0,0 +1,441 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tracks the queries running on this node, the memory datafusion reserves
+//! for them and the time spent polling them, and cancels them on request.
+//! The queries are keyed by trace_id, a datafusion session id starts with the
+//! trace_id of its query.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use config::{
+    get_config,
+    meta::{
+        cluster::{Node, GRPC_MIN_VERSION, GRPC_VERSION_HEADER},
+        search,
+    },
+};
+use datafusion::execution::memory_pool::MemoryPool;
+use hashbrown::HashMap;
+use infra::errors::Error;
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use proto::cluster_rpc;
+use tokio::sync::watch;
+use tonic::{
+    codec::CompressionEncoding, metadata::MetadataValue, service::Interceptor, transport::Channel,
+    Request,
+};
+
+use crate::common::infra::{cluster as infra_cluster, tls};
+
+static RUNNING: Lazy<RwLock<HashMap<String, Arc<RunningQuery>>>> = Lazy::new(Default::default);
+
+struct RunningQuery {
+    trace_id: String,
+    org_id: String,
+    stream_type: String,
+    user_id: Mutex<Option<String>>,
+    sql: String,
+    started_at: i64,
+    refs: AtomicUsize,
+    cpu_time: AtomicU64,
+    memory_pools: Mutex<Vec<Arc<dyn MemoryPool>>>,
+    cancel: watch::Sender<bool>,
+}
+
+impl RunningQuery {
+    fn to_rpc(&self) -> cluster_rpc::RunningQuery {
+        let memory_size = self
+            .memory_pools
+            .lock()
+            .iter()
+            .map(|pool| pool.reserved())
+            .sum::<usize>();
+        cluster_rpc::RunningQuery {
+            trace_id: self.trace_id.clone(),
+            org_id: self.org_id.clone(),
+            stream_type: self.stream_type.clone(),
+            user_id: self.user_id.lock().clone(),
+            sql: self.sql.clone(),
+            started_at: self.started_at,
+            memory_size: memory_size as i64,
+            cpu_time: self.cpu_time.load(Ordering::Relaxed) as i64,
+            is_cancelled: *self.cancel.borrow(),
+        }
+    }
+}
+
+/// Unregisters the query when dropped.
+pub struct QueryGuard {
+    trace_id: String,
+}
+
+impl Drop for QueryGuard {
+    fn drop(&mut self) {
+        let mut running = RUNNING.write();
+        if let Some(query) = running.get(&self.trace_id) {
+            if query.refs.fetch_sub(1, Ordering::Relaxed) == 1 {
+                running.remove(&self.trace_id);
+            }
+        }
+    }
+}
+
+/// Registers a query until the returned guard is dropped. The leader and the
+/// nodes register the same trace_id, so a node which is both shares the entry.
+pub fn register(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: &str,
+    user_id: Option<String>,
+    sql: &str,
+) -> QueryGuard {
+    let mut running = RUNNING.write();
+    match running.get(trace_id) {
+        Some(query) => {
+            query.refs.fetch_add(1, Ordering::Relaxed);
+            if user_id.is_some() {
+                *query.user_id.lock() = user_id;
+            }
+        }
+        None => {
+            running.insert(
+                trace_id.to_string(),
+                Arc::new(RunningQuery {
+                    trace_id: trace_id.to_string(),
+                    org_id: org_id.to_string(),
+                    stream_type: stream_type.to_string(),
+                    user_id: Mutex::new(user_id),
+                    sql: sql.to_string(),
+                    started_at: chrono::Utc::now().timestamp_micros(),
+                    refs: AtomicUsize::new(1),
+                    cpu_time: AtomicU64::new(0),
+                    memory_pools: Mutex::new(Vec::new()),
+                    cancel: watch::channel(false).0,
+                }),
+            );
+        }
+    }
+    QueryGuard {
+        trace_id: trace_id.to_string(),
+    }
+}
+
+fn get(session_id: &str) -> Option<Arc<RunningQuery>> {
+    let running = RUNNING.read();
+    if let Some(query) = running.get(session_id) {
+        return Some(query.clone());
+    }
+    running
+        .iter()
+        .find(|(trace_id, _)| is_session_of(session_id, trace_id))
+        .map(|(_, query)| query.clone())
+}
+
+fn is_session_of(session_id: &str, trace_id: &str) -> bool {
+    session_id
+        .strip_prefix(trace_id)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+}
+
+/// Adds the memory pool of a datafusion session to its query.
+pub fn add_memory_pool(session_id: &str, pool: Arc<dyn MemoryPool>) {
+    if let Some(query) = get(session_id) {
+        query.memory_pools.lock().push(pool);
+    }
+}
+
+/// Resolves once the query of the session is cancelled, never if the query
+/// is not tracked.
+pub fn cancelled(session_id: &str) -> impl Future<Output = ()> + Send + 'static {
+    let cancel = get(session_id).map(|query| query.cancel.subscribe());
+    async move {
+        if let Some(mut cancel) = cancel {
+            if cancel.wait_for(|cancelled| *cancelled).await.is_ok() {
+                return;
+            }
+        }
+        futures::future::pending::<()>().await
+    }
+}
+
+pub fn is_cancelled(session_id: &str) -> bool {
+    get(session_id).is_some_and(|query| *query.cancel.borrow())
+}
+
+/// Adds the time spent polling the future to the cpu time of the query of
+/// the session.
+pub fn timed<F: Future>(session_id: &str, fut: F) -> Timed<F> {
+    Timed {
+        fut: Box::pin(fut),
+        query: get(session_id),
+    }
+}
+
+pub struct Timed<F> {
+    fut: Pin<Box<F>>,
+    query: Option<Arc<RunningQuery>>,
+}
+
+impl<F: Future> Future for Timed<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let start = std::time::Instant::now();
+        let ret = self.fut.as_mut().poll(cx);
+        if let Some(query) = &self.query {
+            query
+                .cpu_time
+                .fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+        }
+        ret
+    }
+}
+
+/// The queries of the organization running on this node.
+pub fn local_queries(org_id: &str) -> Vec<cluster_rpc::RunningQuery> {
+    RUNNING
+        .read()
+        .values()
+        .filter(|query| query.org_id == org_id)
+        .map(|query| query.to_rpc())
+        .collect()
+}
+
+/// Cancels the query on this node, returns false if it does not run here.
+pub fn cancel_local(org_id: &str, trace_id: &str) -> bool {
+    match RUNNING.read().get(trace_id) {
+        Some(query) if query.org_id == org_id => {
+            query.cancel.send_replace(true);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The queries of the organization running in the cluster.
+pub async fn running_queries(org_id: &str) -> Result<search::RunningQueriesResponse, Error> {
+    let mut tasks = Vec::new();
+    for node in query_nodes().await {
+        let org_id = org_id.to_string();
+        tasks.push(tokio::task::spawn(async move {
+            let mut client = node_client(&node).await?;
+            let request = Request::new(cluster_rpc::RunningQueriesRequest { org_id });
+            client
+                .running_queries(request)
+                .await
+                .map(|res| res.into_inner().queries)
+                .map_err(|e| Error::Message(format!("node {}: {}", node.grpc_addr, e)))
+        }));
+    }
+    let mut queries = Vec::new();
+    for task in tasks {
+        match task.await {
+            Ok(Ok(node_queries)) => queries.extend(node_queries),
+            Ok(Err(e)) => log::error!("[QUERY_TRACKER] list running queries error: {}", e),
+            Err(e) => log::error!("[QUERY_TRACKER] list running queries error: {}", e),
+        }
+    }
+    Ok(search::RunningQueriesResponse {
+        queries: merge_queries(queries),
+    })
+}
+
+/// Cancels the query on all the nodes running it.
+pub async fn kill_query(
+    org_id: &str,
+    trace_id: &str,
+) -> Result<search::CancelQueryResponse, Error> {
+    let mut tasks = Vec::new();
+    for node in query_nodes().await {
+        let org_id = org_id.to_string();
+        let trace_id = trace_id.to_string();
+        tasks.push(tokio::task::spawn(async move {
+            let mut client = node_client(&node).await?;
+            let request = Request::new(cluster_rpc::KillQueryRequest { org_id, trace_id });
+            client
+                .kill_query(request)
+                .await
+                .map(|res| res.into_inner().is_success)
+                .map_err(|e| Error::Message(format!("node {}: {}", node.grpc_addr, e)))
+        }));
+    }
+    let mut is_success = false;
+    for task in tasks {
+        match task.await {
+            Ok(Ok(killed)) => is_success |= killed,
+            Ok(Err(e)) => log::error!("[QUERY_TRACKER] kill query error: {}", e),
+            Err(e) => log::error!("[QUERY_TRACKER] kill query error: {}", e),
+        }
+    }
+    Ok(search::CancelQueryResponse {
+        trace_id: trace_id.to_string(),
+        is_success,
+    })
+}
+
+async fn query_nodes() -> Vec<Node> {
+    let mut nodes = infra_cluster::get_cached_online_query_nodes()
+        .await
+        .unwrap_or_default();
+    nodes.sort_by(|a, b| a.grpc_addr.cmp(&b.grpc_addr));
+    nodes.dedup_by(|a, b| a.grpc_addr == b.grpc_addr);
+    nodes
+}
+
+async fn node_client(
+    node: &Node,
+) -> Result<
+    cluster_rpc::search_client::SearchClient<
+        tonic::codegen::InterceptedService<Channel, impl Interceptor>,
+    >,
+    Error,
+> {
+    let cfg = get_config();
+    let token: MetadataValue<_> = infra_cluster::get_internal_grpc_token()
+        .parse()
+        .map_err(|_| Error::Message("invalid token".to_string()))?;
+    let grpc_version: MetadataValue<_> = node
+        .negotiate_grpc_version()
+        .unwrap_or(GRPC_MIN_VERSION)
+        .into();
+    let channel = tls::grpc_endpoint(node.grpc_addr.clone())
+        .map_err(|e| Error::Message(e.to_string()))?
+        .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
+        .connect()
+        .await
+        .map_err(|e| Error::Message(format!("connect node {} error: {}", node.grpc_addr, e)))?;
+    let client = cluster_rpc::search_client::SearchClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            req.metadata_mut()
+                .insert(GRPC_VERSION_HEADER, grpc_version.clone());
+            Ok(req)
+        },
+    );
+    Ok(client
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(cfg.grpc.max_message_size * 1024 * 1024)
+        .max_encoding_message_size(cfg.grpc.max_message_size * 1024 * 1024))
+}
+
+/// Sums the queries reported by the nodes per trace_id, oldest first.
+fn merge_queries(queries: Vec<cluster_rpc::RunningQuery>) -> Vec<search::RunningQuery> {
+    let mut merged: HashMap<String, search::RunningQuery> = HashMap::new();
+    for query in queries {
+        let entry = merged
+            .entry(query.trace_id.clone())
+            .or_insert_with(|| search::RunningQuery {
+                trace_id: query.trace_id.clone(),
+                org_id: query.org_id.clone(),
+                stream_type: query.stream_type.clone(),
+                sql: query.sql.clone(),
+                started_at: query.started_at,
+                ..Default::default()
+            });
+        entry.started_at = entry.started_at.min(query.started_at);
+        if entry.user_id.is_none() {
+            entry.user_id = query.user_id;
+        }
+        entry.memory_size += query.memory_size;
+        entry.cpu_time += query.cpu_time;
+        entry.nodes += 1;
+        entry.is_cancelled |= query.is_cancelled;
+    }
+    let mut merged = merged.into_values().collect::<Vec<_>>();
+    merged.sort_by(|a, b| (a.started_at, &a.trace_id).cmp(&(b.started_at, &b.trace_id)));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_session_of() {
+        assert!(is_session_of("abc", "abc"));
+        assert!(is_session_of("abc-0", "abc"));
+        assert!(is_session_of("abc-wal-1", "abc"));
+        assert!(!is_session_of("abcd-0", "abc"));
+        assert!(!is_session_of("ab", "abc"));
+    }
+
+    #[test]
+    fn test_register_and_cancel() {
+        let guard = register("test_tracker_trace", "org1", "logs", None, "select 1");
+        let guard2 = register(
+            "test_tracker_trace",
+            "org1",
+            "logs",
+            Some("root@example.com".to_string()),
+            "select 1",
+        );
+        let queries = local_queries("org1");
+        let query = queries
+            .iter()
+            .find(|q| q.trace_id == "test_tracker_trace")
+            .unwrap();
+        assert_eq!(query.user_id.as_deref(), Some("root@example.com"));
+        assert!(!is_cancelled("test_tracker_trace-1"));
+        assert!(!cancel_local("org2", "test_tracker_trace"));
+        assert!(cancel_local("org1", "test_tracker_trace"));
+        assert!(is_cancelled("test_tracker_trace-1"));
+
+        drop(guard);
+        assert!(is_cancelled("test_tracker_trace"));
+        drop(guard2);
+        assert!(!is_cancelled("test_tracker_trace"));
+        assert!(!cancel_local("org1", "test_tracker_trace"));
+    }
+
+    #[test]
+    fn test_merge_queries() {
+        let query = |trace_id: &str, started_at, user_id: Option<&str>| cluster_rpc::RunningQuery {
+            trace_id: trace_id.to_string(),
+            org_id: "org1".to_string(),
+            stream_type: "logs".to_string(),
+            user_id: user_id.map(|v| v.to_string()),
+            sql: "select 1".to_string(),
+            started_at,
+            memory_size: 100,
+            cpu_time: 10,
+            is_cancelled: false,
+        };
+        let merged = merge_queries(vec![
+            query("b", 20, None),
+            query("a", 30, None),
+            query("a", 25, Some("root@example.com")),
+        ]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].trace_id, "b");
+        assert_eq!(merged[1].trace_id, "a");
+        assert_eq!(merged[1].started_at, 25);
+        assert_eq!(merged[1].memory_size, 200);
+        assert_eq!(merged[1].cpu_time, 20);
+        assert_eq!(merged[1].nodes, 2);
+        assert_eq!(merged[1].user_id.as_deref(), Some("root@example.com"));
+    }
+}
@@ -0,0 +1,397 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Search templates are SQL queries saved with named parameters. A run binds
+//! the values of the parameters as SQL literals checked against their types,
+//! the text of the query never comes from the caller.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::Error,
+};
+
+use actix_web::{http::StatusCode, HttpResponse};
+use config::{
+    get_config,
+    meta::{
+        search::{self, SearchEventType},
+        sql::Sql,
+    },
+    utils::json::{self, Value},
+};
+
+use crate::{
+    common::meta::{
+        http::HttpResponse as MetaHttpResponse,
+        search_templates::{
+            SearchTemplate, SearchTemplateList, SearchTemplateParam, SearchTemplateParamType,
+            SearchTemplateRunRequest,
+        },
+    },
+    service::{db, rbac},
+};
+
+pub(crate) const SEARCH_TEMPLATE_NOT_FOUND: &str = "Search template not found";
+
+pub async fn save(
+    org_id: &str,
+    name: &str,
+    mut template: SearchTemplate,
+    create: bool,
+) -> Result<HttpResponse, Error> {
+    template.name = name.trim().to_string();
+    if !rbac::is_valid_role_name(&template.name) {
+        return Ok(MetaHttpResponse::bad_request(
+            "Search template name can only contain alphanumeric characters, '_' and '-'",
+        ));
+    }
+    if let Err(e) = validate(&template) {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "Invalid search template: {e}"
+        )));
+    }
+
+    let exists = db::search_templates::get(org_id, &template.name)
+        .await
+        .is_ok();
+    match (create, exists) {
+        (true, true) => {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "Search template {} already exists",
+                template.name
+            )));
+        }
+        (false, false) => return Ok(MetaHttpResponse::not_found(SEARCH_TEMPLATE_NOT_FOUND)),
+        _ => {}
+    }
+
+    match db::search_templates::set(org_id, &template).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(template)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    match db::search_templates::get(org_id, name).await {
+        Ok(template) => Ok(HttpResponse::Ok().json(template)),
+        Err(_) => Ok(MetaHttpResponse::not_found(SEARCH_TEMPLATE_NOT_FOUND)),
+    }
+}
+
+pub async fn list(org_id: &str) -> Result<HttpResponse, Error> {
+    match db::search_templates::list(org_id).await {
+        Ok(list) => Ok(HttpResponse::Ok().json(SearchTemplateList { list })),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    if db::search_templates::get(org_id, name).await.is_err() {
+        return Ok(MetaHttpResponse::not_found(SEARCH_TEMPLATE_NOT_FOUND));
+    }
+    match db::search_templates::delete(org_id, name).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "Search template deleted".to_string(),
+        ))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Builds the search request of a run of the template.
+pub fn bind_request(
+    template: &SearchTemplate,
+    run: &SearchTemplateRunRequest,
+) -> Result<search::Request, anyhow::Error> {
+    if run.start_time >= run.end_time {
+        return Err(anyhow::anyhow!("start_time has to be before end_time"));
+    }
+    let sql = bind(&template.sql, &template.params, &run.params)?;
+    Ok(search::Request {
+        query: search::Query {
+            sql,
+            from: run.from,
+            size: run.size.unwrap_or(get_config().limit.query_default_limit),
+            start_time: run.start_time,
+            end_time: run.end_time,
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: Some(SearchEventType::Other),
+    })
+}
+
+/// Checks the parameters and their defaults, and that the query parses once
+/// every parameter is bound.
+fn validate(template: &SearchTemplate) -> Result<(), anyhow::Error> {
+    let mut names = HashSet::new();
+    for param in template.params.iter() {
+        if !is_param_name(&param.name) {
+            return Err(anyhow::anyhow!(
+                "parameter name [{}] can only contain alphanumeric characters and '_'",
+                param.name
+            ));
+        }
+        if !names.insert(param.name.as_str()) {
+            return Err(anyhow::anyhow!("parameter [{}] is repeated", param.name));
+        }
+        if let Some(default) = param.default.as_ref() {
+            literal(param, default)?;
+        }
+    }
+    let samples = template
+        .params
+        .iter()
+        .map(|param| (param.name.clone(), sample(param.param_type)))
+        .collect::<HashMap<_, _>>();
+    let sql = bind(&template.sql, &template.params, &samples)?;
+    Sql::new(&sql)?;
+    Ok(())
+}
+
+/// Replaces the `:name` parameters of the query by the SQL literals of their
+/// values. The quoted strings and identifiers, the comments and the `::`
+/// casts of the query are left as is.
+fn bind(
+    sql: &str,
+    params: &[SearchTemplateParam],
+    values: &HashMap<String, Value>,
+) -> Result<String, anyhow::Error> {
+    if let Some(name) = values
+        .keys()
+        .find(|name| !params.iter().any(|p| &p.name == *name))
+    {
+        return Err(anyhow::anyhow!("parameter [{name}] is not defined"));
+    }
+
+    let chars = sql.chars().collect::<Vec<_>>();
+    let mut bound = String::with_capacity(sql.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let end = match (chars[i], chars.get(i + 1)) {
+            (c @ ('\'' | '"' | '`'), _) => quoted_end(&chars, i, c),
+            ('-', Some('-')) => find_end(&chars, i + 2, "\n"),
+            ('/', Some('*')) => find_end(&chars, i + 2, "*/"),
+            (':', Some(':')) => i + 2,
+            (':', Some(c))
+                if (c.is_ascii_alphabetic() || *c == '_')
+                    && !(i > 0 && is_word_char(chars[i - 1])) =>
+            {
+                let mut end = i + 1;
+                while end < chars.len() && is_word_char(chars[end]) {
+                    end += 1;
+                }
+                let name = chars[i + 1..end].iter().collect::<String>();
+                let Some(param) = params.iter().find(|p| p.name == name) else {
+                    return Err(anyhow::anyhow!("parameter [{name}] is not defined"));
+                };
+                let value = match values.get(&name).or(param.default.as_ref()) {
+                    Some(value) => value,
+                    None => return Err(anyhow::anyhow!("parameter [{name}] is required")),
+                };
+                bound.push_str(&literal(param, value)?);
+                i = end;
+                continue;
+            }
+            _ => i + 1,
+        };
+        bound.extend(&chars[i..end]);
+        i = end;
+    }
+    Ok(bound)
+}
+
+/// The SQL literal of the value of a parameter.
+fn literal(param: &SearchTemplateParam, value: &Value) -> Result<String, anyhow::Error> {
+    let name = &param.name;
+    let literal = match param.param_type {
+        SearchTemplateParamType::String => value.as_str().map(|v| quote(name, v)).transpose()?,
+        SearchTemplateParamType::Int => value.as_i64().map(|v| signed(v.to_string())),
+        SearchTemplateParamType::Float => value
+            .as_f64()
+            .filter(|v| v.is_finite())
+            .map(|v| signed(v.to_string())),
+        SearchTemplateParamType::Bool => value.as_bool().map(|v| v.to_string()),
+        SearchTemplateParamType::StringList => match value.as_array() {
+            Some(list) if !list.is_empty() && list.iter().all(|v| v.is_string()) => Some(
+                list.iter()
+                    .map(|v| quote(name, v.as_str().unwrap()))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(", "),
+            ),
+            _ => None,
+        },
+    };
+    literal.ok_or_else(|| {
+        anyhow::anyhow!(
+            "parameter [{name}] has to be a {}, got {}",
+            param.param_type,
+            json::to_string(value).unwrap_or_default()
+        )
+    })
+}
+
+/// Quotes a string, the single quotes are doubled. The backslashes are
+/// rejected as some dialects read them as escapes.
+fn quote(name: &str, value: &str) -> Result<String, anyhow::Error> {
+    if value.contains('\\') {
+        return Err(anyhow::anyhow!(
+            "parameter [{name}] can not contain a backslash"
+        ));
+    }
+    Ok(format!("'{}'", value.replace('\'', "''")))
+}
+
+/// Wraps the negative numbers, `a -:n` must not become the comment `a --1`.
+fn signed(value: String) -> String {
+    if value.starts_with('-') {
+        format!("({value})")
+    } else {
+        value
+    }
+}
+
+fn sample(param_type: SearchTemplateParamType) -> Value {
+    match param_type {
+        SearchTemplateParamType::String => Value::from(""),
+        SearchTemplateParamType::Int => Value::from(0),
+        SearchTemplateParamType::Float => Value::from(0.0),
+        SearchTemplateParamType::Bool => Value::from(true),
+        SearchTemplateParamType::StringList => Value::from(vec![""]),
+    }
+}
+
+/// End of the text quoted at `start`, a doubled quote is part of the text.
+fn quoted_end(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// End of the first `pattern` from `start`, or of the query.
+fn find_end(chars: &[char], start: usize, pattern: &str) -> usize {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    (start..chars.len())
+        .find(|&i| chars[i..].starts_with(&pattern))
+        .map(|i| i + pattern.len())
+        .unwrap_or(chars.len())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_param_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(is_word_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, param_type: SearchTemplateParamType) -> SearchTemplateParam {
+        SearchTemplateParam {
+            name: name.to_string(),
+            param_type,
+            default: None,
+        }
+    }
+
+    fn values(items: &[(&str, Value)]) -> HashMap<String, Value> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_bind() {
+        let params = vec![
+            param("host", SearchTemplateParamType::String),
+            param("code", SearchTemplateParamType::Int),
+            param("ratio", SearchTemplateParamType::Float),
+            param("envs", SearchTemplateParamType::StringList),
+        ];
+        let sql = "SELECT * FROM t WHERE host = :host AND code > :code \
+                   AND ratio < :ratio AND env IN (:envs)";
+        let bound = bind(
+            sql,
+            &params,
+            &values(&[
+                ("host", Value::from("a' OR '1'='1")),
+                ("code", Value::from(-1)),
+                ("ratio", Value::from(0.5)),
+                ("envs", Value::from(vec!["prod", "dev"])),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            bound,
+            "SELECT * FROM t WHERE host = 'a'' OR ''1''=''1' AND code > (-1) \
+             AND ratio < 0.5 AND env IN ('prod', 'dev')"
+        );
+    }
+
+    #[test]
+    fn test_bind_skips_quotes_and_casts() {
+        let params = vec![param("v", SearchTemplateParamType::Int)];
+        let sql = "SELECT ':v', \":v\", a::int -- :v\nFROM t WHERE b = :v /* :v */";
+        let bound = bind(sql, &params, &values(&[("v", Value::from(3))])).unwrap();
+        assert_eq!(
+            bound,
+            "SELECT ':v', \":v\", a::int -- :v\nFROM t WHERE b = 3 /* :v */"
+        );
+    }
+
+    #[test]
+    fn test_bind_errors() {
+        let mut params = vec![param("host", SearchTemplateParamType::String)];
+        let sql = "SELECT * FROM t WHERE host = :host";
+        assert!(bind(sql, &params, &HashMap::new()).is_err());
+        assert!(bind(sql, &params, &values(&[("host", Value::from(1))])).is_err());
+        assert!(bind(sql, &params, &values(&[("host", Value::from("a\\"))])).is_err());
+        assert!(bind(sql, &params, &values(&[("other", Value::from("a"))])).is_err());
+        assert!(bind("SELECT :missing", &params, &HashMap::new()).is_err());
+
+        params[0].default = Some(Value::from("web"));
+        assert_eq!(
+            bind(sql, &params, &HashMap::new()).unwrap(),
+            "SELECT * FROM t WHERE host = 'web'"
+        );
+    }
+
+    #[test]
+    fn test_is_param_name() {
+        assert!(is_param_name("host_1"));
+        assert!(is_param_name("_host"));
+        assert!(!is_param_name("1host"));
+        assert!(!is_param_name("host-name"));
+        assert!(!is_param_name(""));
+    }
+}
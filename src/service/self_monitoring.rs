@@ -0,0 +1,545 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Self monitoring: each node writes its load and the latencies of its
+//! endpoints into streams of the usage org, where a default dashboard, and
+//! optionally default alerts, watch over the cluster.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use config::{
+    get_config,
+    meta::stream::StreamType,
+    metrics,
+    utils::json::{self, json},
+};
+use hashbrown::HashMap;
+use prometheus::{core::Collector, proto::MetricFamily};
+use proto::cluster_rpc;
+
+use super::{bundles, node::MetricsCollector, usage::ingestion_service};
+use crate::common::{
+    infra::cluster::leader,
+    meta::{
+        alerts::{Alert, Operator, QueryCondition, QueryType, TriggerCondition},
+        bundles::{Bundle, BundleDashboard, ConflictStrategy, ImportAction, BUNDLE_VERSION},
+        dashboards::Folder,
+    },
+};
+
+pub const NODE_METRICS_STREAM: &str = "node_metrics";
+pub const ENDPOINT_LATENCY_STREAM: &str = "endpoint_latency";
+
+const FOLDER_ID: &str = "self_monitoring";
+const DASHBOARD_ID: &str = "self_monitoring_overview";
+
+// true once the default dashboard and alerts are in place
+static PROVISIONED: AtomicBool = AtomicBool::new(false);
+
+/// Requests of an endpoint, the buckets are cumulative counts by upper bound
+/// in seconds, as prometheus keeps them.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct LatencySample {
+    count: u64,
+    sum: f64,
+    errors: u64,
+    buckets: Vec<(f64, u64)>,
+}
+
+impl LatencySample {
+    fn add(&mut self, count: u64, sum: f64, is_error: bool, buckets: &[(f64, u64)]) {
+        self.count += count;
+        self.sum += sum;
+        if is_error {
+            self.errors += count;
+        }
+        if self.buckets.is_empty() {
+            self.buckets = buckets.to_vec();
+        } else {
+            for (bucket, (_, v)) in self.buckets.iter_mut().zip(buckets) {
+                bucket.1 += v;
+            }
+        }
+    }
+
+    /// The requests since `prev`, all of them when the counters were reset.
+    fn since(&self, prev: Option<&LatencySample>) -> LatencySample {
+        let Some(prev) = prev.filter(|p| p.count <= self.count) else {
+            return self.clone();
+        };
+        LatencySample {
+            count: self.count - prev.count,
+            sum: (self.sum - prev.sum).max(0.0),
+            errors: self.errors.saturating_sub(prev.errors),
+            buckets: self
+                .buckets
+                .iter()
+                .zip(prev.buckets.iter().chain(std::iter::repeat(&(0.0, 0))))
+                .map(|((bound, v), (_, p))| (*bound, v.saturating_sub(*p)))
+                .collect(),
+        }
+    }
+
+    /// Estimates the quantile in seconds by linear interpolation inside its
+    /// bucket, the way prometheus' `histogram_quantile` does.
+    fn quantile(&self, q: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let rank = q * self.count as f64;
+        let mut lower = (0.0, 0);
+        for (bound, count) in self.buckets.iter() {
+            if *count as f64 >= rank {
+                let in_bucket = (*count - lower.1) as f64;
+                if in_bucket == 0.0 {
+                    return *bound;
+                }
+                return lower.0 + (bound - lower.0) * (rank - lower.1 as f64) / in_bucket;
+            }
+            lower = (*bound, *count);
+        }
+        // beyond the last bucket, the best guess is its bound
+        lower.0
+    }
+}
+
+/// Sums up the histograms of the families by endpoint, over the
+/// organizations and streams.
+fn latency_samples(kind: &str, families: &[MetricFamily]) -> HashMap<String, LatencySample> {
+    let mut samples: HashMap<String, LatencySample> = HashMap::new();
+    for family in families {
+        for metric in family.get_metric() {
+            let mut endpoint = "";
+            let mut status = "";
+            for label in metric.get_label() {
+                match label.get_name() {
+                    "endpoint" => endpoint = label.get_value(),
+                    "status" => status = label.get_value(),
+                    _ => {}
+                }
+            }
+            let histogram = metric.get_histogram();
+            let buckets = histogram
+                .get_bucket()
+                .iter()
+                .map(|b| (b.get_upper_bound(), b.get_cumulative_count()))
+                .collect::<Vec<_>>();
+            samples
+                .entry(format!("{kind} {endpoint}"))
+                .or_default()
+                .add(
+                    histogram.get_sample_count(),
+                    histogram.get_sample_sum(),
+                    status.starts_with('5'),
+                    &buckets,
+                );
+        }
+    }
+    samples
+}
+
+/// Samples the local node into the records of the self monitoring streams.
+struct Sampler {
+    node: MetricsCollector,
+    latencies: HashMap<String, LatencySample>,
+}
+
+impl Sampler {
+    fn new() -> Self {
+        Self {
+            node: MetricsCollector::new(),
+            latencies: HashMap::new(),
+        }
+    }
+
+    fn node_record(&mut self, timestamp: i64) -> json::Value {
+        let cfg = get_config();
+        let mut record = json::to_value(self.node.collect()).unwrap();
+        record["node"] = cfg.common.instance_name.clone().into();
+        record["role"] = cfg.common.node_role.clone().into();
+        record["wal_memory_bytes"] = metrics::INGEST_MEMTABLE_BYTES
+            .with_label_values(&[])
+            .get()
+            .into();
+        record[cfg.common.column_timestamp.as_str()] = timestamp.into();
+        record
+    }
+
+    /// One record by endpoint which served requests since the previous
+    /// sample, the latencies are in milliseconds.
+    fn latency_records(&mut self, timestamp: i64) -> Vec<json::Value> {
+        let cfg = get_config();
+        let mut current = latency_samples("http", &metrics::HTTP_RESPONSE_TIME.collect());
+        current.extend(latency_samples(
+            "grpc",
+            &metrics::GRPC_RESPONSE_TIME.collect(),
+        ));
+        let mut records = Vec::new();
+        for (key, sample) in current.iter() {
+            let delta = sample.since(self.latencies.get(key));
+            if delta.count == 0 {
+                continue;
+            }
+            let (kind, endpoint) = key.split_once(' ').unwrap_or(("", key));
+            records.push(json!({
+                cfg.common.column_timestamp.as_str(): timestamp,
+                "node": cfg.common.instance_name,
+                "kind": kind,
+                "endpoint": endpoint,
+                "requests": delta.count,
+                "errors": delta.errors,
+                "avg_ms": delta.sum * 1000.0 / delta.count as f64,
+                "p50_ms": delta.quantile(0.5) * 1000.0,
+                "p95_ms": delta.quantile(0.95) * 1000.0,
+                "p99_ms": delta.quantile(0.99) * 1000.0,
+            }));
+        }
+        self.latencies = current;
+        records
+    }
+}
+
+async fn write(stream_name: &str, data: Vec<json::Value>) {
+    if data.is_empty() {
+        return;
+    }
+    let req = cluster_rpc::UsageRequest {
+        stream_name: stream_name.to_owned(),
+        data: Some(cluster_rpc::UsageData::from(data)),
+    };
+    if let Err(e) = ingestion_service::ingest(&get_config().common.usage_org, req).await {
+        log::error!("[SELF_MONITORING] write {stream_name} failed: {e}");
+    }
+}
+
+pub async fn run() {
+    let cfg = get_config();
+    if !cfg.common.self_monitoring_enabled {
+        return;
+    }
+    leader::campaign(leader::SELF_MONITORING);
+    let mut sampler = Sampler::new();
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+        cfg.common.self_monitoring_interval,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        let now = chrono::Utc::now().timestamp_micros();
+        let node = sampler.node_record(now);
+        write(NODE_METRICS_STREAM, vec![node]).await;
+        let latencies = sampler.latency_records(now);
+        write(ENDPOINT_LATENCY_STREAM, latencies).await;
+        // the alerts need the streams, so they are provisioned once written
+        if !PROVISIONED.load(Ordering::Relaxed) && leader::is_leader(leader::SELF_MONITORING) {
+            provision().await;
+        }
+    }
+}
+
+/// Imports the default dashboard and alerts into the usage org, the ones
+/// which exist already are left as they are.
+async fn provision() {
+    let cfg = get_config();
+    let report = bundles::import(
+        &cfg.common.usage_org,
+        default_bundle(&cfg.common.self_monitoring_alert_destination),
+        ConflictStrategy::Skip,
+    )
+    .await;
+    let failed = report
+        .results
+        .iter()
+        .filter(|r| r.action == ImportAction::Failed)
+        .collect::<Vec<_>>();
+    if failed.is_empty() {
+        PROVISIONED.store(true, Ordering::Relaxed);
+        return;
+    }
+    // retried on the next run, a stream may not have received data yet
+    for r in failed {
+        log::warn!(
+            "[SELF_MONITORING] provision {} {} failed: {}",
+            r.kind,
+            r.name,
+            r.error.as_deref().unwrap_or_default()
+        );
+    }
+}
+
+fn default_bundle(destination: &str) -> Bundle {
+    let alerts = if destination.is_empty() {
+        vec![]
+    } else {
+        default_alerts(destination)
+    };
+    Bundle {
+        version: BUNDLE_VERSION,
+        folders: vec![Folder {
+            folder_id: FOLDER_ID.to_string(),
+            name: "Self monitoring".to_string(),
+            description: "Dashboards of the OpenObserve cluster itself".to_string(),
+            parent_id: None,
+        }],
+        dashboards: vec![BundleDashboard {
+            folder_id: FOLDER_ID.to_string(),
+            dashboard: default_dashboard(),
+        }],
+        alerts,
+        ..Default::default()
+    }
+}
+
+fn default_alerts(destination: &str) -> Vec<Alert> {
+    let alert = |name: &str, stream: &str, description: &str, sql: String| Alert {
+        name: name.to_string(),
+        stream_type: StreamType::Logs,
+        stream_name: stream.to_string(),
+        query_condition: QueryCondition {
+            query_type: QueryType::SQL,
+            sql: Some(sql),
+            ..Default::default()
+        },
+        trigger_condition: TriggerCondition {
+            period: 10,
+            operator: Operator::GreaterThanEquals,
+            threshold: 1,
+            frequency: 5,
+            silence: 30,
+            ..Default::default()
+        },
+        destinations: vec![destination.to_string()],
+        description: description.to_string(),
+        enabled: true,
+        ..Default::default()
+    };
+    vec![
+        alert(
+            "node_high_cpu",
+            NODE_METRICS_STREAM,
+            "A node used more than 90% of its cpu",
+            format!(
+                "SELECT node, max(cpu_usage) AS cpu_usage FROM \"{NODE_METRICS_STREAM}\" GROUP BY node HAVING max(cpu_usage) > 90"
+            ),
+        ),
+        alert(
+            "node_high_memory",
+            NODE_METRICS_STREAM,
+            "A node used more than 90% of its memory",
+            format!(
+                "SELECT node, max(memory_usage * 100 / memory_total) AS memory_usage FROM \"{NODE_METRICS_STREAM}\" WHERE memory_total > 0 GROUP BY node HAVING max(memory_usage * 100 / memory_total) > 90"
+            ),
+        ),
+        alert(
+            "endpoint_errors",
+            ENDPOINT_LATENCY_STREAM,
+            "More than 5% of the requests of an endpoint failed",
+            format!(
+                "SELECT endpoint, sum(errors) AS errors, sum(requests) AS requests FROM \"{ENDPOINT_LATENCY_STREAM}\" GROUP BY endpoint HAVING sum(errors) * 20 > sum(requests)"
+            ),
+        ),
+    ]
+}
+
+fn default_dashboard() -> json::Value {
+    let ts = get_config().common.column_timestamp.clone();
+    let panel = |i: i64, title: &str, stream: &str, y: &[(&str, &str)], by: &str| {
+        let columns = y
+            .iter()
+            .map(|(expr, alias)| format!("{expr} AS \"{alias}\""))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            "SELECT histogram({ts}) AS \"x_axis_1\", {by} AS \"breakdown_1\", {columns} FROM \"{stream}\" GROUP BY x_axis_1, breakdown_1 ORDER BY x_axis_1"
+        );
+        json!({
+            "id": format!("panel_{i}"),
+            "type": "line",
+            "title": title,
+            "description": "",
+            "config": {"show_legends": true, "legends_position": null},
+            "queryType": "sql",
+            "queries": [{
+                "query": sql,
+                "customQuery": true,
+                "fields": {
+                    "stream": stream,
+                    "stream_type": "logs",
+                    "x": [{"label": "Time", "alias": "x_axis_1", "column": ts, "color": null}],
+                    "y": y.iter().map(|(_, alias)| json!({
+                        "label": alias,
+                        "alias": alias,
+                        "column": alias,
+                        "color": null,
+                    })).collect::<Vec<_>>(),
+                    "z": [{"label": by, "alias": "breakdown_1", "column": by, "color": null}],
+                    "filter": [],
+                },
+                "config": {"promql_legend": ""},
+            }],
+            "layout": {"x": (i % 2) * 24, "y": (i / 2) * 9, "w": 24, "h": 9, "i": i},
+        })
+    };
+    let panels = vec![
+        panel(
+            0,
+            "CPU usage (%)",
+            NODE_METRICS_STREAM,
+            &[("avg(cpu_usage)", "cpu_usage")],
+            "node",
+        ),
+        panel(
+            1,
+            "Memory usage (bytes)",
+            NODE_METRICS_STREAM,
+            &[("max(memory_usage)", "memory_usage")],
+            "node",
+        ),
+        panel(
+            2,
+            "WAL backlog (bytes)",
+            NODE_METRICS_STREAM,
+            &[("max(wal_backlog_bytes)", "wal_backlog_bytes")],
+            "node",
+        ),
+        panel(
+            3,
+            "Cache hit rate",
+            NODE_METRICS_STREAM,
+            &[("avg(cache_hit_rate)", "cache_hit_rate")],
+            "node",
+        ),
+        panel(
+            4,
+            "Requests",
+            ENDPOINT_LATENCY_STREAM,
+            &[("sum(requests)", "requests")],
+            "endpoint",
+        ),
+        panel(
+            5,
+            "Errors",
+            ENDPOINT_LATENCY_STREAM,
+            &[("sum(errors)", "errors")],
+            "endpoint",
+        ),
+        panel(
+            6,
+            "p95 latency (ms)",
+            ENDPOINT_LATENCY_STREAM,
+            &[("max(p95_ms)", "p95_ms")],
+            "endpoint",
+        ),
+        panel(
+            7,
+            "p99 latency (ms)",
+            ENDPOINT_LATENCY_STREAM,
+            &[("max(p99_ms)", "p99_ms")],
+            "endpoint",
+        ),
+    ];
+    json!({
+        "version": 3,
+        "dashboardId": DASHBOARD_ID,
+        "title": "OpenObserve self monitoring",
+        "description": "Load of the nodes and latencies of the endpoints",
+        "tabs": [{
+            "tabId": "default",
+            "name": "Default",
+            "panels": panels,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::{HistogramOpts, HistogramVec};
+
+    use super::*;
+    use crate::common::meta::dashboards::v3;
+
+    #[test]
+    fn test_latency_samples() {
+        let histogram = HistogramVec::new(
+            HistogramOpts::new("test_latency", "test").buckets(vec![0.1, 1.0]),
+            &["endpoint", "status", "organization"],
+        )
+        .unwrap();
+        for (org, status, time) in [("a", "200", 0.05), ("b", "200", 0.5), ("b", "500", 2.0)] {
+            histogram
+                .with_label_values(&["/search", status, org])
+                .observe(time);
+        }
+        let samples = latency_samples("http", &histogram.collect());
+        let sample = samples.get("http /search").unwrap();
+        assert_eq!(sample.count, 3);
+        assert_eq!(sample.errors, 1);
+        assert_eq!(sample.buckets, vec![(0.1, 1), (1.0, 2)]);
+    }
+
+    #[test]
+    fn test_latency_since() {
+        let prev = LatencySample {
+            count: 10,
+            sum: 1.0,
+            errors: 1,
+            buckets: vec![(0.1, 8), (1.0, 10)],
+        };
+        let cur = LatencySample {
+            count: 30,
+            sum: 5.0,
+            errors: 2,
+            buckets: vec![(0.1, 18), (1.0, 30)],
+        };
+        let delta = cur.since(Some(&prev));
+        assert_eq!(delta.count, 20);
+        assert_eq!(delta.errors, 1);
+        assert_eq!(delta.buckets, vec![(0.1, 10), (1.0, 20)]);
+        // the counters of a restarted node start over
+        assert_eq!(prev.since(Some(&cur)), prev);
+        assert_eq!(cur.since(None), cur);
+    }
+
+    #[test]
+    fn test_latency_quantile() {
+        let sample = LatencySample {
+            count: 100,
+            sum: 10.0,
+            errors: 0,
+            buckets: vec![(0.1, 50), (0.5, 90), (1.0, 100)],
+        };
+        assert!((sample.quantile(0.5) - 0.1).abs() < 1e-9);
+        assert!((sample.quantile(0.7) - 0.3).abs() < 1e-9);
+        assert!((sample.quantile(0.95) - 0.75).abs() < 1e-9);
+        assert_eq!(LatencySample::default().quantile(0.99), 0.0);
+        // requests slower than the last bucket
+        let slow = LatencySample {
+            count: 10,
+            buckets: vec![(0.1, 1)],
+            ..Default::default()
+        };
+        assert_eq!(slow.quantile(0.99), 0.1);
+    }
+
+    #[test]
+    fn test_default_bundle() {
+        let bundle = default_bundle("");
+        assert!(bundle.alerts.is_empty());
+        assert_eq!(default_bundle("ops").alerts.len(), 3);
+        let dashboard: v3::Dashboard =
+            json::from_value(bundle.dashboards[0].dashboard.clone()).unwrap();
+        assert_eq!(dashboard.tabs[0].panels.len(), 8);
+    }
+}
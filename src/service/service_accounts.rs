@@ -0,0 +1,315 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{io::Error, net::IpAddr};
+
+use actix_web::HttpResponse;
+use config::{get_config, ider, utils::rand::generate_random_string};
+
+use crate::{
+    common::{
+        infra::config::SERVICE_ACCOUNTS,
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            rbac::{Permission, Resource},
+            service_account::{
+                get_principal, is_valid_ip_rule, parse_token, ApiToken, ApiTokenRequest,
+                ApiTokenResponse, RotateTokenRequest, ServiceAccount, ServiceAccountRequest,
+                TokenScope, TOKEN_PREFIX,
+            },
+        },
+    },
+    service::{db, rbac},
+};
+
+const SA_NOT_FOUND: &str = "Service account not found";
+const SA_ALREADY_EXISTS: &str = "Service account already exists";
+const SA_INVALID_NAME: &str =
+    "Service account name can only contain alphanumeric characters, '_' and '-'";
+const TOKEN_NOT_FOUND: &str = "Token not found";
+
+/// Validates an api token of the org, returns the principal the request is
+/// authenticated as.
+pub fn validate_token(
+    org_id: &str,
+    token: &str,
+    ip: Option<IpAddr>,
+) -> Result<String, &'static str> {
+    let Some((token_id, secret)) = parse_token(token) else {
+        return Err("Invalid token");
+    };
+    let Some((name, api_token)) = find_token(org_id, token_id) else {
+        return Err("Invalid token");
+    };
+    if api_token.hash != sha256::digest(secret) {
+        return Err("Invalid token");
+    }
+    if api_token.is_expired(chrono::Utc::now().timestamp_micros()) {
+        return Err("Token expired");
+    }
+    if !api_token.is_ip_allowed(ip) {
+        return Err("Client ip is not allowed");
+    }
+    Ok(get_principal(&name, token_id))
+}
+
+/// Checks the scope of a token for a request, `path` is relative to `/api/`.
+pub fn is_request_allowed(org_id: &str, principal: &str, method: &str, path: &str) -> bool {
+    let Some((name, token_id)) = crate::common::meta::service_account::parse_principal(principal)
+    else {
+        return false;
+    };
+    match rbac::get_permission_for_path(method, path) {
+        Some((permission, resource, obj)) => {
+            is_token_allowed(org_id, name, token_id, resource, &obj, permission)
+        }
+        // not mapped to a resource, streams are checked again by the handlers
+        None => {
+            let Some((_, token)) = find_token(org_id, token_id) else {
+                return false;
+            };
+            let last = path
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or_default();
+            match token.scope {
+                TokenScope::Ingest => last.eq("_bulk"),
+                TokenScope::Read => method.eq("GET") || last.starts_with("_search"),
+                TokenScope::Scim => false,
+            }
+        }
+    }
+}
+
+/// Whether the searches of the service account skip the row filters, it is
+/// set on the account when it is created.
+pub fn bypasses_row_filters(org_id: &str, name: &str) -> bool {
+    get_account(org_id, name).is_some_and(|account| account.bypass_row_filters)
+}
+
+pub fn is_token_allowed(
+    org_id: &str,
+    name: &str,
+    token_id: &str,
+    resource: Resource,
+    obj: &str,
+    permission: Permission,
+) -> bool {
+    match SERVICE_ACCOUNTS.get(&format!("{org_id}/{name}")) {
+        Some(account) => account
+            .tokens
+            .iter()
+            .find(|t| t.id.eq(token_id))
+            .map(|t| {
+                !t.is_expired(chrono::Utc::now().timestamp_micros())
+                    && t.is_allowed(resource, obj, permission)
+            })
+            .unwrap_or_default(),
+        None => false,
+    }
+}
+
+fn find_token(org_id: &str, token_id: &str) -> Option<(String, ApiToken)> {
+    let prefix = format!("{org_id}/");
+    SERVICE_ACCOUNTS
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix))
+        .find_map(|v| {
+            v.value()
+                .tokens
+                .iter()
+                .find(|t| t.id.eq(token_id))
+                .map(|t| (v.value().name.clone(), t.clone()))
+        })
+}
+
+fn get_account(org_id: &str, name: &str) -> Option<ServiceAccount> {
+    SERVICE_ACCOUNTS
+        .get(&format!("{org_id}/{name}"))
+        .map(|v| v.value().clone())
+}
+
+fn redacted(mut account: ServiceAccount) -> ServiceAccount {
+    account.tokens = account.tokens.iter().map(|t| t.redacted()).collect();
+    account
+}
+
+pub async fn list_service_accounts(org_id: &str) -> Result<HttpResponse, Error> {
+    let mut accounts = match db::service_accounts::list(org_id).await {
+        Ok(v) => v.into_iter().map(redacted).collect::<Vec<_>>(),
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    accounts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(HttpResponse::Ok().json(accounts))
+}
+
+pub async fn create_service_account(
+    org_id: &str,
+    req: ServiceAccountRequest,
+) -> Result<HttpResponse, Error> {
+    let name = req.name.trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Ok(MetaHttpResponse::bad_request(SA_INVALID_NAME));
+    }
+    if get_account(org_id, name).is_some() {
+        return Ok(MetaHttpResponse::bad_request(SA_ALREADY_EXISTS));
+    }
+    let account = ServiceAccount {
+        name: name.to_string(),
+        description: req.description,
+        tokens: vec![],
+        bypass_row_filters: req.bypass_row_filters,
+        created_at: chrono::Utc::now().timestamp_micros(),
+    };
+    match db::service_accounts::set(org_id, &account).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(account)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn delete_service_account(org_id: &str, name: &str) -> Result<HttpResponse, Error> {
+    if get_account(org_id, name).is_none() {
+        return Ok(MetaHttpResponse::not_found(SA_NOT_FOUND));
+    }
+    match db::service_accounts::delete(org_id, name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Service account deleted")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn create_token(
+    org_id: &str,
+    name: &str,
+    req: ApiTokenRequest,
+) -> Result<HttpResponse, Error> {
+    let Some(mut account) = get_account(org_id, name) else {
+        return Ok(MetaHttpResponse::not_found(SA_NOT_FOUND));
+    };
+    if let Some(rule) = req.ip_allowlist.iter().find(|v| !is_valid_ip_rule(v)) {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "Invalid ip allowlist entry: {rule}"
+        )));
+    }
+    let now = chrono::Utc::now().timestamp_micros();
+    let expires_at = match req.expires_in {
+        Some(v) if v <= 0 => {
+            return Ok(MetaHttpResponse::bad_request("expires_in must be positive"));
+        }
+        Some(v) => Some(now + v * 1_000_000),
+        None => None,
+    };
+    let (token, api_token) = new_token(ApiToken {
+        scope: req.scope,
+        streams: req.streams,
+        ip_allowlist: req.ip_allowlist,
+        created_at: now,
+        expires_at,
+        ..Default::default()
+    });
+    account.tokens.push(api_token.clone());
+    match db::service_accounts::set(org_id, &account).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(ApiTokenResponse {
+            token,
+            info: api_token.redacted(),
+        })),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Issues a new token with the same restrictions, the old token stays valid
+/// during the grace period.
+pub async fn rotate_token(
+    org_id: &str,
+    name: &str,
+    token_id: &str,
+    req: RotateTokenRequest,
+) -> Result<HttpResponse, Error> {
+    let Some(mut account) = get_account(org_id, name) else {
+        return Ok(MetaHttpResponse::not_found(SA_NOT_FOUND));
+    };
+    let now = chrono::Utc::now().timestamp_micros();
+    let Some(old) = account
+        .tokens
+        .iter_mut()
+        .find(|t| t.id.eq(token_id) && !t.is_expired(now))
+    else {
+        return Ok(MetaHttpResponse::not_found(TOKEN_NOT_FOUND));
+    };
+    if old.rotated_to.is_some() {
+        return Ok(MetaHttpResponse::bad_request("Token is already rotated"));
+    }
+    let grace_period = req
+        .grace_period
+        .unwrap_or(get_config().auth.api_token_rotation_grace)
+        .max(0);
+
+    // keep the remaining lifetime of the old token for the new one
+    let (token, api_token) = new_token(ApiToken {
+        scope: old.scope,
+        streams: old.streams.clone(),
+        ip_allowlist: old.ip_allowlist.clone(),
+        created_at: now,
+        expires_at: old.expires_at.map(|v| now + (v - old.created_at)),
+        ..Default::default()
+    });
+    let grace_expires_at = now + grace_period * 1_000_000;
+    old.expires_at = Some(match old.expires_at {
+        Some(v) => v.min(grace_expires_at),
+        None => grace_expires_at,
+    });
+    old.rotated_to = Some(api_token.id.clone());
+
+    // drop the tokens whose grace period is over
+    account.tokens.retain(|t| !t.is_expired(now));
+    account.tokens.push(api_token.clone());
+    match db::service_accounts::set(org_id, &account).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(ApiTokenResponse {
+            token,
+            info: api_token.redacted(),
+        })),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+pub async fn delete_token(org_id: &str, name: &str, token_id: &str) -> Result<HttpResponse, Error> {
+    let Some(mut account) = get_account(org_id, name) else {
+        return Ok(MetaHttpResponse::not_found(SA_NOT_FOUND));
+    };
+    let len = account.tokens.len();
+    account.tokens.retain(|t| !t.id.eq(token_id));
+    if account.tokens.len() == len {
+        return Ok(MetaHttpResponse::not_found(TOKEN_NOT_FOUND));
+    }
+    match db::service_accounts::set(org_id, &account).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Token deleted")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+// returns the token and its stored form
+fn new_token(mut api_token: ApiToken) -> (String, ApiToken) {
+    let secret = generate_random_string(32);
+    api_token.id = ider::generate();
+    api_token.hash = sha256::digest(secret.as_str());
+    (
+        format!("{TOKEN_PREFIX}{}_{secret}", api_token.id),
+        api_token,
+    )
+}
@@ -22,7 +22,7 @@ use config::{
     utils::json,
     SIZE_IN_MB, SQL_FULL_TEXT_SEARCH_FIELDS,
 };
-use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::datatypes::{DataType, Schema};
 use infra::{
     cache::stats,
     schema::{
@@ -130,12 +130,18 @@ pub fn stream_res(
     stats: Option<StreamStats>,
 ) -> Stream {
     let storage_type = if is_local_disk_storage() { LOCAL } else { S3 };
+    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
     let mappings = schema
         .fields()
         .iter()
+        .filter(|field| !settings.hidden_fields.contains(field.name()))
         .map(|field| StreamProperty {
             prop_type: field.data_type().to_string(),
-            name: field.name().to_string(),
+            name: settings
+                .alias_of(field.name())
+                .unwrap_or(field.name())
+                .to_string(),
+            logical_type: settings.field_types.get(field.name()).copied(),
         })
         .collect::<Vec<_>>();
 
@@ -164,7 +170,6 @@ pub fn stream_res(
         None
     };
 
-    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
     settings.partition_time_level = Some(unwrap_partition_time_level(
         settings.partition_time_level,
         stream_type,
@@ -207,14 +212,46 @@ pub async fn save_stream_settings(
         }
     }
 
+    if let Some(pattern) = settings.line_pattern.as_ref() {
+        if let Err(e) = crate::service::logs::raw::LinePattern::new(pattern) {
+            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                http::StatusCode::BAD_REQUEST.into(),
+                format!("line pattern is invalid: {e}"),
+            )));
+        }
+    }
+
+    if settings.is_expired(chrono::Utc::now().timestamp_micros()) {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            "expires_at has to be in the future".to_string(),
+        )));
+    }
+
     // we need to keep the old partition information, because the hash bucket num can't be changed
     // get old settings and then update partition_keys
     let schema = infra::schema::get(org_id, stream_name, stream_type)
         .await
         .unwrap();
-    let mut old_partition_keys = unwrap_stream_settings(&schema)
-        .unwrap_or_default()
-        .partition_keys;
+    for field in settings.field_types.keys() {
+        if let Ok(f) = schema.field_with_name(field) {
+            if f.data_type() != &DataType::Utf8 {
+                return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    format!(
+                        "field [{field}] is {}, only string fields have a logical type",
+                        f.data_type()
+                    ),
+                )));
+            }
+        }
+    }
+
+    let old_settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    // the hidden fields and the aliases are changed by their own endpoints
+    settings.hidden_fields = old_settings.hidden_fields;
+    settings.field_aliases = old_settings.field_aliases;
+    let mut old_partition_keys = old_settings.partition_keys;
     // first disable all old partition keys
     for v in old_partition_keys.iter_mut() {
         v.disabled = true;
@@ -321,6 +358,17 @@ pub async fn delete_stream(
         );
     };
 
+    // delete the content hashes of the uploaded files
+    if let Err(e) = db::file_list::dedup::delete_stream(org_id, stream_type, stream_name).await {
+        log::error!(
+            "delete content hashes of stream {}/{}/{} error: {}",
+            org_id,
+            stream_type,
+            stream_name,
+            e
+        );
+    }
+
     crate::common::utils::auth::remove_ownership(
         org_id,
         &stream_type.to_string(),
@@ -372,9 +420,153 @@ pub async fn delete_fields(
     Ok(())
 }
 
+/// Hides the fields of a stream from the schema and the search results, the
+/// data of the fields stays in the files and they can be shown again.
+pub async fn set_hidden_fields(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    fields: Vec<String>,
+) -> Result<HttpResponse, Error> {
+    let schema = infra::schema::get(org_id, stream_name, stream_type)
+        .await
+        .unwrap();
+    if schema == Schema::empty() {
+        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+            StatusCode::NOT_FOUND.into(),
+            "stream not found".to_string(),
+        )));
+    }
+    let column_timestamp = &config::get_config().common.column_timestamp;
+    for field in fields.iter() {
+        if field == column_timestamp {
+            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                StatusCode::BAD_REQUEST.into(),
+                format!("field [{field}] can't be hidden"),
+            )));
+        }
+        if schema.field_with_name(field).is_err() {
+            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                StatusCode::BAD_REQUEST.into(),
+                format!("field [{field}] not found in the stream"),
+            )));
+        }
+    }
+    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    settings.hidden_fields = fields;
+    save_field_settings(org_id, stream_name, stream_type, &schema, &settings).await
+}
+
+/// Sets the names the fields of a stream are searched and shown by, the files
+/// keep the original field names.
+pub async fn set_field_aliases(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    aliases: hashbrown::HashMap<String, String>,
+) -> Result<HttpResponse, Error> {
+    let schema = infra::schema::get(org_id, stream_name, stream_type)
+        .await
+        .unwrap();
+    if schema == Schema::empty() {
+        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+            StatusCode::NOT_FOUND.into(),
+            "stream not found".to_string(),
+        )));
+    }
+    if let Err(e) = check_field_aliases(&schema, &aliases) {
+        return Ok(HttpResponse::BadRequest()
+            .json(MetaHttpResponse::error(StatusCode::BAD_REQUEST.into(), e)));
+    }
+    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    settings.field_aliases = aliases;
+    save_field_settings(org_id, stream_name, stream_type, &schema, &settings).await
+}
+
+fn check_field_aliases(
+    schema: &Schema,
+    aliases: &hashbrown::HashMap<String, String>,
+) -> Result<(), String> {
+    let mut fields = hashbrown::HashSet::with_capacity(aliases.len());
+    for (alias, field) in aliases.iter() {
+        if alias.is_empty()
+            || alias.starts_with(|c: char| c.is_ascii_digit())
+            || !alias.chars().all(|c| c.is_alphanumeric() || c == '_')
+        {
+            return Err(format!("alias [{alias}] is not a valid field name"));
+        }
+        if schema.field_with_name(alias).is_ok() {
+            return Err(format!("alias [{alias}] is already a field of the stream"));
+        }
+        if schema.field_with_name(field).is_err() {
+            return Err(format!("field [{field}] not found in the stream"));
+        }
+        if !fields.insert(field) {
+            return Err(format!("field [{field}] has more than one alias"));
+        }
+    }
+    Ok(())
+}
+
+async fn save_field_settings(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    schema: &Schema,
+    settings: &StreamSettings,
+) -> Result<HttpResponse, Error> {
+    let mut metadata = schema.metadata.clone();
+    metadata.insert("settings".to_string(), json::to_string(settings).unwrap());
+    if let Err(e) = db::schema::update_setting(org_id, stream_name, stream_type, metadata).await {
+        return Ok(
+            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR.into(),
+                e.to_string(),
+            )),
+        );
+    }
+    Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+        StatusCode::OK.into(),
+        "".to_string(),
+    )))
+}
+
+/// Leaves the hidden fields out of the search hits and shows the fields by
+/// their aliases.
+pub async fn apply_field_settings(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    hits: &mut [json::Value],
+) {
+    let Some(settings) = infra::schema::get_settings(org_id, stream_name, stream_type).await else {
+        return;
+    };
+    if settings.hidden_fields.is_empty() && settings.field_aliases.is_empty() {
+        return;
+    }
+    for hit in hits.iter_mut().filter_map(|hit| hit.as_object_mut()) {
+        apply_field_settings_to_hit(&settings, hit);
+    }
+}
+
+fn apply_field_settings_to_hit(
+    settings: &StreamSettings,
+    hit: &mut json::Map<String, json::Value>,
+) {
+    for field in settings.hidden_fields.iter() {
+        hit.remove(field);
+    }
+    for (alias, field) in settings.field_aliases.iter() {
+        if let Some(value) = hit.remove(field) {
+            hit.insert(alias.to_string(), value);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use datafusion::arrow::datatypes::{DataType, Field};
+    use datafusion::arrow::datatypes::Field;
 
     use super::*;
 
@@ -385,4 +577,36 @@ mod tests {
         let res = stream_res("Test", StreamType::Logs, schema, Some(stats));
         assert_eq!(res.stats, stats);
     }
+
+    #[test]
+    fn test_check_field_aliases() {
+        let schema = Schema::new(vec![
+            Field::new("svc", DataType::Utf8, true),
+            Field::new("host", DataType::Utf8, true),
+        ]);
+        let aliases = |v: &[(&str, &str)]| {
+            v.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<hashbrown::HashMap<_, _>>()
+        };
+        assert!(check_field_aliases(&schema, &aliases(&[("service", "svc")])).is_ok());
+        assert!(check_field_aliases(&schema, &aliases(&[("host", "svc")])).is_err());
+        assert!(check_field_aliases(&schema, &aliases(&[("service", "pod")])).is_err());
+        assert!(check_field_aliases(&schema, &aliases(&[("my-svc", "svc")])).is_err());
+        assert!(
+            check_field_aliases(&schema, &aliases(&[("service", "svc"), ("app", "svc")])).is_err()
+        );
+    }
+
+    #[test]
+    fn test_apply_field_settings_to_hit() {
+        let settings = StreamSettings {
+            hidden_fields: vec!["secret".to_string()],
+            field_aliases: hashbrown::HashMap::from([("service".to_string(), "svc".to_string())]),
+            ..Default::default()
+        };
+        let mut hit = json::json!({"svc": "api", "secret": "x", "code": 200});
+        apply_field_settings_to_hit(&settings, hit.as_object_mut().unwrap());
+        assert_eq!(hit, json::json!({"service": "api", "code": 200}));
+    }
 }
@@ -0,0 +1,233 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Archives of a stream with its schema, settings and data files under
+//! `archive/{id}/` of the object store. An archive is self-describing, it can
+//! be copied to the bucket of another cluster and restored into any
+//! organization and stream name, the keys of the files follow.
+
+use arrow_schema::Schema;
+use config::{
+    get_config, ider,
+    meta::stream::{FileKey, PartitionTimeLevel, StreamType},
+    utils::json,
+};
+use futures::{StreamExt, TryStreamExt};
+use infra::{file_list as infra_file_list, storage};
+
+use crate::{
+    common::meta::stream::{
+        relative_file_key, ArchivedFile, StreamArchive, StreamArchiveInfo, StreamRestoreRequest,
+        ARCHIVE_VERSION,
+    },
+    service::{db, file_list},
+};
+
+const ARCHIVE_PREFIX: &str = "archive/";
+
+fn manifest_key(id: &str) -> String {
+    format!("{ARCHIVE_PREFIX}{id}/manifest.json")
+}
+
+fn archived_file_key(id: &str, key: &str) -> String {
+    format!("{ARCHIVE_PREFIX}{id}/files/{key}")
+}
+
+/// Copies the objects, several at a time.
+async fn copy(pairs: Vec<(String, String)>) -> Result<(), anyhow::Error> {
+    futures::stream::iter(pairs)
+        .map(|(from, to)| async move {
+            let data = storage::get(&from).await?;
+            storage::put(&to, data).await
+        })
+        .buffer_unordered(get_config().limit.cpu_num)
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(())
+}
+
+/// Archives the stream as its file list has it, the data still in the wal of
+/// the ingesters isn't archived.
+pub async fn archive(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<StreamArchiveInfo, anyhow::Error> {
+    let start = std::time::Instant::now();
+    let schemas = infra::schema::get_versions(org_id, stream_name, stream_type, None).await?;
+    if schemas.iter().all(|s| s.fields().is_empty()) {
+        return Err(anyhow::anyhow!("stream {stream_name} not found"));
+    }
+    let id = ider::generate();
+
+    let mut files = Vec::new();
+    let mut pairs = Vec::new();
+    for (key, meta) in infra_file_list::query(
+        org_id,
+        stream_type,
+        stream_name,
+        PartitionTimeLevel::Unset,
+        None,
+        None,
+    )
+    .await?
+    {
+        let Some(rel) = relative_file_key(&key) else {
+            continue;
+        };
+        pairs.push((key.clone(), archived_file_key(&id, rel)));
+        files.push(ArchivedFile {
+            key: rel.to_string(),
+            meta,
+        });
+    }
+    copy(pairs).await?;
+
+    let info = StreamArchiveInfo {
+        id: id.clone(),
+        org_id: org_id.to_string(),
+        stream_type,
+        stream_name: stream_name.to_string(),
+        created_at: chrono::Utc::now().timestamp_micros(),
+        files: files.len(),
+        records: files.iter().map(|f| f.meta.records).sum(),
+        compressed_size: files.iter().map(|f| f.meta.compressed_size).sum(),
+    };
+    let archive = StreamArchive {
+        version: ARCHIVE_VERSION,
+        info: info.clone(),
+        schemas: schemas
+            .iter()
+            .map(json::to_value)
+            .collect::<Result<Vec<_>, _>>()?,
+        files,
+    };
+    // the manifest goes last, an archive without one is incomplete
+    storage::put(&manifest_key(&id), json::to_vec(&archive)?.into()).await?;
+    log::info!(
+        "[ARCHIVE] archive {org_id}/{stream_type}/{stream_name} into [{id}] with {} files done, took: {}ms",
+        info.files,
+        start.elapsed().as_millis()
+    );
+    Ok(info)
+}
+
+async fn get(id: &str) -> Result<StreamArchive, anyhow::Error> {
+    let data = storage::get(&manifest_key(id))
+        .await
+        .map_err(|_| anyhow::anyhow!("archive {id} not found"))?;
+    let archive: StreamArchive = json::from_slice(&data)?;
+    if archive.version > ARCHIVE_VERSION {
+        return Err(anyhow::anyhow!(
+            "archive {id} has version {}, newer than the supported version {ARCHIVE_VERSION}",
+            archive.version
+        ));
+    }
+    Ok(archive)
+}
+
+/// Lists the archives taken from the organization, all of them when none is
+/// given, newest first.
+pub async fn list(org_id: Option<&str>) -> Result<Vec<StreamArchiveInfo>, anyhow::Error> {
+    let mut list = Vec::new();
+    for file in storage::list(ARCHIVE_PREFIX).await? {
+        let Some(id) = file
+            .strip_prefix(ARCHIVE_PREFIX)
+            .and_then(|v| v.strip_suffix("/manifest.json"))
+        else {
+            continue;
+        };
+        let info = match get(id).await {
+            Ok(archive) => archive.info,
+            Err(e) => {
+                log::warn!("[ARCHIVE] read archive [{id}] error: {e}");
+                continue;
+            }
+        };
+        if org_id.map_or(true, |org_id| info.org_id == org_id) {
+            list.push(info);
+        }
+    }
+    list.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(list)
+}
+
+/// Restores an archive into a new stream of the organization. Only the root
+/// user restores the archives of another organization, such as the ones of
+/// another cluster.
+pub async fn restore(
+    org_id: &str,
+    req: StreamRestoreRequest,
+    is_root: bool,
+) -> Result<StreamArchiveInfo, anyhow::Error> {
+    let start = std::time::Instant::now();
+    let archive = get(&req.archive_id).await?;
+    if archive.info.org_id != org_id && !is_root {
+        return Err(anyhow::anyhow!(
+            "archive {} belongs to another organization",
+            req.archive_id
+        ));
+    }
+    let stream_type = archive.info.stream_type;
+    let stream_name = req
+        .stream_name
+        .unwrap_or_else(|| archive.info.stream_name.clone());
+    if !infra::schema::get(org_id, &stream_name, stream_type)
+        .await?
+        .fields()
+        .is_empty()
+    {
+        return Err(anyhow::anyhow!("stream {stream_name} already exists"));
+    }
+
+    // schema versions, then the settings of the latest one
+    let mut latest = None;
+    for value in archive.schemas {
+        let schema: Schema = json::from_value(value)?;
+        let start_dt = schema
+            .metadata()
+            .get("start_dt")
+            .and_then(|v| v.parse::<i64>().ok());
+        db::schema::merge(org_id, &stream_name, stream_type, &schema, start_dt).await?;
+        latest = Some(schema);
+    }
+    if let Some(schema) = latest {
+        if schema.metadata.contains_key("settings") {
+            db::schema::update_setting(org_id, &stream_name, stream_type, schema.metadata).await?;
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(archive.files.len());
+    let mut files = Vec::with_capacity(archive.files.len());
+    for file in archive.files {
+        let key = format!("files/{org_id}/{stream_type}/{stream_name}/{}", file.key);
+        pairs.push((archived_file_key(&req.archive_id, &file.key), key.clone()));
+        files.push(FileKey::new(&key, file.meta, false));
+    }
+    copy(pairs).await?;
+    let added = file_list::add_files(files).await?;
+
+    log::info!(
+        "[ARCHIVE] restore [{}] into {org_id}/{stream_type}/{stream_name} with {} files done, took: {}ms",
+        req.archive_id,
+        added.len(),
+        start.elapsed().as_millis()
+    );
+    Ok(StreamArchiveInfo {
+        org_id: org_id.to_string(),
+        stream_name,
+        ..archive.info
+    })
+}
@@ -0,0 +1,245 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Streams created from a template document with their schema, settings,
+//! functions and alerts, so that the streams of every new service are
+//! configured the same way. The template is checked before anything is
+//! created, then the stream is created step by step and the first failing
+//! step is returned.
+
+use std::collections::HashSet;
+
+use actix_web::HttpResponse;
+use arrow_schema::{DataType, Field, Schema};
+use chrono::Utc;
+use config::{get_config, meta::stream::StreamType, utils::json};
+
+use crate::{
+    common::meta::{
+        functions::StreamOrder,
+        http::HttpResponse as MetaHttpResponse,
+        stream::{
+            StreamTemplate, StreamTemplateField, StreamTemplateFieldType, StreamTemplateResponse,
+        },
+    },
+    service::{alerts, db, format_stream_name, functions, stream},
+};
+
+/// Creates the stream of the template, it must not exist yet.
+pub async fn create(
+    org_id: &str,
+    mut template: StreamTemplate,
+) -> Result<StreamTemplateResponse, anyhow::Error> {
+    template.stream_name = format_stream_name(template.stream_name.trim());
+    validate(&template)?;
+    let stream_name = template.stream_name.as_str();
+    let stream_type = template.stream_type;
+    if !infra::schema::get(org_id, stream_name, stream_type)
+        .await?
+        .fields()
+        .is_empty()
+    {
+        return Err(anyhow::anyhow!("stream {stream_name} already exists"));
+    }
+    for func in template.functions.iter() {
+        if db::functions::get(org_id, &func.name).await.is_err() {
+            return Err(anyhow::anyhow!("function {} not found", func.name));
+        }
+    }
+
+    let schema = template_schema(&template.fields);
+    db::schema::merge(
+        org_id,
+        stream_name,
+        stream_type,
+        &schema,
+        Some(Utc::now().timestamp_micros()),
+    )
+    .await?;
+    check_response(
+        stream::save_stream_settings(org_id, stream_name, stream_type, template.settings).await?,
+    )
+    .await?;
+
+    let mut response = StreamTemplateResponse {
+        stream_name: stream_name.to_string(),
+        stream_type,
+        ..Default::default()
+    };
+    for func in template.functions {
+        let order = StreamOrder {
+            stream: stream_name.to_string(),
+            order: func.order,
+            stream_type,
+            is_removed: false,
+        };
+        check_response(
+            functions::add_function_to_stream(org_id, stream_type, stream_name, &func.name, order)
+                .await?,
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("function {}: {e}", func.name))?;
+        response.functions.push(func.name);
+    }
+    for mut alert in template.alerts {
+        let name = alert.name.clone();
+        alert.stream_type = stream_type;
+        alerts::save(org_id, stream_name, "", alert, true)
+            .await
+            .map_err(|e| anyhow::anyhow!("alert {name}: {e}"))?;
+        response.alerts.push(name);
+    }
+    Ok(response)
+}
+
+fn validate(template: &StreamTemplate) -> Result<(), anyhow::Error> {
+    if template.stream_name.is_empty() {
+        return Err(anyhow::anyhow!("stream name is required"));
+    }
+    if matches!(
+        template.stream_type,
+        StreamType::EnrichmentTables | StreamType::Index
+    ) {
+        return Err(anyhow::anyhow!(
+            "stream type {} not allowed",
+            template.stream_type
+        ));
+    }
+    let column_timestamp = &get_config().common.column_timestamp;
+    let mut fields = HashSet::new();
+    for field in template.fields.iter() {
+        if field.name.is_empty() || &field.name == column_timestamp {
+            return Err(anyhow::anyhow!("field name [{}] not allowed", field.name));
+        }
+        if !fields.insert(field.name.as_str()) {
+            return Err(anyhow::anyhow!("field [{}] is duplicated", field.name));
+        }
+    }
+    let mut names = HashSet::new();
+    for alert in template.alerts.iter() {
+        if alert.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("alert name is required"));
+        }
+        if !names.insert(alert.name.trim()) {
+            return Err(anyhow::anyhow!("alert [{}] is duplicated", alert.name));
+        }
+    }
+    Ok(())
+}
+
+/// The initial schema of the stream, the timestamp then the template fields.
+fn template_schema(fields: &[StreamTemplateField]) -> Schema {
+    let column_timestamp = &get_config().common.column_timestamp;
+    let mut schema_fields = Vec::with_capacity(fields.len() + 1);
+    schema_fields.push(Field::new(column_timestamp, DataType::Int64, true));
+    for field in fields {
+        let data_type = match field.field_type {
+            StreamTemplateFieldType::String => DataType::Utf8,
+            StreamTemplateFieldType::Int => DataType::Int64,
+            StreamTemplateFieldType::Float => DataType::Float64,
+            StreamTemplateFieldType::Bool => DataType::Boolean,
+        };
+        schema_fields.push(Field::new(&field.name, data_type, true));
+    }
+    Schema::new(schema_fields)
+}
+
+/// Turns the failure response of a step into an error with its message.
+async fn check_response(resp: HttpResponse) -> Result<(), anyhow::Error> {
+    if resp.status().is_success() {
+        return Ok(());
+    }
+    let status = resp.status();
+    let body = actix_web::body::to_bytes(resp.into_body())
+        .await
+        .map_err(|e| anyhow::anyhow!("{status}: {e}"))?;
+    let message = json::from_slice::<MetaHttpResponse>(&body)
+        .map(|r| r.message)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string());
+    Err(anyhow::anyhow!("{status}: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(value: json::Value) -> StreamTemplate {
+        json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let t = template(json::json!({
+            "stream_name": "checkout",
+            "fields": [{"name": "level"}, {"name": "took", "type": "float"}],
+            "alerts": [{"name": "errors", "destinations": ["slack"]}]
+        }));
+        assert!(validate(&t).is_ok());
+        assert_eq!(t.stream_type, StreamType::Logs);
+
+        let t = template(json::json!({
+            "stream_name": "checkout",
+            "fields": [{"name": "level"}, {"name": "level"}]
+        }));
+        assert!(validate(&t).is_err());
+
+        let t = template(json::json!({
+            "stream_name": "checkout",
+            "fields": [{"name": "_timestamp", "type": "int"}]
+        }));
+        assert!(validate(&t).is_err());
+
+        let t = template(json::json!({
+            "stream_name": "checkout",
+            "alerts": [
+                {"name": "errors", "destinations": []},
+                {"name": " errors", "destinations": []}
+            ]
+        }));
+        assert!(validate(&t).is_err());
+
+        let t = template(json::json!({"stream_name": "users", "stream_type": "enrichment_tables"}));
+        assert!(validate(&t).is_err());
+    }
+
+    #[test]
+    fn test_template_schema() {
+        let t = template(json::json!({
+            "stream_name": "checkout",
+            "fields": [
+                {"name": "level"},
+                {"name": "status", "type": "int"},
+                {"name": "took", "type": "float"},
+                {"name": "cached", "type": "bool"}
+            ]
+        }));
+        let schema = template_schema(&t.fields);
+        let types = schema
+            .fields()
+            .iter()
+            .map(|f| (f.name().as_str(), f.data_type().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                ("_timestamp", DataType::Int64),
+                ("level", DataType::Utf8),
+                ("status", DataType::Int64),
+                ("took", DataType::Float64),
+                ("cached", DataType::Boolean),
+            ]
+        );
+    }
+}
@@ -46,7 +46,7 @@ use crate::{
         traces::{Event, Span, SpanRefType},
     },
     service::{
-        db, format_stream_name,
+        format_stream_name,
         ingestion::{evaluate_trigger, grpc::get_val, write_file, TriggerAlertData},
         metadata::{
             distinct_values::DvItem, trace_list_index::TraceListItem, write, MetadataItem,
@@ -84,9 +84,7 @@ pub async fn handle_trace_request(
         );
     }
 
-    if !db::file_list::BLOCKED_ORGS.is_empty()
-        && db::file_list::BLOCKED_ORGS.contains(&org_id.to_string())
-    {
+    if crate::service::ingestion::is_org_quota_exceeded(org_id) {
         return Ok(HttpResponse::Forbidden().json(MetaHttpResponse::error(
             http::StatusCode::FORBIDDEN.into(),
             format!("Quota exceeded for this organization [{}]", org_id),
@@ -389,7 +387,17 @@ pub async fn handle_trace_request(
 
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Traces.to_string()).await;
-    let mut req_stats = write_file(&writer, &traces_stream_name, data_buf).await;
+    let mut req_stats = match write_file(&writer, &traces_stream_name, data_buf).await {
+        Ok(v) => v,
+        Err(e) => {
+            return Ok(
+                HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                    http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
     if let Err(e) = writer.sync().await {
         log::error!("ingestion error while syncing writer: {}", e);
     }
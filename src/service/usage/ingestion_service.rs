@@ -14,16 +14,16 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use anyhow::Error;
+use config::meta::cluster::{GRPC_MIN_VERSION, GRPC_VERSION_HEADER};
 use proto::cluster_rpc;
 use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use tonic::{
     codec::CompressionEncoding,
     metadata::{MetadataKey, MetadataValue},
-    transport::Channel,
     Request,
 };
 
-use crate::common::infra::cluster;
+use crate::common::infra::{cluster, tls};
 
 pub async fn ingest(
     dest_org_id: &str,
@@ -60,7 +60,11 @@ pub async fn ingest(
     let token: MetadataValue<_> = cluster::get_internal_grpc_token()
         .parse()
         .map_err(|_| Error::msg("invalid token".to_string()))?;
-    let channel = Channel::from_shared(node_addr)
+    let grpc_version: MetadataValue<_> = node
+        .negotiate_grpc_version()
+        .unwrap_or(GRPC_MIN_VERSION)
+        .into();
+    let channel = tls::grpc_endpoint(node_addr)
         .unwrap()
         .connect_timeout(std::time::Duration::from_secs(cfg.grpc.connect_timeout))
         .connect()
@@ -77,6 +81,8 @@ pub async fn ingest(
         channel,
         move |mut req: Request<()>| {
             req.metadata_mut().insert("authorization", token.clone());
+            req.metadata_mut()
+                .insert(GRPC_VERSION_HEADER, grpc_version.clone());
             req.metadata_mut()
                 .insert(org_header_key.clone(), dest_org_id.parse().unwrap());
             Ok(req)
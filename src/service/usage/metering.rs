@@ -0,0 +1,235 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Meters the usage of the organizations into hourly rollups. Each node adds
+//! up what it ingests and searches, the leader adds the storage, and they
+//! write their share to the rollup stream of the usage org every interval.
+
+use std::io::Error;
+
+use actix_web::HttpResponse;
+use config::{
+    get_config,
+    meta::{
+        stream::StreamType,
+        usage::{UsageEvent, UsageRollup, USAGE_ROLLUP_STREAM},
+    },
+    utils::json,
+    SIZE_IN_MB,
+};
+use hashbrown::HashMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use proto::cluster_rpc;
+
+use super::ingestion_service;
+use crate::{
+    common::{
+        infra::cluster::leader,
+        meta::{http::HttpResponse as MetaHttpResponse, organization::OrgUsageResponse},
+    },
+    service::search as SearchService,
+};
+
+const HOUR_MICROS: i64 = 3_600_000_000;
+/// Maximum number of hours read at once
+const MAX_HOURS: i64 = 10000;
+
+// (org_id, hour) -> usage metered by the local node since the last write
+static METERS: Lazy<Mutex<HashMap<(String, i64), UsageRollup>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn hour_of(time: i64) -> i64 {
+    time - time.rem_euclid(HOUR_MICROS)
+}
+
+/// Meters a request of the organization, the size is in MB.
+pub fn record(org_id: &str, event: UsageEvent, size: f64, records: i64) {
+    let cfg = get_config();
+    if !cfg.common.usage_metering_enabled || org_id == cfg.common.usage_org {
+        return;
+    }
+    let bytes = (size * SIZE_IN_MB) as i64;
+    let hour = hour_of(chrono::Utc::now().timestamp_micros());
+    let mut meters = METERS.lock();
+    let meter = meters
+        .entry((org_id.to_string(), hour))
+        .or_insert_with(|| UsageRollup::new(org_id, hour));
+    match event {
+        UsageEvent::Ingestion => {
+            meter.ingested_bytes += bytes;
+            meter.ingested_records += records;
+        }
+        UsageEvent::Search => {
+            meter.searches += 1;
+            meter.scanned_bytes += bytes;
+        }
+        UsageEvent::Functions | UsageEvent::Other => {}
+    }
+}
+
+/// Adds the compressed size of the streams of each organization to the
+/// current hour.
+fn record_storage() {
+    let cfg = get_config();
+    let hour = hour_of(chrono::Utc::now().timestamp_micros());
+    let mut storage: HashMap<String, f64> = HashMap::new();
+    for stats in infra::cache::stats::get_stats().iter() {
+        let Some((org_id, _)) = stats.key().split_once('/') else {
+            continue;
+        };
+        if org_id != cfg.common.usage_org {
+            *storage.entry(org_id.to_string()).or_default() += stats.value().compressed_size;
+        }
+    }
+    let mut meters = METERS.lock();
+    for (org_id, size) in storage {
+        let meter = meters
+            .entry((org_id.clone(), hour))
+            .or_insert_with(|| UsageRollup::new(&org_id, hour));
+        meter.storage_bytes = meter.storage_bytes.max(size as i64);
+    }
+}
+
+/// Writes the usage metered since the last write, it is kept for the next
+/// write when the ingestion fails.
+pub async fn flush() {
+    let meters = std::mem::take(&mut *METERS.lock());
+    if meters.is_empty() {
+        return;
+    }
+    let data = meters
+        .values()
+        .map(|rollup| {
+            let mut value = json::to_value(rollup).unwrap();
+            value[get_config().common.column_timestamp.as_str()] = rollup.hour.into();
+            value
+        })
+        .collect::<Vec<_>>();
+    let req = cluster_rpc::UsageRequest {
+        stream_name: USAGE_ROLLUP_STREAM.to_owned(),
+        data: Some(cluster_rpc::UsageData::from(data)),
+    };
+    if let Err(e) = ingestion_service::ingest(&get_config().common.usage_org, req).await {
+        log::error!("Error in ingesting metered usage {:?}", e);
+        let mut current = METERS.lock();
+        for (key, rollup) in meters {
+            match current.get_mut(&key) {
+                Some(v) => v.merge(&rollup),
+                None => {
+                    current.insert(key, rollup);
+                }
+            }
+        }
+    }
+}
+
+pub async fn run() {
+    let cfg = get_config();
+    if !cfg.common.usage_metering_enabled {
+        return;
+    }
+    leader::campaign(leader::USAGE_METERING);
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+        cfg.common.usage_metering_interval,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        // the leader meters the storage for the cluster
+        if leader::is_leader(leader::USAGE_METERING) {
+            record_storage();
+        }
+        flush().await;
+    }
+}
+
+/// Returns the hourly usage of the organization between the times, in
+/// microseconds.
+pub async fn get(org_id: &str, start_time: i64, end_time: i64) -> Result<HttpResponse, Error> {
+    match get_rollups(org_id, start_time, end_time).await {
+        Ok(hours) => {
+            let mut total = UsageRollup::new(org_id, hour_of(start_time));
+            for rollup in hours.iter() {
+                total.merge(rollup);
+            }
+            Ok(HttpResponse::Ok().json(OrgUsageResponse { hours, total }))
+        }
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+async fn get_rollups(
+    org_id: &str,
+    start_time: i64,
+    end_time: i64,
+) -> Result<Vec<UsageRollup>, anyhow::Error> {
+    let cfg = get_config();
+    if !cfg.common.usage_metering_enabled {
+        return Err(anyhow::anyhow!(
+            "the usage needs the metering, ZO_USAGE_METERING_ENABLED"
+        ));
+    }
+    if start_time >= end_time {
+        return Err(anyhow::anyhow!("start_time should be before end_time"));
+    }
+    let sql = format!(
+        "SELECT org_id, \"hour\", sum(ingested_bytes) AS ingested_bytes, sum(ingested_records) AS ingested_records, sum(searches) AS searches, sum(scanned_bytes) AS scanned_bytes, max(storage_bytes) AS storage_bytes FROM \"{USAGE_ROLLUP_STREAM}\" WHERE org_id = '{}' GROUP BY org_id, \"hour\" ORDER BY \"hour\"",
+        org_id.replace('\'', "''")
+    );
+    let req = config::meta::search::Request {
+        query: config::meta::search::Query {
+            sql,
+            sql_mode: "full".to_owned(),
+            from: 0,
+            size: ((end_time - start_time) / HOUR_MICROS + 1).min(MAX_HOURS),
+            start_time: hour_of(start_time),
+            end_time,
+            ..Default::default()
+        },
+        aggs: std::collections::HashMap::new(),
+        encoding: config::meta::search::RequestEncoding::Empty,
+        regions: vec![],
+        clusters: vec![],
+        timeout: 0,
+        search_type: None,
+    };
+    let hits =
+        match SearchService::search_as_system("", &cfg.common.usage_org, StreamType::Logs, &req)
+            .await
+        {
+            Ok(res) => res.hits,
+            Err(infra::errors::Error::ErrorCode(
+                infra::errors::ErrorCodes::SearchStreamNotFound(_),
+            )) => vec![],
+            Err(e) => return Err(e.into()),
+        };
+    Ok(hits
+        .into_iter()
+        .filter_map(|hit| json::from_value::<UsageRollup>(hit).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hour_of() {
+        assert_eq!(hour_of(0), 0);
+        assert_eq!(hour_of(HOUR_MICROS - 1), 0);
+        assert_eq!(hour_of(HOUR_MICROS * 5 + 42), HOUR_MICROS * 5);
+    }
+}
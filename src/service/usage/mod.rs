@@ -38,6 +38,7 @@ use reqwest::Client;
 use tokio::{sync::RwLock, time};
 
 pub mod ingestion_service;
+pub mod metering;
 pub mod stats;
 
 pub static USAGE_DATA: Lazy<Arc<RwLock<Vec<UsageData>>>> =
@@ -60,6 +61,7 @@ pub async fn report_request_usage_stats(
         .with_label_values(&[org_id, stream_name, stream_type.to_string().as_str()])
         .inc_by((stats.size * SIZE_IN_MB) as u64);
     let event: UsageEvent = usage_type.into();
+    metering::record(org_id, event, stats.size, stats.records);
 
     if !get_config().common.usage_enabled {
         return;
@@ -176,11 +178,15 @@ pub async fn flush() {
     // flush audit data
     #[cfg(feature = "enterprise")]
     flush_audit().await;
+    #[cfg(not(feature = "enterprise"))]
+    super::audit::flush().await;
 
     // flush usage report
     flush_usage().await;
     // flush triggers usage report
     flush_triggers_usage().await;
+    // flush metered usage
+    metering::flush().await;
 }
 
 async fn flush_usage() {
@@ -96,7 +96,9 @@ pub async fn publish_stats() -> Result<(), anyhow::Error> {
             search_type: None,
         };
         // do search
-        match SearchService::search("", &cfg.common.usage_org, StreamType::Logs, None, &req).await {
+        match SearchService::search_as_system("", &cfg.common.usage_org, StreamType::Logs, &req)
+            .await
+        {
             Ok(res) => {
                 if !res.hits.is_empty() {
                     match report_stats(res.hits, &org_id, last_query_ts, current_ts).await {
@@ -154,11 +156,10 @@ async fn get_last_stats(
         timeout: 0,
         search_type: None,
     };
-    match SearchService::search(
+    match SearchService::search_as_system(
         "",
         &get_config().common.usage_org,
         StreamType::Logs,
-        None,
         &req,
     )
     .await
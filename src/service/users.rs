@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Error;
+use std::{collections::HashMap, io::Error};
 
 use actix_web::{http, HttpResponse};
 use config::{get_config, ider, utils::rand::generate_random_string};
@@ -32,7 +32,7 @@ use crate::{
         },
         utils::auth::{get_hash, is_root_user},
     },
-    service::db,
+    service::{db, login_security},
 };
 
 pub async fn post_user(
@@ -53,6 +53,14 @@ pub async fn post_user(
             db::user::get(Some(org_id), &usr_req.email).await
         };
         if existing_user.is_err() {
+            if !usr_req.is_external {
+                if let Err(e) = login_security::check_password_policy(&usr_req.password) {
+                    return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::message(
+                        http::StatusCode::BAD_REQUEST.into(),
+                        e,
+                    )));
+                }
+            }
             let salt = ider::uuid();
             let password = get_hash(&usr_req.password, &salt);
             let password_ext = get_hash(&usr_req.password, &cfg.auth.ext_auth_salt);
@@ -203,9 +211,17 @@ pub async fn update_user(
                         &local_user.salt,
                     )) {
                         let new_pass = user.new_password.unwrap();
+                        if let Err(e) = login_security::check_password_policy(&new_pass) {
+                            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::message(
+                                http::StatusCode::BAD_REQUEST.into(),
+                                e,
+                            )));
+                        }
 
                         new_user.password = get_hash(&new_pass, &local_user.salt);
                         new_user.password_ext = Some(get_hash(&new_pass, password_ext_salt));
+                        new_user.password_updated_at = chrono::Utc::now().timestamp_micros();
+                        new_user.force_password_reset = false;
                         log::info!("Password self updated for user: {}", email);
                         is_updated = true;
                     } else {
@@ -220,15 +236,30 @@ pub async fn update_user(
                     && !local_user.is_external
                 {
                     let new_pass = user.new_password.unwrap();
+                    if !is_root_user(email) {
+                        if let Err(e) = login_security::check_password_policy(&new_pass) {
+                            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::message(
+                                http::StatusCode::BAD_REQUEST.into(),
+                                e,
+                            )));
+                        }
+                    }
 
                     new_user.password = get_hash(&new_pass, &local_user.salt);
                     new_user.password_ext = Some(get_hash(&new_pass, password_ext_salt));
+                    new_user.password_updated_at = chrono::Utc::now().timestamp_micros();
                     log::info!("Password by root updated for user: {}", email);
 
                     is_updated = true;
                 } else {
                     message = "You are not authorised to change the password"
                 }
+                if let Some(force_password_reset) = user.force_password_reset {
+                    if !self_update && allow_password_update {
+                        new_user.force_password_reset = force_password_reset;
+                        is_updated = true;
+                    }
+                }
                 if user.first_name.is_some() && !local_user.is_external {
                     new_user.first_name = user.first_name.unwrap();
                     is_updated = true;
@@ -258,6 +289,8 @@ pub async fn update_user(
                         Ok(mut db_user) => {
                             db_user.password = new_user.password;
                             db_user.password_ext = new_user.password_ext;
+                            db_user.password_updated_at = new_user.password_updated_at;
+                            db_user.force_password_reset = new_user.force_password_reset;
                             db_user.first_name = new_user.first_name;
                             db_user.last_name = new_user.last_name;
                             if is_org_updated {
@@ -353,6 +386,56 @@ pub async fn update_user(
     }
 }
 
+/// Changes the password of a user who is asked to reset it, the old password
+/// has been validated by the caller.
+pub async fn reset_password(email: &str, new_password: &str) -> Result<HttpResponse, Error> {
+    let mut db_user = match db::user::get_db_user(email).await {
+        Ok(db_user) => db_user,
+        Err(_) => {
+            return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+                http::StatusCode::NOT_FOUND.into(),
+                "User not found".to_string(),
+            )));
+        }
+    };
+    if db_user.is_external {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::message(
+            http::StatusCode::BAD_REQUEST.into(),
+            "Updates not allowed in OpenObserve, please update with source system".to_string(),
+        )));
+    }
+    if let Err(e) = login_security::check_password_policy(new_password) {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::message(
+            http::StatusCode::BAD_REQUEST.into(),
+            e,
+        )));
+    }
+    let password = get_hash(new_password, &db_user.salt);
+    if db_user.password.eq(&password) {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::message(
+            http::StatusCode::BAD_REQUEST.into(),
+            "New password must be different from the old password".to_string(),
+        )));
+    }
+    db_user.password = password;
+    db_user.password_ext = Some(get_hash(new_password, &get_config().auth.ext_auth_salt));
+    db_user.password_updated_at = chrono::Utc::now().timestamp_micros();
+    db_user.force_password_reset = false;
+    if let Err(e) = db::user::set(&db_user).await {
+        return Ok(
+            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
+                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
+                e.to_string(),
+            )),
+        );
+    }
+    log::info!("Password reset for user: {}", email);
+    Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+        http::StatusCode::OK.into(),
+        "Password updated successfully".to_string(),
+    )))
+}
+
 pub async fn add_user_to_org(
     org_id: &str,
     email: &str,
@@ -530,6 +613,44 @@ pub async fn list_users(org_id: &str) -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Ok().json(UserList { data: user_list }))
 }
 
+/// Returns the attributes of the user in the organization.
+pub async fn get_attributes(org_id: &str, email_id: &str) -> Result<HttpResponse, Error> {
+    if get_user(Some(org_id), email_id).await.is_none() {
+        return Ok(MetaHttpResponse::not_found(
+            "User for the organization not found",
+        ));
+    }
+    match db::user::get_attributes(org_id, email_id).await {
+        Ok(attributes) => Ok(HttpResponse::Ok().json(attributes)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// Replaces the attributes of the user in the organization, the row filters
+/// of the roles refer to them as `{{user.<attribute>}}`.
+pub async fn set_attributes(
+    org_id: &str,
+    email_id: &str,
+    attributes: HashMap<String, String>,
+) -> Result<HttpResponse, Error> {
+    if get_user(Some(org_id), email_id).await.is_none() {
+        return Ok(MetaHttpResponse::not_found(
+            "User for the organization not found",
+        ));
+    }
+    if let Some(name) = attributes.keys().find(|name| {
+        name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }) {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "Attribute name [{name}] can only contain alphanumeric characters and '_'"
+        )));
+    }
+    match db::user::set_attributes(org_id, email_id, &attributes).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(attributes)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
 pub async fn remove_user_from_org(
     org_id: &str,
     email_id: &str,
@@ -606,6 +727,9 @@ pub async fn remove_user_from_org(
                             }
                         }
                     }
+                    if let Err(e) = db::user::delete_attributes(org_id, email_id).await {
+                        log::error!("Error deleting the attributes of user {email_id}: {e}");
+                    }
                     Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
                         http::StatusCode::OK.into(),
                         "User removed from organization".to_string(),
@@ -708,6 +832,8 @@ mod tests {
                 org: "dummy".to_string(),
                 is_external: false,
                 password_ext: Some("pass#123".to_string()),
+                password_updated_at: 0,
+                force_password_reset: false,
             },
         );
     }
@@ -769,6 +895,7 @@ mod tests {
                 new_password: Some("new_pass".to_string()),
                 role: Some(crate::common::meta::user::UserRole::Member),
                 change_password: false,
+                force_password_reset: None,
             },
         )
         .await;
@@ -788,6 +915,7 @@ mod tests {
                 new_password: None,
                 role: Some(crate::common::meta::user::UserRole::Admin),
                 change_password: false,
+                force_password_reset: None,
             },
         )
         .await;
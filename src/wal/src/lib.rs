@@ -17,10 +17,11 @@ mod errors;
 mod reader;
 mod writer;
 
-use std::path::PathBuf;
+use std::{fs::OpenOptions, path::PathBuf};
 
 pub use errors::*;
 pub use reader::Reader;
+use snafu::ResultExt;
 pub use writer::Writer;
 
 const SOFT_MAX_BUFFER_LEN: usize = 1024 * 128; // 128KB
@@ -30,6 +31,29 @@ const FILE_TYPE_IDENTIFIER: &FileTypeIdentifier = b"OPENOBSERVEV2";
 /// File extension for segment files.
 const FILE_EXTENSION: &str = "wal";
 
+/// Truncate the wal file at `offset`, usually the [`Reader::offset`] of its
+/// first corrupt entry, so that the entries written after an unclean
+/// shutdown are dropped instead of replayed. Return the number of bytes
+/// dropped.
+pub fn truncate(path: impl Into<PathBuf>, offset: u64) -> Result<u64> {
+    let path = path.into();
+    let f = OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .context(FileOpenSnafu { path: path.clone() })?;
+    let len = f
+        .metadata()
+        .context(FileReadSnafu { path: path.clone() })?
+        .len();
+    if offset >= len {
+        return Ok(0);
+    }
+    f.set_len(offset)
+        .context(FileWriteSnafu { path: path.clone() })?;
+    f.sync_all().context(FileSyncSnafu { path })?;
+    Ok(len - offset)
+}
+
 pub fn build_file_path(
     root_dir: impl Into<PathBuf>,
     org_id: &str,
@@ -25,9 +25,14 @@ use snafu::{ensure, ResultExt};
 
 use crate::errors::*;
 
+/// The entry header is the crc32 checksum and the length of the entry, two
+/// u32 values.
+const ENTRY_HEADER_LEN: u64 = 8;
+
 pub struct Reader<R> {
     path: PathBuf,
     f: R,
+    offset: u64,
 }
 
 impl Reader<BufReader<File>> {
@@ -46,7 +51,9 @@ impl Reader<BufReader<File>> {
             FileIdentifierMismatchSnafu,
         );
 
-        Ok(Self::new(path, f))
+        let mut reader = Self::new(path, f);
+        reader.offset = super::FILE_TYPE_IDENTIFIER.len() as u64;
+        Ok(reader)
     }
 }
 
@@ -55,13 +62,19 @@ where
     R: Read,
 {
     pub fn new(path: PathBuf, f: R) -> Self {
-        Self { path, f }
+        Self { path, f, offset: 0 }
     }
 
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
 
+    /// Return the position in the file following the last entry read
+    /// successfully, a corrupt or torn entry starts there.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
     // read entry from the wal file
     pub fn read_entry(&mut self) -> Result<Option<Vec<u8>>> {
         let expected_checksum = match self.f.read_u32::<BigEndian>() {
@@ -78,6 +91,7 @@ where
             .context(UnableToReadLengthSnafu)?
             .into();
         if expected_len == 0 {
+            self.offset += ENTRY_HEADER_LEN;
             return Ok(Some(vec![]));
         }
 
@@ -106,6 +120,7 @@ where
             });
         }
 
+        self.offset += ENTRY_HEADER_LEN + expected_len;
         Ok(Some(data))
     }
 }
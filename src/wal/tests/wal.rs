@@ -13,8 +13,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::{fs::OpenOptions, io::Write};
+
 use tempfile::tempdir;
-use wal::{build_file_path, Reader, Writer};
+use wal::{build_file_path, truncate, Reader, Writer};
 
 #[test]
 fn wal() {
@@ -37,3 +39,39 @@ fn wal() {
     }
     assert!(reader.read_entry().unwrap().is_none());
 }
+
+#[test]
+fn wal_truncate_at_torn_entry() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let mut writer = Writer::new(dir, "org", "stream", 1, 0).unwrap();
+    for i in 0..3 {
+        let data = format!("hello world {}", i);
+        writer.write(data.as_bytes(), true).unwrap();
+    }
+    writer.close().unwrap();
+    let path = build_file_path(dir, "org", "stream", 1);
+    let valid_len = std::fs::metadata(&path).unwrap().len();
+
+    // a torn write: the header of an entry without its data
+    let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+    f.write_all(&[0xde, 0xad, 0xbe, 0xef, 0, 0, 0, 64, 1, 2, 3])
+        .unwrap();
+    drop(f);
+
+    let mut reader = Reader::from_path(&path).unwrap();
+    for _ in 0..3 {
+        assert!(reader.read_entry().unwrap().is_some());
+    }
+    assert!(reader.read_entry().is_err());
+    assert_eq!(reader.offset(), valid_len);
+    assert_eq!(truncate(&path, reader.offset()).unwrap(), 11);
+    assert_eq!(truncate(&path, reader.offset()).unwrap(), 0);
+
+    let mut reader = Reader::from_path(&path).unwrap();
+    for i in 0..3 {
+        let data = format!("hello world {}", i);
+        assert_eq!(reader.read_entry().unwrap().unwrap(), data.as_bytes());
+    }
+    assert!(reader.read_entry().unwrap().is_none());
+}
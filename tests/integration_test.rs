@@ -16,9 +16,12 @@
 #[cfg(test)]
 mod tests {
     use core::time;
-    use std::{env, fs, str, sync::Once, thread};
+    use std::{env, fs, io::Write, str, sync::Once, thread};
 
-    use actix_web::{http::header::ContentType, test, web, App};
+    use actix_web::{
+        http::header::{self, ContentType},
+        test, web, App,
+    };
     use bytes::{Bytes, BytesMut};
     use chrono::Utc;
     use config::{get_config, utils::json};
@@ -88,6 +91,7 @@ mod tests {
         // ingest
         e2e_post_json().await;
         e2e_post_multi().await;
+        e2e_post_json_compressed().await;
         e2e_post_trace().await;
         e2e_post_metrics().await;
         // e2e_post_kinesis_data().await;
@@ -251,6 +255,40 @@ mod tests {
         assert!(resp.status().is_success());
     }
 
+    async fn e2e_post_json_compressed() {
+        let auth = setup();
+        let body_str = "[{\"Year\": 1896, \"City\": \"Athens\", \"Sport\": \"Aquatics\", \"Season\": \"summer\",\"_timestamp\":1665136888163792}]";
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(body_str.as_bytes()).unwrap();
+        let payloads = [
+            ("gzip", gzip.finish().unwrap()),
+            ("zstd", zstd::encode_all(body_str.as_bytes(), 3).unwrap()),
+        ];
+        let thread_id: usize = 0;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::JsonConfig::default().limit(get_config().limit.req_json_limit))
+                .app_data(web::PayloadConfig::new(
+                    get_config().limit.req_payload_limit,
+                ))
+                .app_data(web::Data::new(thread_id))
+                .configure(get_service_routes)
+                .configure(get_basic_routes),
+        )
+        .await;
+        for (encoding, payload) in payloads {
+            let req = test::TestRequest::post()
+                .uri(&format!("/api/{}/{}/_json", "e2e", "olympics_schema"))
+                .insert_header(ContentType::json())
+                .insert_header((header::CONTENT_ENCODING, encoding))
+                .append_header(auth)
+                .set_payload(payload)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert!(resp.status().is_success(), "{encoding}");
+        }
+    }
+
     async fn e2e_get_stream() {
         let auth = setup();
         let app = test::init_service(
@@ -1423,6 +1461,17 @@ mod tests {
             .to_request();
         let resp = test::call_service(&app, req).await;
         assert!(resp.status().is_success());
+
+        // the node may still be loading its disk cache, the meta store is up
+        let req = test::TestRequest::get()
+            .uri("/readyz")
+            .insert_header(ContentType::json())
+            .append_header(auth)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success() || resp.status().as_u16() == 503);
+        let body: json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["checks"]["meta_store"]["status"], "ok");
     }
 
     async fn e2e_config() {